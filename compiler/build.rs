@@ -0,0 +1,75 @@
+//! Hashes the compiler's sources to identify its build
+//!
+//! The typeck cache includes the hash in its entry names. Entries written by a compiler built from
+//! different sources are never used, even if both builds have the same package version.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+/// Directories containing sources that affect type inference, relative to the compiler's manifest
+const SOURCE_DIRS: &[&str] = &[".", "../syntax"];
+
+/// Directories that are skipped while collecting sources
+const SKIPPED_DIR_NAMES: &[&str] = &["tests", "target"];
+
+fn collect_sources(dir: &Path, sources: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            let is_skipped = matches!(
+                path.file_name().and_then(|name| name.to_str()),
+                Some(name) if SKIPPED_DIR_NAMES.contains(&name)
+            );
+
+            if !is_skipped {
+                collect_sources(&path, sources)?;
+            }
+        } else if path.extension() == Some(OsStr::new("rs")) {
+            sources.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Updates a 64-bit FNV-1a hash with the passed bytes
+///
+/// This matches `KeyHasher` in the typeck cache.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn main() -> io::Result<()> {
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+
+    let mut sources = vec![];
+    for source_dir in SOURCE_DIRS {
+        let source_dir = manifest_dir.join(source_dir);
+
+        println!("cargo:rerun-if-changed={}", source_dir.display());
+        collect_sources(&source_dir, &mut sources)?;
+    }
+
+    // Directory iteration order is platform specific
+    sources.sort();
+
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    for source in sources {
+        let relative_path = source.strip_prefix(&manifest_dir).unwrap_or(&source);
+        let contents = fs::read(&source)?;
+
+        hash = fnv1a(hash, relative_path.to_string_lossy().as_bytes());
+        hash = fnv1a(hash, &(contents.len() as u64).to_le_bytes());
+        hash = fnv1a(hash, &contents);
+
+        println!("cargo:rerun-if-changed={}", source.display());
+    }
+
+    println!("cargo:rustc-env=ARRET_COMPILER_SOURCE_HASH={:016x}", hash);
+    Ok(())
+}
@@ -6,7 +6,8 @@ use llvm_sys::{LLVMAttributeFunctionIndex, LLVMAttributeReturnIndex, LLVMIntPred
 
 use arret_runtime::boxed;
 
-use crate::codegen::alloc::{ActiveAlloc, AllocAtom, BoxSource};
+use crate::codegen::alloc::{ActiveAlloc, AllocAtom, AllocRecorder, BoxSource};
+use crate::codegen::const_gen::annotate_private_global;
 use crate::codegen::mod_gen::ModCtx;
 use crate::codegen::target_gen::TargetCtx;
 use crate::libcstr;
@@ -99,6 +100,10 @@ fn gen_heap_alloced_box<T: boxed::ConstTagged>(
             boxed::Header::new(type_tag, box_size.to_heap_alloc_type()),
         );
 
+        if let Some(alloc_recorder) = active_alloc.alloc_recorder {
+            gen_record_alloc(tcx, builder, alloc_recorder, type_tag, cell_count);
+        }
+
         alloced_box
     }
 }
@@ -138,6 +143,108 @@ pub fn gen_alloced_box<T: boxed::ConstTagged>(
     )
 }
 
+/// Records a heap allocation with the runtime's allocation profiler
+fn gen_record_alloc(
+    tcx: &mut TargetCtx,
+    builder: LLVMBuilderRef,
+    alloc_recorder: AllocRecorder,
+    type_tag: boxed::TypeTag,
+    cell_count: usize,
+) {
+    unsafe {
+        let record_alloc_args = &mut [
+            alloc_recorder.llvm_task,
+            LLVMConstInt(LLVMInt8TypeInContext(tcx.llx), type_tag as u64, 0),
+            LLVMConstInt(LLVMInt32TypeInContext(tcx.llx), cell_count as u64, 0),
+            alloc_recorder.llvm_site_name,
+        ];
+
+        LLVMBuildCall(
+            builder,
+            alloc_recorder.llvm_record_fun,
+            record_alloc_args.as_mut_ptr(),
+            record_alloc_args.len() as u32,
+            libcstr!(""),
+        );
+    }
+}
+
+/// Returns the recorder for allocations made by the current function
+///
+/// This is `None` unless allocation profiling is enabled. Allocations are attributed to the
+/// function containing the builder's insertion point.
+fn alloc_recorder_for_builder(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    builder: LLVMBuilderRef,
+    llvm_task: LLVMValueRef,
+) -> Option<AllocRecorder> {
+    if !tcx.alloc_profile() {
+        return None;
+    }
+
+    unsafe {
+        let llvm_i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(tcx.llx), 0);
+        let llvm_param_types = &mut [
+            tcx.task_llvm_ptr_type(),
+            LLVMInt8TypeInContext(tcx.llx),
+            LLVMInt32TypeInContext(tcx.llx),
+            llvm_i8_ptr,
+        ];
+
+        let record_alloc_llvm_type = LLVMFunctionType(
+            LLVMVoidTypeInContext(tcx.llx),
+            llvm_param_types.as_mut_ptr(),
+            llvm_param_types.len() as u32,
+            0,
+        );
+
+        let llvm_record_fun = mcx.get_function_or_insert(
+            record_alloc_llvm_type,
+            b"arret_runtime_record_alloc\0",
+            |_| {},
+        );
+
+        // Share a single site name between every allocation in the function
+        let function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(builder));
+
+        let mut function_name_len: libc::size_t = 0;
+        let function_name_ptr = LLVMGetValueName2(function, &mut function_name_len);
+        let function_name =
+            std::slice::from_raw_parts(function_name_ptr as *const u8, function_name_len);
+
+        let mut global_name = b"alloc_site_".to_vec();
+        global_name.extend_from_slice(function_name);
+        global_name.push(0);
+
+        let mut llvm_site_global = LLVMGetNamedGlobal(mcx.module, global_name.as_ptr() as *const _);
+        if llvm_site_global.is_null() {
+            let llvm_site_string = LLVMConstStringInContext(
+                tcx.llx,
+                function_name.as_ptr() as *const _,
+                function_name.len() as u32,
+                0,
+            );
+
+            llvm_site_global = LLVMAddGlobal(
+                mcx.module,
+                LLVMTypeOf(llvm_site_string),
+                global_name.as_ptr() as *const _,
+            );
+            LLVMSetInitializer(llvm_site_global, llvm_site_string);
+            annotate_private_global(llvm_site_global);
+        }
+
+        let llvm_site_name = LLVMConstBitCast(llvm_site_global, llvm_i8_ptr);
+
+        Some(AllocRecorder {
+            llvm_task,
+            llvm_record_fun,
+            llvm_site_name,
+        })
+    }
+}
+
 /// Allocates cells by invoking a function at runtime
 ///
 /// This is the slow path; it is only used when our current heap segment is full.
@@ -235,6 +342,7 @@ pub fn atom_into_active_alloc<'op>(
             box_slots: ptr::null_mut(),
             total_cells: 0,
             used_cells: 0,
+            alloc_recorder: None,
 
             box_source_iter: atom.box_sources.into_iter(),
             cond_plan_iter: atom.cond_plans.into_iter(),
@@ -252,7 +360,7 @@ pub fn atom_into_active_alloc<'op>(
 
         let cont_block = LLVMAppendBasicBlockInContext(tcx.llx, function, libcstr!("alloc_cont"));
 
-        let seg_next_ptr = LLVMBuildStructGEP(builder, llvm_task, 0, libcstr!("seg_next_ptr"));
+        let seg_next_ptr = LLVMBuildStructGEP(builder, llvm_task, 1, libcstr!("seg_next_ptr"));
         let mut seg_old_next = LLVMBuildLoad(builder, seg_next_ptr, libcstr!("seg_old_next"));
 
         let gep_indices = &mut [LLVMConstInt(
@@ -268,7 +376,7 @@ pub fn atom_into_active_alloc<'op>(
             libcstr!("seg_new_next"),
         );
 
-        let seg_end_ptr = LLVMBuildStructGEP(builder, llvm_task, 1, libcstr!("seg_end_ptr"));
+        let seg_end_ptr = LLVMBuildStructGEP(builder, llvm_task, 2, libcstr!("seg_end_ptr"));
         let seg_end = LLVMBuildLoad(builder, seg_end_ptr, libcstr!("seg_end"));
 
         let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
@@ -330,6 +438,7 @@ pub fn atom_into_active_alloc<'op>(
             box_slots,
             total_cells: required_cells,
             used_cells: 0,
+            alloc_recorder: alloc_recorder_for_builder(tcx, mcx, builder, llvm_task),
 
             box_source_iter: atom.box_sources.into_iter(),
             cond_plan_iter: atom.cond_plans.into_iter(),
@@ -57,10 +57,19 @@ impl<'op> AllocAtom<'op> {
     }
 }
 
+/// Runtime call used to record heap allocations when profiling allocations
+#[derive(Clone, Copy)]
+pub struct AllocRecorder {
+    llvm_task: LLVMValueRef,
+    llvm_record_fun: LLVMValueRef,
+    llvm_site_name: LLVMValueRef,
+}
+
 pub struct ActiveAlloc<'op> {
     box_slots: LLVMValueRef,
     total_cells: usize,
     used_cells: usize,
+    alloc_recorder: Option<AllocRecorder>,
 
     box_source_iter: vec::IntoIter<BoxSource>,
     cond_plan_iter: vec::IntoIter<CondPlan<'op>>,
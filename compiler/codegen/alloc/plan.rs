@@ -18,6 +18,7 @@ fn op_needs_heap_checkpoint(tcx: &mut TargetCtx, op: &ops::Op) -> bool {
 
     match op.kind() {
         OpKind::Ret(_)
+        | OpKind::RetValues(_)
         | OpKind::RetVoid
         | OpKind::Unreachable
         | OpKind::Call(_, _)
@@ -148,6 +149,7 @@ mod test {
 
         use crate::codegen::target_machine::create_target_machine;
         use crate::codegen::test::initialise_test_llvm;
+        use crate::opt_level::OptLevel;
 
         initialise_test_llvm();
 
@@ -155,9 +157,10 @@ mod test {
             None,
             LLVMRelocMode::LLVMRelocDynamicNoPic,
             LLVMCodeModel::LLVMCodeModelDefault,
+            OptLevel::O0,
         );
 
-        let mut tcx = TargetCtx::new(target_machine, false);
+        let mut tcx = TargetCtx::new(target_machine, OptLevel::O0);
         let atoms = plan_allocs(&mut tcx, &Captures::new(), ops);
 
         unsafe {
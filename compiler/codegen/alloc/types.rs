@@ -183,6 +183,7 @@ pub fn gen_alloc_boxed_record(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
     builder: LLVMBuilderRef,
+    llvm_task: LLVMValueRef,
     active_alloc: &mut ActiveAlloc<'_>,
     box_source: BoxSource,
     input: &RecordInput<'_>,
@@ -303,7 +304,7 @@ pub fn gen_alloc_boxed_record(
                 let llvm_i32 = LLVMInt32TypeInContext(tcx.llx);
                 let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
 
-                let llvm_param_types = &mut [llvm_i64, llvm_i32];
+                let llvm_param_types = &mut [tcx.task_llvm_ptr_type(), llvm_i64, llvm_i32];
 
                 let alloc_record_data_llvm_type = LLVMFunctionType(
                     LLVMPointerType(llvm_i8, 0),
@@ -325,6 +326,7 @@ pub fn gen_alloc_boxed_record(
                 );
 
                 let alloc_record_data_args = &mut [
+                    llvm_task,
                     LLVMConstInt(llvm_i64, data_layout.size() as u64, 0),
                     LLVMConstInt(llvm_i32, data_layout.align() as u64, 0),
                 ];
@@ -127,6 +127,15 @@ impl<'of> ProgramCaptureCtx<'of> {
                     captures.add(*ret_reg, CaptureKind::ViaRet);
                 }
             }
+            OpKind::RetValues(ret_regs) => {
+                if let RetAbiType::Values(abi_types) = ret_type {
+                    for (ret_reg, abi_type) in ret_regs.iter().zip(abi_types.iter()) {
+                        if let AbiType::Boxed(_) = abi_type {
+                            captures.add(*ret_reg, CaptureKind::ViaRet);
+                        }
+                    }
+                }
+            }
             OpKind::CastBoxed(reg, ops::CastBoxedOp { from_reg, .. })
             | OpKind::Alias(reg, from_reg)
             | OpKind::LoadBoxedPairHead(reg, from_reg)
@@ -137,6 +146,13 @@ impl<'of> ProgramCaptureCtx<'of> {
                     vector_reg: from_reg,
                     ..
                 },
+            )
+            | OpKind::LoadValuesMember(
+                reg,
+                ops::LoadValuesMemberOp {
+                    values_reg: from_reg,
+                    ..
+                },
             ) => {
                 captures.add(*from_reg, captures.get(*reg));
             }
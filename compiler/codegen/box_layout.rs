@@ -49,6 +49,11 @@ impl BoxLayout {
             BoxLayout::ConstTagged(TypeTag::Vector) => b"boxed_vector\0",
             BoxLayout::ConstTagged(TypeTag::Record) => b"boxed_record\0",
             BoxLayout::ConstTagged(TypeTag::Map) => b"boxed_map\0",
+            BoxLayout::ConstTagged(TypeTag::FloatArray) => b"boxed_float_array\0",
+            BoxLayout::ConstTagged(TypeTag::IntArray) => b"boxed_int_array\0",
+            BoxLayout::ConstTagged(TypeTag::Bytes) => b"boxed_bytes\0",
+            BoxLayout::ConstTagged(TypeTag::Rational) => b"boxed_rational\0",
+            BoxLayout::ConstTagged(TypeTag::BigInt) => b"boxed_big_int\0",
         }
     }
 
@@ -108,14 +113,50 @@ impl BoxLayout {
 
                     members.extend_from_slice(&[llvm_i32, llvm_any_ptr, llvm_any_ptr, llvm_any_ptr])
                 }
+                BoxLayout::ConstTagged(TypeTag::Map) => {
+                    let llvm_i32 = LLVMInt32TypeInContext(tcx.llx);
+                    let llvm_map_entry_ptr = LLVMPointerType(tcx.map_entry_llvm_type(), 0);
+
+                    // entries_len, sorted_entries
+                    members.extend_from_slice(&[llvm_i32, llvm_map_entry_ptr]);
+                }
                 BoxLayout::ConstTagged(TypeTag::Vector) => {
                     // inline_len
                     members.push(LLVMInt32TypeInContext(tcx.llx));
                 }
+                BoxLayout::ConstTagged(TypeTag::FloatArray) => {
+                    let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+                    let llvm_double = LLVMDoubleTypeInContext(tcx.llx);
+
+                    members.extend_from_slice(&[llvm_i64, LLVMPointerType(llvm_double, 0)]);
+                }
+                BoxLayout::ConstTagged(TypeTag::IntArray) => {
+                    let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+
+                    members.extend_from_slice(&[llvm_i64, LLVMPointerType(llvm_i64, 0)]);
+                }
+                BoxLayout::ConstTagged(TypeTag::Bytes) => {
+                    let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+                    let llvm_i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(tcx.llx), 0);
+
+                    // len, data, storage
+                    members.extend_from_slice(&[llvm_i64, llvm_i8_ptr, llvm_i8_ptr]);
+                }
+                BoxLayout::ConstTagged(TypeTag::Rational) => {
+                    let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+
+                    // numer, denom
+                    members.extend_from_slice(&[llvm_i64, llvm_i64]);
+                }
+                BoxLayout::ConstTagged(TypeTag::BigInt) => {
+                    let llvm_i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(tcx.llx), 0);
+
+                    // value
+                    members.push(llvm_i8_ptr);
+                }
                 BoxLayout::ConstTagged(TypeTag::Nil)
                 | BoxLayout::ConstTagged(TypeTag::True)
                 | BoxLayout::ConstTagged(TypeTag::False)
-                | BoxLayout::ConstTagged(TypeTag::Map)
                 | BoxLayout::Bool
                 | BoxLayout::Num
                 | BoxLayout::Union => {}
@@ -0,0 +1,210 @@
+//! Verification of constant boxes in generated modules
+//!
+//! Constant boxes are expected to be fully initialised at compile time and placed in read-only
+//! memory. This means programs with many literals don't need to allocate or initialise anything at
+//! startup. These checks catch codegen changes that would silently break that guarantee.
+
+use std::{fmt, slice, str};
+
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+
+/// Prefix used for the names of globals containing constant boxes
+const CONST_GLOBAL_PREFIX: &str = "const_";
+
+#[derive(Debug, PartialEq)]
+pub enum ConstGlobalError {
+    /// Constant global isn't marked as constant so it would be placed in writable memory
+    Mutable(String),
+    /// Constant global has no initialiser
+    Uninitialised(String),
+    /// Constant global is written to by generated code
+    StoredTo(String),
+    /// Constant global refers to a global that isn't constant
+    MutableReference(String, String),
+}
+
+impl fmt::Display for ConstGlobalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstGlobalError::Mutable(name) => write!(f, "`{}` is not marked constant", name),
+            ConstGlobalError::Uninitialised(name) => write!(f, "`{}` has no initialiser", name),
+            ConstGlobalError::StoredTo(name) => {
+                write!(f, "`{}` is initialised at runtime", name)
+            }
+            ConstGlobalError::MutableReference(name, referenced_name) => write!(
+                f,
+                "`{}` refers to non-constant global `{}`",
+                name, referenced_name
+            ),
+        }
+    }
+}
+
+fn value_name(llvm_value: LLVMValueRef) -> String {
+    unsafe {
+        let mut len: libc::size_t = 0;
+        let name_ptr = LLVMGetValueName2(llvm_value, &mut len);
+
+        if name_ptr.is_null() {
+            return String::new();
+        }
+
+        let name_bytes = slice::from_raw_parts(name_ptr as *const u8, len);
+        String::from_utf8_lossy(name_bytes).into_owned()
+    }
+}
+
+/// Returns true if `llvm_value` is the destination of a store, possibly through a constant cast
+fn is_stored_to(llvm_value: LLVMValueRef) -> bool {
+    unsafe {
+        let mut llvm_use = LLVMGetFirstUse(llvm_value);
+
+        while !llvm_use.is_null() {
+            let llvm_user = LLVMGetUser(llvm_use);
+
+            if !LLVMIsAStoreInst(llvm_user).is_null() {
+                if LLVMGetOperand(llvm_user, 1) == llvm_value {
+                    return true;
+                }
+            } else if !LLVMIsAConstantExpr(llvm_user).is_null() && is_stored_to(llvm_user) {
+                return true;
+            }
+
+            llvm_use = LLVMGetNextUse(llvm_use);
+        }
+    }
+
+    false
+}
+
+/// Visits every global variable referenced by a constant value
+fn visit_referenced_globals<F>(llvm_value: LLVMValueRef, visitor: &mut F)
+where
+    F: FnMut(LLVMValueRef),
+{
+    unsafe {
+        if !LLVMIsAGlobalVariable(llvm_value).is_null() {
+            visitor(llvm_value);
+            return;
+        }
+
+        // Functions are referenced by constant thunks; their operands aren't data
+        if !LLVMIsAFunction(llvm_value).is_null() {
+            return;
+        }
+
+        for operand_index in 0..LLVMGetNumOperands(llvm_value) {
+            visit_referenced_globals(LLVMGetOperand(llvm_value, operand_index as u32), visitor);
+        }
+    }
+}
+
+/// Verifies every constant box global in a module
+///
+/// Constant box globals must be marked constant, must be fully initialised at compile time and
+/// may only refer to other constant globals.
+pub fn verify_const_globals(module: LLVMModuleRef) -> Result<(), Vec<ConstGlobalError>> {
+    let mut errors = vec![];
+
+    unsafe {
+        let mut llvm_global = LLVMGetFirstGlobal(module);
+
+        while !llvm_global.is_null() {
+            let name = value_name(llvm_global);
+
+            if name.starts_with(CONST_GLOBAL_PREFIX) {
+                if LLVMIsGlobalConstant(llvm_global) == 0 {
+                    errors.push(ConstGlobalError::Mutable(name.clone()));
+                }
+
+                if is_stored_to(llvm_global) {
+                    errors.push(ConstGlobalError::StoredTo(name.clone()));
+                }
+
+                let llvm_initializer = LLVMGetInitializer(llvm_global);
+                if llvm_initializer.is_null() {
+                    errors.push(ConstGlobalError::Uninitialised(name));
+                } else {
+                    visit_referenced_globals(llvm_initializer, &mut |llvm_referenced| {
+                        if LLVMIsGlobalConstant(llvm_referenced) == 0 {
+                            errors.push(ConstGlobalError::MutableReference(
+                                name.clone(),
+                                value_name(llvm_referenced),
+                            ));
+                        }
+                    });
+                }
+            }
+
+            llvm_global = LLVMGetNextGlobal(llvm_global);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::codegen::const_gen::annotate_private_global;
+    use crate::libcstr;
+
+    #[test]
+    fn const_globals() {
+        unsafe {
+            let llx = LLVMContextCreate();
+            let module = LLVMModuleCreateWithNameInContext(libcstr!("const_check"), llx);
+            let llvm_i64 = LLVMInt64TypeInContext(llx);
+            let llvm_i64_ptr = LLVMPointerType(llvm_i64, 0);
+
+            let add_global = |name: &[u8], llvm_type, llvm_initializer| {
+                let llvm_global = LLVMAddGlobal(module, llvm_type, name.as_ptr() as *const _);
+                LLVMSetInitializer(llvm_global, llvm_initializer);
+                annotate_private_global(llvm_global);
+                llvm_global
+            };
+
+            // Constants referring to other constants are allowed
+            let const_int = add_global(b"const_int\0", llvm_i64, LLVMConstInt(llvm_i64, 1, 0));
+            add_global(b"const_ref\0", llvm_i64_ptr, const_int);
+            assert_eq!(Ok(()), verify_const_globals(module));
+
+            // Constants referring to mutable data are not
+            let mutable_int = add_global(b"mutable_int\0", llvm_i64, LLVMConstInt(llvm_i64, 2, 0));
+            LLVMSetGlobalConstant(mutable_int, 0);
+            add_global(b"const_mutable_ref\0", llvm_i64_ptr, mutable_int);
+
+            // Constants can't be initialised at runtime
+            let fun_type = LLVMFunctionType(LLVMVoidTypeInContext(llx), [].as_mut_ptr(), 0, 0);
+            let function = LLVMAddFunction(module, libcstr!("init"), fun_type);
+            let builder = LLVMCreateBuilderInContext(llx);
+            LLVMPositionBuilderAtEnd(
+                builder,
+                LLVMAppendBasicBlockInContext(llx, function, libcstr!("entry")),
+            );
+            LLVMBuildStore(builder, LLVMConstInt(llvm_i64, 3, 0), const_int);
+            LLVMBuildRetVoid(builder);
+            LLVMDisposeBuilder(builder);
+
+            assert_eq!(
+                Err(vec![
+                    ConstGlobalError::StoredTo("const_int".to_owned()),
+                    ConstGlobalError::MutableReference(
+                        "const_mutable_ref".to_owned(),
+                        "mutable_int".to_owned()
+                    ),
+                ]),
+                verify_const_globals(module)
+            );
+
+            LLVMDisposeModule(module);
+            LLVMContextDispose(llx);
+        }
+    }
+}
@@ -10,6 +10,7 @@ use arret_runtime::boxed;
 use crate::codegen::mod_gen::ModCtx;
 use crate::codegen::record_struct;
 use crate::codegen::target_gen::TargetCtx;
+use crate::crash;
 use crate::libcstr;
 use crate::mir::ops;
 
@@ -308,6 +309,35 @@ pub fn gen_boxed_int(
     }
 }
 
+pub fn gen_boxed_rational(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    numer: i64,
+    denom: i64,
+) -> LLVMValueRef {
+    unsafe {
+        let type_tag = boxed::TypeTag::Rational;
+        let llvm_type = tcx.boxed_abi_to_llvm_struct_type(&type_tag.into());
+        let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+
+        let box_name = format!("const_rational_{}_{}\0", numer, denom);
+
+        let global = mcx.get_global_or_insert(llvm_type, box_name.as_bytes(), || {
+            let members = &mut [
+                tcx.llvm_box_header(type_tag.to_const_header()),
+                LLVMConstInt(llvm_i64, numer as u64, 1),
+                LLVMConstInt(llvm_i64, denom as u64, 1),
+            ];
+
+            LLVMConstNamedStruct(llvm_type, members.as_mut_ptr(), members.len() as u32)
+        });
+
+        LLVMSetAlignment(global, mem::align_of::<boxed::Rational>() as u32);
+        annotate_private_global(global);
+        global
+    }
+}
+
 pub fn gen_boxed_float(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
@@ -643,11 +673,11 @@ pub fn gen_boxed_vector(
     } else if elements_len <= (NODE_SIZE * 2) {
         gen_boxed_external_vector(tcx, mcx, llvm_elements)
     } else {
-        todo!("generating constant vector of length {}", elements_len);
+        crash::unsupported(None, format!("constant vectors of length {}", elements_len));
     }
 }
 
-pub fn gen_boxed_set(
+fn gen_boxed_inline_set(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
     llvm_elements: impl ExactSizeIterator<Item = LLVMValueRef>,
@@ -656,10 +686,6 @@ pub fn gen_boxed_set(
 
     let elements_len = llvm_elements.len();
 
-    if elements_len > boxed::Set::<boxed::Any>::MAX_INLINE_LEN {
-        todo!("generating constant set of length {}", elements_len);
-    }
-
     unsafe {
         let type_tag = boxed::TypeTag::Set;
         let llvm_type = tcx.boxed_abi_to_llvm_struct_type(&type_tag.into());
@@ -690,20 +716,158 @@ pub fn gen_boxed_set(
     }
 }
 
-pub fn gen_boxed_map(
+fn gen_boxed_external_set(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
-    llvm_elements: impl ExactSizeIterator<Item = (LLVMValueRef, LLVMValueRef)>,
+    llvm_hashed_elements: impl ExactSizeIterator<Item = (u64, LLVMValueRef)>,
 ) -> LLVMValueRef {
-    if llvm_elements.len() > 0 {
-        todo!("generating non-empty map");
+    let elements_len = llvm_hashed_elements.len();
+
+    unsafe {
+        let type_tag = boxed::TypeTag::Set;
+        let llvm_type = tcx.boxed_external_set_llvm_type();
+        let llvm_set_entry_type = tcx.set_entry_llvm_type();
+
+        let llvm_i32 = LLVMInt32TypeInContext(tcx.llx);
+        let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+
+        let mut llvm_entries: Vec<LLVMValueRef> = llvm_hashed_elements
+            .map(|(hash, llvm_element)| {
+                let mut entry_members = [LLVMConstInt(llvm_i64, hash, 0), llvm_element];
+
+                LLVMConstNamedStruct(
+                    llvm_set_entry_type,
+                    entry_members.as_mut_ptr(),
+                    entry_members.len() as u32,
+                )
+            })
+            .collect();
+
+        let llvm_entries_value = LLVMConstArray(
+            llvm_set_entry_type,
+            llvm_entries.as_mut_ptr(),
+            llvm_entries.len() as u32,
+        );
+
+        let entries_global = LLVMAddGlobal(
+            mcx.module,
+            LLVMTypeOf(llvm_entries_value),
+            libcstr!("const_set_entries"),
+        );
+        LLVMSetInitializer(entries_global, llvm_entries_value);
+        annotate_private_global(entries_global);
+
+        let first_entry_gep_indices =
+            &mut [LLVMConstInt(llvm_i32, 0, 0), LLVMConstInt(llvm_i32, 0, 0)];
+
+        let mut members = [
+            tcx.llvm_box_header(type_tag.to_const_header()),
+            LLVMConstInt(
+                llvm_i32,
+                u64::from(boxed::Set::<boxed::Any>::EXTERNAL_INLINE_LEN),
+                0,
+            ),
+            LLVMConstInt(llvm_i64, elements_len as u64, 0),
+            LLVMConstInBoundsGEP(
+                entries_global,
+                first_entry_gep_indices.as_mut_ptr(),
+                first_entry_gep_indices.len() as u32,
+            ),
+        ];
+
+        let llvm_value =
+            LLVMConstNamedStruct(llvm_type, members.as_mut_ptr(), members.len() as u32);
+
+        let global = LLVMAddGlobal(mcx.module, llvm_type, libcstr!("const_set"));
+        LLVMSetInitializer(global, llvm_value);
+        LLVMSetAlignment(global, mem::align_of::<boxed::Set>() as u32);
+
+        annotate_private_global(global);
+        global
     }
+}
+
+/// Generates a constant set
+///
+/// The elements must be unique and sorted by their collection hash
+pub fn gen_boxed_set(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    llvm_hashed_elements: impl ExactSizeIterator<Item = (u64, LLVMValueRef)>,
+) -> LLVMValueRef {
+    if llvm_hashed_elements.len() <= boxed::Set::<boxed::Any>::MAX_INLINE_LEN {
+        gen_boxed_inline_set(
+            tcx,
+            mcx,
+            llvm_hashed_elements.map(|(_, llvm_element)| llvm_element),
+        )
+    } else {
+        gen_boxed_external_set(tcx, mcx, llvm_hashed_elements)
+    }
+}
+
+/// Generates a constant map
+///
+/// The entries must have unique keys and be sorted by the collection hash of their key
+pub fn gen_boxed_map(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    llvm_hashed_entries: impl ExactSizeIterator<Item = (u64, LLVMValueRef, LLVMValueRef)>,
+) -> LLVMValueRef {
+    let entries_len = llvm_hashed_entries.len();
 
     unsafe {
         let type_tag = boxed::TypeTag::Map;
         let llvm_type = tcx.boxed_abi_to_llvm_struct_type(&type_tag.into());
+        let llvm_map_entry_type = tcx.map_entry_llvm_type();
+
+        let llvm_i32 = LLVMInt32TypeInContext(tcx.llx);
+        let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
 
-        let mut members: Vec<LLVMValueRef> = vec![tcx.llvm_box_header(type_tag.to_const_header())];
+        let llvm_sorted_entries = if entries_len == 0 {
+            LLVMConstPointerNull(LLVMPointerType(llvm_map_entry_type, 0))
+        } else {
+            let mut llvm_entries: Vec<LLVMValueRef> = llvm_hashed_entries
+                .map(|(hash, llvm_key, llvm_value)| {
+                    let mut entry_members = [LLVMConstInt(llvm_i64, hash, 0), llvm_key, llvm_value];
+
+                    LLVMConstNamedStruct(
+                        llvm_map_entry_type,
+                        entry_members.as_mut_ptr(),
+                        entry_members.len() as u32,
+                    )
+                })
+                .collect();
+
+            let llvm_entries_value = LLVMConstArray(
+                llvm_map_entry_type,
+                llvm_entries.as_mut_ptr(),
+                llvm_entries.len() as u32,
+            );
+
+            let entries_global = LLVMAddGlobal(
+                mcx.module,
+                LLVMTypeOf(llvm_entries_value),
+                libcstr!("const_map_entries"),
+            );
+            LLVMSetInitializer(entries_global, llvm_entries_value);
+            annotate_private_global(entries_global);
+
+            let first_entry_gep_indices =
+                &mut [LLVMConstInt(llvm_i32, 0, 0), LLVMConstInt(llvm_i32, 0, 0)];
+
+            LLVMConstInBoundsGEP(
+                entries_global,
+                first_entry_gep_indices.as_mut_ptr(),
+                first_entry_gep_indices.len() as u32,
+            )
+        };
+
+        let mut members = [
+            tcx.llvm_box_header(type_tag.to_const_header()),
+            LLVMConstInt(llvm_i32, entries_len as u64, 0),
+            llvm_sorted_entries,
+        ];
 
         let llvm_value =
             LLVMConstNamedStruct(llvm_type, members.as_mut_ptr(), members.len() as u32);
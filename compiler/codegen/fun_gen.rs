@@ -1,16 +1,18 @@
 use std::collections::HashMap;
-use std::ffi;
+use std::{ffi, ptr};
 
 use llvm_sys::core::*;
 use llvm_sys::prelude::*;
-use llvm_sys::LLVMCallConv;
+use llvm_sys::{LLVMCallConv, LLVMIntPredicate};
 
 use crate::mir::ops;
 
 use crate::codegen::analysis::escape::{CaptureKind, Captures};
 use crate::codegen::mod_gen::ModCtx;
+use crate::codegen::panic_gen::gen_panic;
 use crate::codegen::target_gen::TargetCtx;
 use crate::codegen::GenAbi;
+use crate::crash;
 use crate::libcstr;
 
 pub(crate) struct FunCtx {
@@ -72,6 +74,79 @@ pub(crate) fn declare_fun(
     }
 }
 
+/// Panics the current task if the stack has grown past the task's stack limit
+///
+/// Tasks without a stack limit have a null limit which always passes the check.
+fn gen_stack_check(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    fcx: &mut FunCtx,
+    fun: &ops::Fun,
+) {
+    unsafe {
+        let llvm_i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(tcx.llx), 0);
+        let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+
+        let stack_save_llvm_type = LLVMFunctionType(llvm_i8_ptr, ptr::null_mut(), 0, 0);
+        let stack_save_fun =
+            mcx.get_function_or_insert(stack_save_llvm_type, b"llvm.stacksave\0", |_| {});
+
+        let stack_pointer = LLVMBuildCall(
+            fcx.builder,
+            stack_save_fun,
+            ptr::null_mut(),
+            0,
+            libcstr!("stack_pointer"),
+        );
+
+        let stack_limit_ptr = LLVMBuildStructGEP(
+            fcx.builder,
+            fcx.current_task,
+            0,
+            libcstr!("stack_limit_ptr"),
+        );
+        let stack_limit = LLVMBuildLoad(fcx.builder, stack_limit_ptr, libcstr!("stack_limit"));
+
+        let stack_pointer_int = LLVMBuildPtrToInt(
+            fcx.builder,
+            stack_pointer,
+            llvm_i64,
+            libcstr!("stack_pointer_int"),
+        );
+        let stack_limit_int = LLVMBuildPtrToInt(
+            fcx.builder,
+            stack_limit,
+            llvm_i64,
+            libcstr!("stack_limit_int"),
+        );
+
+        let stack_overflowed = LLVMBuildICmp(
+            fcx.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            stack_pointer_int,
+            stack_limit_int,
+            libcstr!("stack_overflowed"),
+        );
+
+        let overflow_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("stack_overflow"));
+
+        let cont_block = LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("stack_ok"));
+
+        LLVMBuildCondBr(fcx.builder, stack_overflowed, overflow_block, cont_block);
+
+        LLVMPositionBuilderAtEnd(fcx.builder, overflow_block);
+
+        let panic_message = match fun.source_name {
+            Some(ref source_name) => format!("stack overflow in `{}`", source_name),
+            None => "stack overflow in anonymous function".to_owned(),
+        };
+        gen_panic(tcx, mcx, fcx, &panic_message);
+
+        LLVMPositionBuilderAtEnd(fcx.builder, cont_block);
+    }
+}
+
 pub(crate) fn define_fun(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
@@ -83,6 +158,7 @@ pub(crate) fn define_fun(
     use crate::codegen::op_gen;
     use arret_runtime::abitype::{AbiType, RetAbiType};
 
+    let _processing_span = crash::enter_span(fun.span);
     let alloc_plan = plan_allocs(tcx, captures, &fun.ops);
 
     unsafe {
@@ -110,6 +186,8 @@ pub(crate) fn define_fun(
             tcx.add_boxed_return_attrs(llvm_fun);
         }
 
+        gen_stack_check(tcx, mcx, &mut fcx, fun);
+
         for alloc_atom in alloc_plan {
             op_gen::gen_alloc_atom(tcx, mcx, &mut fcx, alloc_atom);
         }
@@ -15,6 +15,7 @@ use crate::codegen::mod_gen::{gen_mod, GeneratedMod};
 use crate::codegen::target_gen::TargetCtx;
 use crate::mir::ops;
 use crate::mir::printer::print_fun;
+use crate::opt_level::OptLevel;
 
 extern "C" fn orc_sym_resolve(name_ptr: *const libc::c_char, jcx_void: *mut libc::c_void) -> u64 {
     unsafe {
@@ -45,7 +46,7 @@ pub struct RegisteredRecordStruct {
 }
 
 impl JitCtx {
-    pub fn new(optimising: bool) -> JitCtx {
+    pub fn new(opt_level: OptLevel) -> JitCtx {
         #[allow(clippy::fn_to_numeric_cast)]
         unsafe {
             use crate::codegen::target_machine::create_target_machine;
@@ -57,11 +58,12 @@ impl JitCtx {
                 None, // Can't cross compile in the JIT
                 LLVMRelocMode::LLVMRelocDefault,
                 LLVMCodeModel::LLVMCodeModelJITDefault,
+                opt_level,
             );
             let orc = LLVMOrcCreateInstance(target_machine);
 
             let mut jcx = JitCtx {
-                tcx: TargetCtx::new(target_machine, optimising),
+                tcx: TargetCtx::new(target_machine, opt_level),
                 orc,
                 target_machine,
                 symbols: HashMap::new(),
@@ -82,6 +84,7 @@ impl JitCtx {
                 compiler_support::alloc_record_data as u64,
             );
             jcx.add_symbol(b"arret_runtime_equals\0", compiler_support::equals as u64);
+            jcx.add_symbol(b"arret_runtime_map_get\0", compiler_support::map_get as u64);
             jcx.add_symbol(
                 b"arret_runtime_panic_with_string\0",
                 compiler_support::panic_with_string as u64,
@@ -128,6 +131,7 @@ impl JitCtx {
                 Some(interner),
                 self.record_struct_class_ids.clone(),
                 None,
+                false,
             );
 
             // We need to take ownership before we transfer the module to ORC
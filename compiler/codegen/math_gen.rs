@@ -2,9 +2,11 @@ use llvm_sys::core::*;
 use llvm_sys::prelude::*;
 use llvm_sys::LLVMIntPredicate;
 
+use arret_syntax::span::Span;
+
 use crate::codegen::fun_gen::FunCtx;
 use crate::codegen::mod_gen::ModCtx;
-use crate::codegen::panic_gen::gen_panic;
+use crate::codegen::panic_gen::gen_panic_at;
 use crate::codegen::target_gen::TargetCtx;
 use crate::libcstr;
 
@@ -36,6 +38,7 @@ pub(crate) fn gen_checked_int_math(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
     fcx: &mut FunCtx,
+    span: Span,
     int_op: &'static CheckedIntOp,
     llvm_lhs: LLVMValueRef,
     llvm_rhs: LLVMValueRef,
@@ -104,7 +107,7 @@ pub(crate) fn gen_checked_int_math(
         LLVMBuildCondBr(fcx.builder, llvm_overflow, overflow_block, cont_block);
 
         LLVMPositionBuilderAtEnd(fcx.builder, overflow_block);
-        gen_panic(tcx, mcx, fcx, panic_message);
+        gen_panic_at(tcx, mcx, fcx, span, panic_message);
 
         LLVMPositionBuilderAtEnd(fcx.builder, cont_block);
         llvm_math_result
@@ -115,6 +118,7 @@ pub(crate) fn gen_checked_int_rem(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
     fcx: &mut FunCtx,
+    span: Span,
     llvm_numer: LLVMValueRef,
     llvm_denom: LLVMValueRef,
 ) -> LLVMValueRef {
@@ -143,7 +147,7 @@ pub(crate) fn gen_checked_int_rem(
         );
 
         LLVMPositionBuilderAtEnd(fcx.builder, rem_by_zero_block);
-        gen_panic(tcx, mcx, fcx, "division by zero");
+        gen_panic_at(tcx, mcx, fcx, span, "division by zero");
 
         LLVMPositionBuilderAtEnd(fcx.builder, valid_rem_block);
         LLVMBuildSRem(fcx.builder, llvm_numer, llvm_denom, libcstr!("rem"))
@@ -154,6 +158,7 @@ pub(crate) fn gen_checked_int_div(
     tcx: &mut TargetCtx,
     mcx: &mut ModCtx<'_, '_, '_>,
     fcx: &mut FunCtx,
+    span: Span,
     llvm_numer: LLVMValueRef,
     llvm_denom: LLVMValueRef,
 ) -> LLVMValueRef {
@@ -230,7 +235,7 @@ pub(crate) fn gen_checked_int_div(
 
         // Build the common panic block
         LLVMPositionBuilderAtEnd(fcx.builder, div_by_zero_block);
-        gen_panic(tcx, mcx, fcx, "division by zero");
+        gen_panic_at(tcx, mcx, fcx, span, "division by zero");
 
         LLVMPositionBuilderAtEnd(fcx.builder, valid_div_block);
         LLVMBuildSDiv(fcx.builder, llvm_numer, llvm_denom, libcstr!("quot"))
@@ -2,6 +2,7 @@ mod alloc;
 mod analysis;
 mod box_layout;
 mod callee;
+mod const_check;
 mod const_gen;
 mod debug_info;
 mod fun_gen;
@@ -9,6 +10,7 @@ pub(crate) mod jit;
 mod libcstr;
 mod math_gen;
 mod mod_gen;
+mod num_array_gen;
 mod op_gen;
 mod panic_gen;
 pub(crate) mod program;
@@ -9,6 +9,8 @@ use llvm_sys::LLVMLinkage;
 use arret_runtime::boxed::RecordClassId;
 use arret_runtime::intern;
 
+use arret_syntax::span::Span;
+
 use crate::codegen::analysis::AnalysedMod;
 use crate::codegen::debug_info::DebugInfoBuilder;
 use crate::codegen::record_struct;
@@ -20,6 +22,7 @@ pub struct ModCtx<'am, 'sl, 'interner> {
     pub module: LLVMModuleRef,
 
     analysed_mod: &'am AnalysedMod<'am>,
+    source_loader: Option<&'sl SourceLoader>,
     di_builder: Option<DebugInfoBuilder<'sl>>,
     llvm_private_funs: HashMap<ops::PrivateFunId, LLVMValueRef>,
 
@@ -53,7 +56,8 @@ impl<'am, 'sl, 'interner> ModCtx<'am, 'sl, 'interner> {
         analysed_mod: &'am AnalysedMod<'am>,
         jit_interner: Option<&'interner mut intern::Interner>,
         jit_record_struct_class_ids: HashMap<ops::RecordStructId, RecordClassId>,
-        debug_source_loader: Option<&'sl SourceLoader>,
+        source_loader: Option<&'sl SourceLoader>,
+        debug_info: bool,
     ) -> Self {
         use crate::codegen::fun_gen::declare_fun;
         use llvm_sys::transforms::pass_manager_builder::*;
@@ -71,18 +75,20 @@ impl<'am, 'sl, 'interner> ModCtx<'am, 'sl, 'interner> {
 
             function_pass_manager = LLVMCreateFunctionPassManagerForModule(module);
 
-            if tcx.optimising() {
+            let opt_level = tcx.opt_level();
+            if let Some(llvm_pass_level) = opt_level.llvm_pass_level() {
                 let fpmb = LLVMPassManagerBuilderCreate();
-                LLVMPassManagerBuilderSetOptLevel(fpmb, 2);
+                LLVMPassManagerBuilderSetOptLevel(fpmb, llvm_pass_level);
+                LLVMPassManagerBuilderSetSizeLevel(fpmb, opt_level.llvm_size_level());
                 LLVMPassManagerBuilderPopulateFunctionPassManager(fpmb, function_pass_manager);
                 LLVMPassManagerBuilderDispose(fpmb);
             }
         }
 
-        let di_builder = debug_source_loader.map(|source_loader| {
+        let di_builder = source_loader.filter(|_| debug_info).map(|source_loader| {
             DebugInfoBuilder::new(
                 source_loader,
-                tcx.optimising(),
+                tcx.opt_level().optimising(),
                 analysed_mod.entry_fun().ops_fun.span,
                 module,
             )
@@ -102,6 +108,7 @@ impl<'am, 'sl, 'interner> ModCtx<'am, 'sl, 'interner> {
             module,
 
             analysed_mod,
+            source_loader,
             di_builder,
             llvm_private_funs,
 
@@ -116,6 +123,19 @@ impl<'am, 'sl, 'interner> ModCtx<'am, 'sl, 'interner> {
         }
     }
 
+    /// Returns a `file:line` description of where a span is located
+    ///
+    /// This is used to attribute runtime panics to their source expression. This returns `None`
+    /// if the module was generated without a source loader, such as inside the JIT.
+    pub fn source_loc_for_span(&self, span: Span) -> Option<String> {
+        let source_loader = self.source_loader?;
+
+        let filename = source_loader.span_to_filename(span)?;
+        let line_col_range = source_loader.span_to_line_col_range(span)?;
+
+        Some(format!("{}:{}", filename, line_col_range.start.line + 1))
+    }
+
     pub fn intern_name(&mut self, name: &str) -> intern::InternedSym {
         if let Some(ref mut jit_interner) = self.jit_interner {
             jit_interner.intern_static(name)
@@ -325,7 +345,8 @@ pub fn gen_mod<'am, 'sl, 'interner>(
     analysed_mod: &'am AnalysedMod<'am>,
     jit_interner: Option<&'interner mut intern::Interner>,
     jit_record_struct_class_ids: HashMap<ops::RecordStructId, RecordClassId>,
-    debug_source_loader: Option<&'sl SourceLoader>,
+    source_loader: Option<&'sl SourceLoader>,
+    debug_info: bool,
 ) -> GeneratedMod {
     ModCtx::new(
         tcx,
@@ -333,7 +354,8 @@ pub fn gen_mod<'am, 'sl, 'interner>(
         analysed_mod,
         jit_interner,
         jit_record_struct_class_ids,
-        debug_source_loader,
+        source_loader,
+        debug_info,
     )
     .into_generated_mod(tcx)
 }
@@ -0,0 +1,521 @@
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::LLVMIntPredicate;
+
+use arret_syntax::span::Span;
+
+use crate::codegen::fun_gen::FunCtx;
+use crate::codegen::mod_gen::ModCtx;
+use crate::codegen::panic_gen::gen_panic_at;
+use crate::codegen::target_gen::TargetCtx;
+use crate::libcstr;
+
+pub(crate) fn load_boxed_num_array_len(
+    tcx: &mut TargetCtx,
+    fcx: &mut FunCtx,
+    llvm_boxed_array: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let array_len_ptr =
+            LLVMBuildStructGEP(fcx.builder, llvm_boxed_array, 1, libcstr!("array_len_ptr"));
+
+        // The length of an array can't change after construction
+        let llvm_array_len = LLVMBuildLoad(fcx.builder, array_len_ptr, libcstr!("array_len"));
+        tcx.add_invariant_load_metadata(llvm_array_len);
+
+        llvm_array_len
+    }
+}
+
+fn load_boxed_num_array_values_ptr(
+    tcx: &mut TargetCtx,
+    fcx: &mut FunCtx,
+    llvm_boxed_array: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let array_values_ptr_ptr = LLVMBuildStructGEP(
+            fcx.builder,
+            llvm_boxed_array,
+            2,
+            libcstr!("array_values_ptr_ptr"),
+        );
+
+        let llvm_array_values_ptr = LLVMBuildLoad(
+            fcx.builder,
+            array_values_ptr_ptr,
+            libcstr!("array_values_ptr"),
+        );
+        tcx.add_invariant_load_metadata(llvm_array_values_ptr);
+
+        llvm_array_values_ptr
+    }
+}
+
+/// Loads a member from a numeric array, panicking if the index is out of bounds
+pub(crate) fn load_boxed_num_array_member(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    fcx: &mut FunCtx,
+    span: Span,
+    llvm_boxed_array: LLVMValueRef,
+    llvm_index: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let llvm_array_len = load_boxed_num_array_len(tcx, fcx, llvm_boxed_array);
+
+        // Treating the index as unsigned also rejects negative indices
+        let index_in_bounds = LLVMBuildICmp(
+            fcx.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            llvm_index,
+            llvm_array_len,
+            libcstr!("index_in_bounds"),
+        );
+
+        let out_of_bounds_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("index_out_of_bounds"));
+
+        let in_bounds_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("index_in_bounds"));
+
+        LLVMBuildCondBr(
+            fcx.builder,
+            index_in_bounds,
+            in_bounds_block,
+            out_of_bounds_block,
+        );
+
+        LLVMPositionBuilderAtEnd(fcx.builder, out_of_bounds_block);
+        gen_panic_at(tcx, mcx, fcx, span, "array index out of bounds");
+
+        LLVMPositionBuilderAtEnd(fcx.builder, in_bounds_block);
+
+        let llvm_array_values_ptr = load_boxed_num_array_values_ptr(tcx, fcx, llvm_boxed_array);
+
+        let member_gep_indices = &mut [llvm_index];
+        let member_ptr = LLVMBuildInBoundsGEP(
+            fcx.builder,
+            llvm_array_values_ptr,
+            member_gep_indices.as_mut_ptr(),
+            member_gep_indices.len() as u32,
+            libcstr!("array_member_ptr"),
+        );
+
+        // Array members are mutable so this load can't be marked invariant
+        LLVMBuildLoad(fcx.builder, member_ptr, libcstr!("array_member"))
+    }
+}
+
+/// Single iteration of a loop over the members of a float array
+#[derive(Clone, Copy)]
+struct FloatLoopStep {
+    /// Type of the members processed by this step
+    ///
+    /// This is either a vector of `f64` or a scalar `f64`
+    llvm_member_type: LLVMTypeRef,
+    vectorised: bool,
+    llvm_index: LLVMValueRef,
+}
+
+/// Returns the pointer to the float member(s) at the step's index
+fn float_member_ptr(
+    fcx: &mut FunCtx,
+    step: FloatLoopStep,
+    llvm_values_ptr: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let member_gep_indices = &mut [step.llvm_index];
+        let member_ptr = LLVMBuildInBoundsGEP(
+            fcx.builder,
+            llvm_values_ptr,
+            member_gep_indices.as_mut_ptr(),
+            member_gep_indices.len() as u32,
+            libcstr!("member_ptr"),
+        );
+
+        LLVMBuildBitCast(
+            fcx.builder,
+            member_ptr,
+            LLVMPointerType(step.llvm_member_type, 0),
+            libcstr!("typed_member_ptr"),
+        )
+    }
+}
+
+fn load_float_member(
+    fcx: &mut FunCtx,
+    step: FloatLoopStep,
+    llvm_values_ptr: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let member_ptr = float_member_ptr(fcx, step, llvm_values_ptr);
+        let llvm_member = LLVMBuildLoad(fcx.builder, member_ptr, libcstr!("member"));
+
+        // The members are only guaranteed to have the alignment of `f64`
+        LLVMSetAlignment(llvm_member, 8);
+        llvm_member
+    }
+}
+
+fn store_float_member(
+    fcx: &mut FunCtx,
+    step: FloatLoopStep,
+    llvm_values_ptr: LLVMValueRef,
+    llvm_member: LLVMValueRef,
+) {
+    unsafe {
+        let member_ptr = float_member_ptr(fcx, step, llvm_values_ptr);
+        let llvm_store = LLVMBuildStore(fcx.builder, llvm_member, member_ptr);
+        LLVMSetAlignment(llvm_store, 8);
+    }
+}
+
+/// Panics unless both arrays have the same length, returning the length
+fn gen_matching_array_len(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    fcx: &mut FunCtx,
+    span: Span,
+    llvm_lhs_array: LLVMValueRef,
+    llvm_rhs_array: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let llvm_lhs_len = load_boxed_num_array_len(tcx, fcx, llvm_lhs_array);
+        let llvm_rhs_len = load_boxed_num_array_len(tcx, fcx, llvm_rhs_array);
+
+        let lens_match = LLVMBuildICmp(
+            fcx.builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            llvm_lhs_len,
+            llvm_rhs_len,
+            libcstr!("lens_match"),
+        );
+
+        let len_mismatch_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("len_mismatch"));
+
+        let len_match_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("len_match"));
+
+        LLVMBuildCondBr(fcx.builder, lens_match, len_match_block, len_mismatch_block);
+
+        LLVMPositionBuilderAtEnd(fcx.builder, len_mismatch_block);
+        gen_panic_at(tcx, mcx, fcx, span, "array lengths differ");
+
+        LLVMPositionBuilderAtEnd(fcx.builder, len_match_block);
+        llvm_lhs_len
+    }
+}
+
+/// Generates a loop for indices from `llvm_start` to `llvm_end` incrementing by `step_size`
+///
+/// If `llvm_acc_init` is provided then `gen_body` must return the next value of the accumulator.
+/// The final value of the accumulator is returned.
+fn gen_counted_loop<F>(
+    tcx: &mut TargetCtx,
+    fcx: &mut FunCtx,
+    llvm_start: LLVMValueRef,
+    llvm_end: LLVMValueRef,
+    step_size: u64,
+    llvm_acc_init: Option<LLVMValueRef>,
+    mut gen_body: F,
+) -> Option<LLVMValueRef>
+where
+    F: FnMut(
+        &mut TargetCtx,
+        &mut FunCtx,
+        LLVMValueRef,
+        Option<LLVMValueRef>,
+    ) -> Option<LLVMValueRef>,
+{
+    unsafe {
+        let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+
+        let entry_block = LLVMGetInsertBlock(fcx.builder);
+        let cond_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("loop_cond"));
+        let body_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("loop_body"));
+        let exit_block =
+            LLVMAppendBasicBlockInContext(tcx.llx, fcx.function, libcstr!("loop_exit"));
+
+        LLVMBuildBr(fcx.builder, cond_block);
+        LLVMPositionBuilderAtEnd(fcx.builder, cond_block);
+
+        let llvm_index = LLVMBuildPhi(fcx.builder, llvm_i64, libcstr!("index"));
+        let llvm_acc = llvm_acc_init
+            .map(|llvm_init| LLVMBuildPhi(fcx.builder, LLVMTypeOf(llvm_init), libcstr!("acc")));
+
+        let llvm_continue = LLVMBuildICmp(
+            fcx.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            llvm_index,
+            llvm_end,
+            libcstr!("continue"),
+        );
+        LLVMBuildCondBr(fcx.builder, llvm_continue, body_block, exit_block);
+
+        LLVMPositionBuilderAtEnd(fcx.builder, body_block);
+        let llvm_next_acc = gen_body(tcx, fcx, llvm_index, llvm_acc);
+        let llvm_next_index = LLVMBuildNUWAdd(
+            fcx.builder,
+            llvm_index,
+            LLVMConstInt(llvm_i64, step_size, 0),
+            libcstr!("next_index"),
+        );
+
+        let body_end_block = LLVMGetInsertBlock(fcx.builder);
+        LLVMBuildBr(fcx.builder, cond_block);
+
+        let index_incoming_values = &mut [llvm_start, llvm_next_index];
+        let incoming_blocks = &mut [entry_block, body_end_block];
+        LLVMAddIncoming(
+            llvm_index,
+            index_incoming_values.as_mut_ptr(),
+            incoming_blocks.as_mut_ptr(),
+            incoming_blocks.len() as u32,
+        );
+
+        if let (Some(llvm_acc), Some(llvm_init)) = (llvm_acc, llvm_acc_init) {
+            let acc_incoming_values = &mut [
+                llvm_init,
+                llvm_next_acc.expect("loop body didn't return an accumulator"),
+            ];
+
+            LLVMAddIncoming(
+                llvm_acc,
+                acc_incoming_values.as_mut_ptr(),
+                incoming_blocks.as_mut_ptr(),
+                incoming_blocks.len() as u32,
+            );
+        }
+
+        LLVMPositionBuilderAtEnd(fcx.builder, exit_block);
+        llvm_acc
+    }
+}
+
+/// Generates a loop over `llvm_len` float members
+///
+/// Members are processed using vectors of the target's preferred width followed by a scalar loop
+/// for any remaining members. `gen_body` is used to generate both loop bodies.
+///
+/// The final values of the vector and scalar accumulators are returned.
+fn gen_float_array_loop<F>(
+    tcx: &mut TargetCtx,
+    fcx: &mut FunCtx,
+    llvm_len: LLVMValueRef,
+    llvm_acc_inits: Option<(LLVMValueRef, LLVMValueRef)>,
+    mut gen_body: F,
+) -> Option<(LLVMValueRef, LLVMValueRef)>
+where
+    F: FnMut(
+        &mut TargetCtx,
+        &mut FunCtx,
+        FloatLoopStep,
+        Option<LLVMValueRef>,
+    ) -> Option<LLVMValueRef>,
+{
+    unsafe {
+        let llvm_i64 = LLVMInt64TypeInContext(tcx.llx);
+        let llvm_double = LLVMDoubleTypeInContext(tcx.llx);
+
+        let lanes = tcx.float_vector_lanes();
+        let llvm_vector_type = LLVMVectorType(llvm_double, lanes);
+
+        // Round down to a multiple of our lane count
+        let llvm_vector_end = LLVMBuildAnd(
+            fcx.builder,
+            llvm_len,
+            LLVMConstInt(llvm_i64, !(u64::from(lanes) - 1), 0),
+            libcstr!("vector_end"),
+        );
+
+        let llvm_vector_acc = gen_counted_loop(
+            tcx,
+            fcx,
+            LLVMConstInt(llvm_i64, 0, 0),
+            llvm_vector_end,
+            u64::from(lanes),
+            llvm_acc_inits.map(|(vector_init, _)| vector_init),
+            |tcx, fcx, llvm_index, llvm_acc| {
+                let step = FloatLoopStep {
+                    llvm_member_type: llvm_vector_type,
+                    vectorised: true,
+                    llvm_index,
+                };
+
+                gen_body(tcx, fcx, step, llvm_acc)
+            },
+        );
+
+        let llvm_scalar_acc = gen_counted_loop(
+            tcx,
+            fcx,
+            llvm_vector_end,
+            llvm_len,
+            1,
+            llvm_acc_inits.map(|(_, scalar_init)| scalar_init),
+            |tcx, fcx, llvm_index, llvm_acc| {
+                let step = FloatLoopStep {
+                    llvm_member_type: llvm_double,
+                    vectorised: false,
+                    llvm_index,
+                };
+
+                gen_body(tcx, fcx, step, llvm_acc)
+            },
+        );
+
+        llvm_vector_acc
+            .and_then(|vector_acc| llvm_scalar_acc.map(|scalar_acc| (vector_acc, scalar_acc)))
+    }
+}
+
+/// Adds each member of `llvm_rhs_array` to `llvm_lhs_array` in-place
+pub(crate) fn gen_float_array_add(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    fcx: &mut FunCtx,
+    span: Span,
+    llvm_lhs_array: LLVMValueRef,
+    llvm_rhs_array: LLVMValueRef,
+) {
+    unsafe {
+        let llvm_len = gen_matching_array_len(tcx, mcx, fcx, span, llvm_lhs_array, llvm_rhs_array);
+
+        let llvm_lhs_values = load_boxed_num_array_values_ptr(tcx, fcx, llvm_lhs_array);
+        let llvm_rhs_values = load_boxed_num_array_values_ptr(tcx, fcx, llvm_rhs_array);
+
+        gen_float_array_loop(tcx, fcx, llvm_len, None, |_, fcx, step, _| {
+            let llvm_lhs = load_float_member(fcx, step, llvm_lhs_values);
+            let llvm_rhs = load_float_member(fcx, step, llvm_rhs_values);
+
+            let llvm_sum = LLVMBuildFAdd(fcx.builder, llvm_lhs, llvm_rhs, libcstr!("sum"));
+            store_float_member(fcx, step, llvm_lhs_values, llvm_sum);
+
+            None
+        });
+    }
+}
+
+/// Multiplies each member of `llvm_array` by `llvm_factor` in-place
+pub(crate) fn gen_float_array_scale(
+    tcx: &mut TargetCtx,
+    fcx: &mut FunCtx,
+    llvm_array: LLVMValueRef,
+    llvm_factor: LLVMValueRef,
+) {
+    unsafe {
+        let llvm_i32 = LLVMInt32TypeInContext(tcx.llx);
+        let llvm_double = LLVMDoubleTypeInContext(tcx.llx);
+
+        let lanes = tcx.float_vector_lanes();
+        let llvm_vector_type = LLVMVectorType(llvm_double, lanes);
+
+        // Splat the factor across every lane
+        let llvm_factor_first_lane = LLVMBuildInsertElement(
+            fcx.builder,
+            LLVMGetUndef(llvm_vector_type),
+            llvm_factor,
+            LLVMConstInt(llvm_i32, 0, 0),
+            libcstr!("factor_first_lane"),
+        );
+
+        let llvm_factor_vector = LLVMBuildShuffleVector(
+            fcx.builder,
+            llvm_factor_first_lane,
+            LLVMGetUndef(llvm_vector_type),
+            LLVMConstNull(LLVMVectorType(llvm_i32, lanes)),
+            libcstr!("factor_vector"),
+        );
+
+        let llvm_len = load_boxed_num_array_len(tcx, fcx, llvm_array);
+        let llvm_values = load_boxed_num_array_values_ptr(tcx, fcx, llvm_array);
+
+        gen_float_array_loop(tcx, fcx, llvm_len, None, |_, fcx, step, _| {
+            let llvm_member = load_float_member(fcx, step, llvm_values);
+
+            let llvm_step_factor = if step.vectorised {
+                llvm_factor_vector
+            } else {
+                llvm_factor
+            };
+
+            let llvm_product = LLVMBuildFMul(
+                fcx.builder,
+                llvm_member,
+                llvm_step_factor,
+                libcstr!("product"),
+            );
+            store_float_member(fcx, step, llvm_values, llvm_product);
+
+            None
+        });
+    }
+}
+
+/// Returns the dot product of two float arrays
+///
+/// The products are summed in a vectorised order. This may differ from a sequential sum due to
+/// floating point rounding.
+pub(crate) fn gen_float_array_dot_product(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    fcx: &mut FunCtx,
+    span: Span,
+    llvm_lhs_array: LLVMValueRef,
+    llvm_rhs_array: LLVMValueRef,
+) -> LLVMValueRef {
+    unsafe {
+        let llvm_i32 = LLVMInt32TypeInContext(tcx.llx);
+        let llvm_double = LLVMDoubleTypeInContext(tcx.llx);
+
+        let lanes = tcx.float_vector_lanes();
+        let llvm_vector_type = LLVMVectorType(llvm_double, lanes);
+
+        let llvm_len = gen_matching_array_len(tcx, mcx, fcx, span, llvm_lhs_array, llvm_rhs_array);
+
+        let llvm_lhs_values = load_boxed_num_array_values_ptr(tcx, fcx, llvm_lhs_array);
+        let llvm_rhs_values = load_boxed_num_array_values_ptr(tcx, fcx, llvm_rhs_array);
+
+        let llvm_acc_inits = (
+            LLVMConstNull(llvm_vector_type),
+            LLVMConstReal(llvm_double, 0.0),
+        );
+
+        let (llvm_vector_acc, llvm_scalar_acc) = gen_float_array_loop(
+            tcx,
+            fcx,
+            llvm_len,
+            Some(llvm_acc_inits),
+            |_, fcx, step, llvm_acc| {
+                let llvm_lhs = load_float_member(fcx, step, llvm_lhs_values);
+                let llvm_rhs = load_float_member(fcx, step, llvm_rhs_values);
+
+                let llvm_product =
+                    LLVMBuildFMul(fcx.builder, llvm_lhs, llvm_rhs, libcstr!("product"));
+
+                Some(LLVMBuildFAdd(
+                    fcx.builder,
+                    llvm_acc.unwrap(),
+                    llvm_product,
+                    libcstr!("next_acc"),
+                ))
+            },
+        )
+        .unwrap();
+
+        // Sum the lanes of the vector accumulator
+        (0..lanes).fold(llvm_scalar_acc, |llvm_sum, lane| {
+            let llvm_lane = LLVMBuildExtractElement(
+                fcx.builder,
+                llvm_vector_acc,
+                LLVMConstInt(llvm_i32, u64::from(lane), 0),
+                libcstr!("acc_lane"),
+            );
+
+            LLVMBuildFAdd(fcx.builder, llvm_sum, llvm_lane, libcstr!("dot_product"))
+        })
+    }
+}
@@ -9,10 +9,12 @@ use crate::mir::ops::*;
 use crate::codegen::fun_gen::FunCtx;
 use crate::codegen::math_gen;
 use crate::codegen::mod_gen::ModCtx;
+use crate::codegen::num_array_gen;
 use crate::codegen::panic_gen::gen_panic;
 use crate::codegen::record_struct;
 use crate::codegen::target_gen::TargetCtx;
 use crate::codegen::{alloc, const_gen};
+use crate::crash;
 use crate::libcstr;
 
 fn comparison_to_llvm_int_pred(comparison: Comparison) -> LLVMIntPredicate {
@@ -64,6 +66,8 @@ fn gen_op(
     active_alloc: &mut alloc::ActiveAlloc<'_>,
     op: &Op,
 ) {
+    let _processing_span = crash::enter_span(op.span);
+
     unsafe {
         match &op.kind {
             OpKind::ConstBoxedNil(reg, _) => {
@@ -126,6 +130,10 @@ fn gen_op(
                 let llvm_value = const_gen::gen_boxed_float(tcx, mcx, *value);
                 fcx.regs.insert(*reg, llvm_value);
             }
+            OpKind::ConstBoxedRational(reg, (numer, denom)) => {
+                let llvm_value = const_gen::gen_boxed_rational(tcx, mcx, *numer, *denom);
+                fcx.regs.insert(*reg, llvm_value);
+            }
             OpKind::ConstBoxedChar(reg, value) => {
                 let llvm_value = const_gen::gen_boxed_char(tcx, mcx, *value);
                 fcx.regs.insert(*reg, llvm_value);
@@ -181,7 +189,9 @@ fn gen_op(
                 let llvm_value = const_gen::gen_boxed_set(
                     tcx,
                     mcx,
-                    elements.iter().map(|element| fcx.regs[element]),
+                    elements
+                        .iter()
+                        .map(|(hash, element)| (*hash, fcx.regs[element])),
                 );
 
                 fcx.regs.insert(*reg, llvm_value);
@@ -192,7 +202,7 @@ fn gen_op(
                     mcx,
                     entries
                         .iter()
-                        .map(|(key, value)| (fcx.regs[key], fcx.regs[value])),
+                        .map(|(hash, key, value)| (*hash, fcx.regs[key], fcx.regs[value])),
                 );
 
                 fcx.regs.insert(*reg, llvm_value);
@@ -266,6 +276,33 @@ fn gen_op(
                 let llvm_value = fcx.regs[reg];
                 LLVMBuildRet(fcx.builder, llvm_value);
             }
+            OpKind::RetValues(regs) => {
+                let mut llvm_values: Vec<LLVMValueRef> =
+                    regs.iter().map(|reg| fcx.regs[reg]).collect();
+
+                LLVMBuildAggregateRet(
+                    fcx.builder,
+                    llvm_values.as_mut_ptr(),
+                    llvm_values.len() as u32,
+                );
+            }
+            OpKind::LoadValuesMember(
+                reg,
+                LoadValuesMemberOp {
+                    values_reg,
+                    member_index,
+                },
+            ) => {
+                let llvm_values = fcx.regs[values_reg];
+                let llvm_member = LLVMBuildExtractValue(
+                    fcx.builder,
+                    llvm_values,
+                    *member_index as u32,
+                    libcstr!("values_member"),
+                );
+
+                fcx.regs.insert(*reg, llvm_member);
+            }
             OpKind::RetVoid => {
                 LLVMBuildRetVoid(fcx.builder);
             }
@@ -530,6 +567,71 @@ fn gen_op(
 
                 fcx.regs.insert(*reg, llvm_vector_member);
             }
+            OpKind::LoadBoxedNumArrayLen(reg, array_reg) => {
+                let llvm_boxed_array = fcx.regs[array_reg];
+                let llvm_array_len =
+                    num_array_gen::load_boxed_num_array_len(tcx, fcx, llvm_boxed_array);
+
+                fcx.regs.insert(*reg, llvm_array_len);
+            }
+            OpKind::LoadBoxedNumArrayMember(
+                reg,
+                LoadBoxedNumArrayMemberOp {
+                    array_reg,
+                    index_reg,
+                },
+            ) => {
+                let llvm_boxed_array = fcx.regs[array_reg];
+                let llvm_index = fcx.regs[index_reg];
+
+                let llvm_array_member = num_array_gen::load_boxed_num_array_member(
+                    tcx,
+                    mcx,
+                    fcx,
+                    op.span,
+                    llvm_boxed_array,
+                    llvm_index,
+                );
+
+                fcx.regs.insert(*reg, llvm_array_member);
+            }
+            OpKind::FloatArrayAdd(BinaryOp { lhs_reg, rhs_reg }) => {
+                let llvm_lhs_array = fcx.regs[lhs_reg];
+                let llvm_rhs_array = fcx.regs[rhs_reg];
+
+                num_array_gen::gen_float_array_add(
+                    tcx,
+                    mcx,
+                    fcx,
+                    op.span,
+                    llvm_lhs_array,
+                    llvm_rhs_array,
+                );
+            }
+            OpKind::FloatArrayScale(FloatArrayScaleOp {
+                array_reg,
+                factor_reg,
+            }) => {
+                let llvm_array = fcx.regs[array_reg];
+                let llvm_factor = fcx.regs[factor_reg];
+
+                num_array_gen::gen_float_array_scale(tcx, fcx, llvm_array, llvm_factor);
+            }
+            OpKind::FloatArrayDotProduct(reg, BinaryOp { lhs_reg, rhs_reg }) => {
+                let llvm_lhs_array = fcx.regs[lhs_reg];
+                let llvm_rhs_array = fcx.regs[rhs_reg];
+
+                let llvm_dot_product = num_array_gen::gen_float_array_dot_product(
+                    tcx,
+                    mcx,
+                    fcx,
+                    op.span,
+                    llvm_lhs_array,
+                    llvm_rhs_array,
+                );
+
+                fcx.regs.insert(*reg, llvm_dot_product);
+            }
             OpKind::Cond(cond_op) => {
                 let cond_alloc_plan = active_alloc.next_cond_plan();
                 gen_cond(tcx, mcx, fcx, cond_op, cond_alloc_plan);
@@ -775,6 +877,7 @@ fn gen_op(
                     tcx,
                     mcx,
                     fcx,
+                    op.span,
                     &math_gen::CHECKED_ADD,
                     llvm_lhs,
                     llvm_rhs,
@@ -797,6 +900,7 @@ fn gen_op(
                     tcx,
                     mcx,
                     fcx,
+                    op.span,
                     &math_gen::CHECKED_MUL,
                     llvm_lhs,
                     llvm_rhs,
@@ -823,6 +927,7 @@ fn gen_op(
                     tcx,
                     mcx,
                     fcx,
+                    op.span,
                     &math_gen::CHECKED_SUB,
                     llvm_lhs,
                     llvm_rhs,
@@ -850,7 +955,7 @@ fn gen_op(
                 let llvm_denom = fcx.regs[rhs_reg];
 
                 let llvm_value =
-                    math_gen::gen_checked_int_div(tcx, mcx, fcx, llvm_numer, llvm_denom);
+                    math_gen::gen_checked_int_div(tcx, mcx, fcx, op.span, llvm_numer, llvm_denom);
 
                 fcx.regs.insert(*reg, llvm_value);
             }
@@ -867,7 +972,7 @@ fn gen_op(
                 let llvm_denom = fcx.regs[rhs_reg];
 
                 let llvm_value =
-                    math_gen::gen_checked_int_rem(tcx, mcx, fcx, llvm_numer, llvm_denom);
+                    math_gen::gen_checked_int_rem(tcx, mcx, fcx, op.span, llvm_numer, llvm_denom);
 
                 fcx.regs.insert(*reg, llvm_value);
             }
@@ -1009,6 +1114,7 @@ fn gen_op(
                     tcx,
                     mcx,
                     fcx.builder,
+                    fcx.current_task,
                     active_alloc,
                     box_source,
                     &input,
@@ -1,6 +1,8 @@
 use llvm_sys::core::*;
 use llvm_sys::LLVMAttributeFunctionIndex;
 
+use arret_syntax::span::Span;
+
 use crate::codegen::const_gen::annotate_private_global;
 use crate::codegen::fun_gen::FunCtx;
 use crate::codegen::mod_gen::ModCtx;
@@ -85,3 +87,23 @@ pub(crate) fn gen_panic(
         LLVMBuildUnreachable(fcx.builder);
     }
 }
+
+/// Generates a panic for a failed runtime check within the expression at `span`
+///
+/// The source location is appended to the message when it's available.
+pub(crate) fn gen_panic_at(
+    tcx: &mut TargetCtx,
+    mcx: &mut ModCtx<'_, '_, '_>,
+    fcx: &mut FunCtx,
+    span: Span,
+    message: &str,
+) {
+    match mcx.source_loc_for_span(span) {
+        Some(source_loc) => {
+            gen_panic(tcx, mcx, fcx, &format!("{} at {}", message, source_loc));
+        }
+        None => {
+            gen_panic(tcx, mcx, fcx, message);
+        }
+    }
+}
@@ -12,8 +12,11 @@ use crate::codegen::analysis::AnalysedMod;
 use crate::codegen::mod_gen::{gen_mod, GeneratedMod};
 use crate::codegen::target_gen::TargetCtx;
 use crate::context::LinkedLibrary;
+use crate::crash::{self, Unsupported};
 use crate::libcstr;
 use crate::mir;
+use crate::opt_level::OptLevel;
+use crate::trace;
 use crate::SourceLoader;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -29,7 +32,9 @@ pub enum OutputType {
 pub struct Options<'target> {
     target_triple: Option<&'target str>,
     output_type: OutputType,
-    llvm_opt: bool,
+    opt_level: OptLevel,
+    debug_info: bool,
+    alloc_profile: bool,
 }
 
 impl<'target> Options<'target> {
@@ -37,7 +42,9 @@ impl<'target> Options<'target> {
         Options {
             target_triple: None,
             output_type: OutputType::Executable,
-            llvm_opt: true,
+            opt_level: OptLevel::default(),
+            debug_info: false,
+            alloc_profile: false,
         }
     }
 
@@ -48,8 +55,23 @@ impl<'target> Options<'target> {
         }
     }
 
-    pub fn with_llvm_opt(self, llvm_opt: bool) -> Options<'target> {
-        Options { llvm_opt, ..self }
+    pub fn with_opt_level(self, opt_level: OptLevel) -> Options<'target> {
+        Options { opt_level, ..self }
+    }
+
+    pub fn with_debug_info(self, debug_info: bool) -> Options<'target> {
+        Options { debug_info, ..self }
+    }
+
+    /// Returns these options with allocation profiling enabled or disabled
+    ///
+    /// Profiled programs record every heap allocation along with the function that made it. The
+    /// profile is written to stderr when the program exits.
+    pub fn with_alloc_profile(self, alloc_profile: bool) -> Options<'target> {
+        Options {
+            alloc_profile,
+            ..self
+        }
     }
 
     pub fn with_output_type(self, output_type: OutputType) -> Options<'target> {
@@ -103,7 +125,8 @@ fn c_main_llvm_type(tcx: &mut TargetCtx) -> LLVMTypeRef {
 fn program_to_module(
     tcx: &mut TargetCtx,
     program: &mir::BuiltProgram,
-    debug_source_loader: Option<&SourceLoader>,
+    source_loader: Option<&SourceLoader>,
+    debug_info: bool,
 ) -> LLVMModuleRef {
     unsafe {
         let analysed_mod = AnalysedMod::new(&program.private_funs, &program.main);
@@ -120,7 +143,8 @@ fn program_to_module(
             &analysed_mod,
             None,
             HashMap::new(),
-            debug_source_loader,
+            source_loader,
+            debug_info,
         );
 
         LLVMSetLinkage(llvm_arret_main, LLVMLinkage::LLVMPrivateLinkage);
@@ -132,6 +156,26 @@ fn program_to_module(
         let bb = LLVMAppendBasicBlockInContext(tcx.llx, c_main, libcstr!("entry"));
         LLVMPositionBuilderAtEnd(builder, bb);
 
+        if tcx.alloc_profile() {
+            // This needs to happen before the main task's heap is created
+            let request_alloc_profile_llvm_type =
+                LLVMFunctionType(LLVMVoidTypeInContext(tcx.llx), ptr::null_mut(), 0, 0);
+
+            let request_alloc_profile_llvm_fun = LLVMAddFunction(
+                llvm_module,
+                libcstr!("arret_runtime_request_alloc_profile"),
+                request_alloc_profile_llvm_type,
+            );
+
+            LLVMBuildCall(
+                builder,
+                request_alloc_profile_llvm_fun,
+                ptr::null_mut(),
+                0,
+                libcstr!(""),
+            );
+        }
+
         let classmap_class_ptr_type = LLVMPointerType(tcx.classmap_class_llvm_type(), 0);
 
         // Declare arret_runtime_launch_task
@@ -189,26 +233,41 @@ fn target_triple_to_cc_args(target_triple: &str) -> Vec<&str> {
 
 /// Generates code for the program with the given output type
 ///
+/// If `source_loader` is provided runtime panics will include the source location of the
+/// expression that caused them. It's also required to generate debug information.
+///
 /// `codegen::initialise_llvm()` must be called before this.
 pub fn gen_program(
     options: Options<'_>,
     linked_libraries: &[Arc<LinkedLibrary>],
     program: &mir::BuiltProgram,
     output_file: &path::Path,
-    debug_source_loader: Option<&SourceLoader>,
-) {
+    source_loader: Option<&SourceLoader>,
+) -> Result<(), Unsupported> {
     use crate::codegen::target_machine::create_target_machine;
 
-    if env::var_os("ARRET_DUMP_MIR").is_some() {
-        mir::print_program(&mut io::stdout().lock(), program, debug_source_loader).unwrap();
-    }
-
     let Options {
         target_triple,
         output_type,
-        llvm_opt,
+        opt_level,
+        debug_info,
+        alloc_profile,
     } = options;
 
+    let _span = trace::enter_with_args("codegen", "gen_program", || {
+        vec![("opt_level", opt_level.to_string())]
+    });
+
+    if env::var_os("ARRET_DUMP_MIR").is_some() {
+        mir::print_program(
+            &mut io::stdout().lock(),
+            program,
+            linked_libraries,
+            source_loader,
+        )
+        .unwrap();
+    }
+
     let llvm_output_path = if output_type == OutputType::Executable {
         // When outputting an executable this is an intermediate file that we pass to our linker
         output_file.with_extension("o")
@@ -223,19 +282,37 @@ pub fn gen_program(
         target_triple,
         LLVMRelocMode::LLVMRelocDynamicNoPic,
         LLVMCodeModel::LLVMCodeModelDefault,
+        opt_level,
     );
 
-    let mut tcx = TargetCtx::new(target_machine, llvm_opt);
-    let module = program_to_module(&mut tcx, program, debug_source_loader);
-    tcx.finish_module(module);
+    let mut tcx = TargetCtx::new(target_machine, opt_level);
+    tcx.set_alloc_profile(alloc_profile);
+    let module = {
+        let _span = trace::enter("codegen", "gen_module");
+        crash::catch_unsupported(|| program_to_module(&mut tcx, program, source_loader, debug_info))
+    };
 
+    let module = match module {
+        Ok(module) => module,
+        Err(unsupported) => {
+            unsafe { LLVMDisposeTargetMachine(target_machine) };
+            return Err(unsupported);
+        }
+    };
+
+    {
+        let _span = trace::enter("codegen", "optimise_module");
+        tcx.finish_module(module);
+    }
+
+    let emit_span = trace::enter("codegen", "emit");
     unsafe {
         let mut error: *mut libc::c_char = ptr::null_mut();
 
         let llvm_code_gen_file_type = match output_type {
             OutputType::None => {
                 LLVMDisposeTargetMachine(target_machine);
-                return;
+                return Ok(());
             }
             OutputType::LlvmIr => {
                 if LLVMPrintModuleToFile(
@@ -249,7 +326,7 @@ pub fn gen_program(
                         CStr::from_ptr(error).to_str().unwrap()
                     );
                 }
-                return;
+                return Ok(());
             }
             OutputType::Assembly => LLVMCodeGenFileType::LLVMAssemblyFile,
             OutputType::Object | OutputType::Executable => LLVMCodeGenFileType::LLVMObjectFile,
@@ -270,8 +347,11 @@ pub fn gen_program(
         }
         LLVMDisposeTargetMachine(target_machine);
     }
+    drop(emit_span);
 
     if output_type == OutputType::Executable {
+        let _span = trace::enter("codegen", "link");
+
         let target_args = match target_triple {
             Some(triple) => target_triple_to_cc_args(triple),
             None => vec![],
@@ -295,4 +375,6 @@ pub fn gen_program(
             panic!("Error invoking linker");
         }
     }
+
+    Ok(())
 }
@@ -11,10 +11,12 @@ use arret_runtime::boxed;
 use arret_runtime::callback::EntryPointAbiType as CallbackEntryPointAbiType;
 
 use crate::codegen::box_layout::BoxLayout;
+use crate::codegen::const_check;
 use crate::codegen::record_struct;
 use crate::codegen::GenAbi;
 use crate::libcstr;
 use crate::mir::ops;
+use crate::opt_level::OptLevel;
 
 fn llvm_enum_attr_for_name(
     llx: LLVMContextRef,
@@ -46,6 +48,32 @@ fn llvm_i64_md_node(llx: LLVMContextRef, values: &[u64]) -> LLVMMetadataRef {
     }
 }
 
+/// Returns the number of `f64` lanes in the widest vector register supported by a target machine
+///
+/// This is based on the target machine's explicitly enabled features. Every target we support has
+/// at least 128 bit vector registers; narrower targets will have wider vectors split by LLVM.
+fn float_vector_lanes_for_target_machine(target_machine: LLVMTargetMachineRef) -> u32 {
+    use std::ffi;
+
+    unsafe {
+        let llvm_features = LLVMGetTargetMachineFeatureString(target_machine);
+        let features = ffi::CStr::from_ptr(llvm_features).to_string_lossy();
+
+        let has_feature = |name: &str| features.split(',').any(|feature| feature == name);
+
+        let lanes = if has_feature("+avx512f") {
+            8
+        } else if has_feature("+avx") {
+            4
+        } else {
+            2
+        };
+
+        LLVMDisposeMessage(llvm_features);
+        lanes
+    }
+}
+
 #[derive(Default)]
 struct CachedTypes {
     task: Option<LLVMTypeRef>,
@@ -61,6 +89,10 @@ struct CachedTypes {
     boxed_inline_vector: Option<LLVMTypeRef>,
     boxed_external_vector: Option<LLVMTypeRef>,
 
+    set_entry: Option<LLVMTypeRef>,
+    boxed_external_set: Option<LLVMTypeRef>,
+    map_entry: Option<LLVMTypeRef>,
+
     global_interned_name: Option<LLVMTypeRef>,
 
     record_struct_box: HashMap<ops::RecordStructId, LLVMTypeRef>,
@@ -85,8 +117,10 @@ pub struct TargetCtx {
     target_machine: LLVMTargetMachineRef,
     target_data: LLVMTargetDataRef,
 
-    optimising: bool,
+    opt_level: OptLevel,
+    alloc_profile: bool,
     module_pass_manager: LLVMPassManagerRef,
+    float_vector_lanes: u32,
 
     boxed_dereferenceable_attr: LLVMAttributeRef,
     boxed_align_attr: LLVMAttributeRef,
@@ -110,7 +144,7 @@ impl TargetCtx {
     /// Construct a new `TargetCtx`
     ///
     /// `target_machine` remains owned by the caller and must outlive this instance.
-    pub fn new(target_machine: LLVMTargetMachineRef, optimising: bool) -> TargetCtx {
+    pub fn new(target_machine: LLVMTargetMachineRef, opt_level: OptLevel) -> TargetCtx {
         use llvm_sys::transforms::pass_manager_builder::*;
         use std::mem;
 
@@ -119,9 +153,10 @@ impl TargetCtx {
             let module_pass_manager = LLVMCreatePassManager();
             let target_data = LLVMCreateTargetDataLayout(target_machine);
 
-            if optimising {
+            if let Some(llvm_pass_level) = opt_level.llvm_pass_level() {
                 let fpmb = LLVMPassManagerBuilderCreate();
-                LLVMPassManagerBuilderSetOptLevel(fpmb, 2);
+                LLVMPassManagerBuilderSetOptLevel(fpmb, llvm_pass_level);
+                LLVMPassManagerBuilderSetSizeLevel(fpmb, opt_level.llvm_size_level());
                 LLVMPassManagerBuilderPopulateModulePassManager(fpmb, module_pass_manager);
                 LLVMPassManagerBuilderDispose(fpmb);
             }
@@ -131,8 +166,10 @@ impl TargetCtx {
                 target_machine,
                 target_data,
 
-                optimising,
+                opt_level,
+                alloc_profile: false,
                 module_pass_manager,
+                float_vector_lanes: float_vector_lanes_for_target_machine(target_machine),
 
                 boxed_dereferenceable_attr: llvm_enum_attr_for_name(
                     llx,
@@ -165,8 +202,17 @@ impl TargetCtx {
         }
     }
 
-    pub fn optimising(&self) -> bool {
-        self.optimising
+    /// Enables or disables recording of heap allocations for the runtime allocation profiler
+    pub fn set_alloc_profile(&mut self, alloc_profile: bool) {
+        self.alloc_profile = alloc_profile;
+    }
+
+    pub fn alloc_profile(&self) -> bool {
+        self.alloc_profile
+    }
+
+    pub fn opt_level(&self) -> OptLevel {
+        self.opt_level
     }
 
     pub fn target_machine(&self) -> LLVMTargetMachineRef {
@@ -177,11 +223,17 @@ impl TargetCtx {
         self.target_data
     }
 
+    /// Returns the number of `f64` lanes to use for vectorised numeric array operations
+    pub fn float_vector_lanes(&self) -> u32 {
+        self.float_vector_lanes
+    }
+
     pub fn task_llvm_ptr_type(&mut self) -> LLVMTypeRef {
         let llvm_any_ptr = self.boxed_abi_to_llvm_ptr_type(&BoxedAbiType::Any);
         let llx = self.llx;
         *self.cached_types.task.get_or_insert_with(|| unsafe {
-            let members = &mut [llvm_any_ptr, llvm_any_ptr];
+            let llvm_stack_limit = LLVMPointerType(LLVMInt8TypeInContext(llx), 0);
+            let members = &mut [llvm_stack_limit, llvm_any_ptr, llvm_any_ptr];
 
             let llvm_type = LLVMStructCreateNamed(llx, libcstr!("task"));
             LLVMStructSetBody(llvm_type, members.as_mut_ptr(), members.len() as u32, 0);
@@ -368,6 +420,66 @@ impl TargetCtx {
             })
     }
 
+    pub fn set_entry_llvm_type(&mut self) -> LLVMTypeRef {
+        let llx = self.llx;
+        let llvm_any_ptr = self.boxed_abi_to_llvm_ptr_type(&BoxedAbiType::Any);
+
+        *self.cached_types.set_entry.get_or_insert_with(|| unsafe {
+            let llvm_i64 = LLVMInt64TypeInContext(llx);
+
+            // hash, value
+            let members = &mut [llvm_i64, llvm_any_ptr];
+
+            let llvm_type = LLVMStructCreateNamed(llx, libcstr!("set_entry"));
+            LLVMStructSetBody(llvm_type, members.as_mut_ptr(), members.len() as u32, 0);
+
+            llvm_type
+        })
+    }
+
+    pub fn boxed_external_set_llvm_type(&mut self) -> LLVMTypeRef {
+        let llx = self.llx;
+        let llvm_header = self.box_header_llvm_type();
+        let set_entry_type = self.set_entry_llvm_type();
+
+        *self
+            .cached_types
+            .boxed_external_set
+            .get_or_insert_with(|| unsafe {
+                let llvm_i32 = LLVMInt32TypeInContext(llx);
+                let llvm_i64 = LLVMInt64TypeInContext(llx);
+
+                let members = &mut [
+                    llvm_header,
+                    llvm_i32,
+                    llvm_i64,
+                    LLVMPointerType(set_entry_type, 0),
+                ];
+
+                let llvm_type = LLVMStructCreateNamed(llx, libcstr!("boxed_external_set"));
+                LLVMStructSetBody(llvm_type, members.as_mut_ptr(), members.len() as u32, 0);
+
+                llvm_type
+            })
+    }
+
+    pub fn map_entry_llvm_type(&mut self) -> LLVMTypeRef {
+        let llx = self.llx;
+        let llvm_any_ptr = self.boxed_abi_to_llvm_ptr_type(&BoxedAbiType::Any);
+
+        *self.cached_types.map_entry.get_or_insert_with(|| unsafe {
+            let llvm_i64 = LLVMInt64TypeInContext(llx);
+
+            // hash, key, value
+            let members = &mut [llvm_i64, llvm_any_ptr, llvm_any_ptr];
+
+            let llvm_type = LLVMStructCreateNamed(llx, libcstr!("map_entry"));
+            LLVMStructSetBody(llvm_type, members.as_mut_ptr(), members.len() as u32, 0);
+
+            llvm_type
+        })
+    }
+
     pub fn boxed_inline_vector_llvm_type(&mut self) -> LLVMTypeRef {
         let llx = self.llx;
         let llvm_header = self.box_header_llvm_type();
@@ -481,6 +593,21 @@ impl TargetCtx {
     fn ret_abi_to_llvm_type(&mut self, ret_abi_type: &RetAbiType) -> LLVMTypeRef {
         match ret_abi_type {
             RetAbiType::Inhabited(abi_type) => self.abi_to_llvm_type(abi_type),
+            RetAbiType::Values(abi_types) => {
+                let mut llvm_member_types: Vec<LLVMTypeRef> = abi_types
+                    .iter()
+                    .map(|abi_type| self.abi_to_llvm_type(abi_type))
+                    .collect();
+
+                unsafe {
+                    LLVMStructTypeInContext(
+                        self.llx,
+                        llvm_member_types.as_mut_ptr(),
+                        llvm_member_types.len() as u32,
+                        0,
+                    )
+                }
+            }
             RetAbiType::Void | RetAbiType::Never => unsafe { LLVMVoidTypeInContext(self.llx) },
         }
     }
@@ -756,6 +883,12 @@ impl TargetCtx {
             );
             LLVMDisposeMessage(error);
 
+            // Make sure our constant boxes don't require initialisation at runtime
+            if let Err(errors) = const_check::verify_const_globals(module) {
+                let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                panic!("invalid constant globals: {}", messages.join(", "));
+            }
+
             // Optimise
             LLVMRunPassManager(self.module_pass_manager, module);
         }
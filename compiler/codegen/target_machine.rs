@@ -3,6 +3,8 @@ use std::{ffi, ptr};
 use llvm_sys::core::*;
 use llvm_sys::target_machine::*;
 
+use crate::opt_level::OptLevel;
+
 enum TripleString {
     Cross(ffi::CString),
     LlvmDefault(*mut libc::c_char),
@@ -27,10 +29,19 @@ impl Drop for TripleString {
     }
 }
 
+fn codegen_opt_level(opt_level: OptLevel) -> LLVMCodeGenOptLevel {
+    match opt_level {
+        OptLevel::O0 => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+        OptLevel::O1 => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+        OptLevel::O2 | OptLevel::Os => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+    }
+}
+
 pub fn create_target_machine(
     cross_triple: Option<&str>,
     reloc_mode: LLVMRelocMode,
     code_model: LLVMCodeModel,
+    opt_level: OptLevel,
 ) -> LLVMTargetMachineRef {
     let cross_triple = cross_triple.map(|cross_triple| ffi::CString::new(cross_triple).unwrap());
 
@@ -57,7 +68,7 @@ pub fn create_target_machine(
             triple_string.as_ptr(),
             ptr::null(),
             ptr::null(),
-            LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            codegen_opt_level(opt_level),
             reloc_mode,
             code_model,
         )
@@ -6,6 +6,7 @@ use arret_runtime::boxed;
 
 use crate::codegen::fun_gen::FunCtx;
 use crate::codegen::target_gen::TargetCtx;
+use crate::crash;
 use crate::libcstr;
 
 fn load_boxed_external_vector_len(
@@ -210,7 +211,13 @@ fn load_boxed_external_vector_member(
             (TAIL_PTR_INDEX, (member_index - NODE_SIZE) as u64)
         }
     } else {
-        todo!("loading member of vector of length {}", known_vector_len);
+        crash::unsupported(
+            None,
+            format!(
+                "accessing members of vectors of length {}",
+                known_vector_len
+            ),
+        );
     };
 
     unsafe {
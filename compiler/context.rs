@@ -1,4 +1,5 @@
 use crate::hir::PackagePaths;
+use crate::opt_level::OptLevel;
 use crate::rfi;
 use crate::source::SourceLoader;
 
@@ -8,20 +9,23 @@ use std::{hash, path};
 
 use codespan_reporting::diagnostic::Diagnostic;
 
-use arret_syntax::datum::Datum;
+use arret_syntax::datum::{DataStr, Datum};
 use arret_syntax::span::{FileId, Span};
 
 use crate::hir;
+use crate::hir::error::{Error, ErrorKind};
 use crate::hir::exports::Exports;
 use crate::hir::import;
-use crate::hir::loader::{LoadedModule, ModuleName};
+use crate::hir::loader::{find_modules_exporting, LoadedModule, ModuleName};
 use crate::hir::lowering::LoweredModule;
 use crate::promise::PromiseMap;
-use crate::reporting::diagnostic_for_syntax_error;
+use crate::reporting::diagnostics_for_syntax_errors;
 use crate::reporting::errors_to_diagnostics;
-use crate::source::SourceFile;
+use crate::source::{SourceFile, EMPTY_SPAN};
+use crate::trace;
 use crate::ty;
-use crate::typeck::infer;
+use crate::typeck::cache::{compiler_build_key, CacheEnv, CacheKey, KeyHasher, TypeckCache};
+use crate::typeck::{hole, infer};
 
 new_global_id_type!(
     ModuleId,
@@ -33,11 +37,22 @@ new_global_id_type!(
 pub(crate) type ModuleImports = HashMap<ModuleName, Arc<Module>>;
 
 pub struct LinkedLibrary {
-    _loaded: libloading::Library,
+    _loaded: Option<libloading::Library>,
     target_path: Box<path::Path>,
 }
 
 impl LinkedLibrary {
+    /// Creates a library that's linked against without being loaded
+    ///
+    /// This is only suitable for programs that aren't evaluated by the compiler, such as those
+    /// parsed from MIR.
+    pub fn unloaded(target_path: impl Into<Box<path::Path>>) -> LinkedLibrary {
+        LinkedLibrary {
+            _loaded: None,
+            target_path: target_path.into(),
+        }
+    }
+
     pub fn target_path(&self) -> &path::Path {
         &self.target_path
     }
@@ -54,8 +69,15 @@ pub(crate) struct Module {
     pub inferred_locals: Arc<HashMap<hir::LocalId, ty::Ref<ty::Poly>>>,
     pub exports: Exports,
     pub main_local_id: Option<hir::LocalId>,
+    pub warnings: Vec<Diagnostic<FileId>>,
 
     pub linked_library: Option<Arc<LinkedLibrary>>,
+
+    /// Key for the typeck cache entries of this module and the modules importing it
+    ///
+    /// This is `None` if the module's contents can't be identified. Modules importing it won't be
+    /// cached.
+    pub typeck_cache_key: Option<CacheKey>,
 }
 
 impl PartialEq for Module {
@@ -72,13 +94,67 @@ impl hash::Hash for Module {
     }
 }
 
+/// Identifies a module that's being loaded
+#[derive(Clone, PartialEq)]
+enum LoadingModuleKey {
+    Name(ModuleName),
+    File(Box<path::Path>),
+}
+
+/// Module being loaded along with the import that caused it to be loaded
+///
+/// The modules currently being loaded form a stack that's used to detect circular imports. Without
+/// this a module that indirectly imports itself would wait forever for its own compilation.
+#[derive(Clone)]
+pub(crate) struct LoadingModule {
+    key: LoadingModuleKey,
+    display_name: DataStr,
+    import_span: Span,
+}
+
+/// Returns the modules in `loading_modules` with a new module pushed on top
+///
+/// If the module is already being loaded this returns a circular import error instead.
+fn push_loading_module(
+    loading_modules: &[LoadingModule],
+    key: LoadingModuleKey,
+    display_name: DataStr,
+    import_span: Span,
+) -> Result<Vec<LoadingModule>, Error> {
+    if let Some(index) = loading_modules
+        .iter()
+        .position(|loading| loading.key == key)
+    {
+        // Every module after the first occurrence was imported as part of the cycle
+        let cycle_imports = loading_modules[index + 1..]
+            .iter()
+            .map(|loading| (loading.import_span, loading.display_name.clone()))
+            .collect();
+
+        return Err(Error::new(
+            import_span,
+            ErrorKind::CircularImport(display_name, cycle_imports),
+        ));
+    }
+
+    let mut loading_modules = loading_modules.to_vec();
+
+    loading_modules.push(LoadingModule {
+        key,
+        display_name,
+        import_span,
+    });
+
+    Ok(loading_modules)
+}
+
 type CachedModule = Result<Arc<Module>, Vec<Diagnostic<FileId>>>;
 type UncachedModule = Result<Module, Vec<Diagnostic<FileId>>>;
 
 /// Finds all transitive dependencies for a set of imports
 ///
 /// This is inclusive of the imports themselves.
-fn transitive_deps(imports: &ModuleImports) -> HashSet<Arc<Module>> {
+pub(crate) fn transitive_deps(imports: &ModuleImports) -> HashSet<Arc<Module>> {
     let mut all_deps: HashSet<Arc<Module>> = imports.values().cloned().collect();
 
     for import in imports.values() {
@@ -97,8 +173,12 @@ pub(crate) fn prims_to_module(exports: Exports) -> Module {
         inferred_locals: Arc::new(HashMap::new()),
         exports,
         main_local_id: None,
+        warnings: vec![],
 
         linked_library: None,
+
+        // Primitives are built in to the compiler so they're identified by the compiler's build
+        typeck_cache_key: Some(compiler_build_key()),
     }
 }
 
@@ -115,6 +195,7 @@ fn rfi_library_to_module(span: Span, rfi_library: rfi::Library) -> Module {
     } = rfi_library;
 
     let mut lia = LocalIdAlloc::new();
+    let mut key_hasher = KeyHasher::new();
 
     let mut exports = HashMap::with_capacity(exported_funs.len());
     let mut defs = Vec::with_capacity(exported_funs.len());
@@ -125,6 +206,9 @@ fn rfi_library_to_module(span: Span, rfi_library: rfi::Library) -> Module {
         let arret_type: ty::Ref<ty::Poly> =
             Ty::Fun(Box::new(rust_fun.arret_fun_type().clone())).into();
 
+        key_hasher.write_str(fun_name);
+        key_hasher.write_str(&hir::str_for_ty_ref(&arret_type));
+
         let fun_name_data_str: DataStr = fun_name.into();
 
         let def = hir::Def::<hir::Inferred> {
@@ -142,6 +226,8 @@ fn rfi_library_to_module(span: Span, rfi_library: rfi::Library) -> Module {
                 result_ty: arret_type.clone(),
                 kind: hir::ExprKind::RustFun(rust_fun),
             },
+            doc: None,
+            is_const: false,
         };
 
         defs.push(def);
@@ -158,10 +244,12 @@ fn rfi_library_to_module(span: Span, rfi_library: rfi::Library) -> Module {
         exports,
 
         main_local_id: None,
+        warnings: vec![],
         linked_library: Some(Arc::new(LinkedLibrary {
-            _loaded: loaded,
+            _loaded: Some(loaded),
             target_path,
         })),
+        typeck_cache_key: Some(key_hasher.finish()),
     }
 }
 
@@ -171,16 +259,23 @@ fn rfi_library_to_module(span: Span, rfi_library: rfi::Library) -> Module {
 /// source files and Rust libraries; it should be reused whenever possible.
 pub struct CompileCtx {
     package_paths: PackagePaths,
-    enable_optimisations: bool,
+    opt_level: OptLevel,
+    prelude: Option<ModuleName>,
+    strict: bool,
+    warn_boxing: bool,
+    warn_shadowing: bool,
+    verify_mir: bool,
+    typeck_cache: Option<TypeckCache>,
 
     source_loader: SourceLoader,
     rfi_loader: rfi::Loader,
 
     modules_by_name: PromiseMap<ModuleName, CachedModule>,
+    modules_by_file: PromiseMap<FileId, CachedModule>,
 }
 
 impl CompileCtx {
-    pub fn new(package_paths: PackagePaths, enable_optimisations: bool) -> Self {
+    pub fn new(package_paths: PackagePaths, opt_level: OptLevel) -> Self {
         use crate::hir::exports;
         use std::iter;
 
@@ -202,11 +297,18 @@ impl CompileCtx {
 
         Self {
             package_paths,
-            enable_optimisations,
+            opt_level,
+            prelude: None,
+            strict: false,
+            warn_boxing: false,
+            warn_shadowing: false,
+            verify_mir: false,
+            typeck_cache: None,
 
             source_loader: SourceLoader::new(),
             rfi_loader: rfi::Loader::new(),
             modules_by_name: PromiseMap::new(initial_modules),
+            modules_by_file: PromiseMap::new(iter::empty()),
         }
     }
 
@@ -214,8 +316,106 @@ impl CompileCtx {
         &self.package_paths
     }
 
-    pub fn enable_optimisations(&self) -> bool {
-        self.enable_optimisations
+    pub fn opt_level(&self) -> OptLevel {
+        self.opt_level
+    }
+
+    /// Returns this context with a module implicitly imported into every module
+    ///
+    /// The prelude isn't imported into modules from its own package; this allows the prelude and
+    /// its dependencies to be compiled. By default there is no prelude.
+    pub fn with_prelude(self, prelude: Option<ModuleName>) -> Self {
+        Self { prelude, ..self }
+    }
+
+    pub fn prelude(&self) -> Option<&ModuleName> {
+        self.prelude.as_ref()
+    }
+
+    /// Returns this context with strict type checking enabled or disabled
+    ///
+    /// In strict mode function parameters without a declared type can't be inferred as `Any`.
+    /// This doesn't apply to the standard library.
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns this context with warnings for implicit boxing enabled or disabled
+    ///
+    /// These report where building a program's MIR boxes an `Int`, `Float` or `Bool` without an
+    /// explicit `(box)`.
+    pub fn with_warn_boxing(self, warn_boxing: bool) -> Self {
+        Self {
+            warn_boxing,
+            ..self
+        }
+    }
+
+    pub fn warn_boxing(&self) -> bool {
+        self.warn_boxing
+    }
+
+    /// Returns this context with warnings for shadowed bindings enabled or disabled
+    ///
+    /// These report where a local variable shadows a binding from an enclosing scope. Like other
+    /// lint warnings they aren't reported for the standard library.
+    pub fn with_warn_shadowing(self, warn_shadowing: bool) -> Self {
+        Self {
+            warn_shadowing,
+            ..self
+        }
+    }
+
+    pub fn warn_shadowing(&self) -> bool {
+        self.warn_shadowing
+    }
+
+    /// Returns this context with MIR verification enabled or disabled
+    ///
+    /// This checks the invariants of each function's MIR as it's built and after each
+    /// optimisation. Verification is always enabled in debug builds of the compiler.
+    pub fn with_verify_mir(self, verify_mir: bool) -> Self {
+        Self { verify_mir, ..self }
+    }
+
+    pub fn verify_mir(&self) -> bool {
+        self.verify_mir || cfg!(debug_assertions)
+    }
+
+    /// Returns this context with a directory for caching inferred types
+    ///
+    /// Modules compiled again with identical source and imports skip type inference by restoring
+    /// the HIR inferred by a previous compilation. Modules that can't be restored are inferred
+    /// starting from the types previously inferred for their definitions. By default nothing is
+    /// cached.
+    pub fn with_typeck_cache_dir(self, cache_dir: Option<path::PathBuf>) -> Self {
+        Self {
+            typeck_cache: cache_dir.map(TypeckCache::new),
+            ..self
+        }
+    }
+
+    /// Returns if strict type checking applies to a module in the given package
+    fn strict_for_package(&self, package_name: Option<&DataStr>) -> bool {
+        self.strict && package_name.map(AsRef::as_ref) != Some("stdlib")
+    }
+
+    /// Returns if lint warnings are reported for a module in the given package
+    fn lint_for_package(&self, package_name: Option<&DataStr>) -> bool {
+        package_name.map(AsRef::as_ref) != Some("stdlib")
+    }
+
+    /// Returns the prelude for a module in the given package
+    ///
+    /// Entry modules and modules loaded outside of a package have a `package_name` of `None`.
+    fn prelude_for_package(&self, package_name: Option<&DataStr>) -> Option<&ModuleName> {
+        self.prelude
+            .as_ref()
+            .filter(|prelude| Some(prelude.package_name()) != package_name)
     }
 
     pub fn source_loader(&self) -> &SourceLoader {
@@ -231,46 +431,193 @@ impl CompileCtx {
     /// This returns a cached module; the module will only be compiled once per `CompileCtx`
     /// instance. If the module is being compiled on another thread this will block until the
     /// compilation is finished.
-    fn get_module_by_name(&self, span: Span, module_name: ModuleName) -> CachedModule {
+    ///
+    /// `loading_modules` contains the modules whose imports are currently being loaded; importing
+    /// one of them again is an error.
+    fn get_module_by_name(
+        &self,
+        span: Span,
+        module_name: ModuleName,
+        loading_modules: &[LoadingModule],
+    ) -> CachedModule {
+        let loading_modules = push_loading_module(
+            loading_modules,
+            LoadingModuleKey::Name(module_name.clone()),
+            module_name.to_string().into(),
+            span,
+        )
+        .map_err(|err| vec![err.into()])?;
+
         self.modules_by_name
-            .get_or_insert_with(
-                module_name.clone(),
-                move || match hir::loader::load_module_by_name(self, span, &module_name) {
-                    Ok(LoadedModule::Source(source_file)) => {
-                        self.source_file_to_module(&source_file).map(Arc::new)
-                    }
+            .get_or_insert_with(module_name.clone(), move || {
+                let _span = trace::enter_with_args("hir", "load_module", || {
+                    vec![("module", module_name.to_string())]
+                });
+
+                match hir::loader::load_module_by_name(self, span, &module_name) {
+                    Ok(LoadedModule::Source(source_file)) => self
+                        .package_source_file_to_module(
+                            &source_file,
+                            Some(module_name.package_name()),
+                            false,
+                            &loading_modules,
+                        )
+                        .map(Arc::new),
                     Ok(LoadedModule::Rust(rfi_library)) => {
                         Ok(Arc::new(rfi_library_to_module(span, rfi_library)))
                     }
                     Err(err) => Err(vec![err.into()]),
-                },
-            )
+                }
+            })
     }
 
-    /// Returns an uncached module for a source file
+    /// Returns a module for a source file imported with `(file "path")`
+    ///
+    /// The path is resolved relative to the file containing the import. Modules are cached by
+    /// source file so each file is only compiled once regardless of the path used to import it.
+    fn get_module_by_file_path(
+        &self,
+        span: Span,
+        file_path: &DataStr,
+        loading_modules: &[LoadingModule],
+    ) -> CachedModule {
+        let importing_dir = self
+            .source_loader
+            .span_to_filename(span)
+            .and_then(|filename| {
+                path::Path::new(&filename)
+                    .parent()
+                    .map(path::Path::to_path_buf)
+            })
+            .unwrap_or_default();
+
+        let path = importing_dir.join(file_path.as_ref());
+
+        let canonical_path: Box<path::Path> = path
+            .canonicalize()
+            .map_err(|err| vec![Error::from_module_io(span, &path, &err).into()])?
+            .into();
+
+        let loading_modules = push_loading_module(
+            loading_modules,
+            LoadingModuleKey::File(canonical_path.clone()),
+            file_path.clone(),
+            span,
+        )
+        .map_err(|err| vec![err.into()])?;
+
+        let source_file = self
+            .source_loader
+            .load_path_cached(&canonical_path)
+            .map_err(|err| vec![Error::from_module_io(span, &path, &err).into()])?;
+
+        self.modules_by_file
+            .get_or_insert_with(source_file.file_id(), move || {
+                let _span = trace::enter_with_args("hir", "load_file_module", || {
+                    vec![("path", path.to_string_lossy().into_owned())]
+                });
+
+                self.package_source_file_to_module(&source_file, None, false, &loading_modules)
+                    .map(Arc::new)
+            })
+    }
+
+    /// Returns an uncached module for a source file outside of any package
     pub(crate) fn source_file_to_module(&self, source_file: &SourceFile) -> UncachedModule {
+        let filename =
+            self.source_loader
+                .span_to_filename(Span::new(Some(source_file.file_id()), 0, 0));
+
+        // Allow files imported by the entry module to detect when they import it back
+        let canonical_path = filename
+            .as_ref()
+            .and_then(|filename| path::Path::new(filename).canonicalize().ok());
+
+        let loading_modules = match (filename, canonical_path) {
+            (Some(filename), Some(canonical_path)) => vec![LoadingModule {
+                key: LoadingModuleKey::File(canonical_path.into()),
+                display_name: filename.into(),
+                import_span: EMPTY_SPAN,
+            }],
+            _ => vec![],
+        };
+
+        self.package_source_file_to_module(source_file, None, true, &loading_modules)
+    }
+
+    /// Returns an uncached module for a source file
+    ///
+    /// `is_program` should only be set for a program's entry module. This allows expressions at
+    /// the top-level of the module.
+    fn package_source_file_to_module(
+        &self,
+        source_file: &SourceFile,
+        package_name: Option<&DataStr>,
+        is_program: bool,
+        loading_modules: &[LoadingModule],
+    ) -> UncachedModule {
         let data = source_file
             .parsed()
-            .map_err(|err| vec![diagnostic_for_syntax_error(&err)])?;
-
-        self.data_to_module(data)
+            .map_err(|_| diagnostics_for_syntax_errors(source_file))?;
+
+        let mut source_hasher = KeyHasher::new();
+        source_hasher.write_str(source_file.source());
+
+        self.data_to_module(
+            data,
+            Some(source_hasher.finish()),
+            self.prelude_for_package(package_name),
+            self.strict_for_package(package_name),
+            self.lint_for_package(package_name),
+            is_program,
+            &HashMap::new(),
+            loading_modules,
+        )
     }
 
     /// Collects all imports for a module's syntax data
     ///
-    /// This attempts to import modules concurrently where possible
+    /// This attempts to import modules concurrently where possible. If a prelude is passed it's
+    /// imported along with the modules explicitly imported by the data. Imports of `[self name]`
+    /// are resolved from `inline_modules`. Importing any module in `loading_modules` is reported as
+    /// a circular import.
     pub(crate) fn imports_for_data<'a>(
         &self,
         data: impl Iterator<Item = &'a Datum>,
+        prelude: Option<&ModuleName>,
+        inline_modules: &ModuleImports,
+        loading_modules: &[LoadingModule],
     ) -> Result<ModuleImports, Vec<Diagnostic<FileId>>> {
-        let imported_module_names =
+        let mut imported_module_names =
             import::collect_imported_module_names(data).map_err(errors_to_diagnostics)?;
+
+        if let Some(prelude) = prelude {
+            imported_module_names
+                .entry(prelude.clone())
+                .or_insert(EMPTY_SPAN);
+        }
+
         let import_count = imported_module_names.len();
 
         let loaded_module_results: Vec<(ModuleName, CachedModule)> = imported_module_names
             .into_iter()
             .map(|(module_name, span)| {
-                let module = self.get_module_by_name(span, module_name.clone());
+                let module = if let Some(file_path) = module_name.file_path() {
+                    self.get_module_by_file_path(span, file_path, loading_modules)
+                } else if module_name.package_name().as_ref() == import::INLINE_MODULE_PACKAGE_NAME
+                {
+                    inline_modules.get(&module_name).cloned().ok_or_else(|| {
+                        let error = Error::new(
+                            span,
+                            ErrorKind::InlineModuleNotFound(module_name.terminal_name().clone()),
+                        );
+
+                        vec![error.into()]
+                    })
+                } else {
+                    self.get_module_by_name(span, module_name.clone(), loading_modules)
+                };
+
                 (module_name, module)
             })
             .collect();
@@ -295,16 +642,175 @@ impl CompileCtx {
         Ok(imports)
     }
 
+    /// Builds the modules defined by `(module name ...)` forms in a module's syntax data
+    ///
+    /// Inline modules can import the inline modules visible to their parent along with any of
+    /// their siblings defined before them.
+    fn inline_modules_for_data(
+        &self,
+        data: &[Datum],
+        prelude: Option<&ModuleName>,
+        strict: bool,
+        lint: bool,
+        outer_modules: &ModuleImports,
+        loading_modules: &[LoadingModule],
+    ) -> Result<ModuleImports, Vec<Diagnostic<FileId>>> {
+        let mut inline_modules = outer_modules.clone();
+        let mut defined_spans = HashMap::<DataStr, Span>::new();
+
+        for datum in data {
+            let module_args = if let Some(module_args) = import::try_extract_inline_module(datum) {
+                module_args
+            } else {
+                continue;
+            };
+
+            let ((name_span, name), body_data) =
+                import::parse_inline_module(datum.span(), module_args)
+                    .map_err(|err| vec![err.into()])?;
+
+            if let Some(first_span) = defined_spans.insert(name.clone(), name_span) {
+                return Err(vec![Error::new(
+                    name_span,
+                    ErrorKind::DuplicateDef(Some(first_span), name.clone()),
+                )
+                .into()]);
+            }
+
+            let _span = trace::enter_with_args("hir", "load_inline_module", || {
+                vec![("module", name.to_string())]
+            });
+
+            let module = self.data_to_module(
+                body_data,
+                None,
+                prelude,
+                strict,
+                lint,
+                false,
+                &inline_modules,
+                loading_modules,
+            )?;
+
+            inline_modules.insert(
+                ModuleName::new(
+                    import::INLINE_MODULE_PACKAGE_NAME.into(),
+                    vec![],
+                    name.clone(),
+                ),
+                Arc::new(module),
+            );
+        }
+
+        Ok(inline_modules)
+    }
+
+    /// Converts lowering errors to diagnostics
+    ///
+    /// Unbound identifiers exported by a known library module are annotated with the import that
+    /// would provide them.
+    fn lowering_errors_to_diagnostics(&self, errors: Vec<Error>) -> Vec<Diagnostic<FileId>> {
+        errors
+            .into_iter()
+            .map(|error| {
+                let import_notes: Vec<String> = if let ErrorKind::UnboundIdent(ident) = error.kind()
+                {
+                    find_modules_exporting(self, ident.as_ref())
+                        .into_iter()
+                        .map(|module_name| {
+                            format!(
+                                "`{}` is exported by `{}`; consider adding `(import {})`",
+                                ident, module_name, module_name
+                            )
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                Diagnostic::from(error).with_notes(import_notes)
+            })
+            .collect()
+    }
+
+    /// Returns the typeck cache key for a module
+    ///
+    /// This returns `None` if any imported module doesn't have a key.
+    fn module_typeck_cache_key(
+        &self,
+        source_key: CacheKey,
+        strict: bool,
+        is_program: bool,
+        imports: &ModuleImports,
+    ) -> Option<CacheKey> {
+        let mut import_keys = imports
+            .iter()
+            .map(|(module_name, module)| Some((module_name.to_string(), module.typeck_cache_key?)))
+            .collect::<Option<Vec<(String, CacheKey)>>>()?;
+
+        import_keys.sort_unstable_by(|(name_a, _), (name_b, _)| name_a.cmp(name_b));
+
+        let mut key_hasher = KeyHasher::new();
+        key_hasher.write_key(source_key);
+        key_hasher.write_bool(strict);
+        key_hasher.write_bool(is_program);
+        key_hasher.write_str(self.package_paths.target_triple().unwrap_or_default());
+
+        for (module_name, import_key) in import_keys {
+            key_hasher.write_str(&module_name);
+            key_hasher.write_key(import_key);
+        }
+
+        Some(key_hasher.finish())
+    }
+
     /// Returns an uncached module for syntax data
-    fn data_to_module(&self, data: &[Datum]) -> UncachedModule {
-        let imports = self.imports_for_data(data.iter())?;
-        let lowered_module =
-            hir::lowering::lower_data(&imports, data).map_err(errors_to_diagnostics)?;
+    ///
+    /// `source_key` identifies the source the data was parsed from. Modules without one don't use
+    /// the typeck cache.
+    #[allow(clippy::too_many_arguments)]
+    fn data_to_module(
+        &self,
+        data: &[Datum],
+        source_key: Option<CacheKey>,
+        prelude: Option<&ModuleName>,
+        strict: bool,
+        lint: bool,
+        is_program: bool,
+        outer_modules: &ModuleImports,
+        loading_modules: &[LoadingModule],
+    ) -> UncachedModule {
+        let inline_modules = self.inline_modules_for_data(
+            data,
+            prelude,
+            strict,
+            lint,
+            outer_modules,
+            loading_modules,
+        )?;
+        let imports =
+            self.imports_for_data(data.iter(), prelude, &inline_modules, loading_modules)?;
+
+        let lowered_module = {
+            let _span = trace::enter("hir", "lower_module");
+            hir::lowering::lower_data(
+                &self.source_loader,
+                self.package_paths.target_triple(),
+                &imports,
+                prelude,
+                is_program,
+                lint && self.warn_shadowing,
+                data,
+            )
+            .map_err(|errors| self.lowering_errors_to_diagnostics(errors))?
+        };
 
         let LoweredModule {
             defs: lowered_defs,
             exports,
             main_local_id,
+            mut warnings,
+            ty_holes,
         } = lowered_module;
 
         let imported_inferred_vars = transitive_deps(&imports)
@@ -312,14 +818,74 @@ impl CompileCtx {
             .map(|module| (module.module_id, module.inferred_locals.clone()))
             .collect();
 
-        let inferred_module = infer::infer_module(&imported_inferred_vars, lowered_defs)
-            .map_err(errors_to_diagnostics)?;
+        let typeck_cache_key = source_key.and_then(|source_key| {
+            self.module_typeck_cache_key(source_key, strict, is_program, &imports)
+        });
+
+        let typeck_cache = self.typeck_cache.as_ref().zip(typeck_cache_key);
+
+        // Objects in the lowered HIR are only identifiable before inference consumes it
+        let mut cache_env = typeck_cache.map(|_| {
+            let mut cache_env = CacheEnv::new(&self.source_loader);
+
+            for module in transitive_deps(&imports) {
+                if let Some(key) = module.typeck_cache_key {
+                    cache_env.add_imported_module(
+                        module.module_id,
+                        key,
+                        &module.exports,
+                        &module.inferred_locals,
+                    );
+                }
+            }
+
+            cache_env.add_lowered_module(&lowered_defs, &exports);
+            cache_env
+        });
+
+        let restored_module = typeck_cache
+            .zip(cache_env.as_mut())
+            .and_then(|((typeck_cache, key), cache_env)| typeck_cache.load_module(key, cache_env));
+
+        let inferred_module = if let Some(restored_module) = restored_module {
+            restored_module
+        } else {
+            let cached_def_types =
+                typeck_cache.and_then(|(typeck_cache, key)| typeck_cache.load(key));
+
+            let inferred_module = {
+                let _span = trace::enter("typeck", "infer_module");
+                infer::infer_module_with_cached_types(
+                    &imported_inferred_vars,
+                    lowered_defs,
+                    strict,
+                    cached_def_types.as_ref().unwrap_or(&HashMap::new()),
+                )
+                .map_err(errors_to_diagnostics)?
+            };
+
+            if let (Some((typeck_cache, key)), Some(cache_env), None) =
+                (typeck_cache, cache_env.as_mut(), &cached_def_types)
+            {
+                // The cache only saves work; failing to write to it shouldn't fail compilation
+                let _ = typeck_cache.store(key, &inferred_module.defs);
+                let _ = typeck_cache.store_module(key, cache_env, &inferred_module);
+            }
+
+            inferred_module
+        };
+
+        // Type holes are explicitly requested so they're reported even without linting
+        let ty_hole_notes = hole::ty_hole_notes(&ty_holes, &inferred_module);
 
         let infer::InferredModule {
             defs: inferred_defs,
             inferred_locals,
+            warnings: infer_warnings,
         } = inferred_module;
 
+        warnings.extend(infer_warnings);
+
         Ok(Module {
             module_id: ModuleId::alloc(),
 
@@ -328,8 +894,14 @@ impl CompileCtx {
             inferred_locals: Arc::new(inferred_locals),
             exports,
             main_local_id,
+            warnings: if lint {
+                warnings.into_iter().chain(ty_hole_notes).collect()
+            } else {
+                ty_hole_notes
+            },
 
             linked_library: None,
+            typeck_cache_key,
         })
     }
 }
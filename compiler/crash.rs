@@ -0,0 +1,158 @@
+//! Context for reporting compiler crashes
+//!
+//! Passes record the span of the user code they're processing. If the compiler panics the driver
+//! can then report which source construct triggered the panic.
+//!
+//! Valid programs using constructs the compiler doesn't support yet are reported with
+//! [`unsupported`] instead of panicking. This unwinds to the nearest [`catch_unsupported`] where
+//! it's converted to a normal diagnostic.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::panic;
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::span::{FileId, Span};
+
+use crate::reporting::new_primary_label;
+
+thread_local! {
+    static PROCESSING_SPANS: RefCell<Vec<Span>> = RefCell::new(vec![]);
+}
+
+/// Marks a span as being processed until dropped
+///
+/// This is bound to the thread it was created on.
+#[must_use = "the span is only processed until the guard is dropped"]
+pub struct ProcessingSpan {
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Marks a span as being processed by the current thread
+pub fn enter_span(span: Span) -> ProcessingSpan {
+    PROCESSING_SPANS.with(|spans| spans.borrow_mut().push(span));
+
+    ProcessingSpan {
+        _not_send: PhantomData,
+    }
+}
+
+impl Drop for ProcessingSpan {
+    fn drop(&mut self) {
+        PROCESSING_SPANS.with(|spans| {
+            spans.borrow_mut().pop();
+        });
+    }
+}
+
+/// Returns the innermost span being processed by the current thread
+pub fn processing_span() -> Option<Span> {
+    PROCESSING_SPANS
+        .try_with(|spans| spans.try_borrow().ok()?.last().copied())
+        .ok()
+        .flatten()
+}
+
+/// Valid construct the compiler doesn't support yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    span: Option<Span>,
+    description: String,
+}
+
+impl Unsupported {
+    /// Returns the span of the unsupported construct if known
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Returns a short description of what isn't supported
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Aborts compilation of a construct the compiler doesn't support yet
+///
+/// If `span` is `None` the innermost span being processed is used instead. This unwinds without
+/// invoking the panic hook; callers must be inside [`catch_unsupported`].
+pub fn unsupported(span: Option<Span>, description: impl Into<String>) -> ! {
+    panic::resume_unwind(Box::new(Unsupported {
+        span: span.or_else(processing_span),
+        description: description.into(),
+    }))
+}
+
+/// Runs a block, catching any [`unsupported`] aborts
+///
+/// Other panics continue unwinding.
+pub fn catch_unsupported<T, F>(block: F) -> Result<T, Unsupported>
+where
+    F: FnOnce() -> T,
+{
+    panic::catch_unwind(panic::AssertUnwindSafe(block)).or_else(|payload| {
+        match payload.downcast::<Unsupported>() {
+            Ok(unsupported) => Err(*unsupported),
+            Err(other) => panic::resume_unwind(other),
+        }
+    })
+}
+
+impl From<Unsupported> for Diagnostic<FileId> {
+    fn from(unsupported: Unsupported) -> Self {
+        let diagnostic = Diagnostic::error()
+            .with_message(format!("not yet supported: {}", unsupported.description));
+
+        match unsupported.span {
+            Some(span) if span.file_id().is_some() => {
+                diagnostic.with_labels(vec![new_primary_label(
+                    span,
+                    "not supported by this version of the compiler",
+                )])
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arret_syntax::span::t2s;
+
+    #[test]
+    fn nested_processing_spans() {
+        let outer_span = t2s("^^^^^^");
+        let inner_span = t2s(" ^^");
+
+        assert_eq!(None, processing_span());
+
+        {
+            let _outer = enter_span(outer_span);
+            assert_eq!(Some(outer_span), processing_span());
+
+            {
+                let _inner = enter_span(inner_span);
+                assert_eq!(Some(inner_span), processing_span());
+            }
+
+            assert_eq!(Some(outer_span), processing_span());
+        }
+
+        assert_eq!(None, processing_span());
+    }
+
+    #[test]
+    fn catching_unsupported() {
+        let processing = t2s("^^^^");
+
+        assert_eq!(Ok(5), catch_unsupported(|| 5));
+
+        let _processing_span = enter_span(processing);
+        let caught = catch_unsupported(|| unsupported(None, "frobbing")).unwrap_err();
+
+        assert_eq!(Some(processing), caught.span());
+        assert_eq!("frobbing", caught.description());
+    }
+}
@@ -0,0 +1,331 @@
+//! Dependency graphs of modules and definitions
+//!
+//! These are intended for visualising the structure of large programs.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::{FileId, Span};
+
+use crate::context::{CompileCtx, Module, ModuleId};
+use crate::hir;
+use crate::hir::destruc;
+use crate::hir::visitor::visit_exprs;
+use crate::source::SourceFile;
+
+/// Import graph of a program with an optional graph of references between definitions
+#[derive(Debug, PartialEq, Clone)]
+pub struct DepGraph {
+    module_names: BTreeMap<ModuleId, String>,
+    imports: BTreeSet<(ModuleId, ModuleId)>,
+
+    def_names: BTreeMap<hir::ExportId, DataStr>,
+    references: BTreeSet<(hir::ExportId, hir::ExportId)>,
+}
+
+/// Definition along with the module it belongs to
+struct ModuleDef<'a> {
+    module_id: ModuleId,
+    def: &'a hir::Def<hir::Inferred>,
+}
+
+fn visit_destruc_locals<F>(destruc: &destruc::Destruc<hir::Inferred>, visitor: &mut F)
+where
+    F: FnMut(hir::LocalId, &DataStr),
+{
+    match destruc {
+        destruc::Destruc::Scalar(_, scalar) => {
+            if let Some(local_id) = scalar.local_id() {
+                visitor(*local_id, scalar.source_name());
+            }
+        }
+        destruc::Destruc::List(_, list) => {
+            for fixed in list.fixed() {
+                visit_destruc_locals(fixed, visitor);
+            }
+
+            if let Some(rest) = list.rest() {
+                if let Some(local_id) = rest.local_id() {
+                    visitor(*local_id, rest.source_name());
+                }
+            }
+        }
+        destruc::Destruc::Vector(_, vector) => {
+            for member in vector.members() {
+                visit_destruc_locals(member, visitor);
+            }
+        }
+        destruc::Destruc::Map(_, map) => {
+            for (_, value) in map.entries() {
+                visit_destruc_locals(value, visitor);
+            }
+        }
+    }
+}
+
+impl DepGraph {
+    fn visit_module_tree<'a>(
+        &mut self,
+        module_name: String,
+        module: &'a Module,
+        module_defs: &mut HashMap<hir::ExportId, ModuleDef<'a>>,
+    ) {
+        if self.module_names.contains_key(&module.module_id) {
+            return;
+        }
+
+        self.module_names.insert(module.module_id, module_name);
+
+        for (import_name, import) in module.imports.iter() {
+            self.imports.insert((module.module_id, import.module_id));
+            self.visit_module_tree(import_name.to_string(), import, module_defs);
+        }
+
+        for def in module.defs.iter() {
+            visit_destruc_locals(&def.destruc, &mut |local_id, _| {
+                module_defs.insert(
+                    hir::ExportId::new(module.module_id, local_id),
+                    ModuleDef {
+                        module_id: module.module_id,
+                        def,
+                    },
+                );
+            });
+        }
+    }
+
+    /// Adds every definition reachable from `root_export_id` to the graph
+    fn visit_references(
+        &mut self,
+        root_export_id: hir::ExportId,
+        module_defs: &HashMap<hir::ExportId, ModuleDef<'_>>,
+    ) {
+        let mut pending_export_ids = vec![root_export_id];
+
+        while let Some(export_id) = pending_export_ids.pop() {
+            let ModuleDef { module_id, def } = if let Some(module_def) = module_defs.get(&export_id)
+            {
+                module_def
+            } else {
+                continue;
+            };
+
+            if self.def_names.contains_key(&export_id) {
+                continue;
+            }
+
+            visit_destruc_locals(&def.destruc, &mut |local_id, source_name| {
+                if local_id == export_id.local_id() {
+                    self.def_names.insert(export_id, source_name.clone());
+                }
+            });
+
+            visit_exprs(&def.value_expr, &mut |expr| {
+                let referenced_id = match expr.kind {
+                    hir::ExprKind::ExportRef(_, referenced_id) => referenced_id,
+                    hir::ExprKind::LocalRef(_, local_id) => {
+                        hir::ExportId::new(*module_id, local_id)
+                    }
+                    _ => {
+                        return;
+                    }
+                };
+
+                // Local references may refer to non-module variables
+                if module_defs.contains_key(&referenced_id) {
+                    self.references.insert((export_id, referenced_id));
+                    pending_export_ids.push(referenced_id);
+                }
+            });
+        }
+    }
+
+    /// Writes the graph in Graphviz DOT format
+    ///
+    /// Definitions are clustered by the module they belong to.
+    pub fn write_dot(&self, writer: &mut impl Write) -> io::Result<()> {
+        // Number our nodes sequentially so our output doesn't depend on global IDs
+        let module_indices: HashMap<ModuleId, usize> = self
+            .module_names
+            .keys()
+            .enumerate()
+            .map(|(index, module_id)| (*module_id, index))
+            .collect();
+
+        let def_indices: HashMap<hir::ExportId, usize> = self
+            .def_names
+            .keys()
+            .enumerate()
+            .map(|(index, export_id)| (*export_id, index))
+            .collect();
+
+        writeln!(writer, "digraph program {{")?;
+
+        for (module_id, module_name) in self.module_names.iter() {
+            write!(writer, "  m{} [label=", module_indices[module_id])?;
+            write_dot_str(writer, module_name)?;
+            writeln!(writer, ", shape=box];")?;
+        }
+
+        for (from_module_id, to_module_id) in self.imports.iter() {
+            writeln!(
+                writer,
+                "  m{} -> m{};",
+                module_indices[from_module_id], module_indices[to_module_id]
+            )?;
+        }
+
+        let mut module_def_names: BTreeMap<ModuleId, Vec<(hir::ExportId, &DataStr)>> =
+            BTreeMap::new();
+        for (export_id, def_name) in self.def_names.iter() {
+            module_def_names
+                .entry(export_id.module_id())
+                .or_default()
+                .push((*export_id, def_name));
+        }
+
+        for (module_id, def_names) in module_def_names.iter() {
+            writeln!(
+                writer,
+                "  subgraph cluster_m{} {{",
+                module_indices[module_id]
+            )?;
+            write!(writer, "    label=")?;
+            write_dot_str(writer, &self.module_names[module_id])?;
+            writeln!(writer, ";")?;
+
+            for (export_id, def_name) in def_names.iter() {
+                write!(writer, "    d{} [label=", def_indices[export_id])?;
+                write_dot_str(writer, def_name)?;
+                writeln!(writer, "];")?;
+            }
+
+            writeln!(writer, "  }}")?;
+        }
+
+        for (from_export_id, to_export_id) in self.references.iter() {
+            writeln!(
+                writer,
+                "  d{} -> d{};",
+                def_indices[from_export_id], def_indices[to_export_id]
+            )?;
+        }
+
+        writeln!(writer, "}}")
+    }
+}
+
+fn write_dot_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write!(writer, "\"")?;
+
+    for c in value.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            '\n' => write!(writer, "\\n")?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+
+    write!(writer, "\"")
+}
+
+/// Builds the dependency graph for a source file
+///
+/// The import graph is always included. If `include_references` is set the definitions reachable
+/// from `main!` and the references between them are also included; this requires the source file
+/// to define `main!`.
+pub fn program_dep_graph(
+    ccx: &CompileCtx,
+    source_file: &SourceFile,
+    include_references: bool,
+) -> Result<DepGraph, Vec<Diagnostic<FileId>>> {
+    let program = crate::infer_program(ccx, source_file)?;
+    let entry_module = program.entry_module();
+
+    let entry_module_name = ccx
+        .source_loader()
+        .span_to_filename(Span::new(Some(source_file.file_id()), 0, 0))
+        .unwrap_or_default();
+
+    let mut dep_graph = DepGraph {
+        module_names: BTreeMap::new(),
+        imports: BTreeSet::new(),
+        def_names: BTreeMap::new(),
+        references: BTreeSet::new(),
+    };
+
+    let mut module_defs = HashMap::new();
+    dep_graph.visit_module_tree(entry_module_name, entry_module, &mut module_defs);
+
+    if include_references {
+        let main_local_id = entry_module
+            .main_local_id
+            .ok_or_else(|| vec![crate::missing_main_diagnostic(source_file)])?;
+
+        dep_graph.visit_references(
+            hir::ExportId::new(entry_module.module_id, main_local_id),
+            &module_defs,
+        );
+    }
+
+    Ok(dep_graph)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dot_output() {
+        let root_module_id = ModuleId::alloc();
+        let lib_module_id = ModuleId::alloc();
+
+        let lia = hir::var_id::LocalIdAlloc::new();
+        let main_export_id = hir::ExportId::new(root_module_id, lia.alloc());
+        let helper_export_id = hir::ExportId::new(lib_module_id, lia.alloc());
+
+        let dep_graph = DepGraph {
+            module_names: vec![
+                (root_module_id, "root.arret".to_owned()),
+                (lib_module_id, "[my \"lib\"]".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+            imports: vec![(root_module_id, lib_module_id)].into_iter().collect(),
+            def_names: vec![
+                (main_export_id, "main!".into()),
+                (helper_export_id, "helper".into()),
+            ]
+            .into_iter()
+            .collect(),
+            references: vec![(main_export_id, helper_export_id)]
+                .into_iter()
+                .collect(),
+        };
+
+        let mut output = vec![];
+        dep_graph.write_dot(&mut output).unwrap();
+
+        assert_eq!(
+            "digraph program {\n\
+             \x20 m0 [label=\"root.arret\", shape=box];\n\
+             \x20 m1 [label=\"[my \\\"lib\\\"]\", shape=box];\n\
+             \x20 m0 -> m1;\n\
+             \x20 subgraph cluster_m0 {\n\
+             \x20   label=\"root.arret\";\n\
+             \x20   d0 [label=\"main!\"];\n\
+             \x20 }\n\
+             \x20 subgraph cluster_m1 {\n\
+             \x20   label=\"[my \\\"lib\\\"]\";\n\
+             \x20   d1 [label=\"helper\"];\n\
+             \x20 }\n\
+             \x20 d0 -> d1;\n\
+             }\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
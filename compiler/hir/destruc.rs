@@ -9,6 +9,8 @@ use crate::ty::Ty;
 pub enum Destruc<P: hir::Phase> {
     Scalar(Span, Scalar<P>),
     List(Span, List<P>),
+    Vector(Span, Vector<P>),
+    Map(Span, Map<P>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -31,6 +33,39 @@ impl<P: hir::Phase> List<P> {
     }
 }
 
+/// Destructure of a vector with an exact length
+#[derive(Debug, PartialEq, Clone)]
+pub struct Vector<P: hir::Phase> {
+    members: Vec<Destruc<P>>,
+}
+
+impl<P: hir::Phase> Vector<P> {
+    pub fn new(members: Vec<Destruc<P>>) -> Vector<P> {
+        Vector { members }
+    }
+
+    pub fn members(&self) -> &Vec<Destruc<P>> {
+        &self.members
+    }
+}
+
+/// Destructure of map values by their keyword keys
+#[derive(Debug, PartialEq, Clone)]
+pub struct Map<P: hir::Phase> {
+    /// Name of each keyword key including its leading `:` and the destruc for its value
+    entries: Vec<(DataStr, Destruc<P>)>,
+}
+
+impl<P: hir::Phase> Map<P> {
+    pub fn new(entries: Vec<(DataStr, Destruc<P>)>) -> Map<P> {
+        Map { entries }
+    }
+
+    pub fn entries(&self) -> &Vec<(DataStr, Destruc<P>)> {
+        &self.entries
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Scalar<P: hir::Phase> {
     /// ID of the local. If this is None it's treated as a wildcard.
@@ -109,6 +144,25 @@ pub fn subst_destruc(
             Destruc::Scalar(span, subst_scalar_destruc(free_ty_polys, scalar))
         }
         Destruc::List(span, list) => Destruc::List(span, subst_list_destruc(free_ty_polys, list)),
+        Destruc::Vector(span, vector) => Destruc::Vector(
+            span,
+            Vector::new(
+                vector
+                    .members
+                    .into_iter()
+                    .map(|member| subst_destruc(free_ty_polys, member))
+                    .collect(),
+            ),
+        ),
+        Destruc::Map(span, map) => Destruc::Map(
+            span,
+            Map::new(
+                map.entries
+                    .into_iter()
+                    .map(|(key, value)| (key, subst_destruc(free_ty_polys, value)))
+                    .collect(),
+            ),
+        ),
     }
 }
 
@@ -127,5 +181,27 @@ pub fn poly_for_destruc(destruc: &Destruc<hir::Inferred>) -> ty::Ref<ty::Poly> {
     match destruc {
         Destruc::Scalar(_, scalar) => scalar.ty().clone(),
         Destruc::List(_, list) => poly_for_list_destruc(list).into(),
+        Destruc::Vector(_, vector) => {
+            Ty::Vector(vector.members().iter().map(poly_for_destruc).collect()).into()
+        }
+        Destruc::Map(_, map) => ty::Map::new(
+            Ty::Any.into(),
+            map_value_poly(
+                map.entries()
+                    .iter()
+                    .map(|(_, value)| poly_for_destruc(value)),
+            ),
+        )
+        .into(),
     }
 }
+
+/// Returns the value type of a map satisfying each destructured value type
+///
+/// Every value of a map is required to satisfy the type of every destructured value. Keys aren't
+/// tracked by map types.
+pub fn map_value_poly(value_polys: impl Iterator<Item = ty::Ref<ty::Poly>>) -> ty::Ref<ty::Poly> {
+    value_polys.fold(Ty::Any.into(), |acc, value_poly| {
+        ty::intersect::intersect_ty_refs(&acc, &value_poly).unwrap_or_else(|_| Ty::never().into())
+    })
+}
@@ -12,6 +12,7 @@ use crate::reporting::{
 };
 use crate::ty;
 use crate::ty::purity;
+use crate::ty::var_usage::Variance;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ExpectedSym {
@@ -49,28 +50,50 @@ pub enum ErrorKind {
     ExpectedMacroRuleVec(&'static str),
     ExpectedMacroRulePatternList(&'static str),
     ExpectedMacroEllipsisEscape(&'static str),
+    ExpectedSyntaxParam(&'static str),
     ExpectedCompileErrorString(&'static str),
+    ExpectedUnsafeCastReason(&'static str),
+    EmptyUnsafeCastReason,
+    ExpectedDocString(&'static str),
     ExpectedImportFilterKeyword(&'static str),
     ExpectedImportRenameMap(&'static str),
+    ExpectedImportFilePath(&'static str),
     ExpectedRecordTyConsDecl(&'static str),
     ExpectedRecordValueConsDecl(&'static str),
     ExpectedRecordFieldDecl(&'static str),
+    MisplacedRestFields,
+    NonNominalTyPred(DataStr),
+    TypeErased(DataStr, Box<str>),
+    ExpectedMatchClauseVec(&'static str),
+    ExpectedTargetPattern(&'static str),
     ExpectedSym(Box<ExpectedSym>),
     UnboundIdent(DataStr),
+    UnparameterizedSyntaxParam(DataStr),
     WrongArgCount(usize),
     WrongCondArgCount,
+    NoWhenTest(&'static str),
+    NoCondClauseBody,
+    NoThreadedValue(&'static str),
+    UnreachableCondClause(Span),
+    WrongDefArgCount,
     WrongDefLikeArgCount(&'static str),
     WrongDefRecordArgCount,
     DefOutsideBody,
     ExportOutsideModule,
+    PrivateExport(Span, DataStr),
     NonDefInsideModule,
+    TopLevelExprWithMain,
     ExportInsideRepl,
     PackageNotFound,
     ModuleNotFound(Box<path::Path>),
+    CircularImport(DataStr, Box<[(Span, DataStr)]>),
+    InlineModuleNotFound(DataStr),
+    NoModuleName,
     NoMacroRule(Box<[Span]>),
     DuplicateDef(Option<Span>, DataStr),
     MultipleZeroOrMoreMatch(Span),
-    NoVecDestruc,
+    BadVecDestruc,
+    BadMapDestruc,
     UserError(DataStr),
     ReadError(Box<path::Path>),
     SyntaxError(SyntaxError),
@@ -80,15 +103,33 @@ pub enum ErrorKind {
     NoBindingVec,
     BindingsNotVec(&'static str),
     UnevenBindingVec,
+    BadLetrecDestruc,
+    BadLetValuesDestruc,
+    NonFunLetrecValue,
+    NoMatchSubject,
+    NoMatchClauseBody,
+    UnsupportedMatchPattern(&'static str),
+    UnreachableMatchClause(Span),
     BadPolyVarDecl,
+    BadIntRange,
+    BadKeyedMap,
+    DuplicateKeyedMapKey(DataStr),
+    BadOverloadCase,
     UnsupportedLiteralType,
     VarPurityBound,
+    DisjointPolyVarBounds,
     NoParamDecl,
     NoPolyVarsDecl,
     UnsupportedImportFilter,
     MacroMultiPatternRef(Box<[Span]>),
     MacroNoPatternRef,
     MacroNoTemplateVars,
+    MacroVarWrongDepth(DataStr),
+    MacroFnOutsideModule,
+    MacroFnLocalRef,
+    MacroFnNonDatumResult,
+    MacroFnError(Box<Diagnostic<FileId>>),
+    MacroFnWithoutBackend,
     MacroBadEllipsis,
     MacroBadSetPattern,
     WrongMacroRuleVecCount(usize),
@@ -103,6 +144,15 @@ pub enum ErrorKind {
     ExpectedPolyPurityArg(Box<ExpectedPolyPurityArg>),
     UnusedPolyPurityParam(purity::PVarId),
     UnusedPolyTyParam(ty::TVarId),
+    NonTyDefParam,
+    UnguardedRecursiveTy(DataStr),
+    RecursiveTyCapturesPolyVar(DataStr, Span),
+    UnknownVariance(DataStr),
+    VarianceWithoutParam,
+    DeclaredVarianceConflict(Variance, Variance),
+    NestedTyHole,
+    SourceLocUnavailable,
+    NonUnboxableTy(ty::Ref<ty::Poly>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -133,10 +183,15 @@ impl Error {
     }
 
     pub fn with_macro_invocation_span(self, span: Span) -> Error {
-        Error {
-            loc_trace: self.loc_trace.with_macro_invocation(span),
-            ..self
-        }
+        let loc_trace = match self.kind {
+            // `(compile-error)` in a macro template is reporting a problem with the invocation
+            ErrorKind::UserError(_) if !span.contains(self.loc_trace.origin()) => {
+                LocTrace::new(span, Some(span))
+            }
+            _ => self.loc_trace.with_macro_invocation(span),
+        };
+
+        Error { loc_trace, ..self }
     }
 }
 
@@ -196,6 +251,13 @@ impl From<Error> for Diagnostic<FileId> {
                     "expected `(macro-rules ...)`",
                 )]),
 
+            ErrorKind::ExpectedMatchClauseVec(found) => Diagnostic::error()
+                .with_message(format!("expected match clause vector, found {}", found))
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `[pattern body-expr]`",
+                )]),
+
             ErrorKind::ExpectedMacroRuleVec(found) => Diagnostic::error()
                 .with_message(format!("expected macro rule vector, found {}", found))
                 .with_labels(vec![new_primary_label(
@@ -214,10 +276,32 @@ impl From<Error> for Diagnostic<FileId> {
                 .with_message(format!("expected macro symbol to escape, found {}", found))
                 .with_labels(vec![new_primary_label(origin, "expected symbol")]),
 
+            ErrorKind::ExpectedSyntaxParam(found) => Diagnostic::error()
+                .with_message(format!("expected syntax parameter, found {}", found))
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected identifier defined with `(def-syntax-param)`",
+                )]),
+
             ErrorKind::ExpectedCompileErrorString(found) => Diagnostic::error()
                 .with_message(format!("expected error message string, found {}", found))
                 .with_labels(vec![new_primary_label(origin, "expected string")]),
 
+            ErrorKind::ExpectedUnsafeCastReason(found) => Diagnostic::error()
+                .with_message(format!("expected unsafe cast reason string, found {}", found))
+                .with_labels(vec![new_primary_label(origin, "expected string")]),
+
+            ErrorKind::EmptyUnsafeCastReason => Diagnostic::error()
+                .with_message("unsafe cast reason cannot be empty")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected justification for the cast",
+                )]),
+
+            ErrorKind::ExpectedDocString(found) => Diagnostic::error()
+                .with_message(format!("expected docstring, found {}", found))
+                .with_labels(vec![new_primary_label(origin, "expected string")]),
+
             ErrorKind::ExpectedImportFilterKeyword(found) => Diagnostic::error()
                 .with_message(format!("expected import filter keyword, found {}", found))
                 .with_labels(vec![new_primary_label(
@@ -225,6 +309,14 @@ impl From<Error> for Diagnostic<FileId> {
                     "expected `:only`, `:exclude`, `:rename`, `:prefix` or `:prefixed`",
                 )]),
 
+            ErrorKind::ExpectedImportFilePath(found) => Diagnostic::error()
+                .with_message(format!("expected file path string, found {}", found))
+                .with_labels(vec![new_primary_label(origin, "expected file path string")]),
+
+            ErrorKind::ExpectedTargetPattern(found) => Diagnostic::error()
+                .with_message(format!("expected target string or `:else`, found {}", found))
+                .with_labels(vec![new_primary_label(origin, "expected target string")]),
+
             ErrorKind::ExpectedImportRenameMap(found) => Diagnostic::error()
                 .with_message(format!("expected identifier rename map, found {}", found))
                 .with_labels(vec![new_primary_label(
@@ -262,6 +354,29 @@ impl From<Error> for Diagnostic<FileId> {
                     "expected record field declaration",
                 )]),
 
+            ErrorKind::MisplacedRestFields => Diagnostic::error()
+                .with_message("`&rest-fields` must follow the last record field")
+                .with_labels(vec![new_primary_label(origin, "unexpected `&rest-fields`")]),
+
+            ErrorKind::NonNominalTyPred(ty_str) => Diagnostic::error()
+                .with_message(format!(
+                    "cannot define a type predicate for non-nominal type `{}`",
+                    ty_str
+                ))
+                .with_labels(vec![new_primary_label(origin, "expected record type")]),
+
+            ErrorKind::TypeErased(ty_str, reason) => Diagnostic::error()
+                .with_message(format!("`{}` is erased at runtime", ty_str))
+                .with_labels(vec![new_primary_label(origin, "type cannot be tested")])
+                .with_notes(vec![reason.into()]),
+
+            ErrorKind::UnparameterizedSyntaxParam(ref name) => Diagnostic::error()
+                .with_message(format!(
+                    "syntax parameter `{}` used outside of `(syntax-parameterize)`",
+                    name
+                ))
+                .with_labels(vec![new_primary_label(origin, "syntax parameter not bound")]),
+
             ErrorKind::UnboundIdent(ref ident) => {
                 let diagnostic = Diagnostic::error()
                     .with_message(format!("unable to resolve `{}`", ident))
@@ -296,6 +411,41 @@ impl From<Error> for Diagnostic<FileId> {
                     "expected `(if test-expr true-expr false-expr)`",
                 )]),
 
+            ErrorKind::NoWhenTest(name) => Diagnostic::error()
+                .with_message(format!("missing `{}` test", name))
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    format!("expected `({} test-expr body-expr ...)`", name),
+                )]),
+
+            ErrorKind::NoThreadedValue(usage) => Diagnostic::error()
+                .with_message("missing value to thread")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    format!("expected `{}`", usage),
+                )]),
+
+            ErrorKind::NoCondClauseBody => Diagnostic::error()
+                .with_message("`cond` clause has no body")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `test-expr body-expr` after this test",
+                )]),
+
+            ErrorKind::UnreachableCondClause(else_span) => Diagnostic::error()
+                .with_message("unreachable `cond` clause")
+                .with_labels(vec![
+                    new_primary_label(origin, "unreachable clause"),
+                    new_secondary_label(else_span, "`:else` clause always matches"),
+                ]),
+
+            ErrorKind::WrongDefArgCount => Diagnostic::error()
+                .with_message("wrong argument count; expected 2 or 3")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `(def name definition)` or `(def name \"docstring\" definition)`",
+                )]),
+
             ErrorKind::WrongDefLikeArgCount(name) => Diagnostic::error()
                 .with_message("wrong argument count; expected 2")
                 .with_labels(vec![new_primary_label(
@@ -350,13 +500,31 @@ impl From<Error> for Diagnostic<FileId> {
                     "not at top-level of module",
                 )]),
 
+            ErrorKind::PrivateExport(private_span, ref ident) => Diagnostic::error()
+                .with_message(format!("cannot export private `{}`", ident))
+                .with_labels(vec![
+                    new_primary_label(origin, "exported here"),
+                    new_secondary_label(private_span, "declared private here"),
+                ]),
+
             ErrorKind::NonDefInsideModule => Diagnostic::error()
                 .with_message("value at top-level of module body")
                 .with_labels(vec![new_primary_label(
                     origin,
-                    "(import), (export) or definition expected",
+                    "(import), (export), (private) or definition expected",
                 )]),
 
+            ErrorKind::TopLevelExprWithMain => Diagnostic::error()
+                .with_message("top-level expression in a program defining `main!`")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expression outside of `main!`",
+                )])
+                .with_notes(vec![
+                    "top-level expressions are evaluated by an implicit `main!` function"
+                        .to_owned(),
+                ]),
+
             ErrorKind::ExportInsideRepl => Diagnostic::error()
                 .with_message("export not supported within REPL")
                 .with_labels(vec![new_primary_label(origin, "export not supported")]),
@@ -365,6 +533,17 @@ impl From<Error> for Diagnostic<FileId> {
                 .with_message("package not found")
                 .with_labels(vec![new_primary_label(origin, "at this import")]),
 
+            ErrorKind::InlineModuleNotFound(ref name) => Diagnostic::error()
+                .with_message(format!("inline module `{}` not found", name))
+                .with_labels(vec![new_primary_label(origin, "at this import")])
+                .with_notes(vec![
+                    "inline modules can only import modules defined before them".to_owned(),
+                ]),
+
+            ErrorKind::NoModuleName => Diagnostic::error()
+                .with_message("module name missing")
+                .with_labels(vec![new_primary_label(origin, "expected module name")]),
+
             ErrorKind::ModuleNotFound(ref filename) => Diagnostic::error()
                 .with_message(format!(
                     "module not found at `{}`",
@@ -372,6 +551,25 @@ impl From<Error> for Diagnostic<FileId> {
                 ))
                 .with_labels(vec![new_primary_label(origin, "at this import")]),
 
+            ErrorKind::CircularImport(ref module_name, ref cycle_imports) => Diagnostic::error()
+                .with_message(format!("circular import of `{}`", module_name))
+                .with_labels(
+                    iter::once(new_primary_label(
+                        origin,
+                        format!("`{}` imported here", module_name),
+                    ))
+                    .chain(cycle_imports.iter().map(|(span, imported_name)| {
+                        new_secondary_label(
+                            *span,
+                            format!("`{}` imported here as part of the cycle", imported_name),
+                        )
+                    }))
+                    .collect(),
+                )
+                .with_notes(vec![
+                    "modules cannot directly or indirectly import themselves".to_owned(),
+                ]),
+
             ErrorKind::NoMacroRule(pattern_spans) => Diagnostic::error()
                 .with_message("no matching macro rule")
                 .with_labels(
@@ -389,9 +587,36 @@ impl From<Error> for Diagnostic<FileId> {
                     new_secondary_label(first_zero_or_more_span, "first zero or more match"),
                 ]),
 
-            ErrorKind::NoVecDestruc => Diagnostic::error()
+            ErrorKind::SourceLocUnavailable => Diagnostic::error()
+                .with_message("source location is unavailable")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "source location requested here",
+                )]),
+
+            ErrorKind::NonUnboxableTy(ty_ref) => Diagnostic::error()
+                .with_message(format!(
+                    "`{}` has no unboxed representation",
+                    str_for_ty_ref(&ty_ref)
+                ))
+                .with_labels(vec![new_primary_label(origin, "type used in `(unbox)`")])
+                .with_notes(vec![
+                    "only `Int`, `Float` and `Bool` can be unboxed".to_owned()
+                ]),
+
+            ErrorKind::BadVecDestruc => Diagnostic::error()
                 .with_message("vectors can only be used in a destructure in the form `[name Type]`")
-                .with_labels(vec![new_primary_label(origin, "unexpected vector")]),
+                .with_labels(vec![new_primary_label(origin, "unexpected vector")])
+                .with_notes(vec![
+                    "vectors are destructured in the form `[:vector member ...]`".to_owned()
+                ]),
+
+            ErrorKind::BadMapDestruc => Diagnostic::error()
+                .with_message("unsupported map destructuring")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `{:keys [name ...]}` or `{destruc :key}`",
+                )]),
 
             ErrorKind::UserError(ref message) => Diagnostic::error()
                 .with_message(message.as_ref())
@@ -417,7 +642,7 @@ impl From<Error> for Diagnostic<FileId> {
                 .with_message("unsupported destructuring binding")
                 .with_labels(vec![new_primary_label(
                     origin,
-                    "expected variable name, list or `[name Type]`",
+                    "expected variable name, list, vector, map or `[name Type]`",
                 )]),
 
             ErrorKind::BadRestDestruc => Diagnostic::error()
@@ -439,6 +664,77 @@ impl From<Error> for Diagnostic<FileId> {
                 .with_message("binding vector must have an even number of forms")
                 .with_labels(vec![new_primary_label(origin, "extra binding form")]),
 
+            ErrorKind::BadLetrecDestruc => Diagnostic::error()
+                .with_message("unsupported `letrec` destructuring")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected variable name or `[name Type]`",
+                )]),
+
+            ErrorKind::BadLetValuesDestruc => Diagnostic::error()
+                .with_message("unsupported `let-values` destructuring")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected a list of variable names or `[name Type]`",
+                )]),
+
+            ErrorKind::NonFunLetrecValue => Diagnostic::error()
+                .with_message("`letrec` can only bind functions")
+                .with_labels(vec![new_primary_label(origin, "expected `(fn)`")]),
+
+            ErrorKind::NoMatchSubject => Diagnostic::error()
+                .with_message("missing `match` subject")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `(match subject-expr [pattern body-expr] ...)`",
+                )]),
+
+            ErrorKind::NoMatchClauseBody => Diagnostic::error()
+                .with_message("match clause has no body")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `[pattern body-expr]`",
+                )]),
+
+            ErrorKind::UnsupportedMatchPattern(found) => Diagnostic::error()
+                .with_message(format!("unsupported {} pattern", found))
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected literal, variable name or list",
+                )]),
+
+            ErrorKind::UnreachableMatchClause(previous_span) => Diagnostic::error()
+                .with_message("unreachable match clause")
+                .with_labels(vec![
+                    new_primary_label(origin, "unreachable clause"),
+                    new_secondary_label(previous_span, "previous clause always matches"),
+                ]),
+
+            ErrorKind::BadIntRange => Diagnostic::error()
+                .with_message("bad integer range")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `(IntRange start end)` with integer `start` no greater than `end`",
+                )]),
+
+            ErrorKind::BadKeyedMap => Diagnostic::error()
+                .with_message("bad keyed map type")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `(KeyedMap :key Value ...)` optionally followed by `& Key Value`",
+                )]),
+
+            ErrorKind::BadOverloadCase => Diagnostic::error()
+                .with_message("bad overload case")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected one or more monomorphic function types",
+                )]),
+
+            ErrorKind::DuplicateKeyedMapKey(key) => Diagnostic::error()
+                .with_message(format!("duplicate key `{}` in keyed map type", key))
+                .with_labels(vec![new_primary_label(origin, "duplicate key")]),
+
             ErrorKind::BadPolyVarDecl => Diagnostic::error()
                 .with_message("bad polymorphic variable declaration")
                 .with_labels(vec![new_primary_label(
@@ -457,6 +753,13 @@ impl From<Error> for Diagnostic<FileId> {
                 .with_message("purity variables cannot be bound by other variables")
                 .with_labels(vec![new_primary_label(origin, "expected `->` or `->!`")]),
 
+            ErrorKind::DisjointPolyVarBounds => Diagnostic::error()
+                .with_message("type variable bounds have no common type")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "no type satisfies every bound",
+                )]),
+
             ErrorKind::NoParamDecl => Diagnostic::error()
                 .with_message("parameter declaration missing")
                 .with_labels(vec![new_primary_label(
@@ -498,6 +801,50 @@ impl From<Error> for Diagnostic<FileId> {
                     "subtemplate includes no variables",
                 )]),
 
+            ErrorKind::MacroVarWrongDepth(ref name) => Diagnostic::error()
+                .with_message(format!(
+                    "macro variable `{}` is used outside of its zero or more match",
+                    name
+                ))
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected to be inside a subtemplate followed by `...`",
+                )]),
+
+            ErrorKind::MacroFnOutsideModule => Diagnostic::error()
+                .with_message("macro functions can only be defined in modules")
+                .with_labels(vec![new_primary_label(origin, "macro function defined here")]),
+
+            ErrorKind::MacroFnLocalRef => Diagnostic::error()
+                .with_message("macro functions cannot reference local variables")
+                .with_labels(vec![new_primary_label(origin, "local variable referenced here")])
+                .with_notes(vec![
+                    "macro functions are evaluated before their module; only imported definitions can be referenced".to_owned(),
+                ]),
+
+            ErrorKind::MacroFnNonDatumResult => Diagnostic::error()
+                .with_message("macro function did not return a datum")
+                .with_labels(vec![new_primary_label(origin, "at this macro invocation")])
+                .with_notes(vec![
+                    "macro functions must return data that can be written as source".to_owned()
+                ]),
+
+            ErrorKind::MacroFnError(diagnostic) => {
+                let mut diagnostic = *diagnostic;
+                diagnostic
+                    .labels
+                    .push(new_secondary_label(origin, "while expanding this macro"));
+
+                diagnostic
+            }
+
+            ErrorKind::MacroFnWithoutBackend => Diagnostic::error()
+                .with_message("macro functions cannot be expanded without the compiler backend")
+                .with_labels(vec![new_primary_label(origin, "at this macro invocation")])
+                .with_notes(vec![
+                    "the compiler was built without its `backend` feature".to_owned()
+                ]),
+
             ErrorKind::MacroNoPatternRef => Diagnostic::error()
                 .with_message("subtemplate does not reference macro variables from any subpattern")
                 .with_labels(vec![new_primary_label(
@@ -620,6 +967,58 @@ impl From<Error> for Diagnostic<FileId> {
                     tvar.span(),
                     "type parameter declared here",
                 )]),
+
+            ErrorKind::NonTyDefParam => Diagnostic::error()
+                .with_message("type definitions can only be parameterised by types")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected type parameter name or `[name Bound]`",
+                )]),
+
+            ErrorKind::UnguardedRecursiveTy(ref name) => Diagnostic::error()
+                .with_message(format!(
+                    "recursive type `{}` must refer to itself inside another type",
+                    name
+                ))
+                .with_labels(vec![new_primary_label(origin, "unguarded self-reference")]),
+
+            ErrorKind::RecursiveTyCapturesPolyVar(ref name, var_span) => Diagnostic::error()
+                .with_message(format!(
+                    "recursive type cannot refer to polymorphic variable `{}` from an enclosing scope",
+                    name
+                ))
+                .with_labels(vec![
+                    new_primary_label(origin, "recursive type defined here"),
+                    new_secondary_label(var_span, "polymorphic variable declared here"),
+                ]),
+
+            ErrorKind::UnknownVariance(ref keyword) => Diagnostic::error()
+                .with_message(format!("unknown variance `{}`", keyword))
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    "expected `:covariant`, `:contravariant` or `:invariant`",
+                )]),
+
+            ErrorKind::VarianceWithoutParam => Diagnostic::error()
+                .with_message("variance must be followed by a polymorphic parameter")
+                .with_labels(vec![new_primary_label(origin, "variance declared here")]),
+
+            ErrorKind::DeclaredVarianceConflict(declared, used) => Diagnostic::error()
+                .with_message(format!(
+                    "parameter declared {} is used as {}",
+                    declared, used
+                ))
+                .with_labels(vec![new_primary_label(origin, "variance declared here")])
+                .with_notes(vec![
+                    "any parameter can be declared `:invariant`".to_owned(),
+                ]),
+
+            ErrorKind::NestedTyHole => Diagnostic::error()
+                .with_message("`_` can only be used as an entire type annotation")
+                .with_labels(vec![new_primary_label(origin, "type hole")])
+                .with_notes(vec![
+                    "replace the whole annotation with `_` to see the inferred type".to_owned(),
+                ]),
         };
 
         loc_trace.label_macro_invocation(diagnostic)
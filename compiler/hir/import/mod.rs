@@ -3,14 +3,45 @@ mod parse;
 
 use std::collections::HashMap;
 
-use arret_syntax::datum::Datum;
+use arret_syntax::datum::{DataStr, Datum};
 use arret_syntax::span::Span;
 
-use crate::hir::error::Error;
+use crate::hir::error::{Error, ErrorKind};
 use crate::hir::loader::ModuleName;
+use crate::hir::util::expect_spanned_ident;
 
 pub use filter::filter_imported_exports;
-pub use parse::{parse_import_set, ParsedFilter, ParsedImportSet};
+pub use parse::parse_import_set;
+
+/// Package name used to import inline modules defined in the same source file
+pub const INLINE_MODULE_PACKAGE_NAME: &str = "self";
+
+/// Returns the arguments of an inline `(module name ...)` definition
+pub fn try_extract_inline_module(datum: &Datum) -> Option<&[Datum]> {
+    if let Datum::List(_, vs) = datum {
+        match vs.as_ref() {
+            [Datum::Sym(_, name), module_args @ ..] if name.as_ref() == "module" => {
+                Some(module_args)
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+/// Parses the arguments of an inline module into its spanned name and body data
+pub fn parse_inline_module(
+    span: Span,
+    module_args: &[Datum],
+) -> Result<((Span, &DataStr), &[Datum]), Error> {
+    match module_args {
+        [name_datum, body_data @ ..] => {
+            Ok((expect_spanned_ident(name_datum, "module name")?, body_data))
+        }
+        [] => Err(Error::new(span, ErrorKind::NoModuleName)),
+    }
+}
 
 pub fn try_extract_import_set(datum: &Datum) -> Option<&[Datum]> {
     if let Datum::List(_, vs) = datum {
@@ -152,6 +152,19 @@ pub fn parse_import_set(import_set_datum: &Datum) -> Result<ParsedImportSet> {
             let module_name = parse_module_name(span, vs.as_ref())?;
             Ok(ParsedImportSet::Module(span, module_name))
         }
+        Datum::List(_, vs) if matches!(vs.first(), Some(Datum::Sym(_, name)) if name.as_ref() == "file") => {
+            match &vs[1..] {
+                [Datum::Str(_, file_path)] => Ok(ParsedImportSet::Module(
+                    span,
+                    ModuleName::new_file(file_path.clone()),
+                )),
+                [other] => Err(Error::new(
+                    other.span(),
+                    ErrorKind::ExpectedImportFilePath(other.description()),
+                )),
+                _ => Err(Error::new(span, ErrorKind::WrongArgCount(1))),
+            }
+        }
         Datum::List(_, vs) if vs.len() >= 2 => {
             let filter_datum = &vs[0];
 
@@ -0,0 +1,43 @@
+//! Warnings for code that's valid but likely to be a mistake
+//!
+//! These are collected while lowering a module. Unlike errors they don't prevent the module from
+//! being compiled.
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::{FileId, Span};
+
+use crate::reporting::{new_primary_label, new_secondary_label};
+
+pub fn unused_variable_warning(span: Span, name: &DataStr) -> Diagnostic<FileId> {
+    Diagnostic::warning()
+        .with_message(format!("unused variable `{}`", name))
+        .with_labels(vec![new_primary_label(span, "variable bound here")])
+        .with_notes(vec![format!(
+            "if this is intentional prefix it with an underscore: `_{}`",
+            name
+        )])
+}
+
+pub fn unused_import_warning(span: Span) -> Diagnostic<FileId> {
+    Diagnostic::warning()
+        .with_message("unused import")
+        .with_labels(vec![new_primary_label(
+            span,
+            "none of the imported bindings are referenced",
+        )])
+}
+
+pub fn shadowed_binding_warning(
+    span: Span,
+    shadowed_span: Span,
+    name: &DataStr,
+) -> Diagnostic<FileId> {
+    Diagnostic::warning()
+        .with_message(format!("`{}` shadows an existing binding", name))
+        .with_labels(vec![
+            new_primary_label(span, "new binding"),
+            new_secondary_label(shadowed_span, "shadowed binding"),
+        ])
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
-use std::path;
+use std::{fmt, fs, path, str};
 
-use arret_syntax::datum::DataStr;
+use arret_syntax::datum::{DataStr, Datum};
 use arret_syntax::span::Span;
 
 use crate::hir::error::{Error, ErrorKind, Result};
@@ -17,12 +17,14 @@ pub struct PackagePath {
 
 pub struct PackagePaths {
     paths: HashMap<Box<str>, PackagePath>,
+    target_triple: Option<Box<str>>,
 }
 
 impl PackagePaths {
     pub fn empty() -> PackagePaths {
         PackagePaths {
             paths: HashMap::new(),
+            target_triple: None,
         }
     }
 
@@ -44,6 +46,7 @@ impl PackagePaths {
         };
 
         pp.add_package("stdlib", stdlib_path);
+        pp.target_triple = target_triple.map(Into::into);
         pp
     }
 
@@ -53,16 +56,29 @@ impl PackagePaths {
         Self::with_stdlib(parent_path, target_triple)
     }
 
+    /// Returns the target triple being compiled for
+    ///
+    /// [`None`] indicates the host the compiler is running on.
+    pub fn target_triple(&self) -> Option<&str> {
+        self.target_triple.as_deref()
+    }
+
     pub fn add_package(&mut self, package_name: &str, path: PackagePath) {
         self.paths.insert(package_name.into(), path);
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+/// Package name reported for modules imported from a relative source file
+const FILE_PACKAGE_NAME: &str = "file";
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct ModuleName {
     package_name: DataStr,
     path: Vec<DataStr>,
     terminal_name: DataStr,
+
+    /// Source path relative to the importing file for modules imported with `(file "path")`
+    file_path: Option<DataStr>,
 }
 
 #[derive(Debug)]
@@ -77,16 +93,94 @@ impl ModuleName {
             package_name,
             path,
             terminal_name,
+            file_path: None,
+        }
+    }
+
+    /// Creates a name for a module loaded from a source file relative to the importing file
+    ///
+    /// The terminal name is the file's stem. This is used when the module is imported with
+    /// `(:prefixed)`.
+    pub fn new_file(file_path: DataStr) -> ModuleName {
+        let terminal_name = path::Path::new(file_path.as_ref())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into())
+            .unwrap_or_else(|| file_path.clone());
+
+        ModuleName {
+            package_name: FILE_PACKAGE_NAME.into(),
+            path: vec![],
+            terminal_name,
+            file_path: Some(file_path),
         }
     }
 
     pub fn is_rfi(&self) -> bool {
-        self.path.is_empty() && self.terminal_name.as_ref() == "rust"
+        self.file_path.is_none() && self.path.is_empty() && self.terminal_name.as_ref() == "rust"
+    }
+
+    /// Returns the relative source path if this module is imported with `(file "path")`
+    pub fn file_path(&self) -> Option<&DataStr> {
+        self.file_path.as_ref()
     }
 
     pub fn terminal_name(&self) -> &DataStr {
         &self.terminal_name
     }
+
+    pub fn package_name(&self) -> &DataStr {
+        &self.package_name
+    }
+}
+
+/// Error returned when parsing a module name with fewer than two components
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParseModuleNameError;
+
+impl str::FromStr for ModuleName {
+    type Err = ParseModuleNameError;
+
+    /// Parses a module name in the form `[package path ... name]`
+    ///
+    /// The surrounding brackets are optional.
+    fn from_str(s: &str) -> std::result::Result<ModuleName, ParseModuleNameError> {
+        let s = s.trim();
+        let s = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(s);
+
+        let mut components: Vec<DataStr> = s.split_whitespace().map(Into::into).collect();
+        if components.len() < 2 {
+            return Err(ParseModuleNameError);
+        }
+
+        let terminal_name = components.pop().unwrap();
+        let package_name = components.remove(0);
+
+        Ok(ModuleName::new(package_name, components, terminal_name))
+    }
+}
+
+impl fmt::Display for ModuleName {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file_path) = &self.file_path {
+            return write!(
+                formatter,
+                "({} {:?})",
+                FILE_PACKAGE_NAME,
+                file_path.as_ref()
+            );
+        }
+
+        write!(formatter, "[{}", self.package_name)?;
+
+        for path_component in &self.path {
+            write!(formatter, " {}", path_component)?;
+        }
+
+        write!(formatter, " {}]", self.terminal_name)
+    }
 }
 
 pub fn load_module_by_name(
@@ -135,14 +229,107 @@ pub fn load_module_by_name(
     }
 }
 
+/// Returns if a module's data contains an `(export)` form exporting `ident`
+fn data_exports_ident(data: &[Datum], ident: &str) -> bool {
+    data.iter().any(|datum| {
+        let vs = if let Datum::List(_, vs) = datum {
+            vs
+        } else {
+            return false;
+        };
+
+        match vs.split_first() {
+            Some((Datum::Sym(_, name), export_data)) if name.as_ref() == "export" => {
+                export_data.iter().any(|export_datum| match export_datum {
+                    Datum::Sym(_, name) => name.as_ref() == ident,
+                    Datum::Map(_, renames) => renames.iter().any(
+                        |(_, to_datum)| matches!(to_datum, Datum::Sym(_, name) if name.as_ref() == ident),
+                    ),
+                    _ => false,
+                })
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Finds the source modules beneath a directory
+///
+/// Each module is returned as its path components relative to the package root and its terminal
+/// name.
+fn source_modules_in_dir(
+    dir: &path::Path,
+    path_components: &[DataStr],
+    modules: &mut Vec<(Vec<DataStr>, DataStr, path::PathBuf)>,
+) {
+    let entries = if let Ok(entries) = fs::read_dir(dir) {
+        entries
+    } else {
+        return;
+    };
+
+    for entry_path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if entry_path.is_dir() {
+            if let Some(dir_name) = entry_path.file_name() {
+                let mut sub_components = path_components.to_vec();
+                sub_components.push(dir_name.to_string_lossy().into());
+
+                source_modules_in_dir(&entry_path, &sub_components, modules);
+            }
+        } else if entry_path.extension().map_or(false, |ext| ext == "arret") {
+            if let Some(file_stem) = entry_path.file_stem() {
+                modules.push((
+                    path_components.to_vec(),
+                    file_stem.to_string_lossy().into(),
+                    entry_path.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// Returns the names of source modules in the known packages that export `ident`
+///
+/// This scans the top-level `(export)` forms of every module without lowering them. It's used to
+/// suggest imports for unbound identifiers so it only runs once compilation has already failed.
+pub fn find_modules_exporting(ccx: &CompileCtx, ident: &str) -> Vec<ModuleName> {
+    let mut module_names: Vec<ModuleName> = vec![];
+
+    for (package_name, package_path) in &ccx.package_paths().paths {
+        let mut modules = vec![];
+        source_modules_in_dir(&package_path.arret_base, &[], &mut modules);
+
+        for (path, terminal_name, file_path) in modules {
+            let exports_ident = match ccx.source_loader().load_path_cached(&file_path) {
+                Ok(source_file) => {
+                    matches!(source_file.parsed(), Ok(data) if data_exports_ident(data, ident))
+                }
+                Err(_) => false,
+            };
+
+            if exports_ident {
+                module_names.push(ModuleName::new(
+                    package_name.as_ref().into(),
+                    path,
+                    terminal_name,
+                ));
+            }
+        }
+    }
+
+    module_names.sort_by_cached_key(ToString::to_string);
+    module_names
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use crate::opt_level::OptLevel;
     use crate::source::EMPTY_SPAN;
 
     fn load_stdlib_module(name: &'static str) -> Result<LoadedModule> {
-        let ccx = CompileCtx::new(PackagePaths::test_paths(None), true);
+        let ccx = CompileCtx::new(PackagePaths::test_paths(None), OptLevel::default());
         let module_name = ModuleName::new("stdlib".into(), vec![], name.into());
 
         load_module_by_name(&ccx, EMPTY_SPAN, &module_name)
@@ -173,6 +360,45 @@ mod test {
         }
     }
 
+    #[test]
+    fn parse_module_name() {
+        let expected = ModuleName::new("stdlib".into(), vec!["sub".into()], "base".into());
+
+        assert_eq!(Ok(expected.clone()), "[stdlib sub base]".parse());
+        assert_eq!(Ok(expected), "stdlib sub base".parse());
+        assert_eq!(Err(ParseModuleNameError), "[stdlib]".parse::<ModuleName>());
+    }
+
+    #[test]
+    fn file_module_name() {
+        let module_name = ModuleName::new_file("lib/util.arret".into());
+
+        assert_eq!("util", module_name.terminal_name().as_ref());
+        assert_eq!(
+            Some("lib/util.arret"),
+            module_name.file_path().map(AsRef::as_ref)
+        );
+        assert_eq!("(file \"lib/util.arret\")", module_name.to_string());
+        assert!(!module_name.is_rfi());
+    }
+
+    #[test]
+    fn find_stdlib_exports() {
+        let ccx = CompileCtx::new(PackagePaths::test_paths(None), OptLevel::default());
+
+        let module_names: Vec<String> = find_modules_exporting(&ccx, "assert-eq!")
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(vec!["[stdlib test]"], module_names);
+
+        assert!(find_modules_exporting(&ccx, "subset?")
+            .iter()
+            .any(|module_name| module_name.to_string() == "[stdlib set]"));
+
+        assert!(find_modules_exporting(&ccx, "nopenopenope").is_empty());
+    }
+
     #[test]
     fn load_stdlib_missing() {
         let err = load_stdlib_module("notamodule").unwrap_err();
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 use codespan_reporting::diagnostic::Diagnostic;
 
-use arret_syntax::datum::Datum;
+use arret_syntax::datum::{DataStr, Datum};
 use arret_syntax::span::{FileId, Span};
 
+use crate::source::SourceLoader;
 use crate::ty;
 use crate::ty::purity;
 use crate::CompileCtx;
@@ -14,17 +16,26 @@ use crate::hir::destruc;
 use crate::hir::error::{Error, ErrorKind, ExpectedSym, Result};
 use crate::hir::exports::Exports;
 use crate::hir::import;
-use crate::hir::macros::{expand_macro, lower_macro_rules};
+use crate::hir::lint;
+use crate::hir::loader::ModuleName;
+use crate::hir::macros::{expand_macro, lower_macro_rules, Macro, MacroFn};
 use crate::hir::ns::{Ident, NsDataIter, NsDatum};
 use crate::hir::prim::Prim;
 use crate::hir::records::lower_record;
-use crate::hir::scope::{Binding, Scope};
-use crate::hir::types::{lower_poly, lower_polymorphic_var_set, try_lower_purity};
-use crate::hir::util::{expect_one_arg, expect_spanned_ns_ident, try_take_rest_arg};
+use crate::hir::scope::{Binding, Scope, SyntaxParam};
+use crate::hir::threading::{expand_thread, expand_thread_as, ThreadPosition};
+use crate::hir::types::{
+    lower_newtype_def, lower_poly, lower_polymorphic_var_set, lower_ty_def, lower_ty_pred_def,
+    try_lower_purity,
+};
+use crate::hir::util::{
+    expect_arg_count, expect_ns_ident, expect_one_arg, expect_spanned_ns_ident, try_take_rest_arg,
+};
 use crate::hir::var_id::{ExportId, LocalIdAlloc};
 use crate::hir::Lowered;
 use crate::hir::{
-    App, Cond, DeclPurity, DeclTy, Def, Expr, ExprKind, FieldAccessor, Fun, Let, LocalId, Recur,
+    App, BranchKind, Cast, Cond, DeclPurity, DeclTy, Def, Expr, ExprKind, FieldAccessor, Fun, Let,
+    Letrec, LetrecBinding, LocalId, NoClauseMatch, Recur, TyHole, TyHoleKind, Unbox,
 };
 
 #[cfg(test)]
@@ -37,6 +48,12 @@ pub struct LoweredModule {
 
     pub exports: Exports,
     pub main_local_id: Option<LocalId>,
+
+    /// Lint warnings for the module
+    pub warnings: Vec<Diagnostic<FileId>>,
+
+    /// Type annotations replaced with `_`
+    pub ty_holes: Vec<TyHole>,
 }
 
 struct DeferredDef {
@@ -44,16 +61,21 @@ struct DeferredDef {
     macro_invocation_span: Option<Span>,
     destruc: destruc::Destruc<Lowered>,
     value_datum: NsDatum,
+    doc: Option<DataStr>,
+    is_const: bool,
 }
 
 struct DeferredExport {
     span: Span,
     ident: Ident,
+    exported_name: DataStr,
 }
 
 enum DeferredModulePrim {
     Def(DeferredDef),
     Exports(Vec<DeferredExport>),
+    ExportAll,
+    Privates(Vec<(Span, Ident)>),
 }
 
 impl DeferredModulePrim {
@@ -91,29 +113,75 @@ fn lower_user_compile_error(span: Span, arg_iter: NsDataIter) -> Error {
     }
 }
 
+fn lower_current_source_loc(
+    scope: &Scope<'_>,
+    span: Span,
+    arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    expect_arg_count(span, 0, arg_iter.len())?;
+
+    // Macros want to report where they were invoked, not where they were defined
+    let loc_span = scope.macro_invocation_span().unwrap_or(span);
+
+    let (filename, line_col) = scope
+        .source_loader()
+        .and_then(|source_loader| {
+            let filename = source_loader.span_to_filename(loc_span)?;
+            let line_col_range = source_loader.span_to_line_col_range(loc_span)?;
+
+            Some((filename, line_col_range.start))
+        })
+        .ok_or_else(|| Error::new(span, ErrorKind::SourceLocUnavailable))?;
+
+    // Lines and columns are 1-based for humans
+    Ok(Datum::List(
+        span,
+        Box::new([
+            Datum::Str(span, filename.into()),
+            Datum::Int(span, line_col.line as i64 + 1),
+            Datum::Int(span, line_col.column as i64 + 1),
+        ]),
+    )
+    .into())
+}
+
+fn lower_macro_fn(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    mut macro_fn_data: Vec<NsDatum>,
+) -> Result<Arc<Macro>> {
+    let imports = scope
+        .imports()
+        .ok_or_else(|| Error::new(span, ErrorKind::MacroFnOutsideModule))?;
+
+    if macro_fn_data.len() != 1 {
+        return Err(Error::new(span, ErrorKind::WrongArgCount(1)));
+    }
+
+    let fun_datum = macro_fn_data.pop().unwrap();
+
+    // Any locals allocated before this were bound outside of the macro function
+    let first_local_id = lia.alloc();
+    let fun_expr = lower_expr(lia, scope, fun_datum)?;
+
+    Ok(Macro::new_fn(MacroFn::new(
+        imports,
+        first_local_id,
+        fun_expr,
+    )?))
+}
+
 fn lower_macro(
+    lia: &LocalIdAlloc,
     scope: &mut Scope<'_>,
     self_datum: NsDatum,
     transformer_spec: NsDatum,
 ) -> Result<()> {
     let (self_span, self_ident) = expect_spanned_ns_ident(self_datum, "new macro name")?;
 
-    let macro_rules_data = if let NsDatum::List(span, vs) = transformer_spec {
-        let mut transformer_data = vs.into_vec();
-
-        let macro_type_datum = if let Some(macro_type_datum) = transformer_data.first() {
-            macro_type_datum
-        } else {
-            return Err(Error::new(span, ErrorKind::NoMacroType));
-        };
-
-        if let Some(Binding::Prim(Prim::MacroRules)) = scope.get_datum(macro_type_datum) {
-        } else {
-            return Err(Error::new(macro_type_datum.span(), ErrorKind::BadMacroType));
-        }
-
-        transformer_data.remove(0);
-        transformer_data
+    let (span, mut transformer_data) = if let NsDatum::List(span, vs) = transformer_spec {
+        (span, vs.into_vec())
     } else {
         return Err(Error::new(
             transformer_spec.span(),
@@ -121,13 +189,33 @@ fn lower_macro(
         ));
     };
 
-    let mac = lower_macro_rules(scope, &self_ident, macro_rules_data)?;
+    let macro_type_datum = if !transformer_data.is_empty() {
+        transformer_data.remove(0)
+    } else {
+        return Err(Error::new(span, ErrorKind::NoMacroType));
+    };
+
+    let mac = match scope.get_datum(&macro_type_datum) {
+        Some(Binding::Prim(Prim::MacroRules)) => {
+            lower_macro_rules(scope, &self_ident, transformer_data)?
+        }
+        Some(Binding::Prim(Prim::MacroFn)) => lower_macro_fn(lia, scope, span, transformer_data)?,
+        _ => {
+            return Err(Error::new(macro_type_datum.span(), ErrorKind::BadMacroType));
+        }
+    };
+
     scope.insert_binding(self_span, self_ident, Binding::Macro(None, mac))?;
 
     Ok(())
 }
 
-fn lower_defmacro(scope: &mut Scope<'_>, span: Span, mut arg_iter: NsDataIter) -> Result<()> {
+fn lower_defmacro(
+    lia: &LocalIdAlloc,
+    scope: &mut Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<()> {
     if arg_iter.len() != 2 {
         return Err(Error::new(
             span,
@@ -138,7 +226,7 @@ fn lower_defmacro(scope: &mut Scope<'_>, span: Span, mut arg_iter: NsDataIter) -
     let self_datum = arg_iter.next().unwrap();
     let transformer_spec = arg_iter.next().unwrap();
 
-    lower_macro(scope, self_datum, transformer_spec)
+    lower_macro(lia, scope, self_datum, transformer_spec)
 }
 
 fn lower_letmacro(
@@ -147,17 +235,68 @@ fn lower_letmacro(
     span: Span,
     arg_iter: NsDataIter,
 ) -> Result<Expr<Lowered>> {
-    lower_let_like(lia, scope, span, arg_iter, lower_macro, |expr, _| expr)
+    lower_let_like(
+        lia,
+        scope,
+        span,
+        arg_iter,
+        |scope, self_datum, transformer_spec| lower_macro(lia, scope, self_datum, transformer_spec),
+        |expr, _| expr,
+    )
 }
 
-fn lower_type(scope: &mut Scope<'_>, self_datum: NsDatum, ty_datum: NsDatum) -> Result<()> {
-    let (span, ident) = expect_spanned_ns_ident(self_datum, "new type name")?;
-    let ty = lower_poly(scope, ty_datum)?;
+fn lower_def_syntax_param(scope: &mut Scope<'_>, span: Span, arg_iter: NsDataIter) -> Result<()> {
+    let self_datum = expect_one_arg(span, arg_iter)?;
+    let (self_span, self_ident) = expect_spanned_ns_ident(self_datum, "new syntax parameter name")?;
+
+    let syntax_param = SyntaxParam::new(self_ident.name().clone());
+    scope.insert_binding(self_span, self_ident, Binding::SyntaxParam(syntax_param))?;
 
-    scope.insert_binding(span, ident, Binding::Ty(ty))?;
     Ok(())
 }
 
+fn lower_syntax_parameterize(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    lower_let_like(
+        lia,
+        scope,
+        span,
+        arg_iter,
+        |scope, param_datum, value_datum| {
+            let (param_span, param_ident) =
+                expect_spanned_ns_ident(param_datum, "syntax parameter")?;
+
+            let syntax_param = match scope.get_unparameterized(&param_ident) {
+                Some(Binding::SyntaxParam(syntax_param)) => syntax_param.clone(),
+                Some(other) => {
+                    return Err(Error::new(
+                        param_span,
+                        ErrorKind::ExpectedSyntaxParam(other.description()),
+                    ));
+                }
+                None => {
+                    return Err(Error::new(
+                        param_span,
+                        ErrorKind::UnboundIdent(param_ident.into_name()),
+                    ));
+                }
+            };
+
+            let (value_span, value_ident) =
+                expect_spanned_ns_ident(value_datum, "identifier to bind syntax parameter to")?;
+            let binding = scope.get_or_err(value_span, &value_ident)?.clone();
+
+            scope.parameterize_syntax(syntax_param, binding);
+            Ok(())
+        },
+        |expr, _| expr,
+    )
+}
+
 fn lower_deftype(scope: &mut Scope<'_>, span: Span, mut arg_iter: NsDataIter) -> Result<()> {
     if arg_iter.len() != 2 {
         return Err(Error::new(span, ErrorKind::WrongDefLikeArgCount("deftype")));
@@ -166,7 +305,7 @@ fn lower_deftype(scope: &mut Scope<'_>, span: Span, mut arg_iter: NsDataIter) ->
     let self_datum = arg_iter.next().unwrap();
     let ty_datum = arg_iter.next().unwrap();
 
-    lower_type(scope, self_datum, ty_datum)
+    lower_ty_def(scope, self_datum, ty_datum)
 }
 
 fn lower_lettype(
@@ -175,7 +314,7 @@ fn lower_lettype(
     span: Span,
     arg_iter: NsDataIter,
 ) -> Result<Expr<Lowered>> {
-    lower_let_like(lia, scope, span, arg_iter, lower_type, |expr, _| expr)
+    lower_let_like(lia, scope, span, arg_iter, lower_ty_def, |expr, _| expr)
 }
 
 fn lower_defrecord(scope: &mut Scope<'_>, span: Span, mut arg_iter: NsDataIter) -> Result<()> {
@@ -198,6 +337,59 @@ fn lower_letrecord(
     lower_let_like(lia, scope, span, arg_iter, lower_record, |expr, _| expr)
 }
 
+fn lower_defnewtype(scope: &mut Scope<'_>, span: Span, mut arg_iter: NsDataIter) -> Result<()> {
+    if arg_iter.len() != 2 {
+        return Err(Error::new(
+            span,
+            ErrorKind::WrongDefLikeArgCount("defnewtype"),
+        ));
+    }
+
+    let self_datum = arg_iter.next().unwrap();
+    let repr_datum = arg_iter.next().unwrap();
+
+    lower_newtype_def(scope, self_datum, repr_datum)
+}
+
+fn lower_defpred(scope: &mut Scope<'_>, span: Span, arg_iter: NsDataIter) -> Result<()> {
+    let ty_datum = expect_one_arg(span, arg_iter)?;
+    lower_ty_pred_def(scope, ty_datum)
+}
+
+/// Lowers a reference to a newtype's wrapper or unwrapper in to a function
+///
+/// The function casts its parameter from `from_ty` to `to_ty`. As newtypes are erased during
+/// monomorphisation this has no runtime cost.
+fn lower_newtype_conv(
+    lia: &LocalIdAlloc,
+    span: Span,
+    from_ty: ty::Ref<ty::Poly>,
+    to_ty: ty::Ref<ty::Poly>,
+) -> Expr<Lowered> {
+    let local_id = lia.alloc();
+    let param = destruc::Scalar::new(Some(local_id), "value".into(), DeclTy::Known(from_ty));
+
+    let body_expr = ExprKind::Cast(Box::new(Cast {
+        span,
+        ty_ref: to_ty.clone(),
+        value_expr: ExprKind::LocalRef(span, local_id).into(),
+        reason: None,
+    }))
+    .into();
+
+    ExprKind::Fun(Box::new(Fun {
+        span,
+        pvars: purity::PVars::new(),
+        tvars: ty::TVars::new(),
+        purity: DeclPurity::Known(purity::Purity::Pure.into()),
+        params: destruc::List::new(vec![destruc::Destruc::Scalar(span, param)], None),
+        ret_ty: DeclTy::Known(to_ty),
+        ret_ty_span: None,
+        body_expr,
+    }))
+    .into()
+}
+
 /// Lowers an identifier in to a scalar destruc with the passed type
 fn lower_ident_destruc(
     lia: &LocalIdAlloc,
@@ -229,13 +421,30 @@ fn lower_scalar_destruc(
             let mut data = vs.into_vec();
 
             if data.len() != 2 {
-                return Err(Error::new(span, ErrorKind::NoVecDestruc));
+                return Err(Error::new(span, ErrorKind::BadVecDestruc));
             }
 
-            let ty = lower_poly(scope, data.pop().unwrap())?;
-
+            let ty_datum = data.pop().unwrap();
             let (span, ident) = expect_spanned_ns_ident(data.pop().unwrap(), "new variable name")?;
-            lower_ident_destruc(lia, scope, span, ident, ty.into())
+
+            match ty_datum {
+                NsDatum::Ident(hole_span, ref ty_ident) if ty_ident.is_underscore() => {
+                    let scalar = lower_ident_destruc(lia, scope, span, ident, DeclTy::Free)?;
+
+                    if let Some(local_id) = scalar.local_id() {
+                        scope.add_ty_hole(TyHole {
+                            span: hole_span,
+                            kind: TyHoleKind::Local(*local_id),
+                        });
+                    }
+
+                    Ok(scalar)
+                }
+                ty_datum => {
+                    let ty = lower_poly(scope, ty_datum)?;
+                    lower_ident_destruc(lia, scope, span, ident, ty.into())
+                }
+            }
         }
         _ => Err(Error::new(destruc_datum.span(), ErrorKind::BadRestDestruc)),
     }
@@ -260,12 +469,87 @@ fn lower_list_destruc(
     Ok(destruc::List::new(fixed_destrucs, rest_destruc))
 }
 
+/// Lowers the members of a vector destruc in the form `[:vector member ...]`
+fn lower_vector_destruc(
+    lia: &LocalIdAlloc,
+    scope: &mut Scope<'_>,
+    data_iter: NsDataIter,
+) -> Result<destruc::Vector<Lowered>> {
+    let member_destrucs = data_iter
+        .map(|v| lower_destruc(lia, scope, v))
+        .collect::<Result<Vec<destruc::Destruc<Lowered>>>>()?;
+
+    Ok(destruc::Vector::new(member_destrucs))
+}
+
+/// Lowers a map destruc in the form `{:keys [name ...] destruc :key ...}`
+///
+/// Each name in `:keys` is bound to the value of the keyword with the same name. Any other entry
+/// destructures the value of its keyword.
+fn lower_map_destruc(
+    lia: &LocalIdAlloc,
+    scope: &mut Scope<'_>,
+    entry_data: Box<[(NsDatum, NsDatum)]>,
+) -> Result<destruc::Map<Lowered>> {
+    let mut entries = vec![];
+
+    for (key_datum, value_datum) in entry_data.into_vec() {
+        let name_data = match (key_datum, value_datum) {
+            (NsDatum::Keyword(_, keyword), NsDatum::Vector(_, vs))
+                if keyword.as_ref() == ":keys" =>
+            {
+                vs
+            }
+            (destruc_datum, NsDatum::Keyword(_, key)) => {
+                let destruc = lower_destruc(lia, scope, destruc_datum)?;
+                entries.push((key, destruc));
+                continue;
+            }
+            (key_datum, _) => {
+                return Err(Error::new(key_datum.span(), ErrorKind::BadMapDestruc));
+            }
+        };
+
+        for name_datum in name_data.into_vec() {
+            let span = match name_datum {
+                NsDatum::Ident(span, _) | NsDatum::Vector(span, _) => span,
+                other => {
+                    return Err(Error::new(other.span(), ErrorKind::BadMapDestruc));
+                }
+            };
+
+            let scalar = lower_scalar_destruc(lia, scope, name_datum)?;
+            let key: DataStr = format!(":{}", scalar.source_name()).into();
+
+            entries.push((key, destruc::Destruc::Scalar(span, scalar)));
+        }
+    }
+
+    Ok(destruc::Map::new(entries))
+}
+
+/// Returns true if vector data is a vector destruc in the form `[:vector member ...]`
+fn is_vector_destruc(vs: &[NsDatum]) -> bool {
+    matches!(vs.first(), Some(NsDatum::Keyword(_, keyword)) if keyword.as_ref() == ":vector")
+}
+
 fn lower_destruc(
     lia: &LocalIdAlloc,
     scope: &mut Scope<'_>,
     destruc_datum: NsDatum,
 ) -> Result<destruc::Destruc<Lowered>> {
     match destruc_datum {
+        NsDatum::Vector(span, vs) if is_vector_destruc(&vs) => {
+            let mut data_iter = vs.into_vec().into_iter();
+            data_iter.next();
+
+            lower_vector_destruc(lia, scope, data_iter)
+                .map(|vector_destruc| destruc::Destruc::Vector(span, vector_destruc))
+        }
+
+        NsDatum::Map(span, vs) => lower_map_destruc(lia, scope, vs)
+            .map(|map_destruc| destruc::Destruc::Map(span, map_destruc)),
+
         NsDatum::Ident(span, _) | NsDatum::Vector(span, _) => {
             lower_scalar_destruc(lia, scope, destruc_datum)
                 .map(|scalar| destruc::Destruc::Scalar(span, scalar))
@@ -288,18 +572,8 @@ fn lower_destruc(
     }
 }
 
-fn lower_let_like<B, C, O>(
-    lia: &LocalIdAlloc,
-    outer_scope: &Scope<'_>,
-    span: Span,
-    mut arg_iter: NsDataIter,
-    binder: B,
-    fold_output: C,
-) -> Result<Expr<Lowered>>
-where
-    B: Fn(&mut Scope<'_>, NsDatum, NsDatum) -> Result<O>,
-    C: Fn(Expr<Lowered>, O) -> Expr<Lowered>,
-{
+/// Takes a binding vector and returns its target and value pairs
+fn take_binding_pairs(span: Span, arg_iter: &mut NsDataIter) -> Result<Vec<(NsDatum, NsDatum)>> {
     let bindings_datum = arg_iter
         .next()
         .ok_or_else(|| Error::new(span, ErrorKind::NoBindingVec))?;
@@ -313,8 +587,7 @@ where
         ));
     };
 
-    let mut scope = outer_scope.child();
-    let mut outputs = Vec::<O>::with_capacity(bindings_data.len() / 2);
+    let mut binding_pairs = Vec::with_capacity(bindings_data.len() / 2);
 
     let mut bindings_iter = bindings_data.into_iter();
     while let Some(target_datum) = bindings_iter.next() {
@@ -322,10 +595,34 @@ where
             .next()
             .ok_or_else(|| Error::new(target_datum.span(), ErrorKind::UnevenBindingVec))?;
 
-        outputs.push(binder(&mut scope, target_datum, value_datum)?);
+        binding_pairs.push((target_datum, value_datum));
     }
 
+    Ok(binding_pairs)
+}
+
+fn lower_let_like<B, C, O>(
+    lia: &LocalIdAlloc,
+    outer_scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+    binder: B,
+    fold_output: C,
+) -> Result<Expr<Lowered>>
+where
+    B: Fn(&mut Scope<'_>, NsDatum, NsDatum) -> Result<O>,
+    C: Fn(Expr<Lowered>, O) -> Expr<Lowered>,
+{
+    let binding_pairs = take_binding_pairs(span, &mut arg_iter)?;
+
+    let mut scope = outer_scope.child();
+    let outputs = binding_pairs
+        .into_iter()
+        .map(|(target_datum, value_datum)| binder(&mut scope, target_datum, value_datum))
+        .collect::<Result<Vec<O>>>()?;
+
     let body_expr = lower_body(lia, &scope, arg_iter)?;
+    scope.warn_unreferenced_locals();
 
     // This is to build nested `Let` expressions. Types/macros don't need this
     Ok(outputs.into_iter().rfold(body_expr, fold_output))
@@ -387,92 +684,940 @@ fn lower_let(
     )
 }
 
-fn lower_fun(
+fn lower_let_values(
     lia: &LocalIdAlloc,
-    outer_scope: &Scope<'_>,
+    scope: &Scope<'_>,
     span: Span,
-    mut arg_iter: NsDataIter,
+    arg_iter: NsDataIter,
 ) -> Result<Expr<Lowered>> {
-    let mut fun_scope = outer_scope.child();
+    lower_let_like(
+        lia,
+        scope,
+        span,
+        arg_iter,
+        |scope, target_datum, value_datum| {
+            if !matches!(target_datum, NsDatum::List(_, _)) {
+                return Err(Error::new(
+                    target_datum.span(),
+                    ErrorKind::BadLetValuesDestruc,
+                ));
+            }
 
-    let mut next_datum = arg_iter
-        .next()
-        .ok_or_else(|| Error::new(span, ErrorKind::NoParamDecl))?;
+            let value_expr = lower_expr(lia, scope, value_datum)?;
+            let destruc = lower_destruc(lia, scope, target_datum)?;
+            Ok((destruc, value_expr))
+        },
+        |body_expr, (destruc, value_expr)| {
+            ExprKind::Let(Box::new(Let {
+                span,
+                destruc,
+                value_expr,
+                body_expr,
+            }))
+            .into()
+        },
+    )
+}
 
-    // We can either begin with a set of type variables or a list of parameters
-    let (pvars, tvars) = if let NsDatum::Set(_, vs) = next_datum {
-        next_datum = arg_iter
-            .next()
-            .ok_or_else(|| Error::new(span, ErrorKind::NoParamDecl))?;
+fn is_fun_expr(expr: &Expr<Lowered>) -> bool {
+    match &expr.kind {
+        ExprKind::Fun(_) => true,
+        ExprKind::MacroExpand(_, inner_expr) => is_fun_expr(inner_expr),
+        _ => false,
+    }
+}
 
-        lower_polymorphic_var_set(outer_scope, &mut fun_scope, vs.into_vec().into_iter())?
-    } else {
-        (purity::PVars::new(), ty::TVars::new())
-    };
+fn lower_letrec(
+    lia: &LocalIdAlloc,
+    outer_scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let binding_pairs = take_binding_pairs(span, &mut arg_iter)?;
+    let mut scope = outer_scope.child();
 
-    // Pull out our params
-    let params = match next_datum {
-        NsDatum::List(_, vs) => lower_list_destruc(lia, &mut fun_scope, vs.into_vec().into_iter())?,
-        other => {
-            return Err(Error::new(
-                other.span(),
-                ErrorKind::ExpectedParamList(other.description()),
-            ));
+    // Bind all of our variables first so the values can reference each other
+    let mut bound_pairs = Vec::with_capacity(binding_pairs.len());
+    for (target_datum, value_datum) in binding_pairs {
+        if let NsDatum::List(span, _) = target_datum {
+            return Err(Error::new(span, ErrorKind::BadLetrecDestruc));
         }
-    };
 
-    // Determine if we have a purity and return type after the parameters, eg (param) -> RetTy
-    let mut purity = DeclPurity::Free;
-    let mut ret_ty = DeclTy::Free;
-    let mut ret_ty_span = None;
+        let destruc = lower_scalar_destruc(lia, &mut scope, target_datum)?;
+        bound_pairs.push((destruc, value_datum));
+    }
 
-    if arg_iter.len() >= 2 {
-        if let Some(poly_purity) = try_lower_purity(&fun_scope, &arg_iter.as_slice()[0]) {
-            arg_iter.next();
-            purity = poly_purity.into();
+    let bindings = bound_pairs
+        .into_iter()
+        .map(|(destruc, value_datum)| {
+            let value_span = value_datum.span();
+            let value_expr = lower_expr(lia, &scope, value_datum)?;
 
-            match arg_iter.next().unwrap() {
-                NsDatum::Ident(_, ref ident) if ident.is_underscore() => {}
-                ret_datum => {
-                    ret_ty_span = Some(ret_datum.span());
-                    ret_ty = lower_poly(&fun_scope, ret_datum)?.into();
-                }
+            if !is_fun_expr(&value_expr) {
+                return Err(Error::new(value_span, ErrorKind::NonFunLetrecValue));
             }
-        }
-    }
 
-    // Extract the body
-    let body_expr = lower_body(lia, &fun_scope, arg_iter)?;
+            Ok(LetrecBinding {
+                destruc,
+                value_expr,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    Ok(ExprKind::Fun(Box::new(Fun {
+    let body_expr = lower_body(lia, &scope, arg_iter)?;
+
+    Ok(ExprKind::Letrec(Box::new(Letrec {
         span,
-        pvars,
-        tvars,
-        purity,
-        params,
-        ret_ty,
-        ret_ty_span,
+        bindings,
         body_expr,
     }))
     .into())
 }
 
-fn lower_expr_prim_apply(
-    lia: &LocalIdAlloc,
+/// Test or binding performed while matching a `(match)` pattern
+enum MatchStep {
+    /// Continues matching if the test expression is true
+    Test(Expr<Lowered>),
+
+    /// Binds the value expression to the destructure before continuing
+    Bind(destruc::Destruc<Lowered>, Expr<Lowered>),
+}
+
+struct MatchClause {
+    span: Span,
+    steps: Vec<MatchStep>,
+    body_expr: Expr<Lowered>,
+}
+
+impl MatchClause {
+    fn is_irrefutable(&self) -> bool {
+        !self
+            .steps
+            .iter()
+            .any(|step| matches!(step, MatchStep::Test(_)))
+    }
+
+    /// Builds the expression for this clause, evaluating `fail_expr` if it doesn't match
+    ///
+    /// The failure expression is duplicated for each run of tests separated by a binding. Using a
+    /// local function for the failure expression would avoid this but it would prevent the
+    /// remaining clauses from using `(recur)`.
+    fn into_expr(self, fail_expr: Expr<Lowered>) -> Expr<Lowered> {
+        let MatchClause {
+            span,
+            steps,
+            body_expr,
+        } = self;
+
+        let bool_expr = |value| -> Expr<Lowered> { Datum::Bool(span, value).into() };
+
+        // Consecutive tests are combined in to a single test. Occurrence typing flows through the
+        // combined test so later tests and bindings see the refined types of earlier tests.
+        let and_exprs = |test_exprs: Vec<Expr<Lowered>>| {
+            let mut test_iter = test_exprs.into_iter();
+            let last_test_expr = test_iter.next_back().unwrap();
+
+            test_iter.rfold(last_test_expr, |true_expr, test_expr| {
+                ExprKind::Cond(Box::new(Cond {
+                    span,
+                    test_expr,
+                    true_expr,
+                    false_expr: bool_expr(false),
+                }))
+                .into()
+            })
+        };
+
+        let mut pending_tests = vec![];
+        let mut clause_expr = body_expr;
+
+        for step in steps.into_iter().rev() {
+            match step {
+                MatchStep::Test(test_expr) => {
+                    pending_tests.insert(0, test_expr);
+                }
+                MatchStep::Bind(destruc, value_expr) => {
+                    if !pending_tests.is_empty() {
+                        clause_expr = ExprKind::Cond(Box::new(Cond {
+                            span,
+                            test_expr: and_exprs(std::mem::take(&mut pending_tests)),
+                            true_expr: clause_expr,
+                            false_expr: fail_expr.clone(),
+                        }))
+                        .into();
+                    }
+
+                    clause_expr = ExprKind::Let(Box::new(Let {
+                        span,
+                        destruc,
+                        value_expr,
+                        body_expr: clause_expr,
+                    }))
+                    .into();
+                }
+            }
+        }
+
+        if pending_tests.is_empty() {
+            clause_expr
+        } else {
+            ExprKind::Cond(Box::new(Cond {
+                span,
+                test_expr: and_exprs(pending_tests),
+                true_expr: clause_expr,
+                false_expr: fail_expr,
+            }))
+            .into()
+        }
+    }
+}
+
+fn ty_pred_app_expr(span: Span, test_ty: ty::pred::TestTy, local_id: LocalId) -> Expr<Lowered> {
+    ExprKind::App(Box::new(App {
+        span,
+        fun_expr: ExprKind::TyPred(span, test_ty).into(),
+        ty_args: (),
+        fixed_arg_exprs: vec![ExprKind::LocalRef(span, local_id).into()],
+        rest_arg_expr: None,
+    }))
+    .into()
+}
+
+fn not_expr(span: Span, test_expr: Expr<Lowered>) -> Expr<Lowered> {
+    ExprKind::Cond(Box::new(Cond {
+        span,
+        test_expr,
+        true_expr: Datum::Bool(span, false).into(),
+        false_expr: Datum::Bool(span, true).into(),
+    }))
+    .into()
+}
+
+fn lower_list_match_pattern(
+    lia: &LocalIdAlloc,
+    scope: &mut Scope<'_>,
+    subject_local_id: LocalId,
+    span: Span,
+    mut data_iter: NsDataIter,
+    steps: &mut Vec<MatchStep>,
+) -> Result<()> {
+    let rest_datum = try_take_rest_arg(&mut data_iter);
+
+    steps.push(MatchStep::Test(ty_pred_app_expr(
+        span,
+        ty::pred::TestTy::List,
+        subject_local_id,
+    )));
+
+    // Each element is matched by splitting the head from the remaining list
+    let mut list_local_id = subject_local_id;
+    for element_datum in data_iter {
+        steps.push(MatchStep::Test(not_expr(
+            span,
+            ty_pred_app_expr(span, ty::pred::TestTy::Nil, list_local_id),
+        )));
+
+        // Variables can be bound directly without a temporary
+        let (head_destruc, head_pattern) = match element_datum {
+            NsDatum::Ident(ident_span, ident) => (
+                lower_ident_destruc(lia, scope, ident_span, ident, DeclTy::Free)?,
+                None,
+            ),
+            other => {
+                let head_local_id = lia.alloc();
+                let head_destruc =
+                    destruc::Scalar::new(Some(head_local_id), "match-head".into(), DeclTy::Free);
+
+                (head_destruc, Some((head_local_id, other)))
+            }
+        };
+
+        let tail_local_id = lia.alloc();
+        let tail_destruc =
+            destruc::Scalar::new(Some(tail_local_id), "match-tail".into(), DeclTy::Free);
+
+        steps.push(MatchStep::Bind(
+            destruc::Destruc::List(
+                span,
+                destruc::List::new(
+                    vec![destruc::Destruc::Scalar(span, head_destruc)],
+                    Some(Box::new(tail_destruc)),
+                ),
+            ),
+            ExprKind::LocalRef(span, list_local_id).into(),
+        ));
+
+        if let Some((head_local_id, head_datum)) = head_pattern {
+            lower_match_pattern(lia, scope, head_local_id, head_datum, steps)?;
+        }
+
+        list_local_id = tail_local_id;
+    }
+
+    match rest_datum {
+        Some(rest_datum @ NsDatum::Ident(_, _)) => {
+            lower_match_pattern(lia, scope, list_local_id, rest_datum, steps)
+        }
+        Some(other) => Err(Error::new(other.span(), ErrorKind::BadRestDestruc)),
+        None => {
+            steps.push(MatchStep::Test(ty_pred_app_expr(
+                span,
+                ty::pred::TestTy::Nil,
+                list_local_id,
+            )));
+
+            Ok(())
+        }
+    }
+}
+
+/// Lowers a pattern matching the passed local in to a sequence of match steps
+fn lower_match_pattern(
+    lia: &LocalIdAlloc,
+    scope: &mut Scope<'_>,
+    subject_local_id: LocalId,
+    pattern_datum: NsDatum,
+    steps: &mut Vec<MatchStep>,
+) -> Result<()> {
+    let literal_datum = match pattern_datum {
+        NsDatum::Ident(span, ident) => {
+            if !ident.is_underscore() {
+                let scalar = lower_ident_destruc(lia, scope, span, ident, DeclTy::Free)?;
+
+                steps.push(MatchStep::Bind(
+                    destruc::Destruc::Scalar(span, scalar),
+                    ExprKind::LocalRef(span, subject_local_id).into(),
+                ));
+            }
+
+            return Ok(());
+        }
+        NsDatum::List(span, vs) => {
+            let mut data_iter = vs.into_vec().into_iter();
+
+            if let Some(Binding::Prim(Prim::Quote)) = data_iter
+                .as_slice()
+                .first()
+                .and_then(|head_datum| scope.get_datum(head_datum))
+            {
+                data_iter.next();
+                expect_one_arg(span, data_iter)?.into_syntax_datum()
+            } else {
+                return lower_list_match_pattern(
+                    lia,
+                    scope,
+                    subject_local_id,
+                    span,
+                    data_iter,
+                    steps,
+                );
+            }
+        }
+        NsDatum::Vector(_, _) | NsDatum::Set(_, _) | NsDatum::Map(_, _) => {
+            return Err(Error::new(
+                pattern_datum.span(),
+                ErrorKind::UnsupportedMatchPattern(pattern_datum.description()),
+            ));
+        }
+        other => other.into_syntax_datum(),
+    };
+
+    let span = literal_datum.span();
+    steps.push(MatchStep::Test(
+        ExprKind::App(Box::new(App {
+            span,
+            fun_expr: ExprKind::EqPred(span).into(),
+            ty_args: (),
+            fixed_arg_exprs: vec![
+                ExprKind::LocalRef(span, subject_local_id).into(),
+                literal_datum.into(),
+            ],
+            rest_arg_expr: None,
+        }))
+        .into(),
+    ));
+
+    Ok(())
+}
+
+fn lower_match_clause(
+    lia: &LocalIdAlloc,
+    outer_scope: &Scope<'_>,
+    subject_local_id: LocalId,
+    clause_datum: NsDatum,
+) -> Result<MatchClause> {
+    let (span, mut clause_iter) = if let NsDatum::Vector(span, vs) = clause_datum {
+        (span, vs.into_vec().into_iter())
+    } else {
+        return Err(Error::new(
+            clause_datum.span(),
+            ErrorKind::ExpectedMatchClauseVec(clause_datum.description()),
+        ));
+    };
+
+    let pattern_datum = clause_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoMatchClauseBody))?;
+
+    let mut scope = outer_scope.child();
+    let mut steps = vec![];
+    lower_match_pattern(lia, &mut scope, subject_local_id, pattern_datum, &mut steps)?;
+
+    // Guards are evaluated after the pattern has bound its variables
+    if let Some(NsDatum::Keyword(_, keyword)) = clause_iter.as_slice().first() {
+        if keyword.as_ref() == ":when" {
+            clause_iter.next();
+
+            let guard_datum = clause_iter
+                .next()
+                .ok_or_else(|| Error::new(span, ErrorKind::NoMatchClauseBody))?;
+
+            steps.push(MatchStep::Test(lower_expr(lia, &scope, guard_datum)?));
+        }
+    }
+
+    if clause_iter.len() == 0 {
+        return Err(Error::new(span, ErrorKind::NoMatchClauseBody));
+    }
+
+    let body_expr = lower_body(lia, &scope, clause_iter)?;
+
+    Ok(MatchClause {
+        span,
+        steps,
+        body_expr,
+    })
+}
+
+fn lower_match(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let subject_datum = arg_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoMatchSubject))?;
+
+    // Locals are matched directly so occurrence typing applies to them inside the clauses
+    let (subject_local_id, subject_value_expr) = match lower_expr(lia, scope, subject_datum)? {
+        Expr {
+            kind: ExprKind::LocalRef(_, local_id),
+            ..
+        } => (local_id, None),
+        other => (lia.alloc(), Some(other)),
+    };
+
+    let clauses = arg_iter
+        .map(|clause_datum| lower_match_clause(lia, scope, subject_local_id, clause_datum))
+        .collect::<Result<Vec<MatchClause>>>()?;
+
+    if let Some(irrefutable_index) = clauses.iter().position(MatchClause::is_irrefutable) {
+        if let Some(unreachable_clause) = clauses.get(irrefutable_index + 1) {
+            return Err(Error::new(
+                unreachable_clause.span,
+                ErrorKind::UnreachableMatchClause(clauses[irrefutable_index].span),
+            ));
+        }
+    }
+
+    // Evaluate to `()` if no clause matches
+    let no_match_expr: Expr<Lowered> = ExprKind::NoClauseMatch(Box::new(NoClauseMatch {
+        span,
+        subject_local_id,
+        kind: BranchKind::Match,
+    }))
+    .into();
+    let clauses_expr = clauses
+        .into_iter()
+        .rfold(no_match_expr, |fail_expr, clause| {
+            clause.into_expr(fail_expr)
+        });
+
+    Ok(match subject_value_expr {
+        Some(value_expr) => ExprKind::Let(Box::new(Let {
+            span,
+            destruc: destruc::Destruc::Scalar(
+                span,
+                destruc::Scalar::new(Some(subject_local_id), "match-subject".into(), DeclTy::Free),
+            ),
+            value_expr,
+            body_expr: clauses_expr,
+        }))
+        .into(),
+        None => clauses_expr,
+    })
+}
+
+/// Takes an optional purity and return type, eg `-> RetTy`, from before a function body
+fn lower_ret_decl(
+    scope: &Scope<'_>,
+    arg_iter: &mut NsDataIter,
+) -> Result<(DeclPurity, DeclTy, Option<Span>)> {
+    let mut purity = DeclPurity::Free;
+    let mut ret_ty = DeclTy::Free;
+    let mut ret_ty_span = None;
+
+    if arg_iter.len() >= 2 {
+        if let Some(poly_purity) = try_lower_purity(scope, &arg_iter.as_slice()[0]) {
+            arg_iter.next();
+            purity = poly_purity.into();
+
+            match arg_iter.next().unwrap() {
+                NsDatum::Ident(hole_span, ref ident) if ident.is_underscore() => {
+                    ret_ty_span = Some(hole_span);
+                    scope.add_ty_hole(TyHole {
+                        span: hole_span,
+                        kind: TyHoleKind::FunRet,
+                    });
+                }
+                ret_datum => {
+                    ret_ty_span = Some(ret_datum.span());
+                    ret_ty = lower_poly(scope, ret_datum)?.into();
+                }
+            }
+        }
+    }
+
+    Ok((purity, ret_ty, ret_ty_span))
+}
+
+fn lower_fun(
+    lia: &LocalIdAlloc,
+    outer_scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let mut fun_scope = outer_scope.child();
+
+    let mut next_datum = arg_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoParamDecl))?;
+
+    // We can either begin with a set of type variables or a list of parameters
+    let (pvars, tvars) = if let NsDatum::Set(_, vs) = next_datum {
+        next_datum = arg_iter
+            .next()
+            .ok_or_else(|| Error::new(span, ErrorKind::NoParamDecl))?;
+
+        lower_polymorphic_var_set(outer_scope, &mut fun_scope, vs.into_vec().into_iter())?
+    } else {
+        (purity::PVars::new(), ty::TVars::new())
+    };
+
+    // Pull out our params
+    let params = match next_datum {
+        NsDatum::List(_, vs) => lower_list_destruc(lia, &mut fun_scope, vs.into_vec().into_iter())?,
+        other => {
+            return Err(Error::new(
+                other.span(),
+                ErrorKind::ExpectedParamList(other.description()),
+            ));
+        }
+    };
+
+    let (purity, ret_ty, ret_ty_span) = lower_ret_decl(&fun_scope, &mut arg_iter)?;
+
+    // Extract the body
+    let body_expr = lower_body(lia, &fun_scope, arg_iter)?;
+    fun_scope.warn_unreferenced_locals();
+
+    Ok(ExprKind::Fun(Box::new(Fun {
+        span,
+        pvars,
+        tvars,
+        purity,
+        params,
+        ret_ty,
+        ret_ty_span,
+        body_expr,
+    }))
+    .into())
+}
+
+/// Lowers `(loop)` to the immediate application of an anonymous function
+///
+/// The bindings become the function's parameters and their values its arguments. This allows
+/// `(recur)` to restart the loop with new values for each binding.
+fn lower_loop(
+    lia: &LocalIdAlloc,
+    outer_scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let binding_pairs = take_binding_pairs(span, &mut arg_iter)?;
+    let mut loop_scope = outer_scope.child();
+
+    let mut param_destrucs = Vec::with_capacity(binding_pairs.len());
+    let mut initial_exprs = Vec::with_capacity(binding_pairs.len());
+
+    for (target_datum, value_datum) in binding_pairs {
+        // Initial values are evaluated outside of the loop like normal function arguments
+        initial_exprs.push(lower_expr(lia, outer_scope, value_datum)?);
+        param_destrucs.push(lower_destruc(lia, &mut loop_scope, target_datum)?);
+    }
+
+    let (purity, ret_ty, ret_ty_span) = lower_ret_decl(&loop_scope, &mut arg_iter)?;
+    let body_expr = lower_body(lia, &loop_scope, arg_iter)?;
+    loop_scope.warn_unreferenced_locals();
+
+    let fun_expr = ExprKind::Fun(Box::new(Fun {
+        span,
+        pvars: purity::PVars::new(),
+        tvars: ty::TVars::new(),
+        purity,
+        params: destruc::List::new(param_destrucs, None),
+        ret_ty,
+        ret_ty_span,
+        body_expr,
+    }))
+    .into();
+
+    Ok(ExprKind::App(Box::new(App {
+        span,
+        fun_expr,
+        ty_args: (),
+        fixed_arg_exprs: initial_exprs,
+        rest_arg_expr: None,
+    }))
+    .into())
+}
+
+fn lower_when(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    prim_name: &'static str,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let test_datum = arg_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoWhenTest(prim_name)))?;
+
+    let test_expr = lower_expr(lia, scope, test_datum)?;
+    let body_expr = lower_body(lia, scope, arg_iter)?;
+    let unit_expr: Expr<Lowered> = Datum::List(span, Box::new([])).into();
+
+    let (true_expr, false_expr) = if prim_name == "unless" {
+        (unit_expr, body_expr)
+    } else {
+        (body_expr, unit_expr)
+    };
+
+    Ok(ExprKind::Cond(Box::new(Cond {
+        span,
+        test_expr,
+        true_expr,
+        false_expr,
+    }))
+    .into())
+}
+
+/// Returns the local tested by a type or equality predicate
+fn tested_local_id(test_expr: &Expr<Lowered>) -> Option<LocalId> {
+    let app = match &test_expr.kind {
+        ExprKind::App(app) => app,
+        ExprKind::MacroExpand(_, inner_expr) => return tested_local_id(inner_expr),
+        _ => return None,
+    };
+
+    if !matches!(
+        app.fun_expr.kind,
+        ExprKind::TyPred(_, _) | ExprKind::EqPred(_)
+    ) {
+        return None;
+    }
+
+    app.fixed_arg_exprs
+        .iter()
+        .find_map(|arg_expr| match arg_expr.kind {
+            ExprKind::LocalRef(_, local_id) => Some(local_id),
+            _ => None,
+        })
+}
+
+/// Returns the local every `(cond)` clause tests with a predicate
+///
+/// This is used to check the clauses cover every member of the local's type.
+fn cond_subject_local_id(clauses: &[(Expr<Lowered>, Expr<Lowered>)]) -> Option<LocalId> {
+    let mut tested_iter = clauses
+        .iter()
+        .map(|(test_expr, _)| tested_local_id(test_expr));
+    let subject_local_id = tested_iter.next()??;
+
+    if tested_iter.all(|local_id| local_id == Some(subject_local_id)) {
+        Some(subject_local_id)
+    } else {
+        None
+    }
+}
+
+fn lower_cond(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let mut clauses = vec![];
+    let mut else_expr = None;
+
+    while let Some(test_datum) = arg_iter.next() {
+        let test_span = test_datum.span();
+
+        if let Some(else_span) = else_expr.as_ref().map(|(else_span, _)| *else_span) {
+            return Err(Error::new(
+                test_span,
+                ErrorKind::UnreachableCondClause(else_span),
+            ));
+        }
+
+        let body_datum = arg_iter
+            .next()
+            .ok_or_else(|| Error::new(test_span, ErrorKind::NoCondClauseBody))?;
+
+        let is_else =
+            matches!(&test_datum, NsDatum::Keyword(_, keyword) if keyword.as_ref() == ":else");
+        if is_else {
+            else_expr = Some((test_span, lower_expr(lia, scope, body_datum)?));
+        } else {
+            clauses.push((
+                lower_expr(lia, scope, test_datum)?,
+                lower_expr(lia, scope, body_datum)?,
+            ));
+        }
+    }
+
+    // Evaluate to `()` if no clause matches
+    let else_expr = match else_expr {
+        Some((_, else_expr)) => else_expr,
+        None => match cond_subject_local_id(&clauses) {
+            Some(subject_local_id) => ExprKind::NoClauseMatch(Box::new(NoClauseMatch {
+                span,
+                subject_local_id,
+                kind: BranchKind::Cond,
+            }))
+            .into(),
+            None => Datum::List(span, Box::new([])).into(),
+        },
+    };
+
+    Ok(clauses
+        .into_iter()
+        .rfold(else_expr, |false_expr, (test_expr, true_expr)| {
+            ExprKind::Cond(Box::new(Cond {
+                span,
+                test_expr,
+                true_expr,
+                false_expr,
+            }))
+            .into()
+        }))
+}
+
+/// Lowers the expansion of a primitive implemented by rewriting its arguments
+///
+/// The expansion is lowered in its own scope in the same way as a macro expansion.
+fn lower_expanded_prim<F>(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    expand: F,
+) -> Result<Expr<Lowered>>
+where
+    F: FnOnce(&mut Scope<'_>) -> Result<NsDatum>,
+{
+    let mut expansion_scope = scope.child();
+    expansion_scope.enter_macro_invocation(span);
+
+    let expanded_datum = expand(&mut expansion_scope)?;
+
+    lower_expr(lia, &expansion_scope, expanded_datum)
+        .map(|expr| ExprKind::MacroExpand(span, Box::new(expr)).into())
+        .map_err(|e| e.with_macro_invocation_span(span))
+}
+
+/// Lowers `(and)` or `(or)` to nested conds
+///
+/// Each test is only evaluated if the previous tests didn't already determine the result. As this
+/// uses ordinary conds the types of the tests are refined in the same way as a nested `(if)`.
+fn lower_and_or(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    is_and: bool,
+    arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    let mut test_exprs = arg_iter
+        .map(|test_datum| lower_expr(lia, scope, test_datum))
+        .collect::<Result<Vec<_>>>()?;
+
+    // The final test determines the result if it's reached
+    let last_expr = if let Some(last_expr) = test_exprs.pop() {
+        last_expr
+    } else {
+        return Ok(Datum::Bool(span, is_and).into());
+    };
+
+    Ok(test_exprs
+        .into_iter()
+        .rfold(last_expr, |rest_expr, test_expr| {
+            let short_circuit_expr: Expr<Lowered> = Datum::Bool(span, !is_and).into();
+
+            let (true_expr, false_expr) = if is_and {
+                (rest_expr, short_circuit_expr)
+            } else {
+                (short_circuit_expr, rest_expr)
+            };
+
+            ExprKind::Cond(Box::new(Cond {
+                span,
+                test_expr,
+                true_expr,
+                false_expr,
+            }))
+            .into()
+        }))
+}
+
+fn lower_unbox(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    expect_arg_count(span, 2, arg_iter.len())?;
+
+    let ty_datum = arg_iter.next().unwrap();
+    let ty_span = ty_datum.span();
+    let ty_ref = lower_poly(scope, ty_datum)?;
+
+    if !matches!(
+        ty_ref.try_to_fixed(),
+        Some(ty::Ty::Int) | Some(ty::Ty::Float) | Some(ty::Ty::Bool)
+    ) {
+        return Err(Error::new(ty_span, ErrorKind::NonUnboxableTy(ty_ref)));
+    }
+
+    Ok(ExprKind::Unbox(Box::new(Unbox {
+        span,
+        ty_ref,
+        value_expr: lower_expr(lia, scope, arg_iter.next().unwrap())?,
+    }))
+    .into())
+}
+
+/// Lowers `(unsafe-cast Type "reason" value-expr)`
+///
+/// The reason is required so every unchecked cast is justified and can be found by searching for
+/// `unsafe-cast`.
+fn lower_unsafe_cast(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<Expr<Lowered>> {
+    expect_arg_count(span, 3, arg_iter.len())?;
+
+    let ty_ref = lower_poly(scope, arg_iter.next().unwrap())?;
+
+    let reason = match arg_iter.next().unwrap() {
+        NsDatum::Str(reason_span, reason) => {
+            if reason.trim().is_empty() {
+                return Err(Error::new(reason_span, ErrorKind::EmptyUnsafeCastReason));
+            }
+
+            reason
+        }
+        other => {
+            return Err(Error::new(
+                other.span(),
+                ErrorKind::ExpectedUnsafeCastReason(other.description()),
+            ));
+        }
+    };
+
+    Ok(ExprKind::Cast(Box::new(Cast {
+        span,
+        ty_ref,
+        value_expr: lower_expr(lia, scope, arg_iter.next().unwrap())?,
+        reason: Some(reason),
+    }))
+    .into())
+}
+
+/// Selects the body of the first `(cond-target)` clause matching our target
+///
+/// Unselected bodies aren't lowered. This allows them to refer to definitions that only exist on
+/// other targets.
+fn select_cond_target_body(scope: &Scope<'_>, mut arg_iter: NsDataIter) -> Result<Option<NsDatum>> {
+    let mut selected_body = None;
+    let mut else_span = None;
+
+    while let Some(test_datum) = arg_iter.next() {
+        let test_span = test_datum.span();
+
+        if let Some(else_span) = else_span {
+            return Err(Error::new(
+                test_span,
+                ErrorKind::UnreachableCondClause(else_span),
+            ));
+        }
+
+        let body_datum = arg_iter
+            .next()
+            .ok_or_else(|| Error::new(test_span, ErrorKind::NoCondClauseBody))?;
+
+        let is_match = match test_datum {
+            NsDatum::Keyword(_, keyword) if keyword.as_ref() == ":else" => {
+                else_span = Some(test_span);
+                true
+            }
+            NsDatum::Str(_, pattern) => scope.target_matches(&pattern),
+            other => {
+                return Err(Error::new(
+                    test_span,
+                    ErrorKind::ExpectedTargetPattern(other.description()),
+                ));
+            }
+        };
+
+        // Keep checking the remaining clauses so errors aren't target dependent
+        if is_match && selected_body.is_none() {
+            selected_body = Some(body_datum);
+        }
+    }
+
+    Ok(selected_body)
+}
+
+fn lower_expr_prim_apply(
+    lia: &LocalIdAlloc,
     scope: &Scope<'_>,
     span: Span,
     prim: Prim,
     mut arg_iter: NsDataIter,
 ) -> Result<Expr<Lowered>> {
     match prim {
-        Prim::Def | Prim::DefMacro | Prim::DefType | Prim::ImportPlaceholder | Prim::DefRecord => {
-            Err(Error::new(span, ErrorKind::DefOutsideBody))
-        }
+        Prim::Def
+        | Prim::DefConst
+        | Prim::DefMacro
+        | Prim::DefType
+        | Prim::DefSyntaxParam
+        | Prim::ImportPlaceholder
+        | Prim::DefRecord
+        | Prim::DefNewtype
+        | Prim::DefPred => Err(Error::new(span, ErrorKind::DefOutsideBody)),
         Prim::Let => lower_let(lia, scope, span, arg_iter),
+        Prim::LetValues => lower_let_values(lia, scope, span, arg_iter),
+        Prim::Letrec => lower_letrec(lia, scope, span, arg_iter),
+        Prim::Match => lower_match(lia, scope, span, arg_iter),
         Prim::LetMacro => lower_letmacro(lia, scope, span, arg_iter),
         Prim::LetType => lower_lettype(lia, scope, span, arg_iter),
         Prim::LetRecord => lower_letrecord(lia, scope, span, arg_iter),
-        Prim::Export => Err(Error::new(span, ErrorKind::ExportOutsideModule)),
+        Prim::SyntaxParameterize => lower_syntax_parameterize(lia, scope, span, arg_iter),
+        Prim::Export | Prim::ExportAll | Prim::Private => {
+            Err(Error::new(span, ErrorKind::ExportOutsideModule))
+        }
         Prim::Quote => {
             let literal_datum = expect_one_arg(span, arg_iter)?;
             Ok(literal_datum.into_syntax_datum().into())
@@ -491,10 +1636,41 @@ fn lower_expr_prim_apply(
             }))
             .into())
         }
+        Prim::When => lower_when(lia, scope, span, "when", arg_iter),
+        Prim::Unless => lower_when(lia, scope, span, "unless", arg_iter),
+        Prim::Cond => lower_cond(lia, scope, span, arg_iter),
+        Prim::And => lower_and_or(lia, scope, span, true, arg_iter),
+        Prim::Or => lower_and_or(lia, scope, span, false, arg_iter),
+        Prim::ThreadLast => lower_expanded_prim(lia, scope, span, |_| {
+            expand_thread(span, ThreadPosition::Last, arg_iter)
+        }),
+        Prim::ThreadAs => lower_expanded_prim(lia, scope, span, |expansion_scope| {
+            expand_thread_as(expansion_scope, span, arg_iter)
+        }),
+        Prim::CondTarget => match select_cond_target_body(scope, arg_iter)? {
+            Some(body_datum) => lower_expr(lia, scope, body_datum),
+            // Evaluate to `()` if no clause matches
+            None => Ok(Datum::List(span, Box::new([])).into()),
+        },
         Prim::Do => lower_body(lia, scope, arg_iter),
+        Prim::Values => {
+            let member_exprs = arg_iter
+                .map(|arg_datum| lower_expr(lia, scope, arg_datum))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(ExprKind::Values(span, member_exprs).into())
+        }
         Prim::Recur => lower_recur(lia, scope, span, arg_iter),
+        Prim::Loop => lower_loop(lia, scope, span, arg_iter),
         Prim::CompileError => Err(lower_user_compile_error(span, arg_iter)),
-        Prim::MacroRules | Prim::All => {
+        Prim::CurrentSourceLoc => lower_current_source_loc(scope, span, arg_iter),
+        Prim::Box => {
+            let value_datum = expect_one_arg(span, arg_iter)?;
+            Ok(ExprKind::Box(span, Box::new(lower_expr(lia, scope, value_datum)?)).into())
+        }
+        Prim::Unbox => lower_unbox(lia, scope, span, arg_iter),
+        Prim::UnsafeCast => lower_unsafe_cast(lia, scope, span, arg_iter),
+        Prim::MacroRules | Prim::MacroFn | Prim::All => {
             Err(Error::new(span, ErrorKind::ExpectedValue("primitive")))
         }
     }
@@ -573,6 +1749,22 @@ fn lower_expr(lia: &LocalIdAlloc, scope: &Scope<'_>, datum: NsDatum) -> Result<E
                 }))
                 .into())
             }
+            Binding::NewtypeWrap(newtype) => Ok(lower_newtype_conv(
+                lia,
+                span,
+                newtype.repr().clone(),
+                ty::Ty::Newtype(newtype.clone()).into(),
+            )),
+            Binding::NewtypeUnwrap(newtype) => Ok(lower_newtype_conv(
+                lia,
+                span,
+                ty::Ty::Newtype(newtype.clone()).into(),
+                newtype.repr().clone(),
+            )),
+            Binding::SyntaxParam(syntax_param) => Err(Error::new(
+                span,
+                ErrorKind::UnparameterizedSyntaxParam(syntax_param.name().clone()),
+            )),
             other => Err(Error::new(
                 span,
                 ErrorKind::ExpectedValue(other.description()),
@@ -594,6 +1786,7 @@ fn lower_expr(lia: &LocalIdAlloc, scope: &Scope<'_>, datum: NsDatum) -> Result<E
                     }
                     Binding::Macro(module_id, mac) => {
                         let mut macro_scope = scope.child();
+                        macro_scope.enter_macro_invocation(span);
 
                         let expanded_datum = expand_macro(
                             &mut macro_scope,
@@ -607,6 +1800,13 @@ fn lower_expr(lia: &LocalIdAlloc, scope: &Scope<'_>, datum: NsDatum) -> Result<E
                             .map(|expr| ExprKind::MacroExpand(span, Box::new(expr)).into())
                             .map_err(|e| e.with_macro_invocation_span(span));
                     }
+                    Binding::Purity(purity::Ref::Fixed(purity::Purity::Pure)) => {
+                        // `->` is the pure function type in type position and `(->)` threading
+                        // in value position
+                        return lower_expanded_prim(lia, scope, span, |_| {
+                            expand_thread(span, ThreadPosition::First, data_iter)
+                        });
+                    }
                     _ => {}
                 }
             }
@@ -627,26 +1827,72 @@ fn lower_module_prim_apply(
 ) -> Result<Option<DeferredModulePrim>, Vec<Error>> {
     match prim {
         Prim::Export => {
-            let deferred_exports = arg_iter
-                .map(|datum| {
+            let mut deferred_exports = vec![];
+
+            for datum in arg_iter {
+                if let NsDatum::Map(_, renames) = datum {
+                    for (from_datum, to_datum) in renames.into_vec() {
+                        let (span, ident) =
+                            expect_spanned_ns_ident(from_datum, "identifier to rename from")?;
+                        let exported_name =
+                            expect_ns_ident(to_datum, "identifier to rename to")?.into_name();
+
+                        deferred_exports.push(DeferredExport {
+                            span,
+                            ident,
+                            exported_name,
+                        });
+                    }
+                } else {
                     let (span, ident) = expect_spanned_ns_ident(datum, "identifier to export")?;
-                    Ok(DeferredExport { span, ident })
-                })
-                .collect::<Result<Vec<DeferredExport>>>()?;
+                    let exported_name = ident.name().clone();
+
+                    deferred_exports.push(DeferredExport {
+                        span,
+                        ident,
+                        exported_name,
+                    });
+                }
+            }
 
             Ok(Some(DeferredModulePrim::Exports(deferred_exports)))
         }
-        Prim::Def => {
-            if arg_iter.len() != 2 {
-                return Err(vec![Error::new(
-                    span,
-                    ErrorKind::WrongDefLikeArgCount("def"),
-                )]);
+        Prim::ExportAll => {
+            if arg_iter.len() != 0 {
+                return Err(vec![Error::new(span, ErrorKind::WrongArgCount(0))]);
+            }
+
+            Ok(Some(DeferredModulePrim::ExportAll))
+        }
+        Prim::Private => {
+            let deferred_privates = arg_iter
+                .map(|datum| expect_spanned_ns_ident(datum, "identifier to make private"))
+                .collect::<Result<Vec<(Span, Ident)>>>()?;
+
+            Ok(Some(DeferredModulePrim::Privates(deferred_privates)))
+        }
+        Prim::Def | Prim::DefConst => {
+            if arg_iter.len() != 2 && arg_iter.len() != 3 {
+                return Err(vec![Error::new(span, ErrorKind::WrongDefArgCount)]);
             }
 
             let destruc_datum = arg_iter.next().unwrap();
             let destruc = lower_destruc(lia, scope, destruc_datum)?;
 
+            let doc = if arg_iter.len() == 2 {
+                match arg_iter.next().unwrap() {
+                    NsDatum::Str(_, doc) => Some(doc),
+                    other => {
+                        return Err(vec![Error::new(
+                            other.span(),
+                            ErrorKind::ExpectedDocString(other.description()),
+                        )]);
+                    }
+                }
+            } else {
+                None
+            };
+
             let value_datum = arg_iter.next().unwrap();
 
             let deferred_def = DeferredDef {
@@ -654,14 +1900,23 @@ fn lower_module_prim_apply(
                 macro_invocation_span: None,
                 destruc,
                 value_datum,
+                doc,
+                is_const: prim == Prim::DefConst,
             };
 
             Ok(Some(DeferredModulePrim::Def(deferred_def)))
         }
-        Prim::DefMacro => Ok(lower_defmacro(scope, span, arg_iter).map(|_| None)?),
+        Prim::DefMacro => Ok(lower_defmacro(lia, scope, span, arg_iter).map(|_| None)?),
         Prim::DefType => Ok(lower_deftype(scope, span, arg_iter).map(|_| None)?),
+        Prim::DefSyntaxParam => Ok(lower_def_syntax_param(scope, span, arg_iter).map(|_| None)?),
         Prim::DefRecord => Ok(lower_defrecord(scope, span, arg_iter).map(|_| None)?),
+        Prim::DefNewtype => Ok(lower_defnewtype(scope, span, arg_iter).map(|_| None)?),
+        Prim::DefPred => Ok(lower_defpred(scope, span, arg_iter).map(|_| None)?),
         Prim::CompileError => Err(vec![lower_user_compile_error(span, arg_iter)]),
+        Prim::CondTarget => match select_cond_target_body(scope, arg_iter)? {
+            Some(body_datum) => lower_module_def(lia, scope, body_datum),
+            None => Ok(None),
+        },
         _ => Err(vec![Error::new(span, ErrorKind::NonDefInsideModule)]),
     }
 }
@@ -707,11 +1962,16 @@ fn lower_module_def(
     Err(vec![Error::new(span, ErrorKind::NonDefInsideModule)])
 }
 
+/// Inserts the bindings for each import set in an `(import)`
+///
+/// The span of each import set is returned along with the idents it bound.
 fn insert_import_bindings(
     imports: &ModuleImports,
     scope: &mut Scope<'_>,
     arg_data: &[Datum],
-) -> Result<(), Vec<Error>> {
+) -> Result<Vec<(Span, Vec<Ident>)>, Vec<Error>> {
+    let mut import_sets = Vec::with_capacity(arg_data.len());
+
     for arg_datum in arg_data {
         let span = arg_datum.span();
 
@@ -720,6 +1980,14 @@ fn insert_import_bindings(
 
         let exports = import::filter_imported_exports(parsed_import, &import_module.exports)?;
 
+        import_sets.push((
+            span,
+            exports
+                .keys()
+                .map(|name| Ident::new(Scope::root_ns_id(), name.clone()))
+                .collect(),
+        ));
+
         scope.insert_bindings(
             span,
             exports.into_iter().map(|(name, binding)| {
@@ -731,15 +1999,45 @@ fn insert_import_bindings(
         )?;
     }
 
-    Ok(())
+    Ok(import_sets)
 }
 
+/// Lowers the data for a module
+///
+/// If `prelude` is specified all of its exports are implicitly available in the module. The
+/// prelude must be included in `imports`.
+///
+/// If `is_program` is set expressions are allowed at the top-level of the module. They're
+/// evaluated in order by a synthesised `main!` function. `warn_shadowing` enables warnings for
+/// local variables shadowing an existing binding.
 pub(crate) fn lower_data(
+    source_loader: &SourceLoader,
+    target_triple: Option<&str>,
     imports: &ModuleImports,
+    prelude: Option<&ModuleName>,
+    is_program: bool,
+    warn_shadowing: bool,
     data: &[Datum],
 ) -> Result<LoweredModule, Vec<Error>> {
     let lia = LocalIdAlloc::new();
-    let mut scope = Scope::root();
+    let warnings = Mutex::new(vec![]);
+    let ty_holes = Mutex::new(vec![]);
+
+    let prelude_scope = if let Some(prelude) = prelude {
+        let prelude_module = &imports[prelude];
+        Scope::new_with_prelude(prelude_module.module_id, &prelude_module.exports)
+    } else {
+        Scope::new_with_entries(std::iter::empty())
+    };
+
+    let mut scope = prelude_scope
+        .child_with_import()
+        .with_source_loader(source_loader)
+        .with_target_triple(target_triple)
+        .with_imports(imports)
+        .with_warnings(&warnings)
+        .with_warn_shadowing(warn_shadowing)
+        .with_ty_holes(&ty_holes);
 
     // Build up a list of errors to return at once
     let mut errors: Vec<Error> = vec![];
@@ -751,39 +2049,104 @@ pub(crate) fn lower_data(
     //   in the body
     // - Definitions are resolved after the module has been loaded
     let mut deferred_exports = Vec::<DeferredExport>::new();
+    let mut deferred_privates = Vec::<(Span, Ident)>::new();
+    let mut export_all = false;
     let mut deferred_defs = Vec::<DeferredDef>::new();
+    let mut deferred_exprs = Vec::<NsDatum>::new();
+    let mut import_sets = Vec::<(Span, Vec<Ident>)>::new();
 
     for input_datum in data {
+        // Inline modules are built before their parent module is lowered
+        if import::try_extract_inline_module(input_datum).is_some() {
+            continue;
+        }
+
         if let Some(arg_data) = import::try_extract_import_set(input_datum) {
-            if let Err(mut new_errors) = insert_import_bindings(imports, &mut scope, arg_data) {
-                errors.append(&mut new_errors);
+            match insert_import_bindings(imports, &mut scope, arg_data) {
+                Ok(mut new_import_sets) => {
+                    import_sets.append(&mut new_import_sets);
+                }
+                Err(mut new_errors) => {
+                    errors.append(&mut new_errors);
+                }
             }
 
             continue;
         }
 
         let ns_datum = NsDatum::from_syntax_datum(input_datum);
-        match lower_module_def(&lia, &mut scope, ns_datum) {
+        match lower_module_def(&lia, &mut scope, ns_datum.clone()) {
             Ok(Some(DeferredModulePrim::Exports(mut exports))) => {
                 deferred_exports.append(&mut exports);
             }
+            Ok(Some(DeferredModulePrim::ExportAll)) => {
+                export_all = true;
+            }
+            Ok(Some(DeferredModulePrim::Privates(mut privates))) => {
+                deferred_privates.append(&mut privates);
+            }
             Ok(Some(DeferredModulePrim::Def(deferred_def))) => {
                 deferred_defs.push(deferred_def);
             }
             Ok(None) => {}
+            Err(new_errors)
+                if is_program
+                    && new_errors
+                        .iter()
+                        .all(|err| err.kind() == &ErrorKind::NonDefInsideModule) =>
+            {
+                // Re-interpret as an expression in `main!`
+                deferred_exprs.push(ns_datum);
+            }
             Err(mut new_errors) => {
                 errors.append(&mut new_errors);
             }
         };
     }
 
-    // Process any exports
+    // Process any private declarations
+    let mut private_spans = HashMap::<Ident, Span>::with_capacity(deferred_privates.len());
+    for (span, ident) in deferred_privates {
+        if let Err(err) = scope.get_or_err(span, &ident) {
+            errors.push(err);
+        } else {
+            private_spans.insert(ident, span);
+        }
+    }
+
+    // Process any exports. These may be renamed and may refer to imported bindings.
     let mut exports = HashMap::with_capacity(deferred_exports.len());
+    let mut export_sources = HashMap::<DataStr, (Span, Ident)>::new();
     for deferred_export in deferred_exports {
-        let DeferredExport { span, ident } = deferred_export;
+        let DeferredExport {
+            span,
+            ident,
+            exported_name,
+        } = deferred_export;
+
+        if let Some(private_span) = private_spans.get(&ident) {
+            errors.push(Error::new(
+                span,
+                ErrorKind::PrivateExport(*private_span, ident.into_name()),
+            ));
+            continue;
+        }
+
+        // Exporting the same binding twice is harmless but two bindings can't share a name
+        if let Some((first_span, first_ident)) = export_sources.get(&exported_name) {
+            if first_ident != &ident {
+                errors.push(Error::new(
+                    span,
+                    ErrorKind::DuplicateDef(Some(*first_span), exported_name),
+                ));
+                continue;
+            }
+        }
+
         match scope.get_or_err(span, &ident) {
             Ok(binding) => {
-                exports.insert(ident.into_name(), binding.clone());
+                exports.insert(exported_name.clone(), binding.clone());
+                export_sources.insert(exported_name, (span, ident));
             }
             Err(err) => {
                 errors.push(err);
@@ -791,6 +2154,34 @@ pub(crate) fn lower_data(
         };
     }
 
+    if export_all {
+        let imported_idents: HashSet<&Ident> = import_sets
+            .iter()
+            .flat_map(|(_, idents)| idents.iter())
+            .collect();
+
+        // Export everything defined by this module that isn't private. Identifiers introduced by
+        // macro expansion are excluded as they can't be named outside of the macro.
+        for ident in scope.bound_idents() {
+            if ident.ns_id() != Scope::root_ns_id()
+                || imported_idents.contains(ident)
+                || private_spans.contains_key(ident)
+            {
+                continue;
+            }
+
+            // Explicit exports take precedence over our implicit ones
+            match scope.get(ident) {
+                Some(Binding::Prim(Prim::ImportPlaceholder)) | None => {}
+                Some(binding) => {
+                    exports
+                        .entry(ident.name().clone())
+                        .or_insert_with(|| binding.clone());
+                }
+            }
+        }
+    }
+
     // And now process any deferred defs
     let mut defs = Vec::with_capacity(deferred_defs.len());
     for deferred_def in deferred_defs {
@@ -806,23 +2197,104 @@ pub(crate) fn lower_data(
 
     // Try to find `main!`. If we're not the entry module this will be ignored.
     let main_ident = Ident::new(Scope::root_ns_id(), "main!".into());
-    let main_local_id = if let Some(Binding::Var(None, local_id)) = scope.get(&main_ident) {
+    let mut main_local_id = if let Some(Binding::Var(None, local_id)) = scope.get(&main_ident) {
         Some(*local_id)
     } else {
         None
     };
 
+    if let Some(first_expr_span) = deferred_exprs.first().map(NsDatum::span) {
+        if main_local_id.is_some() {
+            errors.push(Error::new(first_expr_span, ErrorKind::TopLevelExprWithMain));
+        } else {
+            match lower_top_level_exprs(&lia, &scope, first_expr_span, deferred_exprs) {
+                Ok((local_id, main_def)) => {
+                    main_local_id = Some(local_id);
+                    defs.push(main_def);
+                }
+                Err(mut new_errors) => {
+                    errors.append(&mut new_errors);
+                }
+            }
+        }
+    }
+
+    for (span, idents) in import_sets {
+        if span.file_id().is_some() && !scope.any_referenced(idents.iter()) {
+            warnings
+                .lock()
+                .unwrap()
+                .push(lint::unused_import_warning(span));
+        }
+    }
+
     if errors.is_empty() {
         Ok(LoweredModule {
             defs,
             exports,
             main_local_id,
+            warnings: std::mem::take(&mut warnings.lock().unwrap()),
+            ty_holes: std::mem::take(&mut ty_holes.lock().unwrap()),
         })
     } else {
         Err(errors)
     }
 }
 
+/// Lowers a program's top-level expressions in to a synthesised `main!` definition
+///
+/// Returns the local ID of `main!` along with its definition.
+fn lower_top_level_exprs(
+    lia: &LocalIdAlloc,
+    scope: &Scope<'_>,
+    span: Span,
+    expr_data: Vec<NsDatum>,
+) -> Result<(LocalId, Def<Lowered>), Vec<Error>> {
+    let mut errors = vec![];
+    let mut body_exprs = Vec::with_capacity(expr_data.len() + 1);
+
+    for expr_datum in expr_data {
+        match lower_expr(lia, scope, expr_datum) {
+            Ok(expr) => body_exprs.push(expr),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // `main!` must return `()`
+    body_exprs.push(Datum::List(span, Box::new([])).into());
+
+    let local_id = lia.alloc();
+    let value_expr = ExprKind::Fun(Box::new(Fun {
+        span,
+        pvars: purity::PVars::new(),
+        tvars: ty::TVars::new(),
+        purity: DeclPurity::Known(purity::Purity::Impure.into()),
+        params: destruc::List::new(vec![], None),
+        ret_ty: ty::Ty::unit().into(),
+        ret_ty_span: None,
+        body_expr: ExprKind::Do(body_exprs).into(),
+    }))
+    .into();
+
+    let main_def = Def {
+        span,
+        macro_invocation_span: None,
+        destruc: destruc::Destruc::Scalar(
+            span,
+            destruc::Scalar::new(Some(local_id), "main!".into(), DeclTy::Free),
+        ),
+        value_expr,
+        doc: None,
+        is_const: false,
+    };
+
+    Ok((local_id, main_def))
+}
+
 fn resolve_deferred_def(
     lia: &LocalIdAlloc,
     scope: &Scope<'_>,
@@ -833,13 +2305,26 @@ fn resolve_deferred_def(
         macro_invocation_span,
         destruc,
         value_datum,
+        doc,
+        is_const,
     } = deferred_def;
 
-    lower_expr(lia, scope, value_datum).map(|value_expr| Def {
+    let value_expr = if let Some(macro_invocation_span) = macro_invocation_span {
+        let mut macro_scope = scope.child();
+        macro_scope.enter_macro_invocation(macro_invocation_span);
+
+        lower_expr(lia, &macro_scope, value_datum)?
+    } else {
+        lower_expr(lia, scope, value_datum)?
+    };
+
+    Ok(Def {
         span,
         macro_invocation_span,
         destruc,
         value_expr,
+        doc,
+        is_const,
     })
 }
 
@@ -855,7 +2340,7 @@ pub(crate) fn lower_repl_datum(
     let lia = LocalIdAlloc::new();
 
     if let Some(arg_data) = import::try_extract_import_set(datum) {
-        let imports = ccx.imports_for_data(std::iter::once(datum))?;
+        let imports = ccx.imports_for_data(std::iter::once(datum), None, &HashMap::new(), &[])?;
 
         insert_import_bindings(&imports, scope, arg_data).map_err(errors_to_diagnostics)?;
 
@@ -871,7 +2356,9 @@ pub(crate) fn lower_repl_datum(
 
             Ok(LoweredReplDatum::EvaluableDef(def))
         }
-        Ok(Some(DeferredModulePrim::Exports(_))) => {
+        Ok(Some(DeferredModulePrim::Exports(_)))
+        | Ok(Some(DeferredModulePrim::ExportAll))
+        | Ok(Some(DeferredModulePrim::Privates(_))) => {
             Err(vec![
                 Error::new(datum.span(), ErrorKind::ExportInsideRepl).into()
             ])
@@ -913,6 +2400,15 @@ fn import_statement_for_module(names: &[&'static str]) -> Datum {
 
 #[cfg(test)]
 fn module_for_str(data_str: &str) -> Result<LoweredModule> {
+    module_for_file_str(None, false, data_str)
+}
+
+#[cfg(test)]
+pub fn module_for_file_str(
+    file_id: Option<FileId>,
+    is_program: bool,
+    data_str: &str,
+) -> Result<LoweredModule> {
     use std::iter;
     use std::sync::Arc;
 
@@ -920,7 +2416,6 @@ fn module_for_str(data_str: &str) -> Result<LoweredModule> {
 
     use crate::context;
     use crate::hir::exports;
-    use crate::hir::loader::ModuleName;
 
     let mut program_data = vec![];
     let mut imports: ModuleImports = HashMap::new();
@@ -944,7 +2439,7 @@ fn module_for_str(data_str: &str) -> Result<LoweredModule> {
         );
     }
 
-    let mut test_data = data_from_str(None, data_str).unwrap();
+    let mut test_data = data_from_str(file_id, data_str).unwrap();
     program_data.append(&mut test_data);
 
     imports.insert(
@@ -952,17 +2447,31 @@ fn module_for_str(data_str: &str) -> Result<LoweredModule> {
         Arc::new(context::prims_to_module(exports::tys_exports())),
     );
 
-    lower_data(&imports, &program_data).map_err(|mut errors| errors.remove(0))
+    lower_data(
+        &SourceLoader::new(),
+        None,
+        &imports,
+        None,
+        is_program,
+        true,
+        &program_data,
+    )
+    .map_err(|mut errors| errors.remove(0))
 }
 
 #[cfg(test)]
 pub fn expr_for_str(data_str: &str) -> Expr<Lowered> {
+    expr_for_file_str(None, data_str)
+}
+
+#[cfg(test)]
+pub fn expr_for_file_str(file_id: Option<FileId>, data_str: &str) -> Expr<Lowered> {
     use arret_syntax::parser::datum_from_str;
 
     let lia = LocalIdAlloc::new();
     let scope = Scope::new_with_primitives();
 
-    let test_datum = datum_from_str(None, data_str).unwrap();
+    let test_datum = datum_from_str(file_id, data_str).unwrap();
     let test_nsdatum = NsDatum::from_syntax_datum(&test_datum);
 
     lower_expr(&lia, &scope, test_nsdatum).unwrap()
@@ -1069,6 +2578,7 @@ mod test {
         let j = "(fn () -> _ 1)";
         let t = "^^^^^^^^^^^^^^";
         let u = "            ^ ";
+        let v = "          ^   ";
 
         let expected: Expr<_> = ExprKind::Fun(Box::new(Fun {
             span: t2s(t),
@@ -1077,7 +2587,7 @@ mod test {
             purity: Purity::Pure.into(),
             params: destruc::List::new(vec![], None),
             ret_ty: DeclTy::Free,
-            ret_ty_span: None,
+            ret_ty_span: Some(t2s(v)),
             body_expr: Datum::Int(t2s(u), 1).into(),
         }))
         .into();
@@ -1127,6 +2637,74 @@ mod test {
         assert_eq!(expected, expr_for_str(j));
     }
 
+    #[test]
+    fn thread_first_expr() {
+        let j = "(-> 1 (2 3) 4)";
+        let t = "^^^^^^^^^^^^^^";
+        let u = "            ^ ";
+        let v = "      ^^^^^   ";
+        let w = "       ^      ";
+        let x = "    ^         ";
+        let y = "         ^    ";
+
+        let expected: Expr<_> = ExprKind::MacroExpand(
+            t2s(t),
+            Box::new(
+                ExprKind::App(Box::new(App {
+                    span: t2s(u),
+                    fun_expr: Datum::Int(t2s(u), 4).into(),
+                    ty_args: (),
+                    fixed_arg_exprs: vec![ExprKind::App(Box::new(App {
+                        span: t2s(v),
+                        fun_expr: Datum::Int(t2s(w), 2).into(),
+                        ty_args: (),
+                        fixed_arg_exprs: vec![
+                            Datum::Int(t2s(x), 1).into(),
+                            Datum::Int(t2s(y), 3).into(),
+                        ],
+                        rest_arg_expr: None,
+                    }))
+                    .into()],
+                    rest_arg_expr: None,
+                }))
+                .into(),
+            ),
+        )
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
+    #[test]
+    fn thread_last_expr() {
+        let j = "(->> 1 (2 3))";
+        let t = "^^^^^^^^^^^^^";
+        let u = "       ^^^^^ ";
+        let v = "        ^    ";
+        let w = "          ^  ";
+        let x = "     ^       ";
+
+        let expected: Expr<_> = ExprKind::MacroExpand(
+            t2s(t),
+            Box::new(
+                ExprKind::App(Box::new(App {
+                    span: t2s(u),
+                    fun_expr: Datum::Int(t2s(v), 2).into(),
+                    ty_args: (),
+                    fixed_arg_exprs: vec![
+                        Datum::Int(t2s(w), 3).into(),
+                        Datum::Int(t2s(x), 1).into(),
+                    ],
+                    rest_arg_expr: None,
+                }))
+                .into(),
+            ),
+        )
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
     #[test]
     fn rest_expr_apply() {
         let j = "(1 2 & 3)";
@@ -1169,6 +2747,53 @@ mod test {
         assert_eq!(expected, expr_for_str(j));
     }
 
+    #[test]
+    fn loop_expr() {
+        let j = "(loop [x 1] -> Int x)";
+
+        let app = if let ExprKind::App(app) = expr_for_str(j).kind {
+            app
+        } else {
+            panic!("expected loop to be lowered to an application");
+        };
+
+        let fun = if let ExprKind::Fun(fun) = app.fun_expr.kind {
+            fun
+        } else {
+            panic!("expected loop function");
+        };
+
+        assert_eq!(1, fun.params.fixed().len());
+        assert!(fun.params.rest().is_none());
+        assert_eq!(DeclPurity::Known(Purity::Pure.into()), fun.purity);
+        assert_eq!(DeclTy::Known(Ty::Int.into()), fun.ret_ty);
+
+        assert_eq!(1, app.fixed_arg_exprs.len());
+        assert!(matches!(
+            app.fixed_arg_exprs[0].kind,
+            ExprKind::Lit(Datum::Int(_, 1))
+        ));
+    }
+
+    #[test]
+    fn values_expr() {
+        let j = "(values 1 2)";
+        let t = "^^^^^^^^^^^^";
+        let u = "        ^   ";
+        let v = "          ^ ";
+
+        let expected: Expr<_> = ExprKind::Values(
+            t2s(t),
+            vec![
+                ExprKind::Lit(Datum::Int(t2s(u), 1)).into(),
+                ExprKind::Lit(Datum::Int(t2s(v), 2)).into(),
+            ],
+        )
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
     #[test]
     fn if_expr() {
         let j = "(if true 1 2)";
@@ -1188,6 +2813,124 @@ mod test {
         assert_eq!(expected, expr_for_str(j));
     }
 
+    #[test]
+    fn when_expr() {
+        let j = "(when true 1)";
+        let t = "^^^^^^^^^^^^^";
+        let u = "      ^^^^   ";
+        let v = "           ^ ";
+
+        let expected: Expr<_> = ExprKind::Cond(Box::new(Cond {
+            span: t2s(t),
+            test_expr: ExprKind::Lit(Datum::Bool(t2s(u), true)).into(),
+            true_expr: ExprKind::Lit(Datum::Int(t2s(v), 1)).into(),
+            false_expr: Datum::List(t2s(t), Box::new([])).into(),
+        }))
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
+    #[test]
+    fn unless_expr() {
+        let j = "(unless true 1)";
+        let t = "^^^^^^^^^^^^^^^";
+        let u = "        ^^^^   ";
+        let v = "             ^ ";
+
+        let expected: Expr<_> = ExprKind::Cond(Box::new(Cond {
+            span: t2s(t),
+            test_expr: ExprKind::Lit(Datum::Bool(t2s(u), true)).into(),
+            true_expr: Datum::List(t2s(t), Box::new([])).into(),
+            false_expr: ExprKind::Lit(Datum::Int(t2s(v), 1)).into(),
+        }))
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
+    #[test]
+    fn and_expr() {
+        let j = "(and)";
+        let t = "^^^^^";
+
+        let expected: Expr<_> = Datum::Bool(t2s(t), true).into();
+        assert_eq!(expected, expr_for_str(j));
+
+        let j = "(and true 1 2)";
+        let t = "^^^^^^^^^^^^^^";
+        let u = "     ^^^^     ";
+        let v = "          ^   ";
+        let w = "            ^ ";
+
+        let expected: Expr<_> = ExprKind::Cond(Box::new(Cond {
+            span: t2s(t),
+            test_expr: ExprKind::Lit(Datum::Bool(t2s(u), true)).into(),
+            true_expr: ExprKind::Cond(Box::new(Cond {
+                span: t2s(t),
+                test_expr: ExprKind::Lit(Datum::Int(t2s(v), 1)).into(),
+                true_expr: ExprKind::Lit(Datum::Int(t2s(w), 2)).into(),
+                false_expr: Datum::Bool(t2s(t), false).into(),
+            }))
+            .into(),
+            false_expr: Datum::Bool(t2s(t), false).into(),
+        }))
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
+    #[test]
+    fn or_expr() {
+        let j = "(or)";
+        let t = "^^^^";
+
+        let expected: Expr<_> = Datum::Bool(t2s(t), false).into();
+        assert_eq!(expected, expr_for_str(j));
+
+        let j = "(or false 1)";
+        let t = "^^^^^^^^^^^^";
+        let u = "    ^^^^^   ";
+        let v = "          ^ ";
+
+        let expected: Expr<_> = ExprKind::Cond(Box::new(Cond {
+            span: t2s(t),
+            test_expr: ExprKind::Lit(Datum::Bool(t2s(u), false)).into(),
+            true_expr: Datum::Bool(t2s(t), true).into(),
+            false_expr: ExprKind::Lit(Datum::Int(t2s(v), 1)).into(),
+        }))
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
+    #[test]
+    fn cond_expr() {
+        let j = "(cond true 1 false 2 :else 3)";
+        let t = "^^^^^^^^^^^^^^^^^^^^^^^^^^^^^";
+        let u = "      ^^^^                   ";
+        let v = "           ^                 ";
+        let w = "             ^^^^^           ";
+        let x = "                   ^         ";
+        let y = "                           ^ ";
+
+        let expected: Expr<_> = ExprKind::Cond(Box::new(Cond {
+            span: t2s(t),
+            test_expr: ExprKind::Lit(Datum::Bool(t2s(u), true)).into(),
+            true_expr: ExprKind::Lit(Datum::Int(t2s(v), 1)).into(),
+            false_expr: ExprKind::Cond(Box::new(Cond {
+                span: t2s(t),
+                test_expr: ExprKind::Lit(Datum::Bool(t2s(w), false)).into(),
+                true_expr: ExprKind::Lit(Datum::Int(t2s(x), 2)).into(),
+                false_expr: ExprKind::Lit(Datum::Int(t2s(y), 3)).into(),
+            }))
+            .into(),
+        }))
+        .into();
+
+        assert_eq!(expected, expr_for_str(j));
+    }
+
     #[test]
     fn expand_trivial_macro() {
         let j = "(letmacro [one (macro-rules [() 1])] (one))";
@@ -1211,6 +2954,360 @@ mod test {
         assert_eq!(2, module.exports.len());
     }
 
+    #[test]
+    fn export_all_module_defs() {
+        let j1 = "(export-all)";
+        let j2 = "(def x 1)";
+        let j3 = "(deftype MyInt Int)";
+        let j4 = "(def internal 2)";
+        let j5 = "(private internal)";
+
+        let j = &[j1, j2, j3, j4, j5].join("");
+
+        let module = module_for_str(j).unwrap();
+
+        // Imported primitives and types shouldn't be exported
+        let mut export_names: Vec<&str> = module.exports.keys().map(|name| name.as_ref()).collect();
+        export_names.sort_unstable();
+        assert_eq!(vec!["MyInt", "x"], export_names);
+    }
+
+    #[test]
+    fn export_renamed() {
+        let j1 = "(export {x renamed-x} {def define} y)";
+        let j2 = "(def x 1)";
+        let j3 = "(def y 2)";
+
+        let j = &[j1, j2, j3].join("");
+
+        let module = module_for_str(j).unwrap();
+
+        let mut export_names: Vec<&str> = module.exports.keys().map(|name| name.as_ref()).collect();
+        export_names.sort_unstable();
+        assert_eq!(vec!["define", "renamed-x", "y"], export_names);
+
+        // Imported bindings can be re-exported
+        assert!(matches!(
+            module.exports.get("define"),
+            Some(Binding::Prim(Prim::Def))
+        ));
+    }
+
+    #[test]
+    fn export_private_def() {
+        let j = "(def x 1)(private x)(export x)";
+        assert!(module_for_str(j).is_err());
+    }
+
+    #[test]
+    fn def_docstring() {
+        let j = "(def x \"The value of x\" 1)";
+
+        let module = module_for_str(j).unwrap();
+        assert_eq!(Some("The value of x".into()), module.defs[0].doc);
+    }
+
+    #[test]
+    fn prelude_bindings() {
+        use std::sync::Arc;
+
+        use arret_syntax::parser::data_from_str;
+
+        use crate::context;
+        use crate::hir::exports;
+
+        let prelude = ModuleName::new("arret".into(), vec!["internal".into()], "primitives".into());
+
+        let mut imports: ModuleImports = HashMap::new();
+        imports.insert(
+            prelude.clone(),
+            Arc::new(context::prims_to_module(exports::prims_exports())),
+        );
+
+        let lower_with_prelude = |data_str: &str, prelude: Option<&ModuleName>| {
+            let data = data_from_str(None, data_str).unwrap();
+            lower_data(
+                &SourceLoader::new(),
+                None,
+                &imports,
+                prelude,
+                false,
+                false,
+                &data,
+            )
+        };
+
+        // `def` is only bound by the prelude; prelude bindings can be shadowed
+        let module = lower_with_prelude("(def x 1) (def if 2)", Some(&prelude)).unwrap();
+        assert_eq!(2, module.defs.len());
+
+        // `import` is still bound in the module's own scope
+        assert!(lower_with_prelude("(def import 1)", Some(&prelude)).is_err());
+
+        // Nothing is implicitly imported without a prelude
+        assert!(lower_with_prelude("(def x 1)", None).is_err());
+    }
+
+    #[test]
+    fn top_level_exprs() {
+        let j = "(def x 1) (do x) 2";
+
+        // Libraries can't contain top-level expressions
+        let err = module_for_str(j).err().unwrap();
+        assert_eq!(&ErrorKind::NonDefInsideModule, err.kind());
+
+        let program = module_for_file_str(None, true, j).unwrap();
+        let main_local_id = program.main_local_id.unwrap();
+
+        let main_def = program.defs.last().unwrap();
+        assert!(matches!(
+            main_def.destruc,
+            destruc::Destruc::Scalar(_, ref scalar) if scalar.local_id() == &Some(main_local_id)
+        ));
+
+        let main_fun = if let ExprKind::Fun(ref fun) = main_def.value_expr.kind {
+            fun
+        } else {
+            panic!("expected `main!` to be a function");
+        };
+
+        assert_eq!(DeclPurity::Known(Purity::Impure.into()), main_fun.purity);
+        assert!(matches!(main_fun.body_expr.kind, ExprKind::Do(ref exprs) if exprs.len() == 3));
+
+        // Programs can't mix top-level expressions with an explicit `main!`
+        let err = module_for_file_str(None, true, "(def main! (fn () ->! ())) 1")
+            .err()
+            .unwrap();
+        assert_eq!(&ErrorKind::TopLevelExprWithMain, err.kind());
+    }
+
+    #[test]
+    fn syntax_parameters() {
+        let j = r#"
+            (def-syntax-param it)
+            (defmacro aif (macro-rules
+              [(test then else) (let [tmp test] (syntax-parameterize [it tmp] (if tmp then else)))]))
+            (def x (aif 1 it 2))
+        "#;
+
+        let module = module_for_str(j).unwrap();
+        let value_expr = &module.defs.last().unwrap().value_expr;
+
+        let let_expr = match value_expr.kind {
+            ExprKind::MacroExpand(_, ref expanded_expr) => match expanded_expr.kind {
+                ExprKind::Let(ref let_expr) => let_expr,
+                _ => panic!("expected `(let)`"),
+            },
+            _ => panic!("expected macro expansion"),
+        };
+
+        let tmp_local_id = match let_expr.destruc {
+            destruc::Destruc::Scalar(_, ref scalar) => scalar.local_id().unwrap(),
+            _ => panic!("expected scalar destructuring"),
+        };
+
+        // `it` in the user's code refers to the macro's hygienic `tmp`
+        assert!(matches!(
+            let_expr.body_expr.kind,
+            ExprKind::Cond(ref cond)
+                if matches!(cond.true_expr.kind, ExprKind::LocalRef(_, local_id) if local_id == tmp_local_id)
+        ));
+
+        let err = module_for_str("(def-syntax-param it) (def x it)")
+            .err()
+            .unwrap();
+        assert_eq!(
+            &ErrorKind::UnparameterizedSyntaxParam("it".into()),
+            err.kind()
+        );
+
+        let err = module_for_str("(def y 1) (def x (syntax-parameterize [y y] y))")
+            .err()
+            .unwrap();
+        assert_eq!(&ErrorKind::ExpectedSyntaxParam("value"), err.kind());
+    }
+
+    #[test]
+    fn export_type_alias() {
+        let j1 = "(export JsonValue)";
+        let j2 = "(deftype JsonValue (RawU Str Float Bool))";
+
+        let j = &[j1, j2].join("");
+
+        let module = module_for_str(j).unwrap();
+        let expected_ty: ty::Ref<ty::Poly> = Ty::Union(Box::new([
+            Ty::Str.into(),
+            Ty::Float.into(),
+            Ty::Bool.into(),
+        ]))
+        .into();
+
+        if let Some(Binding::Ty(exported_ty)) = module.exports.get("JsonValue") {
+            assert_eq!(&expected_ty, exported_ty);
+        } else {
+            panic!("expected `JsonValue` to be exported as a type");
+        }
+    }
+
+    #[test]
+    fn type_constructor_defs() {
+        let j1 = "(export Tree IntPair)";
+        let j2 = "(deftype (Tree A) (RawU A (List (Tree A))))";
+        let j3 = "(deftype (Pair A) (List A A))";
+        let j4 = "(deftype IntPair (Pair Int))";
+
+        let j = &[j1, j2, j3, j4].join("");
+        let module = module_for_str(j).unwrap();
+
+        if let Some(Binding::UserTyCons(cons)) = module.exports.get("Tree") {
+            assert!(cons.is_recursive());
+            assert_eq!(1, cons.params().len());
+        } else {
+            panic!("expected `Tree` to be exported as a type constructor");
+        }
+
+        // Non-recursive constructors are substituted in to their definition
+        let expected_ty: ty::Ref<ty::Poly> =
+            ty::List::new_tuple(Box::new([Ty::Int.into(), Ty::Int.into()])).into();
+
+        if let Some(Binding::Ty(exported_ty)) = module.exports.get("IntPair") {
+            assert_eq!(&expected_ty, exported_ty);
+        } else {
+            panic!("expected `IntPair` to be exported as a type");
+        }
+
+        let err = module_for_str("(deftype Loop (RawU Int Loop))")
+            .err()
+            .unwrap();
+        assert_eq!(&ErrorKind::UnguardedRecursiveTy("Loop".into()), err.kind());
+
+        let err = module_for_str("(deftype (Purity [->p ->!]) Int)")
+            .err()
+            .unwrap();
+        assert_eq!(&ErrorKind::NonTyDefParam, err.kind());
+    }
+
+    #[test]
+    fn declared_variances() {
+        use crate::ty::var_usage::Variance;
+
+        let j1 = "(export Cell Sink Slot)";
+        let j2 = "(deftype (Cell :invariant A) (List A))";
+        let j3 = "(deftype (Sink :contravariant A) (A -> Bool))";
+        let j4 = "(defrecord (Slot :invariant A) (slot [value A]))";
+
+        let j = &[j1, j2, j3, j4].join("");
+        let module = module_for_str(j).unwrap();
+
+        if let Some(Binding::UserTyCons(cons)) = module.exports.get("Cell") {
+            assert!(!cons.is_recursive());
+            assert!(cons.has_declared_variance());
+            assert_eq!(Variance::Invariant, cons.param_variance(0));
+        } else {
+            panic!("expected `Cell` to be exported as a type constructor");
+        }
+
+        if let Some(Binding::UserTyCons(cons)) = module.exports.get("Sink") {
+            assert_eq!(Variance::Contravariant, cons.param_variance(0));
+        } else {
+            panic!("expected `Sink` to be exported as a type constructor");
+        }
+
+        if let Some(Binding::RecordTyCons(cons)) = module.exports.get("Slot") {
+            assert_eq!(Variance::Invariant, cons.poly_params()[0].variance());
+        } else {
+            panic!("expected `Slot` to be exported as a record type constructor");
+        }
+
+        let err = module_for_str("(deftype (Sink :covariant A) (A -> Bool))")
+            .err()
+            .unwrap();
+        assert_eq!(
+            &ErrorKind::DeclaredVarianceConflict(Variance::Covariant, Variance::Contravariant),
+            err.kind()
+        );
+
+        let err = module_for_str("(defrecord (Slot :contravariant A) (slot [value A]))")
+            .err()
+            .unwrap();
+        assert_eq!(
+            &ErrorKind::DeclaredVarianceConflict(Variance::Contravariant, Variance::Covariant),
+            err.kind()
+        );
+    }
+
+    #[test]
+    fn mutual_letrec() {
+        let j = "(letrec [x (fn () (y)) y (fn () (x))])";
+
+        let letrec = if let ExprKind::Letrec(letrec) = expr_for_str(j).kind {
+            letrec
+        } else {
+            panic!("expected letrec");
+        };
+
+        assert_eq!(2, letrec.bindings.len());
+        assert!(letrec
+            .bindings
+            .iter()
+            .all(|binding| binding.destruc.local_id().is_some()));
+    }
+
+    #[test]
+    fn match_literal() {
+        let j = "(match 1 [1 'one] [_ 'other])";
+
+        let hir_let = if let ExprKind::Let(hir_let) = expr_for_str(j).kind {
+            hir_let
+        } else {
+            panic!("expected match subject to be bound");
+        };
+
+        let cond = if let ExprKind::Cond(cond) = hir_let.body_expr.kind {
+            cond
+        } else {
+            panic!("expected cond");
+        };
+
+        assert!(matches!(cond.test_expr.kind, ExprKind::App(_)));
+        assert!(
+            matches!(cond.true_expr.kind, ExprKind::Lit(Datum::Sym(_, ref name)) if name.as_ref() == "one")
+        );
+        assert!(
+            matches!(cond.false_expr.kind, ExprKind::Lit(Datum::Sym(_, ref name)) if name.as_ref() == "other")
+        );
+    }
+
+    #[test]
+    fn match_fallthrough() {
+        let j = "(match 1 [1 'one])";
+
+        let hir_let = if let ExprKind::Let(hir_let) = expr_for_str(j).kind {
+            hir_let
+        } else {
+            panic!("expected match subject to be bound");
+        };
+
+        let cond = if let ExprKind::Cond(cond) = &hir_let.body_expr.kind {
+            cond
+        } else {
+            panic!("expected cond");
+        };
+
+        let no_clause_match =
+            if let ExprKind::NoClauseMatch(no_clause_match) = &cond.false_expr.kind {
+                no_clause_match
+            } else {
+                panic!("expected no clause match");
+            };
+
+        assert_eq!(BranchKind::Match, no_clause_match.kind);
+        assert!(matches!(
+            hir_let.destruc,
+            destruc::Destruc::Scalar(_, ref scalar) if scalar.local_id() == &Some(no_clause_match.subject_local_id)
+        ));
+    }
+
     #[test]
     fn type_predicate() {
         let j = "bool?";
@@ -1228,4 +3325,88 @@ mod test {
         let expected: Expr<_> = ExprKind::EqPred(t2s(t)).into();
         assert_eq!(expected, expr_for_str(j));
     }
+
+    #[test]
+    fn unsafe_cast() {
+        let j = "(unsafe-cast Int \"checked by caller\" 1.0)";
+        let t = "^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^";
+        let u = "                                     ^^^ ";
+
+        let expected: Expr<_> = ExprKind::Cast(Box::new(Cast {
+            span: t2s(t),
+            ty_ref: ty::Ty::Int.into(),
+            value_expr: Datum::Float(t2s(u), 1.0).into(),
+            reason: Some("checked by caller".into()),
+        }))
+        .into();
+        assert_eq!(expected, expr_for_str(j));
+
+        let j = "(def x (unsafe-cast Int 'reason 1.0))";
+        let t = "                        ^^^^^^^      ";
+        let err = Error::new(t2s(t), ErrorKind::ExpectedUnsafeCastReason("list"));
+        assert_eq!(err, module_for_str(j).err().unwrap());
+
+        let j = "(def x (unsafe-cast Int \" \" 1.0))";
+        let t = "                        ^^^      ";
+        let err = Error::new(t2s(t), ErrorKind::EmptyUnsafeCastReason);
+        assert_eq!(err, module_for_str(j).err().unwrap());
+    }
+
+    #[test]
+    fn unused_warnings() {
+        let file_id = FileId::new(1);
+
+        let warning_messages = |data_str: &str| -> Vec<String> {
+            module_for_file_str(file_id, false, data_str)
+                .unwrap()
+                .warnings
+                .into_iter()
+                .map(|warning| warning.message)
+                .collect()
+        };
+
+        // Inner scopes are checked before their enclosing scopes
+        assert_eq!(
+            vec!["unused variable `a`", "unused variable `y`"],
+            warning_messages("(def f (fn (x y _z) (let [a 1 b 2] (do x b))))")
+        );
+
+        // Variables introduced by macros aren't reported
+        assert!(
+            warning_messages("(defmacro m (macro-rules [() (let [tmp 1] ())])) (def x (m))")
+                .is_empty()
+        );
+
+        assert_eq!(
+            vec!["unused import"],
+            warning_messages("(import (:prefix [arret internal types] t/)) (def x 1)")
+        );
+    }
+
+    #[test]
+    fn shadowing_warnings() {
+        let file_id = FileId::new(1);
+
+        let warnings = module_for_file_str(
+            file_id,
+            false,
+            "(def f (fn (x _y) (let [x (do x 1) _y 2] (do x _y))))",
+        )
+        .unwrap()
+        .warnings;
+
+        assert_eq!(1, warnings.len());
+        assert_eq!("`x` shadows an existing binding", warnings[0].message);
+        assert_eq!(2, warnings[0].labels.len());
+
+        // Rebinding the same datum within an expansion isn't reported
+        assert!(module_for_file_str(
+            file_id,
+            false,
+            "(def f (fn (x) (as-> x y (do y 1) (do y 2))))"
+        )
+        .unwrap()
+        .warnings
+        .is_empty());
+    }
 }
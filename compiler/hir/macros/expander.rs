@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
+use arret_syntax::datum::DataStr;
 use arret_syntax::span::Span;
 
 use crate::context::ModuleId;
@@ -10,8 +12,15 @@ use crate::hir::macros::{get_escaped_ident, starts_with_zero_or_more, Macro};
 use crate::hir::ns::{Ident, NsDatum, NsId};
 use crate::hir::scope::{Binding, Scope};
 
+/// Counter used to give gensym idents a unique name
+///
+/// This is global so names remain unique across modules and REPL lines.
+static NEXT_GENSYM_ID: AtomicUsize = AtomicUsize::new(1);
+
 struct ExpandCursor<'data, 'links> {
     match_data: &'data MatchData<'data>,
+    /// Match data for our enclosing subpatterns from outermost to innermost
+    outer_match_data: Vec<&'data MatchData<'data>>,
     var_links: &'links VarLinks,
 
     ident_index: usize,
@@ -22,6 +31,7 @@ struct ExpandCtx<'scope, 'parent> {
     scope: &'scope mut Scope<'parent>,
     module_id: Option<ModuleId>,
     ns_mapping: HashMap<NsId, NsId>,
+    gensym_names: HashMap<DataStr, DataStr>,
 }
 
 impl<'scope, 'parent> ExpandCtx<'scope, 'parent> {
@@ -30,9 +40,23 @@ impl<'scope, 'parent> ExpandCtx<'scope, 'parent> {
             scope,
             module_id,
             ns_mapping: HashMap::new(),
+            gensym_names: HashMap::new(),
         }
     }
 
+    /// Returns the unique name for a gensym ident
+    ///
+    /// Each use of the same gensym ident within an expansion shares the same name.
+    fn gensym_name(&mut self, name: &DataStr) -> DataStr {
+        self.gensym_names
+            .entry(name.clone())
+            .or_insert_with(|| {
+                let gensym_id = NEXT_GENSYM_ID.fetch_add(1, Ordering::Relaxed);
+                format!("{}{}", name, gensym_id).into()
+            })
+            .clone()
+    }
+
     fn expand_ident(
         &mut self,
         self_mac: &Arc<Macro>,
@@ -40,6 +64,8 @@ impl<'scope, 'parent> ExpandCtx<'scope, 'parent> {
         span: Span,
         ident: &Ident,
     ) -> NsDatum {
+        let mut is_gensym = false;
+
         let binding = if !ident.is_underscore() {
             let template_ident = cursor.var_links.template_ident(cursor.ident_index);
             cursor.ident_index += 1;
@@ -48,6 +74,16 @@ impl<'scope, 'parent> ExpandCtx<'scope, 'parent> {
                 TemplateIdent::SubpatternVar(var_index) => {
                     return cursor.match_data.var(*var_index).clone();
                 }
+                TemplateIdent::OuterSubpatternVar(outer_depth, var_index) => {
+                    let outer_match_data =
+                        cursor.outer_match_data[cursor.outer_match_data.len() - outer_depth];
+
+                    return outer_match_data.var(*var_index).clone();
+                }
+                TemplateIdent::Gensym => {
+                    is_gensym = true;
+                    None
+                }
                 TemplateIdent::SelfIdent => Some(Binding::Macro(self.module_id, self_mac.clone())),
                 TemplateIdent::Bound(binding) => Some(if let Some(module_id) = self.module_id {
                     binding.import_from(module_id)
@@ -63,15 +99,19 @@ impl<'scope, 'parent> ExpandCtx<'scope, 'parent> {
         // Re-scope this ident
         let old_ns_id = ident.ns_id();
         let scope = &mut self.scope;
-        let new_ns_id = self
+        let new_ns_id = *self
             .ns_mapping
             .entry(old_ns_id)
             .or_insert_with(|| scope.alloc_ns_id());
 
-        let new_ident = ident.with_ns_id(*new_ns_id);
+        let new_ident = if is_gensym {
+            Ident::new(new_ns_id, self.gensym_name(ident.name()))
+        } else {
+            ident.with_ns_id(new_ns_id)
+        };
 
         if let Some(binding) = binding {
-            scope.replace_binding(span, new_ident.clone(), binding);
+            self.scope.replace_binding(span, new_ident.clone(), binding);
         };
 
         NsDatum::Ident(span, new_ident)
@@ -91,12 +131,16 @@ impl<'scope, 'parent> ExpandCtx<'scope, 'parent> {
 
         cursor.subtemplate_index += 1;
 
+        let mut outer_match_data = cursor.outer_match_data.clone();
+        outer_match_data.push(cursor.match_data);
+
         submatches
             .iter()
             .map(|m| {
                 // Build a new cursor pointing to our subpattern
                 let mut subcursor = ExpandCursor {
                     match_data: m,
+                    outer_match_data: outer_match_data.clone(),
                     var_links: subvar_links,
 
                     ident_index: 0,
@@ -178,6 +222,7 @@ pub fn expand_rule<'scope, 'parent, 'data>(
 
     let mut cursor = ExpandCursor {
         match_data,
+        outer_match_data: vec![],
         var_links,
 
         ident_index: 0,
@@ -13,8 +13,16 @@ use crate::hir::scope::{Binding, Scope};
 pub enum TemplateIdent {
     /// Refers to a template variable with a given index
     SubpatternVar(usize),
+    /// Refers to a template variable in an enclosing subpattern
+    ///
+    /// The first value is the number of subpatterns out from the current subpattern and the second
+    /// value is the variable's index within that subpattern. This allows a variable to be repeated
+    /// in each iteration of a nested zero or more match.
+    OuterSubpatternVar(usize, usize),
     /// Ident of the macro being expanded
     SelfIdent,
+    /// Ident replaced with a unique ident for each expansion
+    Gensym,
     /// Ident bound to the given binding
     Bound(Binding),
     /// Unbound ident
@@ -49,7 +57,7 @@ impl VarLinks {
 #[derive(Debug)]
 struct FoundVars<'data> {
     span: Span,
-    idents: Vec<&'data Ident>,
+    idents: Vec<(Span, &'data Ident)>,
     subs: Vec<FoundVars<'data>>,
 }
 
@@ -61,6 +69,30 @@ impl<'data> FoundVars<'data> {
             subs: vec![],
         }
     }
+
+    fn position(&self, ident: &Ident) -> Option<usize> {
+        self.idents
+            .iter()
+            .position(|(_, found_ident)| *found_ident == ident)
+    }
+
+    /// Returns if we or any of our nested subpatterns contain the passed ident
+    fn contains_nested(&self, ident: &Ident) -> bool {
+        self.position(ident).is_some() || self.subs.iter().any(|sub| sub.contains_nested(ident))
+    }
+
+    /// Returns if we or any of our nested subpatterns contain any idents
+    fn has_nested_idents(&self) -> bool {
+        !self.idents.is_empty() || self.subs.iter().any(FoundVars::has_nested_idents)
+    }
+
+    /// Returns if any ident in our tree is found in the tree of the passed vars
+    fn references_nested(&self, other: &FoundVars<'_>) -> bool {
+        self.idents
+            .iter()
+            .any(|(_, ident)| other.contains_nested(ident))
+            || self.subs.iter().any(|sub| sub.references_nested(other))
+    }
 }
 
 /// Tracks which type of input is being provided to `FindVarsCtx`
@@ -119,7 +151,7 @@ impl<'data> FindVarsCtx<'data> {
             }
         }
 
-        pattern_vars.idents.push(ident);
+        pattern_vars.idents.push((span, ident));
         Ok(())
     }
 
@@ -230,44 +262,64 @@ impl<'data> FindVarsCtx<'data> {
 fn link_template_ident(
     scope: &Scope<'_>,
     self_ident: &Ident,
+    span: Span,
     template_ident: &Ident,
-    pattern_idents: &[&Ident],
-) -> TemplateIdent {
-    // First, see if this corresponds to a var in the pattern
-    if let Some(subpattern_index) = pattern_idents
-        .iter()
-        .position(|pattern_ident| *pattern_ident == template_ident)
-    {
-        TemplateIdent::SubpatternVar(subpattern_index)
+    pattern_vars_stack: &[&FoundVars<'_>],
+) -> Result<TemplateIdent> {
+    // First, see if this corresponds to a var in the pattern at our depth or an enclosing depth
+    for (outer_depth, pattern_vars) in pattern_vars_stack.iter().rev().enumerate() {
+        if let Some(var_index) = pattern_vars.position(template_ident) {
+            return Ok(if outer_depth == 0 {
+                TemplateIdent::SubpatternVar(var_index)
+            } else {
+                TemplateIdent::OuterSubpatternVar(outer_depth, var_index)
+            });
+        }
+    }
+
+    if pattern_vars_stack[0].contains_nested(template_ident) {
+        // This is bound by a zero or more match we're not inside
+        Err(Error::new(
+            span,
+            ErrorKind::MacroVarWrongDepth(template_ident.name().clone()),
+        ))
+    } else if template_ident.is_gensym() {
+        Ok(TemplateIdent::Gensym)
     } else if template_ident == self_ident {
-        TemplateIdent::SelfIdent
-    } else if let Some(binding) = scope.get(template_ident) {
-        TemplateIdent::Bound(binding.clone())
+        Ok(TemplateIdent::SelfIdent)
+    } else if let Some(binding) = scope.get_unparameterized(template_ident) {
+        Ok(TemplateIdent::Bound(binding.clone()))
     } else {
-        TemplateIdent::Unbound
+        Ok(TemplateIdent::Unbound)
     }
 }
 
+/// Links the template vars for a given subpattern
+///
+/// `pattern_vars_stack` contains the vars for every enclosing subpattern ending with the current
+/// subpattern.
 fn link_found_vars(
     scope: &Scope<'_>,
     self_ident: &Ident,
     subpattern_index: usize,
-    pattern_vars: &FoundVars<'_>,
+    pattern_vars_stack: &[&FoundVars<'_>],
     template_vars: &FoundVars<'_>,
 ) -> Result<VarLinks> {
+    let pattern_vars = pattern_vars_stack.last().unwrap();
+
     let template_idents = template_vars
         .idents
         .iter()
-        .map(|template_ident| {
-            link_template_ident(scope, self_ident, template_ident, &pattern_vars.idents)
+        .map(|(span, template_ident)| {
+            link_template_ident(scope, self_ident, *span, template_ident, pattern_vars_stack)
         })
-        .collect();
+        .collect::<Result<Box<[TemplateIdent]>>>()?;
 
     let subtemplates = template_vars
         .subs
         .iter()
         .map(|subtemplate_vars| {
-            if subtemplate_vars.idents.is_empty() {
+            if !subtemplate_vars.has_nested_idents() {
                 return Err(Error::new(
                     template_vars.span,
                     ErrorKind::MacroNoTemplateVars,
@@ -275,16 +327,13 @@ fn link_found_vars(
             }
 
             // Find possible indices for subpatterns in our pattern
+            // Nested subtemplates are included so a subtemplate can be driven entirely by a nested
+            // zero or more match
             let possible_indices = pattern_vars
                 .subs
                 .iter()
                 .enumerate()
-                .filter(|(_, subpattern_vars)| {
-                    subpattern_vars
-                        .idents
-                        .iter()
-                        .any(|subpattern_var| subtemplate_vars.idents.contains(subpattern_var))
-                })
+                .filter(|(_, subpattern_vars)| subtemplate_vars.references_nested(subpattern_vars))
                 .collect::<Vec<(usize, &FoundVars<'_>)>>();
 
             if possible_indices.is_empty() {
@@ -303,11 +352,15 @@ fn link_found_vars(
 
             // Iterate over our subpatterns
             let (pattern_index, subpattern_vars) = possible_indices[0];
+
+            let mut subpattern_vars_stack = pattern_vars_stack.to_vec();
+            subpattern_vars_stack.push(subpattern_vars);
+
             link_found_vars(
                 scope,
                 self_ident,
                 pattern_index,
-                subpattern_vars,
+                &subpattern_vars_stack,
                 subtemplate_vars,
             )
         })
@@ -336,5 +389,5 @@ pub fn link_rule_vars(
     let mut template_vars = FoundVars::new(template.span());
     ftvcx.visit_datum(&mut template_vars, template)?;
 
-    link_found_vars(scope, self_ident, 0, &pattern_vars, &template_vars)
+    link_found_vars(scope, self_ident, 0, &[&pattern_vars], &template_vars)
 }
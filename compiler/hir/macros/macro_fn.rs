@@ -0,0 +1,189 @@
+#[cfg(feature = "backend")]
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "backend")]
+use codespan_reporting::diagnostic::Diagnostic;
+
+#[cfg(feature = "backend")]
+use arret_syntax::datum::Datum;
+#[cfg(feature = "backend")]
+use arret_syntax::span::FileId;
+use arret_syntax::span::Span;
+
+#[cfg(feature = "backend")]
+use crate::context::ModuleId;
+use crate::context::{Module, ModuleImports};
+use crate::hir;
+use crate::hir::error::{Error, ErrorKind, Result};
+use crate::hir::ns::NsDatum;
+use crate::hir::visitor::visit_exprs;
+#[cfg(feature = "backend")]
+use crate::mir::eval_hir::{EvalHirCtx, FunCtx};
+#[cfg(feature = "backend")]
+use crate::opt_level::OptLevel;
+#[cfg(feature = "backend")]
+use crate::typeck::infer::{infer_repl_expr, InferredModuleVars};
+
+/// Macro implemented by an Arret function evaluated at expansion time
+///
+/// The function may only reference definitions imported into its module. This ensures it can be
+/// evaluated before the rest of the module has been type checked.
+pub struct MacroFn {
+    fun_expr: hir::Expr<hir::Lowered>,
+    imports: Vec<Arc<Module>>,
+}
+
+impl fmt::Debug for MacroFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MacroFn")
+            .field("fun_expr", &self.fun_expr)
+            .finish()
+    }
+}
+
+impl MacroFn {
+    /// Creates a new macro function from an expression lowered in a module with `imports`
+    ///
+    /// `first_local_id` must be allocated immediately before lowering `fun_expr`. Any references
+    /// to locals allocated before it refer to variables outside of the macro function.
+    pub fn new(
+        imports: &ModuleImports,
+        first_local_id: hir::LocalId,
+        fun_expr: hir::Expr<hir::Lowered>,
+    ) -> Result<MacroFn> {
+        let mut outer_local_spans = vec![];
+        visit_exprs(&fun_expr, &mut |expr| {
+            if let hir::ExprKind::LocalRef(span, local_id) = expr.kind {
+                if local_id < first_local_id {
+                    outer_local_spans.push(span);
+                }
+            }
+        });
+
+        if let Some(outer_local_span) = outer_local_spans.first() {
+            return Err(Error::new(*outer_local_span, ErrorKind::MacroFnLocalRef));
+        }
+
+        Ok(MacroFn {
+            fun_expr,
+            imports: imports.values().cloned().collect(),
+        })
+    }
+}
+
+/// Evaluation context for a single macro function expansion
+#[cfg(feature = "backend")]
+struct EvalCtx {
+    ehx: EvalHirCtx,
+    inferred_module_vars: InferredModuleVars,
+    visited_modules: HashSet<ModuleId>,
+}
+
+#[cfg(feature = "backend")]
+impl EvalCtx {
+    /// Visits a subtree of modules to evaluate their definitions and collect their inferred types
+    fn visit_module_tree(&mut self, module: &Module) -> Result<(), Diagnostic<FileId>> {
+        if !self.visited_modules.insert(module.module_id) {
+            return Ok(());
+        }
+
+        // Make sure our imports are first
+        for import in module.imports.values() {
+            self.visit_module_tree(import)?;
+        }
+
+        self.inferred_module_vars
+            .insert(module.module_id, module.inferred_locals.clone());
+
+        self.ehx.visit_module_defs(module.module_id, &module.defs)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "backend")]
+fn eval_macro_fn(
+    macro_fn: &MacroFn,
+    invocation_span: Span,
+    stx_datum: Datum,
+) -> Result<Option<Datum>, Diagnostic<FileId>> {
+    use arret_runtime_syntax::reader::unbox_syntax_datum;
+
+    let mut ecx = EvalCtx {
+        ehx: EvalHirCtx::new(OptLevel::default()),
+        inferred_module_vars: HashMap::new(),
+        visited_modules: HashSet::new(),
+    };
+
+    for import in macro_fn.imports.iter() {
+        ecx.visit_module_tree(import)?;
+    }
+
+    let app_expr: hir::Expr<hir::Lowered> = hir::ExprKind::App(Box::new(hir::App {
+        span: invocation_span,
+        fun_expr: macro_fn.fun_expr.clone(),
+        ty_args: (),
+        fixed_arg_exprs: vec![stx_datum.into()],
+        rest_arg_expr: None,
+    }))
+    .into();
+
+    let node = infer_repl_expr(&ecx.inferred_module_vars, app_expr)?;
+
+    let value = ecx
+        .ehx
+        .consume_expr(&mut FunCtx::new(None), &mut None, node.into_expr())?;
+
+    Ok(ecx
+        .ehx
+        .value_to_const(&value)
+        .and_then(|boxed| unbox_syntax_datum(&ecx.ehx, invocation_span, boxed)))
+}
+
+/// Expands a macro function invocation
+///
+/// The function is passed the macro's arguments as a list. Unlike `macro-rules` the returned
+/// datum is not hygienic; any identifiers it contains are resolved in the module's namespace.
+#[cfg(feature = "backend")]
+pub fn expand_macro_fn(
+    invocation_span: Span,
+    macro_fn: &MacroFn,
+    arg_data: &[NsDatum],
+) -> Result<NsDatum> {
+    let stx_datum = Datum::List(
+        invocation_span,
+        arg_data
+            .iter()
+            .cloned()
+            .map(NsDatum::into_syntax_datum)
+            .collect(),
+    );
+
+    match eval_macro_fn(macro_fn, invocation_span, stx_datum) {
+        Ok(Some(expanded_datum)) => Ok(NsDatum::from_syntax_datum(&expanded_datum)),
+        Ok(None) => Err(Error::new(
+            invocation_span,
+            ErrorKind::MacroFnNonDatumResult,
+        )),
+        Err(diagnostic) => Err(Error::new(
+            invocation_span,
+            ErrorKind::MacroFnError(Box::new(diagnostic)),
+        )),
+    }
+}
+
+/// Reports an error for a macro function invocation
+///
+/// Macro functions are evaluated by the backend which isn't available.
+#[cfg(not(feature = "backend"))]
+pub fn expand_macro_fn(
+    invocation_span: Span,
+    _macro_fn: &MacroFn,
+    _arg_data: &[NsDatum],
+) -> Result<NsDatum> {
+    Err(Error::new(
+        invocation_span,
+        ErrorKind::MacroFnWithoutBackend,
+    ))
+}
@@ -63,6 +63,8 @@ impl<'data> MatchCtx<'data> {
             (NsDatum::Int(_, pv), NsDatum::Int(_, av)) => pv == av,
             // Don't match NaNs against other NaNs. This is consistent with `=`.
             (NsDatum::Float(_, pv), NsDatum::Float(_, av)) => pv == av,
+            (NsDatum::Rational(_, pn, pd), NsDatum::Rational(_, an, ad)) => pn == an && pd == ad,
+            (NsDatum::BigInt(_, pv), NsDatum::BigInt(_, av)) => pv == av,
             (NsDatum::Char(_, pv), NsDatum::Char(_, av)) => pv == av,
             (NsDatum::Str(_, pv), NsDatum::Str(_, av)) => pv == av,
             (NsDatum::List(_, pv), NsDatum::Ident(_, arg)) => {
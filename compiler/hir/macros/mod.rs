@@ -1,5 +1,6 @@
 mod expander;
 mod linker;
+mod macro_fn;
 mod matcher;
 
 use std::sync::Arc;
@@ -10,9 +11,11 @@ use crate::context::ModuleId;
 use crate::hir::error::{Error, ErrorKind, Result};
 use crate::hir::macros::expander::expand_rule;
 use crate::hir::macros::linker::{link_rule_vars, VarLinks};
+use crate::hir::macros::macro_fn::expand_macro_fn;
 use crate::hir::macros::matcher::match_rule;
 use crate::hir::ns::{Ident, NsDatum};
-use crate::hir::scope::Scope;
+use crate::hir::prim::Prim;
+use crate::hir::scope::{Binding, Scope};
 
 #[derive(Debug)]
 pub struct Rule {
@@ -22,14 +25,30 @@ pub struct Rule {
     var_links: VarLinks,
 }
 
+pub use crate::hir::macros::macro_fn::MacroFn;
+
+#[derive(Debug)]
+enum MacroKind {
+    Rules(Box<[Rule]>),
+    Fn(MacroFn),
+}
+
 #[derive(Debug)]
 pub struct Macro {
-    rules: Box<[Rule]>,
+    kind: MacroKind,
 }
 
 impl Macro {
     pub fn new(rules: Box<[Rule]>) -> Arc<Self> {
-        Arc::new(Self { rules })
+        Arc::new(Self {
+            kind: MacroKind::Rules(rules),
+        })
+    }
+
+    pub fn new_fn(macro_fn: MacroFn) -> Arc<Self> {
+        Arc::new(Self {
+            kind: MacroKind::Fn(macro_fn),
+        })
     }
 }
 
@@ -114,7 +133,14 @@ pub fn expand_macro<'s, 'p>(
     mac: &Arc<Macro>,
     arg_data: &[NsDatum],
 ) -> Result<NsDatum> {
-    for rule in mac.rules.iter() {
+    let rules = match &mac.kind {
+        MacroKind::Rules(rules) => rules,
+        MacroKind::Fn(macro_fn) => {
+            return expand_macro_fn(invocation_span, macro_fn, arg_data);
+        }
+    };
+
+    for rule in rules.iter() {
         let match_result = match_rule(rule, arg_data);
 
         if let Ok(match_data) = match_result {
@@ -131,6 +157,64 @@ pub fn expand_macro<'s, 'p>(
 
     Err(Error::new(
         invocation_span,
-        ErrorKind::NoMacroRule(mac.rules.iter().map(|rule| rule.pattern_span).collect()),
+        ErrorKind::NoMacroRule(rules.iter().map(|rule| rule.pattern_span).collect()),
     ))
 }
+
+/// Expands a datum if it's a macro invocation
+///
+/// Only the outermost invocation is expanded; the expansion may itself be a macro invocation.
+/// Data that aren't macro invocations return `None`.
+pub fn expand_macro_once(scope: &mut Scope<'_>, datum: &NsDatum) -> Result<Option<NsDatum>> {
+    let (span, vs) = if let NsDatum::List(span, vs) = datum {
+        (*span, vs)
+    } else {
+        return Ok(None);
+    };
+
+    let (module_id, mac) = match vs.first().and_then(|fn_datum| scope.get_datum(fn_datum)) {
+        Some(Binding::Macro(module_id, mac)) => (*module_id, mac.clone()),
+        _ => {
+            return Ok(None);
+        }
+    };
+
+    expand_macro(scope, span, module_id, &mac, &vs[1..]).map(Some)
+}
+
+fn expand_all_slice(scope: &mut Scope<'_>, vs: Box<[NsDatum]>) -> Result<Box<[NsDatum]>> {
+    vs.into_vec()
+        .into_iter()
+        .map(|v| expand_all(scope, v))
+        .collect()
+}
+
+/// Repeatedly expands every macro invocation inside a datum
+///
+/// This doesn't understand the binding forms of the expanded code. Any list beginning with an
+/// identifier bound to a macro in `scope` is expanded unless it's inside a `(quote)`.
+pub fn expand_all(scope: &mut Scope<'_>, mut datum: NsDatum) -> Result<NsDatum> {
+    while let Some(expanded_datum) = expand_macro_once(scope, &datum)? {
+        datum = expanded_datum;
+    }
+
+    Ok(match datum {
+        NsDatum::List(span, vs) => {
+            if let Some(Binding::Prim(Prim::Quote)) = vs.first().and_then(|v| scope.get_datum(v)) {
+                NsDatum::List(span, vs)
+            } else {
+                NsDatum::List(span, expand_all_slice(scope, vs)?)
+            }
+        }
+        NsDatum::Vector(span, vs) => NsDatum::Vector(span, expand_all_slice(scope, vs)?),
+        NsDatum::Set(span, vs) => NsDatum::Set(span, expand_all_slice(scope, vs)?),
+        NsDatum::Map(span, vs) => NsDatum::Map(
+            span,
+            vs.into_vec()
+                .into_iter()
+                .map(|(k, v)| Ok((expand_all(scope, k)?, expand_all(scope, v)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other,
+    })
+}
@@ -2,6 +2,7 @@ pub(crate) mod destruc;
 pub(crate) mod error;
 pub(crate) mod exports;
 pub(crate) mod import;
+mod lint;
 pub(crate) mod loader;
 pub(crate) mod lowering;
 mod macros;
@@ -9,6 +10,8 @@ pub(crate) mod ns;
 mod prim;
 mod records;
 pub(crate) mod scope;
+mod target;
+mod threading;
 mod types;
 mod util;
 pub(crate) mod var_id;
@@ -16,7 +19,7 @@ pub(crate) mod visitor;
 
 use std::sync::Arc;
 
-use arret_syntax::datum::Datum;
+use arret_syntax::datum::{DataStr, Datum};
 use arret_syntax::span::Span;
 
 use crate::rfi;
@@ -120,6 +123,45 @@ pub struct Cond<P: Phase> {
     pub false_expr: Expr<P>,
 }
 
+/// `_` used in place of a type annotation
+///
+/// The annotated type is inferred as if it was omitted. The type checker then reports the type it
+/// inferred for the hole.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TyHole {
+    pub span: Span,
+    pub kind: TyHoleKind,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TyHoleKind {
+    /// Type of a local variable such as `[x _]`
+    Local(LocalId),
+
+    /// Return type of a function such as `-> _`
+    ///
+    /// The function's `ret_ty_span` is the span of the hole.
+    FunRet,
+}
+
+/// Kind of branching form a [`NoClauseMatch`] was lowered from
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum BranchKind {
+    Match,
+    Cond,
+}
+
+/// Fallthrough of a `(match)` or `(cond)` where none of the clauses matched
+///
+/// This evaluates to `()`. The type checker uses the remaining type of the subject to report any
+/// union members the clauses didn't handle.
+#[derive(PartialEq, Debug, Clone)]
+pub struct NoClauseMatch {
+    pub span: Span,
+    pub subject_local_id: LocalId,
+    pub kind: BranchKind,
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Let<P: Phase> {
     pub span: Span,
@@ -128,6 +170,24 @@ pub struct Let<P: Phase> {
     pub body_expr: Expr<P>,
 }
 
+/// Set of functions bound together which can reference each other
+#[derive(PartialEq, Debug, Clone)]
+pub struct Letrec<P: Phase> {
+    pub span: Span,
+    pub bindings: Vec<LetrecBinding<P>>,
+    pub body_expr: Expr<P>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct LetrecBinding<P: Phase> {
+    pub destruc: destruc::Scalar<P>,
+
+    /// Expression for the bound function
+    ///
+    /// This is guaranteed to be a `Fun` expression, optionally wrapped in `MacroExpand`.
+    pub value_expr: Expr<P>,
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct App<P: Phase> {
     pub span: Span,
@@ -144,6 +204,49 @@ pub struct Recur<P: Phase> {
     pub rest_arg_expr: Option<Expr<P>>,
 }
 
+/// Value explicitly converted to its unboxed representation
+#[derive(PartialEq, Debug, Clone)]
+pub struct Unbox<P: Phase> {
+    pub span: Span,
+
+    /// Type of the unboxed value
+    ///
+    /// This is guaranteed to be `Int`, `Float` or `Bool`.
+    pub ty_ref: ty::Ref<ty::Poly>,
+    pub value_expr: Expr<P>,
+}
+
+/// Dynamically typed value checked against a required type at runtime
+///
+/// These are only inserted during type checking where a `Dyn` value is used as a more specific
+/// type.
+#[derive(PartialEq, Debug, Clone)]
+pub struct DynCheck<P: Phase> {
+    pub span: Span,
+
+    /// Type the value is required to have
+    pub ty_ref: ty::Ref<ty::Poly>,
+    pub value_expr: Expr<P>,
+}
+
+/// Value statically reinterpreted as another type without a runtime check
+///
+/// The value must already have the same runtime representation as `ty_ref`. This is used to wrap
+/// and unwrap newtypes and for `(unsafe-cast)`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cast<P: Phase> {
+    pub span: Span,
+
+    /// Type the value is treated as
+    pub ty_ref: ty::Ref<ty::Poly>,
+    pub value_expr: Expr<P>,
+
+    /// Justification given for an `(unsafe-cast)`
+    ///
+    /// This is `None` for casts introduced by the compiler.
+    pub reason: Option<DataStr>,
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct FieldAccessor {
     pub span: Span,
@@ -157,6 +260,38 @@ pub struct Expr<P: Phase> {
     pub kind: ExprKind<P>,
 }
 
+impl<P: Phase> Expr<P> {
+    /// Returns the span of the source the expression was lowered from
+    ///
+    /// This is `None` for expressions synthesised by the compiler without a source location.
+    pub fn span(&self) -> Option<Span> {
+        match &self.kind {
+            ExprKind::Lit(datum) => Some(datum.span()),
+            ExprKind::App(app) => Some(app.span),
+            ExprKind::Recur(recur) => Some(recur.span),
+            ExprKind::Fun(fun) => Some(fun.span),
+            ExprKind::RustFun(_) => None,
+            ExprKind::Let(hir_let) => Some(hir_let.span),
+            ExprKind::Letrec(letrec) => Some(letrec.span),
+            ExprKind::Cond(cond) => Some(cond.span),
+            ExprKind::FieldAccessor(field_accessor) => Some(field_accessor.span),
+            ExprKind::NoClauseMatch(no_clause_match) => Some(no_clause_match.span),
+            ExprKind::Unbox(unbox) => Some(unbox.span),
+            ExprKind::DynCheck(dyn_check) => Some(dyn_check.span),
+            ExprKind::Cast(cast) => Some(cast.span),
+            ExprKind::ExportRef(span, _)
+            | ExprKind::LocalRef(span, _)
+            | ExprKind::TyPred(span, _)
+            | ExprKind::EqPred(span)
+            | ExprKind::RecordCons(span, _)
+            | ExprKind::Values(span, _)
+            | ExprKind::Box(span, _)
+            | ExprKind::MacroExpand(span, _) => Some(*span),
+            ExprKind::Do(exprs) => exprs.last().and_then(Expr::span),
+        }
+    }
+}
+
 impl From<Datum> for Expr<Lowered> {
     fn from(datum: Datum) -> Expr<Lowered> {
         ExprKind::Lit(datum).into()
@@ -171,6 +306,7 @@ pub enum ExprKind<P: Phase> {
     Fun(Box<Fun<P>>),
     RustFun(Arc<rfi::Fun>),
     Let(Box<Let<P>>),
+    Letrec(Box<Letrec<P>>),
     Cond(Box<Cond<P>>),
     ExportRef(Span, ExportId),
     LocalRef(Span, LocalId),
@@ -179,9 +315,19 @@ pub enum ExprKind<P: Phase> {
     EqPred(Span),
     RecordCons(Span, record::ConsId),
     FieldAccessor(Box<FieldAccessor>),
+    NoClauseMatch(Box<NoClauseMatch>),
 
     Do(Vec<Expr<P>>),
 
+    /// Multiple values returned together without allocating a list
+    Values(Span, Vec<Expr<P>>),
+
+    /// Value explicitly converted to its boxed representation
+    Box(Span, Box<Expr<P>>),
+    Unbox(Box<Unbox<P>>),
+    DynCheck(Box<DynCheck<P>>),
+    Cast(Box<Cast<P>>),
+
     /// Used for tracing macro expansion for error report and debug information
     ///
     /// Other than the above this should be treated identically to the inner expression.
@@ -197,18 +343,27 @@ impl From<ExprKind<Lowered>> for Expr<Lowered> {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Def<P: Phase> {
     pub span: Span,
     pub macro_invocation_span: Option<Span>,
     pub destruc: destruc::Destruc<P>,
     pub value_expr: Expr<P>,
+
+    /// Optional docstring following the destructure
+    pub doc: Option<DataStr>,
+
+    /// Indicates if the value must be folded to a constant at compile time
+    pub is_const: bool,
 }
 
-pub use self::loader::PackagePaths;
+pub use self::loader::{ModuleName, PackagePaths, ParseModuleNameError};
+#[cfg(feature = "backend")]
+pub(crate) use self::macros::{expand_all, expand_macro_once};
 pub use self::types::lower_poly;
 pub use self::types::str_for_purity;
 pub use self::types::str_for_ty_ref;
+pub use self::types::str_for_ty_ref_diff;
 
 #[cfg(test)]
 pub use self::types::{lower_polymorphic_var_set, poly_for_str, try_lower_purity, tvar_bounded_by};
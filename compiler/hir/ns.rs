@@ -1,6 +1,6 @@
 use std::vec;
 
-use arret_syntax::datum::{DataStr, Datum};
+use arret_syntax::datum::{BigInt, DataStr, Datum};
 use arret_syntax::span::Span;
 
 use crate::hir::scope::Scope;
@@ -42,6 +42,13 @@ impl Ident {
         self.data_name.as_ref() == "&"
     }
 
+    /// Returns if this ident should be replaced with a unique ident when used in a macro template
+    ///
+    /// These idents end with `#`, e.g. `tmp#`.
+    pub fn is_gensym(&self) -> bool {
+        self.data_name.len() > 1 && self.data_name.ends_with('#')
+    }
+
     pub fn with_ns_id(&self, new_ns_id: NsId) -> Ident {
         Ident {
             ns_id: new_ns_id,
@@ -56,6 +63,8 @@ pub enum NsDatum {
     Char(Span, char),
     Int(Span, i64),
     Float(Span, f64),
+    Rational(Span, i64, i64),
+    BigInt(Span, Box<BigInt>),
     List(Span, Box<[NsDatum]>),
     Str(Span, DataStr),
     Keyword(Span, DataStr),
@@ -76,6 +85,8 @@ impl NsDatum {
             Datum::Char(span, v) => NsDatum::Char(*span, *v),
             Datum::Int(span, v) => NsDatum::Int(*span, *v),
             Datum::Float(span, v) => NsDatum::Float(*span, *v),
+            Datum::Rational(span, n, d) => NsDatum::Rational(*span, *n, *d),
+            Datum::BigInt(span, v) => NsDatum::BigInt(*span, v.clone()),
             Datum::Str(span, v) => NsDatum::Str(*span, v.clone()),
             Datum::Sym(span, v) => {
                 if v.starts_with(':') {
@@ -96,41 +107,74 @@ impl NsDatum {
         }
     }
 
-    fn map_nsdata(vs: Box<[NsDatum]>) -> Box<[Datum]> {
+    fn map_nsdata<F>(vs: Box<[NsDatum]>, ident_to_sym: &F) -> Box<[Datum]>
+    where
+        F: Fn(Ident) -> DataStr,
+    {
         vs.into_vec()
             .into_iter()
-            .map(NsDatum::into_syntax_datum)
+            .map(|v| v.map_into_syntax_datum(ident_to_sym))
             .collect()
     }
 
-    pub fn into_syntax_datum(self) -> Datum {
+    fn map_into_syntax_datum<F>(self, ident_to_sym: &F) -> Datum
+    where
+        F: Fn(Ident) -> DataStr,
+    {
         match self {
             NsDatum::Bool(span, v) => Datum::Bool(span, v),
             NsDatum::Char(span, v) => Datum::Char(span, v),
             NsDatum::Int(span, v) => Datum::Int(span, v),
             NsDatum::Float(span, v) => Datum::Float(span, v),
+            NsDatum::Rational(span, n, d) => Datum::Rational(span, n, d),
+            NsDatum::BigInt(span, v) => Datum::BigInt(span, v),
             NsDatum::Str(span, v) => Datum::Str(span, v),
             NsDatum::Keyword(span, v) => Datum::Sym(span, v),
-            NsDatum::Ident(span, v) => Datum::Sym(span, v.into_name()),
-            NsDatum::List(span, vs) => Datum::List(span, Self::map_nsdata(vs)),
-            NsDatum::Vector(span, vs) => Datum::Vector(span, Self::map_nsdata(vs)),
-            NsDatum::Set(span, vs) => Datum::Set(span, Self::map_nsdata(vs)),
+            NsDatum::Ident(span, v) => Datum::Sym(span, ident_to_sym(v)),
+            NsDatum::List(span, vs) => Datum::List(span, Self::map_nsdata(vs, ident_to_sym)),
+            NsDatum::Vector(span, vs) => Datum::Vector(span, Self::map_nsdata(vs, ident_to_sym)),
+            NsDatum::Set(span, vs) => Datum::Set(span, Self::map_nsdata(vs, ident_to_sym)),
             NsDatum::Map(span, vs) => Datum::Map(
                 span,
                 vs.into_vec()
                     .into_iter()
-                    .map(|(k, v)| (k.into_syntax_datum(), v.into_syntax_datum()))
+                    .map(|(k, v)| {
+                        (
+                            k.map_into_syntax_datum(ident_to_sym),
+                            v.map_into_syntax_datum(ident_to_sym),
+                        )
+                    })
                     .collect(),
             ),
         }
     }
 
+    pub fn into_syntax_datum(self) -> Datum {
+        self.map_into_syntax_datum(&Ident::into_name)
+    }
+
+    /// Converts this datum to a syntax datum while marking the namespace of identifiers
+    ///
+    /// Identifiers outside of the root namespace have their namespace ID appended after an `@`.
+    /// This is intended for debugging macro hygiene.
+    pub fn into_marked_syntax_datum(self) -> Datum {
+        self.map_into_syntax_datum(&|ident: Ident| {
+            if ident.ns_id() == Scope::root_ns_id() {
+                ident.into_name()
+            } else {
+                format!("{}@{}", ident.name(), ident.ns_id().to_u32()).into()
+            }
+        })
+    }
+
     pub fn span(&self) -> Span {
         match self {
             NsDatum::Bool(span, _)
             | NsDatum::Char(span, _)
             | NsDatum::Int(span, _)
             | NsDatum::Float(span, _)
+            | NsDatum::Rational(span, _, _)
+            | NsDatum::BigInt(span, _)
             | NsDatum::Str(span, _)
             | NsDatum::Keyword(span, _)
             | NsDatum::Ident(span, _)
@@ -148,6 +192,8 @@ impl NsDatum {
             NsDatum::Char(_, _) => "character",
             NsDatum::Int(_, _) => "integer",
             NsDatum::Float(_, _) => "floating point number",
+            NsDatum::Rational(_, _, _) => "rational number",
+            NsDatum::BigInt(_, _) => "big integer",
             NsDatum::Str(_, _) => "string",
             NsDatum::Keyword(_, _) => "keyword",
             NsDatum::Ident(_, _) => "symbol",
@@ -24,20 +24,45 @@ macro_rules! export_prims {
 
 export_prims!(
     ("def", Def),
+    ("def-const", DefConst),
     ("let", Let),
+    ("let-values", LetValues),
+    ("letrec", Letrec),
     ("fn", Fun),
     ("if", If),
+    ("when", When),
+    ("unless", Unless),
+    ("cond", Cond),
+    ("and", And),
+    ("or", Or),
+    ("->>", ThreadLast),
+    ("as->", ThreadAs),
+    ("cond-target", CondTarget),
+    ("match", Match),
     ("do", Do),
+    ("values", Values),
     ("recur", Recur),
+    ("loop", Loop),
     ("quote", Quote),
     ("export", Export),
+    ("export-all", ExportAll),
+    ("private", Private),
     ("defmacro", DefMacro),
     ("letmacro", LetMacro),
     ("macro-rules", MacroRules),
+    ("macro-fn", MacroFn),
+    ("def-syntax-param", DefSyntaxParam),
+    ("syntax-parameterize", SyntaxParameterize),
     ("deftype", DefType),
     ("lettype", LetType),
     ("defrecord", DefRecord),
     ("letrecord", LetRecord),
+    ("defnewtype", DefNewtype),
+    ("defpred", DefPred),
     ("compile-error", CompileError),
+    ("current-source-loc", CurrentSourceLoc),
+    ("box", Box),
+    ("unbox", Unbox),
+    ("unsafe-cast", UnsafeCast),
     ("All", All)
 );
@@ -6,12 +6,15 @@ use crate::hir::ns::Ident;
 use crate::hir::ns::{NsDataIter, NsDatum};
 use crate::hir::scope::{Binding, Scope};
 use crate::hir::types::lower_poly;
-use crate::hir::types::lower_polymorphic_var_list;
+use crate::hir::types::{lower_ty_cons_param_list, resolve_declared_variance, TyConsParam};
 use crate::hir::util::{expect_ns_ident, expect_spanned_ns_ident};
 use crate::ty;
 use crate::ty::record;
 use crate::ty::Ty;
 
+/// Marker following the last field of a record with rest fields
+const REST_FIELDS_MARKER: &str = "&rest-fields";
+
 enum LoweredRecordCons {
     Parameterised(Span, Ident, NsDataIter),
     Singleton(Span, Ident),
@@ -96,7 +99,7 @@ pub fn lower_record(
         LoweredRecordCons::Singleton(span, ident) => (span, ident, None),
         LoweredRecordCons::Parameterised(span, ident, param_data_iter) => {
             let poly_params =
-                lower_polymorphic_var_list(outer_scope, &mut inner_scope, param_data_iter)?;
+                lower_ty_cons_param_list(outer_scope, &mut inner_scope, param_data_iter)?;
 
             (span, ident, Some(poly_params))
         }
@@ -107,13 +110,31 @@ pub fn lower_record(
         lower_record_cons_decl(value_cons_datum, ErrorKind::ExpectedRecordValueConsDecl)?;
 
     let fields: Box<[record::Field]>;
+    let has_rest_fields: bool;
     let (value_cons_ident_span, value_cons_ident) = match value_cons_decl {
         LoweredRecordCons::Singleton(_, _) => {
             todo!("singleton record values");
         }
         LoweredRecordCons::Parameterised(span, ident, param_data_iter) => {
-            fields = param_data_iter
-                .map(|field_datum| lower_record_field_decl(&inner_scope, field_datum))
+            let mut field_data: Vec<NsDatum> = param_data_iter.collect();
+
+            has_rest_fields = matches!(
+                field_data.last(),
+                Some(NsDatum::Ident(_, ident)) if ident.name().as_ref() == REST_FIELDS_MARKER
+            );
+
+            if has_rest_fields {
+                field_data.pop();
+            }
+
+            fields = field_data
+                .into_iter()
+                .map(|field_datum| match field_datum {
+                    NsDatum::Ident(span, ident) if ident.name().as_ref() == REST_FIELDS_MARKER => {
+                        Err(Error::new(span, ErrorKind::MisplacedRestFields))
+                    }
+                    field_datum => lower_record_field_decl(&inner_scope, field_datum),
+                })
                 .collect::<Result<Box<_>>>()?;
 
             (span, ident)
@@ -123,7 +144,7 @@ pub fn lower_record(
     // Convert our lowered polymorphic vars to polymorphic parameters
     let poly_params_list = match poly_vars {
         Some(poly_vars) => {
-            use crate::ty::var_usage::VarUsages;
+            use crate::ty::var_usage::{VarUsages, Variance};
 
             let mut var_usages = VarUsages::new();
             for field in fields.iter() {
@@ -131,12 +152,18 @@ pub fn lower_record(
             }
 
             let poly_params_list = poly_vars
-                .into_vec()
                 .into_iter()
-                .map(|poly_var| {
-                    match poly_var {
+                .map(|ty_cons_param| {
+                    let TyConsParam {
+                        declared_variance,
+                        polymorphic_var,
+                    } = ty_cons_param;
+
+                    match polymorphic_var {
                         PolymorphicVar::PVar(pvar) => {
                             if let Some(variance) = var_usages.pvar_variance(&pvar) {
+                                let variance =
+                                    resolve_declared_variance(declared_variance, variance)?;
                                 Ok(record::PolyParam::PVar(variance, pvar))
                             } else {
                                 Err(Error::new(
@@ -147,15 +174,22 @@ pub fn lower_record(
                         }
                         // It'd be nice to check if the param was used but it's been erased to
                         // `Pure` by this point
-                        PolymorphicVar::Pure(span) => Ok(record::PolyParam::Pure(span)),
+                        PolymorphicVar::Pure(span) => {
+                            // Fixed parameters are always invariant
+                            resolve_declared_variance(declared_variance, Variance::Invariant)?;
+                            Ok(record::PolyParam::Pure(span))
+                        }
                         PolymorphicVar::TVar(tvar) => {
                             if let Some(variance) = var_usages.tvar_variance(&tvar) {
+                                let variance =
+                                    resolve_declared_variance(declared_variance, variance)?;
                                 Ok(record::PolyParam::TVar(variance, tvar))
                             } else {
                                 Err(Error::new(tvar.span(), ErrorKind::UnusedPolyTyParam(tvar)))
                             }
                         }
                         PolymorphicVar::TFixed(span, fixed_poly) => {
+                            resolve_declared_variance(declared_variance, Variance::Invariant)?;
                             Ok(record::PolyParam::TFixed(span, fixed_poly))
                         }
                     }
@@ -170,13 +204,23 @@ pub fn lower_record(
     let predicate_name: DataStr = format!("{}?", value_cons_ident.name()).into();
     let predicate_ident = Ident::new(value_cons_ident.ns_id(), predicate_name);
 
-    let record_ty_cons = record::Cons::new(
-        ty_cons_span,
-        ty_ident.name().clone(),
-        value_cons_ident.name().clone(),
-        poly_params_list,
-        fields,
-    );
+    let record_ty_cons = if has_rest_fields {
+        record::Cons::new_with_rest_fields(
+            ty_cons_span,
+            ty_ident.name().clone(),
+            value_cons_ident.name().clone(),
+            poly_params_list,
+            fields,
+        )
+    } else {
+        record::Cons::new(
+            ty_cons_span,
+            ty_ident.name().clone(),
+            value_cons_ident.name().clone(),
+            poly_params_list,
+            fields,
+        )
+    };
 
     for (idx, field) in record_ty_cons.fields().iter().enumerate() {
         if field.name().as_ref() != "_" {
@@ -1,17 +1,25 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
-use arret_syntax::span::Span;
+use codespan_reporting::diagnostic::Diagnostic;
 
-use crate::context::ModuleId;
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::{FileId, Span};
+
+use crate::context::{ModuleId, ModuleImports};
 use crate::hir::error::{Error, ErrorKind};
+use crate::hir::exports::Exports;
+use crate::hir::lint;
 use crate::hir::macros::Macro;
 use crate::hir::ns::{Ident, NsDatum, NsId, NsIdCounter};
 use crate::hir::prim::Prim;
-use crate::hir::{types, LocalId};
+use crate::hir::{types, LocalId, TyHole};
+use crate::source::SourceLoader;
 use crate::ty;
+use crate::ty::newtype;
 use crate::ty::purity;
 use crate::ty::record;
+use crate::ty::recursive;
 
 #[derive(Clone, Debug)]
 pub enum Binding {
@@ -24,8 +32,12 @@ pub enum Binding {
     EqPred,
     RecordValueCons(record::ConsId),
     RecordTyCons(record::ConsId),
+    UserTyCons(recursive::ConsId),
     FieldAccessor(record::ConsId, usize),
+    NewtypeWrap(newtype::NewtypeId),
+    NewtypeUnwrap(newtype::NewtypeId),
     Purity(purity::Ref),
+    SyntaxParam(Arc<SyntaxParam>),
 }
 
 impl Binding {
@@ -35,11 +47,14 @@ impl Binding {
             Binding::Prim(_) => "primitive",
             Binding::Macro(_, _) => "macro",
             Binding::Ty(_) => "type",
-            Binding::TyCons(_) => "type constructor",
+            Binding::TyCons(_) | Binding::UserTyCons(_) => "type constructor",
             Binding::RecordValueCons(_) => "record value constructor",
             Binding::RecordTyCons(_) => "record type constructor",
             Binding::FieldAccessor(_, _) => "record field accessor",
+            Binding::NewtypeWrap(_) => "newtype wrapper",
+            Binding::NewtypeUnwrap(_) => "newtype unwrapper",
             Binding::Purity(_) => "purity",
+            Binding::SyntaxParam(_) => "syntax parameter",
         }
     }
 
@@ -52,6 +67,24 @@ impl Binding {
     }
 }
 
+/// Syntax parameter defined with `(def-syntax-param)`
+///
+/// These are identified by their allocation; the name is only used for diagnostics.
+#[derive(Debug)]
+pub struct SyntaxParam {
+    name: DataStr,
+}
+
+impl SyntaxParam {
+    pub fn new(name: DataStr) -> Arc<Self> {
+        Arc::new(Self { name })
+    }
+
+    pub fn name(&self) -> &DataStr {
+        &self.name
+    }
+}
+
 pub struct SpannedBinding {
     span: Option<Span>,
     binding: Binding,
@@ -62,6 +95,20 @@ pub struct Scope<'parent> {
 
     entries: HashMap<Ident, SpannedBinding>,
     parent: Option<&'parent Scope<'parent>>,
+
+    /// Bindings for syntax parameters rebound by `(syntax-parameterize)` in this scope
+    syntax_param_bindings: Vec<(Arc<SyntaxParam>, Binding)>,
+
+    /// Idents of our entries that have been looked up
+    referenced_idents: Mutex<HashSet<Ident>>,
+
+    source_loader: Option<&'parent SourceLoader>,
+    imports: Option<&'parent ModuleImports>,
+    target_triple: Option<&'parent str>,
+    macro_invocation_span: Option<Span>,
+    warnings: Option<&'parent Mutex<Vec<Diagnostic<FileId>>>>,
+    warn_shadowing: bool,
+    ty_holes: Option<&'parent Mutex<Vec<TyHole>>>,
 }
 
 impl<'parent> Scope<'parent> {
@@ -90,6 +137,18 @@ impl<'parent> Scope<'parent> {
             ns_id_counter: NsIdCounter::new(),
             entries,
             parent: None,
+
+            syntax_param_bindings: vec![],
+
+            referenced_idents: Mutex::new(HashSet::new()),
+
+            source_loader: None,
+            imports: None,
+            target_triple: None,
+            macro_invocation_span: None,
+            warnings: None,
+            warn_shadowing: false,
+            ty_holes: None,
         }
     }
 
@@ -100,6 +159,29 @@ impl<'parent> Scope<'parent> {
         Self::new_with_entries(entries)
     }
 
+    /// Creates a root scope containing the exports of a prelude module
+    ///
+    /// Module scopes should be created as a child of this scope using `child_with_import`. This
+    /// allows modules to shadow bindings from their prelude.
+    pub fn new_with_prelude(prelude_module_id: ModuleId, exports: &Exports) -> Scope<'static> {
+        let mut scope = Self::new_with_entries(std::iter::empty());
+
+        scope.entries = exports
+            .iter()
+            .map(|(name, binding)| {
+                (
+                    Ident::new(Self::root_ns_id(), name.clone()),
+                    SpannedBinding {
+                        span: None,
+                        binding: binding.import_from(prelude_module_id),
+                    },
+                )
+            })
+            .collect();
+
+        scope
+    }
+
     /// Creates a new root scope containing all primitives and types
     pub fn new_with_primitives() -> Scope<'static> {
         use crate::hir::prim::PRIM_EXPORTS;
@@ -118,9 +200,159 @@ impl<'parent> Scope<'parent> {
             ns_id_counter: self.ns_id_counter.clone(),
             entries: HashMap::new(),
             parent: Some(self),
+
+            syntax_param_bindings: vec![],
+
+            referenced_idents: Mutex::new(HashSet::new()),
+
+            source_loader: self.source_loader,
+            imports: self.imports,
+            target_triple: self.target_triple,
+            macro_invocation_span: self.macro_invocation_span,
+            warnings: self.warnings,
+            warn_shadowing: self.warn_shadowing,
+            ty_holes: self.ty_holes,
+        }
+    }
+
+    /// Creates a child scope containing `import`
+    ///
+    /// This is equivalent to `Scope::root()` layered on top of an existing scope.
+    pub fn child_with_import(&'parent self) -> Scope<'parent> {
+        let mut child = self.child();
+
+        child.entries.insert(
+            Ident::new(Self::root_ns_id(), "import".into()),
+            SpannedBinding {
+                span: None,
+                binding: Binding::Prim(Prim::ImportPlaceholder),
+            },
+        );
+
+        child
+    }
+
+    /// Returns this scope with a source loader for resolving the source locations of spans
+    ///
+    /// The source loader is inherited by child scopes.
+    pub fn with_source_loader<'a>(self, source_loader: &'a SourceLoader) -> Scope<'a>
+    where
+        'parent: 'a,
+    {
+        Scope {
+            source_loader: Some(source_loader),
+            ..self
         }
     }
 
+    /// Returns the source loader for resolving the source locations of spans
+    pub fn source_loader(&self) -> Option<&'parent SourceLoader> {
+        self.source_loader
+    }
+
+    /// Returns this scope with the imports of the module being lowered
+    ///
+    /// These are required to evaluate macro functions. The imports are inherited by child scopes.
+    pub fn with_imports<'a>(self, imports: &'a ModuleImports) -> Scope<'a>
+    where
+        'parent: 'a,
+    {
+        Scope {
+            imports: Some(imports),
+            ..self
+        }
+    }
+
+    /// Returns the imports of the module being lowered
+    pub fn imports(&self) -> Option<&'parent ModuleImports> {
+        self.imports
+    }
+
+    /// Returns this scope with the target triple used for conditional compilation
+    ///
+    /// The target triple is inherited by child scopes. If it isn't specified the host's target is
+    /// used.
+    pub fn with_target_triple<'a>(self, target_triple: Option<&'a str>) -> Scope<'a>
+    where
+        'parent: 'a,
+    {
+        Scope {
+            target_triple,
+            ..self
+        }
+    }
+
+    /// Returns if a conditional compilation pattern matches our target
+    pub fn target_matches(&self, pattern: &str) -> bool {
+        crate::hir::target::target_matches(self.target_triple, pattern)
+    }
+
+    /// Returns this scope with a list to collect lint warnings in
+    ///
+    /// The list is inherited by child scopes. Without it no warnings are reported.
+    pub fn with_warnings<'a>(self, warnings: &'a Mutex<Vec<Diagnostic<FileId>>>) -> Scope<'a>
+    where
+        'parent: 'a,
+    {
+        Scope {
+            warnings: Some(warnings),
+            ..self
+        }
+    }
+
+    /// Returns this scope with warnings for shadowed bindings enabled or disabled
+    ///
+    /// This is inherited by child scopes. Warnings are only reported if the scope has a list to
+    /// collect them in.
+    pub fn with_warn_shadowing(self, warn_shadowing: bool) -> Self {
+        Scope {
+            warn_shadowing,
+            ..self
+        }
+    }
+
+    /// Returns this scope with a list to collect type holes in
+    ///
+    /// The list is inherited by child scopes. Without it type holes are treated identically to
+    /// omitted type annotations.
+    pub fn with_ty_holes<'a>(self, ty_holes: &'a Mutex<Vec<TyHole>>) -> Scope<'a>
+    where
+        'parent: 'a,
+    {
+        Scope {
+            ty_holes: Some(ty_holes),
+            ..self
+        }
+    }
+
+    /// Records a type hole if type holes are being collected
+    pub fn add_ty_hole(&self, ty_hole: TyHole) {
+        if let Some(ty_holes) = self.ty_holes {
+            ty_holes.lock().unwrap().push(ty_hole);
+        }
+    }
+
+    /// Reports a lint warning if warnings are being collected
+    ///
+    /// Spans without a file can't be reported and are ignored.
+    fn warn(&self, span: Span, warning: impl FnOnce() -> Diagnostic<FileId>) {
+        if let (Some(warnings), Some(_)) = (self.warnings, span.file_id()) {
+            warnings.lock().unwrap().push(warning());
+        }
+    }
+
+    /// Marks this scope as containing the expansion of a macro invoked at `span`
+    ///
+    /// If this scope is already inside a macro expansion the outermost invocation is kept.
+    pub fn enter_macro_invocation(&mut self, span: Span) {
+        self.macro_invocation_span.get_or_insert(span);
+    }
+
+    /// Returns the span of the outermost macro invocation this scope was expanded from
+    pub fn macro_invocation_span(&self) -> Option<Span> {
+        self.macro_invocation_span
+    }
+
     /// Returns the binding for a given datum if it exists
     ///
     /// Only idents can have bindings; other data will return None.
@@ -133,14 +365,84 @@ impl<'parent> Scope<'parent> {
     }
 
     /// Returns the binding for a given ident if it exists
+    ///
+    /// Syntax parameters are replaced with their binding from the innermost enclosing
+    /// `(syntax-parameterize)`, if any.
     pub fn get<'a>(&'a self, ident: &Ident) -> Option<&'a Binding> {
-        self.entries.get(ident).map(|e| &e.binding).or_else(|| {
-            if let Some(parent) = self.parent {
-                parent.get(ident)
-            } else {
-                None
+        let binding = self.get_unparameterized(ident)?;
+
+        if let Binding::SyntaxParam(syntax_param) = binding {
+            if let Some(param_binding) = self.syntax_param_binding(syntax_param) {
+                return Some(param_binding);
             }
-        })
+        }
+
+        Some(binding)
+    }
+
+    /// Returns the binding for a given ident without replacing syntax parameters
+    ///
+    /// Macro templates use this to refer to the syntax parameter itself. This allows the macro's
+    /// expansion to rebind it wherever the macro is invoked.
+    pub fn get_unparameterized<'a>(&'a self, ident: &Ident) -> Option<&'a Binding> {
+        if let Some(entry) = self.entries.get(ident) {
+            self.referenced_idents.lock().unwrap().insert(ident.clone());
+            return Some(&entry.binding);
+        }
+
+        self.parent
+            .and_then(|parent| parent.get_unparameterized(ident))
+    }
+
+    fn syntax_param_binding<'a>(&'a self, syntax_param: &Arc<SyntaxParam>) -> Option<&'a Binding> {
+        self.syntax_param_bindings
+            .iter()
+            .rev()
+            .find(|(bound_param, _)| Arc::ptr_eq(bound_param, syntax_param))
+            .map(|(_, binding)| binding)
+            .or_else(|| {
+                self.parent
+                    .and_then(|parent| parent.syntax_param_binding(syntax_param))
+            })
+    }
+
+    /// Rebinds a syntax parameter for this scope and its children
+    pub fn parameterize_syntax(&mut self, syntax_param: Arc<SyntaxParam>, binding: Binding) {
+        self.syntax_param_bindings.push((syntax_param, binding));
+    }
+
+    /// Returns if any of the passed idents bound in this scope have been looked up
+    pub fn any_referenced<'a>(&self, mut idents: impl Iterator<Item = &'a Ident>) -> bool {
+        let referenced_idents = self.referenced_idents.lock().unwrap();
+        idents.any(|ident| referenced_idents.contains(ident))
+    }
+
+    /// Reports a warning for each local variable in this scope that was never referenced
+    ///
+    /// Variables introduced by macros and variables with names beginning with `_` are ignored.
+    pub fn warn_unreferenced_locals(&self) {
+        let referenced_idents = self.referenced_idents.lock().unwrap();
+
+        let mut unreferenced_locals: Vec<(Span, &DataStr)> = self
+            .entries
+            .iter()
+            .filter_map(|(ident, entry)| match (entry.span, &entry.binding) {
+                (Some(span), Binding::Var(None, _))
+                    if ident.ns_id() == Self::root_ns_id()
+                        && !ident.name().starts_with('_')
+                        && !referenced_idents.contains(ident) =>
+                {
+                    Some((span, ident.name()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        unreferenced_locals.sort_by_key(|(span, _)| span.start());
+
+        for (span, name) in unreferenced_locals {
+            self.warn(span, || lint::unused_variable_warning(span, name));
+        }
     }
 
     /// Returns the binding for a given ident if it exists, otherwise returns an error
@@ -206,9 +508,38 @@ impl<'parent> Scope<'parent> {
         ident: Ident,
         local_id: LocalId,
     ) -> Result<(), Error> {
+        if self.warn_shadowing
+            && ident.ns_id() == Self::root_ns_id()
+            && !ident.name().starts_with('_')
+        {
+            // Expansions such as `(as->)` rebind the same source datum repeatedly
+            let shadowed_span = self
+                .parent
+                .and_then(|parent| parent.binding_span(&ident))
+                .filter(|shadowed_span| {
+                    shadowed_span.file_id().is_some() && *shadowed_span != span
+                });
+
+            if let Some(shadowed_span) = shadowed_span {
+                self.warn(span, || {
+                    lint::shadowed_binding_warning(span, shadowed_span, ident.name())
+                });
+            }
+        }
+
         self.insert_binding(span, ident, Binding::Var(None, local_id))
     }
 
+    /// Returns the span where an ident was bound if it's known
+    ///
+    /// Unlike `get` this doesn't count as a reference to the binding.
+    fn binding_span(&self, ident: &Ident) -> Option<Span> {
+        match self.entries.get(ident) {
+            Some(entry) => entry.span,
+            None => self.parent.and_then(|parent| parent.binding_span(ident)),
+        }
+    }
+
     /// Returns all bound idents
     pub fn bound_idents(&self) -> impl Iterator<Item = &Ident> {
         self.entries.iter().map(|(ident, _)| ident)
@@ -0,0 +1,81 @@
+//! Matching of conditional compilation patterns against the compile target
+
+/// Operating systems belonging to the `unix` target family
+const UNIX_OSES: &[&str] = &[
+    "linux",
+    "darwin",
+    "macos",
+    "ios",
+    "android",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+];
+
+/// Returns the names a target pattern can match for the passed target triple
+///
+/// This includes the full triple, each of its components and its target family. If no target
+/// triple is specified the names are taken from the host the compiler is running on.
+fn target_names(target_triple: Option<&str>) -> Vec<&str> {
+    use std::env::consts;
+
+    let mut names: Vec<&str> = if let Some(target_triple) = target_triple {
+        std::iter::once(target_triple)
+            .chain(target_triple.split('-'))
+            .collect()
+    } else if consts::OS == "macos" {
+        // Target triples call macOS `darwin`
+        vec![consts::ARCH, consts::OS, "darwin"]
+    } else {
+        vec![consts::ARCH, consts::OS]
+    };
+
+    if names.iter().any(|name| UNIX_OSES.contains(name)) {
+        names.push("unix");
+    }
+
+    names
+}
+
+/// Returns if a conditional compilation pattern matches the target triple
+///
+/// Patterns can be a full target triple such as `"x86_64-unknown-linux-gnu"`, a single component
+/// of the triple such as `"wasm32"` or `"linux"`, or the target family `"unix"`.
+pub fn target_matches(target_triple: Option<&str>, pattern: &str) -> bool {
+    target_names(target_triple).contains(&pattern)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn explicit_target_triple() {
+        let linux_triple = Some("x86_64-unknown-linux-gnu");
+
+        assert!(target_matches(linux_triple, "x86_64-unknown-linux-gnu"));
+        assert!(target_matches(linux_triple, "x86_64"));
+        assert!(target_matches(linux_triple, "linux"));
+        assert!(target_matches(linux_triple, "unix"));
+        assert!(!target_matches(linux_triple, "windows"));
+        assert!(!target_matches(linux_triple, "x86"));
+
+        let wasm_triple = Some("wasm32-unknown-unknown");
+        assert!(target_matches(wasm_triple, "wasm32"));
+        assert!(!target_matches(wasm_triple, "unix"));
+
+        let windows_triple = Some("x86_64-pc-windows-msvc");
+        assert!(target_matches(windows_triple, "windows"));
+        assert!(!target_matches(windows_triple, "unix"));
+    }
+
+    #[test]
+    fn host_target() {
+        assert!(target_matches(None, std::env::consts::ARCH));
+        assert!(target_matches(None, std::env::consts::OS));
+        assert!(!target_matches(None, "no-such-target"));
+    }
+}
@@ -0,0 +1,100 @@
+//! Pipeline threading primitives
+//!
+//! `(->)`, `(->>)` and `(as->)` are expanded to nested applications before they're lowered. They
+//! only rearrange the user's own data; the one identifier they introduce is the `let` used by
+//! `(as->)`. That is bound in a fresh namespace so it can't capture or be shadowed by the user's
+//! identifiers.
+
+use arret_syntax::span::Span;
+
+use crate::hir::error::{Error, ErrorKind, Result};
+use crate::hir::ns::{Ident, NsDataIter, NsDatum};
+use crate::hir::prim::Prim;
+use crate::hir::scope::{Binding, Scope};
+
+/// Argument position the threaded value is inserted at
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThreadPosition {
+    /// Thread as the first argument like `(->)`
+    First,
+    /// Thread as the last argument like `(->>)`
+    Last,
+}
+
+impl ThreadPosition {
+    fn usage(self) -> &'static str {
+        match self {
+            ThreadPosition::First => "(-> value-expr form ...)",
+            ThreadPosition::Last => "(->> value-expr form ...)",
+        }
+    }
+}
+
+/// Inserts `value_datum` in to a threading form
+///
+/// Lists have the value inserted as an argument while any other form is applied to the value.
+fn thread_form(position: ThreadPosition, value_datum: NsDatum, form_datum: NsDatum) -> NsDatum {
+    match form_datum {
+        NsDatum::List(span, vs) if !vs.is_empty() => {
+            let mut vs = vs.into_vec();
+
+            match position {
+                ThreadPosition::First => vs.insert(1, value_datum),
+                ThreadPosition::Last => vs.push(value_datum),
+            }
+
+            NsDatum::List(span, vs.into_boxed_slice())
+        }
+        other => NsDatum::List(other.span(), Box::new([other, value_datum])),
+    }
+}
+
+/// Expands `(->)` or `(->>)` in to nested applications
+pub fn expand_thread(
+    span: Span,
+    position: ThreadPosition,
+    mut arg_iter: NsDataIter,
+) -> Result<NsDatum> {
+    let value_datum = arg_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoThreadedValue(position.usage())))?;
+
+    Ok(arg_iter.fold(value_datum, |value_datum, form_datum| {
+        thread_form(position, value_datum, form_datum)
+    }))
+}
+
+/// Expands `(as->)` in to nested `(let)`s binding each intermediate value to a name
+///
+/// `scope` should be the scope of the expansion. It receives the binding for the introduced `let`.
+pub fn expand_thread_as(
+    scope: &mut Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<NsDatum> {
+    const USAGE: &str = "(as-> value-expr name form ...)";
+
+    let value_datum = arg_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoThreadedValue(USAGE)))?;
+
+    let name_datum = arg_iter
+        .next()
+        .ok_or_else(|| Error::new(span, ErrorKind::NoThreadedValue(USAGE)))?;
+
+    let let_ident = Ident::new(scope.alloc_ns_id(), "let".into());
+    scope.replace_binding(span, let_ident.clone(), Binding::Prim(Prim::Let));
+
+    Ok(arg_iter.fold(value_datum, |value_datum, form_datum| {
+        let form_span = form_datum.span();
+
+        NsDatum::List(
+            form_span,
+            Box::new([
+                NsDatum::Ident(span, let_ident.clone()),
+                NsDatum::Vector(form_span, Box::new([name_datum.clone(), value_datum])),
+                form_datum,
+            ]),
+        )
+    }))
+}
@@ -1,3 +1,6 @@
+use std::cmp;
+
+use arret_syntax::datum::DataStr;
 use arret_syntax::span::Span;
 
 use crate::hir::error::{
@@ -10,10 +13,13 @@ use crate::hir::util::{
     expect_arg_count, expect_one_arg, expect_spanned_ns_ident, try_take_rest_arg,
 };
 use crate::ty;
+use crate::ty::newtype;
 use crate::ty::purity;
 use crate::ty::purity::Purity;
 use crate::ty::record;
+use crate::ty::recursive;
 use crate::ty::ty_args::TyArgs;
+use crate::ty::var_usage::{VarUsages, Variance};
 use crate::ty::Ty;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -21,9 +27,13 @@ pub enum TyCons {
     List,
     Vector,
     Vectorof,
+    Values,
     Set,
     Map,
+    KeyedMap,
+    Overload,
     Union,
+    IntRange,
     #[cfg(test)]
     RawU,
 }
@@ -41,6 +51,62 @@ struct LoweredPolymorphicVar {
     polymorphic_var: PolymorphicVar,
 }
 
+/// Variance explicitly declared for a type constructor parameter
+#[derive(Clone, Copy)]
+pub struct DeclaredVariance {
+    pub span: Span,
+    pub variance: Variance,
+}
+
+/// Type constructor parameter with an optional declared variance
+pub struct TyConsParam {
+    pub declared_variance: Option<DeclaredVariance>,
+    pub polymorphic_var: PolymorphicVar,
+}
+
+/// Lowers a type variable declared with multiple bounds such as `[A Num (RawU Int Str)]`
+///
+/// The variable is bounded by the intersection of its bounds.
+fn lower_multi_bound_tvar(
+    scope: &Scope<'_>,
+    vector_span: Span,
+    arg_data: Vec<NsDatum>,
+) -> Result<LoweredPolymorphicVar> {
+    let mut arg_iter = arg_data.into_iter();
+
+    let (ident_span, ident) =
+        expect_spanned_ns_ident(arg_iter.next().unwrap(), "new polymorphic parameter name")?;
+
+    if ident.is_underscore() {
+        return Err(Error::new(ident_span, ErrorKind::AnonymousPolymorphicParam));
+    }
+
+    let bounds = arg_iter
+        .map(|bound_datum| {
+            if try_lower_purity(scope, &bound_datum).is_some() {
+                return Err(Error::new(bound_datum.span(), ErrorKind::VarPurityBound));
+            }
+
+            lower_poly(scope, bound_datum)
+        })
+        .collect::<Result<Box<[ty::Ref<ty::Poly>]>>>()?;
+
+    let source_name = ident.name().clone();
+    let tvar = ty::TVar::new_with_bounds(vector_span, source_name, bounds)
+        .map_err(|_| Error::new(vector_span, ErrorKind::DisjointPolyVarBounds))?;
+
+    let polymorphic_var = if ty::props::has_subtypes(tvar.bound()) {
+        PolymorphicVar::TVar(tvar)
+    } else {
+        PolymorphicVar::TFixed(vector_span, tvar.bound().clone())
+    };
+
+    Ok(LoweredPolymorphicVar {
+        ident,
+        polymorphic_var,
+    })
+}
+
 fn lower_polymorphic_var(scope: &Scope<'_>, tvar_datum: NsDatum) -> Result<LoweredPolymorphicVar> {
     let span = tvar_datum.span();
 
@@ -63,6 +129,10 @@ fn lower_polymorphic_var(scope: &Scope<'_>, tvar_datum: NsDatum) -> Result<Lower
         NsDatum::Vector(vector_span, vs) => {
             let mut arg_data = vs.into_vec();
 
+            if arg_data.len() > 2 {
+                return lower_multi_bound_tvar(scope, vector_span, arg_data);
+            }
+
             if arg_data.len() == 2 {
                 let bound_datum = arg_data.pop().unwrap();
                 let (ident_span, ident) = expect_spanned_ns_ident(
@@ -159,6 +229,68 @@ fn lower_fun_cons(
     Ok(ty::Fun::new(purity::PVars::new(), ty::TVars::new(), top_fun, params).into())
 }
 
+/// Lowers `(KeyedMap :key Value ... & Key Value)` where the trailing open map is optional
+fn lower_keyed_map_cons(
+    scope: &Scope<'_>,
+    span: Span,
+    mut arg_iter: NsDataIter,
+) -> Result<ty::KeyedMap<ty::Poly>> {
+    let mut entries: Vec<(DataStr, ty::Ref<ty::Poly>)> = vec![];
+    let mut rest = None;
+
+    while let Some(key_datum) = arg_iter.next() {
+        match key_datum {
+            NsDatum::Keyword(key_span, key) => {
+                if entries.iter().any(|(existing_key, _)| existing_key == &key) {
+                    return Err(Error::new(key_span, ErrorKind::DuplicateKeyedMapKey(key)));
+                }
+
+                let value_datum = arg_iter
+                    .next()
+                    .ok_or_else(|| Error::new(span, ErrorKind::BadKeyedMap))?;
+
+                entries.push((key, lower_poly(scope, value_datum)?));
+            }
+            NsDatum::Ident(_, ident) if ident.is_ampersand() && arg_iter.len() == 2 => {
+                let key_ty = lower_poly(scope, arg_iter.next().unwrap())?;
+                let value_ty = lower_poly(scope, arg_iter.next().unwrap())?;
+                rest = Some(ty::Map::new(key_ty, value_ty));
+            }
+            other => {
+                return Err(Error::new(other.span(), ErrorKind::BadKeyedMap));
+            }
+        }
+    }
+
+    Ok(ty::KeyedMap::new(entries.into_boxed_slice(), rest))
+}
+
+/// Lowers `(Overload case-fun-type ...)` in to an intersection of its cases
+fn lower_overload_cons(
+    scope: &Scope<'_>,
+    span: Span,
+    arg_iter: NsDataIter,
+) -> Result<ty::Ref<ty::Poly>> {
+    let mut case_tys = arg_iter
+        .map(|arg_datum| {
+            let case_span = arg_datum.span();
+
+            match lower_poly(scope, arg_datum)? {
+                ty::Ref::Fixed(Ty::Fun(fun)) if !fun.has_polymorphic_vars() => {
+                    Ok(Ty::Fun(fun).into())
+                }
+                _ => Err(Error::new(case_span, ErrorKind::BadOverloadCase)),
+            }
+        })
+        .collect::<Result<Vec<ty::Ref<ty::Poly>>>>()?;
+
+    match case_tys.len() {
+        0 => Err(Error::new(span, ErrorKind::BadOverloadCase)),
+        1 => Ok(case_tys.pop().unwrap()),
+        _ => Ok(Ty::Intersect(case_tys.into_boxed_slice()).into()),
+    }
+}
+
 fn lower_ty_cons_apply(
     scope: &Scope<'_>,
     span: Span,
@@ -174,6 +306,13 @@ fn lower_ty_cons_apply(
 
             Ty::Vector(member_tys).into()
         }
+        TyCons::Values => {
+            let member_tys = arg_iter
+                .map(|arg_datum| lower_poly(scope, arg_datum))
+                .collect::<Result<Box<[ty::Ref<ty::Poly>]>>>()?;
+
+            Ty::Values(member_tys).into()
+        }
         TyCons::Vectorof => {
             let start_datum = expect_one_arg(span, arg_iter)?;
             let start_ty = lower_poly(scope, start_datum)?;
@@ -190,6 +329,8 @@ fn lower_ty_cons_apply(
             let value_ty = lower_poly(scope, arg_iter.next().unwrap())?;
             ty::Map::new(key_ty, value_ty).into()
         }
+        TyCons::KeyedMap => lower_keyed_map_cons(scope, span, arg_iter)?.into(),
+        TyCons::Overload => lower_overload_cons(scope, span, arg_iter)?,
         TyCons::Union => {
             let member_tys = arg_iter
                 .map(|arg_datum| lower_poly(scope, arg_datum))
@@ -197,6 +338,18 @@ fn lower_ty_cons_apply(
 
             ty::unify::unify_ty_ref_iter(member_tys.into_iter())
         }
+        TyCons::IntRange => {
+            expect_arg_count(span, 2, arg_iter.len())?;
+
+            match (arg_iter.next().unwrap(), arg_iter.next().unwrap()) {
+                (NsDatum::Int(_, start), NsDatum::Int(_, end)) if start <= end => {
+                    ty::IntRange::new(start, end).into()
+                }
+                _ => {
+                    return Err(Error::new(span, ErrorKind::BadIntRange));
+                }
+            }
+        }
         #[cfg(test)]
         TyCons::RawU => {
             // This performs a union *without* unifying the types. This is used when testing the
@@ -234,7 +387,7 @@ fn lower_record_ty_cons_apply(
     record_cons: &record::ConsId,
     arg_iter: NsDataIter,
 ) -> Result<ty::Ref<ty::Poly>> {
-    use crate::ty::is_a::{ty_ref_is_a, ty_refs_equivalent};
+    use crate::ty::is_a::ty_refs_equivalent;
     use std::collections::HashMap;
 
     expect_arg_count(span, record_cons.poly_params().len(), arg_iter.len())?;
@@ -264,10 +417,10 @@ fn lower_record_ty_cons_apply(
             }
             record::PolyParam::TVar(_, tvar) => {
                 let arg_type = lower_poly(scope, arg_datum)?;
-                if !ty_ref_is_a(&arg_type, tvar.bound()) {
+                if let Some(unsatisfied_bound) = tvar.unsatisfied_bound(&arg_type) {
                     let details = Box::new(PolyArgIsNotTy {
                         arg_type,
-                        param_bound: tvar.bound().clone(),
+                        param_bound: unsatisfied_bound.clone(),
                         param_span: tvar.span(),
                     });
 
@@ -294,6 +447,45 @@ fn lower_record_ty_cons_apply(
     Ok(record::Instance::new(record_cons.clone(), TyArgs::new(pvar_purities, tvar_types)).into())
 }
 
+fn lower_user_ty_cons_apply(
+    scope: &Scope<'_>,
+    span: Span,
+    cons: &recursive::ConsId,
+    arg_iter: NsDataIter,
+) -> Result<ty::Ref<ty::Poly>> {
+    expect_arg_count(span, cons.params().len(), arg_iter.len())?;
+
+    let ty_args = cons
+        .params()
+        .iter()
+        .zip(arg_iter)
+        .map(|(tvar, arg_datum)| {
+            let arg_span = arg_datum.span();
+            let arg_type = lower_poly(scope, arg_datum)?;
+
+            if let Some(unsatisfied_bound) = tvar.unsatisfied_bound(&arg_type) {
+                let details = Box::new(PolyArgIsNotTy {
+                    arg_type,
+                    param_bound: unsatisfied_bound.clone(),
+                    param_span: tvar.span(),
+                });
+
+                return Err(Error::new(arg_span, ErrorKind::PolyArgIsNotTy(details)));
+            }
+
+            Ok(arg_type)
+        })
+        .collect::<Result<Box<[ty::Ref<ty::Poly>]>>>()?;
+
+    // Constructors with declared variances keep their arguments to compare them by that variance
+    if cons.is_recursive() || cons.has_declared_variance() {
+        Ok(recursive::Instance::new(cons.clone(), ty_args).into())
+    } else {
+        // Non-recursive constructors are simple aliases for their definition
+        Ok(cons.apply(&ty_args).unwrap())
+    }
+}
+
 fn lower_literal_vec(literal_data: Vec<NsDatum>) -> Result<Vec<ty::Ref<ty::Poly>>> {
     literal_data.into_iter().map(lower_literal).collect()
 }
@@ -301,6 +493,7 @@ fn lower_literal_vec(literal_data: Vec<NsDatum>) -> Result<Vec<ty::Ref<ty::Poly>
 fn lower_literal(datum: NsDatum) -> Result<ty::Ref<ty::Poly>> {
     match datum {
         NsDatum::Bool(_, v) => Ok(Ty::LitBool(v).into()),
+        NsDatum::Int(_, v) => Ok(ty::IntRange::singleton(v).into()),
         NsDatum::Keyword(_, name) => Ok(Ty::LitSym(name).into()),
         NsDatum::Ident(_, ident) => Ok(Ty::LitSym(ident.into_name()).into()),
         NsDatum::List(_, vs) => {
@@ -316,6 +509,10 @@ fn lower_literal(datum: NsDatum) -> Result<ty::Ref<ty::Poly>> {
 }
 
 fn lower_ident(scope: &Scope<'_>, span: Span, ident: &Ident) -> Result<ty::Ref<ty::Poly>> {
+    if ident.is_underscore() {
+        return Err(Error::new(span, ErrorKind::NestedTyHole));
+    }
+
     match scope.get_or_err(span, ident)? {
         Binding::Ty(ty) => Ok(ty.clone()),
         Binding::TyPred(test_ty) => Ok(Ty::TyPred(test_ty.clone()).into()),
@@ -398,6 +595,7 @@ fn lower_poly_data_iter(
         Binding::RecordTyCons(record_cons) => {
             lower_record_ty_cons_apply(scope, span, record_cons, data_iter)
         }
+        Binding::UserTyCons(cons) => lower_user_ty_cons_apply(scope, span, cons, data_iter),
         other => Err(Error::new(
             ident_span,
             ErrorKind::ExpectedTyCons(other.description()),
@@ -413,6 +611,235 @@ pub fn lower_poly(scope: &Scope<'_>, datum: NsDatum) -> Result<ty::Ref<ty::Poly>
     }
 }
 
+/// Ensures a recursive type only refers to polymorphic variables it declares
+///
+/// Unfolding an instance only substitutes the constructor's own parameters. Any other variable would
+/// escape the scope it was declared in.
+fn ensure_no_captured_poly_vars(
+    span: Span,
+    cons: &recursive::ConsId,
+    ty_ref: &ty::Ref<ty::Poly>,
+) -> Result<()> {
+    let mut var_usages = VarUsages::new();
+    var_usages.add_poly_usages(ty_ref);
+
+    if let Some(pvar) = var_usages.pvars().next() {
+        return Err(Error::new(
+            span,
+            ErrorKind::RecursiveTyCapturesPolyVar(pvar.source_name().into(), pvar.span()),
+        ));
+    }
+
+    if let Some(tvar) = var_usages
+        .tvars()
+        .find(|tvar| !cons.params().contains(tvar))
+    {
+        return Err(Error::new(
+            span,
+            ErrorKind::RecursiveTyCapturesPolyVar(tvar.source_name().into(), tvar.span()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Lowers a type definition from `(deftype)` or `(lettype)`
+///
+/// `self_datum` is either a type name or a list of the type constructor name followed by its
+/// parameters. The definition may refer to the type being defined.
+pub fn lower_ty_def(scope: &mut Scope<'_>, self_datum: NsDatum, ty_datum: NsDatum) -> Result<()> {
+    let (self_span, self_ident, param_data) = match self_datum {
+        NsDatum::List(_, vs) if !vs.is_empty() => {
+            let mut data_iter = vs.into_vec().into_iter();
+            let (span, ident) =
+                expect_spanned_ns_ident(data_iter.next().unwrap(), "new type constructor name")?;
+
+            (span, ident, Some(data_iter))
+        }
+        other => {
+            let (span, ident) = expect_spanned_ns_ident(other, "new type name")?;
+            (span, ident, None)
+        }
+    };
+
+    let ty_span = ty_datum.span();
+    let mut inner_scope = scope.child();
+
+    let ty_cons_params = match param_data {
+        Some(param_data) => lower_ty_cons_param_list(scope, &mut inner_scope, param_data)?,
+        None => vec![],
+    };
+
+    let mut params = Vec::with_capacity(ty_cons_params.len());
+    let mut declared_variances = Vec::with_capacity(ty_cons_params.len());
+
+    for ty_cons_param in ty_cons_params {
+        match ty_cons_param.polymorphic_var {
+            PolymorphicVar::TVar(tvar) => {
+                params.push(tvar);
+                declared_variances.push(ty_cons_param.declared_variance);
+            }
+            PolymorphicVar::PVar(pvar) => {
+                return Err(Error::new(pvar.span(), ErrorKind::NonTyDefParam));
+            }
+            PolymorphicVar::TFixed(span, _) | PolymorphicVar::Pure(span) => {
+                return Err(Error::new(span, ErrorKind::NonTyDefParam));
+            }
+        }
+    }
+
+    let is_ty_cons = !params.is_empty();
+    let cons = recursive::Cons::new(
+        self_span,
+        self_ident.name().clone(),
+        params.into_boxed_slice(),
+        declared_variances
+            .iter()
+            .map(|declared_variance| declared_variance.map(|declared| declared.variance))
+            .collect(),
+    );
+
+    let self_binding = if is_ty_cons {
+        Binding::UserTyCons(cons.clone())
+    } else {
+        Binding::Ty(recursive::Instance::new(cons.clone(), Box::new([])).into())
+    };
+    inner_scope.insert_binding(self_span, self_ident.clone(), self_binding.clone())?;
+
+    let ty_ref = lower_poly(&inner_scope, ty_datum)?;
+    let is_recursive = inner_scope.any_referenced(std::iter::once(&self_ident));
+
+    let mut var_usages = VarUsages::new();
+    var_usages.add_poly_usages(&ty_ref);
+
+    for (tvar, declared_variance) in cons.params().iter().zip(declared_variances) {
+        // Unused parameters can be declared with any variance
+        if let Some(used_variance) = var_usages.tvar_variance(tvar) {
+            resolve_declared_variance(declared_variance, used_variance)?;
+        }
+    }
+
+    if is_recursive {
+        if !recursive::is_guarded(&cons, &ty_ref) {
+            return Err(Error::new(
+                ty_span,
+                ErrorKind::UnguardedRecursiveTy(self_ident.into_name()),
+            ));
+        }
+
+        ensure_no_captured_poly_vars(self_span, &cons, &ty_ref)?;
+    }
+
+    let outer_binding = if is_recursive || is_ty_cons {
+        cons.set_definition(ty_ref, is_recursive);
+        self_binding
+    } else {
+        Binding::Ty(ty_ref)
+    };
+
+    scope.insert_binding(self_span, self_ident, outer_binding)?;
+    Ok(())
+}
+
+/// Lowers a newtype definition from `(defnewtype)`
+///
+/// Along with the newtype itself this binds `->Name` to wrap a value of the representation type
+/// and `<-Name` to unwrap it again.
+pub fn lower_newtype_def(
+    scope: &mut Scope<'_>,
+    self_datum: NsDatum,
+    repr_datum: NsDatum,
+) -> Result<()> {
+    let (self_span, self_ident) = expect_spanned_ns_ident(self_datum, "new type name")?;
+    let repr = lower_poly(scope, repr_datum)?;
+
+    let newtype = newtype::Newtype::new(self_span, self_ident.name().clone(), repr);
+
+    let wrap_name = format!("->{}", self_ident.name());
+    let unwrap_name = format!("<-{}", self_ident.name());
+
+    scope.insert_binding(
+        self_span,
+        Ident::new(self_ident.ns_id(), wrap_name.into()),
+        Binding::NewtypeWrap(newtype.clone()),
+    )?;
+
+    scope.insert_binding(
+        self_span,
+        Ident::new(self_ident.ns_id(), unwrap_name.into()),
+        Binding::NewtypeUnwrap(newtype.clone()),
+    )?;
+
+    scope.insert_binding(
+        self_span,
+        self_ident,
+        Binding::Ty(Ty::Newtype(newtype).into()),
+    )?;
+    Ok(())
+}
+
+/// Lowers a type predicate definition from `(defpred)`
+///
+/// This binds `Name?` to a predicate testing for the nominal type `Name`. Only records can be
+/// tested; newtypes and record type arguments are erased at runtime.
+pub fn lower_ty_pred_def(scope: &mut Scope<'_>, ty_datum: NsDatum) -> Result<()> {
+    let (ty_span, ty_ident) = expect_spanned_ns_ident(ty_datum, "type name")?;
+
+    let test_ty = match scope.get_or_err(ty_span, &ty_ident)? {
+        Binding::RecordTyCons(cons) => ty::pred::TestTy::RecordClass(cons.clone()),
+        Binding::Ty(ty_ref) => match ty_ref.try_to_fixed() {
+            Some(Ty::Record(instance)) => {
+                let ty_args = instance.ty_args();
+
+                if !ty_args.tvar_types().is_empty() || !ty_args.pvar_purities().is_empty() {
+                    return Err(Error::new(
+                        ty_span,
+                        ErrorKind::TypeErased(
+                            str_for_ty_ref(ty_ref).into(),
+                            "record type arguments are not stored at runtime".into(),
+                        ),
+                    ));
+                }
+
+                ty::pred::TestTy::RecordClass(instance.cons().clone())
+            }
+            Some(Ty::Newtype(newtype)) => {
+                return Err(Error::new(
+                    ty_span,
+                    ErrorKind::TypeErased(
+                        newtype.name().clone(),
+                        format!(
+                            "newtypes share their runtime representation with `{}`",
+                            str_for_ty_ref(newtype.repr())
+                        )
+                        .into(),
+                    ),
+                ));
+            }
+            _ => {
+                return Err(Error::new(
+                    ty_span,
+                    ErrorKind::NonNominalTyPred(str_for_ty_ref(ty_ref).into()),
+                ));
+            }
+        },
+        other => {
+            return Err(Error::new(
+                ty_span,
+                ErrorKind::ExpectedTy(other.description()),
+            ));
+        }
+    };
+
+    let pred_name = format!("{}?", ty_ident.name());
+
+    scope.insert_binding(
+        ty_span,
+        Ident::new(ty_ident.ns_id(), pred_name.into()),
+        Binding::TyPred(test_ty),
+    )
+}
+
 fn bind_polymorphic_vars(
     scope: &mut Scope<'_>,
     lowered_poly_vars: Vec<LoweredPolymorphicVar>,
@@ -487,6 +914,88 @@ pub fn lower_polymorphic_var_list(
     Ok(poly_vars)
 }
 
+/// Lowers the parameter list of a type constructor
+///
+/// Each parameter can be preceded by `:covariant`, `:contravariant` or `:invariant` to declare its
+/// variance.
+pub fn lower_ty_cons_param_list(
+    outer_scope: &Scope<'_>,
+    inner_scope: &mut Scope<'_>,
+    param_data: NsDataIter,
+) -> Result<Vec<TyConsParam>> {
+    let mut declared_variances = vec![];
+    let mut var_data = vec![];
+    let mut pending_variance: Option<DeclaredVariance> = None;
+
+    for param_datum in param_data {
+        if let NsDatum::Keyword(span, keyword) = param_datum {
+            if let Some(pending_variance) = pending_variance {
+                return Err(Error::new(
+                    pending_variance.span,
+                    ErrorKind::VarianceWithoutParam,
+                ));
+            }
+
+            let variance = match keyword.as_ref() {
+                ":covariant" => Variance::Covariant,
+                ":contravariant" => Variance::Contravariant,
+                ":invariant" => Variance::Invariant,
+                _ => return Err(Error::new(span, ErrorKind::UnknownVariance(keyword))),
+            };
+
+            pending_variance = Some(DeclaredVariance { span, variance });
+        } else {
+            declared_variances.push(pending_variance.take());
+            var_data.push(param_datum);
+        }
+    }
+
+    if let Some(pending_variance) = pending_variance {
+        return Err(Error::new(
+            pending_variance.span,
+            ErrorKind::VarianceWithoutParam,
+        ));
+    }
+
+    let poly_vars = lower_polymorphic_var_list(outer_scope, inner_scope, var_data.into_iter())?;
+
+    Ok(declared_variances
+        .into_iter()
+        .zip(poly_vars.into_vec())
+        .map(|(declared_variance, polymorphic_var)| TyConsParam {
+            declared_variance,
+            polymorphic_var,
+        })
+        .collect())
+}
+
+/// Returns the variance of a type constructor parameter from its declaration and usage
+///
+/// Declaring a parameter as invariant is always sound. Otherwise the declared variance must match
+/// how the parameter is used.
+pub fn resolve_declared_variance(
+    declared_variance: Option<DeclaredVariance>,
+    used_variance: Variance,
+) -> Result<Variance> {
+    match declared_variance {
+        None => Ok(used_variance),
+        Some(DeclaredVariance {
+            variance: Variance::Invariant,
+            ..
+        }) => Ok(Variance::Invariant),
+        Some(DeclaredVariance { span, variance }) => {
+            if variance == used_variance {
+                Ok(variance)
+            } else {
+                Err(Error::new(
+                    span,
+                    ErrorKind::DeclaredVarianceConflict(variance, used_variance),
+                ))
+            }
+        }
+    }
+}
+
 pub fn try_lower_purity(scope: &Scope<'_>, datum: &NsDatum) -> Option<purity::Ref> {
     scope.get_datum(datum).and_then(|binding| match binding {
         Binding::Purity(purity) => Some(purity.clone()),
@@ -520,6 +1029,7 @@ macro_rules! export_ty_pred {
 
 pub const TY_EXPORTS: &[(&str, Binding)] = &[
     export_ty!("Any", Ty::Any),
+    export_ty!("Dyn", Ty::Dyn),
     export_ty!("Bool", Ty::Bool),
     export_ty!("Sym", Ty::Sym),
     export_ty!("Str", Ty::Str),
@@ -528,12 +1038,21 @@ pub const TY_EXPORTS: &[(&str, Binding)] = &[
     export_ty!("Num", Ty::Num),
     export_ty!("Char", Ty::Char),
     export_ty!("Record", Ty::TopRecord),
+    export_ty!("FloatArray", Ty::FloatArray),
+    export_ty!("IntArray", Ty::IntArray),
+    export_ty!("Bytes", Ty::Bytes),
+    export_ty!("Rational", Ty::Rational),
+    export_ty!("BigInt", Ty::BigInt),
     export_ty_cons!("List", TyCons::List),
     export_ty_cons!("Vector", TyCons::Vector),
     export_ty_cons!("Vectorof", TyCons::Vectorof),
+    export_ty_cons!("Values", TyCons::Values),
     export_ty_cons!("Setof", TyCons::Set),
     export_ty_cons!("Map", TyCons::Map),
+    export_ty_cons!("KeyedMap", TyCons::KeyedMap),
+    export_ty_cons!("Overload", TyCons::Overload),
     export_ty_cons!("U", TyCons::Union),
+    export_ty_cons!("IntRange", TyCons::IntRange),
     export_purity!("->", Purity::Pure),
     export_purity!("->!", Purity::Impure),
     export_ty_pred!("str?", ty::pred::TestTy::Str),
@@ -550,6 +1069,11 @@ pub const TY_EXPORTS: &[(&str, Binding)] = &[
     export_ty_pred!("fn?", ty::pred::TestTy::Fun),
     export_ty_pred!("nil?", ty::pred::TestTy::Nil),
     export_ty_pred!("record?", ty::pred::TestTy::TopRecord),
+    export_ty_pred!("float-array?", ty::pred::TestTy::FloatArray),
+    export_ty_pred!("int-array?", ty::pred::TestTy::IntArray),
+    export_ty_pred!("bytes?", ty::pred::TestTy::Bytes),
+    export_ty_pred!("rational?", ty::pred::TestTy::Rational),
+    export_ty_pred!("big-int?", ty::pred::TestTy::BigInt),
     #[cfg(test)]
     export_ty_cons!("RawU", TyCons::RawU),
 ];
@@ -579,7 +1103,13 @@ fn str_for_bounds(bound_pvars: &[purity::PVarId], bound_tvars: &[ty::TVarId]) ->
             return tvar.source_name().into();
         }
 
-        format!("[{} {}]", tvar.source_name(), str_for_ty_ref(tvar.bound()))
+        let bound_parts = tvar
+            .bounds()
+            .iter()
+            .map(str_for_ty_ref)
+            .collect::<Vec<String>>();
+
+        format!("[{} {}]", tvar.source_name(), bound_parts.join(" "))
     });
 
     let all_parts = pvar_parts.chain(tvar_parts).collect::<Vec<String>>();
@@ -603,6 +1133,7 @@ fn str_for_record_poly_arg<M: ty::Pm>(
 fn str_for_ty<M: ty::Pm>(ty: &Ty<M>) -> String {
     match ty {
         Ty::Any => "Any".to_owned(),
+        Ty::Dyn => "Dyn".to_owned(),
         Ty::Bool => "Bool".to_owned(),
         Ty::Char => "Char".to_owned(),
         Ty::Int => "Int".to_owned(),
@@ -619,11 +1150,37 @@ fn str_for_ty<M: ty::Pm>(ty: &Ty<M>) -> String {
                 format!("'{}", name)
             }
         }
+        Ty::IntRange(int_range) => {
+            if let Some(value) = int_range.as_singleton() {
+                value.to_string()
+            } else {
+                format!("(IntRange {} {})", int_range.start(), int_range.end())
+            }
+        }
         Ty::Map(map) => format!(
             "(Map {} {})",
             str_for_ty_ref(map.key()),
             str_for_ty_ref(map.value())
         ),
+        Ty::KeyedMap(keyed_map) => {
+            let entry_parts = keyed_map
+                .entries()
+                .iter()
+                .map(|(key, value)| format!(" {} {}", key, str_for_ty_ref(value)));
+
+            let rest_part = keyed_map.rest().map(|rest| {
+                format!(
+                    " & {} {}",
+                    str_for_ty_ref(rest.key()),
+                    str_for_ty_ref(rest.value())
+                )
+            });
+
+            format!(
+                "(KeyedMap{})",
+                entry_parts.chain(rest_part).collect::<String>()
+            )
+        }
         Ty::Set(member) => format!("(Setof {})", str_for_ty_ref(member)),
         Ty::Vector(members) => {
             let result_parts: Vec<String> = members
@@ -634,6 +1191,19 @@ fn str_for_ty<M: ty::Pm>(ty: &Ty<M>) -> String {
             format!("(Vector{})", result_parts.join(""))
         }
         Ty::Vectorof(member) => format!("(Vectorof {})", str_for_ty_ref(member)),
+        Ty::Values(members) => {
+            let result_parts: Vec<String> = members
+                .iter()
+                .map(|member| format!(" {}", str_for_ty_ref(member)))
+                .collect();
+
+            format!("(Values{})", result_parts.join(""))
+        }
+        Ty::FloatArray => "FloatArray".to_owned(),
+        Ty::IntArray => "IntArray".to_owned(),
+        Ty::Bytes => "Bytes".to_owned(),
+        Ty::Rational => "Rational".to_owned(),
+        Ty::BigInt => "BigInt".to_owned(),
         Ty::TopFun(top_fun) => format!(
             "(... {} {})",
             str_for_purity(top_fun.purity()),
@@ -666,6 +1236,14 @@ fn str_for_ty<M: ty::Pm>(ty: &Ty<M>) -> String {
 
             format!("(U{})", member_strs.join(""))
         }
+        Ty::Intersect(members) if ty.overload_cases().is_some() => {
+            let case_strs: Vec<String> = members
+                .iter()
+                .map(|m| format!(" {}", str_for_ty_ref(m)))
+                .collect();
+
+            format!("(Overload{})", case_strs.join(""))
+        }
         Ty::Intersect(members) => {
             let member_strs: Vec<String> = members
                 .iter()
@@ -710,6 +1288,22 @@ fn str_for_ty<M: ty::Pm>(ty: &Ty<M>) -> String {
 
             format!("({})", record_parts.join(" "))
         }
+        Ty::Recursive(instance) => {
+            let cons_name = instance.cons().name();
+
+            if instance.ty_args().is_empty() {
+                cons_name.to_string()
+            } else {
+                let arg_strs: Vec<String> = instance
+                    .ty_args()
+                    .iter()
+                    .map(|ty_arg| format!(" {}", str_for_ty_ref(ty_arg)))
+                    .collect();
+
+                format!("({}{})", cons_name, arg_strs.join(""))
+            }
+        }
+        Ty::Newtype(newtype) => newtype.name().to_string(),
     }
 }
 
@@ -720,6 +1314,124 @@ pub fn str_for_ty_ref<M: ty::Pm>(ty_ref: &ty::Ref<M>) -> String {
     }
 }
 
+/// Types with string representations no longer than this are compared without a diff
+const MAX_UNDIFFED_TY_STR_LEN: usize = 40;
+
+/// Number of characters of context shown around the difference between two types
+const TY_DIFF_CONTEXT_LEN: usize = 16;
+
+/// Returns the index after the subterm of a type string starting at `start`
+///
+/// Subterms are either a single token or a bracketed form. If `start` is at the end of its
+/// enclosing form this returns `start`.
+fn ty_str_subterm_end(chars: &[char], start: usize) -> usize {
+    let mut depth = 0usize;
+
+    for (index, c) in chars.iter().enumerate().skip(start) {
+        match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+            }
+            ')' | ']' | '}' => {
+                if depth == 0 {
+                    return index;
+                }
+
+                depth -= 1;
+                if depth == 0 {
+                    return index + 1;
+                }
+            }
+            ' ' if depth == 0 => {
+                return index;
+            }
+            _ => {}
+        }
+    }
+
+    chars.len()
+}
+
+fn elided_ty_str(chars: &[char], elided_start: usize, end: usize) -> String {
+    let mut output = String::new();
+
+    if elided_start > 0 {
+        output.push_str("...");
+    }
+
+    let mut visible_end = end + TY_DIFF_CONTEXT_LEN;
+    if visible_end + "...".len() >= chars.len() {
+        visible_end = chars.len();
+    } else {
+        // Don't elide part of a token
+        while visible_end > end && chars[visible_end] != ' ' {
+            visible_end -= 1;
+        }
+    }
+
+    output.extend(&chars[elided_start..visible_end]);
+
+    if visible_end < chars.len() {
+        output.push_str("...");
+    }
+
+    output
+}
+
+/// Returns an aligned rendering of two large types marking where they first differ
+///
+/// Both types are printed with the same leading context so the marker applies to each line.
+/// This returns `None` if the types are small enough to be compared directly or are printed
+/// identically.
+pub fn str_for_ty_ref_diff<M: ty::Pm>(expected: &ty::Ref<M>, found: &ty::Ref<M>) -> Option<String> {
+    let expected_chars: Vec<char> = str_for_ty_ref(expected).chars().collect();
+    let found_chars: Vec<char> = str_for_ty_ref(found).chars().collect();
+
+    if cmp::max(expected_chars.len(), found_chars.len()) <= MAX_UNDIFFED_TY_STR_LEN
+        || expected_chars == found_chars
+    {
+        return None;
+    }
+
+    let common_len = expected_chars
+        .iter()
+        .zip(found_chars.iter())
+        .take_while(|(expected_char, found_char)| expected_char == found_char)
+        .count();
+
+    // Mark the whole subterm containing the first difference
+    let mut start = common_len;
+    while start > 0 && !matches!(expected_chars[start - 1], ' ' | '(' | '[' | '{') {
+        start -= 1;
+    }
+
+    let expected_end = ty_str_subterm_end(&expected_chars, start);
+    let found_end = ty_str_subterm_end(&found_chars, start);
+
+    // The leading context is identical so it can be elided from both
+    let mut elided_start = if start > TY_DIFF_CONTEXT_LEN + "...".len() {
+        start - TY_DIFF_CONTEXT_LEN
+    } else {
+        0
+    };
+
+    // Don't elide part of a token
+    while elided_start > 0 && elided_start < start && expected_chars[elided_start - 1] != ' ' {
+        elided_start += 1;
+    }
+
+    let marker_offset = start - elided_start + if elided_start > 0 { "...".len() } else { 0 };
+    let marker_len = cmp::max(1, cmp::max(expected_end, found_end) - start);
+
+    Some(format!(
+        "expected `{}`\n   found `{}`\n          {}{}",
+        elided_ty_str(&expected_chars, elided_start, expected_end),
+        elided_ty_str(&found_chars, elided_start, found_end),
+        " ".repeat(marker_offset),
+        "^".repeat(marker_len)
+    ))
+}
+
 pub fn str_for_purity(purity: &purity::Ref) -> String {
     match purity {
         purity::Ref::Fixed(Purity::Pure) => "->".to_owned(),
@@ -765,7 +1477,6 @@ mod test {
     use std::collections::HashMap;
 
     use crate::source::EMPTY_SPAN;
-    use crate::ty::var_usage::Variance;
 
     fn assert_ty_for_str(expected: Ty<ty::Poly>, datum_str: &str) {
         let expected_poly = expected.into();
@@ -816,6 +1527,27 @@ mod test {
         assert_exact_str_repr(j);
     }
 
+    #[test]
+    fn int_literal() {
+        let j = "-12";
+
+        let expected = ty::IntRange::singleton(-12).into();
+        assert_ty_for_str(expected, j);
+        assert_exact_str_repr(j);
+    }
+
+    #[test]
+    fn int_range_cons() {
+        let j = "(IntRange 0 9)";
+
+        let expected = ty::IntRange::new(0, 9).into();
+        assert_ty_for_str(expected, j);
+        assert_exact_str_repr(j);
+
+        // Single element ranges are equivalent to integer literals
+        assert_eq!(poly_for_str("5"), poly_for_str("(IntRange 5 5)"));
+    }
+
     #[test]
     fn empty_list_literal() {
         let j = "()";
@@ -1015,6 +1747,55 @@ mod test {
         assert_ty_for_str(expected, j);
     }
 
+    #[test]
+    fn overload_cons() {
+        let j = "(Overload (Int -> Int) (Str Str -> Str))";
+
+        let expected = Ty::Intersect(Box::new([
+            ty::Fun::new_mono(
+                ty::List::new_tuple(Box::new([Ty::Int.into()])),
+                Purity::Pure.into(),
+                Ty::Int.into(),
+            )
+            .into(),
+            ty::Fun::new_mono(
+                ty::List::new_tuple(Box::new([Ty::Str.into(), Ty::Str.into()])),
+                Purity::Pure.into(),
+                Ty::Str.into(),
+            )
+            .into(),
+        ]));
+
+        assert_ty_for_str(expected, j);
+        assert_exact_str_repr(j);
+
+        // A single case is a plain function type
+        assert_eq!(
+            poly_for_str("(Int -> Int)"),
+            poly_for_str("(Overload (Int -> Int))")
+        );
+    }
+
+    #[test]
+    fn keyed_map_cons() {
+        let j = "(KeyedMap :name Str :age Int)";
+
+        let expected = ty::KeyedMap::new(
+            Box::new([
+                (":name".into(), Ty::Str.into()),
+                (":age".into(), Ty::Int.into()),
+            ]),
+            None,
+        )
+        .into();
+
+        assert_ty_for_str(expected, j);
+
+        // Keys are printed in a consistent order
+        assert_exact_str_repr("(KeyedMap :age Int :name Str)");
+        assert_exact_str_repr("(KeyedMap :name Str & Sym Any)");
+    }
+
     #[test]
     fn merged_union_cons() {
         let j = "(UnifyingU true false)";
@@ -1031,9 +1812,57 @@ mod test {
         assert_exact_str_repr("(Float & Int -> Sym)");
     }
 
+    #[test]
+    fn ty_ref_diff_str() {
+        fn diff_for_strs(expected_str: &str, found_str: &str) -> Option<String> {
+            str_for_ty_ref_diff(&poly_for_str(expected_str), &poly_for_str(found_str))
+        }
+
+        // Small types are compared directly
+        assert_eq!(None, diff_for_strs("(Int -> Int)", "(Int -> Str)"));
+
+        assert_eq!(
+            Some(
+                [
+                    "expected `...Char Bool -> (List Int Int))`",
+                    "   found `...Char Bool -> Int)`",
+                    "                          ^^^^^^^^^^^^^^",
+                ]
+                .join("\n")
+            ),
+            diff_for_strs(
+                "(Int Float Str Sym Char Bool -> (List Int Int))",
+                "(Int Float Str Sym Char Bool -> Int)"
+            )
+        );
+
+        assert_eq!(
+            Some(
+                [
+                    "expected `...Float Str Sym Char Bool Bytes Float)`",
+                    "   found `...Float Str Sym Str Bool Bytes Float)`",
+                    "                           ^^^^",
+                ]
+                .join("\n")
+            ),
+            diff_for_strs(
+                "(List Int Float Str Sym Char Bool Bytes Float)",
+                "(List Int Float Str Sym Str Bool Bytes Float)"
+            )
+        );
+    }
+
     #[test]
     fn polymorphic_fun_str() {
         assert_exact_str_repr("(All #{[->? ->!] A [B Bool] C} B C ->? A)");
+
+        // Every bound of a type variable with multiple bounds is printed
+        assert_eq!(
+            "(All #{[A Num (U Int Float Sym)]} A -> A)",
+            str_for_ty_ref(&poly_for_str(
+                "(All #{[A Num (RawU Int Float Sym)]} A -> A)"
+            ))
+        );
     }
 
     #[test]
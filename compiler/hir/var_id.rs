@@ -9,6 +9,21 @@ use crate::context::ModuleId;
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
 pub struct LocalId(NonZeroU32);
 
+impl LocalId {
+    /// Returns the raw value of the ID
+    ///
+    /// Lowering allocates local IDs in a fixed order. This value is stable between compilations of
+    /// an unchanged module.
+    pub fn get(self) -> u32 {
+        self.0.get()
+    }
+
+    /// Recreates a local ID from its raw value
+    pub fn from_raw(raw_id: NonZeroU32) -> Self {
+        LocalId(raw_id)
+    }
+}
+
 /// Identifier for a variable exported from another module
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
 pub struct ExportId(ModuleId, LocalId);
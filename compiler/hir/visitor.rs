@@ -38,14 +38,29 @@ where
             visit_exprs(&hir_let.value_expr, visitor);
             visit_exprs(&hir_let.body_expr, visitor);
         }
-        ExprKind::Do(exprs) => {
+        ExprKind::Letrec(letrec) => {
+            for binding in &letrec.bindings {
+                visit_exprs(&binding.value_expr, visitor);
+            }
+            visit_exprs(&letrec.body_expr, visitor);
+        }
+        ExprKind::Do(exprs) | ExprKind::Values(_, exprs) => {
             for expr in exprs {
                 visit_exprs(expr, visitor);
             }
         }
-        ExprKind::MacroExpand(_, expr) => {
+        ExprKind::MacroExpand(_, expr) | ExprKind::Box(_, expr) => {
             visit_exprs(expr, visitor);
         }
+        ExprKind::Unbox(unbox) => {
+            visit_exprs(&unbox.value_expr, visitor);
+        }
+        ExprKind::DynCheck(dyn_check) => {
+            visit_exprs(&dyn_check.value_expr, visitor);
+        }
+        ExprKind::Cast(cast) => {
+            visit_exprs(&cast.value_expr, visitor);
+        }
         ExprKind::ExportRef(_, _)
         | ExprKind::LocalRef(_, _)
         | ExprKind::Lit(_)
@@ -53,7 +68,8 @@ where
         | ExprKind::TyPred(_, _)
         | ExprKind::EqPred(_)
         | ExprKind::RecordCons(_, _)
-        | ExprKind::FieldAccessor(_) => {
+        | ExprKind::FieldAccessor(_)
+        | ExprKind::NoClauseMatch(_) => {
             // Terminal expression
         }
     };
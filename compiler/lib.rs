@@ -1,22 +1,47 @@
+//! Compiler for the Arret language
+//!
+//! The frontend loads, lowers and type checks programs. Tools that only need to analyse programs
+//! can use [`infer_program`], [`program_type_map`] and [`program_dep_graph`] along with the
+//! [`CompileCtx`] and [`SourceLoader`] they're built on. Errors are reported as
+//! [`codespan_reporting`] diagnostics.
+//!
+//! The backend evaluates and generates code for programs using LLVM. It's enabled by the default
+//! `backend` feature; disabling it allows the frontend to be used without linking against LLVM.
+//! Macros implemented with `macro-fn` require the backend to be expanded.
+//!
+//! Only the items exported from the crate root are part of its public API. Changes to them follow
+//! semantic versioning.
+
 #![warn(clippy::all)]
 #![warn(rust_2018_idioms)]
+// Much of the frontend's internal API only exists for the backend
+#![cfg_attr(not(feature = "backend"), allow(dead_code))]
 
 #[macro_use]
 mod id_type;
 
 mod arret_root;
+#[cfg(feature = "backend")]
 mod codegen;
 mod context;
+pub mod crash;
+mod dep_graph;
 mod hir;
+#[cfg(feature = "backend")]
 mod mir;
+mod opt_level;
 mod promise;
+#[cfg(feature = "backend")]
 pub mod repl;
 mod reporting;
 mod rfi;
 mod source;
+pub mod trace;
 mod ty;
+mod type_map;
 mod typeck;
 
+#[cfg(feature = "backend")]
 use std::collections::HashSet;
 use std::sync::Arc;
 
@@ -25,23 +50,87 @@ use codespan_reporting::diagnostic::Diagnostic;
 use arret_syntax::span::FileId;
 
 pub use crate::arret_root::{find_arret_root, FindArretRootError};
+#[cfg(feature = "backend")]
 pub use crate::codegen::initialise_llvm;
+#[cfg(feature = "backend")]
 pub use crate::codegen::program::{gen_program, Options as GenProgramOptions, OutputType};
 pub use crate::context::{CompileCtx, LinkedLibrary};
-pub use crate::hir::PackagePaths;
+pub use crate::dep_graph::{program_dep_graph, DepGraph};
+pub use crate::hir::{ModuleName, PackagePaths, ParseModuleNameError};
 pub use crate::id_type::ArcId;
+#[cfg(feature = "backend")]
 pub use crate::mir::eval_hir::{BuiltProgram, EvalHirCtx};
+#[cfg(feature = "backend")]
+pub use crate::mir::parse_program as parse_program_mir;
+#[cfg(feature = "backend")]
 pub use crate::mir::print_program as print_program_mir;
+#[cfg(feature = "backend")]
+pub use crate::mir::verify::verify_program as verify_program_mir;
+pub use crate::opt_level::{OptLevel, ParseOptLevelError};
 pub use crate::reporting::emit_diagnostics_to_stderr;
-pub use crate::source::{SourceFile, SourceLoader, SourceText};
+pub use crate::source::{LineCol, LineColRange, SourceFile, SourceLoader, SourceText};
+pub use crate::type_map::{
+    program_type_map, write_program_def_types, TypeMap, TypeMapArity, TypeMapEntry,
+};
+
+/// Program that has been lowered and type checked
+pub struct InferredProgram {
+    entry_module: context::Module,
+}
+
+impl InferredProgram {
+    pub(crate) fn entry_module(&self) -> &context::Module {
+        &self.entry_module
+    }
+
+    /// Returns if the program's entry module defines a `main!` function
+    pub fn has_main(&self) -> bool {
+        self.entry_module.main_local_id.is_some()
+    }
+
+    /// Returns the lint warnings for the program's entry module and the modules it imports
+    ///
+    /// Warnings for imported modules come first. Modules from the standard library aren't linted.
+    pub fn warnings(&self) -> Vec<Diagnostic<FileId>> {
+        let mut imported_modules: Vec<Arc<context::Module>> =
+            context::transitive_deps(&self.entry_module.imports)
+                .into_iter()
+                .collect();
+
+        imported_modules.sort_by_key(|module| module.module_id.get());
+
+        imported_modules
+            .iter()
+            .flat_map(|module| module.warnings.iter())
+            .chain(self.entry_module.warnings.iter())
+            .cloned()
+            .collect()
+    }
+}
 
+/// Lowers and type checks a program along with all of the modules it imports
+///
+/// This doesn't require the `backend` feature. All errors found in the program are returned as
+/// diagnostics.
+pub fn infer_program(
+    ccx: &CompileCtx,
+    source_file: &SourceFile,
+) -> Result<InferredProgram, Vec<Diagnostic<FileId>>> {
+    let entry_module = ccx.source_file_to_module(source_file)?;
+    Ok(InferredProgram { entry_module })
+}
+
+#[cfg(feature = "backend")]
 pub struct EvaluableProgram {
     pub ehx: EvalHirCtx,
     pub main_export_id: hir::ExportId,
     pub linked_libraries: Vec<Arc<LinkedLibrary>>,
+    /// Lint warnings for the program's modules
+    pub warnings: Vec<Diagnostic<FileId>>,
 }
 
 /// Visits a subtree of modules, evaluates their definitions and collects their RFI libraries
+#[cfg(feature = "backend")]
 fn include_imports(
     ehx: &mut EvalHirCtx,
     visited_modules: &mut HashSet<context::ModuleId>,
@@ -68,6 +157,20 @@ fn include_imports(
     Ok(())
 }
 
+/// Returns the diagnostic for an entry module without a `main!` function
+pub(crate) fn missing_main_diagnostic(source_file: &SourceFile) -> Diagnostic<FileId> {
+    use codespan_reporting::diagnostic::Label;
+
+    Diagnostic::error()
+        .with_message("no main! function defined in entry module")
+        .with_labels(vec![Label::primary(source_file.file_id(), 0..1)
+            .with_message("main! function expected in this file")])
+        .with_notes(vec![
+            "consider adding `(defn main! () ->! () ...)` to this file".to_owned(),
+        ])
+}
+
+#[cfg(feature = "backend")]
 pub fn program_to_evaluable(
     ccx: &CompileCtx,
     source_file: &SourceFile,
@@ -76,18 +179,13 @@ pub fn program_to_evaluable(
 
     use crate::typeck::infer;
 
-    let entry_module = ccx.source_file_to_module(source_file)?;
-
-    let main_local_id = if let Some(local_id) = entry_module.main_local_id {
-        local_id
-    } else {
-        use codespan_reporting::diagnostic::Label;
+    let inferred_program = infer_program(ccx, source_file)?;
+    let warnings = inferred_program.warnings();
+    let entry_module = inferred_program.entry_module;
 
-        return Err(vec![Diagnostic::error()
-            .with_message("no main! function defined in entry module")
-            .with_labels(vec![Label::primary(source_file.file_id(), 0..1)
-                .with_message("main! function expected in this file")])]);
-    };
+    let main_local_id = entry_module
+        .main_local_id
+        .ok_or_else(|| vec![missing_main_diagnostic(source_file)])?;
 
     let inferred_main_type = &entry_module.inferred_locals[&main_local_id];
 
@@ -99,7 +197,9 @@ pub fn program_to_evaluable(
     )
     .map_err(|err| vec![err.into()])?;
 
-    let mut ehx = EvalHirCtx::new(ccx.enable_optimisations());
+    let mut ehx = EvalHirCtx::new(ccx.opt_level())
+        .with_warn_boxing(ccx.warn_boxing())
+        .with_verify_mir(ccx.verify_mir());
     let mut linked_libraries = vec![];
     let mut visited_modules = HashSet::new();
 
@@ -123,5 +223,6 @@ pub fn program_to_evaluable(
         ehx,
         main_export_id: hir::ExportId::new(entry_module.module_id, main_local_id),
         linked_libraries,
+        warnings,
     })
 }
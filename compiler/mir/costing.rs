@@ -22,6 +22,7 @@ pub fn cost_for_op_category(category: ops::OpCategory) -> OpCost {
         OpCategory::Cond => 5, // Adjusted below to include branches
         OpCategory::MakeCallback => 5,
         OpCategory::MemLoad => 5,
+        OpCategory::MemStore => 5,
         OpCategory::Ret => 5,
         OpCategory::Call => 9, // Adjusted below based on the call purity
         // This is tricky. This could either do a stack allocation (which is cheap) or a heap
@@ -61,22 +61,35 @@ pub fn calculate_env_values(
     local_values: &HashMap<hir::LocalId, Value>,
     capturing_expr: &hir::Expr<hir::Inferred>,
     source_name: Option<&DataStr>,
+) -> EnvValues {
+    calculate_shared_env_values(local_values, std::iter::once(capturing_expr), source_name)
+}
+
+/// Calculates the values captured from the environment by any of the passed expressions
+///
+/// This is used by functions that share their captures, such as those bound by a `(letrec)`.
+pub fn calculate_shared_env_values<'a>(
+    local_values: &HashMap<hir::LocalId, Value>,
+    capturing_exprs: impl Iterator<Item = &'a hir::Expr<hir::Inferred>>,
+    source_name: Option<&DataStr>,
 ) -> EnvValues {
     let mut captured_values = HashMap::new();
 
     // Only process captures if there are local values. This is to avoid visiting the expression
     // when capturing isn't possible
     if !local_values.is_empty() {
-        // Look for references to variables inside the function
-        hir::visitor::visit_exprs(capturing_expr, &mut |expr| {
-            if let hir::ExprKind::LocalRef(_, local_id) = &expr.kind {
-                if !captured_values.contains_key(local_id) {
-                    if let Some(value) = local_values.get(local_id) {
-                        captured_values.insert(*local_id, value.clone());
+        for capturing_expr in capturing_exprs {
+            // Look for references to variables inside the function
+            hir::visitor::visit_exprs(capturing_expr, &mut |expr| {
+                if let hir::ExprKind::LocalRef(_, local_id) = &expr.kind {
+                    if !captured_values.contains_key(local_id) {
+                        if let Some(value) = local_values.get(local_id) {
+                            captured_values.insert(*local_id, value.clone());
+                        }
                     }
                 }
-            }
-        });
+            });
+        }
     }
 
     // Determine which captures are constants
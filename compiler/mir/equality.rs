@@ -302,7 +302,7 @@ pub fn values_statically_equal(
                     // Functions never compare equal
                     boxed::TypeTag::FunThunk => true,
                     // NaN != NaN
-                    boxed::TypeTag::Float => true,
+                    boxed::TypeTag::Float | boxed::TypeTag::FloatArray => true,
                     // Can contain partial equal values
                     boxed::TypeTag::Pair
                     | boxed::TypeTag::Record
@@ -317,7 +317,11 @@ pub fn values_statically_equal(
                     | boxed::TypeTag::Sym
                     | boxed::TypeTag::True
                     | boxed::TypeTag::False
-                    | boxed::TypeTag::Nil => false,
+                    | boxed::TypeTag::Nil
+                    | boxed::TypeTag::IntArray
+                    | boxed::TypeTag::Bytes
+                    | boxed::TypeTag::Rational
+                    | boxed::TypeTag::BigInt => false,
                 }
             }) {
                 if [left_reg, right_reg]
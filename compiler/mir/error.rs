@@ -4,6 +4,7 @@ use codespan_reporting::diagnostic::Diagnostic;
 
 use arret_syntax::span::{FileId, Span};
 
+use crate::crash::Unsupported;
 use crate::mir::inliner::ApplyCookie;
 use crate::reporting::{new_primary_label, LocTrace};
 
@@ -25,6 +26,10 @@ impl Panic {
 #[derive(Debug, PartialEq)]
 pub enum Error {
     Panic(Panic),
+    /// Valid program construct the compiler doesn't support yet
+    Unsupported(Unsupported),
+    /// Value of a `(def-const)` couldn't be folded to a constant
+    NonConstDef(Span),
     /// Internal error used to abort a recursive function application when a loop is detected
     AbortRecursion(ApplyCookie),
     /// Internal error indicating that a divergent value was encountered
@@ -45,6 +50,12 @@ impl Error {
     }
 }
 
+impl From<Unsupported> for Error {
+    fn from(unsupported: Unsupported) -> Error {
+        Error::Unsupported(unsupported)
+    }
+}
+
 impl From<Error> for Diagnostic<FileId> {
     fn from(error: Error) -> Self {
         if let Error::Panic(panic) = error {
@@ -58,6 +69,20 @@ impl From<Error> for Diagnostic<FileId> {
             return panic.loc_trace.label_macro_invocation(diagnostic);
         }
 
+        if let Error::Unsupported(unsupported) = error {
+            return unsupported.into();
+        }
+
+        if let Error::NonConstDef(span) = error {
+            return Diagnostic::error()
+                .with_message("`def-const` value is not a compile-time constant")
+                .with_labels(vec![new_primary_label(span, "non-constant definition")])
+                .with_notes(vec![
+                    "constants can only contain literals, lists and records of constants"
+                        .to_owned(),
+                ]);
+        }
+
         panic!(
             "attempted to convert an internal {:?} flow control error to a diagnostic",
             error
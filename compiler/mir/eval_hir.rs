@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::{alloc, ffi, panic};
 
+use codespan_reporting::diagnostic::Diagnostic;
+
 use arret_runtime::boxed;
 use arret_runtime::boxed::prelude::*;
 use arret_runtime::boxed::refs::Gc;
@@ -11,10 +13,11 @@ use arret_runtime::intern::{AsInterner, Interner};
 use arret_runtime::abitype;
 use arret_runtime_syntax::reader;
 use arret_syntax::datum::{DataStr, Datum};
-use arret_syntax::span::Span;
+use arret_syntax::span::{FileId, Span};
 
 use crate::codegen;
 use crate::context::ModuleId;
+use crate::crash;
 use crate::hir;
 use crate::mir::builder::{Builder, BuiltReg, TryToBuilder};
 use crate::mir::error::{Error, Result};
@@ -24,9 +27,13 @@ use crate::mir::polymorph::PolymorphAbi;
 use crate::mir::value;
 use crate::mir::value::synthetic_fun::SyntheticFuns;
 use crate::mir::value::types::TypeHint;
+use crate::mir::verify;
 use crate::mir::{Expr, Value};
+use crate::opt_level::OptLevel;
+use crate::reporting::new_primary_label;
 use crate::rfi;
 use crate::source::EMPTY_SPAN;
+use crate::trace;
 use crate::ty;
 use crate::ty::purity;
 use crate::ty::purity::Purity;
@@ -54,6 +61,8 @@ pub struct EvaledRecordClass {
 }
 
 pub struct EvalHirCtx {
+    opt_level: OptLevel,
+    verify_mir: bool,
     runtime_task: arret_runtime::task::Task,
     global_values: HashMap<hir::ExportId, Value>,
 
@@ -72,6 +81,12 @@ pub struct EvalHirCtx {
 
     pub(super) record_class_for_cons: HashMap<record::ConsId, EvaledRecordClass>,
     cons_for_jit_record_class_id: HashMap<boxed::RecordClassId, record::ConsId>,
+
+    /// Record constructors defined by the visited definitions in the order they were found
+    program_record_conses: Vec<record::ConsId>,
+
+    /// Locations where unboxed values were implicitly boxed, if they're being recorded
+    implicit_boxings: Option<Vec<(Span, abitype::AbiType)>>,
 }
 
 /// Context for performing a tail call in `(recur)`
@@ -127,6 +142,9 @@ struct BuiltCondBranch {
 pub struct BuiltProgram {
     pub main: ops::Fun,
     pub private_funs: HashMap<ops::PrivateFunId, ops::Fun>,
+
+    /// Warnings for implicit boxing if they were enabled with `EvalHirCtx::with_warn_boxing`
+    pub boxing_warnings: Vec<Diagnostic<FileId>>,
 }
 
 impl BuiltProgram {
@@ -142,6 +160,21 @@ impl BuiltProgram {
     }
 }
 
+fn implicit_boxing_warning(span: Span, from_abi_type: &abitype::AbiType) -> Diagnostic<FileId> {
+    let type_name = match from_abi_type {
+        abitype::AbiType::Int => "Int",
+        abitype::AbiType::Float => "Float",
+        _ => "Bool",
+    };
+
+    Diagnostic::warning()
+        .with_message(format!("implicit boxing of `{}`", type_name))
+        .with_labels(vec![new_primary_label(span, "value boxed here")])
+        .with_notes(vec![
+            "use `(box)` if the boxed representation is intended".to_owned()
+        ])
+}
+
 #[derive(Clone)]
 pub(super) struct ApplyArgs<'tyargs> {
     ty_args: &'tyargs TyArgs<ty::Poly>,
@@ -196,10 +229,12 @@ fn merge_apply_ty_args_into_scope(
 }
 
 impl EvalHirCtx {
-    pub fn new(optimising: bool) -> EvalHirCtx {
-        let thunk_jit = codegen::jit::JitCtx::new(optimising);
+    pub fn new(opt_level: OptLevel) -> EvalHirCtx {
+        let thunk_jit = codegen::jit::JitCtx::new(opt_level);
 
         EvalHirCtx {
+            opt_level,
+            verify_mir: cfg!(debug_assertions),
             runtime_task: arret_runtime::task::Task::new(),
             global_values: HashMap::new(),
 
@@ -217,6 +252,62 @@ impl EvalHirCtx {
 
             record_class_for_cons: HashMap::new(),
             cons_for_jit_record_class_id: HashMap::new(),
+            program_record_conses: vec![],
+
+            implicit_boxings: None,
+        }
+    }
+
+    /// Returns this context with warnings for implicit boxing enabled or disabled
+    ///
+    /// The warnings are returned in `BuiltProgram::boxing_warnings`.
+    pub fn with_warn_boxing(self, warn_boxing: bool) -> Self {
+        Self {
+            implicit_boxings: if warn_boxing { Some(vec![]) } else { None },
+            ..self
+        }
+    }
+
+    pub fn opt_level(&self) -> OptLevel {
+        self.opt_level
+    }
+
+    /// Returns this context with MIR verification enabled or disabled
+    ///
+    /// When enabled every function is verified as it's built and optimised. A function failing
+    /// verification causes a panic. This defaults to enabled in debug builds.
+    pub fn with_verify_mir(self, verify_mir: bool) -> Self {
+        Self { verify_mir, ..self }
+    }
+
+    pub fn verify_mir(&self) -> bool {
+        self.verify_mir
+    }
+
+    /// Records that a value with the passed ABI type is being implicitly boxed
+    ///
+    /// Only `Int`, `Float` and `Bool` values are reported. Other unboxed types are either rare or
+    /// ephemeral.
+    pub(super) fn note_implicit_boxing(&mut self, span: Span, from_abi_type: &abitype::AbiType) {
+        let implicit_boxings = if let Some(implicit_boxings) = &mut self.implicit_boxings {
+            implicit_boxings
+        } else {
+            return;
+        };
+
+        let is_reported_type = matches!(
+            from_abi_type,
+            abitype::AbiType::Int | abitype::AbiType::Float | abitype::AbiType::Bool
+        );
+
+        // Inlined functions can box at the same location multiple times
+        if is_reported_type
+            && span.file_id().is_some()
+            && !implicit_boxings
+                .iter()
+                .any(|(other_span, _)| *other_span == span)
+        {
+            implicit_boxings.push((span, from_abi_type.clone()));
         }
     }
 
@@ -233,39 +324,115 @@ impl EvalHirCtx {
     }
 
     fn destruc_list<F>(
+        &mut self,
         b: &mut Option<Builder>,
         span: Span,
         list: &hir::destruc::List<hir::Inferred>,
         value: Value,
         insert_local: &mut F,
-    ) where
+    ) -> Result<()>
+    where
         F: FnMut(hir::LocalId, Value),
     {
         let mut iter = value.into_unsized_list_iter();
 
         for fixed_destruc in list.fixed() {
             let value = iter.next_unchecked(b, span);
-            Self::destruc_value(b, fixed_destruc, value, insert_local);
+            self.destruc_value(b, fixed_destruc, value, insert_local)?;
         }
 
         if let Some(rest_destruc) = list.rest() {
             Self::destruc_scalar(rest_destruc, iter.into_rest(), insert_local)
         }
+
+        Ok(())
+    }
+
+    fn destruc_vector<F>(
+        &mut self,
+        b: &mut Option<Builder>,
+        span: Span,
+        vector: &hir::destruc::Vector<hir::Inferred>,
+        value: Value,
+        insert_local: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(hir::LocalId, Value),
+    {
+        use crate::mir::vector_member::load_vector_member;
+
+        let members = vector.members();
+
+        for (index, member_destruc) in members.iter().enumerate() {
+            let member_value = match &value {
+                Value::Const(any_ref) => any_ref
+                    .downcast_ref::<boxed::Vector<boxed::Any>>()
+                    .and_then(|vector_ref| vector_ref.get(index))
+                    .map(Value::Const),
+                _ => None,
+            };
+
+            let member_value = match member_value {
+                Some(member_value) => member_value,
+                None => load_vector_member(
+                    self,
+                    b.as_mut().expect("destructuring vector without builder"),
+                    span,
+                    members.len(),
+                    &value,
+                    index,
+                ),
+            };
+
+            self.destruc_value(b, member_destruc, member_value, insert_local)?;
+        }
+
+        Ok(())
+    }
+
+    fn destruc_map<F>(
+        &mut self,
+        b: &mut Option<Builder>,
+        span: Span,
+        map: &hir::destruc::Map<hir::Inferred>,
+        value: Value,
+        insert_local: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(hir::LocalId, Value),
+    {
+        use crate::mir::map_member::load_map_member;
+
+        for (key_name, value_destruc) in map.entries() {
+            let member_value = load_map_member(self, b, span, &value, key_name)?;
+            self.destruc_value(b, value_destruc, member_value, insert_local)?;
+        }
+
+        Ok(())
     }
 
     fn destruc_value<F>(
+        &mut self,
         b: &mut Option<Builder>,
         destruc: &hir::destruc::Destruc<hir::Inferred>,
         value: Value,
         insert_local: &mut F,
-    ) where
+    ) -> Result<()>
+    where
         F: FnMut(hir::LocalId, Value),
     {
         use crate::hir::destruc::Destruc;
 
         match destruc {
-            Destruc::Scalar(_, scalar) => Self::destruc_scalar(scalar, value, insert_local),
-            Destruc::List(span, list) => Self::destruc_list(b, *span, list, value, insert_local),
+            Destruc::Scalar(_, scalar) => {
+                Self::destruc_scalar(scalar, value, insert_local);
+                Ok(())
+            }
+            Destruc::List(span, list) => self.destruc_list(b, *span, list, value, insert_local),
+            Destruc::Vector(span, vector) => {
+                self.destruc_vector(b, *span, vector, value, insert_local)
+            }
+            Destruc::Map(span, map) => self.destruc_map(b, *span, map, value, insert_local),
         }
     }
 
@@ -274,10 +441,14 @@ impl EvalHirCtx {
 
         match destruc {
             Destruc::Scalar(_, scalar) => Some(scalar.source_name()),
-            Destruc::List(_, _) => None,
+            Destruc::List(_, _) | Destruc::Vector(_, _) | Destruc::Map(_, _) => None,
         }
     }
 
+    fn def_trace_name(source_name: Option<&DataStr>) -> String {
+        source_name.map_or_else(|| "<destructure>".to_owned(), |name| name.to_string())
+    }
+
     fn eval_local_ref(&self, fcx: &FunCtx<'_>, local_id: hir::LocalId) -> Value {
         // Try local values
         if let Some(local_value) = fcx.local_values.get(&local_id) {
@@ -308,6 +479,90 @@ impl EvalHirCtx {
             .try_fold(initial_value, |_, expr| self.eval_expr(fcx, b, expr))
     }
 
+    fn eval_values(
+        &mut self,
+        fcx: &mut FunCtx<'_>,
+        b: &mut Option<Builder>,
+        exprs: &[Expr],
+    ) -> Result<Value> {
+        // These are only boxed if they're used as a list
+        let member_values = exprs
+            .iter()
+            .map(|expr| self.eval_expr(fcx, b, expr))
+            .collect::<Result<Box<[Value]>>>()?;
+
+        Ok(Value::List(member_values, None))
+    }
+
+    fn eval_box(
+        &mut self,
+        fcx: &mut FunCtx<'_>,
+        b: &mut Option<Builder>,
+        span: Span,
+        value_expr: &Expr,
+    ) -> Result<Value> {
+        use crate::mir::specific_abi_type::specific_boxed_abi_type_for_type_tags;
+        use crate::mir::value::build_reg::reg_to_boxed_reg;
+
+        let value = self.eval_expr(fcx, b, value_expr)?;
+
+        match (b, &value) {
+            (Some(b), Value::Reg(reg_value))
+                if !matches!(reg_value.abi_type, abitype::AbiType::Boxed(_)) =>
+            {
+                let boxed_abi_type =
+                    specific_boxed_abi_type_for_type_tags(reg_value.possible_type_tags);
+
+                // This deliberately doesn't go through `value_to_reg` so it isn't reported as
+                // implicit boxing
+                let boxed_reg = reg_to_boxed_reg(b, span, reg_value, boxed_abi_type);
+
+                Ok(value::RegValue {
+                    possible_type_tags: reg_value.possible_type_tags,
+                    ..value::RegValue::new(boxed_reg, boxed_abi_type.clone().into())
+                }
+                .into())
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn eval_unbox(
+        &mut self,
+        fcx: &mut FunCtx<'_>,
+        b: &mut Option<Builder>,
+        unbox: &hir::Unbox<hir::Inferred>,
+    ) -> Result<Value> {
+        use crate::mir::specific_abi_type::specific_abi_type_for_ty_ref;
+        use crate::mir::value::build_reg::value_to_reg;
+
+        let value = self.eval_expr(fcx, b, &unbox.value_expr)?;
+        let unboxed_abi_type = specific_abi_type_for_ty_ref(&unbox.ty_ref);
+
+        match (b, &value) {
+            (Some(b), Value::Reg(reg_value)) if reg_value.abi_type != unboxed_abi_type => {
+                let unboxed_reg = value_to_reg(self, b, unbox.span, &value, &unboxed_abi_type);
+                Ok(value::RegValue::new(unboxed_reg, unboxed_abi_type).into())
+            }
+            // Constants are only materialised when they're used
+            _ => Ok(value),
+        }
+    }
+
+    fn eval_dyn_check(
+        &mut self,
+        fcx: &mut FunCtx<'_>,
+        b: &mut Option<Builder>,
+        dyn_check: &hir::DynCheck<hir::Inferred>,
+    ) -> Result<Value> {
+        use crate::mir::typred::eval_dyn_check;
+
+        let value = self.eval_expr(fcx, b, &dyn_check.value_expr)?;
+        let required_ty = fcx.monomorphise(&dyn_check.ty_ref);
+
+        eval_dyn_check(self, b, dyn_check.span, value, &required_ty)
+    }
+
     fn eval_let(
         &mut self,
         fcx: &mut FunCtx<'_>,
@@ -317,13 +572,73 @@ impl EvalHirCtx {
         let source_name = Self::destruc_source_name(&hir_let.destruc);
         let value = self.eval_expr_with_source_name(fcx, b, &hir_let.value_expr, source_name)?;
 
-        Self::destruc_value(b, &hir_let.destruc, value, &mut |local_id, value| {
+        self.destruc_value(b, &hir_let.destruc, value, &mut |local_id, value| {
             fcx.local_values.insert(local_id, value);
-        });
+        })?;
 
         self.eval_expr(fcx, b, &hir_let.body_expr)
     }
 
+    fn eval_letrec(
+        &mut self,
+        fcx: &mut FunCtx<'_>,
+        b: &mut Option<Builder>,
+        hir_letrec: &hir::Letrec<hir::Inferred>,
+    ) -> Result<Value> {
+        use crate::mir::env_values;
+
+        fn letrec_fun_expr(expr: &Expr) -> &hir::Fun<hir::Inferred> {
+            match &expr.kind {
+                hir::ExprKind::Fun(fun_expr) => fun_expr,
+                hir::ExprKind::MacroExpand(_, inner_expr) => letrec_fun_expr(inner_expr),
+                _ => panic!("non-function `(letrec)` value"),
+            }
+        }
+
+        // Functions bound to `_` can't be referenced
+        let bound_funs: Vec<(hir::LocalId, &DataStr, &hir::Fun<hir::Inferred>)> = hir_letrec
+            .bindings
+            .iter()
+            .filter_map(|binding| {
+                binding.destruc.local_id().map(|local_id| {
+                    (
+                        local_id,
+                        binding.destruc.source_name(),
+                        letrec_fun_expr(&binding.value_expr),
+                    )
+                })
+            })
+            .collect();
+
+        if let Some((_, first_source_name, _)) = bound_funs.first() {
+            // The functions share their captures so each one can reference the others
+            let env_values = env_values::calculate_shared_env_values(
+                &fcx.local_values,
+                bound_funs
+                    .iter()
+                    .map(|(_, _, fun_expr)| &fun_expr.body_expr),
+                Some(first_source_name),
+            );
+
+            let letrec_funs = value::ArretFun::new_letrec_group(
+                fcx.module_id,
+                fcx.mono_ty_args.clone(),
+                env_values,
+                bound_funs.iter().map(|(local_id, source_name, fun_expr)| {
+                    (*local_id, Some((*source_name).clone()), (*fun_expr).clone())
+                }),
+            );
+
+            fcx.local_values.extend(
+                letrec_funs
+                    .into_iter()
+                    .map(|(local_id, arret_fun)| (local_id, Value::ArretFun(arret_fun))),
+            );
+        }
+
+        self.eval_expr(fcx, b, &hir_letrec.body_expr)
+    }
+
     fn eval_lit(&mut self, literal: &Datum) -> Value {
         reader::box_syntax_datum(self, literal).into()
     }
@@ -403,7 +718,7 @@ impl EvalHirCtx {
             },
         );
 
-        ret_reg_to_value(ret_reg, ret_abi)
+        ret_reg_to_value(b, span, ret_reg, ret_abi)
     }
 
     pub(super) fn inline_arret_fun_app(
@@ -433,7 +748,13 @@ impl EvalHirCtx {
             inliner_stack,
         };
 
-        Self::destruc_list(
+        inner_fcx.local_values.extend(
+            arret_fun
+                .letrec_sibling_values()
+                .map(|(local_id, sibling_fun)| (local_id, Value::ArretFun(sibling_fun))),
+        );
+
+        self.destruc_list(
             b,
             span,
             &fun_expr.params,
@@ -441,7 +762,7 @@ impl EvalHirCtx {
             &mut |local_id, value| {
                 inner_fcx.local_values.insert(local_id, value);
             },
-        );
+        )?;
 
         self.eval_expr(&mut inner_fcx, b, &fun_expr.body_expr)
     }
@@ -529,12 +850,16 @@ impl EvalHirCtx {
         field_index: usize,
         arg_list_value: &Value,
     ) -> Value {
-        use crate::mir::record_field::load_record_field;
+        use crate::mir::record_field::{load_open_record_field, load_record_field};
 
         let mut iter = arg_list_value.unsized_list_iter();
         let record_value = iter.next_unchecked(b, span);
 
-        load_record_field(self, b, span, record_cons, &record_value, field_index)
+        if record_cons.has_rest_fields() {
+            load_open_record_field(self, b, span, record_cons, &record_value, field_index)
+        } else {
+            load_record_field(self, b, span, record_cons, &record_value, field_index)
+        }
     }
 
     pub fn rust_fun_to_jit_boxed(&mut self, rust_fun: Arc<rfi::Fun>) -> Gc<boxed::FunThunk> {
@@ -696,6 +1021,7 @@ impl EvalHirCtx {
         use crate::mir::app_purity::fun_app_purity;
         use crate::mir::intrinsic;
         use crate::mir::rust_fun::build_rust_fun_app;
+        use crate::mir::value::build_reg::can_build_const;
         use crate::mir::value::to_const::value_to_const;
 
         let ApplyArgs {
@@ -744,7 +1070,15 @@ impl EvalHirCtx {
                 // If we receive a panic while building we want to still build the function call.
                 // This `panic` could be in conditional code and we want to build all the
                 // expressions before this panic for their side effects.
-                if native_result.is_ok() || !b.is_some() {
+                //
+                // Results that can't be built as constants are also returned at runtime instead.
+                let build_call = match &native_result {
+                    Ok(Value::Const(any_ref)) => !can_build_const(self, *any_ref),
+                    Ok(_) => false,
+                    Err(_) => true,
+                };
+
+                if !build_call || !b.is_some() {
                     return native_result;
                 }
             }
@@ -1017,11 +1351,23 @@ impl EvalHirCtx {
             use crate::mir::value::to_const::value_to_const;
 
             if let Some(boxed_arg_list) = value_to_const(self, &arg_list_value) {
+                use crate::mir::value::build_reg::can_build_const;
+
                 let thunk = self.jit_thunk_for_arret_fun(arret_fun);
-                return Self::call_native_fun(span, || {
+                let native_result = Self::call_native_fun(span, || {
                     let captures = boxed::NIL_INSTANCE.as_any_ref();
                     thunk(&mut self.runtime_task, captures, boxed_arg_list)
                 });
+
+                // Results that can't be built as constants are returned at runtime instead
+                let build_call = match &native_result {
+                    Ok(Value::Const(any_ref)) => b.is_some() && !can_build_const(self, *any_ref),
+                    _ => false,
+                };
+
+                if !build_call {
+                    return native_result;
+                }
             }
         }
 
@@ -1062,7 +1408,7 @@ impl EvalHirCtx {
             );
 
             match &self_abi.ret {
-                abitype::RetAbiType::Inhabited(_) => {
+                abitype::RetAbiType::Inhabited(_) | abitype::RetAbiType::Values(_) => {
                     some_b.push(span, OpKind::Ret(ret_reg.into()));
                 }
                 abitype::RetAbiType::Never => {
@@ -1411,6 +1757,44 @@ impl EvalHirCtx {
             .or_insert(evaled_record_class)
     }
 
+    /// Records every record constructor defined inside a definition's value
+    fn add_program_record_conses(&mut self, value_expr: &Expr) {
+        use crate::hir::visitor::visit_exprs;
+
+        let program_record_conses = &mut self.program_record_conses;
+        visit_exprs(value_expr, &mut |expr| {
+            if let hir::ExprKind::RecordCons(_, record_cons) = &expr.kind {
+                if !program_record_conses.contains(record_cons) {
+                    program_record_conses.push(record_cons.clone());
+                }
+            }
+        });
+    }
+
+    /// Returns the record constructors in the program with every field of the passed constructor
+    ///
+    /// These are the possible classes of a value with an instance type of a record constructor
+    /// with rest fields. The passed constructor is always returned first.
+    pub fn record_conses_with_fields_of(
+        &self,
+        record_cons: &record::ConsId,
+    ) -> Vec<record::ConsId> {
+        std::iter::once(record_cons.clone())
+            .chain(
+                self.program_record_conses
+                    .iter()
+                    .filter(|other_cons| {
+                        *other_cons != record_cons
+                            && record_cons
+                                .fields()
+                                .iter()
+                                .all(|field| other_cons.field_index(field.name()).is_some())
+                    })
+                    .cloned(),
+            )
+            .collect()
+    }
+
     pub fn cons_for_jit_record_class_id(
         &self,
         record_class_id: boxed::RecordClassId,
@@ -1496,9 +1880,21 @@ impl EvalHirCtx {
         use crate::mir::optimise::optimise_fun;
         use crate::mir::ret_value::build_value_ret;
 
+        let _span = trace::enter_with_args("mir", "build_fun", || {
+            vec![(
+                "fun",
+                arret_fun
+                    .source_name()
+                    .as_ref()
+                    .map(|source_name| source_name.to_string())
+                    .unwrap_or_else(|| "<anonymous>".to_owned()),
+            )]
+        });
+
         let mut b = Builder::new();
         let fun_expr = arret_fun.fun_expr();
         let span = fun_expr.span;
+        let _processing_span = crash::enter_span(span);
 
         let param_list_poly = poly_for_list_destruc(&arret_fun.fun_expr().params);
         let LoadedArgList {
@@ -1522,6 +1918,12 @@ impl EvalHirCtx {
         // Our env values have been updated with its new reg IDs
         let recur_arret_fun = arret_fun.with_env_values(recur_env_values);
 
+        local_values.extend(
+            recur_arret_fun
+                .letrec_sibling_values()
+                .map(|(local_id, sibling_fun)| (local_id, Value::ArretFun(sibling_fun))),
+        );
+
         // Try to refine our polymorphic type variables based on our requested op ABI
         let mut stx = ty::select::SelectCtx::new(&fun_expr.pvars, &fun_expr.tvars);
 
@@ -1558,29 +1960,33 @@ impl EvalHirCtx {
         };
 
         let mut some_b = Some(b);
-        Self::destruc_list(
-            &mut some_b,
-            span,
-            &fun_expr.params,
-            arg_list_value,
-            &mut |local_id, value| {
-                fcx.local_values.insert(local_id, value);
-            },
-        );
-
-        let app_result = self.eval_expr(&mut fcx, &mut some_b, &fun_expr.body_expr);
+        let app_result = self
+            .destruc_list(
+                &mut some_b,
+                span,
+                &fun_expr.params,
+                arg_list_value,
+                &mut |local_id, value| {
+                    fcx.local_values.insert(local_id, value);
+                },
+            )
+            .and_then(|()| self.eval_expr(&mut fcx, &mut some_b, &fun_expr.body_expr));
 
         let mut b = some_b.unwrap();
         build_value_ret(self, &mut b, span, app_result, &wanted_abi.ret);
 
-        optimise_fun(ops::Fun {
-            span: arret_fun.fun_expr().span,
-            source_name: arret_fun.source_name().clone(),
+        optimise_fun(
+            self.opt_level,
+            self.verify_mir,
+            ops::Fun {
+                span: arret_fun.fun_expr().span,
+                source_name: arret_fun.source_name().clone(),
 
-            abi: wanted_abi.into(),
-            param_regs,
-            ops: b.into_ops(),
-        })
+                abi: wanted_abi.into(),
+                param_regs,
+                ops: b.into_ops(),
+            },
+        )
     }
 
     /// Builds a function with a callback ABI that calls a thunk passed as its captures
@@ -1616,14 +2022,18 @@ impl EvalHirCtx {
 
         build_value_ret(self, &mut b, span, Ok(result_value), &wanted_abi.ret);
 
-        optimise_fun(ops::Fun {
-            span,
-            source_name: Some("callback_to_thunk_adapter".into()),
+        optimise_fun(
+            self.opt_level,
+            self.verify_mir,
+            ops::Fun {
+                span,
+                source_name: Some("callback_to_thunk_adapter".into()),
 
-            abi: wanted_abi.into(),
-            param_regs,
-            ops: b.into_ops(),
-        })
+                abi: wanted_abi.into(),
+                param_regs,
+                ops: b.into_ops(),
+            },
+        )
     }
 
     pub fn thunk_reg_to_callback_reg(
@@ -1662,29 +2072,68 @@ impl EvalHirCtx {
         self.thunk_fun_values.get(&boxed_thunk.as_ptr())
     }
 
+    /// Folds the value of a `(def-const)` in to a single constant
+    ///
+    /// This ensures every reference to the definition is replaced with the constant.
+    fn fold_const_def_value(&mut self, span: Span, value: Value) -> Result<Value> {
+        use crate::mir::value::to_const::is_const_data;
+
+        if !is_const_data(&value) {
+            return Err(Error::NonConstDef(span));
+        }
+
+        self.value_to_const(&value)
+            .map(Value::Const)
+            .ok_or(Error::NonConstDef(span))
+    }
+
     pub fn visit_module_defs<'a>(
         &mut self,
         module_id: ModuleId,
         defs: impl IntoIterator<Item = &'a hir::Def<hir::Inferred>>,
     ) -> Result<()> {
+        let _span = trace::enter("mir", "eval_module_defs");
+
         for def in defs {
             let hir::Def {
+                span,
                 destruc,
                 value_expr,
+                is_const,
                 ..
             } = def;
 
+            let _processing_span = crash::enter_span(*span);
+            self.add_program_record_conses(value_expr);
+
+            let source_name = Self::destruc_source_name(destruc);
+            let _span = trace::enter_with_args("mir", "eval_def", || {
+                vec![("def", Self::def_trace_name(source_name))]
+            });
+
             let mut fcx = FunCtx::new(Some(module_id));
 
             // Don't pass a builder; we should never generate ops based on a def
-            let source_name = Self::destruc_source_name(destruc);
-            let value =
-                self.eval_expr_with_source_name(&mut fcx, &mut None, value_expr, source_name)?;
+            let value = crash::catch_unsupported(|| {
+                self.eval_expr_with_source_name(&mut fcx, &mut None, value_expr, source_name)
+            })??;
 
-            Self::destruc_value(&mut None, destruc, value, &mut |local_id, value| {
-                self.global_values
-                    .insert(hir::ExportId::new(module_id, local_id), value);
-            });
+            let value = if *is_const {
+                self.fold_const_def_value(*span, value)?
+            } else {
+                value
+            };
+
+            let mut local_values = vec![];
+            self.destruc_value(&mut None, destruc, value, &mut |local_id, value| {
+                local_values.push((local_id, value));
+            })?;
+
+            self.global_values.extend(
+                local_values
+                    .into_iter()
+                    .map(|(local_id, value)| (hir::ExportId::new(module_id, local_id), value)),
+            );
         }
 
         Ok(())
@@ -1695,24 +2144,48 @@ impl EvalHirCtx {
         module_id: ModuleId,
         defs: impl IntoIterator<Item = hir::Def<hir::Inferred>>,
     ) -> Result<()> {
+        let _span = trace::enter("mir", "eval_module_defs");
+
         for def in defs {
             let hir::Def {
+                span,
                 destruc,
                 value_expr,
+                is_const,
                 ..
             } = def;
 
+            let _processing_span = crash::enter_span(span);
+            self.add_program_record_conses(&value_expr);
+
+            let source_name = Self::destruc_source_name(&destruc);
+            let _span = trace::enter_with_args("mir", "eval_def", || {
+                vec![("def", Self::def_trace_name(source_name))]
+            });
+
             let mut fcx = FunCtx::new(Some(module_id));
 
             // Don't pass a builder; we should never generate ops based on a def
-            let source_name = Self::destruc_source_name(&destruc);
-            let value =
-                self.consume_expr_with_source_name(&mut fcx, &mut None, value_expr, source_name)?;
+            let value = crash::catch_unsupported(|| {
+                self.consume_expr_with_source_name(&mut fcx, &mut None, value_expr, source_name)
+            })??;
 
-            Self::destruc_value(&mut None, &destruc, value, &mut |local_id, value| {
-                self.global_values
-                    .insert(hir::ExportId::new(module_id, local_id), value);
-            });
+            let value = if is_const {
+                self.fold_const_def_value(span, value)?
+            } else {
+                value
+            };
+
+            let mut local_values = vec![];
+            self.destruc_value(&mut None, &destruc, value, &mut |local_id, value| {
+                local_values.push((local_id, value));
+            })?;
+
+            self.global_values.extend(
+                local_values
+                    .into_iter()
+                    .map(|(local_id, value)| (hir::ExportId::new(module_id, local_id), value)),
+            );
         }
 
         Ok(())
@@ -1727,6 +2200,8 @@ impl EvalHirCtx {
         use arret_runtime::boxed::collect;
         use std::mem;
 
+        let _span = trace::enter("mir", "collect_garbage");
+
         let old_heap = mem::take(self.runtime_task.heap_mut());
         let mut strong_pass = collect::StrongPass::new(old_heap);
 
@@ -1740,6 +2215,10 @@ impl EvalHirCtx {
             value::visit_value_root(&mut strong_pass, value_ref);
         }
 
+        for exit_handler in self.runtime_task.exit_handlers_mut() {
+            strong_pass.visit_box(exit_handler);
+        }
+
         // Any function values that are still live need to be updated
         let weak_pass = strong_pass.into_weak_pass();
 
@@ -1769,6 +2248,7 @@ impl EvalHirCtx {
         let value = match &expr.kind {
             ExprKind::Lit(literal) => Ok(self.eval_lit(literal)),
             ExprKind::Do(exprs) => self.eval_do(fcx, b, exprs),
+            ExprKind::Values(_, exprs) => self.eval_values(fcx, b, exprs),
             ExprKind::Fun(fun_expr) => {
                 Ok(self.eval_arret_fun(fcx, fun_expr.as_ref().clone(), source_name))
             }
@@ -1781,14 +2261,21 @@ impl EvalHirCtx {
                 field_accessor.field_index,
             )),
             ExprKind::LocalRef(_, local_id) => Ok(self.eval_local_ref(fcx, *local_id)),
+            ExprKind::NoClauseMatch(_) => Ok(Value::List(Box::new([]), None)),
             ExprKind::ExportRef(_, export_id) => Ok(self.global_values[export_id].clone()),
             ExprKind::Let(hir_let) => self.eval_let(fcx, b, hir_let),
+            ExprKind::Letrec(hir_letrec) => self.eval_letrec(fcx, b, hir_letrec),
             ExprKind::App(app) => self.eval_app(fcx, b, &expr.result_ty, app),
             ExprKind::Recur(recur) => self.eval_recur(fcx, b, &expr.result_ty, recur),
             ExprKind::MacroExpand(span, expr) => self
                 .eval_expr(fcx, b, expr)
                 .map_err(|err| err.with_macro_invocation_span(*span)),
             ExprKind::Cond(cond) => self.eval_cond(fcx, b, cond),
+            ExprKind::Box(span, value_expr) => self.eval_box(fcx, b, *span, value_expr),
+            ExprKind::Unbox(unbox) => self.eval_unbox(fcx, b, unbox),
+            ExprKind::DynCheck(dyn_check) => self.eval_dyn_check(fcx, b, dyn_check),
+            // Casts only affect the static type of the value
+            ExprKind::Cast(cast) => self.eval_expr(fcx, b, &cast.value_expr),
         }?;
 
         // Annotate this value with the expression's result type as it passes through
@@ -1827,59 +2314,87 @@ impl EvalHirCtx {
         b: &mut Option<Builder>,
         expr: Expr,
     ) -> Result<Value> {
-        self.consume_expr_with_source_name(fcx, b, expr, None)
+        crash::catch_unsupported(|| self.consume_expr_with_source_name(fcx, b, expr, None))?
     }
 
     /// Evaluates the main function of a program
     pub fn eval_main_fun(&mut self, main_export_id: hir::ExportId) -> Result<()> {
+        let _span = trace::enter("mir", "eval_main_fun");
+
         let mut fcx = FunCtx::new(Some(main_export_id.module_id()));
         let main_value = self.eval_local_ref(&fcx, main_export_id.local_id());
 
         let empty_list_value = Value::List(Box::new([]), None);
 
-        self.eval_value_app(
-            &mut fcx,
-            &mut None,
-            EMPTY_SPAN,
-            &Ty::unit().into(),
-            &main_value,
-            ApplyArgs {
-                ty_args: &TyArgs::empty(),
-                list_value: empty_list_value,
-            },
-        )?;
+        crash::catch_unsupported(|| {
+            self.eval_value_app(
+                &mut fcx,
+                &mut None,
+                EMPTY_SPAN,
+                &Ty::unit().into(),
+                &main_value,
+                ApplyArgs {
+                    ty_args: &TyArgs::empty(),
+                    list_value: empty_list_value,
+                },
+            )
+        })??;
+
+        let runtime_task = &mut self.runtime_task;
+        Self::call_native_fun(EMPTY_SPAN, || {
+            runtime_task.run_exit_handlers();
+            boxed::NIL_INSTANCE.as_any_ref()
+        })?;
 
         Ok(())
     }
 
     /// Builds the main function of the program
     pub fn into_built_program(mut self, main_export_id: hir::ExportId) -> Result<BuiltProgram> {
+        let _span = trace::enter("mir", "build_program");
+
         let fcx = FunCtx::new(Some(main_export_id.module_id()));
         let main_value = self.eval_local_ref(&fcx, main_export_id.local_id());
 
-        let main_arret_fun = if let Value::ArretFun(main_arret_fun) = main_value {
-            main_arret_fun
-        } else {
-            unimplemented!("Non-Arret main!");
-        };
+        let main = crash::catch_unsupported(|| {
+            let main_arret_fun = if let Value::ArretFun(main_arret_fun) = main_value {
+                main_arret_fun
+            } else {
+                crash::unsupported(None, "defining `main!` as a Rust function");
+            };
 
-        let main_abi = PolymorphAbi {
-            call_conv: ops::CallConv::Ccc,
+            let main_abi = PolymorphAbi {
+                call_conv: ops::CallConv::Ccc,
 
-            // Main is a top-level function; it can't capture
-            has_captures: false,
-            fixed_params: Box::new([]),
-            rest_param: None,
+                // Main is a top-level function; it can't capture
+                has_captures: false,
+                fixed_params: Box::new([]),
+                rest_param: None,
 
-            ret: abitype::RetAbiType::Void,
-        };
+                ret: abitype::RetAbiType::Void,
+            };
 
-        let main = self.ops_for_arret_fun(&main_arret_fun, main_abi);
+            self.ops_for_arret_fun(&main_arret_fun, main_abi)
+        })?;
 
-        Ok(BuiltProgram {
+        let boxing_warnings = self
+            .implicit_boxings
+            .iter()
+            .flatten()
+            .map(|(span, from_abi_type)| implicit_boxing_warning(*span, from_abi_type))
+            .collect();
+
+        let program = BuiltProgram {
             main,
             private_funs: self.private_funs,
-        })
+            boxing_warnings,
+        };
+
+        if self.verify_mir {
+            verify::expect_verified(verify::verify_program(&program), "building the program");
+        }
+
+        Ok(program)
     }
 
     pub fn value_to_const(&mut self, value: &Value) -> Option<Gc<boxed::Any>> {
@@ -17,9 +17,6 @@ use crate::mir::value;
 use crate::mir::value::Value;
 use crate::ty;
 
-/// Maximum number of consecutive inlinings in a call stack
-const MAX_INLINE_DEPTH: usize = 16;
-
 /// Opaque hash of an Arret fun application
 ///
 /// This is used to heuristically detect recursion loops
@@ -231,7 +228,8 @@ pub(super) fn cond_inline<'a>(
 
     let apply_stack = &fcx.inliner_stack;
     let apply_cookie = ApplyCookie::new(ehx.as_heap(), arret_fun, &apply_args.list_value);
-    if apply_stack.entries.len() >= MAX_INLINE_DEPTH || apply_stack.entries.contains(&apply_cookie)
+    if apply_stack.entries.len() >= ehx.opt_level().max_inline_depth()
+        || apply_stack.entries.contains(&apply_cookie)
     {
         // Abort recursion all the way back to the original call of this function
 
@@ -256,7 +254,11 @@ pub(super) fn cond_inline<'a>(
     let apply_stack = apply_stack.with_apply_cookie(apply_cookie);
 
     // Figure out how much we should prefer an inline version
-    let inline_preference_factor = calc_inline_preference_factor(arret_fun, &apply_args.list_value);
+    let inline_preference_factor = if ehx.opt_level().prefer_inlining() {
+        calc_inline_preference_factor(arret_fun, &apply_args.list_value)
+    } else {
+        1.0
+    };
 
     // Build an inline version
     let inline_result =
@@ -265,7 +267,7 @@ pub(super) fn cond_inline<'a>(
     let inline_ops = inline_b.unwrap().into_ops();
     let inline_ops = if let Ok(ref return_value) = inline_result {
         // In order to cost the inline function accurately we need to optimise it first
-        optimise_inlined_fun(inline_ops, return_value)
+        optimise_inlined_fun(ehx.opt_level(), inline_ops, return_value)
     } else {
         inline_ops
     };
@@ -0,0 +1,375 @@
+//! Constant folding for math and comparison intrinsics
+//!
+//! When every operand is a constant `Int` or `Float` these calculate the result while evaluating
+//! instead of building the operations or calling the stdlib at compile time.
+//!
+//! This follows the same operation and conversion order as the ops built by `math` and `number`.
+//! `Int` operations are checked; if one would overflow or divide by zero the intrinsic isn't
+//! folded. The call is then built normally so it panics at runtime with the same message as the
+//! checked codegen.
+
+use arret_syntax::span::Span;
+
+use arret_runtime::boxed;
+use arret_runtime::boxed::refs::Gc;
+
+use crate::mir::builder::Builder;
+use crate::mir::error::Result;
+use crate::mir::eval_hir::EvalHirCtx;
+use crate::mir::value::Value;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ConstNum {
+    Int(i64),
+    Float(f64),
+}
+
+impl ConstNum {
+    fn to_f64(self) -> f64 {
+        match self {
+            ConstNum::Int(int_value) => int_value as f64,
+            ConstNum::Float(float_value) => float_value,
+        }
+    }
+
+    fn into_value(self, ehx: &mut EvalHirCtx) -> Value {
+        match self {
+            ConstNum::Int(int_value) => boxed::Int::new(ehx, int_value).into(),
+            ConstNum::Float(float_value) => boxed::Float::new(ehx, float_value).into(),
+        }
+    }
+}
+
+fn const_num_for_any_ref(any_ref: Gc<boxed::Any>) -> Option<ConstNum> {
+    Some(match any_ref.downcast_ref::<boxed::Num>()?.as_subtype() {
+        boxed::NumSubtype::Int(int_ref) => ConstNum::Int(int_ref.value()),
+        boxed::NumSubtype::Float(float_ref) => ConstNum::Float(float_ref.value()),
+    })
+}
+
+/// Appends the operands of an argument list if they're all constant numbers
+///
+/// This inspects the list directly instead of iterating over it. Iterating over a list with an
+/// unknown tail would build ops we don't need if the intrinsic can't be folded.
+fn push_const_num_operands(arg_list_value: &Value, operands: &mut Vec<ConstNum>) -> Option<()> {
+    match arg_list_value {
+        Value::List(fixed, rest) => {
+            for fixed_value in fixed.iter() {
+                if let Value::Const(any_ref) = fixed_value {
+                    operands.push(const_num_for_any_ref(*any_ref)?);
+                } else {
+                    return None;
+                }
+            }
+
+            match rest {
+                Some(rest) => push_const_num_operands(rest, operands),
+                None => Some(()),
+            }
+        }
+        Value::Const(any_ref) => {
+            let list_ref = any_ref.downcast_ref::<boxed::List<boxed::Any>>()?;
+
+            for member in list_ref.iter() {
+                operands.push(const_num_for_any_ref(member)?);
+            }
+
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+fn const_num_operands(arg_list_value: &Value) -> Option<Vec<ConstNum>> {
+    let mut operands = vec![];
+    push_const_num_operands(arg_list_value, &mut operands)?;
+    Some(operands)
+}
+
+/// Reduces operands pairwise from left to right
+///
+/// Both operands of a pair are converted to `Float` if either is a `Float`. This returns `None` if
+/// an `Int` operation fails.
+fn reduce_const_nums(
+    initial: ConstNum,
+    operands: &[ConstNum],
+    int64_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Option<ConstNum> {
+    operands
+        .iter()
+        .try_fold(initial, |acc, operand| match (acc, *operand) {
+            (ConstNum::Int(lhs), ConstNum::Int(rhs)) => int64_op(lhs, rhs).map(ConstNum::Int),
+            (lhs, rhs) => Some(ConstNum::Float(float_op(lhs.to_f64(), rhs.to_f64()))),
+        })
+}
+
+fn fold_assoc(
+    operands: &[ConstNum],
+    int64_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Option<ConstNum> {
+    let (initial, rest) = operands.split_first()?;
+    reduce_const_nums(*initial, rest, int64_op, float_op)
+}
+
+fn fold_sub(operands: &[ConstNum]) -> Option<ConstNum> {
+    use std::ops::Sub;
+
+    match operands {
+        // `(- x)` is built as `(- 0 x)`
+        [single] => reduce_const_nums(ConstNum::Int(0), &[*single], i64::checked_sub, f64::sub),
+        [initial, rest @ ..] => reduce_const_nums(*initial, rest, i64::checked_sub, f64::sub),
+        [] => None,
+    }
+}
+
+fn fold_div(operands: &[ConstNum]) -> Option<ConstNum> {
+    let mut float_operands = operands.iter().map(|operand| operand.to_f64());
+    let initial = float_operands.next()?;
+
+    Some(ConstNum::Float(if operands.len() == 1 {
+        1.0 / initial
+    } else {
+        float_operands.fold(initial, |acc, operand| acc / operand)
+    }))
+}
+
+fn fold_int_division(
+    operands: &[ConstNum],
+    int64_op: fn(i64, i64) -> Option<i64>,
+) -> Option<ConstNum> {
+    match operands {
+        [ConstNum::Int(numer), ConstNum::Int(denom)] => int64_op(*numer, *denom).map(ConstNum::Int),
+        _ => None,
+    }
+}
+
+fn fold_compare(
+    operands: &[ConstNum],
+    int64_comparator: fn(&i64, &i64) -> bool,
+    float_comparator: fn(&f64, &f64) -> bool,
+) -> bool {
+    operands.windows(2).all(|pair| match (pair[0], pair[1]) {
+        (ConstNum::Int(lhs), ConstNum::Int(rhs)) => int64_comparator(&lhs, &rhs),
+        (lhs, rhs) => float_comparator(&lhs.to_f64(), &rhs.to_f64()),
+    })
+}
+
+fn eval_num_fold<F>(
+    ehx: &mut EvalHirCtx,
+    arg_list_value: &Value,
+    folder: F,
+) -> Result<Option<Value>>
+where
+    F: FnOnce(&[ConstNum]) -> Option<ConstNum>,
+{
+    Ok(const_num_operands(arg_list_value)
+        .and_then(|operands| folder(&operands))
+        .map(|result| result.into_value(ehx)))
+}
+
+fn eval_compare_fold(
+    arg_list_value: &Value,
+    int64_comparator: fn(&i64, &i64) -> bool,
+    float_comparator: fn(&f64, &f64) -> bool,
+) -> Result<Option<Value>> {
+    use arret_runtime::boxed::prelude::*;
+
+    Ok(const_num_operands(arg_list_value).map(|operands| {
+        let result = fold_compare(&operands, int64_comparator, float_comparator);
+        boxed::Bool::singleton_ref(result).as_any_ref().into()
+    }))
+}
+
+pub fn add(
+    ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    use std::ops::Add;
+
+    eval_num_fold(ehx, arg_list_value, |operands| {
+        fold_assoc(operands, i64::checked_add, f64::add)
+    })
+}
+
+pub fn mul(
+    ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    use std::ops::Mul;
+
+    eval_num_fold(ehx, arg_list_value, |operands| {
+        fold_assoc(operands, i64::checked_mul, f64::mul)
+    })
+}
+
+pub fn sub(
+    ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_num_fold(ehx, arg_list_value, fold_sub)
+}
+
+pub fn div(
+    ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_num_fold(ehx, arg_list_value, fold_div)
+}
+
+pub fn quot(
+    ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_num_fold(ehx, arg_list_value, |operands| {
+        fold_int_division(operands, i64::checked_div)
+    })
+}
+
+pub fn rem(
+    ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_num_fold(ehx, arg_list_value, |operands| {
+        fold_int_division(operands, i64::checked_rem)
+    })
+}
+
+pub fn num_lt(
+    _ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_compare_fold(arg_list_value, i64::lt, f64::lt)
+}
+
+pub fn num_le(
+    _ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_compare_fold(arg_list_value, i64::le, f64::le)
+}
+
+pub fn num_eq(
+    _ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_compare_fold(arg_list_value, i64::eq, f64::eq)
+}
+
+pub fn num_gt(
+    _ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_compare_fold(arg_list_value, i64::gt, f64::gt)
+}
+
+pub fn num_ge(
+    _ehx: &mut EvalHirCtx,
+    _b: &mut Option<Builder>,
+    _span: Span,
+    arg_list_value: &Value,
+) -> Result<Option<Value>> {
+    eval_compare_fold(arg_list_value, i64::ge, f64::ge)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ops::{Add, Mul};
+
+    use ConstNum::{Float, Int};
+
+    #[test]
+    fn fold_int_math() {
+        assert_eq!(
+            Some(Int(6)),
+            fold_assoc(&[Int(1), Int(2), Int(3)], i64::checked_add, f64::add)
+        );
+        assert_eq!(Some(Int(-5)), fold_sub(&[Int(5)]));
+        assert_eq!(Some(Int(-4)), fold_sub(&[Int(1), Int(2), Int(3)]));
+        assert_eq!(
+            Some(Int(3)),
+            fold_int_division(&[Int(10), Int(3)], i64::checked_div)
+        );
+        assert_eq!(
+            Some(Int(1)),
+            fold_int_division(&[Int(10), Int(3)], i64::checked_rem)
+        );
+    }
+
+    #[test]
+    fn fold_mixed_math() {
+        // Operands are only converted to `Float` once a `Float` is reached
+        assert_eq!(
+            Some(Float(7.5)),
+            fold_assoc(&[Int(2), Int(3), Float(1.25)], i64::checked_mul, f64::mul)
+        );
+        assert_eq!(Some(Float(-1.5)), fold_sub(&[Float(1.5)]));
+        assert_eq!(Some(Float(0.25)), fold_div(&[Int(4)]));
+        assert_eq!(Some(Float(2.5)), fold_div(&[Int(10), Float(2.0), Int(2)]));
+    }
+
+    #[test]
+    fn checked_failures_not_folded() {
+        assert_eq!(
+            None,
+            fold_assoc(&[Int(i64::MAX), Int(1)], i64::checked_add, f64::add)
+        );
+        assert_eq!(None, fold_sub(&[Int(i64::MIN)]));
+        assert_eq!(
+            None,
+            fold_assoc(&[Int(i64::MAX), Int(2)], i64::checked_mul, f64::mul)
+        );
+        assert_eq!(None, fold_int_division(&[Int(1), Int(0)], i64::checked_div));
+        assert_eq!(
+            None,
+            fold_int_division(&[Int(i64::MIN), Int(-1)], i64::checked_rem)
+        );
+
+        // `Float` math doesn't overflow
+        assert_eq!(
+            Some(Float(f64::INFINITY)),
+            fold_assoc(&[Float(f64::MAX), Int(2)], i64::checked_mul, f64::mul)
+        );
+    }
+
+    #[test]
+    fn fold_comparisons() {
+        assert!(fold_compare(&[Int(1)], i64::lt, f64::lt));
+        assert!(fold_compare(
+            &[Int(1), Float(1.5), Int(2)],
+            i64::lt,
+            f64::lt
+        ));
+        assert!(!fold_compare(&[Int(1), Int(3), Int(2)], i64::lt, f64::lt));
+        assert!(fold_compare(&[Int(2), Float(2.0)], i64::eq, f64::eq));
+        assert!(!fold_compare(
+            &[Float(f64::NAN), Float(f64::NAN)],
+            i64::eq,
+            f64::eq
+        ));
+        assert!(fold_compare(&[Int(3), Int(3), Int(1)], i64::ge, f64::ge));
+    }
+}
@@ -1,6 +1,8 @@
 mod bitwise;
+mod const_fold;
 mod list;
 mod math;
+mod num_array;
 mod num_utils;
 mod number;
 mod panics;
@@ -68,7 +70,20 @@ define_eval_intrinsics! {
     "length" => list::length,
     "cons" => list::cons,
     "repeat" => list::repeat,
-    "fn-op-categories" => testing::fn_op_categories
+    "fn-op-categories" => testing::fn_op_categories,
+
+    "+" => const_fold::add,
+    "*" => const_fold::mul,
+    "-" => const_fold::sub,
+    "/" => const_fold::div,
+    "quot" => const_fold::quot,
+    "rem" => const_fold::rem,
+
+    "<" => const_fold::num_lt,
+    "<=" => const_fold::num_le,
+    "==" => const_fold::num_eq,
+    ">" => const_fold::num_gt,
+    ">=" => const_fold::num_ge
 }
 
 define_build_intrinsics! {
@@ -100,6 +115,14 @@ define_build_intrinsics! {
     "vector-length" => vector::vector_length,
     "vector-ref" => vector::vector_ref,
 
+    "float-array-length" => num_array::float_array_length,
+    "float-array-ref" => num_array::float_array_ref,
+    "int-array-length" => num_array::int_array_length,
+    "int-array-ref" => num_array::int_array_ref,
+    "array-add!" => num_array::array_add,
+    "array-scale!" => num_array::array_scale,
+    "dot-product" => num_array::dot_product,
+
     "bit-and" => bitwise::bit_and,
     "bit-or" => bitwise::bit_or,
     "bit-xor" => bitwise::bit_xor,
@@ -0,0 +1,219 @@
+use arret_syntax::span::Span;
+
+use arret_runtime::abitype;
+use arret_runtime::boxed;
+
+use crate::mir::builder::Builder;
+use crate::mir::error::Result;
+use crate::mir::eval_hir::EvalHirCtx;
+use crate::mir::intrinsic::BuildOutcome;
+use crate::mir::ops::RegId;
+use crate::mir::Value;
+
+fn num_array_length(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+    array_type_tag: boxed::TypeTag,
+) -> Result<BuildOutcome> {
+    use crate::mir::ops::*;
+    use crate::mir::value;
+    use crate::mir::value::build_reg::value_to_reg;
+
+    let mut iter = arg_list_value.unsized_list_iter();
+    let array_value = iter.next_unchecked(b, span);
+
+    let array_reg = value_to_reg(
+        ehx,
+        b,
+        span,
+        &array_value,
+        &abitype::BoxedAbiType::UniqueTagged(array_type_tag).into(),
+    );
+
+    let array_len_reg = b.push_reg(span, OpKind::LoadBoxedNumArrayLen, array_reg.into());
+    Ok(BuildOutcome::ReturnValue(
+        value::RegValue::new(array_len_reg, abitype::AbiType::Int).into(),
+    ))
+}
+
+fn num_array_ref(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+    array_type_tag: boxed::TypeTag,
+    member_abi_type: abitype::AbiType,
+) -> Result<BuildOutcome> {
+    use crate::mir::ops::*;
+    use crate::mir::value;
+    use crate::mir::value::build_reg::value_to_reg;
+
+    let mut iter = arg_list_value.unsized_list_iter();
+    let array_value = iter.next_unchecked(b, span);
+    let index_value = iter.next_unchecked(b, span);
+
+    let array_reg = value_to_reg(
+        ehx,
+        b,
+        span,
+        &array_value,
+        &abitype::BoxedAbiType::UniqueTagged(array_type_tag).into(),
+    );
+
+    let index_reg = value_to_reg(ehx, b, span, &index_value, &abitype::AbiType::Int);
+
+    let member_reg = b.push_reg(
+        span,
+        OpKind::LoadBoxedNumArrayMember,
+        LoadBoxedNumArrayMemberOp {
+            array_reg: array_reg.into(),
+            index_reg: index_reg.into(),
+        },
+    );
+
+    Ok(BuildOutcome::ReturnValue(
+        value::RegValue::new(member_reg, member_abi_type).into(),
+    ))
+}
+
+pub fn float_array_length(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    num_array_length(ehx, b, span, arg_list_value, boxed::TypeTag::FloatArray)
+}
+
+pub fn int_array_length(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    num_array_length(ehx, b, span, arg_list_value, boxed::TypeTag::IntArray)
+}
+
+pub fn float_array_ref(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    num_array_ref(
+        ehx,
+        b,
+        span,
+        arg_list_value,
+        boxed::TypeTag::FloatArray,
+        abitype::AbiType::Float,
+    )
+}
+
+pub fn int_array_ref(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    num_array_ref(
+        ehx,
+        b,
+        span,
+        arg_list_value,
+        boxed::TypeTag::IntArray,
+        abitype::AbiType::Int,
+    )
+}
+
+fn float_array_arg_reg(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    array_value: &Value,
+) -> RegId {
+    use crate::mir::value::build_reg::value_to_reg;
+
+    value_to_reg(
+        ehx,
+        b,
+        span,
+        array_value,
+        &abitype::BoxedAbiType::UniqueTagged(boxed::TypeTag::FloatArray).into(),
+    )
+    .into()
+}
+
+pub fn array_add(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    use crate::mir::ops::*;
+
+    let mut iter = arg_list_value.unsized_list_iter();
+    let lhs_value = iter.next_unchecked(b, span);
+    let rhs_value = iter.next_unchecked(b, span);
+
+    let lhs_reg = float_array_arg_reg(ehx, b, span, &lhs_value);
+    let rhs_reg = float_array_arg_reg(ehx, b, span, &rhs_value);
+
+    b.push(span, OpKind::FloatArrayAdd(BinaryOp { lhs_reg, rhs_reg }));
+    Ok(BuildOutcome::ReturnValue(Value::List(Box::new([]), None)))
+}
+
+pub fn array_scale(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    use crate::mir::ops::*;
+    use crate::mir::value::build_reg::value_to_reg;
+
+    let mut iter = arg_list_value.unsized_list_iter();
+    let array_value = iter.next_unchecked(b, span);
+    let factor_value = iter.next_unchecked(b, span);
+
+    let array_reg = float_array_arg_reg(ehx, b, span, &array_value);
+    let factor_reg = value_to_reg(ehx, b, span, &factor_value, &abitype::AbiType::Float).into();
+
+    b.push(
+        span,
+        OpKind::FloatArrayScale(FloatArrayScaleOp {
+            array_reg,
+            factor_reg,
+        }),
+    );
+    Ok(BuildOutcome::ReturnValue(Value::List(Box::new([]), None)))
+}
+
+pub fn dot_product(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    arg_list_value: &Value,
+) -> Result<BuildOutcome> {
+    use crate::mir::ops::*;
+    use crate::mir::value;
+
+    let mut iter = arg_list_value.unsized_list_iter();
+    let lhs_value = iter.next_unchecked(b, span);
+    let rhs_value = iter.next_unchecked(b, span);
+
+    let lhs_reg = float_array_arg_reg(ehx, b, span, &lhs_value);
+    let rhs_reg = float_array_arg_reg(ehx, b, span, &rhs_value);
+
+    let dot_product_reg = b.push_reg(
+        span,
+        OpKind::FloatArrayDotProduct,
+        BinaryOp { lhs_reg, rhs_reg },
+    );
+
+    Ok(BuildOutcome::ReturnValue(
+        value::RegValue::new(dot_product_reg, abitype::AbiType::Float).into(),
+    ))
+}
@@ -50,6 +50,7 @@ fn op_category_to_string(category: ops::OpCategory) -> &'static str {
         OpCategory::Cond => ":cond",
         OpCategory::MakeCallback => ":make-callback",
         OpCategory::MemLoad => ":mem-load",
+        OpCategory::MemStore => ":mem-store",
         OpCategory::CastBoxed => ":cast-boxed",
         OpCategory::RegOp => ":reg-op",
         OpCategory::Ret => ":ret",
@@ -63,10 +63,12 @@ pub fn vector_ref(
         return Ok(BuildOutcome::None);
     };
 
-    let index = if let Some(index) = try_value_to_i64(index_value) {
-        index as usize
-    } else {
-        return Ok(BuildOutcome::None);
+    let index = match try_value_to_i64(index_value) {
+        Some(index) if index >= 0 && (index as usize) < known_len => index as usize,
+        _ => {
+            // Out of bounds indices need to raise the runtime's error
+            return Ok(BuildOutcome::None);
+        }
     };
 
     Ok(BuildOutcome::ReturnValue(
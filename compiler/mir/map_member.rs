@@ -0,0 +1,86 @@
+use arret_syntax::span::Span;
+
+use arret_runtime::abitype;
+use arret_runtime::boxed;
+use arret_runtime::boxed::prelude::*;
+
+use crate::codegen::GenAbi;
+use crate::mir::builder::Builder;
+use crate::mir::error::{Error, Panic, Result};
+use crate::mir::eval_hir::EvalHirCtx;
+use crate::mir::value::Value;
+
+const MAP_ABI_TYPE: abitype::BoxedAbiType =
+    abitype::BoxedAbiType::Map(&abitype::BoxedAbiType::Any, &abitype::BoxedAbiType::Any);
+
+/// Loads the value of a keyword key from a map
+///
+/// Values of constant maps are loaded at compile time. A missing key causes a panic at runtime or
+/// an error during compile time evaluation.
+pub fn load_map_member(
+    ehx: &mut EvalHirCtx,
+    b: &mut Option<Builder>,
+    span: Span,
+    map_value: &Value,
+    key_name: &str,
+) -> Result<Value> {
+    use crate::mir::ops::*;
+    use crate::mir::value::build_reg::value_to_reg;
+    use crate::mir::value::RegValue;
+
+    let key_sym = boxed::Sym::new(ehx, key_name);
+
+    if let Value::Const(any_ref) = map_value {
+        if let Some(map_ref) = any_ref.downcast_ref::<boxed::Map<boxed::Any, boxed::Any>>() {
+            if let Some(member_ref) = map_ref.get(ehx.as_heap(), &key_sym.as_any_ref()) {
+                return Ok(member_ref.into());
+            }
+
+            let message = format!("map is missing destructured key `{}`", key_name);
+            return Err(if let Some(b) = b {
+                b.push(span, OpKind::Panic(message));
+                Error::Diverged
+            } else {
+                Error::Panic(Panic::new(span, message))
+            });
+        }
+    }
+
+    let b = b.as_mut().expect("destructuring map without builder");
+
+    let map_reg = value_to_reg(ehx, b, span, map_value, &MAP_ABI_TYPE.into());
+    let key_reg = value_to_reg(
+        ehx,
+        b,
+        span,
+        &Value::Const(key_sym.as_any_ref()),
+        &abitype::BoxedAbiType::UniqueTagged(boxed::TypeTag::Sym).into(),
+    );
+
+    let abi = GenAbi {
+        takes_task: true,
+        params: Box::new([
+            MAP_ABI_TYPE.into(),
+            abitype::BoxedAbiType::UniqueTagged(boxed::TypeTag::Sym).into(),
+        ]),
+        ret: abitype::AbiType::Boxed(abitype::BoxedAbiType::Any).into(),
+    };
+
+    let callee = Callee::StaticSymbol(StaticSymbol {
+        symbol: "arret_runtime_map_get",
+        impure: false,
+        abi,
+    });
+
+    let member_reg = b.push_reg(
+        span,
+        OpKind::Call,
+        CallOp {
+            callee,
+            impure: false,
+            args: Box::new([map_reg.into(), key_reg.into()]),
+        },
+    );
+
+    Ok(RegValue::new(member_reg, abitype::BoxedAbiType::Any.into()).into())
+}
@@ -8,8 +8,10 @@ pub mod error;
 pub mod eval_hir;
 mod inliner;
 mod intrinsic;
+mod map_member;
 pub mod ops;
 mod optimise;
+pub mod parser;
 mod polymorph;
 pub mod printer;
 mod record_field;
@@ -20,8 +22,10 @@ mod tagset;
 mod typred;
 mod value;
 mod vector_member;
+pub mod verify;
 
 pub use eval_hir::BuiltProgram;
+pub use parser::parse_program;
 pub use printer::print_program;
 pub use value::Value;
 
@@ -134,6 +134,21 @@ pub struct LoadBoxedVectorMemberOp {
     pub member_index: usize,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoadBoxedNumArrayMemberOp {
+    pub array_reg: RegId,
+    /// Index of the member to load
+    ///
+    /// This is bounds checked at runtime
+    pub index_reg: RegId,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FloatArrayScaleOp {
+    pub array_reg: RegId,
+    pub factor_reg: RegId,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct CastBoxedOp {
     pub from_reg: RegId,
@@ -180,6 +195,13 @@ pub struct LoadBoxedListLenOp {
     pub min_list_len: usize,
 }
 
+/// Loads a single member from the multiple values returned by a call
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoadValuesMemberOp {
+    pub values_reg: RegId,
+    pub member_index: usize,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct MakeCallbackOp {
     pub captures_reg: RegId,
@@ -217,13 +239,16 @@ pub enum OpKind {
     ConstBoxedInt(RegId, i64),
     ConstBoxedFloat(RegId, f64),
     ConstBoxedChar(RegId, char),
+    ConstBoxedRational(RegId, (i64, i64)),
     ConstBoxedStr(RegId, Box<str>),
     ConstBoxedSym(RegId, Rc<str>),
     ConstBoxedPair(RegId, BoxPairOp),
     ConstBoxedFunThunk(RegId, BoxFunThunkOp),
     ConstBoxedVector(RegId, Box<[RegId]>),
-    ConstBoxedSet(RegId, Box<[RegId]>),
-    ConstBoxedMap(RegId, Box<[(RegId, RegId)]>),
+    /// Constant set of elements paired with their collection hash
+    ConstBoxedSet(RegId, Box<[(u64, RegId)]>),
+    /// Constant map of keys and values paired with the collection hash of their key
+    ConstBoxedMap(RegId, Box<[(u64, RegId, RegId)]>),
 
     AllocBoxedInt(RegId, RegId),
     AllocBoxedFloat(RegId, RegId),
@@ -251,6 +276,15 @@ pub enum OpKind {
     LoadBoxedRecordClassId(RegId, RegId),
     LoadBoxedVectorLen(RegId, RegId),
     LoadBoxedVectorMember(RegId, LoadBoxedVectorMemberOp),
+    LoadBoxedNumArrayLen(RegId, RegId),
+    LoadBoxedNumArrayMember(RegId, LoadBoxedNumArrayMemberOp),
+    LoadValuesMember(RegId, LoadValuesMemberOp),
+
+    /// Adds each member of `rhs_reg` to the corresponding member of `lhs_reg` in-place
+    FloatArrayAdd(BinaryOp),
+    /// Multiplies each member of `array_reg` by `factor_reg` in-place
+    FloatArrayScale(FloatArrayScaleOp),
+    FloatArrayDotProduct(RegId, BinaryOp),
 
     Cond(CondOp),
 
@@ -294,6 +328,8 @@ pub enum OpKind {
     LoadBoxedRecordField(RegId, LoadBoxedRecordFieldOp),
 
     Ret(RegId),
+    /// Returns multiple values together in registers
+    RetValues(Box<[RegId]>),
     RetVoid,
     Unreachable,
     Panic(String),
@@ -309,6 +345,7 @@ pub enum OpCategory {
     Call,
     Cond,
     MemLoad,
+    MemStore,
     CastBoxed,
     RegOp,
     MakeCallback,
@@ -333,6 +370,7 @@ impl OpKind {
             | ConstBoxedInt(reg_id, _)
             | ConstBoxedFloat(reg_id, _)
             | ConstBoxedChar(reg_id, _)
+            | ConstBoxedRational(reg_id, _)
             | ConstBoxedStr(reg_id, _)
             | ConstBoxedSym(reg_id, _)
             | ConstBoxedPair(reg_id, _)
@@ -365,6 +403,10 @@ impl OpKind {
             | LoadBoxedRecordField(reg_id, _)
             | LoadBoxedVectorLen(reg_id, _)
             | LoadBoxedVectorMember(reg_id, _)
+            | LoadBoxedNumArrayLen(reg_id, _)
+            | LoadBoxedNumArrayMember(reg_id, _)
+            | LoadValuesMember(reg_id, _)
+            | FloatArrayDotProduct(reg_id, _)
             | FloatAdd(reg_id, _)
             | Int64Add(reg_id, _)
             | Int64CheckedAdd(reg_id, _)
@@ -398,7 +440,8 @@ impl OpKind {
             | ConstBoxedRecord(reg_id, _)
             | AllocBoxedRecord(reg_id, _) => Some(*reg_id),
             Cond(cond_op) => cond_op.reg_phi.clone().map(|reg_phi| reg_phi.output_reg),
-            Ret(_) | RetVoid | Unreachable | Panic(_) => None,
+            Ret(_) | RetValues(_) | RetVoid | Unreachable | Panic(_) | FloatArrayAdd(_)
+            | FloatArrayScale(_) => None,
         }
     }
 
@@ -420,6 +463,7 @@ impl OpKind {
             | ConstBoxedInt(_, _)
             | ConstBoxedFloat(_, _)
             | ConstBoxedChar(_, _)
+            | ConstBoxedRational(_, _)
             | ConstBoxedStr(_, _)
             | ConstBoxedSym(_, _)
             | RetVoid
@@ -439,15 +483,18 @@ impl OpKind {
             ConstBoxedFunThunk(_, box_fun_thunk_op) | AllocBoxedFunThunk(_, box_fun_thunk_op) => {
                 coll.extend(iter::once(box_fun_thunk_op.captures_reg));
             }
-            ConstBoxedVector(_, element_regs) | ConstBoxedSet(_, element_regs) => {
+            ConstBoxedVector(_, element_regs) | RetValues(element_regs) => {
                 coll.extend(element_regs.iter().copied())
             }
+            ConstBoxedSet(_, element_regs) => {
+                coll.extend(element_regs.iter().map(|(_, element)| *element))
+            }
             ConstBoxedMap(_, entry_regs) => {
                 coll.extend(
                     entry_regs
                         .iter()
-                        .map(|(key, _)| *key)
-                        .chain(entry_regs.iter().map(|(_, value)| *value)),
+                        .map(|(_, key, _)| *key)
+                        .chain(entry_regs.iter().map(|(_, _, value)| *value)),
                 );
             }
             AllocBoxedInt(_, reg_id)
@@ -502,6 +549,13 @@ impl OpKind {
                     vector_reg: reg_id, ..
                 },
             )
+            | LoadBoxedNumArrayLen(_, reg_id)
+            | LoadValuesMember(
+                _,
+                LoadValuesMemberOp {
+                    values_reg: reg_id, ..
+                },
+            )
             | Int64ToFloat(_, reg_id)
             | FloatSqrt(_, reg_id)
             | Int64BitwiseNot(_, reg_id)
@@ -535,6 +589,24 @@ impl OpKind {
             Call(_, CallOp { args, .. }) | TailCall(_, TailCallOp { args, .. }) => {
                 coll.extend(args.iter().cloned());
             }
+            LoadBoxedNumArrayMember(
+                _,
+                LoadBoxedNumArrayMemberOp {
+                    array_reg,
+                    index_reg,
+                },
+            ) => {
+                coll.extend([*array_reg, *index_reg].iter().cloned());
+            }
+            FloatArrayScale(FloatArrayScaleOp {
+                array_reg,
+                factor_reg,
+            }) => {
+                coll.extend([*array_reg, *factor_reg].iter().cloned());
+            }
+            FloatArrayAdd(binary_op) => {
+                coll.extend([binary_op.lhs_reg, binary_op.rhs_reg].iter().cloned());
+            }
             Cond(cond_op) => {
                 coll.extend(iter::once(cond_op.test_reg));
 
@@ -570,7 +642,8 @@ impl OpKind {
             | InternedSymEqual(_, binary_op)
             | TypeTagEqual(_, binary_op)
             | RecordClassIdEqual(_, binary_op)
-            | BoxIdentical(_, binary_op) => {
+            | BoxIdentical(_, binary_op)
+            | FloatArrayDotProduct(_, binary_op) => {
                 coll.extend([binary_op.lhs_reg, binary_op.rhs_reg].iter().cloned());
             }
             IntCompare(_, compare_op) | FloatCompare(_, compare_op) => {
@@ -596,7 +669,8 @@ impl OpKind {
         use crate::mir::ops::OpKind::*;
 
         match self {
-            Ret(_) | RetVoid | Unreachable | Panic(_) => true,
+            Ret(_) | RetValues(_) | RetVoid | Unreachable | Panic(_) => true,
+            FloatArrayAdd(_) | FloatArrayScale(_) => true,
             Call(_, CallOp { impure, .. }) | TailCall(_, TailCallOp { impure, .. }) => *impure,
             Cond(cond_op) => cond_op
                 .true_ops
@@ -630,6 +704,7 @@ impl OpKind {
             | ConstBoxedInt(_, _)
             | ConstBoxedFloat(_, _)
             | ConstBoxedChar(_, _)
+            | ConstBoxedRational(_, _)
             | ConstBoxedStr(_, _)
             | ConstBoxedSym(_, _)
             | ConstBoxedPair(_, _)
@@ -659,7 +734,12 @@ impl OpKind {
             | LoadBoxedRecordClassId(_, _)
             | LoadBoxedRecordField(_, _)
             | LoadBoxedVectorLen(_, _)
-            | LoadBoxedVectorMember(_, _) => OpCategory::MemLoad,
+            | LoadBoxedVectorMember(_, _)
+            | LoadBoxedNumArrayLen(_, _)
+            | LoadBoxedNumArrayMember(_, _)
+            | FloatArrayDotProduct(_, _) => OpCategory::MemLoad,
+
+            FloatArrayAdd(_) | FloatArrayScale(_) => OpCategory::MemStore,
 
             FloatAdd(_, _)
             | Int64Add(_, _)
@@ -689,9 +769,10 @@ impl OpKind {
             | Int64BitwiseNot(_, _)
             | Int64ShiftLeft(_, _)
             | Int64ArithmeticShiftRight(_, _)
-            | Int64LogicalShiftRight(_, _) => OpCategory::RegOp,
+            | Int64LogicalShiftRight(_, _)
+            | LoadValuesMember(_, _) => OpCategory::RegOp,
 
-            Ret(_) | RetVoid => OpCategory::Ret,
+            Ret(_) | RetValues(_) | RetVoid => OpCategory::Ret,
 
             Cond(_) => OpCategory::Cond,
             MakeCallback(_, _) => OpCategory::MakeCallback,
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use crate::mir::ops;
+
+fn ends_with_terminator(ops: &[ops::Op]) -> bool {
+    ops.last()
+        .map(|op| op.kind().is_terminator())
+        .unwrap_or(false)
+}
+
+fn remove_branch_const_conds(
+    ops: Box<[ops::Op]>,
+    // Regs are only defined once so this can be shared between branches
+    const_bools: &mut HashMap<ops::RegId, bool>,
+) -> Box<[ops::Op]> {
+    let mut output_ops: Vec<ops::Op> = Vec::with_capacity(ops.len());
+
+    for op in ops.into_vec() {
+        let ops::Op { span, kind } = op;
+
+        match kind {
+            ops::OpKind::ConstBool(reg, value) => {
+                const_bools.insert(reg, value);
+                output_ops.push(ops::Op::new(span, kind));
+            }
+            ops::OpKind::Cond(ops::CondOp {
+                reg_phi,
+                test_reg,
+                true_ops,
+                false_ops,
+            }) => {
+                let true_ops = remove_branch_const_conds(true_ops, const_bools);
+                let false_ops = remove_branch_const_conds(false_ops, const_bools);
+
+                // If the taken branch terminates then splicing it would also terminate the branch
+                // containing this `Cond`. This would leave any `RegPhi` for that branch without its
+                // result reg.
+                let foldable_test_value =
+                    const_bools.get(&test_reg).copied().filter(|&test_value| {
+                        !ends_with_terminator(if test_value { &true_ops } else { &false_ops })
+                    });
+
+                if let Some(test_value) = foldable_test_value {
+                    let (taken_ops, taken_result_reg) = if test_value {
+                        (
+                            true_ops,
+                            reg_phi.as_ref().map(|reg_phi| reg_phi.true_result_reg),
+                        )
+                    } else {
+                        (
+                            false_ops,
+                            reg_phi.as_ref().map(|reg_phi| reg_phi.false_result_reg),
+                        )
+                    };
+
+                    output_ops.extend(taken_ops.into_vec());
+
+                    if let (Some(reg_phi), Some(taken_result_reg)) = (reg_phi, taken_result_reg) {
+                        output_ops.push(ops::Op::new(
+                            span,
+                            ops::OpKind::Alias(reg_phi.output_reg, taken_result_reg),
+                        ));
+                    }
+                } else {
+                    output_ops.push(ops::Op::new(
+                        span,
+                        ops::OpKind::Cond(ops::CondOp {
+                            reg_phi,
+                            test_reg,
+                            true_ops,
+                            false_ops,
+                        }),
+                    ));
+                }
+            }
+            _ => {
+                output_ops.push(ops::Op::new(span, kind));
+            }
+        }
+
+        // Anything following a terminator is unreachable
+        if ends_with_terminator(&output_ops) {
+            break;
+        }
+    }
+
+    output_ops.into_boxed_slice()
+}
+
+/// Replaces any `Cond` with a constant test with the ops from the branch it takes
+///
+/// Conditions taking a branch ending in a terminator such as `Panic` are left in place. The
+/// evaluator resolves conditions on constant values without building a `Cond`. However, a
+/// test can still be built in to a `ConstBool` reg; for example, when combining the results of
+/// fieldwise comparisons. The `ConstBool` is left behind for `unused_ops` to remove.
+pub fn remove_const_conds(ops: Box<[ops::Op]>) -> Box<[ops::Op]> {
+    let mut const_bools = HashMap::new();
+    remove_branch_const_conds(ops, &mut const_bools)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn const_true_cond() {
+        let test_reg = ops::RegId::alloc();
+        let output_reg = ops::RegId::alloc();
+        let true_result_reg = ops::RegId::alloc();
+        let false_result_reg = ops::RegId::alloc();
+
+        let input_ops: Box<[ops::Op]> = Box::new([
+            ops::OpKind::ConstBool(test_reg, true).into(),
+            ops::OpKind::Cond(ops::CondOp {
+                reg_phi: Some(ops::RegPhi {
+                    output_reg,
+                    true_result_reg,
+                    false_result_reg,
+                }),
+                test_reg,
+                true_ops: Box::new([ops::OpKind::ConstInt64(true_result_reg, 1).into()]),
+                false_ops: Box::new([ops::OpKind::ConstInt64(false_result_reg, 2).into()]),
+            })
+            .into(),
+            ops::OpKind::Ret(output_reg).into(),
+        ]);
+
+        let expected_ops: Box<[ops::Op]> = Box::new([
+            ops::OpKind::ConstBool(test_reg, true).into(),
+            ops::OpKind::ConstInt64(true_result_reg, 1).into(),
+            ops::OpKind::Alias(output_reg, true_result_reg).into(),
+            ops::OpKind::Ret(output_reg).into(),
+        ]);
+
+        assert_eq!(expected_ops, remove_const_conds(input_ops));
+    }
+
+    #[test]
+    fn const_false_cond_terminating() {
+        let test_reg = ops::RegId::alloc();
+        let other_reg = ops::RegId::alloc();
+
+        let input_ops: Box<[ops::Op]> = Box::new([
+            ops::OpKind::ConstBool(test_reg, false).into(),
+            ops::OpKind::Cond(ops::CondOp {
+                reg_phi: None,
+                test_reg,
+                true_ops: Box::new([]),
+                false_ops: Box::new([ops::OpKind::Panic("false".to_owned()).into()]),
+            })
+            .into(),
+            ops::OpKind::ConstInt64(other_reg, 1).into(),
+            ops::OpKind::RetVoid.into(),
+        ]);
+
+        // Taken branches ending in a terminator aren't spliced
+        let expected_ops = input_ops.clone();
+        assert_eq!(expected_ops, remove_const_conds(input_ops));
+    }
+
+    #[test]
+    fn const_cond_terminating_inside_phi_branch() {
+        // This is treated as a parameter with an unknown value
+        let outer_test_reg = ops::RegId::alloc();
+        let outer_output_reg = ops::RegId::alloc();
+        let outer_true_result_reg = ops::RegId::alloc();
+        let outer_false_result_reg = ops::RegId::alloc();
+        let inner_test_reg = ops::RegId::alloc();
+
+        // The taken branch diverges before the outer branch defines its result reg
+        let inner_cond_op = ops::CondOp {
+            reg_phi: None,
+            test_reg: inner_test_reg,
+            true_ops: Box::new([ops::OpKind::Unreachable.into()]),
+            false_ops: Box::new([]),
+        };
+
+        let input_ops: Box<[ops::Op]> = Box::new([
+            ops::OpKind::ConstBool(inner_test_reg, true).into(),
+            ops::OpKind::Cond(ops::CondOp {
+                reg_phi: Some(ops::RegPhi {
+                    output_reg: outer_output_reg,
+                    true_result_reg: outer_true_result_reg,
+                    false_result_reg: outer_false_result_reg,
+                }),
+                test_reg: outer_test_reg,
+                true_ops: Box::new([
+                    ops::OpKind::Cond(inner_cond_op).into(),
+                    ops::OpKind::ConstInt64(outer_true_result_reg, 1).into(),
+                ]),
+                false_ops: Box::new([ops::OpKind::ConstInt64(outer_false_result_reg, 2).into()]),
+            })
+            .into(),
+            ops::OpKind::Ret(outer_output_reg).into(),
+        ]);
+
+        // Splicing the inner `Cond` would remove the definition of the outer true result reg
+        let expected_ops = input_ops.clone();
+        assert_eq!(expected_ops, remove_const_conds(input_ops));
+    }
+
+    #[test]
+    fn nested_const_cond() {
+        // This is treated as a parameter with an unknown value
+        let outer_test_reg = ops::RegId::alloc();
+        let inner_test_reg = ops::RegId::alloc();
+        let inner_result_reg = ops::RegId::alloc();
+
+        let inner_cond_op = ops::CondOp {
+            reg_phi: None,
+            test_reg: inner_test_reg,
+            true_ops: Box::new([ops::OpKind::ConstInt64(inner_result_reg, 1).into()]),
+            false_ops: Box::new([ops::OpKind::Unreachable.into()]),
+        };
+
+        let input_ops: Box<[ops::Op]> = Box::new([
+            ops::OpKind::ConstBool(inner_test_reg, true).into(),
+            ops::OpKind::Cond(ops::CondOp {
+                reg_phi: None,
+                test_reg: outer_test_reg,
+                true_ops: Box::new([ops::OpKind::Cond(inner_cond_op).into()]),
+                false_ops: Box::new([]),
+            })
+            .into(),
+            ops::OpKind::RetVoid.into(),
+        ]);
+
+        // Only the inner `Cond` has a constant test
+        let expected_ops: Box<[ops::Op]> = Box::new([
+            ops::OpKind::ConstBool(inner_test_reg, true).into(),
+            ops::OpKind::Cond(ops::CondOp {
+                reg_phi: None,
+                test_reg: outer_test_reg,
+                true_ops: Box::new([ops::OpKind::ConstInt64(inner_result_reg, 1).into()]),
+                false_ops: Box::new([]),
+            })
+            .into(),
+            ops::OpKind::RetVoid.into(),
+        ]);
+
+        assert_eq!(expected_ops, remove_const_conds(input_ops));
+    }
+}
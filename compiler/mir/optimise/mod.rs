@@ -1,23 +1,63 @@
 use crate::mir::ops;
 use crate::mir::value::Value;
+use crate::mir::verify;
+use crate::opt_level::OptLevel;
 
+mod const_cond;
 mod duplicate_alloc_ops;
 mod unused_ops;
 
-pub fn optimise_fun(fun: ops::Fun) -> ops::Fun {
-    let mut used_ops = unused_ops::remove_unused_fun_ops(fun.ops);
-    duplicate_alloc_ops::remove_redundant_alloc_ops(&mut used_ops);
+fn verify_fun_after(verify_mir: bool, fun: &ops::Fun, stage: &str) {
+    if verify_mir {
+        verify::expect_verified(verify::verify_fun(fun), stage);
+    }
+}
+
+/// Optimise a function
+///
+/// If `verify_mir` is set the function is verified once it's been built and after each
+/// optimisation.
+pub fn optimise_fun(opt_level: OptLevel, verify_mir: bool, mut fun: ops::Fun) -> ops::Fun {
+    verify_fun_after(verify_mir, &fun, "building the function");
+
+    if opt_level.optimising() {
+        fun.ops = const_cond::remove_const_conds(fun.ops);
+        verify_fun_after(verify_mir, &fun, "removing constant conditions");
+    }
+
+    fun.ops = unused_ops::remove_unused_fun_ops(fun.ops);
+    verify_fun_after(verify_mir, &fun, "removing unused ops");
 
-    ops::Fun {
-        ops: unused_ops::remove_unused_fun_ops(used_ops),
-        ..fun
+    if !opt_level.merge_alloc_ops() {
+        return fun;
     }
+
+    duplicate_alloc_ops::remove_redundant_alloc_ops(&mut fun.ops);
+    verify_fun_after(verify_mir, &fun, "removing redundant alloc ops");
+
+    fun.ops = unused_ops::remove_unused_fun_ops(fun.ops);
+    verify_fun_after(verify_mir, &fun, "removing unused ops");
+
+    fun
 }
 
 /// Optimise a function that has been inlined and returned the provided value
-pub fn optimise_inlined_fun(ops: Box<[ops::Op]>, return_value: &Value) -> Box<[ops::Op]> {
+pub fn optimise_inlined_fun(
+    opt_level: OptLevel,
+    ops: Box<[ops::Op]>,
+    return_value: &Value,
+) -> Box<[ops::Op]> {
+    let ops = if opt_level.optimising() {
+        const_cond::remove_const_conds(ops)
+    } else {
+        ops
+    };
+
     let mut used_ops = unused_ops::remove_unused_value_ops(ops, return_value);
-    duplicate_alloc_ops::remove_redundant_alloc_ops(&mut used_ops);
+
+    if opt_level.merge_alloc_ops() {
+        duplicate_alloc_ops::remove_redundant_alloc_ops(&mut used_ops);
+    }
 
     used_ops
 }
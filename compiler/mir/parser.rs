@@ -0,0 +1,1800 @@
+//! Parser for the textual MIR format written by [`print_program`](super::print_program)
+//!
+//! This allows optimisations to be tested against MIR snippets and minimised reproductions to be
+//! compiled directly with `arret compile foo.mir`. Like the printer the format is internal and
+//! unstable; it only needs to round-trip with the printer from the same version of the compiler.
+//!
+//! Registers are allocated as they're parsed so their numbers won't survive a round-trip. Record
+//! structs are identified by their name; distinct records sharing a name will be merged.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::{path, result};
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::span::{ByteIndex, FileId, Span};
+
+use arret_runtime::abitype::{self, EncodeBoxedAbiType};
+use arret_runtime::boxed;
+use arret_runtime::callback;
+
+use crate::codegen::GenAbi;
+use crate::mir::ops;
+use crate::mir::tagset::TypeTagSet;
+use crate::mir::BuiltProgram;
+use crate::reporting::new_primary_label;
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    span: Span,
+    message: String,
+}
+
+impl Error {
+    fn new(span: Span, message: impl Into<String>) -> Error {
+        Error {
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl From<Error> for Diagnostic<FileId> {
+    fn from(error: Error) -> Self {
+        let diagnostic = Diagnostic::error().with_message(error.message);
+
+        if error.span.file_id().is_some() {
+            diagnostic.with_labels(vec![new_primary_label(error.span, "invalid MIR")])
+        } else {
+            diagnostic
+        }
+    }
+}
+
+/// Program parsed from its textual MIR
+pub struct ParsedProgram {
+    pub program: BuiltProgram,
+
+    /// Paths of the libraries the program's static symbols are linked from
+    pub linked_library_paths: Vec<path::PathBuf>,
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn type_tag_for_name(name: &str) -> Option<boxed::TypeTag> {
+    boxed::ALL_TYPE_TAGS
+        .iter()
+        .find(|type_tag| type_tag.to_str() == name)
+        .cloned()
+}
+
+fn comparison_for_str(operator: &str) -> Option<ops::Comparison> {
+    match operator {
+        "<" => Some(ops::Comparison::Lt),
+        "<=" => Some(ops::Comparison::Le),
+        "==" => Some(ops::Comparison::Eq),
+        ">" => Some(ops::Comparison::Gt),
+        ">=" => Some(ops::Comparison::Ge),
+        _ => None,
+    }
+}
+
+/// Leaks a value referenced statically by the runtime's ABI types
+///
+/// Parsed programs are only built once per process so this is bounded by the size of the input.
+fn leak<T>(value: T) -> &'static T {
+    Box::leak(Box::new(value))
+}
+
+struct Parser<'src> {
+    file_id: Option<FileId>,
+    source: &'src str,
+    pos: usize,
+
+    record_structs: HashMap<String, ops::RecordStructId>,
+    private_fun_refs: Vec<(Span, ops::PrivateFunId)>,
+
+    /// Registers of the current function by their printed number
+    regs: HashMap<usize, ops::RegId>,
+}
+
+impl<'src> Parser<'src> {
+    fn new(file_id: Option<FileId>, source: &'src str) -> Self {
+        Parser {
+            file_id,
+            source,
+            pos: 0,
+            record_structs: HashMap::new(),
+            private_fun_refs: vec![],
+            regs: HashMap::new(),
+        }
+    }
+
+    fn rest(&self) -> &'src str {
+        &self.source[self.pos..]
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(self.file_id, start as ByteIndex, self.pos as ByteIndex)
+    }
+
+    fn error_at_pos(&self, message: impl Into<String>) -> Error {
+        let end = self
+            .rest()
+            .chars()
+            .next()
+            .map(|c| self.pos + c.len_utf8())
+            .unwrap_or(self.pos);
+
+        Error::new(
+            Span::new(self.file_id, self.pos as ByteIndex, end as ByteIndex),
+            message,
+        )
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            let trimmed = self.rest().trim_start();
+            self.pos = self.source.len() - trimmed.len();
+
+            if !trimmed.starts_with("//") {
+                return;
+            }
+
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn at_eof(&mut self) -> bool {
+        self.skip_trivia();
+        self.rest().is_empty()
+    }
+
+    fn peek(&mut self, token: &str) -> bool {
+        self.skip_trivia();
+        self.rest().starts_with(token)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Eats a keyword only if it isn't the prefix of a longer identifier
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek(keyword) && !self.rest()[keyword.len()..].starts_with(is_ident_char) {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error_at_pos(format!("expected `{}`", token)))
+        }
+    }
+
+    fn expect_field(&mut self, name: &str) -> Result<()> {
+        self.expect(name)?;
+        self.expect(":")
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'src str {
+        self.skip_trivia();
+
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn ident(&mut self) -> Result<&'src str> {
+        let ident = self.take_while(is_ident_char);
+
+        if ident.is_empty() {
+            Err(self.error_at_pos("expected identifier"))
+        } else {
+            Ok(ident)
+        }
+    }
+
+    /// Parses a list of items separated by commas up to and including `close`
+    fn list<T>(
+        &mut self,
+        close: &str,
+        mut parse_item: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut items = vec![];
+
+        if self.eat(close) {
+            return Ok(items);
+        }
+
+        loop {
+            items.push(parse_item(self)?);
+
+            if self.eat(close) {
+                return Ok(items);
+            }
+
+            self.expect(",")?;
+        }
+    }
+
+    fn integer<T: FromStr>(&mut self) -> Result<T> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        let sign_len = if self.rest().starts_with('-') { 1 } else { 0 };
+        let unsigned = &self.rest()[sign_len..];
+        let digits_len = unsigned
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(unsigned.len());
+
+        if digits_len == 0 {
+            return Err(self.error_at_pos("expected integer"));
+        }
+
+        self.pos += sign_len + digits_len;
+        self.source[start..self.pos]
+            .parse()
+            .map_err(|_| Error::new(self.span_from(start), "integer out of range"))
+    }
+
+    fn i64_literal(&mut self) -> Result<i64> {
+        let value = self.integer()?;
+        self.expect("i64")?;
+        Ok(value)
+    }
+
+    /// Returns the next literal token including any type suffix
+    fn literal_token(&mut self) -> &'src str {
+        self.take_while(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+')
+    }
+
+    fn f64_literal(&mut self) -> Result<f64> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        self.literal_token()
+            .strip_suffix("f64")
+            .and_then(|value_str| value_str.parse().ok())
+            .ok_or_else(|| Error::new(self.span_from(start), "expected `f64` literal"))
+    }
+
+    /// Parses a quoted literal using Rust's escape syntax
+    fn quoted_literal(&mut self, quote: char) -> Result<String> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        if !self.rest().starts_with(quote) {
+            return Err(self.error_at_pos(format!("expected `{}`", quote)));
+        }
+
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+
+        while let Some((index, c)) = chars.next() {
+            if c == quote {
+                self.pos += index + c.len_utf8();
+                return Ok(value);
+            }
+
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+
+            let escaped = match chars.next() {
+                Some((_, 'n')) => Some('\n'),
+                Some((_, 'r')) => Some('\r'),
+                Some((_, 't')) => Some('\t'),
+                Some((_, '0')) => Some('\0'),
+                Some((_, '\\')) => Some('\\'),
+                Some((_, '\'')) => Some('\''),
+                Some((_, '"')) => Some('"'),
+                Some((_, 'u')) => {
+                    let mut hex_digits = String::new();
+
+                    if let Some((_, '{')) = chars.next() {
+                        for (_, c) in &mut chars {
+                            if c == '}' {
+                                break;
+                            }
+                            hex_digits.push(c);
+                        }
+                    }
+
+                    u32::from_str_radix(&hex_digits, 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                }
+                _ => None,
+            };
+
+            if let Some(escaped) = escaped {
+                value.push(escaped);
+            } else {
+                self.pos += index;
+                return Err(self.error_at_pos("invalid escape sequence"));
+            }
+        }
+
+        Err(Error::new(self.span_from(start), "unterminated literal"))
+    }
+
+    fn string_literal(&mut self) -> Result<String> {
+        self.quoted_literal('"')
+    }
+
+    fn char_literal(&mut self) -> Result<char> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        let value = self.quoted_literal('\'')?;
+        let mut chars = value.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(Error::new(
+                self.span_from(start),
+                "character literal must contain exactly one character",
+            )),
+        }
+    }
+
+    fn type_tag(&mut self) -> Result<boxed::TypeTag> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        let name = self.ident()?;
+        type_tag_for_name(name).ok_or_else(|| {
+            Error::new(
+                self.span_from(start),
+                format!("unknown type tag `{}`", name),
+            )
+        })
+    }
+
+    fn reg(&mut self) -> Result<ops::RegId> {
+        self.expect("%")?;
+        let start = self.pos;
+
+        let digits_len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest().len());
+
+        if digits_len == 0 {
+            return Err(self.error_at_pos("expected register number"));
+        }
+
+        self.pos += digits_len;
+        let number = self.source[start..self.pos]
+            .parse()
+            .map_err(|_| Error::new(self.span_from(start), "register number out of range"))?;
+
+        Ok(*self.regs.entry(number).or_insert_with(ops::RegId::alloc))
+    }
+
+    /// Parses a register annotated with its type such as `(%1: i64)`
+    fn typed_operand(&mut self) -> Result<(ops::RegId, &'src str)> {
+        self.expect("(")?;
+        let reg = self.reg()?;
+        self.expect(":")?;
+        let operand_type = self.take_while(|c| c != ')' && !c.is_whitespace());
+        self.expect(")")?;
+
+        Ok((reg, operand_type))
+    }
+
+    fn typed_operand_of(&mut self, expected_type: &str) -> Result<ops::RegId> {
+        self.expect("(")?;
+        let reg = self.reg()?;
+        self.expect(":")?;
+        self.expect(expected_type)?;
+        self.expect(")")?;
+
+        Ok(reg)
+    }
+
+    fn float_array_operand(&mut self) -> Result<ops::RegId> {
+        self.expect("<")?;
+        let reg = self.reg()?;
+        self.expect("as")?;
+        self.expect("boxed::FloatArray>")?;
+
+        Ok(reg)
+    }
+
+    fn operator(&mut self) -> Result<&'static str> {
+        const OPERATORS: &[&str] = &[
+            "<<", ">>", "<=", ">=", "==", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^",
+        ];
+
+        OPERATORS
+            .iter()
+            .find(|operator| self.eat(operator))
+            .cloned()
+            .ok_or_else(|| self.error_at_pos("expected operator"))
+    }
+
+    fn boxed_abi_type(&mut self) -> Result<abitype::BoxedAbiType> {
+        use arret_runtime::abitype::BoxedAbiType;
+
+        self.skip_trivia();
+        let start = self.pos;
+
+        self.expect("boxed::")?;
+        let name = self.ident()?;
+
+        if self.eat("<") {
+            let boxed_abi_type = match name {
+                "Vector" => BoxedAbiType::Vector(leak(self.boxed_abi_type()?)),
+                "List" => BoxedAbiType::List(leak(self.boxed_abi_type()?)),
+                "Pair" => BoxedAbiType::Pair(leak(self.boxed_abi_type()?)),
+                "Set" => BoxedAbiType::Set(leak(self.boxed_abi_type()?)),
+                "Map" => {
+                    let key = leak(self.boxed_abi_type()?);
+                    self.expect(",")?;
+                    BoxedAbiType::Map(key, leak(self.boxed_abi_type()?))
+                }
+                _ => {
+                    return Err(Error::new(
+                        self.span_from(start),
+                        format!("unknown generic boxed type `{}`", name),
+                    ));
+                }
+            };
+
+            self.expect(">")?;
+            return Ok(boxed_abi_type);
+        }
+
+        match name {
+            "Any" => Ok(BoxedAbiType::Any),
+            "Num" => Ok(boxed::Num::BOXED_ABI_TYPE),
+            "Bool" => Ok(boxed::Bool::BOXED_ABI_TYPE),
+            _ => type_tag_for_name(name)
+                .map(BoxedAbiType::UniqueTagged)
+                .ok_or_else(|| {
+                    Error::new(
+                        self.span_from(start),
+                        format!("unknown boxed type `{}`", name),
+                    )
+                }),
+        }
+    }
+
+    fn abi_type(&mut self) -> Result<abitype::AbiType> {
+        use arret_runtime::abitype::AbiType;
+
+        if self.eat_keyword("bool") {
+            Ok(AbiType::Bool)
+        } else if self.eat_keyword("char") {
+            Ok(AbiType::Char)
+        } else if self.eat_keyword("f64") {
+            Ok(AbiType::Float)
+        } else if self.eat_keyword("i64") {
+            Ok(AbiType::Int)
+        } else if self.eat_keyword("InternedSym") {
+            Ok(AbiType::InternedSym)
+        } else if self.eat("Gc<") {
+            let boxed_abi_type = self.boxed_abi_type()?;
+            self.expect(">")?;
+            Ok(AbiType::Boxed(boxed_abi_type))
+        } else if self.eat_keyword("extern") {
+            Ok(AbiType::Callback(self.entry_point_abi_type()?))
+        } else {
+            Err(self.error_at_pos("expected ABI type"))
+        }
+    }
+
+    fn entry_point_abi_type(&mut self) -> Result<&'static callback::EntryPointAbiType> {
+        self.expect("\"C\"")?;
+        self.expect("fn")?;
+        self.expect("(")?;
+        self.expect("&mut")?;
+        self.expect("Task")?;
+        self.expect(",")?;
+        self.expect("boxed::Captures")?;
+
+        let mut params = vec![];
+        while self.eat(",") {
+            params.push(self.abi_type()?);
+        }
+
+        self.expect(")")?;
+        self.expect("->")?;
+        let ret = self.ret_abi_type()?;
+
+        Ok(leak(callback::EntryPointAbiType {
+            params: Box::leak(params.into_boxed_slice()),
+            ret,
+        }))
+    }
+
+    fn param_abi_type(&mut self) -> Result<abitype::ParamAbiType> {
+        use arret_runtime::abitype::ParamCapture;
+
+        let capture = if self.eat("NoCapture<") {
+            ParamCapture::Never
+        } else if self.eat("Capture<") {
+            ParamCapture::Always
+        } else {
+            return Ok(self.abi_type()?.into_param_abi_type());
+        };
+
+        let boxed_abi_type = self.boxed_abi_type()?;
+        self.expect(">")?;
+
+        Ok(abitype::ParamAbiType {
+            abi_type: boxed_abi_type.into(),
+            capture,
+        })
+    }
+
+    fn ret_abi_type(&mut self) -> Result<abitype::RetAbiType> {
+        use arret_runtime::abitype::RetAbiType;
+
+        if self.eat("(") {
+            let abi_types = self.list(")", Self::abi_type)?;
+
+            if abi_types.is_empty() {
+                Ok(RetAbiType::Void)
+            } else {
+                Ok(RetAbiType::Values(abi_types.into_boxed_slice()))
+            }
+        } else if self.eat_keyword("Never") {
+            Ok(RetAbiType::Never)
+        } else {
+            Ok(self.abi_type()?.into_ret_abi_type())
+        }
+    }
+
+    /// Parses a record struct reference such as `record::Point`
+    ///
+    /// Record names can contain punctuation so they're matched against the declared structs.
+    fn record_struct(&mut self) -> Result<ops::RecordStructId> {
+        self.expect("record::")?;
+        let rest = self.rest();
+
+        let matched = self
+            .record_structs
+            .iter()
+            .filter(|(name, _)| {
+                rest.starts_with(name.as_str())
+                    && rest[name.len()..]
+                        .starts_with(|c: char| c.is_whitespace() || ">:".contains(c))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(name, record_struct)| (name.len(), record_struct.clone()));
+
+        if let Some((name_len, record_struct)) = matched {
+            self.pos += name_len;
+            Ok(record_struct)
+        } else {
+            Err(self.error_at_pos("expected declared record struct"))
+        }
+    }
+
+    fn record_struct_decl(&mut self) -> Result<()> {
+        self.expect("record::")?;
+        let start = self.pos;
+
+        let name = self.take_while(|c| c != '(' && !c.is_whitespace());
+        if name.is_empty() {
+            return Err(self.error_at_pos("expected record name"));
+        }
+        let name_span = self.span_from(start);
+
+        self.expect("(")?;
+        let field_abi_types = self.list(")", Self::abi_type)?;
+        self.expect(";")?;
+
+        if self.record_structs.contains_key(name) {
+            return Err(Error::new(
+                name_span,
+                format!("duplicate record struct `{}`", name),
+            ));
+        }
+
+        self.record_structs.insert(
+            name.to_owned(),
+            ops::RecordStruct::new(name.into(), field_abi_types.into_boxed_slice()),
+        );
+
+        Ok(())
+    }
+
+    /// Parses a reference to a private function such as `%add[private-1]` or `[private-2]`
+    fn private_fun_ref(&mut self) -> Result<ops::PrivateFunId> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        if self.rest().starts_with('%') {
+            // The name is only informative; the ID identifies the function
+            let rest = self.rest();
+            self.pos += rest
+                .find(|c: char| c == '[' || c.is_whitespace())
+                .unwrap_or(rest.len());
+        }
+
+        if !self.rest().starts_with("[private-") {
+            return Err(self.error_at_pos("expected `[private-`"));
+        }
+        self.pos += "[private-".len();
+
+        let private_fun_id = ops::PrivateFunId::new(self.integer()?);
+        self.expect("]")?;
+
+        self.private_fun_refs
+            .push((self.span_from(start), private_fun_id));
+
+        Ok(private_fun_id)
+    }
+
+    fn callee(&mut self) -> Result<ops::Callee> {
+        if self.eat("<") {
+            let thunk_reg = self.reg()?;
+            self.expect("as")?;
+            self.expect("boxed::FunThunk>.entry")?;
+
+            Ok(ops::Callee::BoxedFunThunk(thunk_reg))
+        } else {
+            Ok(ops::Callee::PrivateFun(self.private_fun_ref()?))
+        }
+    }
+
+    fn box_pair_op(&mut self) -> Result<ops::BoxPairOp> {
+        self.expect_field("head")?;
+        let head_reg = self.reg()?;
+        self.expect(",")?;
+        self.expect_field("rest")?;
+        let rest_reg = self.reg()?;
+        self.expect(",")?;
+        self.expect_field("list_len")?;
+        let list_len_reg = self.reg()?;
+
+        Ok(ops::BoxPairOp {
+            head_reg,
+            rest_reg,
+            list_len_reg,
+        })
+    }
+
+    fn box_fun_thunk_op(&mut self) -> Result<ops::BoxFunThunkOp> {
+        self.expect_field("captures")?;
+        let captures_reg = self.reg()?;
+        self.expect(",")?;
+        self.expect_field("entry")?;
+        let callee = self.callee()?;
+
+        Ok(ops::BoxFunThunkOp {
+            captures_reg,
+            callee,
+        })
+    }
+
+    fn box_record_op(&mut self) -> Result<ops::BoxRecordOp> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        let record_struct = self.record_struct()?;
+        self.expect("{")?;
+
+        // Field types are redundant with the record struct's declaration
+        let field_regs = self.list("}", |parser| {
+            let field_reg = parser.reg()?;
+            parser.expect(":")?;
+            parser.abi_type()?;
+            Ok(field_reg)
+        })?;
+
+        if field_regs.len() != record_struct.field_abi_types.len() {
+            return Err(Error::new(
+                self.span_from(start),
+                format!(
+                    "expected {} fields for record struct `{}`",
+                    record_struct.field_abi_types.len(),
+                    record_struct.source_name
+                ),
+            ));
+        }
+
+        Ok(ops::BoxRecordOp {
+            record_struct,
+            field_regs: field_regs.into_boxed_slice(),
+        })
+    }
+
+    /// Parses the elements of a constant set or map followed by their hashes
+    fn hashed_elements<T>(
+        &mut self,
+        parse_element: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<(u64, T)>> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        self.expect_field("elements")?;
+        self.expect("[")?;
+        let elements = self.list("]", parse_element)?;
+        self.expect(",")?;
+        self.expect_field("hashes")?;
+        self.expect("[")?;
+        let hashes = self.list("]", Self::integer)?;
+
+        if elements.len() != hashes.len() {
+            return Err(Error::new(
+                self.span_from(start),
+                "expected a hash for each element",
+            ));
+        }
+
+        Ok(hashes.into_iter().zip(elements).collect())
+    }
+
+    fn const_rhs(&mut self, reg: ops::RegId) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        if self.eat("boxed::NIL_INSTANCE") {
+            return Ok(OpKind::ConstBoxedNil(reg, ()));
+        } else if self.eat("boxed::TRUE_INSTANCE") {
+            return Ok(OpKind::ConstBoxedTrue(reg, ()));
+        } else if self.eat("boxed::FALSE_INSTANCE") {
+            return Ok(OpKind::ConstBoxedFalse(reg, ()));
+        } else if self.eat_keyword("true") {
+            return Ok(OpKind::ConstBool(reg, true));
+        } else if self.eat_keyword("false") {
+            return Ok(OpKind::ConstBool(reg, false));
+        } else if self.peek("'") {
+            return Ok(OpKind::ConstChar(reg, self.char_literal()?));
+        } else if self.eat("TypeTag::") {
+            return Ok(OpKind::ConstTypeTag(reg, self.type_tag()?));
+        } else if self.eat("interned::InternedSym") {
+            self.expect("{")?;
+            self.expect_field("name")?;
+            let name = self.string_literal()?;
+            self.expect("}")?;
+
+            return Ok(OpKind::ConstInternedSym(reg, name.into()));
+        } else if self.peek("record::") {
+            let saved_pos = self.pos;
+            let record_struct = self.record_struct()?;
+
+            if self.eat("::CLASS_ID") {
+                return Ok(OpKind::ConstRecordClassId(reg, record_struct));
+            }
+
+            self.pos = saved_pos;
+            return Ok(OpKind::ConstBoxedRecord(reg, self.box_record_op()?));
+        } else if self.peek("%") {
+            let from_reg = self.reg()?;
+            self.expect("as")?;
+            let to_type = self.boxed_abi_type()?;
+
+            return Ok(OpKind::ConstCastBoxed(
+                reg,
+                ops::CastBoxedOp { from_reg, to_type },
+            ));
+        } else if !self.eat("boxed::") {
+            let start = self.pos;
+            let token = self.literal_token();
+
+            let kind = if let Some(value_str) = token.strip_suffix("i64") {
+                value_str
+                    .parse()
+                    .ok()
+                    .map(|value| OpKind::ConstInt64(reg, value))
+            } else if let Some(value_str) = token.strip_suffix("f64") {
+                value_str
+                    .parse()
+                    .ok()
+                    .map(|value| OpKind::ConstFloat(reg, value))
+            } else {
+                None
+            };
+
+            return kind.ok_or_else(|| Error::new(self.span_from(start), "expected constant"));
+        }
+
+        let start = self.pos;
+        let name = self.ident()?;
+        self.expect("{")?;
+
+        let kind = match name {
+            "Pair" => OpKind::ConstBoxedPair(reg, self.box_pair_op()?),
+            "Int" => {
+                self.expect_field("value")?;
+                OpKind::ConstBoxedInt(reg, self.i64_literal()?)
+            }
+            "Float" => {
+                self.expect_field("value")?;
+                OpKind::ConstBoxedFloat(reg, self.f64_literal()?)
+            }
+            "Char" => {
+                self.expect_field("value")?;
+                OpKind::ConstBoxedChar(reg, self.char_literal()?)
+            }
+            "Rational" => {
+                self.expect_field("numer")?;
+                let numer = self.i64_literal()?;
+                self.expect(",")?;
+                self.expect_field("denom")?;
+                let denom = self.i64_literal()?;
+
+                OpKind::ConstBoxedRational(reg, (numer, denom))
+            }
+            "Str" => {
+                self.expect_field("value")?;
+                OpKind::ConstBoxedStr(reg, self.string_literal()?.into_boxed_str())
+            }
+            "Sym" => {
+                self.expect_field("name")?;
+                OpKind::ConstBoxedSym(reg, self.string_literal()?.into())
+            }
+            "FunThunk" => OpKind::ConstBoxedFunThunk(reg, self.box_fun_thunk_op()?),
+            "Vector" => {
+                self.expect_field("elements")?;
+                self.expect("[")?;
+                let element_regs = self.list("]", Self::reg)?;
+
+                OpKind::ConstBoxedVector(reg, element_regs.into_boxed_slice())
+            }
+            "Set" => {
+                let elements = self.hashed_elements(Self::reg)?;
+                OpKind::ConstBoxedSet(reg, elements.into_boxed_slice())
+            }
+            "Map" => {
+                let entries = self
+                    .hashed_elements(|parser| {
+                        parser.expect("(")?;
+                        let key_reg = parser.reg()?;
+                        parser.expect(",")?;
+                        let value_reg = parser.reg()?;
+                        parser.expect(")")?;
+                        Ok((key_reg, value_reg))
+                    })?
+                    .into_iter()
+                    .map(|(hash, (key_reg, value_reg))| (hash, key_reg, value_reg))
+                    .collect();
+
+                OpKind::ConstBoxedMap(reg, entries)
+            }
+            _ => {
+                return Err(Error::new(
+                    self.span_from(start),
+                    format!("unknown constant boxed type `{}`", name),
+                ));
+            }
+        };
+
+        self.expect("}")?;
+        Ok(kind)
+    }
+
+    fn alloc_rhs(&mut self, reg: ops::RegId) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        if self.peek("record::") {
+            return Ok(OpKind::AllocBoxedRecord(reg, self.box_record_op()?));
+        }
+
+        self.expect("boxed::")?;
+        let start = self.pos;
+        let name = self.ident()?;
+        self.expect("{")?;
+
+        let kind = match name {
+            "Pair" => OpKind::AllocBoxedPair(reg, self.box_pair_op()?),
+            "Int" => {
+                self.expect_field("value")?;
+                OpKind::AllocBoxedInt(reg, self.reg()?)
+            }
+            "Float" => {
+                self.expect_field("value")?;
+                OpKind::AllocBoxedFloat(reg, self.reg()?)
+            }
+            "Char" => {
+                self.expect_field("value")?;
+                OpKind::AllocBoxedChar(reg, self.reg()?)
+            }
+            "Sym" => {
+                self.expect_field("interned")?;
+                OpKind::AllocBoxedSym(reg, self.reg()?)
+            }
+            "FunThunk" => OpKind::AllocBoxedFunThunk(reg, self.box_fun_thunk_op()?),
+            _ => {
+                return Err(Error::new(
+                    self.span_from(start),
+                    format!("unknown allocated boxed type `{}`", name),
+                ));
+            }
+        };
+
+        self.expect("}")?;
+        Ok(kind)
+    }
+
+    fn load_rhs(&mut self, reg: ops::RegId) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        self.expect("<")?;
+        let subject_reg = self.reg()?;
+        self.expect("as")?;
+
+        if self.peek("record::") {
+            let record_struct = self.record_struct()?;
+            self.expect(">")?;
+            self.expect(".")?;
+
+            self.skip_trivia();
+            let start = self.pos;
+            let field_index: usize = self.integer()?;
+
+            if field_index >= record_struct.field_abi_types.len() {
+                return Err(Error::new(
+                    self.span_from(start),
+                    format!(
+                        "record struct `{}` has no field {}",
+                        record_struct.source_name, field_index
+                    ),
+                ));
+            }
+
+            // This is redundant with the record struct's declaration
+            self.expect(":")?;
+            self.abi_type()?;
+
+            return Ok(OpKind::LoadBoxedRecordField(
+                reg,
+                ops::LoadBoxedRecordFieldOp {
+                    record_reg: subject_reg,
+                    record_struct,
+                    field_index,
+                },
+            ));
+        }
+
+        if self.eat("boxed::Any>.type_tag") {
+            self.expect("in")?;
+            self.expect("[")?;
+            let possible_type_tags: TypeTagSet =
+                self.list("]", Self::type_tag)?.into_iter().collect();
+
+            Ok(OpKind::LoadBoxedTypeTag(
+                reg,
+                ops::LoadBoxedTypeTagOp {
+                    subject_reg,
+                    possible_type_tags,
+                },
+            ))
+        } else if self.eat("boxed::Pair>.head") {
+            Ok(OpKind::LoadBoxedPairHead(reg, subject_reg))
+        } else if self.eat("boxed::Pair>.rest") {
+            Ok(OpKind::LoadBoxedPairRest(reg, subject_reg))
+        } else if self.eat("boxed::Vector>.len") {
+            Ok(OpKind::LoadBoxedVectorLen(reg, subject_reg))
+        } else if self.eat("boxed::Vector>[") {
+            let member_index = self.integer()?;
+            self.expect("]")?;
+            self.expect("where")?;
+            self.expect("<")?;
+
+            if self.reg()? != subject_reg {
+                return Err(self.error_at_pos("expected the length of the loaded vector"));
+            }
+
+            self.expect("as")?;
+            self.expect("boxed::Vector>.len")?;
+            self.expect("==")?;
+            let known_vector_len = self.integer()?;
+
+            Ok(OpKind::LoadBoxedVectorMember(
+                reg,
+                ops::LoadBoxedVectorMemberOp {
+                    vector_reg: subject_reg,
+                    known_vector_len,
+                    member_index,
+                },
+            ))
+        } else if self.eat("boxed::List>.list_len") {
+            self.expect("where")?;
+            self.expect(">")?;
+            let min_list_len = self.integer()?;
+
+            Ok(OpKind::LoadBoxedListLen(
+                reg,
+                ops::LoadBoxedListLenOp {
+                    list_reg: subject_reg,
+                    min_list_len,
+                },
+            ))
+        } else if self.eat("boxed::NumArray>.len") {
+            Ok(OpKind::LoadBoxedNumArrayLen(reg, subject_reg))
+        } else if self.eat("boxed::NumArray>[") {
+            let index_reg = self.reg()?;
+            self.expect("]")?;
+
+            Ok(OpKind::LoadBoxedNumArrayMember(
+                reg,
+                ops::LoadBoxedNumArrayMemberOp {
+                    array_reg: subject_reg,
+                    index_reg,
+                },
+            ))
+        } else if self.eat("boxed::Sym>.interned") {
+            Ok(OpKind::LoadBoxedSymInterned(reg, subject_reg))
+        } else if self.eat("boxed::Int>.value") {
+            Ok(OpKind::LoadBoxedIntValue(reg, subject_reg))
+        } else if self.eat("boxed::Float>.value") {
+            Ok(OpKind::LoadBoxedFloatValue(reg, subject_reg))
+        } else if self.eat("boxed::Char>.value") {
+            Ok(OpKind::LoadBoxedCharValue(reg, subject_reg))
+        } else if self.eat("boxed::FunThunk>.env") {
+            Ok(OpKind::LoadBoxedFunThunkCaptures(reg, subject_reg))
+        } else if self.eat("boxed::Record>.class_id") {
+            Ok(OpKind::LoadBoxedRecordClassId(reg, subject_reg))
+        } else {
+            Err(self.error_at_pos("unknown load"))
+        }
+    }
+
+    /// Parses an operation on typed operands such as `(%1: i64) + (%2: i64)`
+    fn typed_op_rhs(&mut self, reg: ops::RegId) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        self.skip_trivia();
+        let start = self.pos;
+
+        let (lhs_reg, operand_type) = self.typed_operand()?;
+
+        if operand_type == "i64" && self.eat_keyword("as") {
+            self.expect("f64")?;
+            return Ok(OpKind::Int64ToFloat(reg, lhs_reg));
+        }
+
+        let operator = self.operator()?;
+
+        if operator == "<<" || operator == ">>" {
+            let shift_op = ops::ShiftOp {
+                int_reg: lhs_reg,
+                bit_count: self.integer()?,
+            };
+
+            return match (operand_type, operator) {
+                ("u64", "<<") => Ok(OpKind::Int64ShiftLeft(reg, shift_op)),
+                ("u64", ">>") => Ok(OpKind::Int64LogicalShiftRight(reg, shift_op)),
+                ("i64", ">>") => Ok(OpKind::Int64ArithmeticShiftRight(reg, shift_op)),
+                _ => Err(Error::new(
+                    self.span_from(start),
+                    format!("unsupported shift of `{}`", operand_type),
+                )),
+            };
+        }
+
+        let rhs_reg = self.typed_operand_of(operand_type)?;
+        let binary_op = ops::BinaryOp { lhs_reg, rhs_reg };
+
+        let kind = match (operand_type, operator) {
+            ("f64", "+") => OpKind::FloatAdd(reg, binary_op),
+            ("f64", "-") => OpKind::FloatSub(reg, binary_op),
+            ("f64", "*") => OpKind::FloatMul(reg, binary_op),
+            ("f64", "/") => OpKind::FloatDiv(reg, binary_op),
+            ("u64", "&") => OpKind::Int64BitwiseAnd(reg, binary_op),
+            ("u64", "|") => OpKind::Int64BitwiseOr(reg, binary_op),
+            ("u64", "^") => OpKind::Int64BitwiseXor(reg, binary_op),
+            ("TypeTag", "==") => OpKind::TypeTagEqual(reg, binary_op),
+            ("bool", "==") => OpKind::BoolEqual(reg, binary_op),
+            ("char", "==") => OpKind::CharEqual(reg, binary_op),
+            ("interned::InternedSym", "==") => OpKind::InternedSymEqual(reg, binary_op),
+            ("boxed::RecordClassId", "==") => OpKind::RecordClassIdEqual(reg, binary_op),
+            ("i64", _) | ("f64", _) if comparison_for_str(operator).is_some() => {
+                let compare_op = ops::CompareOp {
+                    comparison: comparison_for_str(operator).unwrap(),
+                    lhs_reg,
+                    rhs_reg,
+                };
+
+                if operand_type == "i64" {
+                    OpKind::IntCompare(reg, compare_op)
+                } else {
+                    OpKind::FloatCompare(reg, compare_op)
+                }
+            }
+            _ => {
+                return Err(Error::new(
+                    self.span_from(start),
+                    format!("unsupported operator `{}` for `{}`", operator, operand_type),
+                ));
+            }
+        };
+
+        Ok(kind)
+    }
+
+    /// Parses integer arithmetic following `checked` or `unchecked`
+    fn int_arith_rhs(&mut self, reg: ops::RegId, checked: bool) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        self.skip_trivia();
+        let start = self.pos;
+
+        let lhs_reg = self.typed_operand_of("i64")?;
+        let operator = self.operator()?;
+        let rhs_reg = self.typed_operand_of("i64")?;
+        let binary_op = ops::BinaryOp { lhs_reg, rhs_reg };
+
+        match (checked, operator) {
+            (true, "+") => Ok(OpKind::Int64CheckedAdd(reg, binary_op)),
+            (true, "-") => Ok(OpKind::Int64CheckedSub(reg, binary_op)),
+            (true, "*") => Ok(OpKind::Int64CheckedMul(reg, binary_op)),
+            (true, "/") => Ok(OpKind::Int64CheckedDiv(reg, binary_op)),
+            (true, "%") => Ok(OpKind::Int64CheckedRem(reg, binary_op)),
+            (false, "+") => Ok(OpKind::Int64Add(reg, binary_op)),
+            (false, "/") => Ok(OpKind::Int64Div(reg, binary_op)),
+            (false, "%") => Ok(OpKind::Int64Rem(reg, binary_op)),
+            _ => Err(Error::new(
+                self.span_from(start),
+                format!("unsupported integer operator `{}`", operator),
+            )),
+        }
+    }
+
+    fn call_rhs(&mut self, reg: ops::RegId, impure: bool) -> Result<ops::OpKind> {
+        if self.eat_keyword("recur") {
+            self.expect("(")?;
+            self.expect("%task")?;
+
+            let mut args = vec![];
+            while self.eat(",") {
+                args.push(self.reg()?);
+            }
+            self.expect(")")?;
+
+            return Ok(ops::OpKind::TailCall(
+                reg,
+                ops::TailCallOp {
+                    impure,
+                    args: args.into_boxed_slice(),
+                },
+            ));
+        }
+
+        let callee = if self.eat("@") {
+            None
+        } else {
+            Some(self.callee()?)
+        };
+
+        let symbol = if callee.is_none() {
+            self.take_while(|c| c != '(' && !c.is_whitespace())
+        } else {
+            ""
+        };
+
+        self.expect("(")?;
+
+        let mut takes_task = false;
+        let mut params = vec![];
+        let mut args = vec![];
+
+        if !self.eat(")") {
+            loop {
+                if args.is_empty() && !takes_task && self.eat_keyword("%task") {
+                    takes_task = true;
+                } else {
+                    args.push(self.reg()?);
+                    self.expect(":")?;
+                    params.push(self.param_abi_type()?);
+                }
+
+                if self.eat(")") {
+                    break;
+                }
+
+                self.expect(",")?;
+            }
+        }
+
+        self.expect(":")?;
+        let ret = self.ret_abi_type()?;
+
+        // Only static symbols need their ABI; it's implied for other callees
+        let callee = callee.unwrap_or_else(|| {
+            ops::Callee::StaticSymbol(ops::StaticSymbol {
+                symbol: Box::leak(symbol.to_owned().into_boxed_str()),
+                impure,
+                abi: GenAbi {
+                    takes_task,
+                    params: params.into_boxed_slice(),
+                    ret,
+                },
+            })
+        });
+
+        Ok(ops::OpKind::Call(
+            reg,
+            ops::CallOp {
+                callee,
+                impure,
+                args: args.into_boxed_slice(),
+            },
+        ))
+    }
+
+    /// Returns true if the next token is a block's result register
+    fn at_block_result(&mut self) -> bool {
+        self.skip_trivia();
+
+        self.rest()
+            .strip_prefix('%')
+            .filter(|after_percent| after_percent.starts_with(|c: char| c.is_ascii_digit()))
+            .map(|after_percent| {
+                after_percent
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .trim_start()
+                    .starts_with('}')
+            })
+            .unwrap_or(false)
+    }
+
+    /// Parses ops up to and including the closing brace of a block
+    ///
+    /// If `has_result` is true the block must end with the register containing its result.
+    fn block(&mut self, has_result: bool) -> Result<(Box<[ops::Op]>, Option<ops::RegId>)> {
+        let mut ops = vec![];
+
+        loop {
+            if has_result && self.at_block_result() {
+                let result_reg = self.reg()?;
+                self.expect("}")?;
+
+                return Ok((ops.into_boxed_slice(), Some(result_reg)));
+            }
+
+            if self.peek("}") {
+                if has_result {
+                    return Err(self.error_at_pos("expected result register"));
+                }
+
+                self.expect("}")?;
+                return Ok((ops.into_boxed_slice(), None));
+            }
+
+            if self.at_eof() {
+                return Err(self.error_at_pos("expected `}`"));
+            }
+
+            ops.push(self.op()?);
+        }
+    }
+
+    fn cond(&mut self, output_reg: Option<ops::RegId>) -> Result<ops::OpKind> {
+        let has_phi = output_reg.is_some();
+
+        let test_reg = self.reg()?;
+        self.expect("{")?;
+        let (true_ops, true_result_reg) = self.block(has_phi)?;
+
+        let has_else = if has_phi {
+            self.expect("else")?;
+            true
+        } else {
+            self.eat_keyword("else")
+        };
+
+        let (false_ops, false_result_reg) = if has_else {
+            self.expect("{")?;
+            self.block(has_phi)?
+        } else {
+            (Box::new([]) as Box<[ops::Op]>, None)
+        };
+
+        if has_phi {
+            self.expect(";")?;
+        }
+
+        let reg_phi = match (output_reg, true_result_reg, false_result_reg) {
+            (Some(output_reg), Some(true_result_reg), Some(false_result_reg)) => {
+                Some(ops::RegPhi {
+                    output_reg,
+                    true_result_reg,
+                    false_result_reg,
+                })
+            }
+            _ => None,
+        };
+
+        Ok(ops::OpKind::Cond(ops::CondOp {
+            reg_phi,
+            test_reg,
+            true_ops,
+            false_ops,
+        }))
+    }
+
+    fn assign_rhs(&mut self, reg: ops::RegId) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        let kind = if self.eat_keyword("if") {
+            // Conds contain their own terminator
+            return self.cond(Some(reg));
+        } else if self.eat_keyword("const") {
+            self.const_rhs(reg)?
+        } else if self.eat_keyword("alloc") {
+            self.alloc_rhs(reg)?
+        } else if self.eat_keyword("pure") {
+            self.call_rhs(reg, false)?
+        } else if self.eat_keyword("impure") {
+            self.call_rhs(reg, true)?
+        } else if self.eat_keyword("checked") {
+            self.int_arith_rhs(reg, true)?
+        } else if self.eat_keyword("unchecked") {
+            self.int_arith_rhs(reg, false)?
+        } else if self.eat_keyword("sqrt") {
+            self.expect("(")?;
+            let radicand_reg = self.reg()?;
+            self.expect(":")?;
+            self.expect("f64")?;
+            self.expect(")")?;
+
+            OpKind::FloatSqrt(reg, radicand_reg)
+        } else if self.eat_keyword("dot") {
+            self.expect("(")?;
+            let lhs_reg = self.float_array_operand()?;
+            self.expect(",")?;
+            let rhs_reg = self.float_array_operand()?;
+            self.expect(")")?;
+
+            OpKind::FloatArrayDotProduct(reg, ops::BinaryOp { lhs_reg, rhs_reg })
+        } else if self.eat("callback::Callback") {
+            self.expect("{")?;
+            self.expect_field("captures")?;
+            let captures_reg = self.reg()?;
+            self.expect(",")?;
+            self.expect_field("entry_point")?;
+            let callee = self.callee()?;
+            self.expect("}")?;
+
+            OpKind::MakeCallback(
+                reg,
+                ops::MakeCallbackOp {
+                    captures_reg,
+                    callee,
+                },
+            )
+        } else if self.eat("~") {
+            OpKind::Int64BitwiseNot(reg, self.typed_operand_of("u64")?)
+        } else if self.eat("&") {
+            let lhs_reg = self.typed_operand_of("boxed::Any")?;
+            self.expect("==")?;
+            self.expect("&")?;
+            let rhs_reg = self.typed_operand_of("boxed::Any")?;
+
+            OpKind::BoxIdentical(reg, ops::BinaryOp { lhs_reg, rhs_reg })
+        } else if self.peek("<") {
+            self.load_rhs(reg)?
+        } else if self.peek("(") {
+            self.typed_op_rhs(reg)?
+        } else {
+            let from_reg = self.reg()?;
+
+            if self.eat(".values[") {
+                let member_index = self.integer()?;
+                self.expect("]")?;
+
+                OpKind::LoadValuesMember(
+                    reg,
+                    ops::LoadValuesMemberOp {
+                        values_reg: from_reg,
+                        member_index,
+                    },
+                )
+            } else if self.eat_keyword("as") {
+                let to_type = self.boxed_abi_type()?;
+                OpKind::CastBoxed(reg, ops::CastBoxedOp { from_reg, to_type })
+            } else {
+                OpKind::Alias(reg, from_reg)
+            }
+        };
+
+        self.expect(";")?;
+        Ok(kind)
+    }
+
+    fn op_kind(&mut self) -> Result<ops::OpKind> {
+        use crate::mir::ops::OpKind;
+
+        if self.eat_keyword("return") {
+            let kind = if self.peek(";") {
+                OpKind::RetVoid
+            } else if self.eat("(") {
+                OpKind::RetValues(self.list(")", Self::reg)?.into_boxed_slice())
+            } else {
+                OpKind::Ret(self.reg()?)
+            };
+
+            self.expect(";")?;
+            Ok(kind)
+        } else if self.eat_keyword("unreachable") {
+            self.expect(";")?;
+            Ok(OpKind::Unreachable)
+        } else if self.eat_keyword("panic") {
+            self.expect("(")?;
+            let message = self.string_literal()?;
+            self.expect(")")?;
+            self.expect(";")?;
+
+            Ok(OpKind::Panic(message))
+        } else if self.eat_keyword("if") {
+            self.cond(None)
+        } else if self.peek("<") {
+            let array_reg = self.float_array_operand()?;
+
+            let kind = if self.eat("+=") {
+                OpKind::FloatArrayAdd(ops::BinaryOp {
+                    lhs_reg: array_reg,
+                    rhs_reg: self.float_array_operand()?,
+                })
+            } else {
+                self.expect("*=")?;
+
+                OpKind::FloatArrayScale(ops::FloatArrayScaleOp {
+                    array_reg,
+                    factor_reg: self.typed_operand_of("f64")?,
+                })
+            };
+
+            self.expect(";")?;
+            Ok(kind)
+        } else {
+            let reg = self.reg()?;
+            self.expect("=")?;
+            self.assign_rhs(reg)
+        }
+    }
+
+    fn op(&mut self) -> Result<ops::Op> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        let kind = self.op_kind()?;
+        Ok(ops::Op::new(self.span_from(start), kind))
+    }
+
+    /// Parses a function following its calling convention
+    fn fun(
+        &mut self,
+        start: usize,
+        call_conv: ops::CallConv,
+    ) -> Result<(Option<ops::PrivateFunId>, ops::Fun)> {
+        self.regs.clear();
+
+        self.expect("fn")?;
+        let name = self.take_while(|c| c != '[' && c != '(' && !c.is_whitespace());
+
+        let private_fun_id = if self.rest().starts_with("[private-") {
+            self.pos += "[private-".len();
+            let private_fun_id = ops::PrivateFunId::new(self.integer()?);
+            self.expect("]")?;
+
+            Some(private_fun_id)
+        } else if name.is_empty() && self.rest().starts_with("[anonymous]") {
+            self.pos += "[anonymous]".len();
+            None
+        } else if name.is_empty() {
+            return Err(self.error_at_pos("expected function name"));
+        } else {
+            None
+        };
+
+        self.expect("(")?;
+        self.expect("%task")?;
+
+        let mut params = vec![];
+        let mut param_regs = vec![];
+        while self.eat(",") {
+            param_regs.push(self.reg()?);
+            self.expect(":")?;
+            params.push(self.abi_type()?);
+        }
+
+        self.expect(")")?;
+        self.expect("->")?;
+        let ret = self.ret_abi_type()?;
+        let span = self.span_from(start);
+
+        self.expect("{")?;
+        let (ops, _) = self.block(false)?;
+
+        let fun = ops::Fun {
+            span,
+            source_name: if name.is_empty() {
+                None
+            } else {
+                Some(name.into())
+            },
+            abi: ops::OpsAbi {
+                call_conv,
+                params: params.into_boxed_slice(),
+                ret,
+            },
+            param_regs: param_regs.into_boxed_slice(),
+            ops,
+        };
+
+        Ok((private_fun_id, fun))
+    }
+}
+
+/// Parses a program from the textual MIR written by [`print_program`](super::print_program)
+pub fn parse_program(file_id: Option<FileId>, source: &str) -> Result<ParsedProgram> {
+    let mut parser = Parser::new(file_id, source);
+
+    let mut linked_library_paths = vec![];
+    let mut main = None;
+    let mut private_funs = HashMap::new();
+
+    while !parser.at_eof() {
+        let start = parser.pos;
+
+        if parser.eat_keyword("struct") {
+            parser.record_struct_decl()?;
+            continue;
+        }
+
+        let call_conv = if parser.eat_keyword("extern") {
+            if parser.eat_keyword("crate") {
+                linked_library_paths.push(parser.string_literal()?.into());
+                parser.expect(";")?;
+                continue;
+            }
+
+            parser.expect("\"C\"")?;
+            ops::CallConv::Ccc
+        } else {
+            ops::CallConv::FastCc
+        };
+
+        let (private_fun_id, fun) = parser.fun(start, call_conv)?;
+        let fun_span = fun.span;
+
+        let duplicate = if let Some(private_fun_id) = private_fun_id {
+            private_funs.insert(private_fun_id, fun).is_some()
+        } else {
+            main.replace(fun).is_some()
+        };
+
+        if duplicate {
+            return Err(Error::new(fun_span, "duplicate function definition"));
+        }
+    }
+
+    for (span, private_fun_id) in &parser.private_fun_refs {
+        if !private_funs.contains_key(private_fun_id) {
+            return Err(Error::new(*span, "reference to undefined private function"));
+        }
+    }
+
+    let main = main.ok_or_else(|| parser.error_at_pos("expected main function"))?;
+
+    Ok(ParsedProgram {
+        program: BuiltProgram {
+            main,
+            private_funs,
+            boxing_warnings: vec![],
+        },
+        linked_library_paths,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::context::LinkedLibrary;
+    use crate::mir::print_program;
+
+    /// Renumbers registers in the order they first appear
+    fn renumber_regs(mir: &str) -> String {
+        let mut reg_numbers: HashMap<&str, usize> = HashMap::new();
+        let mut renumbered = String::new();
+        let mut rest = mir;
+
+        while let Some(percent_index) = rest.find('%') {
+            renumbered.push_str(&rest[..=percent_index]);
+            rest = &rest[percent_index + 1..];
+
+            let digits_len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+
+            if digits_len > 0 {
+                let next_number = reg_numbers.len() + 1;
+                let number = *reg_numbers
+                    .entry(&rest[..digits_len])
+                    .or_insert(next_number);
+
+                renumbered.push_str(&number.to_string());
+                rest = &rest[digits_len..];
+            }
+        }
+
+        renumbered.push_str(rest);
+        renumbered
+    }
+
+    fn assert_round_trips(mir: &str) {
+        let parsed_program = parse_program(None, mir).unwrap();
+
+        let linked_libraries: Vec<Arc<LinkedLibrary>> = parsed_program
+            .linked_library_paths
+            .into_iter()
+            .map(|target_path| Arc::new(LinkedLibrary::unloaded(target_path)))
+            .collect();
+
+        let mut printed = vec![];
+        print_program(
+            &mut printed,
+            &parsed_program.program,
+            &linked_libraries,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            renumber_regs(mir),
+            renumber_regs(&String::from_utf8(printed).unwrap())
+        );
+    }
+
+    #[test]
+    fn round_trip_program() {
+        assert_round_trips(
+            r#"extern crate "/usr/lib/libstdlib.a";
+struct record::Point(i64, Gc<boxed::Any>);
+
+fn add-one[private-1](%task, %1: i64) -> i64 {
+  %2 = const 1i64;
+  %3 = checked (%1: i64) + (%2: i64);
+  return %3;
+}
+
+extern "C" fn [private-2](%task, %4: Gc<boxed::Any>, %5: Gc<boxed::List<boxed::Any>>) -> Gc<boxed::Any> {
+  %6 = <%5 as boxed::List>.list_len where > 1;
+  %7 = %5 as boxed::Pair<boxed::Any>;
+  %8 = <%7 as boxed::Pair>.head;
+  return %8;
+}
+
+fn [private-3](%task, %9: Gc<boxed::FloatArray>, %10: f64) -> (i64, f64) {
+  <%9 as boxed::FloatArray> *= (%10: f64);
+  %11 = dot(<%9 as boxed::FloatArray>, <%9 as boxed::FloatArray>);
+  %12 = <%9 as boxed::NumArray>.len;
+  %13 = sqrt(%11: f64);
+  %14 = (%13: f64) <= (%10: f64);
+  if %14 {
+    panic("too \"small\"\n");
+  } else {
+    %15 = unchecked (%12: i64) % (%12: i64);
+  }
+  return (%12, %13);
+}
+
+fn main!(%task) -> () {
+  %16 = const 'a';
+  %17 = const -1.5f64;
+  %18 = const boxed::Str { value: "hello" };
+  %19 = const boxed::Sym { name: "sym" };
+  %20 = const boxed::Set { elements: [%19], hashes: [12345] };
+  %21 = const boxed::Map { elements: [(%19, %18)], hashes: [12345] };
+  %22 = const boxed::Vector { elements: [%18, %19] };
+  %23 = const boxed::Rational { numer: 1i64, denom: 2i64 };
+  %24 = const boxed::NIL_INSTANCE;
+  %25 = const boxed::FunThunk { captures: %24, entry: [private-2] };
+  %26 = const 5i64;
+  %27 = pure %add-one[private-1](%task, %26: i64): i64;
+  %28 = alloc boxed::Int { value: %27 };
+  %29 = alloc record::Point { %27: i64, %28: Gc<boxed::Any> };
+  %30 = <%29 as record::Point>.1: Gc<boxed::Any>;
+  %31 = <%30 as boxed::Any>.type_tag in [Float, Int];
+  %32 = const TypeTag::Int;
+  %33 = (%31: TypeTag) == (%32: TypeTag);
+  %34 = if %33 {
+    %35 = <%30 as boxed::Int>.value;
+    %35
+  } else {
+    %36 = const 0i64;
+    %36
+  };
+  %37 = (%34: i64) >> 2;
+  %38 = (%37: u64) & (%26: u64);
+  %39 = impure <%25 as boxed::FunThunk>.entry(%task, %24: Gc<boxed::Any>, %24: Gc<boxed::List<boxed::Any>>): Gc<boxed::Any>;
+  %40 = impure @arret_stdlib_print(%task, %39: NoCapture<boxed::Any>): ();
+  %41 = pure [private-3](%task, %22: Gc<boxed::FloatArray>, %17: f64): (i64, f64);
+  %42 = %41.values[1];
+  %43 = <%22 as boxed::Vector>[1] where <%22 as boxed::Vector>.len == 2;
+  if %33 {
+    unreachable;
+  }
+  %44 = const record::Point::CLASS_ID;
+  return;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn comments_ignored() {
+        let parsed_program = parse_program(
+            None,
+            "// Comment before the function\nfn main!(%task) -> () {\n  // Comment in the body\n  return;\n}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![ops::OpKind::RetVoid],
+            parsed_program
+                .program
+                .main
+                .ops
+                .iter()
+                .map(|op| op.kind.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    fn parse_error(source: &str) -> Error {
+        match parse_program(None, source) {
+            Ok(_) => panic!("unexpectedly parsed MIR"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn undefined_private_fun() {
+        let error = parse_error(
+            "fn main!(%task) -> () {\n  %1 = const boxed::NIL_INSTANCE;\n  %2 = const boxed::FunThunk { captures: %1, entry: [private-1] };\n  return;\n}\n",
+        )
+        ;
+
+        assert_eq!("reference to undefined private function", error.message());
+    }
+
+    #[test]
+    fn invalid_constant() {
+        let source = "fn main!(%task) -> () {\n  %1 = const 1u8;\n  return;\n}\n";
+        let error = parse_error(source);
+
+        assert_eq!("expected constant", error.message());
+        assert_eq!(
+            source.find("1u8").unwrap() as ByteIndex,
+            error.span().start()
+        );
+    }
+}
@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use std::io::{Result, Write};
 use std::iter;
+use std::sync::Arc;
 
 use codespan_reporting::files::Files as _;
 
 use arret_syntax::span::Span;
 
 use crate::codegen::GenAbi;
+use crate::context::LinkedLibrary;
 use crate::mir::ops;
 use crate::mir::BuiltProgram;
 use crate::source::SourceLoader;
@@ -31,11 +33,21 @@ fn private_fun_to_string(
     private_funs: &HashMap<ops::PrivateFunId, ops::Fun>,
     private_fun_id: ops::PrivateFunId,
 ) -> String {
-    private_funs[&private_fun_id]
+    // Source names aren't unique so they're always followed by the ID
+    let source_name = private_funs[&private_fun_id]
         .source_name
         .clone()
         .map(|s| format!("%{}", s))
-        .unwrap_or_else(|| format!("[private-{}]", private_fun_id.to_u32()))
+        .unwrap_or_default();
+
+    format!("{}[private-{}]", source_name, private_fun_id.to_u32())
+}
+
+fn hashes_to_string(hashes: impl Iterator<Item = u64>) -> String {
+    hashes
+        .map(|hash| hash.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
 fn callee_to_string(
@@ -162,7 +174,7 @@ fn print_branch(
                 writeln!(w, "%{} = const {}i64;", reg.get(), value)?
             }
             ops::OpKind::ConstChar(reg, value) => {
-                writeln!(w, "%{} = const {:?}", reg.get(), value)?
+                writeln!(w, "%{} = const {:?};", reg.get(), value)?
             }
             ops::OpKind::ConstFloat(reg, value) => {
                 writeln!(w, "%{} = const {}f64;", reg.get(), value)?
@@ -197,27 +209,29 @@ fn print_branch(
             )?,
             ops::OpKind::ConstBoxedSet(reg, element_regs) => writeln!(
                 w,
-                "%{} = const boxed::Set {{ elements: [{}] }};",
+                "%{} = const boxed::Set {{ elements: [{}], hashes: [{}] }};",
                 reg.get(),
                 element_regs
                     .iter()
-                    .map(|element_reg| format!("%{}", element_reg.get()))
+                    .map(|(_, element_reg)| format!("%{}", element_reg.get()))
                     .collect::<Vec<String>>()
-                    .join(", ")
+                    .join(", "),
+                hashes_to_string(element_regs.iter().map(|(hash, _)| *hash))
             )?,
             ops::OpKind::ConstBoxedMap(reg, entry_regs) => writeln!(
                 w,
-                "%{} = const boxed::Map {{ elements: [{}] }};",
+                "%{} = const boxed::Map {{ elements: [{}], hashes: [{}] }};",
                 reg.get(),
                 entry_regs
                     .iter()
-                    .map(|(key_reg, value_reg)| format!(
+                    .map(|(_, key_reg, value_reg)| format!(
                         "(%{}, %{})",
                         key_reg.get(),
                         value_reg.get()
                     ))
                     .collect::<Vec<String>>()
-                    .join(", ")
+                    .join(", "),
+                hashes_to_string(entry_regs.iter().map(|(hash, _, _)| *hash))
             )?,
             ops::OpKind::ConstRecordClassId(reg, record_class_id) => writeln!(
                 w,
@@ -293,6 +307,15 @@ fn print_branch(
                     value_reg.get()
                 )?;
             }
+            ops::OpKind::ConstBoxedRational(reg, (numer, denom)) => {
+                writeln!(
+                    w,
+                    "%{} = const boxed::Rational {{ numer: {}i64, denom: {}i64 }};",
+                    reg.get(),
+                    numer,
+                    denom
+                )?;
+            }
             ops::OpKind::ConstBoxedFloat(reg, value) => {
                 writeln!(
                     w,
@@ -445,7 +468,7 @@ fn print_branch(
             ) => {
                 writeln!(
                     w,
-                    "%{reg} = <%{vector_reg} as boxed::Vector>[{member_index}] where <${vector_reg} as boxed::Vector>.len == {known_vector_len};",
+                    "%{reg} = <%{vector_reg} as boxed::Vector>[{member_index}] where <%{vector_reg} as boxed::Vector>.len == {known_vector_len};",
                     reg = reg.get(),
                     vector_reg = vector_reg.get(),
                     known_vector_len = known_vector_len,
@@ -475,6 +498,44 @@ fn print_branch(
                     list_reg.get(),
                 )?;
             }
+            ops::OpKind::LoadBoxedNumArrayLen(reg, array_reg) => {
+                writeln!(
+                    w,
+                    "%{} = <%{} as boxed::NumArray>.len;",
+                    reg.get(),
+                    array_reg.get(),
+                )?;
+            }
+            ops::OpKind::LoadBoxedNumArrayMember(
+                reg,
+                ops::LoadBoxedNumArrayMemberOp {
+                    array_reg,
+                    index_reg,
+                },
+            ) => {
+                writeln!(
+                    w,
+                    "%{} = <%{} as boxed::NumArray>[%{}];",
+                    reg.get(),
+                    array_reg.get(),
+                    index_reg.get(),
+                )?;
+            }
+            ops::OpKind::LoadValuesMember(
+                reg,
+                ops::LoadValuesMemberOp {
+                    values_reg,
+                    member_index,
+                },
+            ) => {
+                writeln!(
+                    w,
+                    "%{} = %{}.values[{}];",
+                    reg.get(),
+                    values_reg.get(),
+                    member_index
+                )?;
+            }
             ops::OpKind::LoadBoxedSymInterned(reg, sym_reg) => {
                 writeln!(
                     w,
@@ -557,6 +618,34 @@ fn print_branch(
                     rhs_reg.get(),
                 )?;
             }
+            ops::OpKind::FloatArrayAdd(ops::BinaryOp { lhs_reg, rhs_reg }) => {
+                writeln!(
+                    w,
+                    "<%{} as boxed::FloatArray> += <%{} as boxed::FloatArray>;",
+                    lhs_reg.get(),
+                    rhs_reg.get(),
+                )?;
+            }
+            ops::OpKind::FloatArrayScale(ops::FloatArrayScaleOp {
+                array_reg,
+                factor_reg,
+            }) => {
+                writeln!(
+                    w,
+                    "<%{} as boxed::FloatArray> *= (%{}: f64);",
+                    array_reg.get(),
+                    factor_reg.get(),
+                )?;
+            }
+            ops::OpKind::FloatArrayDotProduct(reg, ops::BinaryOp { lhs_reg, rhs_reg }) => {
+                writeln!(
+                    w,
+                    "%{} = dot(<%{} as boxed::FloatArray>, <%{} as boxed::FloatArray>);",
+                    reg.get(),
+                    lhs_reg.get(),
+                    rhs_reg.get(),
+                )?;
+            }
             ops::OpKind::FloatAdd(reg, ops::BinaryOp { lhs_reg, rhs_reg }) => {
                 writeln!(
                     w,
@@ -862,6 +951,16 @@ fn print_branch(
             ops::OpKind::Ret(reg) => {
                 writeln!(w, "return %{};", reg.get())?;
             }
+            ops::OpKind::RetValues(regs) => {
+                writeln!(
+                    w,
+                    "return ({});",
+                    regs.iter()
+                        .map(|reg| format!("%{}", reg.get()))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )?;
+            }
             ops::OpKind::RetVoid => {
                 writeln!(w, "return;")?;
             }
@@ -884,12 +983,14 @@ pub fn print_fun(
     ops_fun: &ops::Fun,
     private_fun_id: Option<ops::PrivateFunId>,
 ) -> Result<()> {
-    let fun_name = ops_fun
-        .source_name
-        .clone()
-        .map(|s| s.to_string())
-        .or_else(|| private_fun_id.map(|pfi| private_fun_to_string(private_funs, pfi)))
-        .unwrap_or_else(|| "[anonymous]".into());
+    let fun_name = match (&ops_fun.source_name, private_fun_id) {
+        (Some(source_name), Some(private_fun_id)) => {
+            format!("{}[private-{}]", source_name, private_fun_id.to_u32())
+        }
+        (None, Some(private_fun_id)) => format!("[private-{}]", private_fun_id.to_u32()),
+        (Some(source_name), None) => source_name.to_string(),
+        (None, None) => "[anonymous]".to_owned(),
+    };
 
     let call_conv_name = match ops_fun.abi.call_conv {
         ops::CallConv::Ccc => "extern \"C\" ",
@@ -919,16 +1020,73 @@ pub fn print_fun(
     Ok(())
 }
 
+fn collect_record_structs(ops: &[ops::Op], record_structs: &mut Vec<ops::RecordStructId>) {
+    for op in ops {
+        let record_struct = match &op.kind {
+            ops::OpKind::ConstRecordClassId(_, record_struct)
+            | ops::OpKind::ConstBoxedRecord(_, ops::BoxRecordOp { record_struct, .. })
+            | ops::OpKind::AllocBoxedRecord(_, ops::BoxRecordOp { record_struct, .. })
+            | ops::OpKind::LoadBoxedRecordField(
+                _,
+                ops::LoadBoxedRecordFieldOp { record_struct, .. },
+            ) => record_struct,
+            ops::OpKind::Cond(cond_op) => {
+                collect_record_structs(&cond_op.true_ops, record_structs);
+                collect_record_structs(&cond_op.false_ops, record_structs);
+                continue;
+            }
+            _ => continue,
+        };
+
+        if !record_structs.contains(record_struct) {
+            record_structs.push(record_struct.clone());
+        }
+    }
+}
+
 /// Prints a textual representation of a program's MIR to to `w`
 ///
-/// This is an internal, undocumented and unstable format that has no equivalent parser. It's
-/// intended to aid human debugging an optimisation.
+/// This is an internal, undocumented and unstable format intended to aid human debugging of
+/// optimisations. It can be parsed back by [`parse_program`](super::parser::parse_program) to
+/// test optimisations or compile minimised reproductions.
 pub fn print_program(
     w: &mut dyn Write,
     program: &BuiltProgram,
+    linked_libraries: &[Arc<LinkedLibrary>],
     source_loader: Option<&SourceLoader>,
 ) -> Result<()> {
-    for (private_fun_id, private_fun) in &program.private_funs {
+    for linked_library in linked_libraries {
+        writeln!(w, "extern crate {:?};", linked_library.target_path())?;
+    }
+
+    let mut private_funs: Vec<_> = program.private_funs.iter().collect();
+    private_funs.sort_unstable_by_key(|(private_fun_id, _)| private_fun_id.to_u32());
+
+    let mut record_structs = vec![];
+    for (_, private_fun) in &private_funs {
+        collect_record_structs(&private_fun.ops, &mut record_structs);
+    }
+    collect_record_structs(&program.main.ops, &mut record_structs);
+
+    for record_struct in &record_structs {
+        writeln!(
+            w,
+            "struct record::{}({});",
+            record_struct.source_name,
+            record_struct
+                .field_abi_types
+                .iter()
+                .map(|field_abi_type| field_abi_type.to_rust_str())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )?;
+    }
+
+    if !linked_libraries.is_empty() || !record_structs.is_empty() {
+        writeln!(w)?;
+    }
+
+    for (private_fun_id, private_fun) in private_funs {
         if private_fun.source_name.is_none() {
             if let Some(human_location) = span_to_human_location(source_loader, private_fun.span) {
                 writeln!(w, "// Anonymous function defined at {}", human_location)?;
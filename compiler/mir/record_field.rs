@@ -1,9 +1,10 @@
 use arret_syntax::span::Span;
 
+use arret_runtime::abitype;
 use arret_runtime::boxed;
 use arret_runtime::boxed::prelude::*;
 
-use crate::mir::builder::TryToBuilder;
+use crate::mir::builder::{Builder, BuiltReg, TryToBuilder};
 use crate::mir::eval_hir::EvalHirCtx;
 use crate::mir::value;
 use crate::mir::value::Value;
@@ -74,3 +75,163 @@ pub fn load_record_field(
         }
     }
 }
+
+/// Builds a dispatch on the record class loading the field from each of the candidate classes
+///
+/// The final candidate is assumed to match without testing its class.
+fn build_record_field_dispatch(
+    ehx: &mut EvalHirCtx,
+    b: &mut Builder,
+    span: Span,
+    record_reg: BuiltReg,
+    record_class_id_reg: BuiltReg,
+    candidates: &[(record::ConsId, usize)],
+    result_abi_type: &abitype::AbiType,
+) -> BuiltReg {
+    use crate::mir::ops::*;
+    use crate::mir::value::build_reg::value_to_reg;
+
+    let (candidate_cons, candidate_field_index) = &candidates[0];
+
+    let load_candidate_field = |ehx: &mut EvalHirCtx, b: &mut Builder| {
+        let record_value: Value = value::RegValue::new(
+            record_reg,
+            abitype::BoxedAbiType::UniqueTagged(boxed::TypeTag::Record).into(),
+        )
+        .into();
+
+        let field_value = load_record_field(
+            ehx,
+            b,
+            span,
+            candidate_cons,
+            &record_value,
+            *candidate_field_index,
+        );
+
+        value_to_reg(ehx, b, span, &field_value, result_abi_type)
+    };
+
+    if candidates.len() == 1 {
+        return load_candidate_field(ehx, b);
+    }
+
+    let mut true_b = Builder::new();
+    let true_result_reg = load_candidate_field(ehx, &mut true_b);
+
+    let mut false_b = Builder::new();
+    let false_result_reg = build_record_field_dispatch(
+        ehx,
+        &mut false_b,
+        span,
+        record_reg,
+        record_class_id_reg,
+        &candidates[1..],
+        result_abi_type,
+    );
+
+    let candidate_class_id_reg = b.push_reg(
+        span,
+        OpKind::ConstRecordClassId,
+        ehx.evaled_record_class_for_cons(candidate_cons)
+            .record_struct
+            .clone(),
+    );
+
+    let is_candidate_reg = b.push_reg(
+        span,
+        OpKind::RecordClassIdEqual,
+        BinaryOp {
+            lhs_reg: record_class_id_reg.into(),
+            rhs_reg: candidate_class_id_reg.into(),
+        },
+    );
+
+    let output_reg = b.alloc_local();
+    b.push(
+        span,
+        OpKind::Cond(CondOp {
+            reg_phi: Some(RegPhi {
+                output_reg: output_reg.into(),
+                true_result_reg: true_result_reg.into(),
+                false_result_reg: false_result_reg.into(),
+            }),
+            test_reg: is_candidate_reg.into(),
+            true_ops: true_b.into_ops(),
+            false_ops: false_b.into_ops(),
+        }),
+    );
+
+    output_reg
+}
+
+/// Loads a field through the accessor of a record constructor with rest fields
+///
+/// Values of these instance types can be records of any class with the same fields. The field is
+/// found by name in the value's class if it's known. Otherwise we dispatch on the value's class at
+/// runtime.
+pub fn load_open_record_field(
+    ehx: &mut EvalHirCtx,
+    b: &mut impl TryToBuilder,
+    span: Span,
+    record_cons: &record::ConsId,
+    record_value: &Value,
+    field_index: usize,
+) -> Value {
+    use crate::mir::ops::*;
+    use crate::mir::value::build_reg::value_to_reg;
+    use crate::mir::value::types::known_record_cons_for_value;
+
+    let field_name = record_cons.fields()[field_index].name().clone();
+
+    if let Some(known_cons) = known_record_cons_for_value(ehx, record_value).cloned() {
+        let known_field_index = known_cons
+            .field_index(&field_name)
+            .expect("record value missing field required by its type");
+
+        return load_record_field(ehx, b, span, &known_cons, record_value, known_field_index);
+    }
+
+    let candidates: Vec<(record::ConsId, usize)> = ehx
+        .record_conses_with_fields_of(record_cons)
+        .into_iter()
+        .map(|candidate_cons| {
+            let candidate_field_index = candidate_cons.field_index(&field_name).unwrap();
+            (candidate_cons, candidate_field_index)
+        })
+        .collect();
+
+    let result_abi_type = ehx
+        .evaled_record_class_for_cons(record_cons)
+        .record_struct
+        .field_abi_types[field_index]
+        .clone();
+
+    let b = if let Some(b) = b.try_to_builder() {
+        b
+    } else {
+        panic!("need builder to access field of boxed record reg");
+    };
+
+    let record_reg = value_to_reg(
+        ehx,
+        b,
+        span,
+        record_value,
+        &abitype::BoxedAbiType::UniqueTagged(boxed::TypeTag::Record).into(),
+    );
+
+    let record_class_id_reg = b.push_reg(span, OpKind::LoadBoxedRecordClassId, record_reg.into());
+
+    let field_reg = build_record_field_dispatch(
+        ehx,
+        b,
+        span,
+        record_reg,
+        record_class_id_reg,
+        &candidates,
+        &result_abi_type,
+    );
+
+    value::RegValue::new(field_reg, result_abi_type).into()
+}
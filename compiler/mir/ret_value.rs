@@ -32,6 +32,19 @@ pub fn build_value_ret(
             abitype::RetAbiType::Void => {
                 b.push(span, ops::OpKind::RetVoid);
             }
+            abitype::RetAbiType::Values(abi_types) => {
+                let mut list_iter = result_value.into_unsized_list_iter();
+
+                let member_regs = abi_types
+                    .iter()
+                    .map(|abi_type| {
+                        let member_value = list_iter.next_unchecked(b, span);
+                        value_to_reg(ehx, b, span, &member_value, abi_type).into()
+                    })
+                    .collect();
+
+                b.push(span, ops::OpKind::RetValues(member_regs));
+            }
         },
         Err(Error::Diverged) => {}
         Err(other) => {
@@ -40,12 +53,38 @@ pub fn build_value_ret(
     }
 }
 
-pub fn ret_reg_to_value(ret_reg: BuiltReg, ret_abi: abitype::RetAbiType) -> Result<Value> {
+pub fn ret_reg_to_value(
+    b: &mut Builder,
+    span: Span,
+    ret_reg: BuiltReg,
+    ret_abi: abitype::RetAbiType,
+) -> Result<Value> {
     match ret_abi {
         abitype::RetAbiType::Inhabited(abi_type) => {
             Ok(value::RegValue::new(ret_reg, abi_type).into())
         }
         abitype::RetAbiType::Never => Err(Error::Diverged),
         abitype::RetAbiType::Void => Ok(Value::List(Box::new([]), None)),
+        abitype::RetAbiType::Values(abi_types) => {
+            let member_values = abi_types
+                .into_vec()
+                .into_iter()
+                .enumerate()
+                .map(|(member_index, abi_type)| {
+                    let member_reg = b.push_reg(
+                        span,
+                        ops::OpKind::LoadValuesMember,
+                        ops::LoadValuesMemberOp {
+                            values_reg: ret_reg.into(),
+                            member_index,
+                        },
+                    );
+
+                    value::RegValue::new(member_reg, abi_type).into()
+                })
+                .collect();
+
+            Ok(Value::List(member_values, None))
+        }
     }
 }
@@ -85,6 +85,9 @@ pub fn build_rust_fun_app(
             Err(Error::Diverged)
         }
         RetAbiType::Inhabited(abi_type) => Ok(reg_to_value(ehx, ret_reg, abi_type, ret_ty)),
+        RetAbiType::Values(_) => {
+            unreachable!("Rust function returning multiple values");
+        }
     }
 }
 
@@ -122,12 +125,16 @@ pub fn ops_for_rust_fun(
 
     build_value_ret(ehx, &mut b, span, app_result, &wanted_abi.ret);
 
-    optimise_fun(ops::Fun {
-        span,
-        source_name: Some(fun_symbol.into()),
+    optimise_fun(
+        ehx.opt_level(),
+        ehx.verify_mir(),
+        ops::Fun {
+            span,
+            source_name: Some(fun_symbol.into()),
 
-        abi: wanted_abi.into(),
-        param_regs,
-        ops: b.into_ops(),
-    })
+            abi: wanted_abi.into(),
+            param_regs,
+            ops: b.into_ops(),
+        },
+    )
 }
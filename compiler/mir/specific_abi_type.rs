@@ -30,6 +30,11 @@ fn specific_boxed_abi_type_for_type_tag(type_tag: TypeTag) -> &'static abitype::
         TypeTag::Record => &TOP_RECORD_BOXED_ABI_TYPE,
         TypeTag::Set => &boxed::Set::<boxed::Any>::BOXED_ABI_TYPE,
         TypeTag::Map => &boxed::Map::<boxed::Any, boxed::Any>::BOXED_ABI_TYPE,
+        TypeTag::FloatArray => &boxed::FloatArray::BOXED_ABI_TYPE,
+        TypeTag::IntArray => &boxed::IntArray::BOXED_ABI_TYPE,
+        TypeTag::Bytes => &boxed::Bytes::BOXED_ABI_TYPE,
+        TypeTag::Rational => &boxed::Rational::BOXED_ABI_TYPE,
+        TypeTag::BigInt => &boxed::BigInt::BOXED_ABI_TYPE,
     }
 }
 
@@ -45,7 +50,7 @@ fn specific_abi_type_for_type_tag(type_tag: TypeTag) -> abitype::AbiType {
     }
 }
 
-fn specific_boxed_abi_type_for_type_tags(
+pub fn specific_boxed_abi_type_for_type_tags(
     possible_type_tags: TypeTagSet,
 ) -> &'static abitype::BoxedAbiType {
     use arret_runtime::abitype::EncodeBoxedAbiType;
@@ -116,6 +121,17 @@ pub fn specific_abi_type_for_ty_ref<M: ty::Pm>(ty_ref: &ty::Ref<M>) -> abitype::
 pub fn specific_ret_abi_type_for_ty_ref<M: ty::Pm>(ty_ref: &ty::Ref<M>) -> abitype::RetAbiType {
     if ty_ref == &ty::List::empty().into() {
         abitype::RetAbiType::Void
+    } else if let Some(Ty::Values(member_tys)) = ty_ref.try_to_fixed() {
+        if member_tys.is_empty() {
+            abitype::RetAbiType::Void
+        } else {
+            abitype::RetAbiType::Values(
+                member_tys
+                    .iter()
+                    .map(specific_abi_type_for_ty_ref)
+                    .collect(),
+            )
+        }
     } else {
         specific_abi_type_for_type_tags(ty_ref.into()).into()
     }
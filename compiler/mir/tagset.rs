@@ -89,8 +89,8 @@ where
 {
     fn from(ty_ref: &'a ty::Ref<M>) -> TypeTagSet {
         match ty_ref.resolve_to_ty() {
-            Ty::Any => TypeTagSet::all(),
-            Ty::Int => TypeTag::Int.into(),
+            Ty::Any | Ty::Dyn => TypeTagSet::all(),
+            Ty::Int | Ty::IntRange(_) => TypeTag::Int.into(),
             Ty::Float => TypeTag::Float.into(),
             Ty::Char => TypeTag::Char.into(),
             Ty::Bool => [TypeTag::True, TypeTag::False].iter().collect(),
@@ -102,8 +102,18 @@ where
             Ty::Fun(_) | Ty::TopFun(_) | Ty::TyPred(_) | Ty::EqPred => TypeTag::FunThunk.into(),
             Ty::Vector(_) | Ty::Vectorof(_) => TypeTag::Vector.into(),
             Ty::Set(_) => TypeTag::Set.into(),
-            Ty::Map(_) => TypeTag::Map.into(),
+            Ty::Map(_) | Ty::KeyedMap(_) => TypeTag::Map.into(),
+            Ty::FloatArray => TypeTag::FloatArray.into(),
+            Ty::IntArray => TypeTag::IntArray.into(),
+            Ty::Bytes => TypeTag::Bytes.into(),
+            Ty::Rational => TypeTag::Rational.into(),
+            Ty::BigInt => TypeTag::BigInt.into(),
             Ty::TopRecord | Ty::RecordClass(_) | Ty::Record(_) => TypeTag::Record.into(),
+            Ty::Recursive(instance) => {
+                ty::recursive::with_unfolded(instance, |unfolded| TypeTagSet::from(unfolded))
+                    .unwrap_or_else(TypeTagSet::all)
+            }
+            Ty::Newtype(newtype) => TypeTagSet::from(newtype.repr()),
             Ty::List(list) => {
                 if list.is_empty() {
                     TypeTag::Nil.into()
@@ -113,6 +123,13 @@ where
                     [TypeTag::Nil, TypeTag::Pair].iter().collect()
                 }
             }
+            Ty::Values(members) => {
+                if members.is_empty() {
+                    TypeTag::Nil.into()
+                } else {
+                    TypeTag::Pair.into()
+                }
+            }
             Ty::Union(members) => members
                 .iter()
                 .map(TypeTagSet::from)
@@ -5,6 +5,7 @@ use arret_runtime::boxed;
 use arret_runtime::boxed::prelude::*;
 
 use crate::mir::builder::{Builder, BuiltReg};
+use crate::mir::error::{Error, Panic, Result};
 use crate::mir::eval_hir::EvalHirCtx;
 use crate::mir::ops::*;
 use crate::mir::tagset::TypeTagSet;
@@ -39,6 +40,11 @@ fn type_tags_for_test_ty(test_ty: &ty::pred::TestTy) -> TypeTagSet {
         TestTy::Set => boxed::TypeTag::Set.into(),
         TestTy::Map => boxed::TypeTag::Map.into(),
         TestTy::TopRecord => boxed::TypeTag::Record.into(),
+        TestTy::FloatArray => boxed::TypeTag::FloatArray.into(),
+        TestTy::IntArray => boxed::TypeTag::IntArray.into(),
+        TestTy::Bytes => boxed::TypeTag::Bytes.into(),
+        TestTy::Rational => boxed::TypeTag::Rational.into(),
+        TestTy::BigInt => boxed::TypeTag::BigInt.into(),
         TestTy::RecordClass(_) => {
             todo!("record classes");
         }
@@ -82,35 +88,18 @@ fn build_is_type_tag(
     )
 }
 
-fn eval_tagged_ty_pred(
+/// Builds a boolean register indicating if the subject's type tag is in `qualifying_type_tags`
+fn build_is_in_type_tags(
     ehx: &mut EvalHirCtx,
-    b: &mut Option<Builder>,
+    b: &mut Builder,
     span: Span,
     subject_value: &Value,
+    possible_type_tags: TypeTagSet,
     qualifying_type_tags: TypeTagSet,
-) -> Value {
-    use crate::mir::value::types::possible_type_tags_for_value;
-    let possible_type_tags = possible_type_tags_for_value(subject_value);
-
-    if possible_type_tags.is_subset(qualifying_type_tags) {
-        // Statically true
-        return boxed::TRUE_INSTANCE.as_any_ref().into();
-    } else if qualifying_type_tags.is_disjoint(possible_type_tags) {
-        // Statically false
-        return boxed::FALSE_INSTANCE.as_any_ref().into();
-    }
-
-    let b = if let Some(some_b) = b {
-        some_b
-    } else {
-        panic!(
-            "runtime tagged type predicate without builder: {:?} is in type tag set {:?}",
-            subject_value, qualifying_type_tags
-        );
-    };
-
+) -> BuiltReg {
     let subject_type_tag_reg = build_load_type_tag(ehx, b, span, subject_value, possible_type_tags);
-    let result_reg = (qualifying_type_tags & possible_type_tags)
+
+    (qualifying_type_tags & possible_type_tags)
         .into_iter()
         .fold(None, |tail_result_reg: Option<BuiltReg>, test_tag| {
             let is_test_tag = build_is_type_tag(b, span, subject_type_tag_reg, test_tag);
@@ -137,7 +126,44 @@ fn eval_tagged_ty_pred(
                 Some(is_test_tag)
             }
         })
-        .unwrap();
+        .unwrap()
+}
+
+fn eval_tagged_ty_pred(
+    ehx: &mut EvalHirCtx,
+    b: &mut Option<Builder>,
+    span: Span,
+    subject_value: &Value,
+    qualifying_type_tags: TypeTagSet,
+) -> Value {
+    use crate::mir::value::types::possible_type_tags_for_value;
+    let possible_type_tags = possible_type_tags_for_value(subject_value);
+
+    if possible_type_tags.is_subset(qualifying_type_tags) {
+        // Statically true
+        return boxed::TRUE_INSTANCE.as_any_ref().into();
+    } else if qualifying_type_tags.is_disjoint(possible_type_tags) {
+        // Statically false
+        return boxed::FALSE_INSTANCE.as_any_ref().into();
+    }
+
+    let b = if let Some(some_b) = b {
+        some_b
+    } else {
+        panic!(
+            "runtime tagged type predicate without builder: {:?} is in type tag set {:?}",
+            subject_value, qualifying_type_tags
+        );
+    };
+
+    let result_reg = build_is_in_type_tags(
+        ehx,
+        b,
+        span,
+        subject_value,
+        possible_type_tags,
+        qualifying_type_tags,
+    );
 
     reg_to_value(
         ehx,
@@ -299,3 +325,72 @@ pub fn eval_ty_pred(
         }
     }
 }
+
+/// Checks a `Dyn` value satisfies `required_ty` at runtime
+///
+/// Only the type tag of the value is checked. The members of collections and the signatures of
+/// functions are trusted. A value failing the check panics at runtime or returns an error during
+/// compile time evaluation.
+pub fn eval_dyn_check(
+    ehx: &mut EvalHirCtx,
+    b: &mut Option<Builder>,
+    span: Span,
+    subject_value: Value,
+    required_ty: &ty::Ref<ty::Mono>,
+) -> Result<Value> {
+    use crate::hir::str_for_ty_ref;
+    use crate::mir::value::types::possible_type_tags_for_value;
+
+    let possible_type_tags = possible_type_tags_for_value(&subject_value);
+    let qualifying_type_tags = TypeTagSet::from(required_ty);
+
+    if possible_type_tags.is_subset(qualifying_type_tags) {
+        // Statically satisfied
+        return Ok(subject_value);
+    }
+
+    let message = format!(
+        "dynamic value does not have type `{}`",
+        str_for_ty_ref(required_ty)
+    );
+
+    if qualifying_type_tags.is_disjoint(possible_type_tags) {
+        // Statically unsatisfied
+        return Err(if let Some(b) = b {
+            b.push(span, OpKind::Panic(message));
+            Error::Diverged
+        } else {
+            Error::Panic(Panic::new(span, message))
+        });
+    }
+
+    let b = if let Some(some_b) = b {
+        some_b
+    } else {
+        panic!(
+            "runtime dynamic check without builder: {:?} is in type tag set {:?}",
+            subject_value, qualifying_type_tags
+        );
+    };
+
+    let is_satisfied_reg = build_is_in_type_tags(
+        ehx,
+        b,
+        span,
+        &subject_value,
+        possible_type_tags,
+        qualifying_type_tags,
+    );
+
+    b.push(
+        span,
+        OpKind::Cond(CondOp {
+            reg_phi: None,
+            test_reg: is_satisfied_reg.into(),
+            true_ops: Box::new([]),
+            false_ops: Box::new([Op::new(span, OpKind::Panic(message))]),
+        }),
+    );
+
+    Ok(subject_value)
+}
@@ -19,10 +19,17 @@ struct ArretFunConsts {
     fun_expr: hir::Fun<hir::Inferred>,
 }
 
+/// Functions bound by the same `(letrec)`
+///
+/// Every function in the group shares the same environment values. This allows a function to be
+/// rebuilt from any of its siblings' environments.
+type LetrecGroup = Rc<[(hir::LocalId, Rc<ArretFunConsts>)]>;
+
 #[derive(Clone, Debug)]
 pub struct ArretFun {
     consts: Rc<ArretFunConsts>,
     env_values: EnvValues,
+    letrec_group: Option<LetrecGroup>,
 }
 
 impl ArretFun {
@@ -42,9 +49,43 @@ impl ArretFun {
                 fun_expr,
             }),
             env_values,
+            letrec_group: None,
         }
     }
 
+    /// Creates a group of functions bound by the same `(letrec)`
+    ///
+    /// `env_values` must contain the values captured by every function in the group.
+    pub fn new_letrec_group(
+        module_id: Option<ModuleId>,
+        env_ty_args: TyArgs<ty::Mono>,
+        env_values: EnvValues,
+        funs: impl IntoIterator<Item = (hir::LocalId, Option<DataStr>, hir::Fun<hir::Inferred>)>,
+    ) -> Vec<(hir::LocalId, ArretFun)> {
+        let letrec_group: LetrecGroup = funs
+            .into_iter()
+            .map(|(local_id, source_name, fun_expr)| {
+                let consts = Rc::new(ArretFunConsts {
+                    id: ArretFunId::alloc(),
+                    module_id,
+                    source_name,
+                    env_ty_args: env_ty_args.clone(),
+                    fun_expr,
+                });
+
+                (local_id, consts)
+            })
+            .collect();
+
+        let group_fun = ArretFun {
+            consts: letrec_group[0].1.clone(),
+            env_values,
+            letrec_group: Some(letrec_group),
+        };
+
+        group_fun.letrec_sibling_values().collect()
+    }
+
     pub fn id(&self) -> ArretFunId {
         self.consts.id
     }
@@ -80,9 +121,29 @@ impl ArretFun {
         ArretFun {
             consts: self.consts.clone(),
             env_values,
+            letrec_group: self.letrec_group.clone(),
         }
     }
 
+    /// Returns the functions bound by the same `(letrec)` as this function
+    ///
+    /// This includes the function itself. The functions share this function's environment values
+    /// so they can be loaded alongside its captures.
+    pub fn letrec_sibling_values(&self) -> impl Iterator<Item = (hir::LocalId, ArretFun)> + '_ {
+        self.letrec_group
+            .iter()
+            .flat_map(|letrec_group| letrec_group.iter())
+            .map(move |(local_id, consts)| {
+                let sibling_fun = ArretFun {
+                    consts: consts.clone(),
+                    env_values: self.env_values.clone(),
+                    letrec_group: self.letrec_group.clone(),
+                };
+
+                (*local_id, sibling_fun)
+            })
+    }
+
     /// Indicates if this `ArretFun` is used in multiple places
     ///
     /// This is a heuristic; if a `Fun` is bound to a variable this will return true regardless
@@ -4,6 +4,7 @@ use arret_runtime::abitype;
 use arret_runtime::boxed;
 use arret_runtime::boxed::refs::Gc;
 
+use crate::crash;
 use crate::mir::builder::Builder;
 use crate::mir::builder::BuiltReg;
 use crate::mir::eval_hir::EvalHirCtx;
@@ -17,6 +18,50 @@ enum RestLen {
     Loaded(BuiltReg),
 }
 
+/// Returns if a value built at compile time can be used in runtime code
+///
+/// Values owning storage outside of their box, such as big integers, can't be built as constants.
+pub fn can_build_const(ehx: &EvalHirCtx, any_ref: Gc<boxed::Any>) -> bool {
+    use arret_runtime::boxed::prelude::*;
+
+    match any_ref.as_subtype() {
+        boxed::AnySubtype::Int(_)
+        | boxed::AnySubtype::Float(_)
+        | boxed::AnySubtype::Rational(_)
+        | boxed::AnySubtype::Char(_)
+        | boxed::AnySubtype::Str(_)
+        | boxed::AnySubtype::Sym(_)
+        | boxed::AnySubtype::False(_)
+        | boxed::AnySubtype::True(_)
+        | boxed::AnySubtype::Nil(_) => true,
+        boxed::AnySubtype::Pair(pair_ref) => {
+            can_build_const(ehx, pair_ref.head())
+                && can_build_const(ehx, pair_ref.rest().as_any_ref())
+        }
+        boxed::AnySubtype::Record(record_ref) => {
+            record_ref
+                .field_values(ehx.as_heap())
+                .all(|field_value| match field_value {
+                    boxed::FieldValue::Boxed(field_ref) => can_build_const(ehx, field_ref),
+                    _ => true,
+                })
+        }
+        boxed::AnySubtype::Vector(vector_ref) => vector_ref
+            .iter()
+            .all(|element_ref| can_build_const(ehx, element_ref)),
+        boxed::AnySubtype::Set(set_ref) => set_ref
+            .iter()
+            .all(|element_ref| can_build_const(ehx, element_ref)),
+        boxed::AnySubtype::Map(map_ref) => map_ref.iter().all(|(key_ref, value_ref)| {
+            can_build_const(ehx, key_ref) && can_build_const(ehx, value_ref)
+        }),
+        boxed::AnySubtype::FunThunk(fun_thunk_ref) => ehx
+            .jit_boxed_to_fun_value(unsafe { Gc::new(fun_thunk_ref as *const _) })
+            .is_some(),
+        _ => false,
+    }
+}
+
 fn const_to_reg(
     ehx: &mut EvalHirCtx,
     b: &mut Builder,
@@ -60,6 +105,16 @@ fn const_to_reg(
 
             b.cast_boxed_cond(span, &from_abi_type, from_reg, to_abi_type.clone())
         }
+        (boxed::AnySubtype::Rational(rational_ref), abitype::AbiType::Boxed(to_abi_type)) => {
+            let from_abi_type = boxed::TypeTag::Rational.into();
+            let from_reg = b.push_reg(
+                span,
+                OpKind::ConstBoxedRational,
+                (rational_ref.numer(), rational_ref.denom()),
+            );
+
+            b.cast_boxed_cond(span, &from_abi_type, from_reg, to_abi_type.clone())
+        }
         (boxed::AnySubtype::Char(char_ref), abitype::AbiType::Boxed(to_abi_type)) => {
             let from_abi_type = boxed::TypeTag::Char.into();
             let from_reg = b.push_reg(span, OpKind::ConstBoxedChar, char_ref.value());
@@ -193,14 +248,18 @@ fn const_to_reg(
             let element_regs = set_ref
                 .iter()
                 .map(|element_ref| {
-                    const_to_reg(
+                    let hash = boxed::collection_hash(ehx.as_heap(), element_ref);
+
+                    let element_reg = const_to_reg(
                         ehx,
                         b,
                         span,
                         element_ref,
                         &abitype::BoxedAbiType::Any.into(),
                     )
-                    .into()
+                    .into();
+
+                    (hash, element_reg)
                 })
                 .collect();
 
@@ -217,6 +276,8 @@ fn const_to_reg(
             let entry_regs = map_ref
                 .iter()
                 .map(|(key_ref, value_ref)| {
+                    let hash = boxed::collection_hash(ehx.as_heap(), key_ref);
+
                     let key_reg =
                         const_to_reg(ehx, b, span, key_ref, &abitype::BoxedAbiType::Any.into())
                             .into();
@@ -225,7 +286,7 @@ fn const_to_reg(
                         const_to_reg(ehx, b, span, value_ref, &abitype::BoxedAbiType::Any.into())
                             .into();
 
-                    (key_reg, value_reg)
+                    (hash, key_reg, value_reg)
                 })
                 .collect();
 
@@ -246,6 +307,13 @@ fn const_to_reg(
 
             value_to_reg(ehx, b, span, &fun_value, abi_type)
         }
+        (_, abitype::AbiType::Boxed(_)) => crash::unsupported(
+            Some(span),
+            format!(
+                "using a `{}` value built at compile time in runtime code",
+                any_ref.header().type_tag().to_str()
+            ),
+        ),
         (subtype, abi_type) => unimplemented!(
             "Unimplemented const {:?} to reg {:?} conversion",
             subtype,
@@ -541,7 +609,10 @@ fn reg_to_reg(
 
     match (&reg_value.abi_type, abi_type) {
         (from, to) if from == to => reg_value.reg,
-        (_, abitype::AbiType::Boxed(to_boxed)) => reg_to_boxed_reg(b, span, reg_value, to_boxed),
+        (from, abitype::AbiType::Boxed(to_boxed)) => {
+            ehx.note_implicit_boxing(span, from);
+            reg_to_boxed_reg(b, span, reg_value, to_boxed)
+        }
         (abitype::AbiType::Boxed(from_boxed), abitype::AbiType::Int) => {
             let boxed_int_reg =
                 b.cast_boxed_cond(span, from_boxed, reg_value.reg, TypeTag::Int.into());
@@ -10,6 +10,14 @@ use crate::mir::value::Value;
 use crate::ty;
 use crate::ty::Ty;
 
+fn singleton_int_for_ty_ref<M: ty::Pm>(arret_ty: &ty::Ref<M>) -> Option<i64> {
+    if let Ty::IntRange(range) = arret_ty.resolve_to_ty() {
+        range.as_singleton()
+    } else {
+        None
+    }
+}
+
 fn reg_to_value_with_constraints<M>(
     heap: &mut impl boxed::AsHeap,
     reg: BuiltReg,
@@ -37,6 +45,8 @@ where
         // information. This means we will need to rebuild the sym every time it's referenced but
         // this should be a net win.
         boxed::Sym::new(heap, value.as_ref()).as_any_ref().into()
+    } else if let Some(value) = singleton_int_for_ty_ref(arret_ty) {
+        boxed::Int::new(heap, value).as_any_ref().into()
     } else {
         value::RegValue {
             reg,
@@ -213,6 +213,7 @@ mod test {
 
     use arret_runtime::boxed;
     use arret_runtime::boxed::prelude::*;
+    use arret_runtime_syntax::diff::assert_boxed_eq;
 
     use crate::source::EMPTY_SPAN;
 
@@ -305,7 +306,7 @@ mod test {
 
             if let Value::Const(next_ref) = next_value {
                 let expected_ref = boxed::Int::new(&mut heap, *expected).as_any_ref();
-                assert!(expected_ref.eq_in_heap(&heap, &next_ref));
+                assert_boxed_eq(&heap, expected_ref, next_ref);
             } else {
                 panic!("expected const value, got {:?}", next_value);
             }
@@ -333,7 +334,7 @@ mod test {
 
             if let Value::Const(next_ref) = next_value {
                 let expected_ref = boxed::Int::new(&mut heap, *expected).as_any_ref();
-                assert!(expected_ref.eq_in_heap(&heap, &next_ref));
+                assert_boxed_eq(&heap, expected_ref, next_ref);
             } else {
                 panic!("expected const value, got {:?}", next_value);
             }
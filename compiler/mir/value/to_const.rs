@@ -123,6 +123,21 @@ pub fn list_to_const(
 /// Attempts to convert a MIR value to a constant boxed values
 ///
 /// Non-singleton regs do not have a constant value at compile time; they will return None
+/// Returns if a value only contains constant data
+///
+/// Unlike [`value_to_const`] this rejects functions. These can be converted to constants but would
+/// no longer be inlined at their use sites.
+pub fn is_const_data(value: &Value) -> bool {
+    match value {
+        Value::Const(_) => true,
+        Value::List(fixed, rest) => {
+            fixed.iter().all(is_const_data) && rest.iter().all(|rest| is_const_data(rest))
+        }
+        Value::Record(_, field_values) => field_values.iter().all(is_const_data),
+        _ => false,
+    }
+}
+
 pub fn value_to_const(ehx: &mut EvalHirCtx, value: &Value) -> Option<Gc<boxed::Any>> {
     match value {
         Value::Const(boxed) => Some(*boxed),
@@ -68,10 +68,12 @@ where
                 }
             }),
 
+        // Instances of constructors with rest fields can have any class with the same fields
+        Some(Ty::Record(instance)) if instance.cons().has_rest_fields() => FoundRecordConses::Multi,
         Some(Ty::Record(instance)) => FoundRecordConses::Single(instance.cons()),
         Some(Ty::RecordClass(cons)) => FoundRecordConses::Single(cons),
         // These could be anything
-        None | Some(Ty::Any) | Some(Ty::TopRecord) => FoundRecordConses::Multi,
+        None | Some(Ty::Any) | Some(Ty::Dyn) | Some(Ty::TopRecord) => FoundRecordConses::Multi,
         Some(_) => FoundRecordConses::None,
     }
 }
@@ -96,6 +98,10 @@ where
         return TypeHint::KnownVectorLen(members.len());
     }
 
+    if let Some(Ty::Values(members)) = ty_ref.try_to_fixed() {
+        return TypeHint::KnownListLen(members.len());
+    }
+
     TypeHint::None
 }
 
@@ -0,0 +1,475 @@
+//! Verifier for the invariants codegen relies on in MIR
+//!
+//! Malformed MIR usually isn't noticed until codegen where it either crashes LLVM or silently
+//! miscompiles. Verifying each function after it's built and after every optimisation instead
+//! reports the pass that introduced the problem.
+//!
+//! Regs defined inside a branch of a `Cond` can only be used after the `Cond` if the other branch
+//! terminates. Otherwise they're visible to the rest of the branch they're defined in.
+//!
+//! This runs in debug builds of the compiler or when `--verify-mir` is passed.
+
+use std::collections::{HashMap, HashSet};
+use std::{fmt, result};
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::span::{FileId, Span};
+
+use crate::crash;
+use crate::mir::ops;
+use crate::mir::BuiltProgram;
+use crate::reporting::new_primary_label;
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    span: Span,
+    message: String,
+}
+
+impl Error {
+    fn new(span: Span, message: impl Into<String>) -> Error {
+        Error {
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl From<Error> for Diagnostic<FileId> {
+    fn from(error: Error) -> Self {
+        let diagnostic = Diagnostic::error().with_message(error.message);
+
+        if error.span.file_id().is_some() {
+            diagnostic.with_labels(vec![new_primary_label(error.span, "invalid MIR")])
+        } else {
+            diagnostic
+        }
+    }
+}
+
+type PrivateFuns = HashMap<ops::PrivateFunId, ops::Fun>;
+
+fn ends_with_terminator(ops: &[ops::Op]) -> bool {
+    ops.last()
+        .map(|op| op.kind().is_terminator())
+        .unwrap_or(false)
+}
+
+struct FunVerifier<'a> {
+    fun: &'a ops::Fun,
+    private_funs: Option<&'a PrivateFuns>,
+
+    /// Every reg defined so far in the function
+    defined_regs: HashSet<ops::RegId>,
+
+    /// Regs that can be used by the current op
+    visible_regs: HashSet<ops::RegId>,
+
+    /// Visible regs in the order they were defined
+    ///
+    /// This allows the regs defined inside a branch to be hidden once it's finished.
+    visible_reg_stack: Vec<ops::RegId>,
+}
+
+impl<'a> FunVerifier<'a> {
+    fn new(fun: &'a ops::Fun, private_funs: Option<&'a PrivateFuns>) -> Self {
+        FunVerifier {
+            fun,
+            private_funs,
+            defined_regs: HashSet::new(),
+            visible_regs: HashSet::new(),
+            visible_reg_stack: vec![],
+        }
+    }
+
+    fn define_reg(&mut self, span: Span, reg: ops::RegId) -> Result<()> {
+        if !self.defined_regs.insert(reg) {
+            return Err(Error::new(
+                span,
+                format!("register %{} is defined more than once", reg.get()),
+            ));
+        }
+
+        self.visible_regs.insert(reg);
+        self.visible_reg_stack.push(reg);
+        Ok(())
+    }
+
+    fn use_reg(&self, span: Span, reg: ops::RegId) -> Result<()> {
+        if self.visible_regs.contains(&reg) {
+            Ok(())
+        } else {
+            Err(Error::new(
+                span,
+                format!("register %{} is used before it's defined", reg.get()),
+            ))
+        }
+    }
+
+    /// Returns the number of parameters a callee takes
+    ///
+    /// This returns `None` if the callee is a private function and we weren't passed the
+    /// program's private functions.
+    fn callee_param_count(&self, span: Span, callee: &ops::Callee) -> Result<Option<usize>> {
+        match callee {
+            ops::Callee::StaticSymbol(static_symbol) => Ok(Some(static_symbol.abi.params.len())),
+            ops::Callee::BoxedFunThunk(_) => Ok(Some(ops::OpsAbi::thunk_abi().params.len())),
+            ops::Callee::PrivateFun(private_fun_id) => match self.private_funs {
+                Some(private_funs) => private_funs
+                    .get(private_fun_id)
+                    .map(|private_fun| Some(private_fun.abi.params.len()))
+                    .ok_or_else(|| Error::new(span, "reference to undefined private function")),
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn verify_arity(span: Span, param_count: usize, arg_count: usize) -> Result<()> {
+        if param_count == arg_count {
+            Ok(())
+        } else {
+            Err(Error::new(
+                span,
+                format!(
+                    "call passes {} arguments to a function taking {} parameters",
+                    arg_count, param_count
+                ),
+            ))
+        }
+    }
+
+    /// Verifies the ops of a branch
+    ///
+    /// The regs defined inside the branch are hidden afterwards and returned.
+    fn verify_branch(
+        &mut self,
+        ops: &[ops::Op],
+        result_reg: Option<ops::RegId>,
+    ) -> Result<Vec<ops::RegId>> {
+        let outer_stack_len = self.visible_reg_stack.len();
+
+        self.verify_ops(ops)?;
+
+        if let Some(result_reg) = result_reg {
+            if !self.visible_regs.contains(&result_reg) {
+                let span = ops.last().map(ops::Op::span).unwrap_or(self.fun.span);
+
+                return Err(Error::new(
+                    span,
+                    format!(
+                        "result register %{} isn't defined in its branch",
+                        result_reg.get()
+                    ),
+                ));
+            }
+        }
+
+        let branch_regs: Vec<ops::RegId> =
+            self.visible_reg_stack.drain(outer_stack_len..).collect();
+
+        for branch_reg in &branch_regs {
+            self.visible_regs.remove(branch_reg);
+        }
+
+        Ok(branch_regs)
+    }
+
+    fn show_regs(&mut self, regs: Vec<ops::RegId>) {
+        self.visible_regs.extend(regs.iter().copied());
+        self.visible_reg_stack.extend(regs);
+    }
+
+    fn verify_cond_op(&mut self, span: Span, cond_op: &ops::CondOp) -> Result<()> {
+        self.use_reg(span, cond_op.test_reg)?;
+
+        let reg_phi = cond_op.reg_phi.as_ref();
+
+        let true_regs = self.verify_branch(
+            &cond_op.true_ops,
+            reg_phi.map(|reg_phi| reg_phi.true_result_reg),
+        )?;
+        let false_regs = self.verify_branch(
+            &cond_op.false_ops,
+            reg_phi.map(|reg_phi| reg_phi.false_result_reg),
+        )?;
+
+        // If one branch terminates then only the other can continue past the `Cond`
+        if ends_with_terminator(&cond_op.false_ops) {
+            self.show_regs(true_regs);
+        }
+        if ends_with_terminator(&cond_op.true_ops) {
+            self.show_regs(false_regs);
+        }
+
+        if let Some(reg_phi) = reg_phi {
+            self.define_reg(span, reg_phi.output_reg)?;
+        }
+
+        Ok(())
+    }
+
+    fn verify_op(&mut self, op: &ops::Op) -> Result<()> {
+        use crate::mir::ops::OpKind;
+
+        let span = op.span();
+
+        let callee = match op.kind() {
+            OpKind::Cond(cond_op) => {
+                return self.verify_cond_op(span, cond_op);
+            }
+            OpKind::Call(_, ops::CallOp { callee, args, .. }) => {
+                if let Some(param_count) = self.callee_param_count(span, callee)? {
+                    Self::verify_arity(span, param_count, args.len())?;
+                }
+
+                Some(callee)
+            }
+            OpKind::TailCall(_, ops::TailCallOp { args, .. }) => {
+                Self::verify_arity(span, self.fun.abi.params.len(), args.len())?;
+                None
+            }
+            OpKind::ConstBoxedFunThunk(_, ops::BoxFunThunkOp { callee, .. })
+            | OpKind::AllocBoxedFunThunk(_, ops::BoxFunThunkOp { callee, .. }) => {
+                self.callee_param_count(span, callee)?;
+                Some(callee)
+            }
+            _ => None,
+        };
+
+        let mut input_regs = vec![];
+        op.kind().add_input_regs(&mut input_regs);
+
+        if let Some(ops::Callee::BoxedFunThunk(thunk_reg)) = callee {
+            input_regs.push(*thunk_reg);
+        }
+
+        for input_reg in input_regs {
+            self.use_reg(span, input_reg)?;
+        }
+
+        if let Some(output_reg) = op.kind().output_reg() {
+            self.define_reg(span, output_reg)?;
+        }
+
+        Ok(())
+    }
+
+    fn verify_ops(&mut self, ops: &[ops::Op]) -> Result<()> {
+        for (index, op) in ops.iter().enumerate() {
+            self.verify_op(op)?;
+
+            if op.kind().is_terminator() {
+                if let Some(next_op) = ops.get(index + 1) {
+                    return Err(Error::new(
+                        next_op.span(),
+                        "op follows a return or unreachable op",
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verify(mut self) -> Result<()> {
+        let fun = self.fun;
+
+        if fun.param_regs.len() != fun.abi.params.len() {
+            return Err(Error::new(
+                fun.span,
+                format!(
+                    "function has {} parameter registers for {} parameters",
+                    fun.param_regs.len(),
+                    fun.abi.params.len()
+                ),
+            ));
+        }
+
+        for param_reg in fun.param_regs.iter() {
+            self.define_reg(fun.span, *param_reg)?;
+        }
+
+        self.verify_ops(&fun.ops)
+    }
+}
+
+/// Verifies a single function
+///
+/// Calls to private functions can't have their arity checked without the rest of the program.
+pub fn verify_fun(fun: &ops::Fun) -> Result<()> {
+    FunVerifier::new(fun, None).verify()
+}
+
+/// Verifies every function in a program including calls between them
+pub fn verify_program(program: &BuiltProgram) -> Result<()> {
+    let mut private_fun_ids: Vec<ops::PrivateFunId> =
+        program.private_funs.keys().copied().collect();
+    private_fun_ids.sort_unstable_by_key(|private_fun_id| private_fun_id.to_u32());
+
+    for private_fun_id in private_fun_ids {
+        FunVerifier::new(
+            &program.private_funs[&private_fun_id],
+            Some(&program.private_funs),
+        )
+        .verify()?;
+    }
+
+    FunVerifier::new(&program.main, Some(&program.private_funs)).verify()
+}
+
+/// Panics if MIR built by the compiler failed verification
+///
+/// `stage` describes the pass that produced the MIR for the panic message.
+pub fn expect_verified(result: Result<()>, stage: &str) {
+    if let Err(error) = result {
+        let _processing_span = crash::enter_span(error.span());
+        panic!("invalid MIR after {}: {}", stage, error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arret_syntax::span::ByteIndex;
+
+    use crate::mir::parse_program;
+
+    fn verify_str(source: &str) -> Result<()> {
+        let parsed_program = parse_program(None, source).unwrap();
+        verify_program(&parsed_program.program)
+    }
+
+    fn assert_invalid_at(source: &str, error_source: &str, message_suffix: &str) {
+        let error = verify_str(source).unwrap_err();
+
+        assert!(
+            error.message().ends_with(message_suffix),
+            "unexpected message `{}`",
+            error.message()
+        );
+
+        assert_eq!(
+            source.find(error_source).unwrap() as ByteIndex,
+            error.span().start()
+        );
+    }
+
+    #[test]
+    fn valid_program() {
+        verify_str(
+            r#"fn add-one[private-1](%task, %1: i64) -> i64 {
+  %2 = const 1i64;
+  %3 = checked (%1: i64) + (%2: i64);
+  %4 = (%3: i64) == (%2: i64);
+  %5 = if %4 {
+    %6 = const 2i64;
+    %6
+  } else {
+    %3
+  };
+  return %5;
+}
+
+fn main!(%task) -> () {
+  %7 = const 5i64;
+  %8 = pure %add-one[private-1](%task, %7: i64): i64;
+  return;
+}
+"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reg_used_before_defined() {
+        assert_invalid_at(
+            "fn main!(%task) -> () {\n  %2 = checked (%1: i64) + (%1: i64);\n  %1 = const 1i64;\n  return;\n}\n",
+            "%2 = checked",
+            "is used before it's defined",
+        );
+    }
+
+    #[test]
+    fn branch_reg_used_after_cond() {
+        assert_invalid_at(
+            "fn main!(%task) -> () {\n  %1 = const true;\n  if %1 {\n    %2 = const 1i64;\n  }\n  %3 = checked (%2: i64) + (%2: i64);\n  return;\n}\n",
+            "%3 = checked",
+            "is used before it's defined",
+        );
+    }
+
+    #[test]
+    fn branch_reg_used_after_terminating_branch() {
+        verify_str(
+            "fn main!(%task) -> () {\n  %1 = const true;\n  if %1 {\n    %2 = const 1i64;\n  } else {\n    unreachable;\n  }\n  %3 = checked (%2: i64) + (%2: i64);\n  return;\n}\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn cond_result_reg_undefined() {
+        assert_invalid_at(
+            "fn main!(%task) -> () {\n  %1 = const true;\n  %2 = if %1 {\n    %3 = const 1i64;\n    %3\n  } else {\n    %4 = const 2i64;\n    %3\n  };\n  return;\n}\n",
+            "%4 = const 2i64",
+            "isn't defined in its branch",
+        );
+    }
+
+    #[test]
+    fn call_arity_mismatch() {
+        assert_invalid_at(
+            "fn [private-1](%task, %1: i64) -> i64 {\n  return %1;\n}\n\nfn main!(%task) -> () {\n  %2 = const 1i64;\n  %3 = pure [private-1](%task, %2: i64, %2: i64): i64;\n  return;\n}\n",
+            "%3 = pure",
+            "call passes 2 arguments to a function taking 1 parameters",
+        );
+    }
+
+    #[test]
+    fn tail_call_arity_mismatch() {
+        assert_invalid_at(
+            "fn [private-1](%task, %1: i64) -> i64 {\n  %2 = pure recur(%task);\n  return %2;\n}\n\nfn main!(%task) -> () {\n  return;\n}\n",
+            "%2 = pure recur",
+            "call passes 0 arguments to a function taking 1 parameters",
+        );
+    }
+
+    #[test]
+    fn op_after_terminator() {
+        assert_invalid_at(
+            "fn main!(%task) -> () {\n  unreachable;\n  return;\n}\n",
+            "return;",
+            "op follows a return or unreachable op",
+        );
+    }
+
+    #[test]
+    fn unverifiable_private_fun_call() {
+        let parsed_program = parse_program(
+            None,
+            "fn [private-1](%task) -> () {\n  return;\n}\n\nfn main!(%task) -> () {\n  %1 = const 1i64;\n  %2 = impure [private-1](%task, %1: i64): ();\n  return;\n}\n",
+        )
+        .unwrap();
+
+        // Without the rest of the program we can't know the arity of private functions
+        verify_fun(&parsed_program.program.main).unwrap();
+        assert!(verify_program(&parsed_program.program).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::str;
+
+/// Optimisation level used when compiling a program
+///
+/// This selects both the MIR optimisations we perform and the LLVM pass pipeline. Runtime checks
+/// such as integer overflow and bounds checks are kept at every level; optimisation levels only
+/// affect how much code is inlined and how aggressively it's simplified.
+///
+/// Compile-time evaluation is also performed at every level. However, lower levels inline less
+/// code so fewer expressions are known to be constant. This can move errors such as overflow in
+/// pure code from compile time to runtime.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptLevel {
+    /// No optimisation beyond what's required to generate code
+    O0,
+    /// Limited inlining and a basic LLVM pass pipeline
+    O1,
+    /// Full optimisation
+    O2,
+    /// Full optimisation while preferring smaller code
+    Os,
+}
+
+/// Error returned when parsing an unknown optimisation level
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParseOptLevelError;
+
+impl OptLevel {
+    /// Returns if any optimisations are enabled
+    pub fn optimising(self) -> bool {
+        self != OptLevel::O0
+    }
+
+    /// Returns the maximum number of consecutive MIR inlinings in a call stack
+    ///
+    /// Functions with a single usage are always inlined regardless of level.
+    pub(crate) fn max_inline_depth(self) -> usize {
+        match self {
+            OptLevel::O0 => 0,
+            OptLevel::O1 => 4,
+            OptLevel::O2 | OptLevel::Os => 16,
+        }
+    }
+
+    /// Returns if the MIR inliner should prefer inlining over smaller code
+    pub(crate) fn prefer_inlining(self) -> bool {
+        self != OptLevel::Os
+    }
+
+    /// Returns if redundant allocations should be merged in MIR
+    pub(crate) fn merge_alloc_ops(self) -> bool {
+        self.optimising()
+    }
+
+    /// Returns the level used to populate LLVM pass managers
+    pub(crate) fn llvm_pass_level(self) -> Option<u32> {
+        match self {
+            OptLevel::O0 => None,
+            OptLevel::O1 => Some(1),
+            OptLevel::O2 | OptLevel::Os => Some(2),
+        }
+    }
+
+    /// Returns the LLVM size level used to populate LLVM pass managers
+    pub(crate) fn llvm_size_level(self) -> u32 {
+        if self == OptLevel::Os {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl Default for OptLevel {
+    fn default() -> OptLevel {
+        OptLevel::O2
+    }
+}
+
+impl str::FromStr for OptLevel {
+    type Err = ParseOptLevelError;
+
+    fn from_str(s: &str) -> Result<OptLevel, ParseOptLevelError> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "s" => Ok(OptLevel::Os),
+            _ => Err(ParseOptLevelError),
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptLevel::O0 => write!(f, "-O0"),
+            OptLevel::O1 => write!(f, "-O1"),
+            OptLevel::O2 => write!(f, "-O2"),
+            OptLevel::Os => write!(f, "-Os"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_opt_level() {
+        for opt_level in &[OptLevel::O0, OptLevel::O1, OptLevel::O2, OptLevel::Os] {
+            let flag = opt_level.to_string();
+            assert_eq!(Ok(*opt_level), flag["-O".len()..].parse());
+        }
+
+        assert_eq!(Err(ParseOptLevelError), "3".parse::<OptLevel>());
+    }
+}
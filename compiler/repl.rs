@@ -11,7 +11,7 @@ use crate::context;
 use crate::context::ModuleId;
 use crate::hir;
 use crate::hir::scope::Scope;
-use crate::reporting::{diagnostic_for_syntax_error, errors_to_diagnostics, new_primary_label};
+use crate::reporting::{diagnostics_for_syntax_errors, errors_to_diagnostics, new_primary_label};
 use crate::ty;
 use crate::CompileCtx;
 
@@ -31,6 +31,24 @@ pub enum EvalKind {
 
     /// Fully evaluates the expression
     Value,
+
+    /// Looks up the docstring of a bound identifier
+    ///
+    /// The input must consist of a single identifier
+    Doc,
+
+    /// Expands the macros in the expression without evaluating it
+    Expand(ExpandOptions),
+}
+
+/// Options for `EvalKind::Expand`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExpandOptions {
+    /// Expands every macro invocation instead of only the outermost invocation
+    pub all: bool,
+
+    /// Appends the hygiene namespace to identifiers introduced by macros
+    pub show_hygiene: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -64,6 +82,14 @@ pub enum EvaledLine {
 
     /// Line was evaluate to a value
     ExprValue(EvaledExprValue),
+
+    /// Line was an identifier with the given docstring
+    ///
+    /// This is `None` if the identifier's definition had no docstring
+    Doc(Option<DataStr>),
+
+    /// Line was expanded to the given rendered datum
+    Expansion(String),
 }
 
 struct ReplEngine<'ccx> {
@@ -72,6 +98,7 @@ struct ReplEngine<'ccx> {
 
     inferred_module_vars: HashMap<context::ModuleId, Arc<HashMap<hir::LocalId, ty::Ref<ty::Poly>>>>,
     seen_modules: HashSet<context::ModuleId>,
+    docs: HashMap<(context::ModuleId, hir::LocalId), DataStr>,
 
     ehx: EvalHirCtx,
 }
@@ -84,8 +111,9 @@ impl<'ccx> ReplEngine<'ccx> {
 
             seen_modules: HashSet::new(),
             inferred_module_vars: HashMap::new(),
+            docs: HashMap::new(),
 
-            ehx: EvalHirCtx::new(ccx.enable_optimisations()),
+            ehx: EvalHirCtx::new(ccx.opt_level()).with_verify_mir(ccx.verify_mir()),
         }
     }
 
@@ -103,6 +131,92 @@ impl<'ccx> ReplEngine<'ccx> {
             .collect()
     }
 
+    /// Records the docstring of a definition so it can be looked up by `EvalKind::Doc`
+    fn record_def_doc<P: hir::Phase>(&mut self, module_id: ModuleId, def: &hir::Def<P>) {
+        if let (Some(doc), hir::destruc::Destruc::Scalar(_, scalar)) = (&def.doc, &def.destruc) {
+            if let Some(local_id) = scalar.local_id() {
+                self.docs.insert((module_id, *local_id), doc.clone());
+            }
+        }
+    }
+
+    /// Returns the docstring for an identifier bound in the root scope
+    fn doc_for_datum(
+        &self,
+        input_datum: &arret_syntax::datum::Datum,
+    ) -> Result<EvaledLine, Vec<Diagnostic<FileId>>> {
+        use arret_syntax::datum::Datum;
+
+        use crate::hir::ns::Ident;
+        use crate::hir::scope::Binding;
+
+        let (span, name) = if let Datum::Sym(span, name) = input_datum {
+            (*span, name)
+        } else {
+            return Err(vec![Diagnostic::error()
+                .with_message(format!(
+                    "expected identifier, found {}",
+                    input_datum.description()
+                ))
+                .with_labels(vec![new_primary_label(
+                    input_datum.span(),
+                    "expected identifier",
+                )])]);
+        };
+
+        let ident = Ident::new(Scope::root_ns_id(), name.clone());
+        match self.root_scope.get(&ident) {
+            Some(Binding::Var(Some(module_id), local_id)) => Ok(EvaledLine::Doc(
+                self.docs.get(&(*module_id, *local_id)).cloned(),
+            )),
+            Some(_) => Ok(EvaledLine::Doc(None)),
+            None => Err(vec![Diagnostic::error()
+                .with_message(format!("unable to resolve `{}`", name))
+                .with_labels(vec![new_primary_label(
+                    span,
+                    "not found in this scope",
+                )])]),
+        }
+    }
+
+    /// Expands the macros in a datum and renders the result
+    fn expand_datum(
+        &mut self,
+        input_datum: &arret_syntax::datum::Datum,
+        options: ExpandOptions,
+    ) -> Result<EvaledLine, Vec<Diagnostic<FileId>>> {
+        use crate::hir::ns::NsDatum;
+        use arret_runtime_syntax::{reader, writer};
+
+        // Any bindings introduced by the expansion are discarded with this scope
+        let mut scope = Scope::child(&self.root_scope).with_source_loader(self.ccx.source_loader());
+        let ns_datum = NsDatum::from_syntax_datum(input_datum);
+
+        let expanded_datum = if options.all {
+            hir::expand_all(&mut scope, ns_datum)
+        } else {
+            hir::expand_macro_once(&mut scope, &ns_datum)
+                .map(|expanded_datum| expanded_datum.unwrap_or(ns_datum))
+        }
+        .map_err(|err| vec![err.into()])?;
+
+        let syntax_datum = if options.show_hygiene {
+            expanded_datum.into_marked_syntax_datum()
+        } else {
+            expanded_datum.into_syntax_datum()
+        };
+
+        // Render using the same writer as evaluated values
+        let boxed = reader::box_syntax_datum(&mut self.ehx, &syntax_datum);
+
+        let mut output_buf: Vec<u8> = vec![];
+        writer::write_boxed(&mut output_buf, &self.ehx, boxed).unwrap();
+
+        Ok(EvaledLine::Expansion(
+            String::from_utf8(output_buf).unwrap(),
+        ))
+    }
+
     /// Visits a subtree of modules and adds any missing defs and inferred module vars
     fn visit_module_tree(
         &mut self,
@@ -122,6 +236,10 @@ impl<'ccx> ReplEngine<'ccx> {
         self.inferred_module_vars
             .insert(root_module.module_id, root_module.inferred_locals.clone());
 
+        for def in root_module.defs.iter() {
+            self.record_def_doc(root_module.module_id, def);
+        }
+
         self.ehx
             .visit_module_defs(root_module.module_id, &root_module.defs)?;
 
@@ -141,7 +259,7 @@ impl<'ccx> ReplEngine<'ccx> {
 
         let input_data = source_file
             .parsed()
-            .map_err(|err| vec![diagnostic_for_syntax_error(&err)])?;
+            .map_err(|_| diagnostics_for_syntax_errors(&source_file))?;
 
         let input_datum = match input_data {
             [] => {
@@ -160,8 +278,19 @@ impl<'ccx> ReplEngine<'ccx> {
             }
         };
 
+        match kind {
+            EvalKind::Doc => {
+                return self.doc_for_datum(input_datum);
+            }
+            EvalKind::Expand(options) => {
+                return self.expand_datum(input_datum, options);
+            }
+            EvalKind::Type | EvalKind::Value => {}
+        }
+
         let module_id = ModuleId::alloc();
-        let mut child_scope = Scope::child(&self.root_scope);
+        let mut child_scope =
+            Scope::child(&self.root_scope).with_source_loader(self.ccx.source_loader());
 
         let lowered_repl_datum =
             hir::lowering::lower_repl_datum(self.ccx, &mut child_scope, input_datum)
@@ -181,8 +310,11 @@ impl<'ccx> ReplEngine<'ccx> {
                 Ok(EvaledLine::Defs(self.bound_names()))
             }
             LoweredReplDatum::EvaluableDef(def) => {
-                let inferred_module = infer_module(&self.inferred_module_vars, vec![def])
-                    .map_err(errors_to_diagnostics)?;
+                self.record_def_doc(module_id, &def);
+
+                let inferred_module =
+                    infer_module(&self.inferred_module_vars, vec![def], self.ccx.strict())
+                        .map_err(errors_to_diagnostics)?;
 
                 self.inferred_module_vars
                     .insert(module_id, Arc::new(inferred_module.inferred_locals));
@@ -202,6 +334,9 @@ impl<'ccx> ReplEngine<'ccx> {
 
                 match kind {
                     EvalKind::Type => Ok(EvaledLine::ExprType(type_str)),
+                    EvalKind::Doc | EvalKind::Expand(_) => {
+                        unreachable!("non-evaluating command evaluated as expression")
+                    }
                     EvalKind::Value => {
                         use crate::mir::eval_hir::FunCtx;
                         use arret_runtime_syntax::writer;
@@ -364,11 +499,14 @@ mod test {
     #[test]
     fn basic_session() {
         use crate::codegen::test::initialise_test_llvm;
-        use crate::PackagePaths;
+        use crate::{OptLevel, PackagePaths};
 
         initialise_test_llvm();
 
-        let ccx = Arc::new(CompileCtx::new(PackagePaths::test_paths(None), true));
+        let ccx = Arc::new(CompileCtx::new(
+            PackagePaths::test_paths(None),
+            OptLevel::default(),
+        ));
         let mut rcx = ReplCtx::new(ccx);
 
         assert_empty(&mut rcx, "       ");
@@ -393,6 +531,41 @@ mod test {
         assert_defs(&mut rcx, "(def x 'second)");
         assert_expr(&mut rcx, "second", "'second", "x");
 
+        // Docstrings
+        assert_defs(&mut rcx, "(def documented \"Documented value\" 1)");
+        assert_eq!(
+            EvaledLine::Doc(Some("Documented value".into())),
+            eval_line_sync(&mut rcx, "documented".to_owned(), EvalKind::Doc).unwrap()
+        );
+        assert_eq!(
+            EvaledLine::Doc(None),
+            eval_line_sync(&mut rcx, "x".to_owned(), EvalKind::Doc).unwrap()
+        );
+
+        // Macro expansion
+        let expand_once = EvalKind::Expand(ExpandOptions {
+            all: false,
+            show_hygiene: false,
+        });
+        let expand_all = EvalKind::Expand(ExpandOptions {
+            all: true,
+            show_hygiene: false,
+        });
+
+        assert_eq!(
+            EvaledLine::Expansion("(if y 2 1)".to_owned()),
+            eval_line_sync(&mut rcx, "(if-not y 1 2)".to_owned(), expand_once).unwrap()
+        );
+        assert_eq!(
+            EvaledLine::Expansion("(unless x (if y 2 1) (quote (if-not y 1 2)))".to_owned()),
+            eval_line_sync(
+                &mut rcx,
+                "(when-not x (if-not y 1 2) '(if-not y 1 2))".to_owned(),
+                expand_all
+            )
+            .unwrap()
+        );
+
         // `(do)` at the expression level
         assert_expr(&mut rcx, "baz", "'baz", "(do 'foo 'bar 'baz)");
 
@@ -407,5 +580,8 @@ mod test {
 
         assert_expr(&mut rcx, "1", "Int", "(return-one)");
         assert_expr(&mut rcx, "two", "'two", "(return-two)");
+
+        // Capturing closures can be boxed inside constant data
+        assert_expr(&mut rcx, "(#fn)", "(List & (-> Int))", "(list return-one)");
     }
 }
@@ -2,7 +2,7 @@ use codespan_reporting::diagnostic::{Diagnostic, Label};
 
 use arret_syntax::span::{FileId, Span};
 
-use crate::source::SourceLoader;
+use crate::source::{SourceFile, SourceLoader};
 
 /// Traces the location of report through macro expansions
 #[derive(Debug, PartialEq, Clone)]
@@ -96,6 +96,15 @@ pub fn diagnostic_for_syntax_error(error: &arret_syntax::error::Error) -> Diagno
     diagnostic.with_labels(vec![primary_label])
 }
 
+/// Returns diagnostics for every syntax error in the passed source file
+pub fn diagnostics_for_syntax_errors(source_file: &SourceFile) -> Vec<Diagnostic<FileId>> {
+    source_file
+        .syntax_errors()
+        .iter()
+        .map(diagnostic_for_syntax_error)
+        .collect()
+}
+
 pub fn new_primary_label(span: Span, message: impl Into<String>) -> Label<FileId> {
     Label::primary(span.file_id().unwrap(), span.byte_range()).with_message(message)
 }
@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::ops::Range;
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use std::{fmt, fs, io, path};
 
 use codespan_reporting::files::Error as CodespanError;
@@ -43,10 +44,27 @@ impl From<&'static str> for SourceText {
     }
 }
 
+/// Zero-based line and column position within a source file
+///
+/// Columns are counted in Unicode scalar values from the start of the line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Half-open range of line and column positions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LineColRange {
+    pub start: LineCol,
+    pub end: LineCol,
+}
+
 pub struct SourceFile {
     file_id: FileId,
     source: SourceText,
-    parsed: Result<Vec<Datum>, arret_syntax::error::Error>,
+    data: Vec<Datum>,
+    syntax_errors: Vec<arret_syntax::error::Error>,
 }
 
 impl SourceFile {
@@ -58,12 +76,18 @@ impl SourceFile {
         self.source.as_ref()
     }
 
+    /// Returns the parsed data or the first syntax error
     pub fn parsed(&self) -> Result<&[Datum], arret_syntax::error::Error> {
-        match &self.parsed {
-            Ok(data) => Ok(data),
-            Err(err) => Err(err.clone()),
+        match self.syntax_errors.first() {
+            Some(err) => Err(err.clone()),
+            None => Ok(&self.data),
         }
     }
+
+    /// Returns every syntax error encountered while parsing the file
+    pub fn syntax_errors(&self) -> &[arret_syntax::error::Error] {
+        &self.syntax_errors
+    }
 }
 
 impl fmt::Debug for SourceFile {
@@ -108,11 +132,43 @@ impl ReportableFile {
 
         Some(*start..end)
     }
+
+    fn line_col(&self, offset: usize) -> LineCol {
+        let source = self.source();
+
+        // Round down any offset inside a character to the start of that character
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = self.line_index(offset);
+        let line_start = self.line_offsets[line];
+
+        LineCol {
+            line,
+            column: source[line_start..offset].chars().count(),
+        }
+    }
+
+    fn line_col_range(&self, span: Span) -> Option<LineColRange> {
+        let byte_range = span.byte_range();
+
+        if byte_range.end > self.source().len() {
+            return None;
+        }
+
+        Some(LineColRange {
+            start: self.line_col(byte_range.start),
+            end: self.line_col(byte_range.end),
+        })
+    }
 }
 
 #[derive(Default)]
 pub struct SourceLoader {
     files: RwLock<Vec<ReportableFile>>,
+    files_by_path: Mutex<HashMap<Box<path::Path>, Arc<SourceFile>>>,
 }
 
 impl SourceLoader {
@@ -130,9 +186,31 @@ impl SourceLoader {
         ))
     }
 
+    /// Synchronously read path into a `SourceFile` shared with previous loads of the same file
+    ///
+    /// Paths are canonicalised first; different paths to the same file return the same instance.
+    pub fn load_path_cached(&self, path: &path::Path) -> Result<Arc<SourceFile>, io::Error> {
+        let canonical_path: Box<path::Path> = path.canonicalize()?.into();
+
+        if let Some(source_file) = self.files_by_path.lock().unwrap().get(&canonical_path) {
+            return Ok(source_file.clone());
+        }
+
+        let source_file = Arc::new(self.load_path(path)?);
+
+        // Another thread may have loaded the file while we were reading it
+        Ok(self
+            .files_by_path
+            .lock()
+            .unwrap()
+            .entry(canonical_path)
+            .or_insert(source_file)
+            .clone())
+    }
+
     /// Loads a caller-provided string into a `SourceFile`
     pub fn load_string(&self, filename: OsString, source: impl Into<SourceText>) -> SourceFile {
-        use arret_syntax::parser::data_from_str;
+        use arret_syntax::parser::data_from_str_with_recovery;
 
         let source = source.into();
         let reportable_file = ReportableFile {
@@ -149,10 +227,13 @@ impl SourceLoader {
         };
 
         let file_id = FileId::new(file_index as u32).unwrap();
+        let (data, syntax_errors) = data_from_str_with_recovery(Some(file_id), source.as_ref());
+
         SourceFile {
             file_id,
-            parsed: data_from_str(Some(file_id), source.as_ref()),
             source,
+            data,
+            syntax_errors,
         }
     }
 
@@ -163,6 +244,39 @@ impl SourceLoader {
         self.files.write().unwrap().reserve(additional)
     }
 
+    /// Maps a span to the line and column range it covers
+    ///
+    /// This returns `None` if the span doesn't belong to a file loaded by this instance or if it
+    /// falls outside of the file's source.
+    pub fn span_to_line_col_range(&self, span: Span) -> Option<LineColRange> {
+        self.files().span_to_line_col_range(span)
+    }
+
+    /// Returns the name of the file containing a span
+    ///
+    /// This returns `None` if the span doesn't belong to a file loaded by this instance.
+    pub fn span_to_filename(&self, span: Span) -> Option<String> {
+        self.files()
+            .get_file(span.file_id()?)
+            .map(ReportableFile::name)
+    }
+
+    /// Returns the name and source of every file loaded by this instance
+    ///
+    /// This is used for crash reporting and to identify the files referred to by typeck cache
+    /// entries. It tolerates the lock being poisoned by a panic while a file was being loaded.
+    pub fn loaded_files(&self) -> Vec<(OsString, SourceText)> {
+        let files = self
+            .files
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        files
+            .iter()
+            .map(|file| (file.filename.clone(), file.source.clone()))
+            .collect()
+    }
+
     /// Returns a `ReportableFiles` instance usable with `codespan-reporting`
     ///
     /// This will take our instance's read lock.
@@ -181,6 +295,11 @@ impl<'a> ReportableFiles<'a> {
     fn get_file(&self, file_id: FileId) -> Option<&ReportableFile> {
         self.files.get((file_id.get() - 1) as usize)
     }
+
+    /// Maps a span to the line and column range it covers
+    pub fn span_to_line_col_range(&self, span: Span) -> Option<LineColRange> {
+        self.get_file(span.file_id()?)?.line_col_range(span)
+    }
 }
 
 type CodespanResult<T> = Result<T, CodespanError>;
@@ -219,3 +338,67 @@ impl<'a> codespan_reporting::files::Files<'a> for ReportableFiles<'a> {
             })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn span_to_line_col_range() {
+        let source_loader = SourceLoader::new();
+        let source_file = source_loader.load_string("test.arret".into(), "(one)\n(λ two)\n");
+        let file_id = Some(source_file.file_id());
+
+        assert_eq!(
+            Some(LineColRange {
+                start: LineCol { line: 0, column: 1 },
+                end: LineCol { line: 0, column: 4 },
+            }),
+            source_loader.span_to_line_col_range(Span::new(file_id, 1, 4))
+        );
+
+        // Columns should be counted in characters, not bytes
+        assert_eq!(
+            Some(LineColRange {
+                start: LineCol { line: 1, column: 3 },
+                end: LineCol { line: 1, column: 6 },
+            }),
+            source_loader.span_to_line_col_range(Span::new(file_id, 10, 13))
+        );
+
+        // Offsets inside a character should be treated as the start of that character
+        assert_eq!(
+            Some(LineColRange {
+                start: LineCol { line: 1, column: 1 },
+                end: LineCol { line: 1, column: 2 },
+            }),
+            source_loader.span_to_line_col_range(Span::new(file_id, 8, 9))
+        );
+
+        assert_eq!(
+            None,
+            source_loader.span_to_line_col_range(Span::new(file_id, 10, 100))
+        );
+        assert_eq!(None, source_loader.span_to_line_col_range(EMPTY_SPAN));
+    }
+
+    #[test]
+    fn load_path_cached() {
+        let source_loader = SourceLoader::new();
+
+        let base_file = source_loader
+            .load_path_cached(path::Path::new("../stdlib/arret/base.arret"))
+            .unwrap();
+
+        // Different paths to the same file should return the same instance
+        let same_file = source_loader
+            .load_path_cached(path::Path::new("../stdlib/arret/../arret/base.arret"))
+            .unwrap();
+        assert!(Arc::ptr_eq(&base_file, &same_file));
+
+        let err = source_loader
+            .load_path_cached(path::Path::new("../stdlib/arret/notafile.arret"))
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+    }
+}
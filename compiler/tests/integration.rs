@@ -278,6 +278,7 @@ fn result_for_single_test(
             mut ehx,
             main_export_id,
             linked_libraries,
+            ..
         } = arret_compiler::program_to_evaluable(ccx, source_file)?;
 
         // Try evaluating if we're not supposed to panic
@@ -307,8 +308,9 @@ fn result_for_single_test(
             &linked_libraries,
             &mir_program,
             &output_path,
-            None,
-        );
+            Some(ccx.source_loader()),
+        )
+        .map_err(|unsupported| vec![unsupported.into()])?;
 
         (output_path, run_type)
     };
@@ -519,7 +521,10 @@ where
 #[test]
 fn integration() {
     let package_paths = arret_compiler::PackagePaths::test_paths(None);
-    let ccx = Arc::new(arret_compiler::CompileCtx::new(package_paths, true));
+    let ccx = Arc::new(arret_compiler::CompileCtx::new(
+        package_paths,
+        arret_compiler::OptLevel::default(),
+    ));
 
     use arret_compiler::initialise_llvm;
     initialise_llvm(false);
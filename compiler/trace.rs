@@ -0,0 +1,515 @@
+//! Structured tracing of the compiler's internal passes
+//!
+//! Passes are instrumented with named spans grouped by category. Tracing is disabled until
+//! [`start_tracing`] is called; until then entering a span costs a single atomic load.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io, path, ptr};
+
+/// Categories of compiler spans
+pub const CATEGORIES: &[&str] = &["hir", "typeck", "mir", "codegen"];
+
+/// Selects which span categories are traced
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceFilter {
+    /// Enabled categories or `None` if all categories are enabled
+    categories: Option<Vec<&'static str>>,
+}
+
+impl TraceFilter {
+    /// Returns a filter enabling every category
+    pub fn all() -> TraceFilter {
+        TraceFilter { categories: None }
+    }
+
+    /// Parses a comma separated list of categories
+    ///
+    /// `all` or `*` enables every category.
+    pub fn parse(filter_str: &str) -> Result<TraceFilter, InvalidTraceFilter> {
+        let mut categories = vec![];
+
+        for category_str in filter_str.split(',').map(str::trim) {
+            if category_str == "all" || category_str == "*" {
+                return Ok(TraceFilter::all());
+            }
+
+            match CATEGORIES
+                .iter()
+                .find(|category| **category == category_str)
+            {
+                Some(category) => categories.push(*category),
+                None => return Err(InvalidTraceFilter(category_str.to_owned())),
+            }
+        }
+
+        Ok(TraceFilter {
+            categories: Some(categories),
+        })
+    }
+
+    fn is_enabled(&self, category: &str) -> bool {
+        match &self.categories {
+            Some(categories) => categories.contains(&category),
+            None => true,
+        }
+    }
+}
+
+/// Error for a trace filter containing an unknown category
+#[derive(Debug, PartialEq)]
+pub struct InvalidTraceFilter(String);
+
+impl fmt::Display for InvalidTraceFilter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "unknown trace category `{}`; expected `all` or one of: {}",
+            self.0,
+            CATEGORIES.join(", ")
+        )
+    }
+}
+
+/// Destination for trace output
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceOutput {
+    /// Human-readable log written to stderr as spans are entered and exited
+    Log,
+
+    /// Chrome trace event JSON written to the passed path by [`finish_tracing`]
+    ///
+    /// This can be loaded in `chrome://tracing` or compatible viewers.
+    ChromeJson(path::PathBuf),
+
+    /// Folded stacks of self time in microseconds written to the passed path by [`finish_tracing`]
+    ///
+    /// This is the input format used by flamegraph tools such as `flamegraph.pl` and `inferno`.
+    FoldedStacks(path::PathBuf),
+}
+
+type TraceArgs = Vec<(&'static str, String)>;
+
+struct CompleteEvent {
+    category: &'static str,
+    name: &'static str,
+    args: TraceArgs,
+    thread_id: u64,
+    start: Duration,
+    duration: Duration,
+}
+
+struct Tracer {
+    filter: TraceFilter,
+    output: TraceOutput,
+    start: Instant,
+    events: Mutex<Vec<CompleteEvent>>,
+    folded_stacks: Mutex<HashMap<String, u128>>,
+}
+
+/// Span on the current thread's stack for building folded stacks
+struct StackFrame {
+    label: String,
+    child_duration: Duration,
+}
+
+static TRACER: AtomicPtr<Tracer> = AtomicPtr::new(ptr::null_mut());
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    static SPAN_DEPTH: Cell<usize> = Cell::new(0);
+    static SPAN_STACK: RefCell<Vec<StackFrame>> = RefCell::new(vec![]);
+}
+
+fn active_tracer() -> Option<&'static Tracer> {
+    // The tracer is never freed once it's been installed
+    unsafe { TRACER.load(Ordering::Acquire).as_ref() }
+}
+
+/// Starts tracing spans matching the passed filter
+///
+/// This can only be called once per process.
+pub fn start_tracing(filter: TraceFilter, output: TraceOutput) {
+    let tracer = Box::into_raw(Box::new(Tracer::new(filter, output)));
+
+    if TRACER
+        .compare_exchange(ptr::null_mut(), tracer, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        panic!("tracing already started");
+    }
+}
+
+/// Writes any buffered trace output
+///
+/// This should be called once compilation has finished. Spans exited after this is called will
+/// not be written.
+pub fn finish_tracing() -> io::Result<()> {
+    match active_tracer() {
+        Some(tracer) => tracer.finish(),
+        None => Ok(()),
+    }
+}
+
+/// Span of compiler work
+///
+/// The span is exited when this is dropped.
+#[must_use = "spans are exited as soon as they're dropped"]
+pub struct TraceSpan {
+    entered: Option<EnteredSpan>,
+}
+
+struct EnteredSpan {
+    tracer: &'static Tracer,
+    category: &'static str,
+    name: &'static str,
+    args: TraceArgs,
+    start: Instant,
+}
+
+/// Enters a span with the given category and name
+pub fn enter(category: &'static str, name: &'static str) -> TraceSpan {
+    enter_with_args(category, name, Vec::new)
+}
+
+/// Enters a span with arguments describing the work being done
+///
+/// The arguments are only built if the span is being traced.
+pub fn enter_with_args<F>(category: &'static str, name: &'static str, build_args: F) -> TraceSpan
+where
+    F: FnOnce() -> TraceArgs,
+{
+    let entered = active_tracer()
+        .filter(|tracer| tracer.filter.is_enabled(category))
+        .map(|tracer| {
+            let args = build_args();
+
+            match tracer.output {
+                TraceOutput::Log => {
+                    tracer.log_line(&format!("{}::{}{}", category, name, ArgsDisplay(&args)));
+                }
+                TraceOutput::FoldedStacks(_) => {
+                    SPAN_STACK.with(|stack| {
+                        stack.borrow_mut().push(StackFrame {
+                            label: folded_frame_label(category, name, &args),
+                            child_duration: Duration::default(),
+                        })
+                    });
+                }
+                TraceOutput::ChromeJson(_) => {}
+            }
+
+            SPAN_DEPTH.with(|depth| depth.set(depth.get() + 1));
+
+            EnteredSpan {
+                tracer,
+                category,
+                name,
+                args,
+                start: Instant::now(),
+            }
+        });
+
+    TraceSpan { entered }
+}
+
+impl Drop for TraceSpan {
+    fn drop(&mut self) {
+        if let Some(entered) = self.entered.take() {
+            let duration = entered.start.elapsed();
+            SPAN_DEPTH.with(|depth| depth.set(depth.get() - 1));
+
+            entered.tracer.exit_span(CompleteEvent {
+                category: entered.category,
+                name: entered.name,
+                args: entered.args,
+                thread_id: THREAD_ID.with(|thread_id| *thread_id),
+                start: entered.start.duration_since(entered.tracer.start),
+                duration,
+            });
+        }
+    }
+}
+
+struct ArgsDisplay<'a>(&'a [(&'static str, String)]);
+
+impl<'a> fmt::Display for ArgsDisplay<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.0 {
+            write!(formatter, " {}={}", name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a frame label for a folded stack
+///
+/// Frames are separated by `;` and the sample count is separated by the final space so neither can
+/// appear in the label.
+fn folded_frame_label(category: &str, name: &str, args: &[(&'static str, String)]) -> String {
+    let mut label = format!("{}::{}", category, name);
+
+    if !args.is_empty() {
+        let arg_values: Vec<&str> = args.iter().map(|(_, value)| value.as_str()).collect();
+        label.push_str(&format!("({})", arg_values.join(", ")));
+    }
+
+    label
+        .chars()
+        .map(|c| match c {
+            ';' => ':',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+pub(crate) fn write_json_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write!(writer, "\"")?;
+
+    for c in value.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            '\n' => write!(writer, "\\n")?,
+            '\r' => write!(writer, "\\r")?,
+            '\t' => write!(writer, "\\t")?,
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+
+    write!(writer, "\"")
+}
+
+impl Tracer {
+    fn new(filter: TraceFilter, output: TraceOutput) -> Tracer {
+        Tracer {
+            filter,
+            output,
+            start: Instant::now(),
+            events: Mutex::new(vec![]),
+            folded_stacks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn log_line(&self, message: &str) {
+        let thread_id = THREAD_ID.with(|thread_id| *thread_id);
+        let depth = SPAN_DEPTH.with(Cell::get);
+
+        eprintln!(
+            "[{:>10.3}ms t{}] {:indent$}{}",
+            self.start.elapsed().as_secs_f64() * 1000.0,
+            thread_id,
+            "",
+            message,
+            indent = depth * 2
+        );
+    }
+
+    fn exit_span(&self, event: CompleteEvent) {
+        match self.output {
+            TraceOutput::Log => {
+                self.log_line(&format!(
+                    "{}::{} finished in {:.3}ms",
+                    event.category,
+                    event.name,
+                    event.duration.as_secs_f64() * 1000.0
+                ));
+            }
+            TraceOutput::ChromeJson(_) => {
+                self.events.lock().unwrap().push(event);
+            }
+            TraceOutput::FoldedStacks(_) => {
+                let (stack_path, self_duration) = SPAN_STACK.with(|stack| {
+                    let mut stack = stack.borrow_mut();
+                    let stack_path = stack
+                        .iter()
+                        .map(|frame| frame.label.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(";");
+
+                    let frame = stack.pop().unwrap();
+                    if let Some(parent_frame) = stack.last_mut() {
+                        parent_frame.child_duration += event.duration;
+                    }
+
+                    (
+                        stack_path,
+                        event
+                            .duration
+                            .checked_sub(frame.child_duration)
+                            .unwrap_or_default(),
+                    )
+                });
+
+                self.add_folded_sample(stack_path, self_duration);
+            }
+        }
+    }
+
+    fn add_folded_sample(&self, stack_path: String, self_duration: Duration) {
+        *self
+            .folded_stacks
+            .lock()
+            .unwrap()
+            .entry(stack_path)
+            .or_insert(0) += self_duration.as_micros();
+    }
+
+    fn write_folded_stacks(&self, writer: &mut impl Write) -> io::Result<()> {
+        let folded_stacks = self.folded_stacks.lock().unwrap();
+
+        // Sort the stacks so the output is deterministic
+        let mut sorted_stacks: Vec<(&String, &u128)> = folded_stacks.iter().collect();
+        sorted_stacks.sort();
+
+        for (stack_path, micros) in sorted_stacks {
+            writeln!(writer, "{} {}", stack_path, micros)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_chrome_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        let events = self.events.lock().unwrap();
+
+        write!(writer, "{{\"traceEvents\":[")?;
+
+        for (index, event) in events.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+
+            write!(writer, "{{\"name\":")?;
+            write_json_str(writer, event.name)?;
+            write!(writer, ",\"cat\":")?;
+            write_json_str(writer, event.category)?;
+            write!(
+                writer,
+                ",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{},\"args\":{{",
+                event.thread_id,
+                event.start.as_micros(),
+                event.duration.as_micros()
+            )?;
+
+            for (arg_index, (name, value)) in event.args.iter().enumerate() {
+                if arg_index > 0 {
+                    write!(writer, ",")?;
+                }
+
+                write_json_str(writer, name)?;
+                write!(writer, ":")?;
+                write_json_str(writer, value)?;
+            }
+
+            write!(writer, "}}}}")?;
+        }
+
+        writeln!(writer, "],\"displayTimeUnit\":\"ms\"}}")
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        match &self.output {
+            TraceOutput::Log => {}
+            TraceOutput::ChromeJson(output_path) => {
+                let mut output_file = io::BufWriter::new(fs::File::create(output_path)?);
+                self.write_chrome_json(&mut output_file)?;
+                output_file.flush()?;
+            }
+            TraceOutput::FoldedStacks(output_path) => {
+                let mut output_file = io::BufWriter::new(fs::File::create(output_path)?);
+                self.write_folded_stacks(&mut output_file)?;
+                output_file.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_filter() {
+        assert_eq!(Ok(TraceFilter::all()), TraceFilter::parse("all"));
+        assert_eq!(Ok(TraceFilter::all()), TraceFilter::parse("hir,*"));
+
+        let filter = TraceFilter::parse("hir, codegen").unwrap();
+        assert!(filter.is_enabled("hir"));
+        assert!(filter.is_enabled("codegen"));
+        assert!(!filter.is_enabled("mir"));
+
+        assert_eq!(
+            Err(InvalidTraceFilter("llvm".to_owned())),
+            TraceFilter::parse("hir,llvm")
+        );
+    }
+
+    #[test]
+    fn chrome_json_output() {
+        let tracer = Tracer::new(
+            TraceFilter::all(),
+            TraceOutput::ChromeJson(path::PathBuf::new()),
+        );
+
+        tracer.exit_span(CompleteEvent {
+            category: "mir",
+            name: "build_fun",
+            args: vec![("fun", "say \"hi\"".to_owned())],
+            thread_id: 1,
+            start: Duration::from_micros(5),
+            duration: Duration::from_micros(10),
+        });
+
+        let mut output = vec![];
+        tracer.write_chrome_json(&mut output).unwrap();
+
+        assert_eq!(
+            "{\"traceEvents\":[{\"name\":\"build_fun\",\"cat\":\"mir\",\"ph\":\"X\",\"pid\":1,\
+             \"tid\":1,\"ts\":5,\"dur\":10,\"args\":{\"fun\":\"say \\\"hi\\\"\"}}],\
+             \"displayTimeUnit\":\"ms\"}\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn folded_stacks_output() {
+        let tracer = Tracer::new(
+            TraceFilter::all(),
+            TraceOutput::FoldedStacks(path::PathBuf::new()),
+        );
+
+        tracer.add_folded_sample(
+            "typeck::infer_module;typeck::infer_def(main!)".to_owned(),
+            Duration::from_micros(10),
+        );
+        tracer.add_folded_sample("typeck::infer_module".to_owned(), Duration::from_micros(4));
+        tracer.add_folded_sample("typeck::infer_module".to_owned(), Duration::from_micros(1));
+
+        let mut output = vec![];
+        tracer.write_folded_stacks(&mut output).unwrap();
+
+        assert_eq!(
+            "typeck::infer_module 5\ntypeck::infer_module;typeck::infer_def(main!) 10\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        assert_eq!(
+            "hir::load_module([stdlib_base])",
+            folded_frame_label(
+                "hir",
+                "load_module",
+                &[("module", "[stdlib base]".to_owned())]
+            )
+        );
+    }
+}
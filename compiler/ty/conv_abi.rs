@@ -26,6 +26,11 @@ fn type_tag_to_ty<M: ty::Pm>(type_tag: boxed::TypeTag) -> Ty<M> {
             key: Ty::Any.into(),
             value: Ty::Any.into(),
         })),
+        TypeTag::FloatArray => Ty::FloatArray,
+        TypeTag::IntArray => Ty::IntArray,
+        TypeTag::Bytes => Ty::Bytes,
+        TypeTag::Rational => Ty::Rational,
+        TypeTag::BigInt => Ty::BigInt,
     }
 }
 
@@ -117,6 +122,13 @@ impl ConvertableAbiType for abitype::RetAbiType {
             RetAbiType::Void => Ty::unit().into(),
             RetAbiType::Never => Ty::never().into(),
             RetAbiType::Inhabited(abi_type) => abi_type.to_ty_ref(),
+            RetAbiType::Values(abi_types) => Ty::Values(
+                abi_types
+                    .iter()
+                    .map(|abi_type| abi_type.to_ty_ref())
+                    .collect(),
+            )
+            .into(),
         }
     }
 
@@ -127,6 +139,14 @@ impl ConvertableAbiType for abitype::RetAbiType {
             RetAbiType::Void => "()".to_owned(),
             RetAbiType::Never => "Never".to_owned(),
             RetAbiType::Inhabited(abi_type) => abi_type.to_rust_str(),
+            RetAbiType::Values(abi_types) => {
+                let member_strs: Vec<String> = abi_types
+                    .iter()
+                    .map(|abi_type| abi_type.to_rust_str())
+                    .collect();
+
+                format!("({})", member_strs.join(", "))
+            }
         }
     }
 }
@@ -1,15 +1,43 @@
-use arret_syntax::datum::Datum;
+use arret_syntax::datum::{DataStr, Datum};
 
 use crate::ty;
 use crate::ty::Ty;
 
+/// Returns a closed keyed map type for a map with only keyword keys
+///
+/// This returns `None` for empty maps and maps with any other key so they're typed as a `Map`.
+fn keyword_map_ty<M: ty::Pm>(vs: &[(Datum, Datum)]) -> Option<ty::KeyedMap<M>> {
+    if vs.is_empty() {
+        return None;
+    }
+
+    let mut entries = vs
+        .iter()
+        .map(|(key, value)| match key {
+            Datum::Sym(_, name) if name.starts_with(':') => {
+                Some((name.clone(), ty_ref_for_datum(value)))
+            }
+            _ => None,
+        })
+        .collect::<Option<Vec<(DataStr, ty::Ref<M>)>>>()?;
+
+    // Later values replace earlier values with the same key
+    entries.reverse();
+    entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+    entries.dedup_by(|(key1, _), (key2, _)| key1 == key2);
+
+    Some(ty::KeyedMap::new(entries.into_boxed_slice(), None))
+}
+
 pub fn ty_ref_for_datum<M: ty::Pm>(datum: &Datum) -> ty::Ref<M> {
     (match datum {
         Datum::Bool(_, val) => Ty::LitBool(*val),
         Datum::Sym(_, val) => Ty::LitSym(val.clone()),
         Datum::Char(_, _) => Ty::Char,
-        Datum::Int(_, _) => Ty::Int,
+        Datum::Int(_, val) => ty::IntRange::singleton(*val).into(),
         Datum::Float(_, _) => Ty::Float,
+        Datum::Rational(_, _, _) => Ty::Rational,
+        Datum::BigInt(_, _) => Ty::BigInt,
         Datum::Str(_, _) => Ty::Str,
         Datum::List(_, vs) => {
             ty::List::new_tuple(vs.iter().map(|datum| ty_ref_for_datum(datum)).collect()).into()
@@ -20,6 +48,10 @@ pub fn ty_ref_for_datum<M: ty::Pm>(datum: &Datum) -> ty::Ref<M> {
             Ty::Set(Box::new(unified_type))
         }
         Datum::Map(_, vs) => {
+            if let Some(keyed_map) = keyword_map_ty(vs) {
+                return keyed_map.into();
+            }
+
             let unified_key =
                 ty::unify::unify_ty_ref_iter(vs.iter().map(|(k, _)| ty_ref_for_datum(k)));
 
@@ -46,8 +78,8 @@ mod test {
 
     #[test]
     fn trivial_types() {
-        assert_poly_for_str("Int", "1");
-        assert_poly_for_str("Int", "-51");
+        assert_poly_for_str("1", "1");
+        assert_poly_for_str("-51", "-51");
         assert_poly_for_str("Char", "\\newline");
         assert_poly_for_str("Str", r#""Test string""#);
     }
@@ -66,13 +98,13 @@ mod test {
     #[test]
     fn fixed_list() {
         assert_poly_for_str("()", "()");
-        assert_poly_for_str("(List Int Int 'foo)", "(1 2 foo)");
+        assert_poly_for_str("(List 1 2 'foo)", "(1 2 foo)");
     }
 
     #[test]
     fn fixed_vec() {
         assert_poly_for_str("[]", "[]");
-        assert_poly_for_str("(Vector false Int 'foo)", "[false 2 foo]");
+        assert_poly_for_str("(Vector false 2 'foo)", "[false 2 foo]");
     }
 
     #[test]
@@ -84,6 +116,15 @@ mod test {
     #[test]
     fn fixed_map() {
         assert_poly_for_str("(Map (RawU) (RawU))", "{}");
-        assert_poly_for_str("(Map Bool (RawU Int 'foo))", "{true 1, false foo}");
+        assert_poly_for_str("(Map Bool (RawU 1 'foo))", "{true 1, false foo}");
+        assert_poly_for_str("(Map (RawU :one 'two) 1)", "{:one 1, two 1}");
+    }
+
+    #[test]
+    fn keyword_map() {
+        assert_poly_for_str(
+            "(KeyedMap :name Str :age 13)",
+            r#"{:name "Ferris" :age 13}"#,
+        );
     }
 }
@@ -2,11 +2,15 @@ use std::cmp;
 use std::iter;
 use std::result;
 
+use arret_syntax::datum::DataStr;
+
 use crate::ty;
 use crate::ty::list_iter::ListIterator;
+use crate::ty::newtype;
 use crate::ty::purity;
 use crate::ty::purity::Purity;
 use crate::ty::record;
+use crate::ty::recursive;
 use crate::ty::ty_args::TyArgs;
 use crate::ty::var_usage::Variance;
 use crate::ty::Ty;
@@ -129,13 +133,22 @@ fn intersect_record_field_ty_refs<M: ty::Pm>(
     tvar: &ty::TVarId,
     ty_args1: &TyArgs<M>,
     ty_args2: &TyArgs<M>,
+) -> Result<ty::Ref<M>> {
+    intersect_variant_ty_refs(
+        variance,
+        &ty_args1.tvar_types()[tvar],
+        &ty_args2.tvar_types()[tvar],
+    )
+}
+
+fn intersect_variant_ty_refs<M: ty::Pm>(
+    variance: Variance,
+    ty_ref1: &ty::Ref<M>,
+    ty_ref2: &ty::Ref<M>,
 ) -> Result<ty::Ref<M>> {
     use crate::ty::is_a::ty_refs_equivalent;
     use crate::ty::unify::unify_to_ty_ref;
 
-    let ty_ref1 = &ty_args1.tvar_types()[tvar];
-    let ty_ref2 = &ty_args2.tvar_types()[tvar];
-
     match variance {
         Variance::Covariant => intersect_ty_refs(ty_ref1, ty_ref2),
         Variance::Contravariant => Ok(unify_to_ty_ref(ty_ref1, ty_ref2)),
@@ -197,6 +210,110 @@ fn intersect_record_instance<M: ty::Pm>(
     ))
 }
 
+/// Intersects a recursive type by unfolding it
+///
+/// If we reach our unfolding limit the intersection is left unsimplified.
+fn intersect_unfolded<M: ty::Pm>(
+    instance: &recursive::Instance<M>,
+    recursive_ref: &ty::Ref<M>,
+    other_ref: &ty::Ref<M>,
+) -> Result<ty::Ref<M>> {
+    recursive::with_unfolded(instance, |unfolded| intersect_ty_refs(unfolded, other_ref))
+        .unwrap_or_else(|| Ok(flatten_ref_intersect(recursive_ref, other_ref)))
+}
+
+/// Intersects a newtype with another type
+///
+/// Newtypes can't be distinguished from their representation at runtime. If the values of the
+/// representation could satisfy the other type we conservatively keep the newtype.
+fn intersect_newtype<M: ty::Pm>(
+    newtype: &newtype::NewtypeId,
+    newtype_ref: &ty::Ref<M>,
+    other_ref: &ty::Ref<M>,
+) -> Result<ty::Ref<M>> {
+    intersect_ty_refs(&newtype.repr_for_pm(), other_ref).map(|_| newtype_ref.clone())
+}
+
+fn intersect_maps<M: ty::Pm>(map1: &ty::Map<M>, map2: &ty::Map<M>) -> Result<ty::Map<M>> {
+    Ok(ty::Map::new(
+        intersect_ty_refs(map1.key(), map2.key())?,
+        intersect_ty_refs(map1.value(), map2.value())?,
+    ))
+}
+
+/// Intersects the value of a known key with the value the other map type allows for that key
+fn intersect_known_key<M: ty::Pm>(
+    key: &DataStr,
+    value: &ty::Ref<M>,
+    other_keyed: &ty::KeyedMap<M>,
+) -> Result<ty::Ref<M>> {
+    if let Some(other_value) = other_keyed.get(key) {
+        return intersect_ty_refs(value, other_value);
+    }
+
+    match other_keyed.rest() {
+        Some(other_rest) => {
+            intersect_ty_refs(&Ty::LitSym(key.clone()).into(), other_rest.key())?;
+            intersect_ty_refs(value, other_rest.value())
+        }
+        // The key must be present but the other map can't contain it
+        None => Err(Error::Disjoint),
+    }
+}
+
+fn intersect_keyed_maps<M: ty::Pm>(
+    keyed1: &ty::KeyedMap<M>,
+    keyed2: &ty::KeyedMap<M>,
+) -> Result<ty::Ref<M>> {
+    let mut entries = Vec::with_capacity(keyed1.entries().len());
+
+    for (key, value1) in keyed1.entries() {
+        entries.push((key.clone(), intersect_known_key(key, value1, keyed2)?));
+    }
+
+    for (key, value2) in keyed2.entries() {
+        if keyed1.get(key).is_none() {
+            entries.push((key.clone(), intersect_known_key(key, value2, keyed1)?));
+        }
+    }
+
+    // If the rests are disjoint the map can only contain its known keys
+    let rest = match (keyed1.rest(), keyed2.rest()) {
+        (Some(rest1), Some(rest2)) => intersect_maps(rest1, rest2).ok(),
+        _ => None,
+    };
+
+    Ok(ty::KeyedMap::new(entries.into_boxed_slice(), rest).into())
+}
+
+fn intersect_recursive_instances<M: ty::Pm>(
+    ref1: &ty::Ref<M>,
+    instance1: &recursive::Instance<M>,
+    ref2: &ty::Ref<M>,
+    instance2: &recursive::Instance<M>,
+) -> Result<ty::Ref<M>> {
+    if instance1.cons() == instance2.cons() {
+        let merged_ty_args = instance1
+            .ty_args()
+            .iter()
+            .zip(instance2.ty_args().iter())
+            .enumerate()
+            .map(|(param_index, (ty_arg1, ty_arg2))| {
+                let variance = instance1.cons().param_variance(param_index);
+                intersect_variant_ty_refs(variance, ty_arg1, ty_arg2)
+            })
+            .collect::<Result<Box<[ty::Ref<M>]>>>();
+
+        if let Ok(merged_ty_args) = merged_ty_args {
+            return Ok(recursive::Instance::new(instance1.cons().clone(), merged_ty_args).into());
+        }
+    }
+
+    // Disjoint type arguments can still have values in common. For example, `(Tree Int)` and
+    // `(Tree Str)` both contain the empty list.
+    intersect_unfolded(instance1, ref1, ref2)
+}
+
 /// Intersects two types under the assumption that they are not subtypes
 fn non_subty_intersect<M: ty::Pm>(
     ref1: &ty::Ref<M>,
@@ -229,6 +346,18 @@ fn non_subty_intersect<M: ty::Pm>(
             Ok(acc)
         }
 
+        // Recursive types
+        (Ty::Recursive(instance1), Ty::Recursive(instance2)) => {
+            intersect_recursive_instances(ref1, instance1, ref2, instance2)
+        }
+        (Ty::Recursive(instance1), _) => intersect_unfolded(instance1, ref1, ref2),
+        (_, Ty::Recursive(instance2)) => intersect_unfolded(instance2, ref2, ref1),
+
+        // Newtypes
+        (Ty::Newtype(_), Ty::Newtype(_)) => Err(Error::Disjoint),
+        (Ty::Newtype(newtype1), _) => intersect_newtype(newtype1, ref1, ref2),
+        (_, Ty::Newtype(newtype2)) => intersect_newtype(newtype2, ref2, ref1),
+
         // Set type
         (Ty::Set(member1), Ty::Set(member2)) => Ok(Ty::Set(Box::new(intersect_ty_refs(
             member1.as_ref(),
@@ -237,11 +366,12 @@ fn non_subty_intersect<M: ty::Pm>(
         .into()),
 
         // Map type
-        (Ty::Map(map1), Ty::Map(map2)) => Ok(ty::Map::new(
-            intersect_ty_refs(map1.key(), map2.key())?,
-            intersect_ty_refs(map1.value(), map2.value())?,
-        )
-        .into()),
+        (Ty::Map(map1), Ty::Map(map2)) => Ok(intersect_maps(map1, map2)?.into()),
+        (Ty::KeyedMap(keyed1), Ty::KeyedMap(keyed2)) => intersect_keyed_maps(keyed1, keyed2),
+        (Ty::KeyedMap(keyed), Ty::Map(map)) | (Ty::Map(map), Ty::KeyedMap(keyed)) => {
+            let map_as_keyed = ty::KeyedMap::new(Box::new([]), Some(map.as_ref().clone()));
+            intersect_keyed_maps(keyed, &map_as_keyed)
+        }
 
         // Vector types
         (Ty::Vectorof(member1), Ty::Vectorof(member2)) => Ok(Ty::Vectorof(Box::new(
@@ -271,9 +401,35 @@ fn non_subty_intersect<M: ty::Pm>(
             Ok(Ty::Vector(intersected_members).into())
         }
 
+        // Integer ranges
+        (Ty::IntRange(range1), Ty::IntRange(range2)) => range1
+            .intersect(range2)
+            .map(Into::into)
+            .ok_or(Error::Disjoint),
+
         // List types
         (Ty::List(list1), Ty::List(list2)) => Ok(intersect_list(list1, list2)?.into()),
 
+        // Multiple values
+        (Ty::Values(members1), Ty::Values(members2)) => {
+            if members1.len() != members2.len() {
+                Err(Error::Disjoint)
+            } else {
+                let intersected_members = members1
+                    .iter()
+                    .zip(members2.iter())
+                    .map(|(member1, member2)| intersect_ty_refs(member1, member2))
+                    .collect::<Result<Box<[ty::Ref<M>]>>>()?;
+
+                Ok(Ty::Values(intersected_members).into())
+            }
+        }
+        (Ty::Values(members), Ty::List(list)) | (Ty::List(list), Ty::Values(members)) => {
+            // The intersection is always a list with the same fixed length as the values
+            let intersected_list = intersect_list(&ty::List::new_tuple(members.clone()), list)?;
+            Ok(Ty::Values(intersected_list.fixed().into()).into())
+        }
+
         // Function types
         (Ty::TopFun(top_fun1), Ty::TopFun(top_fun2)) => {
             let intersected_purity = intersect_purity_refs(top_fun1.purity(), top_fun2.purity());
@@ -451,6 +607,16 @@ mod test {
         assert_merged("Bool", "Bool", "Any");
     }
 
+    #[test]
+    fn int_range_types() {
+        assert_merged("(IntRange 3 5)", "(IntRange 0 5)", "(IntRange 3 9)");
+        assert_merged("5", "(IntRange 0 5)", "(IntRange 5 9)");
+        assert_merged("(IntRange 0 9)", "(IntRange 0 9)", "Int");
+        assert_merged("1", "Num", "1");
+        assert_disjoint("(IntRange 0 4)", "(IntRange 5 9)");
+        assert_disjoint("1", "Float");
+    }
+
     #[test]
     fn union_types() {
         assert_merged("'bar", "(RawU 'foo 'bar)", "(RawU 'bar 'baz)");
@@ -490,6 +656,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn keyed_map_types() {
+        assert_disjoint("(KeyedMap :name Str)", "(KeyedMap :name Int)");
+        assert_disjoint("(KeyedMap :name Str)", "(KeyedMap :age Int)");
+        assert_merged(
+            "(KeyedMap :name Str :age Int & Sym Any)",
+            "(KeyedMap :name Str & Sym Any)",
+            "(KeyedMap :age Int & Sym Any)",
+        );
+        assert_merged(
+            "(KeyedMap :name Str & Sym (RawU Str Int))",
+            "(KeyedMap :name Str & Sym Any)",
+            "(Map Sym (RawU Str Int))",
+        );
+    }
+
     #[test]
     fn set_types() {
         assert_disjoint("(Setof Sym)", "(Setof Str)");
@@ -513,6 +695,25 @@ mod test {
         assert_disjoint("(List Sym Sym)", "(List Sym)");
     }
 
+    #[test]
+    fn values_types() {
+        assert_disjoint("(Values Int)", "(Values Float)");
+        assert_disjoint("(Values Int Int)", "(Values Int)");
+        assert_merged(
+            "(Values true Int)",
+            "(Values Bool Int)",
+            "(Values true Num)",
+        );
+
+        assert_disjoint("(Values Int Int)", "(List Int)");
+        assert_merged("(Values Int Int)", "(Values Int Int)", "(List & Int)");
+        assert_merged(
+            "(Values true Int)",
+            "(Values Bool Int)",
+            "(List true & Any)",
+        );
+    }
+
     #[test]
     fn vec_types() {
         assert_disjoint("(Vector Int)", "(Vector Float)");
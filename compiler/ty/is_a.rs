@@ -1,8 +1,11 @@
+use arret_syntax::datum::DataStr;
+
 use crate::ty;
 use crate::ty::list_iter::ListIterator;
 use crate::ty::purity;
 use crate::ty::purity::Purity;
 use crate::ty::record;
+use crate::ty::recursive;
 use crate::ty::var_usage::Variance;
 use crate::ty::Ty;
 
@@ -34,6 +37,57 @@ fn list_is_a<M: ty::Pm>(sub_list: &ty::List<M>, par_list: &ty::List<M>) -> bool
         .all(|(sub, par)| ty_ref_is_a(sub, par))
 }
 
+fn map_is_a<M: ty::Pm>(sub_map: &ty::Map<M>, par_map: &ty::Map<M>) -> bool {
+    ty_ref_is_a(sub_map.key(), par_map.key()) && ty_ref_is_a(sub_map.value(), par_map.value())
+}
+
+/// Returns true if a known key and its value are allowed by a rest map type
+fn map_entry_is_a<M: ty::Pm>(key: &DataStr, value: &ty::Ref<M>, par_map: &ty::Map<M>) -> bool {
+    ty_ref_is_a(&Ty::LitSym(key.clone()).into(), par_map.key())
+        && ty_ref_is_a(value, par_map.value())
+}
+
+fn keyed_map_is_a<M: ty::Pm>(sub_keyed: &ty::KeyedMap<M>, par_keyed: &ty::KeyedMap<M>) -> bool {
+    // Every key known by the parent must be known by the subtype
+    let known_keys_are_a =
+        par_keyed
+            .entries()
+            .iter()
+            .all(|(key, par_value)| match sub_keyed.get(key) {
+                Some(sub_value) => ty_ref_is_a(sub_value, par_value),
+                None => false,
+            });
+
+    // Any other known keys must be allowed by the parent's rest
+    let extra_keys_are_a = sub_keyed
+        .entries()
+        .iter()
+        .filter(|(key, _)| par_keyed.get(key).is_none())
+        .all(|(key, sub_value)| {
+            par_keyed
+                .rest()
+                .map_or(false, |par_rest| map_entry_is_a(key, sub_value, par_rest))
+        });
+
+    let rest_is_a = match (sub_keyed.rest(), par_keyed.rest()) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(sub_rest), Some(par_rest)) => map_is_a(sub_rest, par_rest),
+    };
+
+    known_keys_are_a && extra_keys_are_a && rest_is_a
+}
+
+fn keyed_map_is_a_map<M: ty::Pm>(sub_keyed: &ty::KeyedMap<M>, par_map: &ty::Map<M>) -> bool {
+    sub_keyed
+        .entries()
+        .iter()
+        .all(|(key, value)| map_entry_is_a(key, value, par_map))
+        && sub_keyed
+            .rest()
+            .map_or(true, |sub_rest| map_is_a(sub_rest, par_map))
+}
+
 fn record_field_is_a<F, R>(variance: Variance, is_a: &F, sub: &R, par: &R) -> bool
 where
     F: Fn(&R, &R) -> bool,
@@ -45,31 +99,81 @@ where
     }
 }
 
+/// Returns if a record instance has a compatible field for every field of the parent instance
+///
+/// This is the width subtyping used for parent constructors declared with `&rest-fields`.
+fn record_instance_has_fields_of<M: ty::Pm>(
+    sub_instance: &record::Instance<M>,
+    par_instance: &record::Instance<M>,
+) -> bool {
+    par_instance
+        .cons()
+        .fields()
+        .iter()
+        .filter(|par_field| par_field.name().as_ref() != "_")
+        .all(|par_field| {
+            sub_instance
+                .cons()
+                .field_index(par_field.name())
+                .map_or(false, |sub_field_index| {
+                    let sub_field = &sub_instance.cons().fields()[sub_field_index];
+
+                    ty_ref_is_a(
+                        &M::subst_ty_args(sub_instance.ty_args(), sub_field.ty_ref()),
+                        &M::subst_ty_args(par_instance.ty_args(), par_field.ty_ref()),
+                    )
+                })
+        })
+}
+
 fn record_instance_is_a<M: ty::Pm>(
     sub_instance: &record::Instance<M>,
     par_instance: &record::Instance<M>,
 ) -> bool {
-    // Make sure they came from the same constructor and satisfy their params
-    sub_instance.cons() == par_instance.cons()
-        && sub_instance
-            .cons()
-            .poly_params()
-            .iter()
-            .all(|poly_param| match poly_param {
-                record::PolyParam::PVar(variance, pvar) => record_field_is_a(
-                    *variance,
-                    &purity_ref_is_a,
-                    &sub_instance.ty_args().pvar_purities()[pvar],
-                    &par_instance.ty_args().pvar_purities()[pvar],
-                ),
-                record::PolyParam::TVar(variance, tvar) => record_field_is_a(
-                    *variance,
-                    &ty_ref_is_a,
-                    &sub_instance.ty_args().tvar_types()[tvar],
-                    &par_instance.ty_args().tvar_types()[tvar],
-                ),
-                record::PolyParam::Pure(_) | record::PolyParam::TFixed(_, _) => true,
-            })
+    if sub_instance.cons() != par_instance.cons() {
+        return par_instance.cons().has_rest_fields()
+            && record_instance_has_fields_of(sub_instance, par_instance);
+    }
+
+    // Make sure they satisfy their params
+    sub_instance
+        .cons()
+        .poly_params()
+        .iter()
+        .all(|poly_param| match poly_param {
+            record::PolyParam::PVar(variance, pvar) => record_field_is_a(
+                *variance,
+                &purity_ref_is_a,
+                &sub_instance.ty_args().pvar_purities()[pvar],
+                &par_instance.ty_args().pvar_purities()[pvar],
+            ),
+            record::PolyParam::TVar(variance, tvar) => record_field_is_a(
+                *variance,
+                &ty_ref_is_a,
+                &sub_instance.ty_args().tvar_types()[tvar],
+                &par_instance.ty_args().tvar_types()[tvar],
+            ),
+            record::PolyParam::Pure(_) | record::PolyParam::TFixed(_, _) => true,
+        })
+}
+
+fn recursive_instance_is_a<M: ty::Pm>(
+    sub_instance: &recursive::Instance<M>,
+    par_instance: &recursive::Instance<M>,
+) -> bool {
+    sub_instance
+        .ty_args()
+        .iter()
+        .zip(par_instance.ty_args().iter())
+        .enumerate()
+        .all(|(param_index, (sub_arg, par_arg))| {
+            record_field_is_a(
+                sub_instance.cons().param_variance(param_index),
+                &ty_ref_is_a,
+                sub_arg,
+                par_arg,
+            )
+        })
 }
 
 fn monomorphic_fun_is_a(sub_fun: &ty::Fun, par_fun: &ty::Fun) -> bool {
@@ -102,6 +206,21 @@ fn ty_is_a<M: ty::Pm>(
         (Ty::Union(sub_members), _) => sub_members
             .iter()
             .all(|sub_member| ty_ref_is_a(sub_member, parent_ref)),
+
+        // Recursive types
+        (Ty::Recursive(sub_instance), Ty::Recursive(par_instance))
+            if sub_instance.cons() == par_instance.cons() =>
+        {
+            recursive_instance_is_a(sub_instance, par_instance)
+        }
+        (Ty::Recursive(sub_instance), _) if parent_ty != &Ty::Any => {
+            // If we reach our unfolding limit then we can't prove this is a subtype
+            recursive::with_unfolded(sub_instance, |sub_unfolded| {
+                ty_ref_is_a(sub_unfolded, parent_ref)
+            })
+            .unwrap_or(false)
+        }
+
         (_, Ty::Union(par_members)) => par_members
             .iter()
             .any(|par_member| ty_ref_is_a(sub_ref, par_member)),
@@ -115,7 +234,7 @@ fn ty_is_a<M: ty::Pm>(
             .any(|sub_member| ty_ref_is_a(sub_member, parent_ref)),
 
         // Any type
-        (_, Ty::Any) => true,
+        (_, Ty::Any) | (_, Ty::Dyn) => true,
 
         // Sym types
         (Ty::LitSym(_), Ty::Sym) => true,
@@ -128,14 +247,22 @@ fn ty_is_a<M: ty::Pm>(
 
         // Ints
         (Ty::Int, Ty::Num) => true,
+        (Ty::IntRange(_), Ty::Int) | (Ty::IntRange(_), Ty::Num) => true,
+        (Ty::IntRange(sub_range), Ty::IntRange(par_range)) => par_range.contains_range(sub_range),
 
         // Sets
         (Ty::Set(sub), Ty::Set(par)) => ty_ref_is_a(sub.as_ref(), par.as_ref()),
 
         // Maps
-        (Ty::Map(sub_map), Ty::Map(par_map)) => {
-            ty_ref_is_a(sub_map.key(), par_map.key())
-                && ty_ref_is_a(sub_map.value(), par_map.value())
+        (Ty::Map(sub_map), Ty::Map(par_map)) => map_is_a(sub_map, par_map),
+        (Ty::KeyedMap(sub_keyed), Ty::KeyedMap(par_keyed)) => keyed_map_is_a(sub_keyed, par_keyed),
+        (Ty::KeyedMap(sub_keyed), Ty::Map(par_map)) => keyed_map_is_a_map(sub_keyed, par_map),
+        (Ty::Map(sub_map), Ty::KeyedMap(par_keyed)) => {
+            // Plain maps can't guarantee any key is present
+            par_keyed.entries().is_empty()
+                && par_keyed
+                    .rest()
+                    .map_or(false, |par_rest| map_is_a(sub_map, par_rest))
         }
 
         // Vector types
@@ -181,6 +308,18 @@ fn ty_is_a<M: ty::Pm>(
         // List types
         (Ty::List(sub_list), Ty::List(par_list)) => list_is_a(sub_list, par_list),
 
+        // Multiple values
+        (Ty::Values(sub_members), Ty::Values(par_members)) => {
+            (sub_members.len() == par_members.len())
+                && sub_members
+                    .iter()
+                    .zip(par_members.iter())
+                    .all(|(sub_member, par_member)| ty_ref_is_a(sub_member, par_member))
+        }
+        (Ty::Values(sub_members), Ty::List(par_list)) => {
+            list_is_a(&ty::List::new_tuple(sub_members.clone()), par_list)
+        }
+
         // Record types
         (Ty::RecordClass(_), Ty::TopRecord) => true,
         (Ty::Record(_), Ty::TopRecord) => true,
@@ -189,10 +328,24 @@ fn ty_is_a<M: ty::Pm>(
             record_instance_is_a(sub_instance, par_instance)
         }
         (Ty::RecordClass(sub_cons), Ty::RecordClass(par_cons)) => sub_cons == par_cons,
-        (Ty::Record(sub_instance), Ty::RecordClass(par_cons)) => sub_instance.cons() == par_cons,
+        (Ty::Record(sub_instance), Ty::RecordClass(par_cons)) => {
+            // Instances of a constructor with rest fields include instances of other classes
+            sub_instance.cons() == par_cons && !par_cons.has_rest_fields()
+        }
         (Ty::RecordClass(sub_cons), Ty::Record(par_instance)) => {
             // If the record class has no polymorphic params then it only has one instance
-            sub_cons == par_instance.cons() && sub_cons.poly_params().is_empty()
+            sub_cons.poly_params().is_empty()
+                && record_instance_is_a(
+                    &record::Instance::new(sub_cons.clone(), ty::ty_args::TyArgs::empty()),
+                    par_instance,
+                )
+        }
+
+        (_, Ty::Recursive(par_instance)) => {
+            recursive::with_unfolded(par_instance, |par_unfolded| {
+                ty_ref_is_a(sub_ref, par_unfolded)
+            })
+            .unwrap_or(false)
         }
 
         _ => false,
@@ -307,6 +460,25 @@ mod test {
         assert!(!ty_ref_is_a(&any_int, &any_sym));
     }
 
+    #[test]
+    fn int_range_types() {
+        let one = poly_for_str("1");
+        let digit = poly_for_str("(IntRange 0 9)");
+        let small = poly_for_str("(IntRange -9 9)");
+        let any_int = poly_for_str("Int");
+
+        assert!(ty_ref_is_a(&one, &digit));
+        assert!(!ty_ref_is_a(&digit, &one));
+
+        assert!(ty_ref_is_a(&digit, &small));
+        assert!(!ty_ref_is_a(&small, &digit));
+
+        assert!(ty_ref_is_a(&digit, &any_int));
+        assert!(ty_ref_is_a(&digit, &poly_for_str("Num")));
+        assert!(!ty_ref_is_a(&any_int, &digit));
+        assert!(!ty_ref_is_a(&digit, &poly_for_str("Float")));
+    }
+
     #[test]
     fn set_types() {
         let foo_set = poly_for_str("(Setof 'foo)");
@@ -337,6 +509,34 @@ mod test {
         assert!(!ty_ref_is_a(&int_to_any_sym, &any_sym_to_any_sym));
     }
 
+    #[test]
+    fn keyed_map_types() {
+        let name_str = poly_for_str("(KeyedMap :name Str)");
+        let name_age = poly_for_str("(KeyedMap :name Str :age Int)");
+        let name_open = poly_for_str("(KeyedMap :name Str & Sym Any)");
+        let sym_to_any = poly_for_str("(Map Sym Any)");
+
+        assert!(ty_ref_is_a(&name_str, &name_str));
+
+        // Extra keys are only allowed by an open keyed map
+        assert!(!ty_ref_is_a(&name_age, &name_str));
+        assert!(ty_ref_is_a(&name_age, &name_open));
+        assert!(ty_ref_is_a(&name_str, &name_open));
+        assert!(!ty_ref_is_a(&name_open, &name_str));
+
+        // Missing keys are never allowed
+        assert!(!ty_ref_is_a(&name_str, &name_age));
+
+        // Keyed maps are maps of their keys and values
+        assert!(ty_ref_is_a(&name_age, &sym_to_any));
+        assert!(!ty_ref_is_a(&name_age, &poly_for_str("(Map Sym Str)")));
+        assert!(!ty_ref_is_a(&sym_to_any, &name_str));
+        assert!(ty_ref_is_a(
+            &poly_for_str("(Map 'foo Str)"),
+            &poly_for_str("(KeyedMap & Sym Str)")
+        ));
+    }
+
     #[test]
     fn union_types() {
         let foo_sym = poly_for_str("'foo");
@@ -419,6 +619,20 @@ mod test {
         assert!(!ty_ref_is_a(&any, &never));
     }
 
+    #[test]
+    fn dyn_types() {
+        let any = poly_for_str("Any");
+        let dyn_poly = poly_for_str("Dyn");
+        let foo_sym = poly_for_str("'foo");
+
+        assert!(ty_ref_is_a(&foo_sym, &dyn_poly));
+        assert!(ty_ref_is_a(&any, &dyn_poly));
+        assert!(ty_ref_is_a(&dyn_poly, &any));
+
+        // Using `Dyn` as a more specific type requires a runtime check
+        assert!(!ty_ref_is_a(&dyn_poly, &foo_sym));
+    }
+
     #[test]
     fn list_types() {
         let empty_list = poly_for_str("()");
@@ -463,6 +677,25 @@ mod test {
         assert!(!ty_ref_is_a(&three_ints_vec, &two_ints_vec));
     }
 
+    #[test]
+    fn values_types() {
+        let two_ints_values = poly_for_str("(Values Int Int)");
+        let two_nums_values = poly_for_str("(Values Num Num)");
+        let three_ints_values = poly_for_str("(Values Int Int Int)");
+        let two_ints_list = poly_for_str("(List Int Int)");
+        let listof_int = poly_for_str("(List & Int)");
+
+        assert!(ty_ref_is_a(&two_ints_values, &two_nums_values));
+        assert!(!ty_ref_is_a(&two_nums_values, &two_ints_values));
+        assert!(!ty_ref_is_a(&two_ints_values, &three_ints_values));
+
+        // Multiple values can be used as a list but not the other way around
+        assert!(ty_ref_is_a(&two_ints_values, &two_ints_list));
+        assert!(ty_ref_is_a(&two_ints_values, &listof_int));
+        assert!(!ty_ref_is_a(&two_ints_list, &two_ints_values));
+        assert!(!ty_ref_is_a(&three_ints_values, &two_ints_list));
+    }
+
     #[test]
     fn num_types() {
         let int = poly_for_str("Int");
@@ -790,4 +1023,75 @@ mod test {
             &int_any_num_instance_poly
         ));
     }
+
+    #[test]
+    fn rest_fields_record_instances() {
+        use crate::ty::ty_args::TyArgs;
+
+        let mono_instance = |cons: &record::ConsId| -> ty::Ref<ty::Poly> {
+            record::Instance::new(cons.clone(), TyArgs::empty()).into()
+        };
+
+        let named_cons = record::Cons::new_with_rest_fields(
+            EMPTY_SPAN,
+            "Named".into(),
+            "named".into(),
+            None,
+            Box::new([record::Field::new(
+                EMPTY_SPAN,
+                "name".into(),
+                Ty::Str.into(),
+            )]),
+        );
+        let exact_cons = record::Cons::new(
+            EMPTY_SPAN,
+            "Exact".into(),
+            "exact".into(),
+            None,
+            Box::new([record::Field::new(
+                EMPTY_SPAN,
+                "name".into(),
+                Ty::Str.into(),
+            )]),
+        );
+        let person_cons = record::Cons::new(
+            EMPTY_SPAN,
+            "Person".into(),
+            "person".into(),
+            None,
+            Box::new([
+                record::Field::new(EMPTY_SPAN, "age".into(), Ty::Int.into()),
+                record::Field::new(EMPTY_SPAN, "name".into(), Ty::Str.into()),
+            ]),
+        );
+        let sym_named_cons = record::Cons::new(
+            EMPTY_SPAN,
+            "SymNamed".into(),
+            "sym-named".into(),
+            None,
+            Box::new([record::Field::new(
+                EMPTY_SPAN,
+                "name".into(),
+                Ty::Sym.into(),
+            )]),
+        );
+
+        let named = mono_instance(&named_cons);
+        let person = mono_instance(&person_cons);
+
+        // Width subtyping is only allowed with rest fields
+        assert!(ty_ref_is_a(&person, &named));
+        assert!(ty_ref_is_a(&mono_instance(&exact_cons), &named));
+        assert!(!ty_ref_is_a(&person, &mono_instance(&exact_cons)));
+        assert!(!ty_ref_is_a(&named, &person));
+
+        // Shared fields must have compatible types
+        assert!(!ty_ref_is_a(&mono_instance(&sym_named_cons), &named));
+
+        // The record class remains nominal
+        let named_class: ty::Ref<ty::Poly> = named_cons.into();
+        assert!(ty_ref_is_a(&named_class, &named));
+        assert!(!ty_ref_is_a(&named, &named_class));
+        assert!(!ty_ref_is_a(&person, &named_class));
+    }
 }
@@ -5,20 +5,26 @@ use crate::ty::Ty;
 #[derive(Clone)]
 pub struct ListIterator<'list, M: ty::Pm> {
     fixed: &'list [ty::Ref<M>],
-    rest: &'list ty::Ref<M>,
+    rest: Option<&'list ty::Ref<M>>,
 }
 
 impl<'list, M: ty::Pm> ListIterator<'list, M> {
     pub fn new(list: &'list ty::List<M>) -> ListIterator<'list, M> {
         ListIterator {
             fixed: list.fixed(),
-            rest: list.rest(),
+            rest: Some(list.rest()),
         }
     }
 
+    /// Creates an iterator over a fixed number of members without a rest type
+    pub fn new_fixed(fixed: &'list [ty::Ref<M>]) -> ListIterator<'list, M> {
+        ListIterator { fixed, rest: None }
+    }
+
     pub fn try_new_from_ty_ref(ty_ref: &'list ty::Ref<M>) -> Option<ListIterator<'list, M>> {
         match ty_ref.try_to_fixed() {
             Some(Ty::List(list)) => Some(Self::new(list)),
+            Some(Ty::Values(members)) => Some(Self::new_fixed(members)),
             _ => None,
         }
     }
@@ -28,20 +34,21 @@ impl<'list, M: ty::Pm> ListIterator<'list, M> {
     }
 
     pub fn has_rest(&self) -> bool {
-        !self.rest.is_never()
+        matches!(self.rest, Some(rest) if !rest.is_never())
     }
 
     pub fn tail_type(self) -> ty::List<M> {
-        ty::List::new(self.fixed.to_vec().into_boxed_slice(), self.rest.clone())
+        ty::List::new(
+            self.fixed.to_vec().into_boxed_slice(),
+            self.rest.cloned().unwrap_or_else(|| Ty::never().into()),
+        )
     }
 
     pub fn collect_rest(self) -> ty::Ref<M> {
-        use std::iter;
-
         if self.fixed.is_empty() {
-            self.rest.clone()
+            self.rest.cloned().unwrap_or_else(|| Ty::never().into())
         } else {
-            ty::unify::unify_ty_ref_iter(self.fixed.iter().chain(iter::once(self.rest)).cloned())
+            ty::unify::unify_ty_ref_iter(self.fixed.iter().chain(self.rest).cloned())
         }
     }
 }
@@ -51,11 +58,7 @@ impl<'list, M: ty::Pm> Iterator for ListIterator<'list, M> {
 
     fn next(&mut self) -> Option<&'list ty::Ref<M>> {
         if self.fixed.is_empty() {
-            if self.rest.is_never() {
-                None
-            } else {
-                Some(self.rest)
-            }
+            self.rest.filter(|rest| !rest.is_never())
         } else {
             let next = self.fixed.first();
             self.fixed = &self.fixed[1..];
@@ -3,10 +3,12 @@ pub mod datum;
 pub mod intersect;
 pub mod is_a;
 pub mod list_iter;
+pub mod newtype;
 pub mod pred;
 pub mod props;
 pub mod purity;
 pub mod record;
+pub mod recursive;
 pub mod select;
 pub mod subst;
 pub mod subtract;
@@ -27,6 +29,7 @@ pub struct TVar {
     span: Span,
     source_name: DataStr,
     bound: Ref<Poly>,
+    intersected_bounds: Box<[Ref<Poly>]>,
 }
 
 pub type TVarId = ArcId<TVar>;
@@ -38,9 +41,34 @@ impl TVar {
             span,
             source_name,
             bound,
+            intersected_bounds: Box::new([]),
         })
     }
 
+    /// Creates a type variable bounded by every one of the passed bounds
+    ///
+    /// This fails if the bounds have no common subtype.
+    pub fn new_with_bounds(
+        span: Span,
+        source_name: DataStr,
+        bounds: Box<[Ref<Poly>]>,
+    ) -> Result<TVarId, intersect::Error> {
+        let bound = bounds.iter().try_fold(Ty::Any.into(), |acc, bound| {
+            intersect::intersect_ty_refs(&acc, bound)
+        })?;
+
+        Ok(TVarId::new(TVar {
+            span,
+            source_name,
+            bound,
+            intersected_bounds: if bounds.len() > 1 {
+                bounds
+            } else {
+                Box::new([])
+            },
+        }))
+    }
+
     pub fn span(&self) -> Span {
         self.span
     }
@@ -52,12 +80,33 @@ impl TVar {
     pub fn bound(&self) -> &Ref<Poly> {
         &self.bound
     }
+
+    /// Returns each bound the type variable was declared with
+    ///
+    /// This only differs from `bound` for variables with multiple bounds.
+    pub fn bounds(&self) -> &[Ref<Poly>] {
+        if self.intersected_bounds.is_empty() {
+            std::slice::from_ref(&self.bound)
+        } else {
+            &self.intersected_bounds
+        }
+    }
+
+    /// Returns the first declared bound the passed type doesn't satisfy
+    pub fn unsatisfied_bound(&self, ty_ref: &Ref<Poly>) -> Option<&Ref<Poly>> {
+        self.bounds()
+            .iter()
+            .find(|bound| !is_a::ty_ref_is_a(ty_ref, bound))
+    }
 }
 
 /// Marker that determines if type variables are allowed within a type
 pub trait Pm: PartialEq + Clone + Copy + Sized + fmt::Debug {
     /// Resolves a possibly variable type to its bound
     fn resolve_ref_to_ty(ty_ref: &Ref<Self>) -> &Ty<Self>;
+
+    /// Substitutes type arguments in to a polymorphic type
+    fn subst_ty_args(ty_args: &ty_args::TyArgs<Self>, poly: &Ref<Poly>) -> Ref<Self>;
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -69,6 +118,10 @@ impl Pm for Mono {
             Ref::Var(_, _) => unreachable!(),
         }
     }
+
+    fn subst_ty_args(ty_args: &ty_args::TyArgs<Mono>, poly: &Ref<Poly>) -> Ref<Mono> {
+        subst::monomorphise(ty_args, poly)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -80,6 +133,10 @@ impl Pm for Poly {
             Ref::Var(tvar, _) => Self::resolve_ref_to_ty(tvar.bound()),
         }
     }
+
+    fn subst_ty_args(ty_args: &ty_args::TyArgs<Poly>, poly: &Ref<Poly>) -> Ref<Poly> {
+        subst::subst_poly(ty_args, poly)
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -171,14 +228,28 @@ impl From<TVarId> for Ref<Poly> {
 #[derive(PartialEq, Debug, Clone)]
 pub enum Ty<M: Pm> {
     Any,
+
+    /// Gradual type compatible with any other type
+    ///
+    /// Values of this type are checked at runtime when they're used where a more specific type is
+    /// required. Otherwise this behaves like `Any`.
+    Dyn,
+
     Bool,
     Char,
     Float,
     Map(Box<Map<M>>),
+
+    /// Map with its own value type for each of a set of known keys
+    KeyedMap(Box<KeyedMap<M>>),
+
     Int,
     Num,
+    Rational,
+    BigInt,
     LitBool(bool),
     LitSym(DataStr),
+    IntRange(IntRange),
     Set(Box<Ref<M>>),
     Str,
     Sym,
@@ -195,13 +266,32 @@ pub enum Ty<M: Pm> {
     Vector(Box<[Ref<M>]>),
     Vectorof(Box<Ref<M>>),
 
+    // Unboxed numeric array types
+    FloatArray,
+    IntArray,
+
+    // Immutable byte sequence
+    Bytes,
+
     // List types
     List(List<M>),
 
+    /// Multiple values returned together from a function
+    ///
+    /// This is a subtype of the fixed length list containing the same members. Functions
+    /// returning multiple values can return them in registers instead of allocating a list.
+    Values(Box<[Ref<M>]>),
+
     // Record types
     TopRecord,
     RecordClass(record::ConsId),
     Record(Box<record::Instance<M>>),
+
+    /// Instance of a recursive type defined by `(deftype)`
+    Recursive(Box<recursive::Instance<M>>),
+
+    /// Nominal type defined by `(defnewtype)`
+    Newtype(newtype::NewtypeId),
 }
 
 impl<M: Pm> Ty<M> {
@@ -219,6 +309,111 @@ impl<M: Pm> Ty<M> {
     pub fn is_never(&self) -> bool {
         self == &Ty::Union(Box::new([]))
     }
+
+    /// Returns the cases of an overloaded function type
+    ///
+    /// Overloads are intersections of monomorphic function types. An application selects the
+    /// first case accepting its arguments.
+    pub fn overload_cases(&self) -> Option<Vec<&Fun>> {
+        if let Ty::Intersect(members) = self {
+            members
+                .iter()
+                .map(|member| match member.try_to_fixed() {
+                    Some(Ty::Fun(fun)) if !fun.has_polymorphic_vars() => Some(fun.as_ref()),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            None
+        }
+    }
+}
+
+/// Inclusive range of integers
+///
+/// A range containing a single integer is the singleton type of that integer.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct IntRange {
+    start: i64,
+    end: i64,
+}
+
+impl IntRange {
+    pub fn new(start: i64, end: i64) -> IntRange {
+        assert!(start <= end, "integer range cannot be empty");
+        IntRange { start, end }
+    }
+
+    pub fn singleton(value: i64) -> IntRange {
+        IntRange {
+            start: value,
+            end: value,
+        }
+    }
+
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    /// Returns the integer this range contains if it's a singleton
+    pub fn as_singleton(&self) -> Option<i64> {
+        if self.start == self.end {
+            Some(self.start)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if every integer in `other` is contained in this range
+    pub fn contains_range(&self, other: &IntRange) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Returns the integers contained in both ranges
+    pub fn intersect(&self, other: &IntRange) -> Option<IntRange> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+
+        if start <= end {
+            Some(IntRange { start, end })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a range containing both ranges if they overlap or are adjacent
+    pub fn try_merge(&self, other: &IntRange) -> Option<IntRange> {
+        let (lower, upper) = if self.start <= other.start {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        if upper.start <= lower.end.saturating_add(1) {
+            Some(IntRange {
+                start: lower.start,
+                end: lower.end.max(upper.end),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<M: Pm> From<IntRange> for Ty<M> {
+    fn from(int_range: IntRange) -> Self {
+        Ty::IntRange(int_range)
+    }
+}
+
+impl<M: Pm> From<IntRange> for Ref<M> {
+    fn from(int_range: IntRange) -> Self {
+        Ref::Fixed(Ty::IntRange(int_range))
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -253,6 +448,82 @@ impl<M: Pm> From<Map<M>> for Ref<M> {
     }
 }
 
+/// Map containing a set of known symbol keys
+///
+/// Each known key is present in every value of the type. Any other keys are described by an
+/// optional rest map type; without one the map can only contain its known keys.
+#[derive(PartialEq, Debug, Clone)]
+pub struct KeyedMap<M: Pm> {
+    entries: Box<[(DataStr, Ref<M>)]>,
+    rest: Option<Map<M>>,
+}
+
+impl<M: Pm> KeyedMap<M> {
+    /// Creates a new keyed map type
+    ///
+    /// Entries are sorted by their key to give each type a single representation.
+    pub fn new(entries: Box<[(DataStr, Ref<M>)]>, rest: Option<Map<M>>) -> KeyedMap<M> {
+        let mut entries = entries.into_vec();
+        entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+
+        KeyedMap {
+            entries: entries.into_boxed_slice(),
+            rest,
+        }
+    }
+
+    /// Returns the known keys and their value types sorted by key
+    pub fn entries(&self) -> &[(DataStr, Ref<M>)] {
+        &self.entries
+    }
+
+    /// Returns the type of any other keys and their values
+    pub fn rest(&self) -> Option<&Map<M>> {
+        self.rest.as_ref()
+    }
+
+    /// Returns the value type of a known key
+    pub fn get(&self, key: &str) -> Option<&Ref<M>> {
+        self.entries
+            .binary_search_by(|(entry_key, _)| entry_key.as_ref().cmp(key))
+            .ok()
+            .map(|index| &self.entries[index].1)
+    }
+
+    /// Returns the most specific `Map` type containing every value of this type
+    pub fn to_map(&self) -> Map<M> {
+        let rest_keys = self.rest.iter().map(|rest| rest.key().clone());
+        let rest_values = self.rest.iter().map(|rest| rest.value().clone());
+
+        Map::new(
+            unify::unify_ty_ref_iter(
+                self.entries
+                    .iter()
+                    .map(|(key, _)| Ty::LitSym(key.clone()).into())
+                    .chain(rest_keys),
+            ),
+            unify::unify_ty_ref_iter(
+                self.entries
+                    .iter()
+                    .map(|(_, value)| value.clone())
+                    .chain(rest_values),
+            ),
+        )
+    }
+}
+
+impl<M: Pm> From<KeyedMap<M>> for Ty<M> {
+    fn from(keyed_map: KeyedMap<M>) -> Self {
+        Ty::KeyedMap(Box::new(keyed_map))
+    }
+}
+
+impl<M: Pm> From<KeyedMap<M>> for Ref<M> {
+    fn from(keyed_map: KeyedMap<M>) -> Self {
+        Ref::Fixed(Ty::KeyedMap(Box::new(keyed_map)))
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct List<M: Pm> {
     fixed: Box<[Ref<M>]>,
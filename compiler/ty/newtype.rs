@@ -0,0 +1,122 @@
+//! Nominal types sharing the representation of another type
+//!
+//! These are defined by `(defnewtype Meters Float)`. A newtype is distinct from every other type,
+//! including its representation, during type checking. It's erased to its representation when
+//! it's monomorphised so it has no runtime cost.
+
+use std::fmt;
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::Span;
+
+use crate::id_type::ArcId;
+use crate::ty;
+use crate::ty::ty_args::TyArgs;
+
+pub struct Newtype {
+    span: Span,
+    name: DataStr,
+    repr: ty::Ref<ty::Poly>,
+}
+
+impl Newtype {
+    pub fn new(span: Span, name: DataStr, repr: ty::Ref<ty::Poly>) -> NewtypeId {
+        NewtypeId::new(Self { span, name, repr })
+    }
+
+    /// Returns the span where the newtype was defined
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the name of the newtype
+    pub fn name(&self) -> &DataStr {
+        &self.name
+    }
+
+    /// Returns the type the newtype is represented as
+    pub fn repr(&self) -> &ty::Ref<ty::Poly> {
+        &self.repr
+    }
+
+    /// Returns the representation type for comparison with types of the same marker
+    pub fn repr_for_pm<M: ty::Pm>(&self) -> ty::Ref<M> {
+        M::subst_ty_args(&TyArgs::empty(), &self.repr)
+    }
+}
+
+impl fmt::Debug for Newtype {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Newtype")
+            .field("name", &self.name)
+            .field("repr", &self.repr)
+            .finish()
+    }
+}
+
+pub type NewtypeId = ArcId<Newtype>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::source::EMPTY_SPAN;
+    use crate::ty::intersect::intersect_ty_refs;
+    use crate::ty::is_a::ty_ref_is_a;
+    use crate::ty::subst::monomorphise;
+    use crate::ty::subtract::subtract_ty_refs;
+    use crate::ty::Ty;
+
+    fn new_meters() -> ty::Ref<ty::Poly> {
+        Ty::Newtype(Newtype::new(EMPTY_SPAN, "Meters".into(), Ty::Float.into())).into()
+    }
+
+    #[test]
+    fn newtype_is_a() {
+        let meters = new_meters();
+        let other_meters = new_meters();
+        let float: ty::Ref<ty::Poly> = Ty::Float.into();
+
+        assert!(ty_ref_is_a(&meters, &meters));
+        assert!(ty_ref_is_a(&meters, &Ty::Any.into()));
+
+        assert!(!ty_ref_is_a(&meters, &float));
+        assert!(!ty_ref_is_a(&float, &meters));
+
+        // Each definition is distinct even with the same name and representation
+        assert!(!ty_ref_is_a(&meters, &other_meters));
+    }
+
+    #[test]
+    fn newtype_intersect() {
+        let meters = new_meters();
+        let other_meters = new_meters();
+
+        assert_eq!(
+            Ok(meters.clone()),
+            intersect_ty_refs(&meters, &Ty::Num.into())
+        );
+        assert!(intersect_ty_refs(&meters, &Ty::Str.into()).is_err());
+        assert!(intersect_ty_refs(&meters, &other_meters).is_err());
+    }
+
+    #[test]
+    fn newtype_subtract() {
+        let meters = new_meters();
+
+        assert_eq!(
+            ty::Ref::<ty::Poly>::from(Ty::never()),
+            subtract_ty_refs(&meters, &Ty::Num.into())
+        );
+        assert_eq!(meters, subtract_ty_refs(&meters, &Ty::Str.into()));
+    }
+
+    #[test]
+    fn newtype_monomorphise() {
+        let meters_list: ty::Ref<ty::Poly> = ty::List::new_uniform(new_meters()).into();
+        let float_list: ty::Ref<ty::Mono> = ty::List::new_uniform(Ty::Float.into()).into();
+
+        assert_eq!(float_list, monomorphise(&TyArgs::empty(), &meters_list));
+    }
+}
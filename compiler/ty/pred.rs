@@ -22,19 +22,24 @@ pub enum TestTy {
     Nil,
     TopRecord,
     RecordClass(record::ConsId),
+    FloatArray,
+    IntArray,
+    Bytes,
+    Rational,
+    BigInt,
 }
 
 impl TestTy {
     pub fn match_subject_ref<M: ty::Pm>(&self, ty_ref: &ty::Ref<M>) -> Option<bool> {
         let resolved_ty = ty_ref.resolve_to_ty();
         match resolved_ty {
-            Ty::Any => None,
+            Ty::Any | Ty::Dyn => None,
             Ty::Sym | Ty::LitSym(_) => Some(self == &TestTy::Sym),
             Ty::Bool | Ty::LitBool(_) => Some(self == &TestTy::Bool),
             Ty::Char => Some(self == &TestTy::Char),
             Ty::Float => Some(self == &TestTy::Float || self == &TestTy::Num),
-            Ty::Map(_) => Some(self == &TestTy::Map),
-            Ty::Int => Some(self == &TestTy::Int || self == &TestTy::Num),
+            Ty::Map(_) | Ty::KeyedMap(_) => Some(self == &TestTy::Map),
+            Ty::Int | Ty::IntRange(_) => Some(self == &TestTy::Int || self == &TestTy::Num),
             Ty::Num => match self {
                 TestTy::Num => Some(true),
                 TestTy::Int | TestTy::Float => None,
@@ -56,7 +61,17 @@ impl TestTy {
                 TestTy::List => Some(true),
                 _ => Some(false),
             },
+            Ty::Values(members) => match self {
+                TestTy::Nil => Some(members.is_empty()),
+                TestTy::List => Some(true),
+                _ => Some(false),
+            },
             Ty::Vector(_) | Ty::Vectorof(_) => Some(self == &TestTy::Vector),
+            Ty::FloatArray => Some(self == &TestTy::FloatArray),
+            Ty::IntArray => Some(self == &TestTy::IntArray),
+            Ty::Bytes => Some(self == &TestTy::Bytes),
+            Ty::Rational => Some(self == &TestTy::Rational),
+            Ty::BigInt => Some(self == &TestTy::BigInt),
             Ty::TopRecord => match self {
                 TestTy::TopRecord => Some(true),
                 TestTy::RecordClass(_) => None,
@@ -69,9 +84,17 @@ impl TestTy {
             },
             Ty::Record(instance) => match self {
                 TestTy::TopRecord => Some(true),
+                // This may be an instance of any record type with the same fields
+                TestTy::RecordClass(_) if instance.cons().has_rest_fields() => None,
                 TestTy::RecordClass(test_cons) => Some(instance.cons() == test_cons),
                 _ => Some(false),
             },
+            Ty::Recursive(instance) => {
+                ty::recursive::with_unfolded(instance, |unfolded| self.match_subject_ref(unfolded))
+                    .flatten()
+            }
+            // Newtypes are indistinguishable from their representation at runtime
+            Ty::Newtype(newtype) => self.match_subject_ref(newtype.repr()),
             Ty::Union(members) => {
                 let results: Vec<Option<bool>> = members
                     .iter()
@@ -123,10 +146,16 @@ impl TestTy {
             TestTy::Fun => ty::TopFun::new(Purity::Impure.into(), Ty::Any.into()).into(),
             TestTy::Nil => ty::List::empty().into(),
             TestTy::TopRecord => Ty::TopRecord,
+            TestTy::FloatArray => Ty::FloatArray,
+            TestTy::IntArray => Ty::IntArray,
+            TestTy::Bytes => Ty::Bytes,
+            TestTy::Rational => Ty::Rational,
+            TestTy::BigInt => Ty::BigInt,
             TestTy::RecordClass(cons) => {
-                if cons.poly_params().is_empty() {
+                if cons.poly_params().is_empty() && !cons.has_rest_fields() {
                     // There's a single instance of this record; we can return the instance type.
-                    // Instance types can be used in more situations than top types.
+                    // Instance types can be used in more situations than top types. Instance
+                    // types with rest fields also include other record classes.
                     record::Instance::new(cons.clone(), TyArgs::empty()).into()
                 } else {
                     Ty::RecordClass(cons.clone())
@@ -154,6 +183,11 @@ impl fmt::Display for TestTy {
             TestTy::Nil => write!(formatter, "nil?"),
             TestTy::TopRecord => write!(formatter, "record?"),
             TestTy::RecordClass(cons) => write!(formatter, "{}?", cons.value_cons_name()),
+            TestTy::FloatArray => write!(formatter, "float-array?"),
+            TestTy::IntArray => write!(formatter, "int-array?"),
+            TestTy::Bytes => write!(formatter, "bytes?"),
+            TestTy::Rational => write!(formatter, "rational?"),
+            TestTy::BigInt => write!(formatter, "big-int?"),
         }
     }
 }
@@ -401,4 +435,29 @@ mod test {
         assert_test_ty_may_match(&TestTy::Int, str_num_intersect.clone());
         assert_test_ty_wont_match(&TestTy::Sym, str_num_intersect);
     }
+
+    #[test]
+    fn rest_fields_record_class_test_ty() {
+        use crate::ty::ty_args::TyArgs;
+
+        let cons = record::Cons::new_with_rest_fields(
+            EMPTY_SPAN,
+            "cons".into(),
+            "cons?".into(),
+            None,
+            Box::new([]),
+        );
+
+        let test_ty = TestTy::RecordClass(cons.clone());
+
+        let test_class_poly: ty::Ref<ty::Poly> = cons.clone().into();
+        let test_instance_poly: ty::Ref<ty::Poly> =
+            record::Instance::new(cons, TyArgs::empty()).into();
+
+        // Instances may belong to another class with the same fields
+        assert_test_ty_will_match(&test_ty, test_class_poly.clone());
+        assert_test_ty_may_match(&test_ty, test_instance_poly);
+
+        assert_eq!(test_class_poly, test_ty.to_ty().into());
+    }
 }
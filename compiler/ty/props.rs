@@ -6,6 +6,7 @@ use crate::ty::Ty;
 fn ty_has_subtypes<M: ty::Pm>(ty: &Ty<M>) -> bool {
     match ty {
         Ty::Any
+        | Ty::Dyn
         | Ty::Bool
         | Ty::Num
         | Ty::Sym
@@ -19,8 +20,16 @@ fn ty_has_subtypes<M: ty::Pm>(ty: &Ty<M>) -> bool {
         | Ty::LitBool(_)
         | Ty::LitSym(_)
         | Ty::Str
+        | Ty::FloatArray
+        | Ty::IntArray
+        | Ty::Bytes
+        | Ty::Rational
+        | Ty::BigInt
         | Ty::TyPred(_)
-        | Ty::EqPred => false,
+        | Ty::EqPred
+        | Ty::Newtype(_) => false,
+
+        Ty::IntRange(int_range) => int_range.as_singleton().is_none(),
 
         Ty::Fun(fun) => {
             fun.purity() != &Purity::Pure.into()
@@ -29,25 +38,42 @@ fn ty_has_subtypes<M: ty::Pm>(ty: &Ty<M>) -> bool {
                 || has_subtypes(fun.ret())
         }
         Ty::Map(map) => has_subtypes(map.key()) || has_subtypes(map.value()),
+        Ty::KeyedMap(keyed_map) => {
+            // Any rest can contain a varying number of keys
+            keyed_map
+                .rest()
+                .map_or(false, |rest| !rest.key().is_never())
+                || keyed_map
+                    .entries()
+                    .iter()
+                    .any(|(_, value)| has_subtypes(value))
+        }
         Ty::Set(member) => has_subtypes(member.as_ref()),
-        Ty::Vector(members) => members.iter().any(has_subtypes),
+        Ty::Vector(members) | Ty::Values(members) => members.iter().any(has_subtypes),
         Ty::Union(members) => !members.is_empty(),
         Ty::List(list) => {
             // Any arbitrary fixed length list is a subtype of a list with rest
             list.has_rest() || list.fixed().iter().any(has_subtypes)
         }
 
-        // Any record type supporting variance has subtypes
-        Ty::Record(instance) => instance
-            .cons()
-            .poly_params()
-            .iter()
-            .any(|poly_param| poly_param.variance() != Variance::Invariant),
+        // Any record type with rest fields or supporting variance has subtypes
+        Ty::Record(instance) => {
+            instance.cons().has_rest_fields()
+                || instance
+                    .cons()
+                    .poly_params()
+                    .iter()
+                    .any(|poly_param| poly_param.variance() != Variance::Invariant)
+        }
 
         Ty::Vectorof(_) => {
             // Any arbitrary fixed length vector is a subtype of this vector
             true
         }
+        Ty::Recursive(_) => {
+            // Every member of the unfolded type is a subtype
+            true
+        }
         Ty::Intersect(_) => {
             // If we're correctly normalised we should have subtypes
             true
@@ -65,7 +91,8 @@ pub fn has_subtypes<M: ty::Pm>(ty_ref: &ty::Ref<M>) -> bool {
 fn ty_is_literal<M: ty::Pm>(ty: &Ty<M>) -> bool {
     match ty {
         Ty::LitBool(_) | Ty::LitSym(_) => true,
-        Ty::Vector(members) => members.iter().all(is_literal),
+        Ty::IntRange(int_range) => int_range.as_singleton().is_some(),
+        Ty::Vector(members) | Ty::Values(members) => members.iter().all(is_literal),
         Ty::List(list) => !list.has_rest() && list.fixed().iter().all(is_literal),
         _ => false,
     }
@@ -95,6 +95,7 @@ pub struct Cons {
     value_cons_name: DataStr,
     poly_params_list: Option<Box<[PolyParam]>>,
     fields: Box<[Field]>,
+    has_rest_fields: bool,
 }
 
 impl Cons {
@@ -111,6 +112,27 @@ impl Cons {
             value_cons_name,
             poly_params_list,
             fields,
+            has_rest_fields: false,
+        })
+    }
+
+    /// Creates a constructor whose instance types accept records with additional fields
+    ///
+    /// This is declared with a trailing `&rest-fields` in the value constructor's field list.
+    pub fn new_with_rest_fields(
+        span: Span,
+        ty_cons_name: DataStr,
+        value_cons_name: DataStr,
+        poly_params_list: Option<Box<[PolyParam]>>,
+        fields: Box<[Field]>,
+    ) -> ConsId {
+        ConsId::new(Self {
+            span,
+            ty_cons_name,
+            value_cons_name,
+            poly_params_list,
+            fields,
+            has_rest_fields: true,
         })
     }
 
@@ -152,6 +174,21 @@ impl Cons {
         self.fields.as_ref()
     }
 
+    /// Returns true if instances of any record type with at least our fields are our subtypes
+    ///
+    /// The record class remains distinct; only instances of the constructor itself satisfy its
+    /// type predicate.
+    pub fn has_rest_fields(&self) -> bool {
+        self.has_rest_fields
+    }
+
+    /// Returns the index of the field with the passed name
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .position(|field| field.name().as_ref() == name)
+    }
+
     /// Returns an identity map of polymorphic variables associated with the constructor
     pub fn identity_ty_args(&self) -> TyArgs<ty::Poly> {
         use std::collections::HashMap;
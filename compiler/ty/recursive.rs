@@ -0,0 +1,410 @@
+//! User defined type constructors
+//!
+//! These are defined by `(deftype)` with a parameter list such as `(deftype (Pair A) (List A A))`
+//! or by any `(deftype)` that refers to itself such as `(deftype (Tree A) (RawU A (List (Tree
+//! A))))`.
+//!
+//! Non-recursive constructors are transparent; applying them substitutes their arguments directly
+//! in to their definition. Recursive constructors produce [instance types](Instance) that are
+//! iso-recursive: an instance is only related to the type it's defined as by explicitly
+//! [unfolding](with_unfolded) it. Constructors declaring the variance of their parameters such as
+//! `(deftype (Cell :invariant A) (List A))` also produce instances so their arguments are compared
+//! using the declared variance.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::Span;
+
+use crate::id_type::ArcId;
+use crate::ty;
+use crate::ty::ty_args::TyArgs;
+use crate::ty::var_usage::{VarUsages, Variance};
+use crate::ty::Ty;
+
+/// Maximum number of nested unfoldings before comparisons give up
+///
+/// Structurally comparing two distinct recursive types can unfold them indefinitely. This limit
+/// bounds that work; once it's reached the comparison returns its most conservative answer.
+const MAX_UNFOLD_DEPTH: usize = 16;
+
+thread_local! {
+    static UNFOLD_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+struct Definition {
+    ty_ref: ty::Ref<ty::Poly>,
+    is_recursive: bool,
+    param_variances: Box<[Variance]>,
+}
+
+/// Type constructor defined by `(deftype)`
+///
+/// The definition is provided after construction as it may refer to the constructor itself.
+pub struct Cons {
+    span: Span,
+    name: DataStr,
+    params: Box<[ty::TVarId]>,
+    declared_variances: Box<[Option<Variance>]>,
+    definition: RwLock<Option<Arc<Definition>>>,
+}
+
+impl Cons {
+    /// Creates a new type constructor
+    ///
+    /// `declared_variances` contains the explicitly declared variance of each parameter. Any
+    /// parameter without a declared variance has its variance calculated from its definition.
+    pub fn new(
+        span: Span,
+        name: DataStr,
+        params: Box<[ty::TVarId]>,
+        declared_variances: Box<[Option<Variance>]>,
+    ) -> ConsId {
+        ConsId::new(Self {
+            span,
+            name,
+            params,
+            declared_variances,
+            definition: RwLock::new(None),
+        })
+    }
+
+    /// Returns the span where the constructor was defined
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the name of the type constructor
+    pub fn name(&self) -> &DataStr {
+        &self.name
+    }
+
+    /// Returns the type parameters of the constructor
+    pub fn params(&self) -> &[ty::TVarId] {
+        &self.params
+    }
+
+    /// Sets the type the constructor is defined as
+    ///
+    /// This can only be called once. `is_recursive` indicates if `ty_ref` contains instances of
+    /// this constructor.
+    pub fn set_definition(&self, ty_ref: ty::Ref<ty::Poly>, is_recursive: bool) {
+        let mut var_usages = VarUsages::new();
+        var_usages.add_poly_usages(&ty_ref);
+
+        // Unused parameters have no effect on the type so any variance is sound
+        let param_variances = self
+            .params
+            .iter()
+            .zip(self.declared_variances.iter())
+            .map(|(tvar, declared_variance)| {
+                declared_variance
+                    .or_else(|| var_usages.tvar_variance(tvar))
+                    .unwrap_or(Variance::Covariant)
+            })
+            .collect();
+
+        let definition = Definition {
+            ty_ref,
+            is_recursive,
+            param_variances,
+        };
+
+        let mut definition_lock = self.definition.write().unwrap();
+        if definition_lock.is_some() {
+            panic!("type constructor `{}` defined twice", self.name);
+        }
+
+        definition_lock.replace(Arc::new(definition));
+    }
+
+    fn loaded_definition(&self) -> Option<Arc<Definition>> {
+        self.definition.read().unwrap().clone()
+    }
+
+    /// Returns the type the constructor is defined as
+    ///
+    /// This is `None` while the definition is being lowered.
+    pub fn definition(&self) -> Option<ty::Ref<ty::Poly>> {
+        self.loaded_definition()
+            .map(|definition| definition.ty_ref.clone())
+    }
+
+    /// Returns true if the constructor has been defined in terms of itself
+    pub fn is_recursive(&self) -> bool {
+        self.loaded_definition()
+            .map_or(true, |definition| definition.is_recursive)
+    }
+
+    /// Returns true if any parameter has an explicitly declared variance
+    pub fn has_declared_variance(&self) -> bool {
+        self.declared_variances.iter().any(Option::is_some)
+    }
+
+    /// Returns the variance of the parameter at `param_index`
+    ///
+    /// While the definition is being lowered every parameter without a declared variance is
+    /// assumed to be covariant. Any self-reference using a parameter in a different position will
+    /// then calculate the parameter as invariant.
+    pub fn param_variance(&self, param_index: usize) -> Variance {
+        match self.loaded_definition() {
+            Some(definition) => definition.param_variances[param_index],
+            None => self.declared_variances[param_index].unwrap_or(Variance::Covariant),
+        }
+    }
+
+    /// Substitutes the passed type arguments in to the constructor's definition
+    ///
+    /// This returns `None` while the definition is being lowered.
+    pub fn apply<M: ty::Pm>(&self, ty_args: &[ty::Ref<M>]) -> Option<ty::Ref<M>> {
+        let definition = self.definition()?;
+
+        let tvar_types = self
+            .params
+            .iter()
+            .cloned()
+            .zip(ty_args.iter().cloned())
+            .collect();
+
+        Some(M::subst_ty_args(
+            &TyArgs::new(HashMap::new(), tvar_types),
+            &definition,
+        ))
+    }
+}
+
+impl fmt::Debug for Cons {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Our definition can contain ourselves; only print our name to avoid recursing forever
+        formatter
+            .debug_struct("Cons")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+pub type ConsId = ArcId<Cons>;
+
+/// Instance of a recursive type constructor
+#[derive(PartialEq, Debug, Clone)]
+pub struct Instance<M: ty::Pm> {
+    cons: ConsId,
+    ty_args: Box<[ty::Ref<M>]>,
+}
+
+impl<M: ty::Pm> Instance<M> {
+    pub fn new(cons: ConsId, ty_args: Box<[ty::Ref<M>]>) -> Self {
+        Self { cons, ty_args }
+    }
+
+    /// Returns the type constructor this instance was constructed from
+    pub fn cons(&self) -> &ConsId {
+        &self.cons
+    }
+
+    /// Returns the type arguments for each of the constructor's parameters
+    pub fn ty_args(&self) -> &[ty::Ref<M>] {
+        &self.ty_args
+    }
+}
+
+impl<M: ty::Pm> From<Instance<M>> for Ty<M> {
+    fn from(instance: Instance<M>) -> Self {
+        Ty::Recursive(Box::new(instance))
+    }
+}
+
+impl<M: ty::Pm> From<Instance<M>> for ty::Ref<M> {
+    fn from(instance: Instance<M>) -> Self {
+        ty::Ref::Fixed(Ty::Recursive(Box::new(instance)))
+    }
+}
+
+struct UnfoldDepthGuard;
+
+impl Drop for UnfoldDepthGuard {
+    fn drop(&mut self) {
+        UNFOLD_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Unfolds an instance once and passes the resulting type to `f`
+///
+/// This returns `None` if the unfolding depth limit has been reached or the instance's constructor
+/// is still being defined.
+pub fn with_unfolded<M, F, T>(instance: &Instance<M>, f: F) -> Option<T>
+where
+    M: ty::Pm,
+    F: FnOnce(&ty::Ref<M>) -> T,
+{
+    let unfolded = instance.cons.apply(&instance.ty_args)?;
+
+    let within_limit = UNFOLD_DEPTH.with(|depth| {
+        if depth.get() >= MAX_UNFOLD_DEPTH {
+            false
+        } else {
+            depth.set(depth.get() + 1);
+            true
+        }
+    });
+
+    if !within_limit {
+        return None;
+    }
+
+    let _guard = UnfoldDepthGuard;
+    Some(f(&unfolded))
+}
+
+/// Returns true if a recursive definition only refers to itself inside another type constructor
+///
+/// Definitions such as `(deftype Loop (RawU Int Loop))` have no meaningful unfolding.
+pub fn is_guarded(cons: &ConsId, ty_ref: &ty::Ref<ty::Poly>) -> bool {
+    match ty_ref {
+        ty::Ref::Fixed(Ty::Union(members)) | ty::Ref::Fixed(Ty::Intersect(members)) => {
+            members.iter().all(|member| is_guarded(cons, member))
+        }
+        ty::Ref::Fixed(Ty::Recursive(instance)) => instance.cons() != cons,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::source::EMPTY_SPAN;
+    use crate::ty::intersect::intersect_ty_refs;
+    use crate::ty::is_a::ty_ref_is_a;
+    use crate::ty::subtract::subtract_ty_refs;
+
+    /// Builds `(deftype (Tree A) (RawU A (List (Tree A))))`
+    fn tree_cons() -> ConsId {
+        let tvar = ty::TVar::new(EMPTY_SPAN, "A".into(), Ty::Any.into());
+        let cons = Cons::new(
+            EMPTY_SPAN,
+            "Tree".into(),
+            Box::new([tvar.clone()]),
+            Box::new([None]),
+        );
+
+        let self_instance: ty::Ref<ty::Poly> =
+            Instance::new(cons.clone(), Box::new([tvar.clone().into()])).into();
+
+        let definition: ty::Ref<ty::Poly> = Ty::Union(Box::new([
+            tvar.into(),
+            ty::List::new_uniform(self_instance).into(),
+        ]))
+        .into();
+
+        cons.set_definition(definition, true);
+        cons
+    }
+
+    fn tree_of(cons: &ConsId, member: Ty<ty::Poly>) -> ty::Ref<ty::Poly> {
+        Instance::new(cons.clone(), Box::new([member.into()])).into()
+    }
+
+    #[test]
+    fn unfold_instance() {
+        let cons = tree_cons();
+        assert_eq!(Variance::Covariant, cons.param_variance(0));
+
+        let int_instance = Instance::<ty::Poly>::new(cons.clone(), Box::new([Ty::Int.into()]));
+        let int_tree: ty::Ref<ty::Poly> = int_instance.clone().into();
+
+        let expected: ty::Ref<ty::Poly> = Ty::Union(Box::new([
+            Ty::Int.into(),
+            ty::List::new_uniform(int_tree).into(),
+        ]))
+        .into();
+
+        assert_eq!(
+            Some(expected),
+            with_unfolded(&int_instance, |unfolded| unfolded.clone())
+        );
+    }
+
+    #[test]
+    fn instance_is_a() {
+        let cons = tree_cons();
+        let int_tree = tree_of(&cons, Ty::Int);
+        let num_tree = tree_of(&cons, Ty::Num);
+
+        let int: ty::Ref<ty::Poly> = Ty::Int.into();
+        let int_list: ty::Ref<ty::Poly> = ty::List::new_uniform(int.clone()).into();
+        let nested_int_list: ty::Ref<ty::Poly> =
+            ty::List::new_tuple(Box::new([int_list.clone(), int.clone()])).into();
+
+        assert!(ty_ref_is_a(&int, &int_tree));
+        assert!(ty_ref_is_a(&int_list, &int_tree));
+        assert!(ty_ref_is_a(&nested_int_list, &int_tree));
+        assert!(!ty_ref_is_a(&Ty::Str.into(), &int_tree));
+
+        assert!(ty_ref_is_a(&int_tree, &num_tree));
+        assert!(!ty_ref_is_a(&num_tree, &int_tree));
+
+        let int_or_any_list: ty::Ref<ty::Poly> = Ty::Union(Box::new([
+            int.clone(),
+            ty::List::new_uniform(Ty::Any.into()).into(),
+        ]))
+        .into();
+
+        assert!(ty_ref_is_a(&int_tree, &int_or_any_list));
+        assert!(!ty_ref_is_a(&int_tree, &int_list));
+    }
+
+    #[test]
+    fn declared_variance_is_a() {
+        let tvar = ty::TVar::new(EMPTY_SPAN, "A".into(), Ty::Any.into());
+        let cons = Cons::new(
+            EMPTY_SPAN,
+            "Cell".into(),
+            Box::new([tvar.clone()]),
+            Box::new([Some(Variance::Invariant)]),
+        );
+        cons.set_definition(ty::List::new_uniform(tvar.into()).into(), false);
+
+        let int_cell = tree_of(&cons, Ty::Int);
+        let num_cell = tree_of(&cons, Ty::Num);
+
+        assert!(ty_ref_is_a(&int_cell, &int_cell));
+        assert!(!ty_ref_is_a(&int_cell, &num_cell));
+        assert!(!ty_ref_is_a(&num_cell, &int_cell));
+    }
+
+    #[test]
+    fn instance_intersect() {
+        let cons = tree_cons();
+        let int_tree = tree_of(&cons, Ty::Int);
+        let num_tree = tree_of(&cons, Ty::Num);
+
+        assert_eq!(
+            Ok(int_tree.clone()),
+            intersect_ty_refs(&int_tree, &num_tree)
+        );
+        assert_eq!(
+            Ok(Ty::Int.into()),
+            intersect_ty_refs(&int_tree, &Ty::Num.into())
+        );
+        assert!(intersect_ty_refs(&int_tree, &Ty::Str.into()).is_err());
+    }
+
+    #[test]
+    fn instance_subtract() {
+        let cons = tree_cons();
+        let int_tree = tree_of(&cons, Ty::Int);
+        let any_list: ty::Ref<ty::Poly> = ty::List::new_uniform(Ty::Any.into()).into();
+
+        assert_eq!(
+            ty::Ref::<ty::Poly>::from(Ty::Int),
+            subtract_ty_refs(&int_tree, &any_list)
+        );
+
+        // Subtracting an unrelated type should leave the instance intact
+        assert_eq!(int_tree, subtract_ty_refs(&int_tree, &Ty::Str.into()));
+    }
+}
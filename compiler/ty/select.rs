@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
+use arret_syntax::span::Span;
+
 use crate::ty;
 use crate::ty::list_iter::ListIterator;
 use crate::ty::purity;
 use crate::ty::purity::Purity;
 use crate::ty::record;
+use crate::ty::recursive;
 use crate::ty::ty_args::TyArgs;
 use crate::ty::Ty;
 
@@ -13,6 +16,27 @@ pub enum Error<'vars> {
     UnselectedTVar(&'vars ty::TVarId),
 }
 
+/// Value providing evidence for the type of a type variable
+#[derive(PartialEq, Clone, Debug)]
+pub struct TVarOrigin {
+    pub span: Span,
+    pub evidence: ty::Ref<ty::Poly>,
+}
+
+/// Evidence for each type variable along with where it came from
+pub type TVarOrigins = HashMap<ty::TVarId, Vec<TVarOrigin>>;
+
+/// Replaces top-level integer ranges with `Int`
+fn widen_int_ranges(poly: &ty::Ref<ty::Poly>) -> ty::Ref<ty::Poly> {
+    match poly {
+        ty::Ref::Fixed(Ty::IntRange(_)) => Ty::Int.into(),
+        ty::Ref::Fixed(Ty::Union(members)) => {
+            ty::unify::unify_ty_ref_iter(members.iter().map(widen_int_ranges))
+        }
+        _ => poly.clone(),
+    }
+}
+
 /// Selects a set of polymorphic variables for a function application
 ///
 /// This context is constructed with a set of purity and type variables the applied function is
@@ -26,6 +50,9 @@ pub struct SelectCtx<'vars> {
 
     pvar_purities: HashMap<purity::PVarId, purity::Ref>,
     tvar_types: HashMap<ty::TVarId, ty::Ref<ty::Poly>>,
+
+    evidence_span: Option<Span>,
+    tvar_origins: TVarOrigins,
 }
 
 impl<'vars> SelectCtx<'vars> {
@@ -38,6 +65,8 @@ impl<'vars> SelectCtx<'vars> {
             selecting_tvars,
             pvar_purities: HashMap::with_capacity(selecting_pvars.len()),
             tvar_types: HashMap::with_capacity(selecting_tvars.len()),
+            evidence_span: None,
+            tvar_origins: HashMap::new(),
         }
     }
 
@@ -71,6 +100,10 @@ impl<'vars> SelectCtx<'vars> {
         evidence_instance: &record::Instance<ty::Poly>,
     ) {
         if target_instance.cons() != evidence_instance.cons() {
+            if target_instance.cons().has_rest_fields() {
+                self.add_evidence_record_fields(target_instance, evidence_instance);
+            }
+
             return;
         }
 
@@ -85,6 +118,31 @@ impl<'vars> SelectCtx<'vars> {
         }
     }
 
+    /// Adds evidence from the fields of a record instance used through rest fields
+    fn add_evidence_record_fields(
+        &mut self,
+        target_instance: &record::Instance<ty::Poly>,
+        evidence_instance: &record::Instance<ty::Poly>,
+    ) {
+        use crate::ty::Pm;
+
+        for target_field in target_instance.cons().fields() {
+            let evidence_field_index =
+                if let Some(index) = evidence_instance.cons().field_index(target_field.name()) {
+                    index
+                } else {
+                    continue;
+                };
+
+            let evidence_field = &evidence_instance.cons().fields()[evidence_field_index];
+
+            self.add_evidence(
+                &ty::Poly::subst_ty_args(target_instance.ty_args(), target_field.ty_ref()),
+                &ty::Poly::subst_ty_args(evidence_instance.ty_args(), evidence_field.ty_ref()),
+            );
+        }
+    }
+
     fn add_evidence_list(
         &mut self,
         target_list: &ty::List<ty::Poly>,
@@ -121,13 +179,18 @@ impl<'vars> SelectCtx<'vars> {
                 self.add_evidence(target_map.key(), &Ty::never().into());
                 self.add_evidence(target_map.value(), &Ty::never().into());
             }
+            Ty::KeyedMap(target_keyed) => {
+                for (_, target_value) in target_keyed.entries() {
+                    self.add_evidence(target_value, &Ty::never().into());
+                }
+            }
             Ty::List(target_list) => {
                 for target_fixed in target_list.fixed() {
                     self.add_evidence(target_fixed, &Ty::never().into());
                 }
                 self.add_evidence(target_list.rest(), &Ty::never().into());
             }
-            Ty::Vector(target_members) => {
+            Ty::Vector(target_members) | Ty::Values(target_members) => {
                 for target_member in target_members.iter() {
                     self.add_evidence(target_member, &Ty::never().into());
                 }
@@ -151,10 +214,27 @@ impl<'vars> SelectCtx<'vars> {
                 self.add_evidence(target_map.key(), evidence_map.key());
                 self.add_evidence(target_map.value(), evidence_map.value());
             }
+            (Ty::Map(target_map), Ty::KeyedMap(evidence_keyed)) => {
+                let evidence_map = evidence_keyed.to_map();
+                self.add_evidence(target_map.key(), evidence_map.key());
+                self.add_evidence(target_map.value(), evidence_map.value());
+            }
+            (Ty::KeyedMap(target_keyed), Ty::KeyedMap(evidence_keyed)) => {
+                for (key, target_value) in target_keyed.entries() {
+                    if let Some(evidence_value) = evidence_keyed.get(key) {
+                        self.add_evidence(target_value, evidence_value);
+                    }
+                }
+            }
             (Ty::List(target_list), Ty::List(evidence_list)) => {
                 self.add_evidence_list(target_list, evidence_list);
             }
-            (Ty::Vector(target_members), Ty::Vector(evidence_members)) => {
+            (Ty::List(target_list), Ty::Values(evidence_members)) => {
+                let evidence_list = ty::List::new_tuple(evidence_members.clone());
+                self.add_evidence_list(target_list, &evidence_list);
+            }
+            (Ty::Vector(target_members), Ty::Vector(evidence_members))
+            | (Ty::Values(target_members), Ty::Values(evidence_members)) => {
                 for (target_member, evidence_member) in
                     target_members.iter().zip(evidence_members.iter())
                 {
@@ -187,6 +267,27 @@ impl<'vars> SelectCtx<'vars> {
             (Ty::Record(target_instance), Ty::Record(evidence_instance)) => {
                 self.add_evidence_record(target_instance, evidence_instance)
             }
+            (Ty::Recursive(target_instance), Ty::Recursive(evidence_instance))
+                if target_instance.cons() == evidence_instance.cons() =>
+            {
+                for (target_arg, evidence_arg) in target_instance
+                    .ty_args()
+                    .iter()
+                    .zip(evidence_instance.ty_args().iter())
+                {
+                    self.add_evidence(target_arg, evidence_arg);
+                }
+            }
+            (Ty::Recursive(target_instance), _) => {
+                recursive::with_unfolded(target_instance, |target_unfolded| {
+                    self.add_evidence(target_unfolded, evidence_poly)
+                });
+            }
+            (_, Ty::Recursive(evidence_instance)) => {
+                recursive::with_unfolded(evidence_instance, |evidence_unfolded| {
+                    self.add_evidence(target_poly, evidence_unfolded)
+                });
+            }
             (Ty::Union(target_members), _) => {
                 for target_member in target_members.iter() {
                     self.add_evidence(target_member, evidence_poly);
@@ -212,6 +313,25 @@ impl<'vars> SelectCtx<'vars> {
             return;
         }
 
+        if let Some(span) = self.evidence_span {
+            self.tvar_origins
+                .entry(tvar.clone())
+                .or_default()
+                .push(TVarOrigin {
+                    span,
+                    evidence: evidence_poly.clone(),
+                });
+        }
+
+        // Functions such as `(+)` return their type variable for a computed value. Selecting an
+        // integer range would claim the result is inside the range of the arguments.
+        let widened_poly = widen_int_ranges(evidence_poly);
+        let evidence_poly = if ty::is_a::ty_ref_is_a(&widened_poly, tvar.bound()) {
+            &widened_poly
+        } else {
+            evidence_poly
+        };
+
         self.tvar_types
             .entry(tvar.clone())
             .and_modify(|existing| {
@@ -234,6 +354,31 @@ impl<'vars> SelectCtx<'vars> {
         }
     }
 
+    /// Adds evidence from the value at `span`
+    ///
+    /// Any evidence for a type variable remembers the span so conflicting evidence can be
+    /// explained. Values without a span are added like `add_evidence`.
+    pub fn add_evidence_at(
+        &mut self,
+        span: Option<Span>,
+        target_poly: &ty::Ref<ty::Poly>,
+        evidence_poly: &ty::Ref<ty::Poly>,
+    ) {
+        self.evidence_span = span;
+        self.add_evidence(target_poly, evidence_poly);
+        self.evidence_span = None;
+    }
+
+    /// Returns the evidence added for type variables through `add_evidence_at`
+    pub fn tvar_origins(&self) -> &TVarOrigins {
+        &self.tvar_origins
+    }
+
+    /// Takes the evidence added for type variables through `add_evidence_at`
+    pub fn take_tvar_origins(&mut self) -> TVarOrigins {
+        std::mem::take(&mut self.tvar_origins)
+    }
+
     pub fn add_evidence_purity(
         &mut self,
         target_purity: &purity::Ref,
@@ -468,6 +613,28 @@ mod test {
         assert_selected_type(&stx, &poly_a, &scope.poly_for_str("Bool"));
     }
 
+    #[test]
+    fn values_types() {
+        let scope = TestScope::new("A B");
+        let poly_a = scope.poly_for_str("A");
+        let poly_b = scope.poly_for_str("B");
+
+        let mut stx = scope.select_ctx();
+        stx.add_evidence(
+            &scope.poly_for_str("(Values A B)"),
+            &scope.poly_for_str("(Values Int Sym)"),
+        );
+        assert_selected_type(&stx, &poly_a, &scope.poly_for_str("Int"));
+        assert_selected_type(&stx, &poly_b, &scope.poly_for_str("Sym"));
+
+        let mut stx = scope.select_ctx();
+        stx.add_evidence(
+            &scope.poly_for_str("(List & A)"),
+            &scope.poly_for_str("(Values true false)"),
+        );
+        assert_selected_type(&stx, &poly_a, &scope.poly_for_str("Bool"));
+    }
+
     #[test]
     fn map_types() {
         let scope = TestScope::new("A B");
@@ -1,6 +1,7 @@
 use crate::ty;
 use crate::ty::purity;
 use crate::ty::record;
+use crate::ty::recursive;
 use crate::ty::ty_args::TyArgs;
 use crate::ty::Ty;
 
@@ -21,6 +22,13 @@ where
     )
 }
 
+fn subst_map<S>(stx: &S, map: &ty::Map<S::InputPM>) -> ty::Map<S::OutputPM>
+where
+    S: Substitution,
+{
+    ty::Map::new(stx.subst_ty_ref(map.key()), stx.subst_ty_ref(map.value()))
+}
+
 fn subst_record_instance<S>(
     stx: &S,
     instance: &record::Instance<S::InputPM>,
@@ -66,10 +74,31 @@ where
 {
     let poly_stx = stx.as_poly_subst();
 
-    // TODO: This doesn't seem right
+    // Any variables we're substituting are being instantiated. The remaining variables are still
+    // bound by the function itself, such as in the type of a higher-rank parameter.
+    let pvars = fun
+        .pvars()
+        .iter()
+        .filter(|pvar| {
+            let pvar_ref = purity::Ref::Var((*pvar).clone());
+            poly_stx.subst_purity_ref(&pvar_ref) == pvar_ref
+        })
+        .cloned()
+        .collect();
+
+    let tvars = fun
+        .tvars()
+        .iter()
+        .filter(|tvar| {
+            let tvar_ref: ty::Ref<ty::Poly> = (*tvar).clone().into();
+            poly_stx.subst_ty_ref(&tvar_ref) == tvar_ref
+        })
+        .cloned()
+        .collect();
+
     ty::Fun::new(
-        purity::PVars::new(),
-        ty::TVars::new(),
+        pvars,
+        tvars,
         subst_top_fun(stx, fun.top_fun()),
         subst_list(poly_stx, fun.params()),
     )
@@ -81,12 +110,18 @@ where
 {
     match ty {
         Ty::Any => Ty::Any,
+        Ty::Dyn => Ty::Dyn,
         Ty::Bool => Ty::Bool,
         Ty::Char => Ty::Char,
         Ty::Float => Ty::Float,
         Ty::Int => Ty::Int,
         Ty::Num => Ty::Num,
         Ty::Str => Ty::Str,
+        Ty::FloatArray => Ty::FloatArray,
+        Ty::IntArray => Ty::IntArray,
+        Ty::Bytes => Ty::Bytes,
+        Ty::Rational => Ty::Rational,
+        Ty::BigInt => Ty::BigInt,
         Ty::Sym => Ty::Sym,
         Ty::EqPred => Ty::EqPred,
         Ty::TopRecord => Ty::TopRecord,
@@ -94,19 +129,34 @@ where
         Ty::TyPred(test_ty) => Ty::TyPred(test_ty.clone()),
         Ty::TopFun(top_fun) => subst_top_fun(stx, top_fun).into(),
         Ty::Fun(fun) => subst_fun(stx, fun).into(),
-        Ty::Map(map) => {
-            ty::Map::new(stx.subst_ty_ref(map.key()), stx.subst_ty_ref(map.value())).into()
-        }
+        Ty::Map(map) => subst_map(stx, map).into(),
+        Ty::KeyedMap(keyed_map) => ty::KeyedMap::new(
+            keyed_map
+                .entries()
+                .iter()
+                .map(|(key, value)| (key.clone(), stx.subst_ty_ref(value)))
+                .collect(),
+            keyed_map.rest().map(|rest| subst_map(stx, rest)),
+        )
+        .into(),
         Ty::LitBool(val) => Ty::LitBool(*val),
         Ty::LitSym(val) => Ty::LitSym(val.clone()),
+        Ty::IntRange(int_range) => Ty::IntRange(*int_range),
         Ty::Set(member) => Ty::Set(Box::new(stx.subst_ty_ref(member))),
         Ty::Union(members) => Ty::Union(subst_ty_ref_slice(stx, members)),
         Ty::Intersect(members) => Ty::Intersect(subst_ty_ref_slice(stx, members)),
         Ty::Vector(members) => Ty::Vector(subst_ty_ref_slice(stx, members)),
+        Ty::Values(members) => Ty::Values(subst_ty_ref_slice(stx, members)),
         Ty::Vectorof(member) => Ty::Vectorof(Box::new(stx.subst_ty_ref(member))),
         Ty::List(list) => subst_list(stx, list).into(),
         Ty::RecordClass(cons) => Ty::RecordClass(cons.clone()),
         Ty::Record(instance) => Ty::Record(Box::new(subst_record_instance(stx, instance))),
+        Ty::Recursive(instance) => recursive::Instance::new(
+            instance.cons().clone(),
+            subst_ty_ref_slice(stx, instance.ty_args()),
+        )
+        .into(),
+        Ty::Newtype(newtype) => Ty::Newtype(newtype.clone()),
     }
 }
 
@@ -244,6 +294,8 @@ impl<'tyargs> Substitution for Monomorphise<'tyargs> {
 
     fn subst_ty_ref(&self, poly: &ty::Ref<ty::Poly>) -> ty::Ref<ty::Mono> {
         match poly {
+            // Newtypes have no runtime representation of their own
+            ty::Ref::Fixed(Ty::Newtype(newtype)) => self.subst_ty_ref(newtype.repr()),
             ty::Ref::Fixed(fixed) => subst_ty(self, fixed).into(),
             ty::Ref::Var(tvar, _) => self
                 .mono_ty_args
@@ -288,6 +340,7 @@ impl<'tyargs> Substitution for PartialMonomorphise<'tyargs> {
 
     fn subst_ty_ref(&self, poly: &ty::Ref<ty::Poly>) -> ty::Ref<ty::Poly> {
         match poly {
+            ty::Ref::Fixed(Ty::Newtype(newtype)) => self.subst_ty_ref(newtype.repr()),
             ty::Ref::Fixed(fixed) => subst_ty(self, fixed).into(),
             ty::Ref::Var(tvar, _) => {
                 if let Some(mono) = self.mono_ty_args.tvar_types().get(tvar) {
@@ -11,6 +11,35 @@ where
     )
 }
 
+fn subtract_int_ranges<M: ty::Pm>(
+    minuend_range: &ty::IntRange,
+    subtrahend_range: &ty::IntRange,
+) -> ty::Ref<M> {
+    if minuend_range.intersect(subtrahend_range).is_none() {
+        return Ty::IntRange(*minuend_range).into();
+    }
+
+    let below = if minuend_range.start() < subtrahend_range.start() {
+        Some(ty::IntRange::new(
+            minuend_range.start(),
+            subtrahend_range.start() - 1,
+        ))
+    } else {
+        None
+    };
+
+    let above = if subtrahend_range.end() < minuend_range.end() {
+        Some(ty::IntRange::new(
+            subtrahend_range.end() + 1,
+            minuend_range.end(),
+        ))
+    } else {
+        None
+    };
+
+    ty::unify::unify_ty_ref_iter(below.into_iter().chain(above).map(Into::into))
+}
+
 fn subtract_tys<M: ty::Pm>(
     minuend_ty: &Ty<M>,
     subtrahend_ref: &ty::Ref<M>,
@@ -34,6 +63,30 @@ fn subtract_tys<M: ty::Pm>(
             subtrahend_ref,
         ),
         (Ty::Union(members), _) => subtract_ref_iters(members.iter(), subtrahend_ref),
+        (Ty::IntRange(minuend_range), Ty::IntRange(subtrahend_range)) => {
+            subtract_int_ranges(minuend_range, subtrahend_range)
+        }
+        (Ty::Newtype(newtype), _) => {
+            // Newtype values pass every runtime test their representation passes
+            if ty::is_a::ty_ref_is_a(&newtype.repr_for_pm(), subtrahend_ref) {
+                Ty::never().into()
+            } else {
+                minuend_ty.clone().into()
+            }
+        }
+        (Ty::Recursive(instance), _) => {
+            ty::recursive::with_unfolded(instance, |unfolded| {
+                let remaining = subtract_ty_refs(unfolded, subtrahend_ref);
+
+                if ty::is_a::ty_refs_equivalent(&remaining, unfolded) {
+                    // Keep our original name if nothing was subtracted
+                    minuend_ty.clone().into()
+                } else {
+                    remaining
+                }
+            })
+            .unwrap_or_else(|| minuend_ty.clone().into())
+        }
         (Ty::List(minuend_list), Ty::List(subtrahend_list))
             // Make sure this is even useful or else we can recurse splitting list types
             // indefinitely
@@ -135,6 +188,23 @@ mod test {
         assert_subtraction("Int", "Num", "Float");
     }
 
+    #[test]
+    fn int_range_subtraction() {
+        assert_subtraction("(IntRange 1 9)", "(IntRange 0 9)", "0");
+        assert_subtraction("(IntRange 0 8)", "(IntRange 0 9)", "9");
+        assert_subtraction(
+            "(RawU (IntRange 0 4) (IntRange 6 9))",
+            "(IntRange 0 9)",
+            "5",
+        );
+        assert_subtraction("(IntRange 3 4)", "(IntRange 0 4)", "(IntRange -5 2)");
+        assert_subtraction("(RawU)", "(IntRange 0 9)", "Int");
+        assert_subtraction("(IntRange 0 9)", "(IntRange 0 9)", "(IntRange 10 20)");
+
+        // We can't represent an integer type with a hole in it
+        assert_subtraction("Int", "Int", "0");
+    }
+
     #[test]
     fn union_subtraction() {
         assert_subtraction("Sym", "(RawU Sym Str)", "Str");
@@ -15,6 +15,7 @@ use crate::ty;
 use crate::ty::purity;
 use crate::ty::purity::Purity;
 use crate::ty::record;
+use crate::ty::recursive;
 use crate::ty::ty_args::TyArgs;
 use crate::ty::var_usage::Variance;
 use crate::ty::Ty;
@@ -127,6 +128,43 @@ fn unify_fun<M: ty::Pm>(fun1: &ty::Fun, fun2: &ty::Fun) -> UnifiedTy<M> {
     }
 }
 
+fn unify_maps<M: ty::Pm>(map1: &ty::Map<M>, map2: &ty::Map<M>) -> ty::Map<M> {
+    ty::Map::new(
+        unify_to_ty_ref(map1.key(), map2.key()),
+        unify_to_ty_ref(map1.value(), map2.value()),
+    )
+}
+
+/// Unifies two keyed map types
+///
+/// Maps with the same known keys keep their keys. Otherwise they're widened to a plain `Map`.
+fn unify_keyed_maps<M: ty::Pm>(keyed1: &ty::KeyedMap<M>, keyed2: &ty::KeyedMap<M>) -> ty::Ref<M> {
+    let same_keys = keyed1.entries().len() == keyed2.entries().len()
+        && keyed1
+            .entries()
+            .iter()
+            .zip(keyed2.entries().iter())
+            .all(|((key1, _), (key2, _))| key1 == key2);
+
+    if !same_keys || keyed1.rest().is_some() != keyed2.rest().is_some() {
+        return unify_maps(&keyed1.to_map(), &keyed2.to_map()).into();
+    }
+
+    let entries = keyed1
+        .entries()
+        .iter()
+        .zip(keyed2.entries().iter())
+        .map(|((key, value1), (_, value2))| (key.clone(), unify_to_ty_ref(value1, value2)))
+        .collect();
+
+    let rest = keyed1
+        .rest()
+        .zip(keyed2.rest())
+        .map(|(rest1, rest2)| unify_maps(rest1, rest2));
+
+    ty::KeyedMap::new(entries, rest).into()
+}
+
 fn unify_record_field_purities<M: ty::Pm>(
     variance: Variance,
     pvar: &purity::PVarId,
@@ -157,13 +195,22 @@ fn unify_record_field_ty_refs<M: ty::Pm>(
     tvar: &ty::TVarId,
     ty_args1: &TyArgs<M>,
     ty_args2: &TyArgs<M>,
+) -> UnifiedTy<M> {
+    unify_variant_ty_refs(
+        variance,
+        &ty_args1.tvar_types()[tvar],
+        &ty_args2.tvar_types()[tvar],
+    )
+}
+
+fn unify_variant_ty_refs<M: ty::Pm>(
+    variance: Variance,
+    ty_ref1: &ty::Ref<M>,
+    ty_ref2: &ty::Ref<M>,
 ) -> UnifiedTy<M> {
     use crate::ty::intersect::intersect_ty_refs;
     use crate::ty::is_a::ty_refs_equivalent;
 
-    let ty_ref1 = &ty_args1.tvar_types()[tvar];
-    let ty_ref2 = &ty_args2.tvar_types()[tvar];
-
     match variance {
         Variance::Covariant => unify_ty_refs(ty_ref1, ty_ref2),
         Variance::Contravariant => match intersect_ty_refs(ty_ref1, ty_ref2) {
@@ -235,6 +282,32 @@ fn unify_record_instance<M: ty::Pm>(
     )
 }
 
+fn unify_recursive_instances<M: ty::Pm>(
+    instance1: &recursive::Instance<M>,
+    instance2: &recursive::Instance<M>,
+) -> UnifiedTy<M> {
+    let mut merged_ty_args = Vec::with_capacity(instance1.ty_args().len());
+
+    for (param_index, (ty_arg1, ty_arg2)) in instance1
+        .ty_args()
+        .iter()
+        .zip(instance2.ty_args().iter())
+        .enumerate()
+    {
+        let variance = instance1.cons().param_variance(param_index);
+
+        match unify_variant_ty_refs(variance, ty_arg1, ty_arg2) {
+            UnifiedTy::Merged(merged) => merged_ty_args.push(merged),
+            UnifiedTy::Discerned => return UnifiedTy::Discerned,
+        }
+    }
+
+    UnifiedTy::Merged(
+        recursive::Instance::new(instance1.cons().clone(), merged_ty_args.into_boxed_slice())
+            .into(),
+    )
+}
+
 fn unify_ty<M: ty::Pm>(
     ref1: &ty::Ref<M>,
     ty1: &Ty<M>,
@@ -247,12 +320,25 @@ fn unify_ty<M: ty::Pm>(
     match (ty1, ty2) {
         // Handle supertype relationships
         (_, Ty::Any) | (Ty::Any, _) => UnifiedTy::Merged(Ty::Any.into()),
+        (_, Ty::Dyn) | (Ty::Dyn, _) => UnifiedTy::Merged(Ty::Dyn.into()),
         (Ty::LitSym(_), Ty::Sym) | (Ty::Sym, Ty::LitSym(_)) => UnifiedTy::Merged(Ty::Sym.into()),
         (Ty::LitBool(_), Ty::Bool) | (Ty::Bool, Ty::LitBool(_)) => {
             UnifiedTy::Merged(Ty::Bool.into())
         }
         (Ty::Float, Ty::Num) | (Ty::Num, Ty::Float) => UnifiedTy::Merged(Ty::Num.into()),
         (Ty::Int, Ty::Num) | (Ty::Num, Ty::Int) => UnifiedTy::Merged(Ty::Num.into()),
+        (Ty::IntRange(_), Ty::Int) | (Ty::Int, Ty::IntRange(_)) => {
+            UnifiedTy::Merged(Ty::Int.into())
+        }
+        (Ty::IntRange(_), Ty::Num) | (Ty::Num, Ty::IntRange(_)) => {
+            UnifiedTy::Merged(Ty::Num.into())
+        }
+
+        // Merge overlapping or adjacent integer ranges
+        (Ty::IntRange(range1), Ty::IntRange(range2)) => match range1.try_merge(range2) {
+            Some(merged_range) => UnifiedTy::Merged(merged_range.into()),
+            None => UnifiedTy::Discerned,
+        },
 
         // Simplify (U true false) => Bool
         (Ty::LitBool(true), Ty::LitBool(false)) | (Ty::LitBool(false), Ty::LitBool(true)) => {
@@ -269,11 +355,12 @@ fn unify_ty<M: ty::Pm>(
         }
 
         // Map type
-        (Ty::Map(map1), Ty::Map(map2)) => {
-            let unified_key_ref = unify_to_ty_ref(map1.key(), map2.key());
-            let unified_val_ref = unify_to_ty_ref(map1.value(), map2.value());
-
-            UnifiedTy::Merged(ty::Map::new(unified_key_ref, unified_val_ref).into())
+        (Ty::Map(map1), Ty::Map(map2)) => UnifiedTy::Merged(unify_maps(map1, map2).into()),
+        (Ty::KeyedMap(keyed1), Ty::KeyedMap(keyed2)) => {
+            UnifiedTy::Merged(unify_keyed_maps(keyed1, keyed2))
+        }
+        (Ty::KeyedMap(keyed), Ty::Map(map)) | (Ty::Map(map), Ty::KeyedMap(keyed)) => {
+            UnifiedTy::Merged(unify_maps(&keyed.to_map(), map).into())
         }
 
         // Vector types
@@ -342,13 +429,57 @@ fn unify_ty<M: ty::Pm>(
             UnifiedTy::Merged(new_union)
         }
 
+        // Recursive types
+        (Ty::Recursive(instance1), Ty::Recursive(instance2))
+            if instance1.cons() == instance2.cons() =>
+        {
+            unify_recursive_instances(instance1, instance2)
+        }
+        (Ty::Recursive(_), _) if ty::is_a::ty_ref_is_a(ref2, ref1) => {
+            UnifiedTy::Merged(ref1.clone())
+        }
+        (_, Ty::Recursive(_)) if ty::is_a::ty_ref_is_a(ref1, ref2) => {
+            UnifiedTy::Merged(ref2.clone())
+        }
+
         // List types
         (Ty::List(list1), Ty::List(list2)) => match unify_list(list1, list2) {
             UnifiedList::Discerned => UnifiedTy::Discerned,
             UnifiedList::Merged(merged_list) => UnifiedTy::Merged(merged_list.into()),
         },
 
+        // Multiple value types
+        (Ty::Values(members1), Ty::Values(members2)) => {
+            if members1.len() != members2.len() {
+                UnifiedTy::Discerned
+            } else {
+                let unified_members = members1
+                    .iter()
+                    .zip(members2.iter())
+                    .map(|(member1, member2)| unify_to_ty_ref(member1, member2))
+                    .collect();
+
+                UnifiedTy::Merged(Ty::Values(unified_members).into())
+            }
+        }
+        (Ty::Values(_), Ty::List(_)) if ty::is_a::ty_ref_is_a(ref1, ref2) => {
+            UnifiedTy::Merged(ref2.clone())
+        }
+        (Ty::List(_), Ty::Values(_)) if ty::is_a::ty_ref_is_a(ref2, ref1) => {
+            UnifiedTy::Merged(ref1.clone())
+        }
+
         // Record types
+        (Ty::Record(instance1), Ty::Record(instance2)) if instance1.cons() != instance2.cons() => {
+            // Instances of different constructors can only merge through rest fields
+            if ty::is_a::ty_ref_is_a(ref1, ref2) {
+                UnifiedTy::Merged(ref2.clone())
+            } else if ty::is_a::ty_ref_is_a(ref2, ref1) {
+                UnifiedTy::Merged(ref1.clone())
+            } else {
+                UnifiedTy::Discerned
+            }
+        }
         (Ty::Record(instance1), Ty::Record(instance2)) => {
             unify_record_instance(instance1, instance2)
         }
@@ -466,6 +597,18 @@ mod test {
         assert_discerned("Str", "Sym");
     }
 
+    #[test]
+    fn int_range_types() {
+        assert_merged("(IntRange 1 2)", "1", "2");
+        assert_merged("(IntRange 0 9)", "(IntRange 0 5)", "(IntRange 3 9)");
+        assert_merged("(IntRange 0 9)", "(IntRange 0 5)", "(IntRange 6 9)");
+        assert_merged("Int", "(IntRange 0 9)", "Int");
+        assert_merged("Num", "1", "Num");
+        assert_discerned("1", "3");
+
+        assert_merged_iter("(IntRange 1 3)", &["1", "3", "2"]);
+    }
+
     #[test]
     fn two_sym_types() {
         assert_discerned("'foo", "'bar");
@@ -535,6 +678,20 @@ mod test {
         );
     }
 
+    #[test]
+    fn keyed_map_types() {
+        assert_merged(
+            "(KeyedMap :name Str :age Int)",
+            "(KeyedMap :name Str :age 1)",
+            "(KeyedMap :name Str :age Int)",
+        );
+        assert_merged(
+            "(Map (RawU :name :age) (RawU Str Int))",
+            "(KeyedMap :name Str)",
+            "(KeyedMap :age Int)",
+        );
+    }
+
     #[test]
     fn union_types() {
         assert_merged("(RawU 'foo 'bar 'baz)", "(RawU 'foo 'bar)", "'baz");
@@ -599,6 +756,18 @@ mod test {
         assert_merged("(List & Sym)", "(List)", "(List Sym & Sym)");
     }
 
+    #[test]
+    fn values_types() {
+        assert_merged(
+            "(Values Bool Int)",
+            "(Values true Int)",
+            "(Values false Int)",
+        );
+        assert_discerned("(Values Int)", "(Values Int Int)");
+        assert_merged("(List & Num)", "(Values Int Float)", "(List & Num)");
+        assert_discerned("(Values Int Int)", "(List Int)");
+    }
+
     #[test]
     fn vec_types() {
         assert_merged("(Vectorof Bool)", "(Vector true)", "(Vectorof false)");
@@ -783,4 +952,58 @@ mod test {
             unify_ty_refs(&int_false_instance2_poly, &float_bool_instance2_poly)
         );
     }
+
+    #[test]
+    fn rest_fields_record_instances() {
+        use crate::ty::ty_args::TyArgs;
+
+        let named_cons = record::Cons::new_with_rest_fields(
+            EMPTY_SPAN,
+            "Named".into(),
+            "named".into(),
+            None,
+            Box::new([record::Field::new(
+                EMPTY_SPAN,
+                "name".into(),
+                Ty::Str.into(),
+            )]),
+        );
+        let person_cons = record::Cons::new(
+            EMPTY_SPAN,
+            "Person".into(),
+            "person".into(),
+            None,
+            Box::new([
+                record::Field::new(EMPTY_SPAN, "age".into(), Ty::Int.into()),
+                record::Field::new(EMPTY_SPAN, "name".into(), Ty::Str.into()),
+            ]),
+        );
+        let pet_cons = record::Cons::new(
+            EMPTY_SPAN,
+            "Pet".into(),
+            "pet".into(),
+            None,
+            Box::new([record::Field::new(
+                EMPTY_SPAN,
+                "name".into(),
+                Ty::Str.into(),
+            )]),
+        );
+
+        let named: ty::Ref<ty::Poly> = record::Instance::new(named_cons, TyArgs::empty()).into();
+        let person: ty::Ref<ty::Poly> = record::Instance::new(person_cons, TyArgs::empty()).into();
+        let pet: ty::Ref<ty::Poly> = record::Instance::new(pet_cons, TyArgs::empty()).into();
+
+        assert_eq!(
+            UnifiedTy::Merged(named.clone()),
+            unify_ty_refs(&person, &named)
+        );
+        assert_eq!(
+            UnifiedTy::Merged(named.clone()),
+            unify_ty_refs(&named, &pet)
+        );
+
+        // Neither of these has rest fields
+        assert_eq!(UnifiedTy::Discerned, unify_ty_refs(&person, &pet));
+    }
 }
@@ -1,7 +1,7 @@
 //! Calculates the usage of a polymorphic variable in type
 
 use std::collections::HashMap;
-use std::ops;
+use std::{fmt, ops};
 
 use crate::ty;
 use crate::ty::purity;
@@ -59,6 +59,16 @@ impl ops::BitAndAssign for Variance {
     }
 }
 
+impl fmt::Display for Variance {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(match self {
+            Variance::Covariant => "covariant",
+            Variance::Contravariant => "contravariant",
+            Variance::Invariant => "invariant",
+        })
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct VarUsages {
     pvar_variances: HashMap<purity::PVarId, Variance>,
@@ -80,19 +90,27 @@ fn visit_top_fun(var_usages: &mut VarUsages, polarity: Variance, top_fun: &ty::T
 fn visit_ty(var_usages: &mut VarUsages, polarity: Variance, poly_ty: &Ty<ty::Poly>) {
     match poly_ty {
         Ty::Any
+        | Ty::Dyn
         | Ty::Bool
         | Ty::Char
         | Ty::LitBool(_)
         | Ty::Sym
         | Ty::LitSym(_)
+        | Ty::IntRange(_)
         | Ty::Int
         | Ty::Float
         | Ty::Num
         | Ty::Str
+        | Ty::FloatArray
+        | Ty::IntArray
+        | Ty::Bytes
+        | Ty::Rational
+        | Ty::BigInt
         | Ty::TyPred(_)
         | Ty::EqPred
         | Ty::TopRecord
-        | Ty::RecordClass(_) => {
+        | Ty::RecordClass(_)
+        | Ty::Newtype(_) => {
             // Terminal type
         }
 
@@ -105,11 +123,22 @@ fn visit_ty(var_usages: &mut VarUsages, polarity: Variance, poly_ty: &Ty<ty::Pol
             visit_poly(var_usages, polarity, map.value());
         }
 
+        Ty::KeyedMap(keyed_map) => {
+            for (_, value) in keyed_map.entries() {
+                visit_poly(var_usages, polarity, value);
+            }
+
+            if let Some(rest) = keyed_map.rest() {
+                visit_poly(var_usages, polarity, rest.key());
+                visit_poly(var_usages, polarity, rest.value());
+            }
+        }
+
         Ty::Set(member) | Ty::Vectorof(member) => {
             visit_poly(var_usages, polarity, member);
         }
 
-        Ty::Union(members) | Ty::Intersect(members) | Ty::Vector(members) => {
+        Ty::Union(members) | Ty::Intersect(members) | Ty::Vector(members) | Ty::Values(members) => {
             for member in members.iter() {
                 visit_poly(var_usages, polarity, member);
             }
@@ -141,6 +170,13 @@ fn visit_ty(var_usages: &mut VarUsages, polarity: Variance, poly_ty: &Ty<ty::Pol
                 }
             }
         }
+
+        Ty::Recursive(instance) => {
+            for (param_index, ty_arg) in instance.ty_args().iter().enumerate() {
+                let variance = instance.cons().param_variance(param_index);
+                visit_poly(var_usages, polarity * variance, ty_arg);
+            }
+        }
     }
 }
 
@@ -188,6 +224,16 @@ impl VarUsages {
     pub fn tvar_variance(&self, tvar: &ty::TVarId) -> Option<Variance> {
         self.tvar_variances.get(tvar).copied()
     }
+
+    /// Returns every purity variable that was used
+    pub fn pvars(&self) -> impl Iterator<Item = &purity::PVarId> {
+        self.pvar_variances.keys()
+    }
+
+    /// Returns every type variable that was used
+    pub fn tvars(&self) -> impl Iterator<Item = &ty::TVarId> {
+        self.tvar_variances.keys()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,378 @@
+//! Machine-readable summaries of a program's definitions
+//!
+//! This allows external tools such as editor plugins and documentation generators to inspect the
+//! inferred types of a program without hosting the compiler.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::{FileId, Span};
+
+use crate::context::{CompileCtx, Module, ModuleId};
+use crate::hir;
+use crate::hir::destruc;
+use crate::hir::scope::Binding;
+use crate::source::{LineColRange, SourceFile, SourceLoader};
+use crate::trace::write_json_str;
+use crate::ty;
+use crate::ty::Ty;
+use crate::InferredProgram;
+
+/// Fixed and rest parameters of a function definition
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TypeMapArity {
+    pub fixed: usize,
+    pub rest: bool,
+}
+
+/// Inferred information about a single definition
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeMapEntry {
+    pub name: DataStr,
+    pub filename: Option<String>,
+    pub line_col_range: Option<LineColRange>,
+
+    /// Rendered type of the definition
+    pub type_str: String,
+
+    /// Rendered purity if the definition is a function
+    pub purity_str: Option<String>,
+    pub arity: Option<TypeMapArity>,
+    pub doc: Option<DataStr>,
+
+    /// Indicates if the definition is exported from its module
+    pub exported: bool,
+}
+
+/// Definitions of a program and all of its imported modules
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeMap {
+    entries: Vec<TypeMapEntry>,
+}
+
+fn new_entry(
+    source_loader: &SourceLoader,
+    span: Span,
+    name: &DataStr,
+    ty_ref: &ty::Ref<ty::Poly>,
+    doc: &Option<DataStr>,
+    exported: bool,
+) -> TypeMapEntry {
+    let fun_type = match ty_ref.try_to_fixed() {
+        Some(Ty::Fun(fun_type)) => Some(fun_type),
+        _ => None,
+    };
+
+    TypeMapEntry {
+        name: name.clone(),
+        filename: source_loader.span_to_filename(span),
+        line_col_range: source_loader.span_to_line_col_range(span),
+        type_str: hir::str_for_ty_ref(ty_ref),
+        purity_str: fun_type.map(|fun_type| hir::str_for_purity(fun_type.purity())),
+        arity: fun_type.map(|fun_type| TypeMapArity {
+            fixed: fun_type.params().fixed().len(),
+            rest: fun_type.params().has_rest(),
+        }),
+        doc: doc.clone(),
+        exported,
+    }
+}
+
+/// Returns if the passed local is exported from its module
+fn is_exported(module: &Module, local_id: hir::LocalId) -> bool {
+    module.exports.values().any(|binding| match binding {
+        Binding::Var(None, exported_local_id) => *exported_local_id == local_id,
+        Binding::Var(Some(module_id), exported_local_id) => {
+            *module_id == module.module_id && *exported_local_id == local_id
+        }
+        _ => false,
+    })
+}
+
+fn visit_destruc(
+    source_loader: &SourceLoader,
+    module: &Module,
+    def: &hir::Def<hir::Inferred>,
+    destruc: &destruc::Destruc<hir::Inferred>,
+    entries: &mut Vec<TypeMapEntry>,
+) {
+    match destruc {
+        destruc::Destruc::Scalar(span, scalar) => {
+            if let Some(local_id) = scalar.local_id() {
+                entries.push(new_entry(
+                    source_loader,
+                    *span,
+                    scalar.source_name(),
+                    scalar.ty(),
+                    &def.doc,
+                    is_exported(module, *local_id),
+                ));
+            }
+        }
+        destruc::Destruc::List(_, list) => {
+            for fixed in list.fixed() {
+                visit_destruc(source_loader, module, def, fixed, entries);
+            }
+
+            if let Some(rest) = list.rest() {
+                if let Some(local_id) = rest.local_id() {
+                    entries.push(new_entry(
+                        source_loader,
+                        def.span,
+                        rest.source_name(),
+                        rest.ty(),
+                        &def.doc,
+                        is_exported(module, *local_id),
+                    ));
+                }
+            }
+        }
+        destruc::Destruc::Vector(_, vector) => {
+            for member in vector.members() {
+                visit_destruc(source_loader, module, def, member, entries);
+            }
+        }
+        destruc::Destruc::Map(_, map) => {
+            for (_, value) in map.entries() {
+                visit_destruc(source_loader, module, def, value, entries);
+            }
+        }
+    }
+}
+
+fn visit_module_tree(
+    source_loader: &SourceLoader,
+    visited_modules: &mut HashSet<ModuleId>,
+    module: &Module,
+    entries: &mut Vec<TypeMapEntry>,
+) {
+    if !visited_modules.insert(module.module_id) {
+        return;
+    }
+
+    for import in module.imports.values() {
+        visit_module_tree(source_loader, visited_modules, import, entries);
+    }
+
+    for def in module.defs.iter() {
+        visit_destruc(source_loader, module, def, &def.destruc, entries);
+    }
+}
+
+/// Sorts entries by their location
+///
+/// Modules are imported and definitions are inferred in an unspecified order; this makes our
+/// output stable.
+fn sort_entries(entries: &mut [TypeMapEntry]) {
+    entries.sort_by(|a, b| {
+        (&a.filename, a.line_col_range.map(|range| range.start))
+            .cmp(&(&b.filename, b.line_col_range.map(|range| range.start)))
+    });
+}
+
+/// Builds a type map for a source file and every module it imports
+///
+/// Unlike compiling a program this doesn't require a `main!` function.
+pub fn program_type_map(
+    ccx: &CompileCtx,
+    source_file: &SourceFile,
+) -> Result<TypeMap, Vec<Diagnostic<FileId>>> {
+    let program = crate::infer_program(ccx, source_file)?;
+    let entry_module = program.entry_module();
+    let source_loader = ccx.source_loader();
+
+    let mut entries = vec![];
+    visit_module_tree(
+        source_loader,
+        &mut HashSet::new(),
+        entry_module,
+        &mut entries,
+    );
+
+    sort_entries(&mut entries);
+    Ok(TypeMap { entries })
+}
+
+/// Writes the type of every definition in a program's entry module
+///
+/// Each definition is written on its own line as `[name Type]` in source order. Definitions with
+/// a declared type are written with that type; the rest are written with their inferred type.
+/// Imported modules aren't included.
+pub fn write_program_def_types(
+    ccx: &CompileCtx,
+    program: &InferredProgram,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let entry_module = program.entry_module();
+
+    let mut entries = vec![];
+    for def in entry_module.defs.iter() {
+        visit_destruc(
+            ccx.source_loader(),
+            entry_module,
+            def,
+            &def.destruc,
+            &mut entries,
+        );
+    }
+
+    sort_entries(&mut entries);
+
+    for entry in entries {
+        writeln!(writer, "[{} {}]", entry.name, entry.type_str)?;
+    }
+
+    Ok(())
+}
+
+fn write_json_opt_str(writer: &mut impl Write, value: Option<&str>) -> io::Result<()> {
+    match value {
+        Some(value) => write_json_str(writer, value),
+        None => write!(writer, "null"),
+    }
+}
+
+impl TypeMap {
+    pub fn entries(&self) -> &[TypeMapEntry] {
+        &self.entries
+    }
+
+    /// Writes the type map as JSON
+    ///
+    /// Lines and columns are one-based. Fields that don't apply to a definition are `null`.
+    pub fn write_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        write!(writer, "{{\"definitions\":[")?;
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+
+            write!(writer, "{{\"name\":")?;
+            write_json_str(writer, &entry.name)?;
+            write!(writer, ",\"file\":")?;
+            write_json_opt_str(writer, entry.filename.as_deref())?;
+
+            match entry.line_col_range {
+                Some(LineColRange { start, end }) => write!(
+                    writer,
+                    ",\"start\":{{\"line\":{},\"column\":{}}},\"end\":{{\"line\":{},\"column\":{}}}",
+                    start.line + 1,
+                    start.column + 1,
+                    end.line + 1,
+                    end.column + 1
+                )?,
+                None => write!(writer, ",\"start\":null,\"end\":null")?,
+            }
+
+            write!(writer, ",\"type\":")?;
+            write_json_str(writer, &entry.type_str)?;
+            write!(writer, ",\"purity\":")?;
+            write_json_opt_str(writer, entry.purity_str.as_deref())?;
+
+            match entry.arity {
+                Some(TypeMapArity { fixed, rest }) => write!(
+                    writer,
+                    ",\"arity\":{{\"fixed\":{},\"rest\":{}}}",
+                    fixed, rest
+                )?,
+                None => write!(writer, ",\"arity\":null")?,
+            }
+
+            write!(writer, ",\"doc\":")?;
+            write_json_opt_str(writer, entry.doc.as_deref())?;
+            write!(writer, ",\"exported\":{}}}", entry.exported)?;
+        }
+
+        writeln!(writer, "]}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::hir::PackagePaths;
+    use crate::opt_level::OptLevel;
+    use crate::source::LineCol;
+
+    #[test]
+    fn json_output() {
+        let type_map = TypeMap {
+            entries: vec![
+                TypeMapEntry {
+                    name: "square".into(),
+                    filename: Some("math.arret".to_owned()),
+                    line_col_range: Some(LineColRange {
+                        start: LineCol { line: 0, column: 6 },
+                        end: LineCol {
+                            line: 0,
+                            column: 12,
+                        },
+                    }),
+                    type_str: "(Int -> Int)".to_owned(),
+                    purity_str: Some("->".to_owned()),
+                    arity: Some(TypeMapArity {
+                        fixed: 1,
+                        rest: false,
+                    }),
+                    doc: Some("Returns \"x\" squared".into()),
+                    exported: true,
+                },
+                TypeMapEntry {
+                    name: "one".into(),
+                    filename: None,
+                    line_col_range: None,
+                    type_str: "Int".to_owned(),
+                    purity_str: None,
+                    arity: None,
+                    doc: None,
+                    exported: false,
+                },
+            ],
+        };
+
+        let mut output = vec![];
+        type_map.write_json(&mut output).unwrap();
+
+        assert_eq!(
+            "{\"definitions\":[{\"name\":\"square\",\"file\":\"math.arret\",\
+             \"start\":{\"line\":1,\"column\":7},\"end\":{\"line\":1,\"column\":13},\
+             \"type\":\"(Int -> Int)\",\"purity\":\"->\",\"arity\":{\"fixed\":1,\"rest\":false},\
+             \"doc\":\"Returns \\\"x\\\" squared\",\"exported\":true},\
+             {\"name\":\"one\",\"file\":null,\"start\":null,\"end\":null,\"type\":\"Int\",\
+             \"purity\":null,\"arity\":null,\"doc\":null,\"exported\":false}]}\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn def_types_output() {
+        let ccx = CompileCtx::new(PackagePaths::test_paths(None), OptLevel::default());
+        let source_file = ccx.source_loader().load_string(
+            "types.arret".into(),
+            "(import [arret internal primitives] [arret internal types])\n\
+             (def [declared Int] 1)\n\
+             (def inferred 'foo)\n\
+             (def (first second) '(1.0 2.0))\n\
+             (def identity (fn #{T} ([x T]) -> T x))",
+        );
+
+        let program = crate::infer_program(&ccx, &source_file).unwrap();
+
+        let mut output = vec![];
+        write_program_def_types(&ccx, &program, &mut output).unwrap();
+
+        assert_eq!(
+            "[declared Int]\n\
+             [inferred 'foo]\n\
+             [first Float]\n\
+             [second Float]\n\
+             [identity (All #{T} T -> T)]\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
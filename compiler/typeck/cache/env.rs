@@ -0,0 +1,463 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::hash;
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::{FileId, Span};
+
+use crate::context::ModuleId;
+use crate::hir;
+use crate::hir::exports::Exports;
+use crate::hir::scope::Binding;
+use crate::hir::visitor::visit_exprs;
+use crate::source::{SourceLoader, SourceText};
+use crate::ty;
+use crate::ty::purity;
+use crate::ty::record;
+use crate::ty::recursive;
+use crate::ty::Ty;
+use crate::typeck::cache::{CacheKey, KeyHasher};
+
+/// Name and span identifying a nominal type or polymorphic variable
+pub(super) type NameSpanKey = (DataStr, Option<FileId>, u32, u32);
+
+pub(super) fn name_span_key(name: &str, span: Span) -> NameSpanKey {
+    (name.into(), span.file_id(), span.start(), span.end())
+}
+
+/// Objects of one kind indexed by their name and span
+///
+/// Distinct objects sharing the same name and span can't be told apart. Their key is kept without
+/// an object so nothing referring to them is restored.
+pub(super) struct NominalMap<T> {
+    entries: HashMap<NameSpanKey, Option<T>>,
+    visited: HashSet<T>,
+}
+
+impl<T> NominalMap<T>
+where
+    T: Clone + Eq + hash::Hash,
+{
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Adds an object to the map
+    ///
+    /// This returns true if the object hadn't been visited before.
+    fn insert(&mut self, key: NameSpanKey, value: &T) -> bool {
+        if !self.visited.insert(value.clone()) {
+            return false;
+        }
+
+        match self.entries.entry(key) {
+            Entry::Vacant(vacant) => {
+                vacant.insert(Some(value.clone()));
+            }
+            Entry::Occupied(mut occupied) => {
+                occupied.insert(None);
+            }
+        }
+
+        true
+    }
+
+    /// Returns the object indexed by a key
+    ///
+    /// This returns `Some(None)` if multiple objects share the key.
+    pub(super) fn get(&self, key: &NameSpanKey) -> Option<Option<&T>> {
+        self.entries.get(key).map(Option::as_ref)
+    }
+}
+
+/// Module and source files a cached module can refer to
+///
+/// Inferred HIR refers to nominal types, polymorphic variables, source files and other modules by
+/// identity. These are recreated whenever a module is lowered so a cache entry instead refers to
+/// them by a key that's stable between compilations. Every object with an identity must be found
+/// in the environment by its key when the entry is restored.
+///
+/// Polymorphic variables introduced by type inference aren't part of the environment. They're
+/// stored in full and recreated when an entry is restored.
+pub struct CacheEnv {
+    source_files: Vec<(OsString, SourceText)>,
+    source_keys: HashMap<FileId, CacheKey>,
+
+    module_keys: HashMap<ModuleId, CacheKey>,
+    key_modules: HashMap<CacheKey, Option<ModuleId>>,
+
+    pub(super) tvars: NominalMap<ty::TVarId>,
+    pub(super) pvars: NominalMap<purity::PVarId>,
+    pub(super) record_conses: NominalMap<record::ConsId>,
+    pub(super) recursive_conses: NominalMap<recursive::ConsId>,
+    pub(super) newtypes: NominalMap<ty::newtype::NewtypeId>,
+}
+
+impl CacheEnv {
+    /// Creates an environment containing the source files loaded so far
+    pub fn new(source_loader: &SourceLoader) -> Self {
+        Self {
+            source_files: source_loader.loaded_files(),
+            source_keys: HashMap::new(),
+
+            module_keys: HashMap::new(),
+            key_modules: HashMap::new(),
+
+            tvars: NominalMap::new(),
+            pvars: NominalMap::new(),
+            record_conses: NominalMap::new(),
+            recursive_conses: NominalMap::new(),
+            newtypes: NominalMap::new(),
+        }
+    }
+
+    /// Adds an imported module identified by its typeck cache key
+    pub fn add_imported_module(
+        &mut self,
+        module_id: ModuleId,
+        key: CacheKey,
+        exports: &Exports,
+        inferred_locals: &HashMap<hir::LocalId, ty::Ref<ty::Poly>>,
+    ) {
+        self.module_keys.insert(module_id, key);
+
+        match self.key_modules.entry(key) {
+            Entry::Vacant(vacant) => {
+                vacant.insert(Some(module_id));
+            }
+            Entry::Occupied(mut occupied) => {
+                if occupied.get() != &Some(module_id) {
+                    occupied.insert(None);
+                }
+            }
+        }
+
+        self.add_exports(exports);
+        for ty_ref in inferred_locals.values() {
+            self.add_ty_ref(ty_ref);
+        }
+    }
+
+    /// Adds the definitions and exports of the module being cached
+    pub fn add_lowered_module(&mut self, defs: &[hir::Def<hir::Lowered>], exports: &Exports) {
+        for def in defs {
+            self.add_lowered_destruc(&def.destruc);
+            visit_exprs(&def.value_expr, &mut |expr| self.add_lowered_expr(expr));
+        }
+
+        self.add_exports(exports);
+    }
+
+    pub(super) fn module_key(&self, module_id: ModuleId) -> Option<CacheKey> {
+        self.module_keys.get(&module_id).cloned()
+    }
+
+    pub(super) fn module_for_key(&self, key: CacheKey) -> Option<ModuleId> {
+        self.key_modules.get(&key).cloned().flatten()
+    }
+
+    /// Returns the name of a source file and a key for its contents
+    pub(super) fn source_file_key(&mut self, file_id: FileId) -> Option<(OsString, CacheKey)> {
+        let (filename, source) = self.source_files.get(file_id.get() as usize - 1)?;
+
+        let source_key = *self.source_keys.entry(file_id).or_insert_with(|| {
+            let mut key_hasher = KeyHasher::new();
+            key_hasher.write_str(source.as_ref());
+            key_hasher.finish()
+        });
+
+        Some((filename.clone(), source_key))
+    }
+
+    /// Finds a loaded source file with the passed name and contents
+    pub(super) fn find_source_file(&mut self, filename: &str, key: CacheKey) -> Option<FileId> {
+        let candidate_ids: Vec<FileId> = self
+            .source_files
+            .iter()
+            .enumerate()
+            .filter(|(_, (candidate_filename, _))| candidate_filename.to_str() == Some(filename))
+            .map(|(index, _)| FileId::new(index as u32 + 1).unwrap())
+            .collect();
+
+        candidate_ids.into_iter().find(|file_id| {
+            self.source_file_key(*file_id)
+                .map(|(_, candidate_key)| candidate_key)
+                == Some(key)
+        })
+    }
+
+    fn add_exports(&mut self, exports: &Exports) {
+        for binding in exports.values() {
+            match binding {
+                Binding::Ty(ty_ref) => self.add_ty_ref(ty_ref),
+                Binding::TyPred(test_ty) => self.add_test_ty(test_ty),
+                Binding::RecordValueCons(cons)
+                | Binding::RecordTyCons(cons)
+                | Binding::FieldAccessor(cons, _) => self.add_record_cons(cons),
+                Binding::UserTyCons(cons) => self.add_recursive_cons(cons),
+                Binding::NewtypeWrap(newtype) | Binding::NewtypeUnwrap(newtype) => {
+                    self.add_newtype(newtype)
+                }
+                Binding::Purity(purity) => self.add_purity(purity),
+                Binding::Var(_, _)
+                | Binding::Prim(_)
+                | Binding::Macro(_, _)
+                | Binding::TyCons(_)
+                | Binding::EqPred
+                | Binding::SyntaxParam(_) => {}
+            }
+        }
+    }
+
+    fn add_decl_ty(&mut self, decl_ty: &hir::DeclTy) {
+        if let hir::DeclTy::Known(ty_ref) = decl_ty {
+            self.add_ty_ref(ty_ref);
+        }
+    }
+
+    fn add_lowered_destruc(&mut self, destruc: &hir::destruc::Destruc<hir::Lowered>) {
+        use crate::hir::destruc::Destruc;
+
+        match destruc {
+            Destruc::Scalar(_, scalar) => self.add_decl_ty(scalar.ty()),
+            Destruc::List(_, list) => self.add_lowered_list_destruc(list),
+            Destruc::Vector(_, vector) => {
+                for member in vector.members() {
+                    self.add_lowered_destruc(member);
+                }
+            }
+            Destruc::Map(_, map) => {
+                for (_, value_destruc) in map.entries() {
+                    self.add_lowered_destruc(value_destruc);
+                }
+            }
+        }
+    }
+
+    fn add_lowered_list_destruc(&mut self, list: &hir::destruc::List<hir::Lowered>) {
+        for fixed in list.fixed() {
+            self.add_lowered_destruc(fixed);
+        }
+
+        if let Some(rest) = list.rest() {
+            self.add_decl_ty(rest.ty());
+        }
+    }
+
+    fn add_lowered_expr(&mut self, expr: &hir::Expr<hir::Lowered>) {
+        use crate::hir::ExprKind;
+
+        match &expr.kind {
+            ExprKind::Fun(fun) => {
+                for pvar in &fun.pvars {
+                    self.add_pvar(pvar);
+                }
+                for tvar in &fun.tvars {
+                    self.add_tvar(tvar);
+                }
+                if let hir::DeclPurity::Known(purity) = &fun.purity {
+                    self.add_purity(purity);
+                }
+
+                self.add_lowered_list_destruc(&fun.params);
+                self.add_decl_ty(&fun.ret_ty);
+            }
+            ExprKind::Let(hir_let) => self.add_lowered_destruc(&hir_let.destruc),
+            ExprKind::Letrec(letrec) => {
+                for binding in &letrec.bindings {
+                    self.add_decl_ty(binding.destruc.ty());
+                }
+            }
+            ExprKind::Unbox(unbox) => self.add_ty_ref(&unbox.ty_ref),
+            ExprKind::DynCheck(dyn_check) => self.add_ty_ref(&dyn_check.ty_ref),
+            ExprKind::Cast(cast) => self.add_ty_ref(&cast.ty_ref),
+            ExprKind::TyPred(_, test_ty) => self.add_test_ty(test_ty),
+            ExprKind::RecordCons(_, cons) => self.add_record_cons(cons),
+            ExprKind::FieldAccessor(field_accessor) => {
+                self.add_record_cons(&field_accessor.record_cons)
+            }
+            _ => {}
+        }
+    }
+
+    fn add_tvar(&mut self, tvar: &ty::TVarId) {
+        if self
+            .tvars
+            .insert(name_span_key(tvar.source_name(), tvar.span()), tvar)
+        {
+            for bound in tvar.bounds() {
+                self.add_ty_ref(bound);
+            }
+        }
+    }
+
+    fn add_pvar(&mut self, pvar: &purity::PVarId) {
+        self.pvars
+            .insert(name_span_key(pvar.source_name(), pvar.span()), pvar);
+    }
+
+    fn add_purity(&mut self, purity: &purity::Ref) {
+        if let purity::Ref::Var(pvar) = purity {
+            self.add_pvar(pvar);
+        }
+    }
+
+    fn add_record_cons(&mut self, cons: &record::ConsId) {
+        if !self
+            .record_conses
+            .insert(name_span_key(cons.ty_cons_name(), cons.span()), cons)
+        {
+            return;
+        }
+
+        for poly_param in cons.poly_params() {
+            match poly_param {
+                record::PolyParam::PVar(_, pvar) => self.add_pvar(pvar),
+                record::PolyParam::TVar(_, tvar) => self.add_tvar(tvar),
+                record::PolyParam::TFixed(_, ty_ref) => self.add_ty_ref(ty_ref),
+                record::PolyParam::Pure(_) => {}
+            }
+        }
+
+        for field in cons.fields() {
+            self.add_ty_ref(field.ty_ref());
+        }
+    }
+
+    fn add_recursive_cons(&mut self, cons: &recursive::ConsId) {
+        if !self
+            .recursive_conses
+            .insert(name_span_key(cons.name(), cons.span()), cons)
+        {
+            return;
+        }
+
+        for param in cons.params() {
+            self.add_tvar(param);
+        }
+
+        if let Some(definition) = cons.definition() {
+            self.add_ty_ref(&definition);
+        }
+    }
+
+    fn add_newtype(&mut self, newtype: &ty::newtype::NewtypeId) {
+        if self
+            .newtypes
+            .insert(name_span_key(newtype.name(), newtype.span()), newtype)
+        {
+            self.add_ty_ref(newtype.repr());
+        }
+    }
+
+    fn add_test_ty(&mut self, test_ty: &ty::pred::TestTy) {
+        if let ty::pred::TestTy::RecordClass(cons) = test_ty {
+            self.add_record_cons(cons);
+        }
+    }
+
+    fn add_ty_args(&mut self, ty_args: &ty::ty_args::TyArgs<ty::Poly>) {
+        for (pvar, purity) in ty_args.pvar_purities() {
+            self.add_pvar(pvar);
+            self.add_purity(purity);
+        }
+
+        for (tvar, ty_ref) in ty_args.tvar_types() {
+            self.add_tvar(tvar);
+            self.add_ty_ref(ty_ref);
+        }
+    }
+
+    fn add_list(&mut self, list: &ty::List<ty::Poly>) {
+        for fixed in list.fixed() {
+            self.add_ty_ref(fixed);
+        }
+        self.add_ty_ref(list.rest());
+    }
+
+    fn add_ty_ref(&mut self, ty_ref: &ty::Ref<ty::Poly>) {
+        match ty_ref {
+            ty::Ref::Var(tvar, _) => self.add_tvar(tvar),
+            ty::Ref::Fixed(ty) => self.add_ty(ty),
+        }
+    }
+
+    fn add_ty(&mut self, ty: &Ty<ty::Poly>) {
+        match ty {
+            Ty::Map(map) => {
+                self.add_ty_ref(map.key());
+                self.add_ty_ref(map.value());
+            }
+            Ty::KeyedMap(keyed_map) => {
+                for (_, value) in keyed_map.entries() {
+                    self.add_ty_ref(value);
+                }
+                if let Some(rest) = keyed_map.rest() {
+                    self.add_ty_ref(rest.key());
+                    self.add_ty_ref(rest.value());
+                }
+            }
+            Ty::Set(member) | Ty::Vectorof(member) => self.add_ty_ref(member),
+            Ty::Union(members)
+            | Ty::Intersect(members)
+            | Ty::Vector(members)
+            | Ty::Values(members) => {
+                for member in members.iter() {
+                    self.add_ty_ref(member);
+                }
+            }
+            Ty::TopFun(top_fun) => {
+                self.add_purity(top_fun.purity());
+                self.add_ty_ref(top_fun.ret());
+            }
+            Ty::Fun(fun) => {
+                for pvar in fun.pvars() {
+                    self.add_pvar(pvar);
+                }
+                for tvar in fun.tvars() {
+                    self.add_tvar(tvar);
+                }
+                self.add_purity(fun.purity());
+                self.add_ty_ref(fun.ret());
+                self.add_list(fun.params());
+            }
+            Ty::TyPred(test_ty) => self.add_test_ty(test_ty),
+            Ty::List(list) => self.add_list(list),
+            Ty::RecordClass(cons) => self.add_record_cons(cons),
+            Ty::Record(instance) => {
+                self.add_record_cons(instance.cons());
+                self.add_ty_args(instance.ty_args());
+            }
+            Ty::Recursive(instance) => {
+                self.add_recursive_cons(instance.cons());
+                for ty_arg in instance.ty_args().iter() {
+                    self.add_ty_ref(ty_arg);
+                }
+            }
+            Ty::Newtype(newtype) => self.add_newtype(newtype),
+            Ty::Any
+            | Ty::Dyn
+            | Ty::Bool
+            | Ty::Char
+            | Ty::Float
+            | Ty::Int
+            | Ty::Num
+            | Ty::Rational
+            | Ty::BigInt
+            | Ty::LitBool(_)
+            | Ty::LitSym(_)
+            | Ty::IntRange(_)
+            | Ty::Str
+            | Ty::Sym
+            | Ty::EqPred
+            | Ty::FloatArray
+            | Ty::IntArray
+            | Ty::Bytes
+            | Ty::TopRecord => {}
+        }
+    }
+}
@@ -0,0 +1,1843 @@
+//! Binary encoding of a module's inferred HIR
+//!
+//! Entries start with the entry header and a checksum of the remaining data. This is followed by a
+//! table of the source files referred to by spans, the module's inferred locals and finally its
+//! inferred definitions. Integers and lengths are encoded as little endian and strings are prefixed
+//! with their length.
+//!
+//! Objects with an identity are encoded in full the first time they're encountered and referenced
+//! by their index afterwards. See [`CacheEnv`] for how objects outside of the entry are restored.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::num::NonZeroU32;
+use std::str;
+
+use arret_syntax::datum::{BigInt, DataStr, Datum};
+use arret_syntax::span::{FileId, Span};
+
+use crate::hir;
+use crate::hir::destruc;
+use crate::ty;
+use crate::ty::purity;
+use crate::ty::record;
+use crate::ty::recursive;
+use crate::ty::Ty;
+use crate::typeck::cache::env::{name_span_key, CacheEnv};
+use crate::typeck::cache::{CacheKey, KeyHasher, ENTRY_HEADER};
+use crate::typeck::infer::InferredModule;
+
+const TY_VAR: u8 = 0;
+const TY_ANY: u8 = 1;
+const TY_DYN: u8 = 2;
+const TY_BOOL: u8 = 3;
+const TY_CHAR: u8 = 4;
+const TY_FLOAT: u8 = 5;
+const TY_MAP: u8 = 6;
+const TY_KEYED_MAP: u8 = 7;
+const TY_INT: u8 = 8;
+const TY_NUM: u8 = 9;
+const TY_RATIONAL: u8 = 10;
+const TY_BIG_INT: u8 = 11;
+const TY_LIT_BOOL: u8 = 12;
+const TY_LIT_SYM: u8 = 13;
+const TY_INT_RANGE: u8 = 14;
+const TY_SET: u8 = 15;
+const TY_STR: u8 = 16;
+const TY_SYM: u8 = 17;
+const TY_UNION: u8 = 18;
+const TY_INTERSECT: u8 = 19;
+const TY_TOP_FUN: u8 = 20;
+const TY_FUN: u8 = 21;
+const TY_TY_PRED: u8 = 22;
+const TY_EQ_PRED: u8 = 23;
+const TY_VECTOR: u8 = 24;
+const TY_VECTOROF: u8 = 25;
+const TY_FLOAT_ARRAY: u8 = 26;
+const TY_INT_ARRAY: u8 = 27;
+const TY_BYTES: u8 = 28;
+const TY_LIST: u8 = 29;
+const TY_VALUES: u8 = 30;
+const TY_TOP_RECORD: u8 = 31;
+const TY_RECORD_CLASS: u8 = 32;
+const TY_RECORD: u8 = 33;
+const TY_RECURSIVE: u8 = 34;
+const TY_NEWTYPE: u8 = 35;
+
+const PURITY_PURE: u8 = 0;
+const PURITY_IMPURE: u8 = 1;
+const PURITY_VAR: u8 = 2;
+
+const TEST_TY_SYM: u8 = 0;
+const TEST_TY_STR: u8 = 1;
+const TEST_TY_BOOL: u8 = 2;
+const TEST_TY_NUM: u8 = 3;
+const TEST_TY_INT: u8 = 4;
+const TEST_TY_FLOAT: u8 = 5;
+const TEST_TY_CHAR: u8 = 6;
+const TEST_TY_LIST: u8 = 7;
+const TEST_TY_VECTOR: u8 = 8;
+const TEST_TY_SET: u8 = 9;
+const TEST_TY_MAP: u8 = 10;
+const TEST_TY_FUN: u8 = 11;
+const TEST_TY_NIL: u8 = 12;
+const TEST_TY_TOP_RECORD: u8 = 13;
+const TEST_TY_RECORD_CLASS: u8 = 14;
+const TEST_TY_FLOAT_ARRAY: u8 = 15;
+const TEST_TY_INT_ARRAY: u8 = 16;
+const TEST_TY_BYTES: u8 = 17;
+const TEST_TY_RATIONAL: u8 = 18;
+const TEST_TY_BIG_INT: u8 = 19;
+
+/// Object encoded earlier in the entry
+const OBJECT_BACKREF: u8 = 0;
+/// Object found in the [`CacheEnv`] by its name and span
+const OBJECT_SHARED: u8 = 1;
+/// Polymorphic variable introduced by inference that's recreated from its encoding
+const OBJECT_FRESH: u8 = 2;
+
+const DATUM_BOOL: u8 = 0;
+const DATUM_CHAR: u8 = 1;
+const DATUM_INT: u8 = 2;
+const DATUM_BIG_INT: u8 = 3;
+const DATUM_FLOAT: u8 = 4;
+const DATUM_RATIONAL: u8 = 5;
+const DATUM_LIST: u8 = 6;
+const DATUM_STR: u8 = 7;
+const DATUM_SYM: u8 = 8;
+const DATUM_VECTOR: u8 = 9;
+const DATUM_MAP: u8 = 10;
+const DATUM_SET: u8 = 11;
+
+const DESTRUC_SCALAR: u8 = 0;
+const DESTRUC_LIST: u8 = 1;
+const DESTRUC_VECTOR: u8 = 2;
+const DESTRUC_MAP: u8 = 3;
+
+const EXPR_LIT: u8 = 0;
+const EXPR_APP: u8 = 1;
+const EXPR_RECUR: u8 = 2;
+const EXPR_FUN: u8 = 3;
+const EXPR_LET: u8 = 4;
+const EXPR_LETREC: u8 = 5;
+const EXPR_COND: u8 = 6;
+const EXPR_EXPORT_REF: u8 = 7;
+const EXPR_LOCAL_REF: u8 = 8;
+const EXPR_TY_PRED: u8 = 9;
+const EXPR_EQ_PRED: u8 = 10;
+const EXPR_RECORD_CONS: u8 = 11;
+const EXPR_FIELD_ACCESSOR: u8 = 12;
+const EXPR_NO_CLAUSE_MATCH: u8 = 13;
+const EXPR_DO: u8 = 14;
+const EXPR_VALUES: u8 = 15;
+const EXPR_BOX: u8 = 16;
+const EXPR_UNBOX: u8 = 17;
+const EXPR_DYN_CHECK: u8 = 18;
+const EXPR_CAST: u8 = 19;
+const EXPR_MACRO_EXPAND: u8 = 20;
+
+/// Module can't be restored from a cache entry
+///
+/// This is returned for modules referring to objects that can't be found by their key along with
+/// entries that are truncated or corrupt.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Unrestorable;
+
+pub type Result<T> = std::result::Result<T, Unrestorable>;
+
+struct Encoder<'e> {
+    env: &'e mut CacheEnv,
+    output: Vec<u8>,
+
+    file_indices: HashMap<FileId, u32>,
+    files: Vec<(String, CacheKey)>,
+
+    tvar_indices: HashMap<ty::TVarId, usize>,
+    pvar_indices: HashMap<purity::PVarId, usize>,
+    record_cons_indices: HashMap<record::ConsId, usize>,
+    recursive_cons_indices: HashMap<recursive::ConsId, usize>,
+    newtype_indices: HashMap<ty::newtype::NewtypeId, usize>,
+}
+
+impl<'e> Encoder<'e> {
+    fn push_u32(&mut self, value: u32) {
+        self.output.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_u64(&mut self, value: u64) {
+        self.output.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_len(&mut self, len: usize) {
+        self.push_u64(len as u64);
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        self.output.push(value as u8);
+    }
+
+    fn push_str(&mut self, value: &str) {
+        self.push_len(value.len());
+        self.output.extend_from_slice(value.as_bytes());
+    }
+
+    fn push_opt_str(&mut self, value: &Option<DataStr>) {
+        self.push_bool(value.is_some());
+        if let Some(value) = value {
+            self.push_str(value);
+        }
+    }
+
+    fn push_span(&mut self, span: Span) -> Result<()> {
+        let file_index = match span.file_id() {
+            Some(file_id) => self.file_index(file_id)? + 1,
+            None => 0,
+        };
+
+        self.push_u32(file_index);
+        self.push_u32(span.start());
+        self.push_u32(span.end());
+        Ok(())
+    }
+
+    fn push_opt_span(&mut self, span: Option<Span>) -> Result<()> {
+        self.push_bool(span.is_some());
+        span.map_or(Ok(()), |span| self.push_span(span))
+    }
+
+    fn file_index(&mut self, file_id: FileId) -> Result<u32> {
+        if let Some(file_index) = self.file_indices.get(&file_id) {
+            return Ok(*file_index);
+        }
+
+        let (filename, source_key) = self.env.source_file_key(file_id).ok_or(Unrestorable)?;
+        let filename = filename.into_string().map_err(|_| Unrestorable)?;
+
+        let file_index = self.files.len() as u32;
+        self.files.push((filename, source_key));
+        self.file_indices.insert(file_id, file_index);
+
+        Ok(file_index)
+    }
+
+    fn push_local_id(&mut self, local_id: hir::LocalId) {
+        self.push_u32(local_id.get());
+    }
+
+    fn push_opt_local_id(&mut self, local_id: Option<hir::LocalId>) {
+        self.push_u32(local_id.map_or(0, hir::LocalId::get));
+    }
+
+    fn push_tvar(&mut self, tvar: &ty::TVarId) -> Result<()> {
+        if let Some(index) = self.tvar_indices.get(tvar) {
+            self.output.push(OBJECT_BACKREF);
+            self.push_len(*index);
+            return Ok(());
+        }
+
+        match self
+            .env
+            .tvars
+            .get(&name_span_key(tvar.source_name(), tvar.span()))
+        {
+            Some(Some(shared)) if shared == tvar => {
+                self.output.push(OBJECT_SHARED);
+                self.push_str(tvar.source_name());
+                self.push_span(tvar.span())?;
+            }
+            Some(_) => {
+                return Err(Unrestorable);
+            }
+            None => {
+                self.output.push(OBJECT_FRESH);
+                self.push_str(tvar.source_name());
+                self.push_span(tvar.span())?;
+
+                self.push_len(tvar.bounds().len());
+                for bound in tvar.bounds() {
+                    self.push_ty_ref(bound)?;
+                }
+            }
+        }
+
+        let index = self.tvar_indices.len();
+        self.tvar_indices.insert(tvar.clone(), index);
+        Ok(())
+    }
+
+    fn push_pvar(&mut self, pvar: &purity::PVarId) -> Result<()> {
+        if let Some(index) = self.pvar_indices.get(pvar) {
+            self.output.push(OBJECT_BACKREF);
+            self.push_len(*index);
+            return Ok(());
+        }
+
+        let tag = match self
+            .env
+            .pvars
+            .get(&name_span_key(pvar.source_name(), pvar.span()))
+        {
+            Some(Some(shared)) if shared == pvar => OBJECT_SHARED,
+            Some(_) => {
+                return Err(Unrestorable);
+            }
+            None => OBJECT_FRESH,
+        };
+
+        self.output.push(tag);
+        self.push_str(pvar.source_name());
+        self.push_span(pvar.span())?;
+
+        let index = self.pvar_indices.len();
+        self.pvar_indices.insert(pvar.clone(), index);
+        Ok(())
+    }
+
+    fn push_record_cons(&mut self, cons: &record::ConsId) -> Result<()> {
+        if let Some(index) = self.record_cons_indices.get(cons) {
+            self.output.push(OBJECT_BACKREF);
+            self.push_len(*index);
+            return Ok(());
+        }
+
+        match self
+            .env
+            .record_conses
+            .get(&name_span_key(cons.ty_cons_name(), cons.span()))
+        {
+            Some(Some(shared)) if shared == cons => {}
+            _ => {
+                return Err(Unrestorable);
+            }
+        }
+
+        self.output.push(OBJECT_SHARED);
+        self.push_str(cons.ty_cons_name());
+        self.push_span(cons.span())?;
+
+        let index = self.record_cons_indices.len();
+        self.record_cons_indices.insert(cons.clone(), index);
+        Ok(())
+    }
+
+    fn push_recursive_cons(&mut self, cons: &recursive::ConsId) -> Result<()> {
+        if let Some(index) = self.recursive_cons_indices.get(cons) {
+            self.output.push(OBJECT_BACKREF);
+            self.push_len(*index);
+            return Ok(());
+        }
+
+        match self
+            .env
+            .recursive_conses
+            .get(&name_span_key(cons.name(), cons.span()))
+        {
+            Some(Some(shared)) if shared == cons => {}
+            _ => {
+                return Err(Unrestorable);
+            }
+        }
+
+        self.output.push(OBJECT_SHARED);
+        self.push_str(cons.name());
+        self.push_span(cons.span())?;
+
+        let index = self.recursive_cons_indices.len();
+        self.recursive_cons_indices.insert(cons.clone(), index);
+        Ok(())
+    }
+
+    fn push_newtype(&mut self, newtype: &ty::newtype::NewtypeId) -> Result<()> {
+        if let Some(index) = self.newtype_indices.get(newtype) {
+            self.output.push(OBJECT_BACKREF);
+            self.push_len(*index);
+            return Ok(());
+        }
+
+        match self
+            .env
+            .newtypes
+            .get(&name_span_key(newtype.name(), newtype.span()))
+        {
+            Some(Some(shared)) if shared == newtype => {}
+            _ => {
+                return Err(Unrestorable);
+            }
+        }
+
+        self.output.push(OBJECT_SHARED);
+        self.push_str(newtype.name());
+        self.push_span(newtype.span())?;
+
+        let index = self.newtype_indices.len();
+        self.newtype_indices.insert(newtype.clone(), index);
+        Ok(())
+    }
+
+    fn push_purity(&mut self, purity: &purity::Ref) -> Result<()> {
+        match purity {
+            purity::Ref::Fixed(purity::Purity::Pure) => self.output.push(PURITY_PURE),
+            purity::Ref::Fixed(purity::Purity::Impure) => self.output.push(PURITY_IMPURE),
+            purity::Ref::Var(pvar) => {
+                self.output.push(PURITY_VAR);
+                self.push_pvar(pvar)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_test_ty(&mut self, test_ty: &ty::pred::TestTy) -> Result<()> {
+        use crate::ty::pred::TestTy;
+
+        let tag = match test_ty {
+            TestTy::Sym => TEST_TY_SYM,
+            TestTy::Str => TEST_TY_STR,
+            TestTy::Bool => TEST_TY_BOOL,
+            TestTy::Num => TEST_TY_NUM,
+            TestTy::Int => TEST_TY_INT,
+            TestTy::Float => TEST_TY_FLOAT,
+            TestTy::Char => TEST_TY_CHAR,
+            TestTy::List => TEST_TY_LIST,
+            TestTy::Vector => TEST_TY_VECTOR,
+            TestTy::Set => TEST_TY_SET,
+            TestTy::Map => TEST_TY_MAP,
+            TestTy::Fun => TEST_TY_FUN,
+            TestTy::Nil => TEST_TY_NIL,
+            TestTy::TopRecord => TEST_TY_TOP_RECORD,
+            TestTy::RecordClass(cons) => {
+                self.output.push(TEST_TY_RECORD_CLASS);
+                return self.push_record_cons(cons);
+            }
+            TestTy::FloatArray => TEST_TY_FLOAT_ARRAY,
+            TestTy::IntArray => TEST_TY_INT_ARRAY,
+            TestTy::Bytes => TEST_TY_BYTES,
+            TestTy::Rational => TEST_TY_RATIONAL,
+            TestTy::BigInt => TEST_TY_BIG_INT,
+        };
+
+        self.output.push(tag);
+        Ok(())
+    }
+
+    fn push_ty_refs(&mut self, ty_refs: &[ty::Ref<ty::Poly>]) -> Result<()> {
+        self.push_len(ty_refs.len());
+        for ty_ref in ty_refs {
+            self.push_ty_ref(ty_ref)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_map(&mut self, map: &ty::Map<ty::Poly>) -> Result<()> {
+        self.push_ty_ref(map.key())?;
+        self.push_ty_ref(map.value())
+    }
+
+    fn push_list(&mut self, list: &ty::List<ty::Poly>) -> Result<()> {
+        self.push_ty_refs(list.fixed())?;
+        self.push_ty_ref(list.rest())
+    }
+
+    fn push_ty_args(&mut self, ty_args: &ty::ty_args::TyArgs<ty::Poly>) -> Result<()> {
+        self.push_len(ty_args.pvar_purities().len());
+        for (pvar, purity) in ty_args.pvar_purities() {
+            self.push_pvar(pvar)?;
+            self.push_purity(purity)?;
+        }
+
+        self.push_len(ty_args.tvar_types().len());
+        for (tvar, ty_ref) in ty_args.tvar_types() {
+            self.push_tvar(tvar)?;
+            self.push_ty_ref(ty_ref)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_ty_ref(&mut self, ty_ref: &ty::Ref<ty::Poly>) -> Result<()> {
+        let ty = match ty_ref {
+            ty::Ref::Var(tvar, _) => {
+                self.output.push(TY_VAR);
+                return self.push_tvar(tvar);
+            }
+            ty::Ref::Fixed(ty) => ty,
+        };
+
+        match ty {
+            Ty::Any => self.output.push(TY_ANY),
+            Ty::Dyn => self.output.push(TY_DYN),
+            Ty::Bool => self.output.push(TY_BOOL),
+            Ty::Char => self.output.push(TY_CHAR),
+            Ty::Float => self.output.push(TY_FLOAT),
+            Ty::Map(map) => {
+                self.output.push(TY_MAP);
+                self.push_map(map)?;
+            }
+            Ty::KeyedMap(keyed_map) => {
+                self.output.push(TY_KEYED_MAP);
+
+                self.push_len(keyed_map.entries().len());
+                for (key, value) in keyed_map.entries() {
+                    self.push_str(key);
+                    self.push_ty_ref(value)?;
+                }
+
+                self.push_bool(keyed_map.rest().is_some());
+                if let Some(rest) = keyed_map.rest() {
+                    self.push_map(rest)?;
+                }
+            }
+            Ty::Int => self.output.push(TY_INT),
+            Ty::Num => self.output.push(TY_NUM),
+            Ty::Rational => self.output.push(TY_RATIONAL),
+            Ty::BigInt => self.output.push(TY_BIG_INT),
+            Ty::LitBool(value) => {
+                self.output.push(TY_LIT_BOOL);
+                self.push_bool(*value);
+            }
+            Ty::LitSym(name) => {
+                self.output.push(TY_LIT_SYM);
+                self.push_str(name);
+            }
+            Ty::IntRange(int_range) => {
+                self.output.push(TY_INT_RANGE);
+                self.push_u64(int_range.start() as u64);
+                self.push_u64(int_range.end() as u64);
+            }
+            Ty::Set(member) => {
+                self.output.push(TY_SET);
+                self.push_ty_ref(member)?;
+            }
+            Ty::Str => self.output.push(TY_STR),
+            Ty::Sym => self.output.push(TY_SYM),
+            Ty::Union(members) => {
+                self.output.push(TY_UNION);
+                self.push_ty_refs(members)?;
+            }
+            Ty::Intersect(members) => {
+                self.output.push(TY_INTERSECT);
+                self.push_ty_refs(members)?;
+            }
+            Ty::TopFun(top_fun) => {
+                self.output.push(TY_TOP_FUN);
+                self.push_purity(top_fun.purity())?;
+                self.push_ty_ref(top_fun.ret())?;
+            }
+            Ty::Fun(fun) => {
+                self.output.push(TY_FUN);
+
+                self.push_len(fun.pvars().len());
+                for pvar in fun.pvars() {
+                    self.push_pvar(pvar)?;
+                }
+
+                self.push_len(fun.tvars().len());
+                for tvar in fun.tvars() {
+                    self.push_tvar(tvar)?;
+                }
+
+                self.push_purity(fun.purity())?;
+                self.push_ty_ref(fun.ret())?;
+                self.push_list(fun.params())?;
+            }
+            Ty::TyPred(test_ty) => {
+                self.output.push(TY_TY_PRED);
+                self.push_test_ty(test_ty)?;
+            }
+            Ty::EqPred => self.output.push(TY_EQ_PRED),
+            Ty::Vector(members) => {
+                self.output.push(TY_VECTOR);
+                self.push_ty_refs(members)?;
+            }
+            Ty::Vectorof(member) => {
+                self.output.push(TY_VECTOROF);
+                self.push_ty_ref(member)?;
+            }
+            Ty::FloatArray => self.output.push(TY_FLOAT_ARRAY),
+            Ty::IntArray => self.output.push(TY_INT_ARRAY),
+            Ty::Bytes => self.output.push(TY_BYTES),
+            Ty::List(list) => {
+                self.output.push(TY_LIST);
+                self.push_list(list)?;
+            }
+            Ty::Values(members) => {
+                self.output.push(TY_VALUES);
+                self.push_ty_refs(members)?;
+            }
+            Ty::TopRecord => self.output.push(TY_TOP_RECORD),
+            Ty::RecordClass(cons) => {
+                self.output.push(TY_RECORD_CLASS);
+                self.push_record_cons(cons)?;
+            }
+            Ty::Record(instance) => {
+                self.output.push(TY_RECORD);
+                self.push_record_cons(instance.cons())?;
+                self.push_ty_args(instance.ty_args())?;
+            }
+            Ty::Recursive(instance) => {
+                self.output.push(TY_RECURSIVE);
+                self.push_recursive_cons(instance.cons())?;
+                self.push_ty_refs(instance.ty_args())?;
+            }
+            Ty::Newtype(newtype) => {
+                self.output.push(TY_NEWTYPE);
+                self.push_newtype(newtype)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_datum(&mut self, datum: &Datum) -> Result<()> {
+        match datum {
+            Datum::Bool(span, value) => {
+                self.output.push(DATUM_BOOL);
+                self.push_span(*span)?;
+                self.push_bool(*value);
+            }
+            Datum::Char(span, value) => {
+                self.output.push(DATUM_CHAR);
+                self.push_span(*span)?;
+                self.push_u32(*value as u32);
+            }
+            Datum::Int(span, value) => {
+                self.output.push(DATUM_INT);
+                self.push_span(*span)?;
+                self.push_u64(*value as u64);
+            }
+            Datum::BigInt(span, value) => {
+                let signed_bytes = value.to_signed_bytes_le();
+
+                self.output.push(DATUM_BIG_INT);
+                self.push_span(*span)?;
+                self.push_len(signed_bytes.len());
+                self.output.extend_from_slice(&signed_bytes);
+            }
+            Datum::Float(span, value) => {
+                self.output.push(DATUM_FLOAT);
+                self.push_span(*span)?;
+                self.push_u64(value.to_bits());
+            }
+            Datum::Rational(span, numer, denom) => {
+                self.output.push(DATUM_RATIONAL);
+                self.push_span(*span)?;
+                self.push_u64(*numer as u64);
+                self.push_u64(*denom as u64);
+            }
+            Datum::List(span, members) => {
+                self.output.push(DATUM_LIST);
+                self.push_span(*span)?;
+                self.push_data(members)?;
+            }
+            Datum::Str(span, value) => {
+                self.output.push(DATUM_STR);
+                self.push_span(*span)?;
+                self.push_str(value);
+            }
+            Datum::Sym(span, value) => {
+                self.output.push(DATUM_SYM);
+                self.push_span(*span)?;
+                self.push_str(value);
+            }
+            Datum::Vector(span, members) => {
+                self.output.push(DATUM_VECTOR);
+                self.push_span(*span)?;
+                self.push_data(members)?;
+            }
+            Datum::Map(span, entries) => {
+                self.output.push(DATUM_MAP);
+                self.push_span(*span)?;
+
+                self.push_len(entries.len());
+                for (key, value) in entries.iter() {
+                    self.push_datum(key)?;
+                    self.push_datum(value)?;
+                }
+            }
+            Datum::Set(span, members) => {
+                self.output.push(DATUM_SET);
+                self.push_span(*span)?;
+                self.push_data(members)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_data(&mut self, data: &[Datum]) -> Result<()> {
+        self.push_len(data.len());
+        for datum in data {
+            self.push_datum(datum)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_scalar_destruc(&mut self, scalar: &destruc::Scalar<hir::Inferred>) -> Result<()> {
+        self.push_opt_local_id(*scalar.local_id());
+        self.push_str(scalar.source_name());
+        self.push_ty_ref(scalar.ty())
+    }
+
+    fn push_list_destruc(&mut self, list: &destruc::List<hir::Inferred>) -> Result<()> {
+        self.push_destrucs(list.fixed())?;
+
+        self.push_bool(list.rest().is_some());
+        if let Some(rest) = list.rest() {
+            self.push_scalar_destruc(rest)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_destrucs(&mut self, destrucs: &[destruc::Destruc<hir::Inferred>]) -> Result<()> {
+        self.push_len(destrucs.len());
+        for destruc in destrucs {
+            self.push_destruc(destruc)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_destruc(&mut self, destruc: &destruc::Destruc<hir::Inferred>) -> Result<()> {
+        use crate::hir::destruc::Destruc;
+
+        match destruc {
+            Destruc::Scalar(span, scalar) => {
+                self.output.push(DESTRUC_SCALAR);
+                self.push_span(*span)?;
+                self.push_scalar_destruc(scalar)
+            }
+            Destruc::List(span, list) => {
+                self.output.push(DESTRUC_LIST);
+                self.push_span(*span)?;
+                self.push_list_destruc(list)
+            }
+            Destruc::Vector(span, vector) => {
+                self.output.push(DESTRUC_VECTOR);
+                self.push_span(*span)?;
+                self.push_destrucs(vector.members())
+            }
+            Destruc::Map(span, map) => {
+                self.output.push(DESTRUC_MAP);
+                self.push_span(*span)?;
+
+                self.push_len(map.entries().len());
+                for (key, value_destruc) in map.entries() {
+                    self.push_str(key);
+                    self.push_destruc(value_destruc)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn push_exprs(&mut self, exprs: &[hir::Expr<hir::Inferred>]) -> Result<()> {
+        self.push_len(exprs.len());
+        for expr in exprs {
+            self.push_expr(expr)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_opt_expr(&mut self, expr: &Option<hir::Expr<hir::Inferred>>) -> Result<()> {
+        self.push_bool(expr.is_some());
+        expr.as_ref().map_or(Ok(()), |expr| self.push_expr(expr))
+    }
+
+    fn push_expr(&mut self, expr: &hir::Expr<hir::Inferred>) -> Result<()> {
+        use crate::hir::ExprKind;
+
+        self.push_ty_ref(&expr.result_ty)?;
+
+        match &expr.kind {
+            ExprKind::Lit(datum) => {
+                self.output.push(EXPR_LIT);
+                self.push_datum(datum)?;
+            }
+            ExprKind::App(app) => {
+                self.output.push(EXPR_APP);
+                self.push_span(app.span)?;
+                self.push_expr(&app.fun_expr)?;
+                self.push_ty_args(&app.ty_args)?;
+                self.push_exprs(&app.fixed_arg_exprs)?;
+                self.push_opt_expr(&app.rest_arg_expr)?;
+            }
+            ExprKind::Recur(recur) => {
+                self.output.push(EXPR_RECUR);
+                self.push_span(recur.span)?;
+                self.push_exprs(&recur.fixed_arg_exprs)?;
+                self.push_opt_expr(&recur.rest_arg_expr)?;
+            }
+            ExprKind::Fun(fun) => {
+                self.output.push(EXPR_FUN);
+                self.push_span(fun.span)?;
+
+                self.push_len(fun.pvars.len());
+                for pvar in &fun.pvars {
+                    self.push_pvar(pvar)?;
+                }
+
+                self.push_len(fun.tvars.len());
+                for tvar in &fun.tvars {
+                    self.push_tvar(tvar)?;
+                }
+
+                self.push_purity(&fun.purity)?;
+                self.push_list_destruc(&fun.params)?;
+                self.push_ty_ref(&fun.ret_ty)?;
+                self.push_opt_span(fun.ret_ty_span)?;
+                self.push_expr(&fun.body_expr)?;
+            }
+            ExprKind::RustFun(_) => {
+                // Rust functions are loaded from RFI libraries and can't be encoded
+                return Err(Unrestorable);
+            }
+            ExprKind::Let(hir_let) => {
+                self.output.push(EXPR_LET);
+                self.push_span(hir_let.span)?;
+                self.push_destruc(&hir_let.destruc)?;
+                self.push_expr(&hir_let.value_expr)?;
+                self.push_expr(&hir_let.body_expr)?;
+            }
+            ExprKind::Letrec(letrec) => {
+                self.output.push(EXPR_LETREC);
+                self.push_span(letrec.span)?;
+
+                self.push_len(letrec.bindings.len());
+                for binding in &letrec.bindings {
+                    self.push_scalar_destruc(&binding.destruc)?;
+                    self.push_expr(&binding.value_expr)?;
+                }
+
+                self.push_expr(&letrec.body_expr)?;
+            }
+            ExprKind::Cond(cond) => {
+                self.output.push(EXPR_COND);
+                self.push_span(cond.span)?;
+                self.push_expr(&cond.test_expr)?;
+                self.push_expr(&cond.true_expr)?;
+                self.push_expr(&cond.false_expr)?;
+            }
+            ExprKind::ExportRef(span, export_id) => {
+                let module_key = self
+                    .env
+                    .module_key(export_id.module_id())
+                    .ok_or(Unrestorable)?;
+
+                self.output.push(EXPR_EXPORT_REF);
+                self.push_span(*span)?;
+                self.push_u64(module_key.0);
+                self.push_local_id(export_id.local_id());
+            }
+            ExprKind::LocalRef(span, local_id) => {
+                self.output.push(EXPR_LOCAL_REF);
+                self.push_span(*span)?;
+                self.push_local_id(*local_id);
+            }
+            ExprKind::TyPred(span, test_ty) => {
+                self.output.push(EXPR_TY_PRED);
+                self.push_span(*span)?;
+                self.push_test_ty(test_ty)?;
+            }
+            ExprKind::EqPred(span) => {
+                self.output.push(EXPR_EQ_PRED);
+                self.push_span(*span)?;
+            }
+            ExprKind::RecordCons(span, cons) => {
+                self.output.push(EXPR_RECORD_CONS);
+                self.push_span(*span)?;
+                self.push_record_cons(cons)?;
+            }
+            ExprKind::FieldAccessor(field_accessor) => {
+                self.output.push(EXPR_FIELD_ACCESSOR);
+                self.push_span(field_accessor.span)?;
+                self.push_record_cons(&field_accessor.record_cons)?;
+                self.push_len(field_accessor.field_index);
+            }
+            ExprKind::NoClauseMatch(no_clause_match) => {
+                self.output.push(EXPR_NO_CLAUSE_MATCH);
+                self.push_span(no_clause_match.span)?;
+                self.push_local_id(no_clause_match.subject_local_id);
+                self.push_bool(no_clause_match.kind == hir::BranchKind::Cond);
+            }
+            ExprKind::Do(exprs) => {
+                self.output.push(EXPR_DO);
+                self.push_exprs(exprs)?;
+            }
+            ExprKind::Values(span, exprs) => {
+                self.output.push(EXPR_VALUES);
+                self.push_span(*span)?;
+                self.push_exprs(exprs)?;
+            }
+            ExprKind::Box(span, value_expr) => {
+                self.output.push(EXPR_BOX);
+                self.push_span(*span)?;
+                self.push_expr(value_expr)?;
+            }
+            ExprKind::Unbox(unbox) => {
+                self.output.push(EXPR_UNBOX);
+                self.push_span(unbox.span)?;
+                self.push_ty_ref(&unbox.ty_ref)?;
+                self.push_expr(&unbox.value_expr)?;
+            }
+            ExprKind::DynCheck(dyn_check) => {
+                self.output.push(EXPR_DYN_CHECK);
+                self.push_span(dyn_check.span)?;
+                self.push_ty_ref(&dyn_check.ty_ref)?;
+                self.push_expr(&dyn_check.value_expr)?;
+            }
+            ExprKind::Cast(cast) => {
+                self.output.push(EXPR_CAST);
+                self.push_span(cast.span)?;
+                self.push_ty_ref(&cast.ty_ref)?;
+                self.push_expr(&cast.value_expr)?;
+                self.push_opt_str(&cast.reason);
+            }
+            ExprKind::MacroExpand(span, inner_expr) => {
+                self.output.push(EXPR_MACRO_EXPAND);
+                self.push_span(*span)?;
+                self.push_expr(inner_expr)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_def(&mut self, def: &hir::Def<hir::Inferred>) -> Result<()> {
+        self.push_span(def.span)?;
+        self.push_opt_span(def.macro_invocation_span)?;
+        self.push_destruc(&def.destruc)?;
+        self.push_expr(&def.value_expr)?;
+        self.push_opt_str(&def.doc);
+        self.push_bool(def.is_const);
+        Ok(())
+    }
+
+    fn push_module(&mut self, inferred_module: &InferredModule) -> Result<()> {
+        // Sort the locals so identical modules have identical entries
+        let mut inferred_locals: Vec<_> = inferred_module.inferred_locals.iter().collect();
+        inferred_locals.sort_unstable_by_key(|(local_id, _)| **local_id);
+
+        self.push_len(inferred_locals.len());
+        for (local_id, ty_ref) in inferred_locals {
+            self.push_local_id(*local_id);
+            self.push_ty_ref(ty_ref)?;
+        }
+
+        self.push_len(inferred_module.defs.len());
+        for def in &inferred_module.defs {
+            self.push_def(def)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes a module's inferred HIR
+///
+/// Modules with warnings can't be encoded; their warnings would be lost when they're restored.
+pub fn encode_module(env: &mut CacheEnv, inferred_module: &InferredModule) -> Result<Vec<u8>> {
+    if !inferred_module.warnings.is_empty() {
+        return Err(Unrestorable);
+    }
+
+    let mut encoder = Encoder {
+        env,
+        output: vec![],
+
+        file_indices: HashMap::new(),
+        files: vec![],
+
+        tvar_indices: HashMap::new(),
+        pvar_indices: HashMap::new(),
+        record_cons_indices: HashMap::new(),
+        recursive_cons_indices: HashMap::new(),
+        newtype_indices: HashMap::new(),
+    };
+
+    encoder.push_module(inferred_module)?;
+    let body = std::mem::take(&mut encoder.output);
+
+    // The file table is only complete once the body has been encoded
+    let files = std::mem::take(&mut encoder.files);
+    encoder.push_len(files.len());
+    for (filename, source_key) in files {
+        encoder.push_str(&filename);
+        encoder.push_u64(source_key.0);
+    }
+
+    encoder.output.extend_from_slice(&body);
+    let payload = encoder.output;
+
+    let mut checksum_hasher = KeyHasher::new();
+    checksum_hasher.write_bytes(&payload);
+
+    let mut output = Vec::with_capacity(payload.len() + ENTRY_HEADER.len() + 16);
+    output.extend_from_slice(&(ENTRY_HEADER.len() as u64).to_le_bytes());
+    output.extend_from_slice(ENTRY_HEADER.as_bytes());
+    output.extend_from_slice(&checksum_hasher.finish().0.to_le_bytes());
+    output.extend_from_slice(&payload);
+
+    Ok(output)
+}
+
+struct Decoder<'a> {
+    env: &'a mut CacheEnv,
+    input: &'a [u8],
+
+    files: Vec<Option<FileId>>,
+
+    tvars: Vec<ty::TVarId>,
+    pvars: Vec<purity::PVarId>,
+    record_conses: Vec<record::ConsId>,
+    recursive_conses: Vec<recursive::ConsId>,
+    newtypes: Vec<ty::newtype::NewtypeId>,
+}
+
+impl<'a> Decoder<'a> {
+    fn take_bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.input.len() < count {
+            return Err(Unrestorable);
+        }
+
+        let (taken, rest) = self.input.split_at(count);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn take_u8(&mut self) -> Result<u8> {
+        Ok(self.take_bytes(1)?[0])
+    }
+
+    fn take_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn take_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take_bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Takes a length prefix for members of at least `min_member_size` bytes
+    ///
+    /// This ensures the length is plausible before we attempt to allocate space for the members.
+    fn take_len(&mut self, min_member_size: usize) -> Result<usize> {
+        let len = self.take_u64()?;
+
+        if len > (self.input.len() / min_member_size) as u64 {
+            return Err(Unrestorable);
+        }
+
+        Ok(len as usize)
+    }
+
+    fn take_index(&mut self) -> Result<usize> {
+        let index = self.take_u64()?;
+        Ok(index as usize)
+    }
+
+    fn take_bool(&mut self) -> Result<bool> {
+        match self.take_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Unrestorable),
+        }
+    }
+
+    fn take_str(&mut self) -> Result<&'a str> {
+        let len = self.take_len(1)?;
+        str::from_utf8(self.take_bytes(len)?).map_err(|_| Unrestorable)
+    }
+
+    fn take_data_str(&mut self) -> Result<DataStr> {
+        self.take_str().map(Into::into)
+    }
+
+    fn take_opt_data_str(&mut self) -> Result<Option<DataStr>> {
+        if self.take_bool()? {
+            self.take_data_str().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn take_span(&mut self) -> Result<Span> {
+        let file_id = match self.take_u32()? {
+            0 => None,
+            file_index => Some(
+                self.files
+                    .get(file_index as usize - 1)
+                    .cloned()
+                    .flatten()
+                    .ok_or(Unrestorable)?,
+            ),
+        };
+
+        let start = self.take_u32()?;
+        let end = self.take_u32()?;
+
+        Ok(Span::new(file_id, start, end))
+    }
+
+    fn take_opt_span(&mut self) -> Result<Option<Span>> {
+        if self.take_bool()? {
+            self.take_span().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn take_local_id(&mut self) -> Result<hir::LocalId> {
+        self.take_opt_local_id()?.ok_or(Unrestorable)
+    }
+
+    fn take_opt_local_id(&mut self) -> Result<Option<hir::LocalId>> {
+        Ok(NonZeroU32::new(self.take_u32()?).map(hir::LocalId::from_raw))
+    }
+
+    /// Takes a name and span identifying an object
+    fn take_name_span(&mut self) -> Result<(&'a str, Span)> {
+        let name = self.take_str()?;
+        let span = self.take_span()?;
+
+        Ok((name, span))
+    }
+
+    fn take_tvar(&mut self) -> Result<ty::TVarId> {
+        let tvar = match self.take_u8()? {
+            OBJECT_BACKREF => {
+                let index = self.take_index()?;
+                return self.tvars.get(index).cloned().ok_or(Unrestorable);
+            }
+            OBJECT_SHARED => {
+                let (name, span) = self.take_name_span()?;
+
+                self.env
+                    .tvars
+                    .get(&name_span_key(name, span))
+                    .flatten()
+                    .cloned()
+                    .ok_or(Unrestorable)?
+            }
+            OBJECT_FRESH => {
+                let (name, span) = self.take_name_span()?;
+                let mut bounds = self.take_ty_refs()?;
+
+                if bounds.len() == 1 {
+                    ty::TVar::new(span, name.into(), bounds.pop().unwrap())
+                } else {
+                    ty::TVar::new_with_bounds(span, name.into(), bounds.into_boxed_slice())
+                        .map_err(|_| Unrestorable)?
+                }
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        self.tvars.push(tvar.clone());
+        Ok(tvar)
+    }
+
+    fn take_pvar(&mut self) -> Result<purity::PVarId> {
+        let pvar = match self.take_u8()? {
+            OBJECT_BACKREF => {
+                let index = self.take_index()?;
+                return self.pvars.get(index).cloned().ok_or(Unrestorable);
+            }
+            OBJECT_SHARED => {
+                let (name, span) = self.take_name_span()?;
+
+                self.env
+                    .pvars
+                    .get(&name_span_key(name, span))
+                    .flatten()
+                    .cloned()
+                    .ok_or(Unrestorable)?
+            }
+            OBJECT_FRESH => {
+                let (name, span) = self.take_name_span()?;
+                purity::PVar::new(span, name.into())
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        self.pvars.push(pvar.clone());
+        Ok(pvar)
+    }
+
+    fn take_record_cons(&mut self) -> Result<record::ConsId> {
+        let cons = match self.take_u8()? {
+            OBJECT_BACKREF => {
+                let index = self.take_index()?;
+                return self.record_conses.get(index).cloned().ok_or(Unrestorable);
+            }
+            OBJECT_SHARED => {
+                let (name, span) = self.take_name_span()?;
+
+                self.env
+                    .record_conses
+                    .get(&name_span_key(name, span))
+                    .flatten()
+                    .cloned()
+                    .ok_or(Unrestorable)?
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        self.record_conses.push(cons.clone());
+        Ok(cons)
+    }
+
+    fn take_recursive_cons(&mut self) -> Result<recursive::ConsId> {
+        let cons = match self.take_u8()? {
+            OBJECT_BACKREF => {
+                let index = self.take_index()?;
+                return self
+                    .recursive_conses
+                    .get(index)
+                    .cloned()
+                    .ok_or(Unrestorable);
+            }
+            OBJECT_SHARED => {
+                let (name, span) = self.take_name_span()?;
+
+                self.env
+                    .recursive_conses
+                    .get(&name_span_key(name, span))
+                    .flatten()
+                    .cloned()
+                    .ok_or(Unrestorable)?
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        self.recursive_conses.push(cons.clone());
+        Ok(cons)
+    }
+
+    fn take_newtype(&mut self) -> Result<ty::newtype::NewtypeId> {
+        let newtype = match self.take_u8()? {
+            OBJECT_BACKREF => {
+                let index = self.take_index()?;
+                return self.newtypes.get(index).cloned().ok_or(Unrestorable);
+            }
+            OBJECT_SHARED => {
+                let (name, span) = self.take_name_span()?;
+
+                self.env
+                    .newtypes
+                    .get(&name_span_key(name, span))
+                    .flatten()
+                    .cloned()
+                    .ok_or(Unrestorable)?
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        self.newtypes.push(newtype.clone());
+        Ok(newtype)
+    }
+
+    fn take_purity(&mut self) -> Result<purity::Ref> {
+        Ok(match self.take_u8()? {
+            PURITY_PURE => purity::Purity::Pure.into(),
+            PURITY_IMPURE => purity::Purity::Impure.into(),
+            PURITY_VAR => self.take_pvar()?.into(),
+            _ => {
+                return Err(Unrestorable);
+            }
+        })
+    }
+
+    fn take_test_ty(&mut self) -> Result<ty::pred::TestTy> {
+        use crate::ty::pred::TestTy;
+
+        Ok(match self.take_u8()? {
+            TEST_TY_SYM => TestTy::Sym,
+            TEST_TY_STR => TestTy::Str,
+            TEST_TY_BOOL => TestTy::Bool,
+            TEST_TY_NUM => TestTy::Num,
+            TEST_TY_INT => TestTy::Int,
+            TEST_TY_FLOAT => TestTy::Float,
+            TEST_TY_CHAR => TestTy::Char,
+            TEST_TY_LIST => TestTy::List,
+            TEST_TY_VECTOR => TestTy::Vector,
+            TEST_TY_SET => TestTy::Set,
+            TEST_TY_MAP => TestTy::Map,
+            TEST_TY_FUN => TestTy::Fun,
+            TEST_TY_NIL => TestTy::Nil,
+            TEST_TY_TOP_RECORD => TestTy::TopRecord,
+            TEST_TY_RECORD_CLASS => TestTy::RecordClass(self.take_record_cons()?),
+            TEST_TY_FLOAT_ARRAY => TestTy::FloatArray,
+            TEST_TY_INT_ARRAY => TestTy::IntArray,
+            TEST_TY_BYTES => TestTy::Bytes,
+            TEST_TY_RATIONAL => TestTy::Rational,
+            TEST_TY_BIG_INT => TestTy::BigInt,
+            _ => {
+                return Err(Unrestorable);
+            }
+        })
+    }
+
+    fn take_ty_refs(&mut self) -> Result<Vec<ty::Ref<ty::Poly>>> {
+        let len = self.take_len(1)?;
+        (0..len).map(|_| self.take_ty_ref()).collect()
+    }
+
+    fn take_map(&mut self) -> Result<ty::Map<ty::Poly>> {
+        let key = self.take_ty_ref()?;
+        let value = self.take_ty_ref()?;
+
+        Ok(ty::Map::new(key, value))
+    }
+
+    fn take_list(&mut self) -> Result<ty::List<ty::Poly>> {
+        let fixed = self.take_ty_refs()?;
+        let rest = self.take_ty_ref()?;
+
+        Ok(ty::List::new(fixed.into_boxed_slice(), rest))
+    }
+
+    fn take_ty_args(&mut self) -> Result<ty::ty_args::TyArgs<ty::Poly>> {
+        let pvar_purities_len = self.take_len(2)?;
+        let pvar_purities = (0..pvar_purities_len)
+            .map(|_| Ok((self.take_pvar()?, self.take_purity()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let tvar_types_len = self.take_len(2)?;
+        let tvar_types = (0..tvar_types_len)
+            .map(|_| Ok((self.take_tvar()?, self.take_ty_ref()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(ty::ty_args::TyArgs::new(pvar_purities, tvar_types))
+    }
+
+    fn take_ty_ref(&mut self) -> Result<ty::Ref<ty::Poly>> {
+        let ty = match self.take_u8()? {
+            TY_VAR => {
+                return Ok(self.take_tvar()?.into());
+            }
+            TY_ANY => Ty::Any,
+            TY_DYN => Ty::Dyn,
+            TY_BOOL => Ty::Bool,
+            TY_CHAR => Ty::Char,
+            TY_FLOAT => Ty::Float,
+            TY_MAP => Ty::Map(Box::new(self.take_map()?)),
+            TY_KEYED_MAP => {
+                let entries_len = self.take_len(2)?;
+                let entries = (0..entries_len)
+                    .map(|_| Ok((self.take_data_str()?, self.take_ty_ref()?)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let rest = if self.take_bool()? {
+                    Some(self.take_map()?)
+                } else {
+                    None
+                };
+
+                ty::KeyedMap::new(entries.into_boxed_slice(), rest).into()
+            }
+            TY_INT => Ty::Int,
+            TY_NUM => Ty::Num,
+            TY_RATIONAL => Ty::Rational,
+            TY_BIG_INT => Ty::BigInt,
+            TY_LIT_BOOL => Ty::LitBool(self.take_bool()?),
+            TY_LIT_SYM => Ty::LitSym(self.take_data_str()?),
+            TY_INT_RANGE => {
+                let start = self.take_u64()? as i64;
+                let end = self.take_u64()? as i64;
+
+                if start > end {
+                    return Err(Unrestorable);
+                }
+
+                ty::IntRange::new(start, end).into()
+            }
+            TY_SET => Ty::Set(Box::new(self.take_ty_ref()?)),
+            TY_STR => Ty::Str,
+            TY_SYM => Ty::Sym,
+            TY_UNION => Ty::Union(self.take_ty_refs()?.into_boxed_slice()),
+            TY_INTERSECT => Ty::Intersect(self.take_ty_refs()?.into_boxed_slice()),
+            TY_TOP_FUN => {
+                let purity = self.take_purity()?;
+                let ret = self.take_ty_ref()?;
+
+                ty::TopFun::new(purity, ret).into()
+            }
+            TY_FUN => {
+                let pvars_len = self.take_len(1)?;
+                let pvars = (0..pvars_len)
+                    .map(|_| self.take_pvar())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let tvars_len = self.take_len(1)?;
+                let tvars = (0..tvars_len)
+                    .map(|_| self.take_tvar())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let purity = self.take_purity()?;
+                let ret = self.take_ty_ref()?;
+                let params = self.take_list()?;
+
+                ty::Fun::new(pvars, tvars, ty::TopFun::new(purity, ret), params).into()
+            }
+            TY_TY_PRED => Ty::TyPred(self.take_test_ty()?),
+            TY_EQ_PRED => Ty::EqPred,
+            TY_VECTOR => Ty::Vector(self.take_ty_refs()?.into_boxed_slice()),
+            TY_VECTOROF => Ty::Vectorof(Box::new(self.take_ty_ref()?)),
+            TY_FLOAT_ARRAY => Ty::FloatArray,
+            TY_INT_ARRAY => Ty::IntArray,
+            TY_BYTES => Ty::Bytes,
+            TY_LIST => Ty::List(self.take_list()?),
+            TY_VALUES => Ty::Values(self.take_ty_refs()?.into_boxed_slice()),
+            TY_TOP_RECORD => Ty::TopRecord,
+            TY_RECORD_CLASS => Ty::RecordClass(self.take_record_cons()?),
+            TY_RECORD => {
+                let cons = self.take_record_cons()?;
+                let ty_args = self.take_ty_args()?;
+
+                record::Instance::new(cons, ty_args).into()
+            }
+            TY_RECURSIVE => {
+                let cons = self.take_recursive_cons()?;
+                let ty_args = self.take_ty_refs()?;
+
+                recursive::Instance::new(cons, ty_args.into_boxed_slice()).into()
+            }
+            TY_NEWTYPE => Ty::Newtype(self.take_newtype()?),
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        Ok(ty.into())
+    }
+
+    fn take_data(&mut self) -> Result<Box<[Datum]>> {
+        let len = self.take_len(1)?;
+        (0..len).map(|_| self.take_datum()).collect()
+    }
+
+    fn take_datum(&mut self) -> Result<Datum> {
+        let tag = self.take_u8()?;
+        let span = self.take_span()?;
+
+        Ok(match tag {
+            DATUM_BOOL => Datum::Bool(span, self.take_bool()?),
+            DATUM_CHAR => {
+                let code_point = self.take_u32()?;
+                Datum::Char(span, std::char::from_u32(code_point).ok_or(Unrestorable)?)
+            }
+            DATUM_INT => Datum::Int(span, self.take_u64()? as i64),
+            DATUM_BIG_INT => {
+                let len = self.take_len(1)?;
+                let value = BigInt::from_signed_bytes_le(self.take_bytes(len)?);
+
+                Datum::BigInt(span, Box::new(value))
+            }
+            DATUM_FLOAT => Datum::Float(span, f64::from_bits(self.take_u64()?)),
+            DATUM_RATIONAL => {
+                let numer = self.take_u64()? as i64;
+                let denom = self.take_u64()? as i64;
+
+                Datum::Rational(span, numer, denom)
+            }
+            DATUM_LIST => Datum::List(span, self.take_data()?),
+            DATUM_STR => Datum::Str(span, self.take_data_str()?),
+            DATUM_SYM => Datum::Sym(span, self.take_data_str()?),
+            DATUM_VECTOR => Datum::Vector(span, self.take_data()?),
+            DATUM_MAP => {
+                let len = self.take_len(2)?;
+                let entries = (0..len)
+                    .map(|_| Ok((self.take_datum()?, self.take_datum()?)))
+                    .collect::<Result<Box<[_]>>>()?;
+
+                Datum::Map(span, entries)
+            }
+            DATUM_SET => Datum::Set(span, self.take_data()?),
+            _ => {
+                return Err(Unrestorable);
+            }
+        })
+    }
+
+    fn take_scalar_destruc(&mut self) -> Result<destruc::Scalar<hir::Inferred>> {
+        let local_id = self.take_opt_local_id()?;
+        let source_name = self.take_data_str()?;
+        let ty = self.take_ty_ref()?;
+
+        Ok(destruc::Scalar::new(local_id, source_name, ty))
+    }
+
+    fn take_list_destruc(&mut self) -> Result<destruc::List<hir::Inferred>> {
+        let fixed = self.take_destrucs()?;
+
+        let rest = if self.take_bool()? {
+            Some(Box::new(self.take_scalar_destruc()?))
+        } else {
+            None
+        };
+
+        Ok(destruc::List::new(fixed, rest))
+    }
+
+    fn take_destrucs(&mut self) -> Result<Vec<destruc::Destruc<hir::Inferred>>> {
+        let len = self.take_len(1)?;
+        (0..len).map(|_| self.take_destruc()).collect()
+    }
+
+    fn take_destruc(&mut self) -> Result<destruc::Destruc<hir::Inferred>> {
+        use crate::hir::destruc::Destruc;
+
+        let tag = self.take_u8()?;
+        let span = self.take_span()?;
+
+        Ok(match tag {
+            DESTRUC_SCALAR => Destruc::Scalar(span, self.take_scalar_destruc()?),
+            DESTRUC_LIST => Destruc::List(span, self.take_list_destruc()?),
+            DESTRUC_VECTOR => Destruc::Vector(span, destruc::Vector::new(self.take_destrucs()?)),
+            DESTRUC_MAP => {
+                let len = self.take_len(2)?;
+                let entries = (0..len)
+                    .map(|_| Ok((self.take_data_str()?, self.take_destruc()?)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Destruc::Map(span, destruc::Map::new(entries))
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        })
+    }
+
+    fn take_exprs(&mut self) -> Result<Vec<hir::Expr<hir::Inferred>>> {
+        let len = self.take_len(2)?;
+        (0..len).map(|_| self.take_expr()).collect()
+    }
+
+    fn take_opt_expr(&mut self) -> Result<Option<hir::Expr<hir::Inferred>>> {
+        if self.take_bool()? {
+            self.take_expr().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn take_expr(&mut self) -> Result<hir::Expr<hir::Inferred>> {
+        use crate::hir::ExprKind;
+
+        let result_ty = self.take_ty_ref()?;
+
+        let kind = match self.take_u8()? {
+            EXPR_LIT => ExprKind::Lit(self.take_datum()?),
+            EXPR_APP => {
+                let span = self.take_span()?;
+                let fun_expr = self.take_expr()?;
+                let ty_args = self.take_ty_args()?;
+                let fixed_arg_exprs = self.take_exprs()?;
+                let rest_arg_expr = self.take_opt_expr()?;
+
+                ExprKind::App(Box::new(hir::App {
+                    span,
+                    fun_expr,
+                    ty_args,
+                    fixed_arg_exprs,
+                    rest_arg_expr,
+                }))
+            }
+            EXPR_RECUR => {
+                let span = self.take_span()?;
+                let fixed_arg_exprs = self.take_exprs()?;
+                let rest_arg_expr = self.take_opt_expr()?;
+
+                ExprKind::Recur(Box::new(hir::Recur {
+                    span,
+                    fixed_arg_exprs,
+                    rest_arg_expr,
+                }))
+            }
+            EXPR_FUN => {
+                let span = self.take_span()?;
+
+                let pvars_len = self.take_len(1)?;
+                let pvars = (0..pvars_len)
+                    .map(|_| self.take_pvar())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let tvars_len = self.take_len(1)?;
+                let tvars = (0..tvars_len)
+                    .map(|_| self.take_tvar())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let purity = self.take_purity()?;
+                let params = self.take_list_destruc()?;
+                let ret_ty = self.take_ty_ref()?;
+                let ret_ty_span = self.take_opt_span()?;
+                let body_expr = self.take_expr()?;
+
+                ExprKind::Fun(Box::new(hir::Fun {
+                    span,
+                    pvars,
+                    tvars,
+                    purity,
+                    params,
+                    ret_ty,
+                    ret_ty_span,
+                    body_expr,
+                }))
+            }
+            EXPR_LET => {
+                let span = self.take_span()?;
+                let destruc = self.take_destruc()?;
+                let value_expr = self.take_expr()?;
+                let body_expr = self.take_expr()?;
+
+                ExprKind::Let(Box::new(hir::Let {
+                    span,
+                    destruc,
+                    value_expr,
+                    body_expr,
+                }))
+            }
+            EXPR_LETREC => {
+                let span = self.take_span()?;
+
+                let bindings_len = self.take_len(2)?;
+                let bindings = (0..bindings_len)
+                    .map(|_| {
+                        Ok(hir::LetrecBinding {
+                            destruc: self.take_scalar_destruc()?,
+                            value_expr: self.take_expr()?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let body_expr = self.take_expr()?;
+
+                ExprKind::Letrec(Box::new(hir::Letrec {
+                    span,
+                    bindings,
+                    body_expr,
+                }))
+            }
+            EXPR_COND => {
+                let span = self.take_span()?;
+                let test_expr = self.take_expr()?;
+                let true_expr = self.take_expr()?;
+                let false_expr = self.take_expr()?;
+
+                ExprKind::Cond(Box::new(hir::Cond {
+                    span,
+                    test_expr,
+                    true_expr,
+                    false_expr,
+                }))
+            }
+            EXPR_EXPORT_REF => {
+                let span = self.take_span()?;
+                let module_key = CacheKey(self.take_u64()?);
+                let module_id = self.env.module_for_key(module_key).ok_or(Unrestorable)?;
+                let local_id = self.take_local_id()?;
+
+                ExprKind::ExportRef(span, hir::ExportId::new(module_id, local_id))
+            }
+            EXPR_LOCAL_REF => {
+                let span = self.take_span()?;
+                ExprKind::LocalRef(span, self.take_local_id()?)
+            }
+            EXPR_TY_PRED => {
+                let span = self.take_span()?;
+                ExprKind::TyPred(span, self.take_test_ty()?)
+            }
+            EXPR_EQ_PRED => ExprKind::EqPred(self.take_span()?),
+            EXPR_RECORD_CONS => {
+                let span = self.take_span()?;
+                ExprKind::RecordCons(span, self.take_record_cons()?)
+            }
+            EXPR_FIELD_ACCESSOR => {
+                let span = self.take_span()?;
+                let record_cons = self.take_record_cons()?;
+                let field_index = self.take_index()?;
+
+                if field_index >= record_cons.fields().len() {
+                    return Err(Unrestorable);
+                }
+
+                ExprKind::FieldAccessor(Box::new(hir::FieldAccessor {
+                    span,
+                    record_cons,
+                    field_index,
+                }))
+            }
+            EXPR_NO_CLAUSE_MATCH => {
+                let span = self.take_span()?;
+                let subject_local_id = self.take_local_id()?;
+                let kind = if self.take_bool()? {
+                    hir::BranchKind::Cond
+                } else {
+                    hir::BranchKind::Match
+                };
+
+                ExprKind::NoClauseMatch(Box::new(hir::NoClauseMatch {
+                    span,
+                    subject_local_id,
+                    kind,
+                }))
+            }
+            EXPR_DO => ExprKind::Do(self.take_exprs()?),
+            EXPR_VALUES => {
+                let span = self.take_span()?;
+                ExprKind::Values(span, self.take_exprs()?)
+            }
+            EXPR_BOX => {
+                let span = self.take_span()?;
+                ExprKind::Box(span, Box::new(self.take_expr()?))
+            }
+            EXPR_UNBOX => {
+                let span = self.take_span()?;
+                let ty_ref = self.take_ty_ref()?;
+                let value_expr = self.take_expr()?;
+
+                ExprKind::Unbox(Box::new(hir::Unbox {
+                    span,
+                    ty_ref,
+                    value_expr,
+                }))
+            }
+            EXPR_DYN_CHECK => {
+                let span = self.take_span()?;
+                let ty_ref = self.take_ty_ref()?;
+                let value_expr = self.take_expr()?;
+
+                ExprKind::DynCheck(Box::new(hir::DynCheck {
+                    span,
+                    ty_ref,
+                    value_expr,
+                }))
+            }
+            EXPR_CAST => {
+                let span = self.take_span()?;
+                let ty_ref = self.take_ty_ref()?;
+                let value_expr = self.take_expr()?;
+                let reason = self.take_opt_data_str()?;
+
+                ExprKind::Cast(Box::new(hir::Cast {
+                    span,
+                    ty_ref,
+                    value_expr,
+                    reason,
+                }))
+            }
+            EXPR_MACRO_EXPAND => {
+                let span = self.take_span()?;
+                ExprKind::MacroExpand(span, Box::new(self.take_expr()?))
+            }
+            _ => {
+                return Err(Unrestorable);
+            }
+        };
+
+        Ok(hir::Expr { result_ty, kind })
+    }
+
+    fn take_def(&mut self) -> Result<hir::Def<hir::Inferred>> {
+        let span = self.take_span()?;
+        let macro_invocation_span = self.take_opt_span()?;
+        let destruc = self.take_destruc()?;
+        let value_expr = self.take_expr()?;
+        let doc = self.take_opt_data_str()?;
+        let is_const = self.take_bool()?;
+
+        Ok(hir::Def {
+            span,
+            macro_invocation_span,
+            destruc,
+            value_expr,
+            doc,
+            is_const,
+        })
+    }
+
+    fn take_files(&mut self) -> Result<()> {
+        let len = self.take_len(9)?;
+
+        for _ in 0..len {
+            let filename = self.take_str()?;
+            let source_key = CacheKey(self.take_u64()?);
+
+            // Files that can't be found are only an error if a span refers to them
+            let file_id = self.env.find_source_file(filename.as_ref(), source_key);
+            self.files.push(file_id);
+        }
+
+        Ok(())
+    }
+
+    fn take_module(&mut self) -> Result<InferredModule> {
+        self.take_files()?;
+
+        let locals_len = self.take_len(5)?;
+        let inferred_locals = (0..locals_len)
+            .map(|_| Ok((self.take_local_id()?, self.take_ty_ref()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let defs_len = self.take_len(1)?;
+        let defs = (0..defs_len)
+            .map(|_| self.take_def())
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(InferredModule {
+            inferred_locals,
+            defs,
+            warnings: vec![],
+        })
+    }
+}
+
+/// Decodes a module's inferred HIR
+pub fn decode_module(env: &mut CacheEnv, input: &[u8]) -> Result<InferredModule> {
+    let mut decoder = Decoder {
+        env,
+        input,
+
+        files: vec![],
+
+        tvars: vec![],
+        pvars: vec![],
+        record_conses: vec![],
+        recursive_conses: vec![],
+        newtypes: vec![],
+    };
+
+    if decoder.take_str()? != ENTRY_HEADER {
+        return Err(Unrestorable);
+    }
+
+    let checksum = CacheKey(decoder.take_u64()?);
+
+    let mut checksum_hasher = KeyHasher::new();
+    checksum_hasher.write_bytes(decoder.input);
+    if checksum_hasher.finish() != checksum {
+        return Err(Unrestorable);
+    }
+
+    let inferred_module = decoder.take_module()?;
+
+    if !decoder.input.is_empty() {
+        return Err(Unrestorable);
+    }
+
+    Ok(inferred_module)
+}
@@ -0,0 +1,511 @@
+//! On-disk cache of the HIR inferred for a module
+//!
+//! Entries are keyed by a hash of the module's source along with the keys of every module it
+//! imports. Changing a module therefore invalidates the entries of every module depending on it.
+//! Entry file names also include a key identifying the compiler build so entries written by a
+//! different compiler are never used.
+//!
+//! Each module has two entries. The first holds the module's complete inferred HIR; modules
+//! restored from it skip type inference entirely. Inferred HIR refers to record types, newtypes
+//! and type variables by identity. These are found again by their name and span in the freshly
+//! lowered module and the modules it imports; see [`CacheEnv`] for the details.
+//!
+//! Modules that can't be restored fall back to the second entry. This holds the inferred type of
+//! each top-level definition which is assumed when the module is inferred again: definitions can
+//! be referenced before they're visited and mutually recursive definitions start from their final
+//! types. These types are stored using the same syntax used to declare them. Only types that can
+//! be lowered back to an identically printed type in a scope containing just the primitives are
+//! cached; this excludes any type referring to a user defined type.
+
+mod env;
+mod hir_entry;
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::{fs, io, path};
+
+use arret_syntax::datum::DataStr;
+use arret_syntax::parser::datum_from_str;
+
+use crate::hir;
+use crate::hir::destruc;
+use crate::hir::ns::NsDatum;
+use crate::hir::scope::Scope;
+use crate::ty;
+use crate::typeck::infer::InferredModule;
+
+pub use self::env::CacheEnv;
+
+/// First line of every cache entry
+///
+/// Entries written by a different version of the compiler are ignored.
+const ENTRY_HEADER: &str = concat!("arret-typeck-cache ", env!("CARGO_PKG_VERSION"));
+
+/// Key identifying a module's cache entry
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CacheKey(u64);
+
+/// Builds a `CacheKey` using 64-bit FNV-1a
+///
+/// Unlike `std::hash` the result is stable between compiler builds and platforms.
+#[derive(Clone)]
+pub struct KeyHasher(u64);
+
+impl KeyHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    pub fn new() -> Self {
+        KeyHasher(Self::OFFSET_BASIS)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Writes a length prefixed string so adjacent strings can't run together
+    pub fn write_str(&mut self, value: &str) {
+        self.write_bytes(&(value.len() as u64).to_le_bytes());
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bytes(&[value as u8]);
+    }
+
+    pub fn write_key(&mut self, key: CacheKey) {
+        self.write_bytes(&key.0.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> CacheKey {
+        CacheKey(self.0)
+    }
+}
+
+impl Default for KeyHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a key identifying the running compiler build
+///
+/// This combines the package version with a hash of the compiler's sources calculated by its build
+/// script. Any change to the compiler invalidates every entry.
+pub fn compiler_build_key() -> CacheKey {
+    let mut key_hasher = KeyHasher::new();
+    key_hasher.write_str(ENTRY_HEADER);
+    key_hasher.write_str(env!("ARRET_COMPILER_SOURCE_HASH"));
+    key_hasher.finish()
+}
+
+/// Cached types for a module's top-level definitions keyed by their name
+pub type CachedDefTypes = HashMap<DataStr, ty::Ref<ty::Poly>>;
+
+/// Parses a serialised type
+pub fn deserialise_poly(serialised: &str) -> Option<ty::Ref<ty::Poly>> {
+    let datum = datum_from_str(None, serialised).ok()?;
+    let scope = Scope::new_with_primitives();
+
+    hir::lower_poly(&scope, NsDatum::from_syntax_datum(&datum)).ok()
+}
+
+/// Returns a stable serialisation of a type
+///
+/// This returns `None` if the type can't be deserialised back to the same type.
+pub fn serialise_poly(poly: &ty::Ref<ty::Poly>) -> Option<String> {
+    let serialised = hir::str_for_ty_ref(poly);
+
+    // Each entry is stored on its own line
+    if serialised.contains(&['\n', '\t'][..]) {
+        return None;
+    }
+
+    deserialise_poly(&serialised)
+        .filter(|deserialised| hir::str_for_ty_ref(deserialised) == serialised)
+        .map(|_| serialised)
+}
+
+/// Directory of cached module types
+pub struct TypeckCache {
+    cache_dir: Box<path::Path>,
+    build_key: CacheKey,
+}
+
+impl TypeckCache {
+    /// Creates a cache stored in the passed directory
+    ///
+    /// The directory is created when the first entry is stored.
+    pub fn new(cache_dir: impl Into<Box<path::Path>>) -> Self {
+        TypeckCache {
+            cache_dir: cache_dir.into(),
+            build_key: compiler_build_key(),
+        }
+    }
+
+    fn entry_path(&self, key: CacheKey, extension: &str) -> path::PathBuf {
+        self.cache_dir.join(format!(
+            "{:016x}-{:016x}.{}",
+            self.build_key.0, key.0, extension
+        ))
+    }
+
+    /// Writes an entry to a temporary file before moving it in to place
+    ///
+    /// This ensures concurrent compilers never observe a partial entry.
+    fn write_entry(&self, entry_path: path::PathBuf, contents: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.cache_dir)?;
+
+        let temp_path = entry_path.with_extension(format!("{}.tmp", std::process::id()));
+
+        fs::File::create(&temp_path)?.write_all(contents)?;
+        fs::rename(&temp_path, &entry_path)
+    }
+
+    /// Loads the definition types for a cache key
+    ///
+    /// Missing, outdated or unreadable entries return `None`.
+    pub fn load(&self, key: CacheKey) -> Option<CachedDefTypes> {
+        let contents = fs::read_to_string(self.entry_path(key, "types")).ok()?;
+        let mut lines = contents.lines();
+
+        if lines.next()? != ENTRY_HEADER {
+            return None;
+        }
+
+        lines
+            .map(|line| {
+                let (name, serialised) = line.split_once('\t')?;
+                Some((name.into(), deserialise_poly(serialised)?))
+            })
+            .collect()
+    }
+
+    /// Stores the types of a module's inferred top-level definitions
+    ///
+    /// Definitions with types that can't be serialised are omitted.
+    pub fn store(&self, key: CacheKey, defs: &[hir::Def<hir::Inferred>]) -> io::Result<()> {
+        let mut contents = format!("{}\n", ENTRY_HEADER);
+        for def in defs {
+            if let destruc::Destruc::Scalar(_, scalar) = &def.destruc {
+                if scalar.local_id().is_none() {
+                    continue;
+                }
+
+                if let Some(serialised) = serialise_poly(scalar.ty()) {
+                    contents.push_str(&format!("{}\t{}\n", scalar.source_name(), serialised));
+                }
+            }
+        }
+
+        self.write_entry(self.entry_path(key, "types"), contents.as_bytes())
+    }
+
+    /// Restores a module's inferred HIR for a cache key
+    ///
+    /// `env` must contain the freshly lowered module and every module it transitively imports.
+    /// Missing or outdated entries along with modules referring to anything that can't be found in
+    /// the environment return `None`.
+    pub fn load_module(&self, key: CacheKey, env: &mut CacheEnv) -> Option<InferredModule> {
+        let contents = fs::read(self.entry_path(key, "hir")).ok()?;
+        hir_entry::decode_module(env, &contents).ok()
+    }
+
+    /// Stores a module's inferred HIR
+    ///
+    /// Modules with warnings or referring to anything that can't be found in the environment
+    /// aren't stored.
+    pub fn store_module(
+        &self,
+        key: CacheKey,
+        env: &mut CacheEnv,
+        inferred_module: &InferredModule,
+    ) -> io::Result<()> {
+        match hir_entry::encode_module(env, inferred_module) {
+            Ok(contents) => self.write_entry(self.entry_path(key, "hir"), &contents),
+            Err(hir_entry::Unrestorable) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::hir::lowering::{module_for_file_str, LoweredModule};
+    use crate::source::SourceLoader;
+    use crate::typeck::infer::{infer_module, infer_module_with_cached_types};
+
+    const MODULE_DATA_STR: &str = r#"
+        (defrecord Point (point [x Int] [y Int]))
+        (defnewtype Meters Float)
+        (deftype Distance (U Meters Int))
+
+        (def origin (point 0 0))
+        (def [distance Distance] (->Meters 1.0))
+        (def ident (fn #{A} ([v A]) -> A v))
+        (def px (fn ([p Point]) (point-x p)))
+        (def first-key (fn ([m (Map Sym Int)]) (let [{a :a} m] a)))
+        (def pair (fn () (ident '(one 2))))
+        "#;
+
+    fn lower_module(source_loader: &SourceLoader, data_str: &str) -> LoweredModule {
+        let source_file = source_loader.load_string("cached.arret".into(), data_str.to_owned());
+        module_for_file_str(Some(source_file.file_id()), false, data_str).unwrap()
+    }
+
+    fn cache_env_for_module(
+        source_loader: &SourceLoader,
+        lowered_module: &LoweredModule,
+    ) -> CacheEnv {
+        let mut cache_env = CacheEnv::new(source_loader);
+        cache_env.add_lowered_module(&lowered_module.defs, &lowered_module.exports);
+        cache_env
+    }
+
+    fn def_type_strs(inferred_module: &InferredModule) -> Vec<(Option<hir::LocalId>, String)> {
+        inferred_module
+            .defs
+            .iter()
+            .map(|def| match &def.destruc {
+                destruc::Destruc::Scalar(_, scalar) => {
+                    (*scalar.local_id(), hir::str_for_ty_ref(scalar.ty()))
+                }
+                _ => (
+                    None,
+                    hir::str_for_ty_ref(&destruc::poly_for_destruc(&def.destruc)),
+                ),
+            })
+            .collect()
+    }
+
+    fn assert_round_trips(ty_str: &str) {
+        let poly = hir::poly_for_str(ty_str);
+        let serialised = serialise_poly(&poly).unwrap();
+
+        assert_eq!(
+            hir::str_for_ty_ref(&poly),
+            hir::str_for_ty_ref(&deserialise_poly(&serialised).unwrap())
+        );
+    }
+
+    #[test]
+    fn poly_round_trip() {
+        assert_round_trips("Int");
+        assert_round_trips("'foo");
+        assert_round_trips("(List Int & Str)");
+        assert_round_trips("(Map Sym (RawU Int Str))");
+        assert_round_trips("(Int Str -> Bool)");
+        assert_round_trips("(All #{A [->! ->!]} (A -> A) A ->! A)");
+    }
+
+    #[test]
+    fn nominal_types_not_serialised() {
+        use crate::source::EMPTY_SPAN;
+        use crate::ty::newtype::Newtype;
+        use crate::ty::Ty;
+
+        let meters: ty::Ref<ty::Poly> =
+            Ty::Newtype(Newtype::new(EMPTY_SPAN, "Meters".into(), Ty::Float.into())).into();
+
+        assert_eq!(None, serialise_poly(&meters));
+    }
+
+    #[test]
+    fn key_hasher() {
+        let mut source_hasher = KeyHasher::new();
+        source_hasher.write_str("(def x 1)");
+        let source_key = source_hasher.finish();
+
+        let mut changed_hasher = KeyHasher::new();
+        changed_hasher.write_str("(def x 2)");
+        assert_ne!(source_key, changed_hasher.finish());
+
+        // Strings are length prefixed so concatenations hash differently
+        let mut split_hasher = KeyHasher::new();
+        split_hasher.write_str("(def x");
+        split_hasher.write_str(" 1)");
+        assert_ne!(source_key, split_hasher.finish());
+    }
+
+    #[test]
+    fn store_and_load() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = TypeckCache::new(cache_dir.path());
+        let key = KeyHasher::new().finish();
+
+        let data_str = "(def [x Int] 1) (def f (fn () (g))) (def g (fn () (f)))";
+
+        let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+
+        assert!(cache.load(key).is_none());
+        cache.store(key, &inferred_module.defs).unwrap();
+
+        let cached_def_types = cache.load(key).unwrap();
+        assert_eq!(3, cached_def_types.len());
+        assert_eq!("Int", hir::str_for_ty_ref(&cached_def_types["x"]));
+        assert_eq!("(-> (U))", hir::str_for_ty_ref(&cached_def_types["f"]));
+
+        // Inferring again from the cached types should produce the same types
+        let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+        let reinferred_module = infer_module_with_cached_types(
+            &HashMap::new(),
+            lowered_module.defs,
+            false,
+            &cached_def_types,
+        )
+        .unwrap();
+
+        for (def, redef) in inferred_module
+            .defs
+            .iter()
+            .zip(reinferred_module.defs.iter())
+        {
+            if let (destruc::Destruc::Scalar(_, scalar), destruc::Destruc::Scalar(_, rescalar)) =
+                (&def.destruc, &redef.destruc)
+            {
+                assert_eq!(
+                    hir::str_for_ty_ref(scalar.ty()),
+                    hir::str_for_ty_ref(rescalar.ty())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn outdated_entries_ignored() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = TypeckCache::new(cache_dir.path());
+        let key = KeyHasher::new().finish();
+
+        fs::write(
+            cache.entry_path(key, "types"),
+            "arret-typeck-cache 0.0.0\nx\tInt\n",
+        )
+        .unwrap();
+        assert!(cache.load(key).is_none());
+    }
+
+    #[test]
+    fn other_builds_ignored() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = TypeckCache::new(cache_dir.path());
+        let key = KeyHasher::new().finish();
+
+        let data_str = "(def [x Int] 1)";
+        let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+        cache.store(key, &inferred_module.defs).unwrap();
+
+        let other_build_cache = TypeckCache {
+            cache_dir: cache_dir.path().into(),
+            build_key: KeyHasher::new().finish(),
+        };
+
+        assert!(cache.load(key).is_some());
+        assert!(other_build_cache.load(key).is_none());
+    }
+
+    #[test]
+    fn module_round_trip() {
+        let source_loader = SourceLoader::new();
+        let lowered_module = lower_module(&source_loader, MODULE_DATA_STR);
+        let mut cache_env = cache_env_for_module(&source_loader, &lowered_module);
+
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+
+        let encoded = hir_entry::encode_module(&mut cache_env, &inferred_module).unwrap();
+        let decoded = hir_entry::decode_module(&mut cache_env, &encoded).unwrap();
+
+        assert_eq!(inferred_module.defs, decoded.defs);
+        assert_eq!(inferred_module.inferred_locals, decoded.inferred_locals);
+    }
+
+    #[test]
+    fn module_restored_after_lowering_again() {
+        let source_loader = SourceLoader::new();
+
+        let lowered_module = lower_module(&source_loader, MODULE_DATA_STR);
+        let mut cache_env = cache_env_for_module(&source_loader, &lowered_module);
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+        let encoded = hir_entry::encode_module(&mut cache_env, &inferred_module).unwrap();
+
+        // Lowering in another compilation creates new records, newtypes and polymorphic variables
+        let next_source_loader = SourceLoader::new();
+        let relowered_module = lower_module(&next_source_loader, MODULE_DATA_STR);
+        let mut recache_env = cache_env_for_module(&next_source_loader, &relowered_module);
+        let restored_module = hir_entry::decode_module(&mut recache_env, &encoded).unwrap();
+
+        assert_eq!(
+            def_type_strs(&inferred_module),
+            def_type_strs(&restored_module)
+        );
+
+        // Restored HIR must refer to the objects in the new lowering
+        let reinferred_module =
+            infer_module(&HashMap::new(), relowered_module.defs, false).unwrap();
+        assert_eq!(reinferred_module.defs, restored_module.defs);
+    }
+
+    #[test]
+    fn module_with_unknown_objects_not_restored() {
+        let source_loader = SourceLoader::new();
+
+        let lowered_module = lower_module(&source_loader, MODULE_DATA_STR);
+        let mut cache_env = cache_env_for_module(&source_loader, &lowered_module);
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+        let encoded = hir_entry::encode_module(&mut cache_env, &inferred_module).unwrap();
+
+        // The records and newtypes can't be found without the lowered module
+        let mut empty_env = CacheEnv::new(&source_loader);
+        assert!(hir_entry::decode_module(&mut empty_env, &encoded).is_err());
+
+        // Corrupt entries are rejected
+        let mut corrupt = encoded.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(hir_entry::decode_module(&mut cache_env, &corrupt).is_err());
+        assert!(hir_entry::decode_module(&mut cache_env, &encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn module_with_changed_source_file_not_restored() {
+        let source_loader = SourceLoader::new();
+
+        let lowered_module = lower_module(&source_loader, MODULE_DATA_STR);
+        let mut cache_env = cache_env_for_module(&source_loader, &lowered_module);
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+        let encoded = hir_entry::encode_module(&mut cache_env, &inferred_module).unwrap();
+
+        // Spans can't refer to a file with the same name but different contents
+        let other_loader = SourceLoader::new();
+        let changed_module = lower_module(&other_loader, &format!("{} ", MODULE_DATA_STR));
+        let mut changed_env = cache_env_for_module(&other_loader, &changed_module);
+        assert!(hir_entry::decode_module(&mut changed_env, &encoded).is_err());
+    }
+
+    #[test]
+    fn store_and_load_module() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = TypeckCache::new(cache_dir.path());
+        let key = KeyHasher::new().finish();
+
+        let source_loader = SourceLoader::new();
+        let lowered_module = lower_module(&source_loader, MODULE_DATA_STR);
+        let mut cache_env = cache_env_for_module(&source_loader, &lowered_module);
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+
+        assert!(cache.load_module(key, &mut cache_env).is_none());
+        cache
+            .store_module(key, &mut cache_env, &inferred_module)
+            .unwrap();
+
+        let restored_module = cache.load_module(key, &mut cache_env).unwrap();
+        assert_eq!(
+            def_type_strs(&inferred_module),
+            def_type_strs(&restored_module)
+        );
+    }
+}
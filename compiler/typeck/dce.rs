@@ -16,10 +16,15 @@ pub fn expr_can_side_effect(expr: &hir::Expr<hir::Inferred>) -> bool {
         | ExprKind::TyPred(_, _)
         | ExprKind::RecordCons(_, _)
         | ExprKind::FieldAccessor(_)
+        | ExprKind::NoClauseMatch(_)
         | ExprKind::Fun(_)
         | ExprKind::RustFun(_) => false,
-        ExprKind::Do(exprs) => exprs.iter().any(expr_can_side_effect),
-        ExprKind::MacroExpand(_, inner) => expr_can_side_effect(inner),
+        ExprKind::Do(exprs) | ExprKind::Values(_, exprs) => exprs.iter().any(expr_can_side_effect),
+        ExprKind::MacroExpand(_, inner) | ExprKind::Box(_, inner) => expr_can_side_effect(inner),
+        ExprKind::Unbox(unbox) => expr_can_side_effect(&unbox.value_expr),
+        ExprKind::Cast(cast) => expr_can_side_effect(&cast.value_expr),
+        // Failing the check will panic
+        ExprKind::DynCheck(_) => true,
         ExprKind::Cond(cond) => {
             expr_can_side_effect(&cond.test_expr)
                 || expr_can_side_effect(&cond.true_expr)
@@ -28,6 +33,8 @@ pub fn expr_can_side_effect(expr: &hir::Expr<hir::Inferred>) -> bool {
         ExprKind::Let(let_expr) => {
             expr_can_side_effect(&let_expr.value_expr) || expr_can_side_effect(&let_expr.body_expr)
         }
+        // Our bindings are all functions which can't side effect
+        ExprKind::Letrec(letrec) => expr_can_side_effect(&letrec.body_expr),
         ExprKind::App(app) => {
             if let ty::Ref::Fixed(Ty::Fun(ref fun_type)) = app.fun_expr.result_ty {
                 fun_type.top_fun().purity() != &Purity::Pure.into()
@@ -1,3 +1,6 @@
+use arret_syntax::datum::DataStr;
+use arret_syntax::span::Span;
+
 use crate::hir;
 use crate::hir::destruc;
 use crate::ty;
@@ -35,6 +38,43 @@ pub fn type_for_decl_list_destruc(
     ty::List::new(fixed_polys, rest_poly)
 }
 
+/// Returns the type of a vector member at the given index
+pub fn vector_member_type(
+    vector_type: &ty::Ref<ty::Poly>,
+    index: usize,
+) -> Option<&ty::Ref<ty::Poly>> {
+    match vector_type.resolve_to_ty() {
+        Ty::Vector(members) => members.get(index),
+        Ty::Vectorof(member) => Some(member.as_ref()),
+        _ => None,
+    }
+}
+
+/// Returns the value type of a map for the given keyword key
+pub fn map_member_type<'a>(
+    map_type: &'a ty::Ref<ty::Poly>,
+    key: &str,
+) -> Option<&'a ty::Ref<ty::Poly>> {
+    match map_type.resolve_to_ty() {
+        Ty::Map(map) => Some(map.value()),
+        Ty::KeyedMap(keyed_map) => keyed_map
+            .get(key)
+            .or_else(|| keyed_map.rest().map(|rest| rest.value())),
+        _ => None,
+    }
+}
+
+/// Returns the entry type for a map destruc key if it's known by the guide type
+fn keyed_map_guide_type<'a>(
+    guide_type: Option<&'a ty::Ref<ty::Poly>>,
+    key: &str,
+) -> Option<&'a ty::Ref<ty::Poly>> {
+    match guide_type?.resolve_to_ty() {
+        Ty::KeyedMap(keyed_map) => keyed_map.get(key),
+        _ => None,
+    }
+}
+
 /// Returns the required type for a destruc
 pub fn type_for_decl_destruc(
     destruc: &destruc::Destruc<hir::Lowered>,
@@ -52,6 +92,225 @@ pub fn type_for_decl_destruc(
 
             type_for_decl_list_destruc(list, guide_type_iter).into()
         }
+
+        destruc::Destruc::Vector(_, vector) => Ty::Vector(
+            vector
+                .members()
+                .iter()
+                .enumerate()
+                .map(|(index, member)| {
+                    let member_guide_type =
+                        guide_type.and_then(|guide_type| vector_member_type(guide_type, index));
+
+                    type_for_decl_destruc(member, member_guide_type)
+                })
+                .collect(),
+        )
+        .into(),
+
+        destruc::Destruc::Map(_, map) => {
+            // Keys known by a keyed map guide keep their own type. Every other value must be
+            // satisfied by the map's value type.
+            let mut known_entries = vec![];
+            let mut other_value_polys = vec![];
+
+            for (key, value) in map.entries() {
+                if let Some(entry_guide_type) = keyed_map_guide_type(guide_type, key) {
+                    known_entries.push((
+                        key.clone(),
+                        type_for_decl_destruc(value, Some(entry_guide_type)),
+                    ));
+                } else {
+                    let value_guide_type =
+                        guide_type.and_then(|guide_type| map_member_type(guide_type, key));
+
+                    other_value_polys.push(type_for_decl_destruc(value, value_guide_type));
+                }
+            }
+
+            let rest = ty::Map::new(
+                Ty::Any.into(),
+                destruc::map_value_poly(other_value_polys.into_iter()),
+            );
+
+            if known_entries.is_empty() {
+                rest.into()
+            } else {
+                ty::KeyedMap::new(known_entries.into_boxed_slice(), Some(rest)).into()
+            }
+        }
+    }
+}
+
+fn destruc_is_fully_declared(destruc: &destruc::Destruc<hir::Lowered>) -> bool {
+    match destruc {
+        destruc::Destruc::Scalar(_, scalar) => *scalar.ty() != hir::DeclTy::Free,
+        destruc::Destruc::List(_, list) => list_destruc_is_fully_declared(list),
+        destruc::Destruc::Vector(_, vector) => {
+            vector.members().iter().all(destruc_is_fully_declared)
+        }
+        destruc::Destruc::Map(_, map) => map
+            .entries()
+            .iter()
+            .all(|(_, value)| destruc_is_fully_declared(value)),
+    }
+}
+
+/// Returns true if every type in the list destruc has been explicitly declared
+pub fn list_destruc_is_fully_declared(list: &destruc::List<hir::Lowered>) -> bool {
+    let fixed_declared = list.fixed().iter().all(destruc_is_fully_declared);
+
+    let rest_declared = list
+        .rest()
+        .as_ref()
+        .map(|rest| *rest.ty() != hir::DeclTy::Free)
+        .unwrap_or(true);
+
+    fixed_declared && rest_declared
+}
+
+fn is_implicit_any_scalar(
+    decl_scalar: &destruc::Scalar<hir::Lowered>,
+    revealed_scalar: &destruc::Scalar<hir::Inferred>,
+) -> bool {
+    // Wildcards are never referenced so their type doesn't matter
+    decl_scalar.local_id().is_some()
+        && *decl_scalar.ty() == hir::DeclTy::Free
+        && *revealed_scalar.ty() == Ty::Any.into()
+}
+
+fn find_implicit_any_destruc(
+    decl_destruc: &destruc::Destruc<hir::Lowered>,
+    revealed_destruc: &destruc::Destruc<hir::Inferred>,
+) -> Option<(Span, DataStr)> {
+    match (decl_destruc, revealed_destruc) {
+        (
+            destruc::Destruc::Scalar(span, decl_scalar),
+            destruc::Destruc::Scalar(_, revealed_scalar),
+        ) => {
+            if is_implicit_any_scalar(decl_scalar, revealed_scalar) {
+                Some((*span, decl_scalar.source_name().clone()))
+            } else {
+                None
+            }
+        }
+        (destruc::Destruc::List(span, decl_list), destruc::Destruc::List(_, revealed_list)) => {
+            find_implicit_any_param(*span, decl_list, revealed_list)
+        }
+        (
+            destruc::Destruc::Vector(_, decl_vector),
+            destruc::Destruc::Vector(_, revealed_vector),
+        ) => decl_vector
+            .members()
+            .iter()
+            .zip(revealed_vector.members())
+            .find_map(|(decl_member, revealed_member)| {
+                find_implicit_any_destruc(decl_member, revealed_member)
+            }),
+        (destruc::Destruc::Map(_, decl_map), destruc::Destruc::Map(_, revealed_map)) => decl_map
+            .entries()
+            .iter()
+            .zip(revealed_map.entries())
+            .find_map(|((_, decl_value), (_, revealed_value))| {
+                find_implicit_any_destruc(decl_value, revealed_value)
+            }),
+        _ => {
+            panic!("mismatched declared and revealed destrucs");
+        }
+    }
+}
+
+/// Returns the first undeclared parameter in a list destruc that was inferred as `Any`
+///
+/// `rest_span` is used for the rest parameter as it has no span of its own.
+pub fn find_implicit_any_param(
+    rest_span: Span,
+    decl_list: &destruc::List<hir::Lowered>,
+    revealed_list: &destruc::List<hir::Inferred>,
+) -> Option<(Span, DataStr)> {
+    for (decl_fixed, revealed_fixed) in decl_list.fixed().iter().zip(revealed_list.fixed()) {
+        if let Some(found) = find_implicit_any_destruc(decl_fixed, revealed_fixed) {
+            return Some(found);
+        }
+    }
+
+    match (decl_list.rest(), revealed_list.rest()) {
+        (Some(decl_rest), Some(revealed_rest))
+            if is_implicit_any_scalar(decl_rest, revealed_rest) =>
+        {
+            Some((rest_span, decl_rest.source_name().clone()))
+        }
+        _ => None,
+    }
+}
+
+/// Returns true if a parameter list has a named scalar parameter without a declared type
+pub fn has_free_scalar_param(decl_list: &destruc::List<hir::Lowered>) -> bool {
+    decl_list.fixed().iter().any(|decl_fixed| {
+        matches!(
+            decl_fixed,
+            destruc::Destruc::Scalar(_, scalar)
+                if scalar.local_id().is_some() && *scalar.ty() == hir::DeclTy::Free
+        )
+    })
+}
+
+/// Returns a name for a new type variable that isn't used by any existing type variable
+fn fresh_tvar_name(tvars: &[ty::TVarId]) -> DataStr {
+    let is_unused = |name: &str| tvars.iter().all(|tvar| tvar.source_name() != name);
+
+    ('A'..='Z')
+        .map(|letter| letter.to_string())
+        .chain((1..).map(|index| format!("T{}", index)))
+        .find(|name| is_unused(name))
+        .unwrap()
+        .into()
+}
+
+/// Declares each fixed scalar parameter that was implicitly inferred as `Any` with a new type
+/// variable
+///
+/// The new type variables are appended to `tvars`. This returns `None` if there are no such
+/// parameters.
+pub fn generalise_implicit_any_params(
+    decl_list: &destruc::List<hir::Lowered>,
+    revealed_list: &destruc::List<hir::Inferred>,
+    tvars: &mut ty::TVars,
+) -> Option<destruc::List<hir::Lowered>> {
+    let mut generalised_any = false;
+
+    let fixed = decl_list
+        .fixed()
+        .iter()
+        .zip(revealed_list.fixed())
+        .map(
+            |(decl_fixed, revealed_fixed)| match (decl_fixed, revealed_fixed) {
+                (
+                    destruc::Destruc::Scalar(span, decl_scalar),
+                    destruc::Destruc::Scalar(_, revealed_scalar),
+                ) if is_implicit_any_scalar(decl_scalar, revealed_scalar) => {
+                    let tvar = ty::TVar::new(*span, fresh_tvar_name(tvars), Ty::Any.into());
+                    tvars.push(tvar.clone());
+                    generalised_any = true;
+
+                    destruc::Destruc::Scalar(
+                        *span,
+                        destruc::Scalar::new(
+                            *decl_scalar.local_id(),
+                            decl_scalar.source_name().clone(),
+                            hir::DeclTy::Known(tvar.into()),
+                        ),
+                    )
+                }
+                _ => decl_fixed.clone(),
+            },
+        )
+        .collect();
+
+    if generalised_any {
+        Some(destruc::List::new(fixed, decl_list.rest().clone()))
+    } else {
+        None
     }
 }
 
@@ -88,6 +347,20 @@ where
                 visit_scalar_locals(rest, visitor);
             }
 
+            None
+        }
+        destruc::Destruc::Vector(_, ref vector) => {
+            for member in vector.members() {
+                visit_locals(member, visitor);
+            }
+
+            None
+        }
+        destruc::Destruc::Map(_, ref map) => {
+            for (_, value) in map.entries() {
+                visit_locals(value, visitor);
+            }
+
             None
         }
     }
@@ -3,12 +3,14 @@ use std::{error, fmt};
 
 use codespan_reporting::diagnostic::Diagnostic;
 
+use arret_syntax::datum::DataStr;
 use arret_syntax::span::{FileId, Span};
 
 use crate::hir;
 use crate::reporting::{new_primary_label, new_secondary_label, LocTrace};
 use crate::ty;
 use crate::ty::purity;
+use crate::ty::select::TVarOrigin;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub struct WantedArity {
@@ -56,27 +58,73 @@ impl IsNotRetTy {
     }
 }
 
+#[derive(PartialEq, Debug, Clone)]
+pub struct IsNotTVarBound {
+    value_poly: ty::Ref<ty::Poly>,
+    tvar: ty::TVarId,
+    bound_poly: ty::Ref<ty::Poly>,
+}
+
+/// Polymorphic function used recursively before its type is known
+#[derive(PartialEq, Debug, Clone)]
+pub struct PolyRecursion {
+    fun_span: Span,
+    missing_annotation: &'static str,
+}
+
+impl PolyRecursion {
+    pub fn new(fun_span: Span, missing_annotation: &'static str) -> PolyRecursion {
+        PolyRecursion {
+            fun_span,
+            missing_annotation,
+        }
+    }
+}
+
+/// Values providing evidence for a type variable involved in an error
+#[derive(PartialEq, Debug, Clone)]
+pub struct TVarProvenance {
+    tvar: ty::TVarId,
+    origins: Vec<TVarOrigin>,
+}
+
+/// Reason the type of the `(main!)` function is unsupported
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum WrongMainTy {
+    NotFun,
+    Polymorphic,
+    TakesParams,
+    WrongRetTy,
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum ErrorKind {
     IsNotTy(ty::Ref<ty::Poly>, ty::Ref<ty::Poly>),
     IsNotFun(ty::Ref<ty::Poly>),
     IsNotPurity(ty::Ref<ty::Poly>, purity::Ref),
     IsNotRetTy(IsNotRetTy),
+    IsNotTVarBound(IsNotTVarBound),
     VarHasEmptyType(ty::Ref<ty::Poly>, ty::Ref<ty::Poly>),
     TopFunApply(ty::Ref<ty::Poly>),
     RecursiveType,
+    PolyRecursion(Box<PolyRecursion>),
     RecurWithoutFunTypeDecl,
     NonTailRecur,
     DependsOnError,
     WrongArity(usize, WantedArity),
     UnselectedPVar(purity::PVarId),
     UnselectedTVar(ty::TVarId),
+    WrongMainTy(ty::Ref<ty::Poly>, WrongMainTy),
+    ImplicitAnyParam(DataStr),
+    NonExhaustiveMatch(ty::Ref<ty::Poly>),
+    NoMatchingOverload(ty::Ref<ty::Poly>, ty::Ref<ty::Poly>),
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Error {
     loc_trace: LocTrace,
     kind: ErrorKind,
+    tvar_provenance: Option<Box<TVarProvenance>>,
 }
 
 impl Error {
@@ -85,13 +133,66 @@ impl Error {
     }
 
     pub fn new_with_loc_trace(loc_trace: LocTrace, kind: ErrorKind) -> Error {
-        Error { loc_trace, kind }
+        Error {
+            loc_trace,
+            kind,
+            tvar_provenance: None,
+        }
     }
 
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
 
+    pub fn loc_trace(&self) -> &LocTrace {
+        &self.loc_trace
+    }
+
+    /// Names the bound that failed when an argument doesn't satisfy a type variable's bounds
+    ///
+    /// Type variables with multiple bounds are checked against their intersection. This finds the
+    /// individual bound the argument's type doesn't satisfy.
+    pub fn with_unsatisfied_tvar_bound(self, tvar: &ty::TVarId) -> Error {
+        let value_poly = if let ErrorKind::IsNotTy(ref value_poly, _) = self.kind {
+            value_poly
+        } else {
+            return self;
+        };
+
+        if tvar.bounds().len() < 2 {
+            return self;
+        }
+
+        let kind = if let Some(bound_poly) = tvar.unsatisfied_bound(value_poly) {
+            ErrorKind::IsNotTVarBound(IsNotTVarBound {
+                value_poly: value_poly.clone(),
+                tvar: tvar.clone(),
+                bound_poly: bound_poly.clone(),
+            })
+        } else {
+            return self;
+        };
+
+        Error { kind, ..self }
+    }
+
+    /// Labels the values that selected the type of a type variable involved in the error
+    ///
+    /// This only applies to mismatched types; any other error is returned unchanged.
+    pub fn with_tvar_provenance(self, tvar: &ty::TVarId, origins: Vec<TVarOrigin>) -> Error {
+        if origins.is_empty() || !matches!(self.kind, ErrorKind::IsNotTy(_, _)) {
+            return self;
+        }
+
+        Error {
+            tvar_provenance: Some(Box::new(TVarProvenance {
+                tvar: tvar.clone(),
+                origins,
+            })),
+            ..self
+        }
+    }
+
     pub fn with_macro_invocation_span(self, span: Span) -> Error {
         Error {
             loc_trace: self.loc_trace.with_macro_invocation(span),
@@ -110,13 +211,22 @@ impl From<Error> for Diagnostic<FileId> {
                 hir::str_for_ty_ref(sub)
             )).with_labels(vec![new_primary_label(origin, "application requires function")]),
 
-            ErrorKind::IsNotTy(ref sub, ref parent) => Diagnostic::error()
-                .with_message("mismatched types")
-                .with_labels(vec![new_primary_label(origin, format!(
-                    "`{}` is not a `{}`",
-                    hir::str_for_ty_ref(sub),
-                    hir::str_for_ty_ref(parent)
-                ))]),
+            ErrorKind::IsNotTy(ref sub, ref parent) => {
+                let diagnostic = Diagnostic::error().with_message("mismatched types");
+
+                // Large types are hard to compare when printed in full on the same line
+                if let Some(ty_diff) = hir::str_for_ty_ref_diff(parent, sub) {
+                    diagnostic
+                        .with_labels(vec![new_primary_label(origin, "value has an unexpected type")])
+                        .with_notes(vec![ty_diff])
+                } else {
+                    diagnostic.with_labels(vec![new_primary_label(origin, format!(
+                        "`{}` is not a `{}`",
+                        hir::str_for_ty_ref(sub),
+                        hir::str_for_ty_ref(parent)
+                    ))])
+                }
+            }
 
             ErrorKind::IsNotPurity(ref fun, ref purity) => {
                 use crate::ty::purity::Purity;
@@ -166,6 +276,22 @@ impl From<Error> for Diagnostic<FileId> {
                 }
             }
 
+            ErrorKind::IsNotTVarBound(IsNotTVarBound {
+                value_poly,
+                tvar,
+                bound_poly,
+            }) => Diagnostic::error()
+                .with_message("mismatched types")
+                .with_labels(vec![
+                    new_primary_label(origin, format!(
+                        "`{}` does not satisfy the bound `{}` of `{}`",
+                        hir::str_for_ty_ref(value_poly),
+                        hir::str_for_ty_ref(bound_poly),
+                        tvar.source_name()
+                    )),
+                    new_secondary_label(tvar.span(), "type parameter declared here"),
+                ]),
+
             ErrorKind::VarHasEmptyType(ref current_type, ref required_type) => {
                 Diagnostic::error()
                   .with_message("type annotation needed")
@@ -208,11 +334,24 @@ impl From<Error> for Diagnostic<FileId> {
                     new_primary_label(origin, "recursive usage requires explicit type annotation")
                 ]),
 
+            ErrorKind::PolyRecursion(ref details) => Diagnostic::error()
+                .with_message("polymorphic function used recursively without a complete type annotation")
+                .with_labels(vec![
+                    new_primary_label(origin, "recursive usage requires a complete type annotation"),
+                    new_secondary_label(details.fun_span, format!(
+                        "declare {} for this function",
+                        details.missing_annotation
+                    )),
+                ])
+                .with_notes(vec![
+                    "type arguments for recursive usages can't be inferred from the function's own body".to_owned()
+                ]),
+
             ErrorKind::RecurWithoutFunTypeDecl => Diagnostic::error()
                 .with_message("type annotation needed")
                 .with_labels(vec![
                     new_primary_label(origin,
-                        "`(recur)` requires the function to have a complete type annotation"),
+                        "`(recur)` requires the function or loop to have a complete type annotation"),
                 ]),
 
             ErrorKind::NonTailRecur => Diagnostic::error()
@@ -243,7 +382,87 @@ impl From<Error> for Diagnostic<FileId> {
             )).with_labels(vec![
                 new_primary_label(origin,"at this application"),
                 new_secondary_label(tvar.span(), "type variable defined here")
-            ])
+            ]),
+
+            ErrorKind::WrongMainTy(ref main_type, reason) => {
+                let (message, label_message) = match reason {
+                    WrongMainTy::NotFun => ("`main!` must be a function", "not a function"),
+                    WrongMainTy::Polymorphic => (
+                        "`main!` cannot be polymorphic",
+                        "type or purity variables are not allowed",
+                    ),
+                    WrongMainTy::TakesParams => (
+                        "`main!` cannot take parameters",
+                        "expected no parameters",
+                    ),
+                    WrongMainTy::WrongRetTy => ("`main!` must return `()`", "unsupported return type"),
+                };
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![new_primary_label(origin, format!(
+                        "{}; `main!` has type `{}`",
+                        label_message,
+                        hir::str_for_ty_ref(main_type)
+                    ))])
+                    .with_notes(vec![
+                        "consider using the signature `(defn main! () ->! () ...)`".to_owned()
+                    ])
+            }
+
+            ErrorKind::ImplicitAnyParam(ref param_name) => Diagnostic::error()
+                .with_message(format!(
+                    "parameter `{}` has an implicit type of `Any`",
+                    param_name
+                ))
+                .with_labels(vec![new_primary_label(origin, "type annotation needed")])
+                .with_notes(vec![format!(
+                    "implicit `Any` parameters are forbidden in strict mode; consider declaring `[{} Any]`",
+                    param_name
+                )]),
+
+            ErrorKind::NonExhaustiveMatch(ref missing_type) => Diagnostic::error()
+                .with_message("non-exhaustive match")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    format!("`{}` not handled", hir::str_for_ty_ref(missing_type)),
+                )])
+                .with_notes(vec![
+                    "consider adding a clause for the missing type or a `_` clause".to_owned()
+                ]),
+
+            ErrorKind::NoMatchingOverload(ref overload, ref args) => Diagnostic::error()
+                .with_message("no matching overload")
+                .with_labels(vec![new_primary_label(
+                    origin,
+                    format!("arguments of type `{}` match no case", hir::str_for_ty_ref(args)),
+                )])
+                .with_notes(vec![format!(
+                    "function has type `{}`",
+                    hir::str_for_ty_ref(overload)
+                )]),
+        };
+
+        let diagnostic = if let Some(tvar_provenance) = error.tvar_provenance {
+            let TVarProvenance { tvar, origins } = *tvar_provenance;
+
+            diagnostic.with_labels(
+                origins
+                    .into_iter()
+                    .map(|origin| {
+                        new_secondary_label(
+                            origin.span,
+                            format!(
+                                "`{}` constrained to `{}` here",
+                                tvar.source_name(),
+                                hir::str_for_ty_ref(&origin.evidence)
+                            ),
+                        )
+                    })
+                    .collect(),
+            )
+        } else {
+            diagnostic
         };
 
         error.loc_trace.label_macro_invocation(diagnostic)
@@ -0,0 +1,163 @@
+//! Reporting of the types inferred for type holes
+
+use std::collections::HashMap;
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use arret_syntax::span::{FileId, Span};
+
+use crate::hir;
+use crate::hir::destruc;
+use crate::hir::visitor::visit_exprs;
+use crate::reporting::new_primary_label;
+use crate::ty;
+use crate::typeck::infer::InferredModule;
+
+type LocalTypes<'a> = HashMap<hir::LocalId, &'a ty::Ref<ty::Poly>>;
+
+fn add_scalar_local_types<'a>(
+    scalar: &'a destruc::Scalar<hir::Inferred>,
+    local_types: &mut LocalTypes<'a>,
+) {
+    if let Some(local_id) = scalar.local_id() {
+        local_types.insert(*local_id, scalar.ty());
+    }
+}
+
+fn add_destruc_local_types<'a>(
+    destruc: &'a destruc::Destruc<hir::Inferred>,
+    local_types: &mut LocalTypes<'a>,
+) {
+    match destruc {
+        destruc::Destruc::Scalar(_, scalar) => add_scalar_local_types(scalar, local_types),
+        destruc::Destruc::List(_, list) => add_list_local_types(list, local_types),
+        destruc::Destruc::Vector(_, vector) => {
+            for member in vector.members() {
+                add_destruc_local_types(member, local_types);
+            }
+        }
+        destruc::Destruc::Map(_, map) => {
+            for (_, value) in map.entries() {
+                add_destruc_local_types(value, local_types);
+            }
+        }
+    }
+}
+
+fn add_list_local_types<'a>(
+    list: &'a destruc::List<hir::Inferred>,
+    local_types: &mut LocalTypes<'a>,
+) {
+    for fixed in list.fixed() {
+        add_destruc_local_types(fixed, local_types);
+    }
+
+    // Rest destrucs are annotated with their member type so this is what their holes report
+    if let Some(rest) = list.rest() {
+        add_scalar_local_types(rest, local_types);
+    }
+}
+
+fn ty_hole_note(span: Span, inferred_type: &ty::Ref<ty::Poly>) -> Diagnostic<FileId> {
+    Diagnostic::note()
+        .with_message(format!(
+            "type hole inferred as `{}`",
+            hir::str_for_ty_ref(inferred_type)
+        ))
+        .with_labels(vec![new_primary_label(span, "type hole")])
+}
+
+/// Returns a note for each type hole describing the type inferred for it
+///
+/// Holes in code that failed to type check or was removed during type checking aren't reported.
+pub fn ty_hole_notes(
+    ty_holes: &[hir::TyHole],
+    inferred_module: &InferredModule,
+) -> Vec<Diagnostic<FileId>> {
+    if ty_holes.is_empty() {
+        return vec![];
+    }
+
+    let mut local_types: LocalTypes<'_> = HashMap::new();
+    let mut fun_ret_types: Vec<(Span, &ty::Ref<ty::Poly>)> = vec![];
+
+    for def in &inferred_module.defs {
+        add_destruc_local_types(&def.destruc, &mut local_types);
+
+        visit_exprs(&def.value_expr, &mut |expr| match &expr.kind {
+            hir::ExprKind::Fun(fun) => {
+                add_list_local_types(&fun.params, &mut local_types);
+
+                if let Some(ret_ty_span) = fun.ret_ty_span {
+                    fun_ret_types.push((ret_ty_span, &fun.ret_ty));
+                }
+            }
+            hir::ExprKind::Let(hir_let) => {
+                add_destruc_local_types(&hir_let.destruc, &mut local_types);
+            }
+            hir::ExprKind::Letrec(letrec) => {
+                for binding in &letrec.bindings {
+                    add_scalar_local_types(&binding.destruc, &mut local_types);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    ty_holes
+        .iter()
+        .filter_map(|ty_hole| {
+            let inferred_type = match ty_hole.kind {
+                hir::TyHoleKind::Local(local_id) => local_types.get(&local_id),
+                hir::TyHoleKind::FunRet => fun_ret_types
+                    .iter()
+                    .find(|(ret_ty_span, _)| *ret_ty_span == ty_hole.span)
+                    .map(|(_, ret_type)| ret_type),
+            }?;
+
+            Some(ty_hole_note(ty_hole.span, inferred_type))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::hir::lowering::module_for_file_str;
+    use crate::typeck::infer::infer_module;
+
+    fn ty_hole_messages(data_str: &str) -> Vec<String> {
+        let lowered_module = module_for_file_str(FileId::new(1), false, data_str).unwrap();
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+
+        ty_hole_notes(&lowered_module.ty_holes, &inferred_module)
+            .into_iter()
+            .map(|note| note.message)
+            .collect()
+    }
+
+    #[test]
+    fn local_ty_holes() {
+        assert_eq!(
+            vec!["type hole inferred as `1`"],
+            ty_hole_messages("(def [x _] 1)")
+        );
+
+        assert_eq!(
+            vec!["type hole inferred as `Float`"],
+            ty_hole_messages("(def x (let [[y _] 2.0] y))")
+        );
+    }
+
+    #[test]
+    fn fun_ty_holes() {
+        assert_eq!(
+            vec![
+                "type hole inferred as `Bool`",
+                "type hole inferred as `(U 'foo 'bar)`"
+            ],
+            ty_hole_messages("(def f (fn ([x _]) -> _ (if x 'foo 'bar)))")
+        );
+    }
+}
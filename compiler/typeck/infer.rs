@@ -1,11 +1,14 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::result;
 use std::sync::Arc;
 
 use crate::context::ModuleId;
+use crate::crash;
 use crate::hir;
 use crate::hir::destruc;
 use crate::rfi;
+use crate::trace;
 use crate::ty;
 use crate::ty::list_iter::ListIterator;
 use crate::ty::purity;
@@ -15,10 +18,12 @@ use crate::ty::ty_args::TyArgs;
 use crate::ty::Ty;
 use crate::typeck;
 use crate::typeck::dce::expr_can_side_effect;
-use crate::typeck::error::{Error, ErrorKind, IsNotRetTy, WantedArity};
+use crate::typeck::error::{Error, ErrorKind, IsNotRetTy, PolyRecursion, WantedArity, WrongMainTy};
+
+use codespan_reporting::diagnostic::Diagnostic;
 
 use arret_syntax::datum::Datum;
-use arret_syntax::span::Span;
+use arret_syntax::span::{FileId, Span};
 
 type Result<T> = result::Result<T, Error>;
 
@@ -56,23 +61,71 @@ struct VarTypeCond {
     override_type: ty::Ref<ty::Poly>,
 }
 
-impl VarTypeCond {
-    fn with_when(self, when: NodeBool) -> VarTypeCond {
-        VarTypeCond { when, ..self }
+impl NodeBool {
+    fn inverted(self) -> NodeBool {
+        match self {
+            NodeBool::True => NodeBool::False,
+            NodeBool::False => NodeBool::True,
+        }
     }
+}
 
+impl VarTypeCond {
     fn into_inverted(self) -> VarTypeCond {
         VarTypeCond {
-            when: if self.when == NodeBool::True {
-                NodeBool::False
-            } else {
-                NodeBool::True
-            },
+            when: self.when.inverted(),
             ..self
         }
     }
 }
 
+/// Type conditions of a cond branch along with the branch's result
+struct BranchConds<'a> {
+    /// Result of the branch if it's statically known
+    ///
+    /// Divergent branches are represented as `Some(None)` as they never produce a result.
+    node_bool: Option<Option<NodeBool>>,
+    type_conds: &'a [VarTypeCond],
+}
+
+impl BranchConds<'_> {
+    /// Returns if the branch can produce a result of `when`
+    fn can_produce(&self, when: NodeBool) -> bool {
+        match self.node_bool {
+            Some(Some(node_bool)) => node_bool == when,
+            Some(None) => false,
+            None => true,
+        }
+    }
+}
+
+fn branch_node_bool(node: &InferredNode) -> Option<Option<NodeBool>> {
+    if node.is_divergent() {
+        return Some(None);
+    }
+
+    try_to_bool(node.result_ty()).map(|value| {
+        Some(if value {
+            NodeBool::True
+        } else {
+            NodeBool::False
+        })
+    })
+}
+
+/// Returns the overriding type of the last condition for a local
+fn last_cond_type(
+    type_conds: &[VarTypeCond],
+    when: NodeBool,
+    local_id: hir::LocalId,
+) -> Option<&ty::Ref<ty::Poly>> {
+    type_conds
+        .iter()
+        .rev()
+        .find(|type_cond| type_cond.when == when && type_cond.override_local_id == local_id)
+        .map(|type_cond| &type_cond.override_type)
+}
+
 new_indexing_id_type!(FreeTyId, u32);
 new_indexing_id_type!(InputDefId, u32);
 
@@ -85,6 +138,7 @@ struct FunApp {
     rest_arg_expr: Option<hir::Expr<hir::Lowered>>,
 }
 
+#[derive(Clone)]
 enum VarType {
     // Introduced a definition that has yet to be processed
     Pending(InputDefId),
@@ -171,12 +225,64 @@ pub type InferredModuleVars = HashMap<ModuleId, Arc<InferredLocals>>;
 pub struct InferredModule {
     pub inferred_locals: InferredLocals,
     pub defs: Vec<hir::Def<hir::Inferred>>,
+    pub warnings: Vec<Diagnostic<FileId>>,
+}
+
+/// Maximum number of times a component of mutually recursive definitions is inferred
+///
+/// Each pass refines the types assumed for the component's functions. Components that haven't
+/// settled by this point require an explicit type annotation.
+const MAX_SCC_PASSES: usize = 16;
+
+/// Minimum number of definitions in a module before its independent definitions are inferred in
+/// parallel
+///
+/// Smaller modules are inferred faster than threads can be started for them.
+const MIN_PARALLEL_DEFS: usize = 64;
+
+/// Stack size for inference threads if the main thread's stack size is unknown or unlimited
+///
+/// This is the default main thread stack size on Linux.
+const FALLBACK_INFERENCE_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Returns the stack size to use for threads inferring definitions in parallel
+///
+/// Inference recurses through each expression. Threads need the same stack size as the main thread
+/// to accept every definition that would be accepted by serial inference.
+#[cfg(unix)]
+fn inference_thread_stack_size() -> usize {
+    let mut stack_limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    // This is used as the main thread's stack size
+    if unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut stack_limit) } != 0
+        || stack_limit.rlim_cur == libc::RLIM_INFINITY
+    {
+        return FALLBACK_INFERENCE_STACK_SIZE;
+    }
+
+    stack_limit.rlim_cur as usize
+}
+
+#[cfg(not(unix))]
+fn inference_thread_stack_size() -> usize {
+    FALLBACK_INFERENCE_STACK_SIZE
 }
 
 struct RecursiveDefsCtx<'types> {
     input_defs: Vec<InputDef>,
     complete_defs: Vec<hir::Def<hir::Inferred>>,
 
+    /// Types cached from a previous inference of the module's definitions
+    cached_def_types: HashMap<hir::LocalId, ty::Ref<ty::Poly>>,
+
+    /// Components of definitions referring to each other in a cycle
+    cyclic_sccs: Vec<Vec<InputDefId>>,
+    /// Index in to `cyclic_sccs` for each input definition
+    def_cyclic_scc_indices: Vec<Option<usize>>,
+
     // The inferred types for free types in the order they're encountered
     //
     // Each (def), (let) and (fn) push entries to `free_ty_polys` before they evaluate their body
@@ -185,6 +291,112 @@ struct RecursiveDefsCtx<'types> {
 
     self_locals: HashMap<hir::LocalId, VarType>,
     imported_vars: &'types InferredModuleVars,
+
+    /// Locals of module definitions currently having their type inferred, outermost first
+    recursive_def_locals: Vec<hir::LocalId>,
+
+    /// Recursive locals bound to polymorphic functions without a complete type annotation
+    incomplete_poly_funs: HashMap<hir::LocalId, PolyRecursion>,
+
+    /// Types of locals before occurrence typing narrowed them
+    narrowed_local_origins: HashMap<hir::LocalId, ty::Ref<ty::Poly>>,
+
+    /// Indicates if we're visiting a branch that's statically known to not be taken
+    in_untaken_branch: bool,
+
+    /// Number of enclosing function bodies being visited
+    fun_depth: usize,
+
+    warnings: Vec<Diagnostic<FileId>>,
+
+    /// Forbids undeclared function parameters from being inferred as `Any`
+    strict: bool,
+}
+
+/// Returns the declared type of a function expression if all of its types are explicitly declared
+fn decl_fun_type(expr: &hir::Expr<hir::Lowered>) -> Option<ty::Fun> {
+    match &expr.kind {
+        hir::ExprKind::MacroExpand(_, inner_expr) => decl_fun_type(inner_expr),
+        hir::ExprKind::Fun(decl_fun) => {
+            let purity = match &decl_fun.purity {
+                hir::DeclPurity::Known(poly_purity) => poly_purity.clone(),
+                hir::DeclPurity::Free => return None,
+            };
+
+            let ret_type = match &decl_fun.ret_ty {
+                hir::DeclTy::Known(poly) => poly.clone(),
+                hir::DeclTy::Free => return None,
+            };
+
+            if !typeck::destruc::list_destruc_is_fully_declared(&decl_fun.params) {
+                return None;
+            }
+
+            Some(ty::Fun::new(
+                decl_fun.pvars.clone(),
+                decl_fun.tvars.clone(),
+                ty::TopFun::new(purity, ret_type),
+                typeck::destruc::type_for_decl_list_destruc(&decl_fun.params, None),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type assumed for a monomorphic function before inferring its definition's component
+///
+/// Undeclared parameters are assumed to be `Any` and an undeclared return type is assumed to be
+/// `(U)`. These are refined by repeatedly inferring the component until its types are stable.
+fn scc_assumed_fun_type(expr: &hir::Expr<hir::Lowered>) -> Option<ty::Fun> {
+    match &expr.kind {
+        hir::ExprKind::MacroExpand(_, inner_expr) => scc_assumed_fun_type(inner_expr),
+        hir::ExprKind::Fun(decl_fun) if decl_fun.pvars.is_empty() && decl_fun.tvars.is_empty() => {
+            let purity = match &decl_fun.purity {
+                hir::DeclPurity::Known(poly_purity) => poly_purity.clone(),
+                hir::DeclPurity::Free => Purity::Pure.into(),
+            };
+
+            let ret_type = match &decl_fun.ret_ty {
+                hir::DeclTy::Known(poly) => poly.clone(),
+                hir::DeclTy::Free => Ty::never().into(),
+            };
+
+            Some(ty::Fun::new_mono(
+                typeck::destruc::type_for_decl_list_destruc(&decl_fun.params, None),
+                purity,
+                ret_type,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Returns details of a polymorphic function expression without a complete type annotation
+///
+/// These can't be used recursively as the type arguments for each usage are unknown.
+fn incomplete_poly_fun(expr: &hir::Expr<hir::Lowered>) -> Option<PolyRecursion> {
+    match &expr.kind {
+        hir::ExprKind::MacroExpand(_, inner_expr) => incomplete_poly_fun(inner_expr),
+        hir::ExprKind::Fun(decl_fun)
+            if !decl_fun.pvars.is_empty() || !decl_fun.tvars.is_empty() =>
+        {
+            let missing_params = !typeck::destruc::list_destruc_is_fully_declared(&decl_fun.params);
+            let missing_ret =
+                decl_fun.ret_ty == hir::DeclTy::Free || decl_fun.purity == hir::DeclPurity::Free;
+
+            let missing_annotation = match (missing_params, missing_ret) {
+                (true, true) => "parameter and return types",
+                (true, false) => "parameter types",
+                (false, true) => "a return type",
+                (false, false) => {
+                    return None;
+                }
+            };
+
+            Some(PolyRecursion::new(decl_fun.span, missing_annotation))
+        }
+        _ => None,
+    }
 }
 
 /// Tries to convert a polymorphic type to a literal boolean value
@@ -236,6 +448,75 @@ fn error_kind_for_type_error(
 }
 
 /// Ensures `value_poly` is appropriate for `value_use`
+/// Returns the type of a function implementing every case of an overload
+///
+/// This accepts the arguments of any case and returns the union of their return types.
+fn overload_impl_type(cases: &[&ty::Fun]) -> ty::Fun {
+    let fixed_len = cases
+        .iter()
+        .map(|case| case.params().fixed().len())
+        .min()
+        .unwrap_or(0);
+
+    let fixed = (0..fixed_len)
+        .map(|index| {
+            ty::unify::unify_ty_ref_iter(
+                cases
+                    .iter()
+                    .map(|case| case.params().fixed()[index].clone()),
+            )
+        })
+        .collect();
+
+    // Any remaining fixed parameters become part of the rest parameter
+    let rest = ty::unify::unify_ty_ref_iter(cases.iter().flat_map(|case| {
+        case.params().fixed()[fixed_len..]
+            .iter()
+            .chain(std::iter::once(case.params().rest()))
+            .cloned()
+    }));
+
+    let purity = cases
+        .iter()
+        .map(|case| case.purity().clone())
+        .reduce(|purity1, purity2| ty::unify::unify_purity_refs(&purity1, &purity2))
+        .unwrap_or_else(|| Purity::Pure.into());
+
+    let ret = ty::unify::unify_ty_ref_iter(cases.iter().map(|case| case.ret().clone()));
+
+    ty::Fun::new_mono(ty::List::new(fixed, rest), purity, ret)
+}
+
+/// Wraps the application of an overloaded function in a cast to the selected case's return type
+fn cast_overload_app(
+    span: Span,
+    impl_ret_type: ty::Ref<ty::Poly>,
+    app_node: InferredNode,
+) -> InferredNode {
+    let InferredNode {
+        expr: app_expr,
+        type_conds,
+    } = app_node;
+
+    let case_ret_type = app_expr.result_ty;
+
+    InferredNode {
+        expr: hir::Expr {
+            result_ty: case_ret_type.clone(),
+            kind: hir::ExprKind::Cast(Box::new(hir::Cast {
+                span,
+                ty_ref: case_ret_type,
+                value_expr: hir::Expr {
+                    result_ty: impl_ret_type,
+                    kind: app_expr.kind,
+                },
+                reason: None,
+            })),
+        },
+        type_conds,
+    }
+}
+
 fn ensure_is_a(
     span: Span,
     value_poly: &ty::Ref<ty::Poly>,
@@ -250,10 +531,51 @@ fn ensure_is_a(
         return Ok(());
     }
 
+    if value_poly == &Ty::Dyn.into() {
+        // This will be checked at runtime by `check_dyn_node`
+        return Ok(());
+    }
+
     let error_kind = error_kind_for_type_error(value_poly, value_use);
     Err(Error::new(span, error_kind))
 }
 
+/// Wraps a node producing a `Dyn` value in a runtime check of its required type
+///
+/// Values used as `Any` or a type variable don't need to be checked.
+fn check_dyn_node(result_use: &ResultUse<'_>, node: InferredNode) -> InferredNode {
+    let required_type = result_use.required_type();
+
+    if !result_use.is_used()
+        || node.result_ty() != &Ty::Dyn.into()
+        || !matches!(required_type, ty::Ref::Fixed(_))
+        || ty::is_a::ty_ref_is_a(&Ty::Any.into(), required_type)
+    {
+        return node;
+    }
+
+    // Other expressions pass their result use to the inner expression producing the `Dyn` value
+    let span = match &node.expr.kind {
+        hir::ExprKind::LocalRef(span, _) | hir::ExprKind::ExportRef(span, _) => *span,
+        hir::ExprKind::App(app) => app.span,
+        _ => {
+            return node;
+        }
+    };
+
+    InferredNode {
+        expr: hir::Expr {
+            result_ty: required_type.clone(),
+            kind: hir::ExprKind::DynCheck(Box::new(hir::DynCheck {
+                span,
+                ty_ref: required_type.clone(),
+                value_expr: node.expr,
+            })),
+        },
+        type_conds: node.type_conds,
+    }
+}
+
 fn member_type_for_poly_list(
     span: Span,
     poly_type: &ty::Ref<ty::Poly>,
@@ -317,6 +639,21 @@ impl<'types> RecursiveDefsCtx<'types> {
     ) -> RecursiveDefsCtx<'types> {
         let mut self_locals = HashMap::new();
 
+        let def_count = defs.len();
+        let input_def_id_for_index = |def_index: usize| InputDefId::new(def_count - def_index - 1);
+
+        let cyclic_sccs: Vec<Vec<InputDefId>> = typeck::scc::cyclic_def_sccs(&defs)
+            .into_iter()
+            .map(|scc| scc.into_iter().map(input_def_id_for_index).collect())
+            .collect();
+
+        let mut def_cyclic_scc_indices = vec![None; def_count];
+        for (scc_index, scc) in cyclic_sccs.iter().enumerate() {
+            for def_id in scc {
+                def_cyclic_scc_indices[def_id.to_usize()] = Some(scc_index);
+            }
+        }
+
         // We do this in reverse order because we infer our defs in reverse order. This doesn't
         // matter for correctness. However, presumably most definitions have more dependencies
         // before them than after them. Visiting them in forward order should cause less
@@ -331,10 +668,12 @@ impl<'types> RecursiveDefsCtx<'types> {
                 typeck::destruc::visit_locals(&hir_def.destruc, &mut |local_id, decl_type| {
                     let var_type = match decl_type {
                         hir::DeclTy::Known(poly_type) => VarType::Known(poly_type.clone()),
-                        hir::DeclTy::Free => {
+                        hir::DeclTy::Free => match decl_fun_type(&hir_def.value_expr) {
+                            // Fully declared functions can be referenced before they're visited
+                            Some(fun_type) => VarType::Known(fun_type.into()),
                             // Record the definition ID so we can deal with forward type references
-                            VarType::Pending(def_id)
-                        }
+                            None => VarType::Pending(def_id),
+                        },
                     };
 
                     self_locals.insert(local_id, var_type);
@@ -347,10 +686,20 @@ impl<'types> RecursiveDefsCtx<'types> {
         RecursiveDefsCtx {
             complete_defs: Vec::with_capacity(input_defs.len()),
             input_defs,
+            cached_def_types: HashMap::new(),
+            cyclic_sccs,
+            def_cyclic_scc_indices,
             free_ty_polys: vec![],
 
             self_locals,
             imported_vars,
+            recursive_def_locals: vec![],
+            incomplete_poly_funs: HashMap::new(),
+            narrowed_local_origins: HashMap::new(),
+            in_untaken_branch: false,
+            fun_depth: 0,
+            warnings: vec![],
+            strict: false,
         }
     }
 
@@ -429,24 +778,129 @@ impl<'types> RecursiveDefsCtx<'types> {
                     ..
                 } = *type_cond;
 
-                (
-                    override_local_id,
-                    self.self_locals
-                        .insert(override_local_id, VarType::Known(override_type.clone()))
-                        .unwrap(),
-                )
+                let original_var_type = self
+                    .self_locals
+                    .insert(override_local_id, VarType::Known(override_type.clone()))
+                    .unwrap();
+
+                // Remember the type before the outermost narrowing for exhaustiveness checking
+                let inserted_origin = match (
+                    &original_var_type,
+                    self.narrowed_local_origins.entry(override_local_id),
+                ) {
+                    (VarType::Known(original_type), Entry::Vacant(entry)) => {
+                        entry.insert(original_type.clone());
+                        true
+                    }
+                    _ => false,
+                };
+
+                (override_local_id, original_var_type, inserted_origin)
             })
-            .collect::<Vec<(hir::LocalId, VarType)>>();
+            .collect::<Vec<(hir::LocalId, VarType, bool)>>();
 
         let result = inner(self);
 
         // Restore the original types
         // We need to use `rev()` to make sure we restore the original type if multiple conds
         // applied to a single var.
-        for (local_id, original_var_type) in restore_var_types.into_iter().rev() {
+        for (local_id, original_var_type, inserted_origin) in restore_var_types.into_iter().rev() {
             self.self_locals.insert(local_id, original_var_type);
+
+            if inserted_origin {
+                self.narrowed_local_origins.remove(&local_id);
+            }
+        }
+
+        result
+    }
+
+    /// Merges the type conditions of a cond's test and branches in to conditions on its result
+    ///
+    /// A local's type when the cond has a given result is the union of its types in each branch
+    /// that can produce that result. This allows compound tests such as `(or)` to narrow locals
+    /// tested by each of their branches.
+    fn merge_branch_type_conds(
+        &self,
+        test_type_conds: &[VarTypeCond],
+        true_branch: BranchConds<'_>,
+        false_branch: BranchConds<'_>,
+    ) -> Vec<VarTypeCond> {
+        let mut local_ids: Vec<hir::LocalId> = vec![];
+        for type_cond in test_type_conds
+            .iter()
+            .chain(true_branch.type_conds)
+            .chain(false_branch.type_conds)
+        {
+            if !local_ids.contains(&type_cond.override_local_id) {
+                local_ids.push(type_cond.override_local_id);
+            }
+        }
+
+        let mut merged_type_conds = vec![];
+        for when in [NodeBool::True, NodeBool::False] {
+            let branches = [
+                (NodeBool::True, &true_branch),
+                (NodeBool::False, &false_branch),
+            ];
+
+            for &local_id in &local_ids {
+                let outer_type = match self.self_locals.get(&local_id) {
+                    Some(VarType::Known(outer_type)) => Some(outer_type),
+                    _ => None,
+                };
+
+                // Find the type of the local in each branch producing this result
+                let branch_types = branches
+                    .iter()
+                    .filter(|(_, branch)| branch.can_produce(when))
+                    .map(|(test_when, branch)| {
+                        last_cond_type(branch.type_conds, when, local_id)
+                            .or_else(|| last_cond_type(test_type_conds, *test_when, local_id))
+                            .or(outer_type)
+                    })
+                    .collect::<Option<Vec<&ty::Ref<ty::Poly>>>>();
+
+                let merged_type = match branch_types.and_then(|branch_types| {
+                    let mut branch_types = branch_types.into_iter();
+                    let first_type = branch_types.next()?.clone();
+
+                    Some(branch_types.fold(first_type, |merged_type, branch_type| {
+                        ty::unify::unify_to_ty_ref(&merged_type, branch_type)
+                    }))
+                }) {
+                    Some(merged_type) => merged_type,
+                    None => continue,
+                };
+
+                // Don't bother overriding with a type that isn't any narrower
+                if outer_type.map_or(false, |outer_type| {
+                    ty::is_a::ty_ref_is_a(outer_type, &merged_type)
+                }) {
+                    continue;
+                }
+
+                merged_type_conds.push(VarTypeCond {
+                    when,
+                    override_local_id: local_id,
+                    override_type: merged_type,
+                });
+            }
         }
 
+        merged_type_conds
+    }
+
+    fn with_untaken_branch<F, R>(&mut self, is_untaken: bool, inner: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        let old_in_untaken_branch = self.in_untaken_branch;
+        self.in_untaken_branch = old_in_untaken_branch || is_untaken;
+
+        let result = inner(self);
+
+        self.in_untaken_branch = old_in_untaken_branch;
         result
     }
 
@@ -481,15 +935,17 @@ impl<'types> RecursiveDefsCtx<'types> {
             }
         };
 
-        let true_node =
-            self.with_type_conds_applied(&test_node.type_conds, NodeBool::True, |s| {
+        let true_node = self.with_untaken_branch(test_known_bool == Some(false), |s| {
+            s.with_type_conds_applied(&test_node.type_conds, NodeBool::True, |s| {
                 s.visit_expr(pv, true_result_use, true_expr)
-            })?;
+            })
+        })?;
 
-        let false_node =
-            self.with_type_conds_applied(&test_node.type_conds, NodeBool::False, |s| {
+        let false_node = self.with_untaken_branch(test_known_bool == Some(true), |s| {
+            s.with_type_conds_applied(&test_node.type_conds, NodeBool::False, |s| {
                 s.visit_expr(pv, false_result_use, false_expr)
-            })?;
+            })
+        })?;
 
         if test_node.is_divergent() {
             // Test diverged; we don't need the branches
@@ -511,48 +967,17 @@ impl<'types> RecursiveDefsCtx<'types> {
                 let result_ty =
                     ty::unify::unify_to_ty_ref(true_node.result_ty(), false_node.result_ty());
 
-                let false_node_bool = try_to_bool(false_node.result_ty());
-                let true_node_bool = try_to_bool(true_node.result_ty());
-
-                let mut type_conds: Vec<VarTypeCond> = test_node
-                    .type_conds
-                    .into_iter()
-                    .filter_map(|type_cond| match type_cond.when {
-                        NodeBool::True => {
-                            if false_node_bool == Some(false) {
-                                // If the false node is always false then our result type being
-                                // true implies the test was true
-                                Some(type_cond.with_when(NodeBool::True))
-                            } else if false_node_bool == Some(true) {
-                                // If the false node is always true then our result type being
-                                // false implies the test was true
-                                Some(type_cond.with_when(NodeBool::False))
-                            } else {
-                                None
-                            }
-                        }
-                        NodeBool::False => {
-                            if true_node_bool == Some(true) {
-                                Some(type_cond.with_when(NodeBool::False))
-                            } else if true_node_bool == Some(false) {
-                                Some(type_cond.with_when(NodeBool::True))
-                            } else {
-                                None
-                            }
-                        }
-                    })
-                    .collect();
-
-                // If the false branch is always false we can move the occurrence typing from the
-                // true branch upwards. The same reasoning applies for the true branch. Note that
-                // this may override conds that we brought in from our test node. These should
-                // already have the outer occurrence typing applied so they will be more specific.
-                if false_node_bool == Some(false) {
-                    type_conds.extend(true_node.type_conds);
-                }
-                if true_node_bool == Some(true) {
-                    type_conds.extend(false_node.type_conds);
-                }
+                let type_conds = self.merge_branch_type_conds(
+                    &test_node.type_conds,
+                    BranchConds {
+                        node_bool: branch_node_bool(&true_node),
+                        type_conds: &true_node.type_conds,
+                    },
+                    BranchConds {
+                        node_bool: branch_node_bool(&false_node),
+                        type_conds: &false_node.type_conds,
+                    },
+                );
 
                 Ok(InferredNode {
                     expr: hir::Expr {
@@ -678,6 +1103,56 @@ impl<'types> RecursiveDefsCtx<'types> {
         })
     }
 
+    fn visit_no_clause_match(
+        &mut self,
+        result_use: &ResultUse<'_>,
+        no_clause_match: hir::NoClauseMatch,
+    ) -> Result<InferredNode> {
+        let hir::NoClauseMatch {
+            span,
+            subject_local_id,
+            kind,
+        } = no_clause_match;
+
+        if let Some(VarType::Known(remaining_type)) = self.self_locals.get(&subject_local_id) {
+            let original_type = self
+                .narrowed_local_origins
+                .get(&subject_local_id)
+                .unwrap_or(remaining_type);
+
+            if !self.in_untaken_branch && !remaining_type.is_never() && is_union_like(original_type)
+            {
+                match kind {
+                    hir::BranchKind::Match => {
+                        return Err(Error::new(
+                            span,
+                            ErrorKind::NonExhaustiveMatch(remaining_type.clone()),
+                        ));
+                    }
+                    hir::BranchKind::Cond => {
+                        let warning = non_exhaustive_cond_warning(span, remaining_type);
+                        self.warnings.push(warning);
+                    }
+                }
+            }
+        }
+
+        let unit_type = Ty::unit().into();
+        ensure_is_a(span, &unit_type, result_use)?;
+
+        Ok(InferredNode {
+            expr: hir::Expr {
+                result_ty: unit_type,
+                kind: hir::ExprKind::NoClauseMatch(Box::new(hir::NoClauseMatch {
+                    span,
+                    subject_local_id,
+                    kind,
+                })),
+            },
+            type_conds: vec![],
+        })
+    }
+
     fn visit_local_ref(
         &mut self,
         result_use: &ResultUse<'_>,
@@ -686,7 +1161,7 @@ impl<'types> RecursiveDefsCtx<'types> {
     ) -> Result<InferredNode> {
         let pending_def_id = match self.self_locals[&local_id] {
             VarType::Pending(def_id) => def_id,
-            VarType::Recursive => return Err(Error::new(span, ErrorKind::RecursiveType)),
+            VarType::Recursive => return Err(self.recursive_type_error(span, local_id)),
             VarType::Error => return Err(Error::new(span, ErrorKind::DependsOnError)),
             VarType::Known(ref known_type) => {
                 ensure_is_a(span, known_type, result_use)?;
@@ -723,6 +1198,34 @@ impl<'types> RecursiveDefsCtx<'types> {
         self.visit_local_ref(result_use, span, local_id)
     }
 
+    /// Returns the error for a reference to a local still having its type inferred
+    ///
+    /// If the recursion passes through a polymorphic function without a complete type annotation
+    /// this points to the annotation that would resolve it.
+    fn recursive_type_error(&self, span: Span, local_id: hir::LocalId) -> Error {
+        // Module definitions can recurse through each other; find the whole cycle
+        let cycle_locals = match self
+            .recursive_def_locals
+            .iter()
+            .rposition(|def_local_id| *def_local_id == local_id)
+        {
+            Some(cycle_start) => &self.recursive_def_locals[cycle_start..],
+            None => std::slice::from_ref(&local_id),
+        };
+
+        let poly_recursion = cycle_locals
+            .iter()
+            .find_map(|cycle_local_id| self.incomplete_poly_funs.get(cycle_local_id));
+
+        match poly_recursion {
+            Some(poly_recursion) => Error::new(
+                span,
+                ErrorKind::PolyRecursion(Box::new(poly_recursion.clone())),
+            ),
+            None => Error::new(span, ErrorKind::RecursiveType),
+        }
+    }
+
     fn visit_do(
         &mut self,
         pv: &mut PurityVar,
@@ -783,6 +1286,64 @@ impl<'types> RecursiveDefsCtx<'types> {
         }
     }
 
+    fn visit_values(
+        &mut self,
+        pv: &mut PurityVar,
+        result_use: &ResultUse<'_>,
+        span: Span,
+        member_exprs: Vec<hir::Expr<hir::Lowered>>,
+    ) -> Result<InferredNode> {
+        // Use the required type to guide the types of our members
+        let required_type = result_use.required_type();
+        let mut required_member_iter = match required_type.try_to_fixed() {
+            Some(Ty::Values(members)) if members.len() == member_exprs.len() => {
+                Some(ListIterator::new_fixed(members))
+            }
+            _ => ListIterator::try_new_from_ty_ref(required_type),
+        };
+
+        let any_type: ty::Ref<ty::Poly> = Ty::Any.into();
+        let mut is_divergent = false;
+
+        let mut member_types = Vec::with_capacity(member_exprs.len());
+        let mut inferred_member_exprs = Vec::with_capacity(member_exprs.len());
+
+        for member_expr in member_exprs {
+            let required_member_type = required_member_iter
+                .as_mut()
+                .and_then(|iter| iter.next())
+                .unwrap_or(&any_type);
+
+            let member_use = if result_use.is_used() {
+                ResultUse::InnerExpr(required_member_type)
+            } else {
+                ResultUse::Unused(&any_type)
+            };
+
+            let member_node = self.visit_expr(pv, &member_use, member_expr)?;
+
+            is_divergent = is_divergent || member_node.is_divergent();
+            member_types.push(member_node.result_ty().clone());
+            inferred_member_exprs.push(member_node.expr);
+        }
+
+        let result_ty: ty::Ref<ty::Poly> = if is_divergent {
+            Ty::never().into()
+        } else {
+            Ty::Values(member_types.into_boxed_slice()).into()
+        };
+
+        ensure_is_a(span, &result_ty, result_use)?;
+
+        Ok(InferredNode {
+            expr: hir::Expr {
+                result_ty,
+                kind: hir::ExprKind::Values(span, inferred_member_exprs),
+            },
+            type_conds: vec![],
+        })
+    }
+
     /// Visits a function expression
     ///
     /// This does a limited amount of backwards type propagation; it will attempt to fill in any
@@ -796,34 +1357,198 @@ impl<'types> RecursiveDefsCtx<'types> {
     ) -> Result<InferredNode> {
         let span = decl_fun.span;
 
-        // This is set to false if we encounter any free types in our params or ret
-        let mut decl_tys_are_known = true;
+        if decl_fun.pvars.is_empty() && decl_fun.tvars.is_empty() {
+            let required_type = result_use.required_type();
 
-        let required_fun_type = result_use.required_type().find_member(|t| {
-            if let Ty::Fun(fun) = t {
-                Some(fun.as_ref())
-            } else {
-                None
+            if let Some(cases) = required_type.try_to_fixed().and_then(Ty::overload_cases) {
+                return self.visit_overload_fun(required_type, &cases, decl_fun, self_local_id);
             }
-        });
+        }
 
-        let required_top_fun_type = required_fun_type.map(ty::Fun::top_fun).or_else(|| {
-            result_use.required_type().find_member(|t| {
-                if let Ty::TopFun(top_fun) = t {
-                    Some(top_fun.as_ref())
+        let is_guided = result_use
+            .required_type()
+            .find_member(|t| {
+                if let Ty::Fun(fun) = t {
+                    Some(fun.as_ref())
                 } else {
                     None
                 }
             })
-        });
-
-        let initial_param_type: ty::List<ty::Poly> = typeck::destruc::type_for_decl_list_destruc(
-            &decl_fun.params,
-            // Use the required type as a guide for any free types in the parameter list
-            required_fun_type.map(|fun| ListIterator::new(fun.params())),
-        );
+            .is_some();
 
-        // Bind all of our parameter variables
+        // Parameters guided by a required type were declared elsewhere
+        let strict_decl_params = if self.strict && !is_guided {
+            Some(decl_fun.params.clone())
+        } else {
+            None
+        };
+
+        // Functions bound by `(def)` or `(let)` are generalised over their unconstrained parameters.
+        // Nested functions are skipped as each generalisation attempt would otherwise be repeated
+        // every time an enclosing function is inferred again.
+        let generalisable_fun = if self_local_id.is_some()
+            && self.fun_depth == 0
+            && !is_guided
+            && typeck::destruc::has_free_scalar_param(&decl_fun.params)
+        {
+            Some(decl_fun.clone())
+        } else {
+            None
+        };
+
+        let warnings_start = self.warnings.len();
+        let mut node = self.visit_fun_once(result_use, decl_fun, self_local_id)?;
+        let warnings_end = self.warnings.len();
+
+        if let Some(decl_fun) = generalisable_fun {
+            if let Some(generalised_node) =
+                self.try_generalise_fun(result_use, decl_fun, self_local_id, &node)
+            {
+                // Our first attempt's warnings were reported again for the generalised function
+                self.warnings.drain(warnings_start..warnings_end);
+                node = generalised_node;
+            }
+        }
+
+        if let (Some(strict_decl_params), hir::ExprKind::Fun(revealed_fun)) =
+            (strict_decl_params, &node.expr.kind)
+        {
+            if let Some((param_span, param_name)) = typeck::destruc::find_implicit_any_param(
+                span,
+                &strict_decl_params,
+                &revealed_fun.params,
+            ) {
+                return Err(Error::new(
+                    param_span,
+                    ErrorKind::ImplicitAnyParam(param_name),
+                ));
+            }
+        }
+
+        Ok(node)
+    }
+
+    /// Visits a function expression required to be an overloaded function
+    ///
+    /// The function is checked separately against each case of the overload. Its body is then
+    /// inferred once more with parameters accepting the arguments of every case.
+    fn visit_overload_fun(
+        &mut self,
+        overload_type: &ty::Ref<ty::Poly>,
+        cases: &[&ty::Fun],
+        decl_fun: hir::Fun<hir::Lowered>,
+        self_local_id: Option<hir::LocalId>,
+    ) -> Result<InferredNode> {
+        let warnings_len = self.warnings.len();
+
+        for case in cases {
+            let case_type: ty::Ref<ty::Poly> = (*case).clone().into();
+
+            self.visit_fun_once(
+                &ResultUse::InnerExpr(&case_type),
+                decl_fun.clone(),
+                self_local_id,
+            )?;
+
+            // Only report warnings from the final inference
+            self.warnings.truncate(warnings_len);
+        }
+
+        let impl_type: ty::Ref<ty::Poly> = overload_impl_type(cases).into();
+        let mut node =
+            self.visit_fun_once(&ResultUse::InnerExpr(&impl_type), decl_fun, self_local_id)?;
+
+        node.expr.result_ty = overload_type.clone();
+        Ok(node)
+    }
+
+    /// Infers a function again with its implicit `Any` parameters declared as type variables
+    ///
+    /// This returns `None` if there are no implicit `Any` parameters or the function doesn't type
+    /// check once they're generalised.
+    fn try_generalise_fun(
+        &mut self,
+        result_use: &ResultUse<'_>,
+        mut decl_fun: hir::Fun<hir::Lowered>,
+        self_local_id: Option<hir::LocalId>,
+        node: &InferredNode,
+    ) -> Option<InferredNode> {
+        let revealed_fun = if let hir::ExprKind::Fun(revealed_fun) = &node.expr.kind {
+            revealed_fun
+        } else {
+            return None;
+        };
+
+        decl_fun.params = typeck::destruc::generalise_implicit_any_params(
+            &decl_fun.params,
+            &revealed_fun.params,
+            &mut decl_fun.tvars,
+        )?;
+
+        let free_ty_polys_len = self.free_ty_polys.len();
+        let warnings_len = self.warnings.len();
+        let self_locals = self.self_locals.clone();
+
+        match self.visit_fun_once(result_use, decl_fun, self_local_id) {
+            Ok(generalised_node) => Some(generalised_node),
+            Err(_) => {
+                // Restore the locals' types from our first attempt
+                self.self_locals = self_locals;
+                self.free_ty_polys.truncate(free_ty_polys_len);
+                self.warnings.truncate(warnings_len);
+                None
+            }
+        }
+    }
+
+    fn visit_fun_once(
+        &mut self,
+        result_use: &ResultUse<'_>,
+        mut decl_fun: hir::Fun<hir::Lowered>,
+        self_local_id: Option<hir::LocalId>,
+    ) -> Result<InferredNode> {
+        let span = decl_fun.span;
+
+        // This is set to false if we encounter any free types in our params or ret
+        let mut decl_tys_are_known = true;
+
+        let required_fun_type = result_use.required_type().find_member(|t| {
+            if let Ty::Fun(fun) = t {
+                Some(fun.as_ref())
+            } else {
+                None
+            }
+        });
+
+        if let Some(required_fun_type) = required_fun_type {
+            if required_fun_type.has_polymorphic_vars()
+                && decl_fun.pvars.is_empty()
+                && decl_fun.tvars.is_empty()
+            {
+                // We're being passed where a polymorphic function is expected. Our free types will
+                // be guided by its rigid type variables so we need to become polymorphic over them.
+                decl_fun.pvars = required_fun_type.pvars().to_vec();
+                decl_fun.tvars = required_fun_type.tvars().to_vec();
+            }
+        }
+
+        let required_top_fun_type = required_fun_type.map(ty::Fun::top_fun).or_else(|| {
+            result_use.required_type().find_member(|t| {
+                if let Ty::TopFun(top_fun) = t {
+                    Some(top_fun.as_ref())
+                } else {
+                    None
+                }
+            })
+        });
+
+        let initial_param_type: ty::List<ty::Poly> = typeck::destruc::type_for_decl_list_destruc(
+            &decl_fun.params,
+            // Use the required type as a guide for any free types in the parameter list
+            required_fun_type.map(|fun| ListIterator::new(fun.params())),
+        );
+
+        // Bind all of our parameter variables
         let free_ty_offset = self.destruc_list_value(
             &decl_fun.params,
             ListIterator::new(&initial_param_type),
@@ -886,7 +1611,11 @@ impl<'types> RecursiveDefsCtx<'types> {
             known_self_type: known_self_type.as_ref(),
         });
 
-        let body_node = self.visit_expr(&mut fun_pv, &body_result_use, decl_fun.body_expr)?;
+        self.fun_depth += 1;
+        let body_result = self.visit_expr(&mut fun_pv, &body_result_use, decl_fun.body_expr);
+        self.fun_depth -= 1;
+
+        let body_node = body_result?;
         let revealed_ret_type = body_node.result_ty();
         let revealed_purity = fun_pv.into_poly();
 
@@ -895,6 +1624,7 @@ impl<'types> RecursiveDefsCtx<'types> {
 
             destruc::subst_list_destruc(&mut inferred_free_types, decl_fun.params)
         };
+
         let revealed_param_type = hir::destruc::poly_for_list_destruc(&revealed_param_destruc);
 
         let revealed_type = ty::Fun::new(
@@ -1022,12 +1752,17 @@ impl<'types> RecursiveDefsCtx<'types> {
         } in non_fun_fixed_args
         {
             let wanted_arg_type = ty::subst::subst_poly(&non_fun_param_pta, param_type);
-            let fixed_arg_node =
-                self.visit_expr(pv, &ResultUse::InnerExpr(&wanted_arg_type), expr)?;
+            let arg_span = expr.span();
+            let fixed_arg_node = self
+                .visit_expr(pv, &ResultUse::InnerExpr(&wanted_arg_type), expr)
+                .map_err(|error| match param_type {
+                    ty::Ref::Var(tvar, _) => error.with_unsatisfied_tvar_bound(tvar),
+                    ty::Ref::Fixed(_) => error,
+                })?;
 
             is_divergent = is_divergent || fixed_arg_node.is_divergent();
 
-            fun_param_stx.add_evidence(param_type, fixed_arg_node.result_ty());
+            fun_param_stx.add_evidence_at(arg_span, param_type, fixed_arg_node.result_ty());
             inferred_fixed_arg_exprs.push((index, fixed_arg_node.expr));
         }
 
@@ -1035,12 +1770,13 @@ impl<'types> RecursiveDefsCtx<'types> {
         let inferred_rest_arg_expr = if let Some(rest_arg_expr) = rest_arg_expr {
             let tail_type = param_iter.tail_type().into();
             let wanted_tail_type = ty::subst::subst_poly(&non_fun_param_pta, &tail_type);
+            let rest_arg_span = rest_arg_expr.span();
             let rest_arg_node =
                 self.visit_expr(pv, &ResultUse::InnerExpr(&wanted_tail_type), rest_arg_expr)?;
 
             is_divergent = is_divergent || rest_arg_node.is_divergent();
 
-            fun_param_stx.add_evidence(&tail_type, rest_arg_node.result_ty());
+            fun_param_stx.add_evidence_at(rest_arg_span, &tail_type, rest_arg_node.result_ty());
             Some(rest_arg_node.expr)
         } else if param_iter.fixed_len() > 0 {
             // We wanted more args!
@@ -1066,18 +1802,30 @@ impl<'types> RecursiveDefsCtx<'types> {
         } in fun_fixed_args
         {
             let wanted_arg_type = ty::subst::subst_poly(&fun_param_pta, param_type);
-            let fixed_arg_node =
-                self.visit_expr(pv, &ResultUse::InnerExpr(&wanted_arg_type), expr)?;
+            let arg_span = expr.span();
+            let fixed_arg_node = self
+                .visit_expr(pv, &ResultUse::InnerExpr(&wanted_arg_type), expr)
+                .map_err(|error| {
+                    // The function's type was selected from the other arguments
+                    with_tvar_provenance(
+                        error,
+                        ret_stx.tvar_origins(),
+                        fun_type.tvars(),
+                        param_type,
+                        1,
+                    )
+                })?;
 
             is_divergent = is_divergent || fixed_arg_node.is_divergent();
 
-            ret_stx.add_evidence(param_type, fixed_arg_node.result_ty());
+            ret_stx.add_evidence_at(arg_span, param_type, fixed_arg_node.result_ty());
             inferred_fixed_arg_exprs.push((index, fixed_arg_node.expr));
         }
 
         inferred_fixed_arg_exprs.sort_unstable_by_key(|k| k.0);
         let inferred_fixed_arg_exprs = inferred_fixed_arg_exprs.into_iter().map(|e| e.1).collect();
 
+        let tvar_origins = ret_stx.take_tvar_origins();
         let ret_pta = ret_stx
             .into_complete_poly_ty_args()
             .map_err(|error| match error {
@@ -1099,7 +1847,10 @@ impl<'types> RecursiveDefsCtx<'types> {
         let app_purity = ty::subst::subst_purity(&ret_pta, fun_type.purity());
         unify_app_purity(pv, &app_purity);
 
-        ensure_is_a(span, &ret_type, result_use)?;
+        ensure_is_a(span, &ret_type, result_use).map_err(|error| {
+            // Only conflicting evidence explains a mismatched return type
+            with_tvar_provenance(error, &tvar_origins, fun_type.tvars(), fun_type.ret(), 2)
+        })?;
 
         Ok(InferredNode {
             expr: hir::Expr {
@@ -1116,6 +1867,125 @@ impl<'types> RecursiveDefsCtx<'types> {
         })
     }
 
+    /// Visit the application of an overloaded function
+    ///
+    /// If only one case accepts the number of supplied arguments it's applied like a normal
+    /// function. Otherwise the arguments are visited without any type information and the first
+    /// case accepting their types is selected.
+    ///
+    /// The application itself returns the return type of the overload's implementation. It's
+    /// wrapped in a cast to the return type of the selected case.
+    fn visit_overload_app(
+        &mut self,
+        pv: &mut PurityVar,
+        result_use: &ResultUse<'_>,
+        span: Span,
+        overload_type: &ty::Ref<ty::Poly>,
+        cases: &[&ty::Fun],
+        fun_app: FunApp,
+    ) -> Result<InferredNode> {
+        let impl_ret_type = overload_impl_type(cases).ret().clone();
+
+        if fun_app.rest_arg_expr.is_none() {
+            let supplied_arg_count = fun_app.fixed_arg_exprs.len();
+
+            let mut arity_cases = cases.iter().filter(|case| {
+                let fixed_len = case.params().fixed().len();
+
+                supplied_arg_count == fixed_len
+                    || (supplied_arg_count > fixed_len && !case.params().rest().is_never())
+            });
+
+            if let (Some(case), None) = (arity_cases.next(), arity_cases.next()) {
+                let app_node = self.visit_fun_app(pv, result_use, span, case, fun_app)?;
+                return Ok(cast_overload_app(span, impl_ret_type, app_node));
+            }
+        }
+
+        let FunApp {
+            fun_expr,
+            fixed_arg_exprs,
+            rest_arg_expr,
+        } = fun_app;
+
+        let mut is_divergent = false;
+
+        let mut fixed_arg_types = Vec::with_capacity(fixed_arg_exprs.len());
+        let mut inferred_fixed_arg_exprs = Vec::with_capacity(fixed_arg_exprs.len());
+
+        for fixed_arg_expr in fixed_arg_exprs {
+            let fixed_arg_node =
+                self.visit_expr(pv, &ResultUse::InnerExpr(&Ty::Any.into()), fixed_arg_expr)?;
+
+            is_divergent = is_divergent || fixed_arg_node.is_divergent();
+
+            fixed_arg_types.push(fixed_arg_node.result_ty().clone());
+            inferred_fixed_arg_exprs.push(fixed_arg_node.expr);
+        }
+
+        let (inferred_rest_arg_expr, args_type) = if let Some(rest_arg_expr) = rest_arg_expr {
+            let wanted_rest_type = ty::List::new_uniform(Ty::Any.into()).into();
+            let rest_arg_node =
+                self.visit_expr(pv, &ResultUse::InnerExpr(&wanted_rest_type), rest_arg_expr)?;
+
+            is_divergent = is_divergent || rest_arg_node.is_divergent();
+
+            let args_type = match ListIterator::try_new_from_ty_ref(rest_arg_node.result_ty()) {
+                Some(rest_iter) => {
+                    let rest_list = rest_iter.tail_type();
+                    fixed_arg_types.extend(rest_list.fixed().iter().cloned());
+
+                    ty::List::new(fixed_arg_types.into_boxed_slice(), rest_list.rest().clone())
+                }
+                None => ty::List::new(fixed_arg_types.into_boxed_slice(), Ty::Any.into()),
+            };
+
+            (Some(rest_arg_node.expr), args_type)
+        } else {
+            (
+                None,
+                ty::List::new_tuple(fixed_arg_types.into_boxed_slice()),
+            )
+        };
+
+        let args_type: ty::Ref<ty::Poly> = args_type.into();
+
+        let selected_case = cases
+            .iter()
+            .find(|case| ty::is_a::ty_ref_is_a(&args_type, &case.params().clone().into()))
+            .ok_or_else(|| {
+                Error::new(
+                    span,
+                    ErrorKind::NoMatchingOverload(overload_type.clone(), args_type.clone()),
+                )
+            })?;
+
+        let ret_type = if is_divergent {
+            Ty::never().into()
+        } else {
+            selected_case.ret().clone()
+        };
+
+        unify_app_purity(pv, selected_case.purity());
+        ensure_is_a(span, &ret_type, result_use)?;
+
+        let app_node = InferredNode {
+            expr: hir::Expr {
+                result_ty: ret_type,
+                kind: hir::ExprKind::App(Box::new(hir::App {
+                    span,
+                    fun_expr,
+                    ty_args: TyArgs::empty(),
+                    fixed_arg_exprs: inferred_fixed_arg_exprs,
+                    rest_arg_expr: inferred_rest_arg_expr,
+                })),
+            },
+            type_conds: vec![],
+        };
+
+        Ok(cast_overload_app(span, impl_ret_type, app_node))
+    }
+
     /// Visit a `(recur)`
     ///
     /// This is similar to `visit_fun_app`. However, we require that the `(recur)`'s arguments match
@@ -1578,7 +2448,31 @@ impl<'types> RecursiveDefsCtx<'types> {
             ty::TopFun::new(wanted_purity, result_use.required_type().clone()).into();
 
         let fun_node = self.visit_expr(pv, &ResultUse::InnerExpr(&wanted_fun_type), fun_expr)?;
-        let revealed_fun_type = fun_node.result_ty().clone();
+        let revealed_fun_type = match fun_node.result_ty() {
+            ty::Ref::Fixed(Ty::Recursive(instance)) => {
+                // Recursive types need to be unfolded to reveal the function they contain
+                ty::recursive::with_unfolded(instance, |unfolded| unfolded.clone())
+                    .unwrap_or_else(|| fun_node.result_ty().clone())
+            }
+            other => other.clone(),
+        };
+
+        if let Some(cases) = revealed_fun_type.resolve_to_ty().overload_cases() {
+            let fun_app = FunApp {
+                fun_expr: fun_node.expr,
+                fixed_arg_exprs,
+                rest_arg_expr,
+            };
+
+            return self.visit_overload_app(
+                pv,
+                result_use,
+                span,
+                &revealed_fun_type,
+                &cases,
+                fun_app,
+            );
+        }
 
         match revealed_fun_type.resolve_to_ty() {
             Ty::TopFun(_) => Err(Error::new(span, ErrorKind::TopFunApply(revealed_fun_type))),
@@ -1657,6 +2551,10 @@ impl<'types> RecursiveDefsCtx<'types> {
             self.self_locals.insert(local_id, var_type);
         });
 
+        if let Some(self_local_id) = self_local_id {
+            self.note_incomplete_poly_fun(self_local_id, &value_expr);
+        }
+
         let value_node = self.visit_expr_with_self_local_id(
             pv,
             &ResultUse::InnerExpr(&required_destruc_type),
@@ -1690,6 +2588,136 @@ impl<'types> RecursiveDefsCtx<'types> {
         })
     }
 
+    fn visit_letrec(
+        &mut self,
+        pv: &mut PurityVar,
+        result_use: &ResultUse<'_>,
+        hir_letrec: hir::Letrec<hir::Lowered>,
+    ) -> Result<InferredNode> {
+        let hir::Letrec {
+            span,
+            bindings,
+            body_expr,
+        } = hir_letrec;
+
+        // Pre-bind our variables so the functions can reference each other
+        for binding in &bindings {
+            if let Some(local_id) = *binding.destruc.local_id() {
+                let var_type = match binding.destruc.ty() {
+                    hir::DeclTy::Known(poly_type) => VarType::Known(poly_type.clone()),
+                    hir::DeclTy::Free => match decl_fun_type(&binding.value_expr) {
+                        Some(fun_type) => VarType::Known(fun_type.into()),
+                        None => VarType::Recursive,
+                    },
+                };
+
+                self.self_locals.insert(local_id, var_type);
+                self.note_incomplete_poly_fun(local_id, &binding.value_expr);
+            }
+        }
+
+        let mut inferred_bindings = Vec::with_capacity(bindings.len());
+        for hir::LetrecBinding {
+            destruc,
+            value_expr,
+        } in bindings
+        {
+            let required_type = match destruc.ty() {
+                hir::DeclTy::Known(poly_type) => poly_type.clone(),
+                hir::DeclTy::Free => Ty::Any.into(),
+            };
+
+            let value_node = self.visit_expr_with_self_local_id(
+                pv,
+                &ResultUse::InnerExpr(&required_type),
+                value_expr,
+                *destruc.local_id(),
+            )?;
+
+            let free_ty_offset = self.destruc_scalar_value(&destruc, value_node.result_ty(), false);
+            let mut inferred_free_types = self.free_ty_polys.drain(free_ty_offset..);
+
+            inferred_bindings.push(hir::LetrecBinding {
+                destruc: destruc::subst_scalar_destruc(&mut inferred_free_types, destruc),
+                value_expr: value_node.expr,
+            });
+        }
+
+        let body_node = self.visit_expr(pv, result_use, body_expr)?;
+
+        Ok(InferredNode {
+            expr: hir::Expr {
+                result_ty: body_node.result_ty().clone(),
+                kind: hir::ExprKind::Letrec(Box::new(hir::Letrec {
+                    span,
+                    bindings: inferred_bindings,
+                    body_expr: body_node.expr,
+                })),
+            },
+            type_conds: body_node.type_conds,
+        })
+    }
+
+    fn visit_unbox(
+        &mut self,
+        pv: &mut PurityVar,
+        result_use: &ResultUse<'_>,
+        unbox: hir::Unbox<hir::Lowered>,
+    ) -> Result<InferredNode> {
+        let hir::Unbox {
+            span,
+            ty_ref,
+            value_expr,
+        } = unbox;
+
+        // Our value must be of the unboxed type even if our result is unused
+        let value_node = self.visit_expr(pv, &ResultUse::InnerExpr(&ty_ref), value_expr)?;
+        ensure_is_a(span, &ty_ref, result_use)?;
+
+        Ok(InferredNode {
+            expr: hir::Expr {
+                result_ty: ty_ref.clone(),
+                kind: hir::ExprKind::Unbox(Box::new(hir::Unbox {
+                    span,
+                    ty_ref,
+                    value_expr: value_node.expr,
+                })),
+            },
+            type_conds: value_node.type_conds,
+        })
+    }
+
+    fn visit_cast(
+        &mut self,
+        pv: &mut PurityVar,
+        result_use: &ResultUse<'_>,
+        cast: hir::Cast<hir::Lowered>,
+    ) -> Result<InferredNode> {
+        let hir::Cast {
+            span,
+            ty_ref,
+            value_expr,
+            reason,
+        } = cast;
+
+        // The value can have any type; it's only its static type that changes
+        let value_node = self.visit_expr(pv, &ResultUse::InnerExpr(&Ty::Any.into()), value_expr)?;
+        ensure_is_a(span, &ty_ref, result_use)?;
+
+        Ok(InferredNode {
+            expr: hir::Expr {
+                result_ty: ty_ref.clone(),
+                kind: hir::ExprKind::Cast(Box::new(hir::Cast {
+                    span,
+                    ty_ref,
+                    value_expr: value_node.expr,
+                    reason,
+                })),
+            },
+            type_conds: vec![],
+        })
+    }
+
     fn visit_rust_fun(
         &self,
         result_use: &ResultUse<'_>,
@@ -1719,10 +2747,11 @@ impl<'types> RecursiveDefsCtx<'types> {
         self_local_id: Option<hir::LocalId>,
     ) -> Result<InferredNode> {
         use crate::hir::ExprKind;
-        match expr.kind {
+        let node = match expr.kind {
             ExprKind::Lit(datum) => self.visit_lit(result_use, datum),
             ExprKind::Cond(cond) => self.visit_cond(pv, result_use, *cond),
             ExprKind::Do(exprs) => self.visit_do(pv, result_use, exprs),
+            ExprKind::Values(span, exprs) => self.visit_values(pv, result_use, span, exprs),
             ExprKind::Fun(fun) => self.visit_fun(result_use, *fun, self_local_id),
             ExprKind::RustFun(rust_fun) => self.visit_rust_fun(result_use, rust_fun),
             ExprKind::TyPred(span, test_type) => self.visit_ty_pred(result_use, span, test_type),
@@ -1734,7 +2763,11 @@ impl<'types> RecursiveDefsCtx<'types> {
                 self.visit_field_accessor(result_use, field_accessor)
             }
             ExprKind::Let(hir_let) => self.visit_let(pv, result_use, *hir_let),
+            ExprKind::Letrec(hir_letrec) => self.visit_letrec(pv, result_use, *hir_letrec),
             ExprKind::LocalRef(span, local_id) => self.visit_local_ref(result_use, span, local_id),
+            ExprKind::NoClauseMatch(no_clause_match) => {
+                self.visit_no_clause_match(result_use, *no_clause_match)
+            }
             ExprKind::ExportRef(span, export_id) => {
                 self.visit_export_ref(result_use, span, export_id)
             }
@@ -1750,7 +2783,24 @@ impl<'types> RecursiveDefsCtx<'types> {
                     ..inferred
                 })
                 .map_err(|err| err.with_macro_invocation_span(span)),
-        }
+            ExprKind::Box(span, inner_expr) => {
+                self.visit_expr(pv, result_use, *inner_expr)
+                    .map(|inferred| InferredNode {
+                        expr: hir::Expr {
+                            result_ty: inferred.expr.result_ty.clone(),
+                            kind: ExprKind::Box(span, Box::new(inferred.expr)),
+                        },
+                        ..inferred
+                    })
+            }
+            ExprKind::Unbox(unbox) => self.visit_unbox(pv, result_use, *unbox),
+            ExprKind::Cast(cast) => self.visit_cast(pv, result_use, *cast),
+            ExprKind::DynCheck(_) => {
+                panic!("Dynamic checks are only inserted during type checking")
+            }
+        }?;
+
+        Ok(check_dyn_node(result_use, node))
     }
 
     fn visit_expr(
@@ -1857,34 +2907,86 @@ impl<'types> RecursiveDefsCtx<'types> {
                     .expect("Tried to destruc non-list");
                 self.destruc_list_value(list, value_type_iter, is_param)
             }
-        }
-    }
+            destruc::Destruc::Vector(_, vector) => {
+                let start_offset = self.free_ty_polys.len();
 
-    fn visit_def(&mut self, hir_def: hir::Def<hir::Lowered>) -> Result<hir::Def<hir::Inferred>> {
-        let hir::Def {
+                for (index, member) in vector.members().iter().enumerate() {
+                    let member_type = typeck::destruc::vector_member_type(value_type, index)
+                        .cloned()
+                        .unwrap_or_else(|| Ty::Any.into());
+
+                    self.destruc_value(member, &member_type, is_param);
+                }
+
+                start_offset
+            }
+            destruc::Destruc::Map(_, map) => {
+                let start_offset = self.free_ty_polys.len();
+
+                for (key, value) in map.entries() {
+                    let member_type = typeck::destruc::map_member_type(value_type, key)
+                        .cloned()
+                        .unwrap_or_else(|| Ty::Any.into());
+
+                    self.destruc_value(value, &member_type, is_param);
+                }
+
+                start_offset
+            }
+        }
+    }
+
+    fn visit_def(&mut self, hir_def: hir::Def<hir::Lowered>) -> Result<hir::Def<hir::Inferred>> {
+        let hir::Def {
             span,
             macro_invocation_span,
             destruc,
             value_expr,
+            doc,
+            is_const,
         } = hir_def;
 
+        let _span = trace::enter_with_args("typeck", "infer_def", || {
+            let def_name = match &destruc {
+                destruc::Destruc::Scalar(_, scalar) => scalar.source_name().to_string(),
+                _ => "<destructure>".to_owned(),
+            };
+
+            vec![("def", def_name)]
+        });
+
+        let _processing_span = crash::enter_span(span);
+
         // Module definitions must be pure
         let mut pv = PurityVar::Known(Purity::Pure.into());
 
-        // Mark all of our free typed variable as recursive
+        // Mark all of our free typed variable as recursive unless their type is already assumed
         let self_local_id = typeck::destruc::visit_locals(&destruc, &mut |local_id, decl_type| {
-            if *decl_type == hir::DeclTy::Free {
+            if *decl_type == hir::DeclTy::Free
+                && !matches!(self.self_locals.get(&local_id), Some(VarType::Known(_)))
+            {
                 self.self_locals.insert(local_id, VarType::Recursive);
             }
         });
 
+        if let Some(self_local_id) = self_local_id {
+            self.note_incomplete_poly_fun(self_local_id, &value_expr);
+            self.recursive_def_locals.push(self_local_id);
+        }
+
         let required_type = typeck::destruc::type_for_decl_destruc(&destruc, None);
-        let value_node = match self.visit_expr_with_self_local_id(
+        let value_result = self.visit_expr_with_self_local_id(
             &mut pv,
             &ResultUse::InnerExpr(&required_type),
             value_expr,
             self_local_id,
-        ) {
+        );
+
+        if self_local_id.is_some() {
+            self.recursive_def_locals.pop();
+        }
+
+        let value_node = match value_result {
             Ok(value_node) => value_node,
             Err(error) => {
                 // Mark this def as an error so we can suppress cascade errors
@@ -1903,41 +3005,177 @@ impl<'types> RecursiveDefsCtx<'types> {
             macro_invocation_span,
             destruc: destruc::subst_destruc(&mut inferred_free_types, destruc),
             value_expr: value_node.expr,
+            doc,
+            is_const,
         })
     }
 
-    fn recurse_into_def_id(&mut self, def_id: InputDefId) -> Result<()> {
-        let def_index = def_id.to_usize();
+    fn note_incomplete_poly_fun(
+        &mut self,
+        local_id: hir::LocalId,
+        value_expr: &hir::Expr<hir::Lowered>,
+    ) {
+        if let Some(poly_recursion) = incomplete_poly_fun(value_expr) {
+            self.incomplete_poly_funs.insert(local_id, poly_recursion);
+        }
+    }
 
-        let previous_state = std::mem::replace(&mut self.input_defs[def_index], InputDef::Complete);
+    fn take_pending_def(&mut self, def_id: InputDefId) -> hir::Def<hir::Lowered> {
+        let previous_state =
+            std::mem::replace(&mut self.input_defs[def_id.to_usize()], InputDef::Complete);
 
         if let InputDef::Pending(def) = previous_state {
-            let inferred_def = self.visit_def(def)?;
-            self.complete_defs.push(inferred_def);
+            def
         } else {
             panic!("Tried to infer already complete def. An error previously occurred?")
         }
+    }
+
+    /// Returns the types to initially assume for the functions of a cyclic component
+    ///
+    /// This returns `None` if the component can't be inferred together. This happens if any
+    /// definition without a declared type isn't a monomorphic function.
+    fn scc_assumed_types(
+        &self,
+        def_ids: &[InputDefId],
+    ) -> Option<Vec<(hir::LocalId, ty::Ref<ty::Poly>)>> {
+        let mut assumed_types = vec![];
+
+        for def_id in def_ids {
+            let def = match &self.input_defs[def_id.to_usize()] {
+                InputDef::Pending(def) => def,
+                InputDef::Complete => return None,
+            };
+
+            let scalar = match &def.destruc {
+                destruc::Destruc::Scalar(_, scalar) => scalar,
+                _ => return None,
+            };
+
+            let local_id = match scalar.local_id() {
+                Some(local_id) => *local_id,
+                None => continue,
+            };
+
+            if let Some(cached_type) = self.cached_def_types.get(&local_id) {
+                assumed_types.push((local_id, cached_type.clone()));
+            } else if *scalar.ty() == hir::DeclTy::Free && decl_fun_type(&def.value_expr).is_none()
+            {
+                let fun_type = scc_assumed_fun_type(&def.value_expr)?;
+                assumed_types.push((local_id, fun_type.into()));
+            }
+        }
+
+        Some(assumed_types)
+    }
+
+    /// Infers a component of mutually recursive definitions together
+    ///
+    /// The component is inferred with the assumed types for its functions. This repeats with the
+    /// newly inferred types until they stop changing.
+    fn visit_def_scc(
+        &mut self,
+        def_ids: &[InputDefId],
+        mut assumed_types: Vec<(hir::LocalId, ty::Ref<ty::Poly>)>,
+    ) -> Result<()> {
+        let defs: Vec<hir::Def<hir::Lowered>> = def_ids
+            .iter()
+            .map(|def_id| self.take_pending_def(*def_id))
+            .collect();
+
+        for _ in 0..MAX_SCC_PASSES {
+            let warnings_len = self.warnings.len();
+
+            for (local_id, assumed_type) in &assumed_types {
+                self.self_locals
+                    .insert(*local_id, VarType::Known(assumed_type.clone()));
+            }
+
+            let inferred_defs = match defs
+                .iter()
+                .map(|def| self.visit_def(def.clone()))
+                .collect::<Result<Vec<_>>>()
+            {
+                Ok(inferred_defs) => inferred_defs,
+                Err(error) => {
+                    self.mark_scc_locals_as_error(&assumed_types);
+                    return Err(error);
+                }
+            };
+
+            let inferred_types: Vec<(hir::LocalId, ty::Ref<ty::Poly>)> = assumed_types
+                .iter()
+                .map(|(local_id, _)| match &self.self_locals[local_id] {
+                    VarType::Known(inferred_type) => (*local_id, inferred_type.clone()),
+                    _ => panic!("def local not inferred"),
+                })
+                .collect();
+
+            // Unions can be built in a different order on each pass so compare by subtyping
+            let types_are_stable = inferred_types.iter().zip(assumed_types.iter()).all(
+                |((_, inferred_type), (_, assumed_type))| {
+                    ty::is_a::ty_ref_is_a(inferred_type, assumed_type)
+                        && ty::is_a::ty_ref_is_a(assumed_type, inferred_type)
+                },
+            );
+
+            if types_are_stable {
+                self.complete_defs.extend(inferred_defs);
+                return Ok(());
+            }
+
+            // Discard any warnings from this pass; they'll be raised again on the next one
+            self.warnings.truncate(warnings_len);
+            assumed_types = inferred_types;
+        }
+
+        self.mark_scc_locals_as_error(&assumed_types);
+        Err(Error::new(defs[0].span, ErrorKind::RecursiveType))
+    }
+
+    fn mark_scc_locals_as_error(&mut self, assumed_types: &[(hir::LocalId, ty::Ref<ty::Poly>)]) {
+        for (local_id, _) in assumed_types {
+            self.self_locals.insert(*local_id, VarType::Error);
+        }
+    }
+
+    fn recurse_into_def_id(&mut self, def_id: InputDefId) -> Result<()> {
+        if let Some(scc_index) = self.def_cyclic_scc_indices[def_id.to_usize()] {
+            let scc_def_ids = self.cyclic_sccs[scc_index].clone();
+
+            if let Some(assumed_types) = self.scc_assumed_types(&scc_def_ids) {
+                return self.visit_def_scc(&scc_def_ids, assumed_types);
+            }
+        }
+
+        let def = self.take_pending_def(def_id);
+        let inferred_def = self.visit_def(def)?;
+        self.complete_defs.push(inferred_def);
 
         Ok(())
     }
 
-    fn into_inferred_module(mut self) -> result::Result<InferredModule, Vec<Error>> {
+    /// Infers any remaining pending definitions and returns the inferred module
+    ///
+    /// This also returns the source index of the input definition whose inference completed each of
+    /// the module's definitions.
+    fn into_inferred_module(mut self) -> result::Result<(InferredModule, Vec<usize>), Vec<Error>> {
         let mut errs = vec![];
-        while let Some(def_state) = self.input_defs.pop() {
-            match def_state {
-                InputDef::Pending(def) => match self.visit_def(def) {
-                    Ok(inferred_def) => {
-                        self.complete_defs.push(inferred_def);
+        let def_count = self.input_defs.len();
+        let mut def_roots = Vec::with_capacity(def_count);
+
+        for def_index in (0..def_count).rev() {
+            if let InputDef::Pending(_) = self.input_defs[def_index] {
+                if let Err(err) = self.recurse_into_def_id(InputDefId::new(def_index)) {
+                    // If this is due to a previous error it's just noise to report it
+                    if err.kind() != &ErrorKind::DependsOnError {
+                        errs.push(err);
                     }
-                    Err(err) => {
-                        // If this is due to a previous error it's just noise to report it
-                        if err.kind() != &ErrorKind::DependsOnError {
-                            errs.push(err);
-                        }
-                    }
-                },
-                InputDef::Complete => {}
+                }
             }
+
+            // Input definitions are stored in reverse
+            def_roots.resize(self.complete_defs.len(), def_count - def_index - 1);
         }
 
         if !errs.is_empty() {
@@ -1953,10 +3191,104 @@ impl<'types> RecursiveDefsCtx<'types> {
             })
             .collect();
 
-        Ok(InferredModule {
+        let inferred_module = InferredModule {
             inferred_locals,
             defs: self.complete_defs,
-        })
+            warnings: self.warnings,
+        };
+
+        Ok((inferred_module, def_roots))
+    }
+}
+
+/// Returns up to two origins providing distinct evidence for a type variable
+fn distinct_tvar_origins(
+    tvar_origins: &ty::select::TVarOrigins,
+    tvar: &ty::TVarId,
+) -> Vec<ty::select::TVarOrigin> {
+    let mut distinct_origins: Vec<ty::select::TVarOrigin> = vec![];
+
+    for origin in tvar_origins.get(tvar).into_iter().flatten() {
+        if distinct_origins.len() == 2 {
+            break;
+        }
+
+        if !distinct_origins
+            .iter()
+            .any(|distinct| distinct.evidence == origin.evidence)
+        {
+            distinct_origins.push(origin.clone());
+        }
+    }
+
+    distinct_origins
+}
+
+/// Labels the values that selected a type variable appearing in `poly`
+///
+/// At least `min_origins` values with distinct types must have provided evidence for the type
+/// variable. Otherwise the error is returned unchanged.
+fn with_tvar_provenance(
+    error: Error,
+    tvar_origins: &ty::select::TVarOrigins,
+    tvars: &[ty::TVarId],
+    poly: &ty::Ref<ty::Poly>,
+    min_origins: usize,
+) -> Error {
+    let mut var_usages = ty::var_usage::VarUsages::new();
+    var_usages.add_poly_usages(poly);
+
+    for tvar in tvars {
+        if var_usages.tvar_variance(tvar).is_none() {
+            continue;
+        }
+
+        let origins = distinct_tvar_origins(tvar_origins, tvar);
+        if origins.len() >= min_origins {
+            return error.with_tvar_provenance(tvar, origins);
+        }
+    }
+
+    error
+}
+
+/// Returns true if a type is a union that can be exhaustively matched member by member
+fn is_union_like(poly: &ty::Ref<ty::Poly>) -> bool {
+    match poly {
+        ty::Ref::Fixed(Ty::Union(_)) | ty::Ref::Fixed(Ty::Bool) | ty::Ref::Fixed(Ty::Num) => true,
+        ty::Ref::Fixed(Ty::IntRange(range)) => range.as_singleton().is_none(),
+        _ => false,
+    }
+}
+
+fn non_exhaustive_cond_warning(span: Span, missing_type: &ty::Ref<ty::Poly>) -> Diagnostic<FileId> {
+    use crate::reporting::new_primary_label;
+
+    Diagnostic::warning()
+        .with_message("non-exhaustive cond")
+        .with_labels(vec![new_primary_label(
+            span,
+            format!("`{}` not handled", hir::str_for_ty_ref(missing_type)),
+        )])
+        .with_notes(vec![
+            "consider adding a clause for the missing type or an `:else` clause".to_owned(),
+        ])
+}
+
+/// Determines why the inferred type of `(main!)` isn't a supported main function type
+fn wrong_main_ty_reason(inferred_main_type: &ty::Ref<ty::Poly>) -> WrongMainTy {
+    let main_fun = if let ty::Ref::Fixed(Ty::Fun(main_fun)) = inferred_main_type {
+        main_fun
+    } else {
+        return WrongMainTy::NotFun;
+    };
+
+    if main_fun.has_polymorphic_vars() {
+        WrongMainTy::Polymorphic
+    } else if !main_fun.params().is_empty() {
+        WrongMainTy::TakesParams
+    } else {
+        WrongMainTy::WrongRetTy
     }
 }
 
@@ -1988,18 +3320,218 @@ pub fn ensure_main_type(
 
         return Err(Error::new_with_loc_trace(
             main_loc_trace,
-            ErrorKind::IsNotTy(inferred_main_type.clone(), expected_main_type),
+            ErrorKind::WrongMainTy(
+                inferred_main_type.clone(),
+                wrong_main_ty_reason(inferred_main_type),
+            ),
         ));
     };
 
     Ok(())
 }
 
+/// Infers the types of a module's definitions
+///
+/// If `strict` is set any function parameter without a declared type must be inferred as a type
+/// more specific than `Any`.
 pub fn infer_module(
     imported_inferred_vars: &InferredModuleVars,
     defs: Vec<hir::Def<hir::Lowered>>,
+    strict: bool,
+) -> result::Result<InferredModule, Vec<Error>> {
+    infer_module_with_cached_types(imported_inferred_vars, defs, strict, &HashMap::new())
+}
+
+/// Infers definitions on the current thread
+///
+/// See [`RecursiveDefsCtx::into_inferred_module`] for the meaning of the returned indices.
+fn infer_defs_serially(
+    imported_inferred_vars: &InferredModuleVars,
+    defs: Vec<hir::Def<hir::Lowered>>,
+    strict: bool,
+    cached_def_types: &typeck::cache::CachedDefTypes,
+) -> result::Result<(InferredModule, Vec<usize>), Vec<Error>> {
+    let mut rdcx = RecursiveDefsCtx::new(imported_inferred_vars, defs);
+    rdcx.strict = strict;
+
+    for input_def in &rdcx.input_defs {
+        let scalar = if let InputDef::Pending(hir::Def {
+            destruc: destruc::Destruc::Scalar(_, scalar),
+            ..
+        }) = input_def
+        {
+            scalar
+        } else {
+            continue;
+        };
+
+        let local_id = if let Some(local_id) = scalar.local_id() {
+            *local_id
+        } else {
+            continue;
+        };
+
+        if let (Some(cached_type), Some(VarType::Pending(_))) = (
+            cached_def_types.get(scalar.source_name()),
+            rdcx.self_locals.get(&local_id),
+        ) {
+            rdcx.self_locals
+                .insert(local_id, VarType::Known(cached_type.clone()));
+            rdcx.cached_def_types.insert(local_id, cached_type.clone());
+        }
+    }
+
+    rdcx.into_inferred_module()
+}
+
+/// Infers the types of a module's definitions starting from their previously inferred types
+///
+/// Any definition without a declared type in `cached_def_types` is assumed to have its cached type
+/// until it's visited. These must have been inferred from identical definitions and imports.
+///
+/// Groups of definitions independent of each other are inferred in parallel for larger modules.
+/// Errors and warnings are sorted by their location so they're reported in the same order
+/// regardless of scheduling.
+pub fn infer_module_with_cached_types(
+    imported_inferred_vars: &InferredModuleVars,
+    defs: Vec<hir::Def<hir::Lowered>>,
+    strict: bool,
+    cached_def_types: &typeck::cache::CachedDefTypes,
 ) -> result::Result<InferredModule, Vec<Error>> {
-    RecursiveDefsCtx::new(imported_inferred_vars, defs).into_inferred_module()
+    infer_defs_on_threads(
+        imported_inferred_vars,
+        defs,
+        strict,
+        cached_def_types,
+        num_cpus::get(),
+    )
+}
+
+fn infer_defs_on_threads(
+    imported_inferred_vars: &InferredModuleVars,
+    defs: Vec<hir::Def<hir::Lowered>>,
+    strict: bool,
+    cached_def_types: &typeck::cache::CachedDefTypes,
+    thread_count: usize,
+) -> result::Result<InferredModule, Vec<Error>> {
+    let def_groups = if defs.len() >= MIN_PARALLEL_DEFS && thread_count > 1 {
+        typeck::scc::independent_def_groups(&defs)
+    } else {
+        vec![]
+    };
+
+    if def_groups.len() < 2 {
+        return infer_defs_serially(imported_inferred_vars, defs, strict, cached_def_types)
+            .map(|(inferred_module, _)| inferred_module);
+    }
+
+    // Balance the groups between threads by their number of definitions
+    let mut batch_def_indices: Vec<Vec<usize>> = vec![vec![]; thread_count.min(def_groups.len())];
+    for def_group in def_groups {
+        let smallest_batch = batch_def_indices
+            .iter_mut()
+            .min_by_key(|def_indices| def_indices.len())
+            .unwrap();
+
+        smallest_batch.extend(def_group);
+    }
+
+    let mut unbatched_defs: Vec<Option<hir::Def<hir::Lowered>>> =
+        defs.into_iter().map(Some).collect();
+
+    // Keep each batch in source order
+    for def_indices in batch_def_indices.iter_mut() {
+        def_indices.sort_unstable();
+    }
+
+    let batch_defs: Vec<Vec<hir::Def<hir::Lowered>>> = batch_def_indices
+        .iter()
+        .map(|def_indices| {
+            def_indices
+                .iter()
+                .map(|def_index| unbatched_defs[*def_index].take().unwrap())
+                .collect()
+        })
+        .collect();
+
+    let stack_size = inference_thread_stack_size();
+    let batch_results: Vec<_> = crossbeam_utils::thread::scope(|scope| {
+        let batch_threads: Vec<_> = batch_defs
+            .into_iter()
+            .map(|defs| {
+                scope
+                    .builder()
+                    .name("type inference".to_owned())
+                    .stack_size(stack_size)
+                    .spawn(move |_| {
+                        infer_defs_serially(imported_inferred_vars, defs, strict, cached_def_types)
+                    })
+                    .expect("failed to spawn type inference thread")
+            })
+            .collect();
+
+        batch_threads
+            .into_iter()
+            .map(|batch_thread| {
+                batch_thread
+                    .join()
+                    .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+            })
+            .collect()
+    })
+    .unwrap_or_else(|payload| std::panic::resume_unwind(payload));
+
+    let mut inferred_module = InferredModule {
+        inferred_locals: HashMap::new(),
+        defs: vec![],
+        warnings: vec![],
+    };
+    let mut rooted_defs = vec![];
+    let mut errs = vec![];
+
+    for (def_indices, batch_result) in batch_def_indices.iter().zip(batch_results) {
+        match batch_result {
+            Ok((batch_module, def_roots)) => {
+                inferred_module
+                    .inferred_locals
+                    .extend(batch_module.inferred_locals);
+                inferred_module.warnings.extend(batch_module.warnings);
+
+                // Map the roots back to the module's definition indices
+                rooted_defs.extend(
+                    def_roots
+                        .into_iter()
+                        .map(|batch_def_index| def_indices[batch_def_index])
+                        .zip(batch_module.defs),
+                );
+            }
+            Err(batch_errs) => errs.extend(batch_errs),
+        }
+    }
+
+    if !errs.is_empty() {
+        errs.sort_by_key(|err| {
+            let origin = err.loc_trace().origin();
+            (origin.file_id(), origin.start())
+        });
+
+        return Err(errs);
+    }
+
+    inferred_module.warnings.sort_by_key(|warning| {
+        warning
+            .labels
+            .first()
+            .map(|label| (label.file_id, label.range.start))
+    });
+
+    // Independent groups never complete each other's definitions. Ordering by root gives the same
+    // order as serial inference where definitions follow their dependencies. This is a stable sort
+    // so each root's definitions stay in the order they were completed.
+    rooted_defs.sort_by_key(|(def_root, _)| *def_root);
+    inferred_module.defs = rooted_defs.into_iter().map(|(_, def)| def).collect();
+
+    Ok(inferred_module)
 }
 
 pub fn infer_repl_expr(
@@ -2015,7 +3547,7 @@ pub fn infer_repl_expr(
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::hir::lowering::expr_for_str;
+    use crate::hir::lowering::{expr_for_file_str, expr_for_str};
     use arret_syntax::span::t2s;
 
     fn type_for_expr(
@@ -2053,13 +3585,13 @@ mod test {
 
     #[test]
     fn literal_expr() {
-        assert_type_for_expr("Int", "1");
+        assert_type_for_expr("1", "1");
     }
 
     #[test]
     fn do_expr() {
         assert_type_for_expr("'()", "(do)");
-        assert_type_for_expr("Int", "(do 'one 'two 3)");
+        assert_type_for_expr("3", "(do 'one 'two 3)");
 
         // We have no diverging primitives so we can't test this case easily. This is covered in
         // run-pass.
@@ -2076,6 +3608,62 @@ mod test {
         assert_constrained_type_for_expr("true", "(if true true false)", "true");
     }
 
+    #[test]
+    fn and_occurrence_typing() {
+        // A false `(and)` doesn't tell us which of its tests failed
+        assert_type_for_expr(
+            "((RawU Int Str) Bool -> Bool)",
+            "(fn ([x (U Int Str)] [y Bool]) (if (if y (str? x) false) false (str? x)))",
+        );
+
+        assert_type_for_expr(
+            "((RawU Int Str) Bool -> Str)",
+            "(fn ([x (U Int Str)] [y Bool]) (if (and y (str? x)) x \"\"))",
+        );
+    }
+
+    #[test]
+    fn or_occurrence_typing() {
+        assert_type_for_expr(
+            "((RawU Int Str) -> Int)",
+            "(fn ([x (U Int Str)]) (if (or (str? x) false) 0 x))",
+        );
+    }
+
+    #[test]
+    fn not_occurrence_typing() {
+        // This is the expansion of `(not (str? x))`
+        assert_type_for_expr(
+            "((RawU Int Str) -> Int)",
+            "(fn ([x (U Int Str)]) (if (if (str? x) false true) x 0))",
+        );
+
+        assert_type_for_expr(
+            "((RawU Int Str) -> Str)",
+            "(fn ([x (U Int Str)]) (if (if (str? x) false true) \"\" x))",
+        );
+    }
+
+    #[test]
+    fn compound_occurrence_typing() {
+        // Either test of an `(or)` can narrow the same variable
+        assert_type_for_expr(
+            "((RawU Int Str Sym) -> (RawU Sym Str))",
+            "(fn ([x (U Int Str Sym)]) (if (or (str? x) (sym? x)) x \"\"))",
+        );
+
+        // A false `(and)` is the union of each test failing
+        assert_type_for_expr(
+            "((RawU Num Str) -> (RawU Str Float))",
+            "(fn ([x (U Num Str)]) (if (and (num? x) (int? x)) 1.0 x))",
+        );
+
+        assert_type_for_expr(
+            "((RawU Int Str Sym) -> Int)",
+            "(fn ([x (U Int Str Sym)]) (if (if (or (str? x) (sym? x)) false true) x 0))",
+        );
+    }
+
     #[test]
     fn fun_expr() {
         assert_type_for_expr("(-> ())", "(fn ())");
@@ -2125,6 +3713,142 @@ mod test {
         assert_type_error(&err, j);
     }
 
+    #[test]
+    fn let_polymorphism() {
+        let str_for_expr = |expr_str| {
+            let poly = type_for_expr(&Ty::Any.into(), expr_for_str(expr_str)).unwrap();
+            hir::str_for_ty_ref(&poly)
+        };
+
+        assert_eq!(
+            "(All #{A} A -> A)",
+            str_for_expr("(let [identity (fn (x) x)] identity)")
+        );
+        assert_eq!(
+            "(All #{A B} A B -> A)",
+            str_for_expr("(let [first (fn (x y) x)] first)")
+        );
+
+        // Constrained parameters aren't generalised
+        assert_eq!(
+            "(All #{A} A Bool -> A)",
+            str_for_expr("(let [f (fn (x y) (if y x x))] f)")
+        );
+
+        // Anonymous functions aren't generalised
+        assert_eq!("(Any -> Any)", str_for_expr("(fn (x) x)"));
+
+        // Functions nested inside another function aren't generalised
+        assert_eq!(
+            "(All #{A} A -> Any)",
+            str_for_expr("(let [f (fn (x) (let [g (fn (y) y)] (g x)))] f)")
+        );
+        assert_eq!(
+            "(All #{A} A -> (Any -> Any))",
+            str_for_expr("(let [f (fn (x) (let [g (fn (y) (let [h (fn (z) z)] h))] (g x)))] f)")
+        );
+
+        // Deeply nested functions are inferred in linear time
+        const NESTING_DEPTH: usize = 32;
+        let mut nested_expr_str = format!("x{}", NESTING_DEPTH);
+        for i in (0..NESTING_DEPTH).rev() {
+            nested_expr_str = format!(
+                "(let [f{i} (fn (x{j}) {body})] (f{i} x{i}))",
+                i = i,
+                j = i + 1,
+                body = nested_expr_str
+            );
+        }
+        assert_eq!(
+            "(Any -> Any)",
+            str_for_expr(&format!("(fn (x0) {})", nested_expr_str))
+        );
+    }
+
+    #[test]
+    fn higher_rank_params() {
+        let str_for_expr = |expr_str| {
+            let poly = type_for_expr(&Ty::Any.into(), expr_for_str(expr_str)).unwrap();
+            hir::str_for_ty_ref(&poly)
+        };
+
+        // The parameter can be applied to different types
+        assert_eq!(
+            "((All #{A} A -> A) -> Int)",
+            str_for_expr("(fn ([f (All #{A} A -> A)]) (do (f 'foo) (f 1)))")
+        );
+
+        // Anonymous functions passed as the parameter become polymorphic
+        assert_eq!(
+            "(All #{A} A -> A)",
+            str_for_expr("((fn ([f (All #{A} A -> A)]) f) (fn (x) x))")
+        );
+
+        // Monomorphic functions can't be passed
+        for expr_str in &[
+            "((fn ([f (All #{A} A -> A)]) (f 1)) (fn ([x Int]) x))",
+            "((fn ([f (All #{A} A -> A)]) (f 1)) (fn (x) 1))",
+        ] {
+            assert!(type_for_expr(&Ty::Any.into(), expr_for_str(expr_str)).is_err());
+        }
+    }
+
+    #[test]
+    fn multiple_tvar_bounds() {
+        let label_for_expr = |expr_str| {
+            let expr = expr_for_file_str(FileId::new(1), expr_str);
+            let diagnostic: Diagnostic<FileId> =
+                type_for_expr(&Ty::Any.into(), expr).unwrap_err().into();
+
+            diagnostic.labels[0].message.clone()
+        };
+
+        assert_type_for_expr(
+            "Int",
+            "((fn #{[A (RawU Int Sym Str) (RawU Int Sym Char)]} ([x A]) -> A x) 1)",
+        );
+
+        assert_eq!(
+            "`Str` does not satisfy the bound `(U Int Sym Char)` of `A`",
+            label_for_expr(
+                r#"((fn #{[A (RawU Int Sym Str) (RawU Int Sym Char)]} ([x A]) -> A x) "one")"#
+            )
+        );
+    }
+
+    #[test]
+    fn strict_fun_params() {
+        fn strict_type_for_expr(expr_str: &str) -> Result<ty::Ref<ty::Poly>> {
+            let imported_vars = HashMap::new();
+            let mut rdcx = RecursiveDefsCtx::new(&imported_vars, vec![]);
+            rdcx.strict = true;
+
+            let mut pv = PurityVar::Known(Purity::Pure.into());
+
+            rdcx.visit_expr(
+                &mut pv,
+                &ResultUse::InnerExpr(&Ty::Any.into()),
+                expr_for_str(expr_str),
+            )
+            .map(|node| node.expr.result_ty)
+        }
+
+        // Declared, refined and unused parameters are allowed
+        assert!(strict_type_for_expr("(fn ([x Any]) x)").is_ok());
+        assert!(strict_type_for_expr("(fn (x) (if x true false))").is_ok());
+        assert!(strict_type_for_expr("(fn (_) true)").is_ok());
+
+        let j = "(fn (x) x)";
+        let t = "     ^    ";
+        let err = Error::new(t2s(t), ErrorKind::ImplicitAnyParam("x".into()));
+        assert_eq!(err, strict_type_for_expr(j).unwrap_err());
+
+        let j = "(fn (& rest) rest)";
+        let t = "^^^^^^^^^^^^^^^^^^";
+        let err = Error::new(t2s(t), ErrorKind::ImplicitAnyParam("rest".into()));
+        assert_eq!(err, strict_type_for_expr(j).unwrap_err());
+    }
+
     #[test]
     fn app_types() {
         assert_type_for_expr("'foo", "((fn () 'foo))");
@@ -2196,6 +3920,417 @@ mod test {
         assert_type_for_expr("(->! false)", "(fn () ((fn () ->! false false)))");
     }
 
+    #[test]
+    fn def_purity() {
+        use crate::hir::lowering::module_for_file_str;
+
+        // Unannotated defs should be usable from a pure function even if they're defined later
+        let lowered_module = module_for_file_str(
+            None,
+            false,
+            "(def user (fn () -> Int (helper))) (def helper (fn () 1))",
+        )
+        .unwrap();
+
+        let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).unwrap();
+
+        let def_types: Vec<String> = inferred_module
+            .defs
+            .iter()
+            .map(|def| hir::str_for_ty_ref(&def.value_expr.result_ty))
+            .collect();
+
+        assert_eq!(vec!["(-> 1)", "(-> 1)"], def_types);
+    }
+
+    #[test]
+    fn newtypes() {
+        use crate::hir::lowering::module_for_file_str;
+
+        fn infer_defs(data_str: &str) -> Option<Vec<String>> {
+            let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+            let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).ok()?;
+
+            Some(
+                inferred_module
+                    .defs
+                    .iter()
+                    .map(|def| hir::str_for_ty_ref(&def.value_expr.result_ty))
+                    .collect(),
+            )
+        }
+
+        assert_eq!(
+            vec!["Meters", "Float"],
+            infer_defs(
+                "(defnewtype Meters Float) (def distance (->Meters 1.0)) (def raw (<-Meters distance))"
+            )
+            .unwrap()
+        );
+
+        // Newtypes are distinct from their representation in both directions
+        assert!(infer_defs("(defnewtype Meters Float) (def bad (->Meters 1))").is_none());
+        assert!(
+            infer_defs("(defnewtype Meters Float) (def bad (fn () -> Float (->Meters 1.0)))")
+                .is_none()
+        );
+        assert!(infer_defs("(defnewtype Meters Float) (def bad (<-Meters 1.0))").is_none());
+    }
+
+    #[test]
+    fn mutually_recursive_defs() {
+        use crate::hir::lowering::module_for_file_str;
+
+        fn infer_defs(data_str: &str) -> Option<Vec<String>> {
+            let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+            let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).ok()?;
+
+            Some(
+                inferred_module
+                    .defs
+                    .iter()
+                    .map(|def| hir::str_for_ty_ref(&def.value_expr.result_ty))
+                    .collect(),
+            )
+        }
+
+        assert_eq!(
+            vec!["(Bool -> 'done)", "(Bool -> 'done)"],
+            infer_defs(
+                "(def ping (fn ([n Bool]) (if n 'done (pong n)))) (def pong (fn ([n Bool]) (ping n)))"
+            )
+            .unwrap()
+        );
+
+        assert_eq!(
+            vec!["(Bool -> (U 'step 'base))", "(Bool -> (U 'base 'step))"],
+            infer_defs("(def base (fn ([n Bool]) (if n (step n) 'base))) (def step (fn ([n Bool]) (if n 'step (base n))))").unwrap()
+        );
+
+        // Arguments are still checked against the inferred parameter types
+        assert!(infer_defs(
+            "(def ping (fn ([n Bool]) (if n 'done (pong 1)))) (def pong (fn ([n Bool]) (ping n)))"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn parallel_independent_defs() {
+        use crate::hir::lowering::module_for_file_str;
+
+        let def_count = MIN_PARALLEL_DEFS * 2;
+        // Use multiple threads even if the host has a single CPU
+        let thread_count = 4;
+
+        // Every other definition depends on the one before it
+        let data_str: String = (0..def_count)
+            .map(|i| {
+                if i % 2 == 0 {
+                    format!("(def [d{} Int] {})", i, i)
+                } else {
+                    format!("(def d{} (fn () d{}))", i, i - 1)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let def_source_names =
+            |defs: &[hir::Def<hir::Inferred>]| -> Vec<arret_syntax::datum::DataStr> {
+                defs.iter()
+                    .filter_map(|def| match &def.destruc {
+                        destruc::Destruc::Scalar(_, scalar) => Some(scalar.source_name().clone()),
+                        _ => None,
+                    })
+                    .collect()
+            };
+
+        let lowered_module = module_for_file_str(None, false, &data_str).unwrap();
+        let (serial_module, _) =
+            infer_defs_serially(&HashMap::new(), lowered_module.defs, false, &HashMap::new())
+                .unwrap();
+
+        let lowered_module = module_for_file_str(None, false, &data_str).unwrap();
+        let inferred_module = infer_defs_on_threads(
+            &HashMap::new(),
+            lowered_module.defs,
+            false,
+            &HashMap::new(),
+            thread_count,
+        )
+        .unwrap();
+
+        assert_eq!(def_count, inferred_module.defs.len());
+        assert_eq!(def_count, inferred_module.inferred_locals.len());
+
+        // Definitions should be in the same order as if they were inferred serially
+        assert_eq!(
+            def_source_names(&serial_module.defs),
+            def_source_names(&inferred_module.defs)
+        );
+
+        for def in &inferred_module.defs {
+            if let destruc::Destruc::Scalar(_, scalar) = &def.destruc {
+                let inferred_type = hir::str_for_ty_ref(scalar.ty());
+
+                if scalar
+                    .source_name()
+                    .ends_with(&['0', '2', '4', '6', '8'][..])
+                {
+                    assert_eq!("Int", inferred_type);
+                } else {
+                    assert!(inferred_type.starts_with("(-> "));
+                }
+            }
+        }
+
+        // Errors should be reported in source order regardless of which thread found them
+        let data_str: String = (0..def_count)
+            .map(|i| {
+                format!(
+                    "(def [d{} Int] {})",
+                    i,
+                    if i % 16 == 0 { "'sym" } else { "1" }
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let lowered_module = module_for_file_str(None, false, &data_str).unwrap();
+        let errs = infer_defs_on_threads(
+            &HashMap::new(),
+            lowered_module.defs,
+            false,
+            &HashMap::new(),
+            thread_count,
+        )
+        .err()
+        .unwrap();
+
+        let err_starts: Vec<usize> = errs
+            .iter()
+            .map(|err| err.loc_trace().origin().start() as usize)
+            .collect();
+
+        assert_eq!(def_count / 16, err_starts.len());
+        assert!(err_starts.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn parallel_deeply_nested_def() {
+        use crate::hir::lowering::module_for_file_str;
+
+        // This requires more stack than a spawned thread's default in debug builds
+        const NESTING_DEPTH: usize = 500;
+
+        let deep_expr = format!(
+            "{}1{}",
+            "(if true ".repeat(NESTING_DEPTH),
+            " 2)".repeat(NESTING_DEPTH)
+        );
+
+        let data_str: String = (0..MIN_PARALLEL_DEFS)
+            .map(|i| format!("(def d{} {})", i, i))
+            .chain(std::iter::once(format!("(def deep {})", deep_expr)))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        // Test threads have a smaller stack than the main thread
+        std::thread::Builder::new()
+            .stack_size(inference_thread_stack_size())
+            .spawn(move || {
+                let lowered_module = module_for_file_str(None, false, &data_str).unwrap();
+                let inferred_module = infer_defs_on_threads(
+                    &HashMap::new(),
+                    lowered_module.defs,
+                    false,
+                    &HashMap::new(),
+                    4,
+                )
+                .unwrap();
+
+                assert_eq!(MIN_PARALLEL_DEFS + 1, inferred_module.defs.len());
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn tvar_provenance() {
+        use crate::hir::lowering::module_for_file_str;
+
+        fn infer_error_labels(data_str: &str) -> Vec<String> {
+            let lowered_module = module_for_file_str(FileId::new(1), false, data_str).unwrap();
+            let errs = infer_module(&HashMap::new(), lowered_module.defs, false)
+                .err()
+                .unwrap();
+
+            let diagnostic: Diagnostic<FileId> = errs.into_iter().next().unwrap().into();
+
+            diagnostic
+                .labels
+                .into_iter()
+                .map(|label| {
+                    format!(
+                        "{}: {}",
+                        &data_str[label.range.start..label.range.end],
+                        label.message
+                    )
+                })
+                .collect()
+        }
+
+        assert_eq!(
+            vec![
+                "(pick 1 'foo): `(U Int 'foo)` is not a `Int`",
+                "1: `A` constrained to `1` here",
+                "foo: `A` constrained to `'foo` here",
+            ],
+            infer_error_labels(
+                "(def pick (fn #{A} ([x A] [y A]) -> A x)) (def [r Int] (pick 1 'foo))"
+            )
+        );
+
+        // Function arguments are checked against the type selected from the other arguments
+        assert_eq!(
+            vec![
+                "(fn ([x Int]) x): `(Int -> Int)` is not a `(Str -> Int)`",
+                "\"one\": `A` constrained to `Str` here",
+            ],
+            infer_error_labels(
+                "(def apply-to (fn #{A} ([f (A -> Int)] [value A]) -> Int (f value)))
+                 (def r (apply-to (fn ([x Int]) x) \"one\"))"
+            )
+        );
+    }
+
+    #[test]
+    fn poly_recursion() {
+        use crate::hir::lowering::module_for_file_str;
+
+        fn infer_error_kinds(data_str: &str) -> Vec<ErrorKind> {
+            let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+
+            match infer_module(&HashMap::new(), lowered_module.defs, false) {
+                Ok(_) => vec![],
+                Err(errs) => errs.into_iter().map(|err| err.kind().clone()).collect(),
+            }
+        }
+
+        let error_kinds = infer_error_kinds("(def f (fn #{A} ([x A]) (f x)))");
+        assert!(matches!(
+            error_kinds.as_slice(),
+            [ErrorKind::PolyRecursion(_)]
+        ));
+
+        // Other values have the original error
+        assert_eq!(
+            vec![ErrorKind::RecursiveType],
+            infer_error_kinds("(def x (do x))")
+        );
+
+        // This should be detected through mutually recursive definitions
+        let error_kinds =
+            infer_error_kinds("(def g (fn ([y Int]) (f y))) (def f (fn #{A} ([x A]) (g 1) x))");
+        assert!(matches!(
+            error_kinds.as_slice(),
+            [ErrorKind::PolyRecursion(_)]
+        ));
+
+        // Declaring the return type should resolve it
+        assert_eq!(
+            Vec::<ErrorKind>::new(),
+            infer_error_kinds(
+                "(def g (fn ([y Int]) (f y))) (def f (fn #{A} ([x A]) -> A (g 1) x))"
+            )
+        );
+    }
+
+    #[test]
+    fn derived_ty_preds() {
+        use crate::hir::lowering::module_for_file_str;
+
+        fn infer_def(data_str: &str) -> bool {
+            let full_str = format!(
+                "(defrecord Point (point [x Int])) (defpred Point) {}",
+                data_str
+            );
+
+            let lowered_module = module_for_file_str(None, false, &full_str).unwrap();
+            infer_module(&HashMap::new(), lowered_module.defs, false).is_ok()
+        }
+
+        // The predicate should narrow to the record in the true branch
+        assert!(infer_def(
+            "(def f (fn ([v (U Point Str)]) -> Int (if (Point? v) (point-x v) 0)))"
+        ));
+
+        // ... and subtract it in the false branch
+        assert!(infer_def(
+            "(def f (fn ([v (U Point Str)]) -> Str (if (Point? v) \"point\" v)))"
+        ));
+        assert!(!infer_def(
+            "(def f (fn ([v (U Point Str)]) -> Str (if (Point? v) v \"other\")))"
+        ));
+    }
+
+    #[test]
+    fn overloads() {
+        use crate::hir::lowering::module_for_file_str;
+
+        fn infer_defs(data_str: &str) -> Option<Vec<String>> {
+            let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+            let inferred_module = infer_module(&HashMap::new(), lowered_module.defs, false).ok()?;
+
+            Some(
+                inferred_module
+                    .defs
+                    .iter()
+                    .map(|def| hir::str_for_ty_ref(&def.value_expr.result_ty))
+                    .collect(),
+            )
+        }
+
+        // The body is checked against each case separately
+        assert_eq!(
+            vec!["(Overload (Int -> Int) (Str -> Str))", "Int", "Str"],
+            infer_defs(
+                r#"(def [pick (Overload (Int -> Int) (Str -> Str))] (fn (x) (if (int? x) x "x")))
+                   (def int-result (pick 1))
+                   (def str-result (pick "one"))"#
+            )
+            .unwrap()
+        );
+
+        // Cases can have different arities
+        assert_eq!(
+            vec!["(Overload (Int -> Int) (Int Int -> Int))", "Int", "Int"],
+            infer_defs(
+                "(def [first-int (Overload (Int -> Int) (Int Int -> Int))] (fn (x & _) x))
+                 (def one (first-int 1))
+                 (def two (first-int 1 2))"
+            )
+            .unwrap()
+        );
+
+        assert!(infer_defs(
+            "(def [first-int (Overload (Int -> Int) (Int Int -> Int))] (fn (x & _) x))
+             (def three (first-int 1 2 3))"
+        )
+        .is_none());
+
+        // Every case must be satisfied by the body
+        assert!(
+            infer_defs("(def [bad (Overload (Int -> Int) (Str -> Str))] (fn (x) 1))").is_none()
+        );
+
+        // Applications must match a case
+        assert!(infer_defs(
+            "(def [pick (Overload (Int -> Int) (Str -> Str))] (fn (x) x)) (def bad (pick 'sym))"
+        )
+        .is_none());
+    }
+
     #[test]
     fn impure_app_within_pure() {
         // Calling an impure function inside a function declared as pure should fail
@@ -2231,16 +4366,29 @@ mod test {
 
     #[test]
     fn list_destruc() {
-        assert_type_for_expr("Int", "(let [(x) '(1)] x)");
+        assert_type_for_expr("1", "(let [(x) '(1)] x)");
         assert_type_for_expr(
             "(List true false)",
             "(let [(_ & rest) '(1 true false)] rest)",
         );
     }
 
+    #[test]
+    fn map_destruc() {
+        assert_type_for_expr(
+            "Str",
+            r#"(let [{:keys [name]} {:name "Ferris" :age 13}] name)"#,
+        );
+        assert_type_for_expr("13", r#"(let [{age :age} {:name "Ferris" :age 13}] age)"#);
+        assert_type_for_expr(
+            "((KeyedMap :name Str & Sym Int) -> Int)",
+            "(fn ([m (KeyedMap :name Str & Sym Int)]) (let [{:keys [age]} m] age))",
+        );
+    }
+
     #[test]
     fn var_ref() {
-        assert_type_for_expr("Int", "(let [x 1] x)")
+        assert_type_for_expr("1", "(let [x 1] x)")
     }
 
     #[test]
@@ -2256,7 +4404,95 @@ mod test {
         assert_type_for_expr("true", "(= 'foo 'foo)");
         assert_type_for_expr("false", "(= 'bar 'foo)");
 
-        // This looks stupid but we can only evaluate based on types. Both 1 and 2 are `Int`.
-        assert_type_for_expr("Bool", "(= 1 2)");
+        // Integer literals have singleton types
+        assert_type_for_expr("false", "(= 1 2)");
+        assert_type_for_expr("true", "(= 1 1)");
+        assert_type_for_expr("(Int -> Bool)", "(fn ([x Int]) (= x 1))");
+    }
+
+    #[test]
+    fn dyn_values() {
+        fn dyn_check_count(expr_str: &str) -> usize {
+            use crate::hir::visitor::visit_exprs;
+
+            let imported_vars = HashMap::new();
+            let mut rdcx = RecursiveDefsCtx::new(&imported_vars, vec![]);
+            let mut pv = PurityVar::Known(Purity::Pure.into());
+
+            let node = rdcx
+                .visit_expr(
+                    &mut pv,
+                    &ResultUse::InnerExpr(&Ty::Any.into()),
+                    expr_for_str(expr_str),
+                )
+                .unwrap();
+
+            let mut count = 0;
+            visit_exprs(&node.expr, &mut |expr| {
+                if let hir::ExprKind::DynCheck(_) = expr.kind {
+                    count += 1;
+                }
+            });
+
+            count
+        }
+
+        // `Dyn` values can be used as any type
+        assert_type_for_expr("(Dyn -> Int)", "(fn ([x Dyn]) -> Int x)");
+        assert_type_for_expr("(Dyn -> Bool)", "(fn ([x Dyn]) (if x true false))");
+        assert_type_for_expr("(Dyn -> Dyn)", "(fn ([x Dyn]) x)");
+
+        // Only uses as a more specific type are checked at runtime
+        assert_eq!(1, dyn_check_count("(fn ([x Dyn]) -> Int x)"));
+        assert_eq!(1, dyn_check_count("(fn ([x Dyn]) (if x true false))"));
+        assert_eq!(0, dyn_check_count("(fn ([x Dyn]) -> Any x)"));
+        assert_eq!(0, dyn_check_count("(fn ([x Dyn]) x)"));
+    }
+
+    fn warnings_for_expr(expr_str: &str) -> Vec<Diagnostic<FileId>> {
+        let imported_vars = HashMap::new();
+        let mut rdcx = RecursiveDefsCtx::new(&imported_vars, vec![]);
+
+        let mut pv = PurityVar::Known(Purity::Pure.into());
+        let expr = expr_for_file_str(FileId::new(1), expr_str);
+
+        rdcx.visit_expr(&mut pv, &ResultUse::InnerExpr(&Ty::Any.into()), expr)
+            .unwrap();
+
+        rdcx.warnings
+    }
+
+    #[test]
+    fn exhaustive_match() {
+        assert_type_for_expr(
+            "((RawU 'a 'b) -> (IntRange 1 2))",
+            "(fn ([x (U 'a 'b)]) (match x ['a 1] ['b 2]))",
+        );
+
+        // Non-union subjects don't need to be exhaustively matched
+        assert_type_for_expr("(Sym -> (RawU 1 '()))", "(fn ([x Sym]) (match x ['a 1]))");
+
+        let j = "(fn ([x (U 'a 'b 'c)]) (match x ['a 1]))";
+        let t = "                       ^^^^^^^^^^^^^^^^ ";
+        let err = Error::new(
+            t2s(t),
+            ErrorKind::NonExhaustiveMatch(hir::poly_for_str("(RawU 'b 'c)")),
+        );
+        assert_type_error(&err, j);
+    }
+
+    #[test]
+    fn exhaustive_cond() {
+        assert!(
+            warnings_for_expr("(fn ([x (U Int Str)]) (cond (int? x) 1 (str? x) 2))").is_empty()
+        );
+        assert!(
+            warnings_for_expr("(fn ([x (U Int Str Sym)]) (cond (int? x) 1 :else 2))").is_empty()
+        );
+
+        let warnings = warnings_for_expr("(fn ([x (U Int Str Sym)]) (cond (int? x) 1 (str? x) 2))");
+        assert_eq!(1, warnings.len());
+        assert_eq!("non-exhaustive cond", warnings[0].message);
+        assert_eq!("`Sym` not handled", warnings[0].labels[0].message);
     }
 }
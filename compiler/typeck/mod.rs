@@ -1,4 +1,7 @@
+pub mod cache;
 mod dce;
 mod destruc;
 pub mod error;
+pub mod hole;
 pub mod infer;
+mod scc;
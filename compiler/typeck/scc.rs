@@ -0,0 +1,237 @@
+//! Strongly connected components of a module's definitions
+//!
+//! Definitions referring to each other in a cycle can't be inferred one at a time. These cycles are
+//! found before type checking so each component can be inferred together once any of its
+//! definitions is visited.
+//!
+//! Definitions that don't refer to each other even indirectly are independent; they can be
+//! inferred in parallel.
+
+use std::collections::HashMap;
+
+use crate::hir;
+use crate::hir::visitor::visit_exprs;
+use crate::typeck;
+
+struct SccFinder<'a> {
+    edges: &'a [Vec<usize>],
+
+    next_index: usize,
+    indices: Vec<Option<usize>>,
+    low_links: Vec<usize>,
+
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+
+    sccs: Vec<Vec<usize>>,
+}
+
+impl<'a> SccFinder<'a> {
+    fn new(edges: &'a [Vec<usize>]) -> Self {
+        let def_count = edges.len();
+
+        SccFinder {
+            edges,
+            next_index: 0,
+            indices: vec![None; def_count],
+            low_links: vec![0; def_count],
+            stack: vec![],
+            on_stack: vec![false; def_count],
+            sccs: vec![],
+        }
+    }
+
+    /// Visits a definition using Tarjan's algorithm
+    fn visit(&mut self, def_index: usize) {
+        self.indices[def_index] = Some(self.next_index);
+        self.low_links[def_index] = self.next_index;
+        self.next_index += 1;
+
+        self.stack.push(def_index);
+        self.on_stack[def_index] = true;
+
+        for &dep_index in &self.edges[def_index] {
+            match self.indices[dep_index] {
+                None => {
+                    self.visit(dep_index);
+                    self.low_links[def_index] =
+                        self.low_links[def_index].min(self.low_links[dep_index]);
+                }
+                Some(dep_visit_index) if self.on_stack[dep_index] => {
+                    self.low_links[def_index] = self.low_links[def_index].min(dep_visit_index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_links[def_index]) == self.indices[def_index] {
+            let mut scc = vec![];
+
+            loop {
+                let member_index = self.stack.pop().unwrap();
+                self.on_stack[member_index] = false;
+                scc.push(member_index);
+
+                if member_index == def_index {
+                    break;
+                }
+            }
+
+            self.sccs.push(scc);
+        }
+    }
+}
+
+/// Returns the indices of the definitions each definition refers to
+fn def_edges(defs: &[hir::Def<hir::Lowered>]) -> Vec<Vec<usize>> {
+    let mut local_defs: HashMap<hir::LocalId, usize> = HashMap::new();
+
+    for (def_index, def) in defs.iter().enumerate() {
+        typeck::destruc::visit_locals(&def.destruc, &mut |local_id, _| {
+            local_defs.insert(local_id, def_index);
+        });
+    }
+
+    defs.iter()
+        .map(|def| {
+            let mut dep_indices = vec![];
+
+            visit_exprs(&def.value_expr, &mut |expr| {
+                if let hir::ExprKind::LocalRef(_, local_id) = expr.kind {
+                    if let Some(&dep_index) = local_defs.get(&local_id) {
+                        if !dep_indices.contains(&dep_index) {
+                            dep_indices.push(dep_index);
+                        }
+                    }
+                }
+            });
+
+            dep_indices
+        })
+        .collect()
+}
+
+/// Returns each component of the definitions containing a reference cycle
+///
+/// Definitions are identified by their index in `defs`. Both the components and their members are
+/// returned in source order.
+pub fn cyclic_def_sccs(defs: &[hir::Def<hir::Lowered>]) -> Vec<Vec<usize>> {
+    let edges = def_edges(defs);
+    let mut finder = SccFinder::new(&edges);
+
+    for def_index in 0..defs.len() {
+        if finder.indices[def_index].is_none() {
+            finder.visit(def_index);
+        }
+    }
+
+    let mut cyclic_sccs: Vec<Vec<usize>> = finder
+        .sccs
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || edges[scc[0]].contains(&scc[0]))
+        .map(|mut scc| {
+            scc.sort_unstable();
+            scc
+        })
+        .collect();
+
+    cyclic_sccs.sort_unstable_by_key(|scc| scc[0]);
+    cyclic_sccs
+}
+
+/// Returns the groups of definitions that are independent of each other
+///
+/// Two definitions are in the same group if either refers to the other directly or through other
+/// definitions in the module. Both the groups and their members are returned in source order.
+pub fn independent_def_groups(defs: &[hir::Def<hir::Lowered>]) -> Vec<Vec<usize>> {
+    fn find_root(parents: &mut [usize], def_index: usize) -> usize {
+        let parent_index = parents[def_index];
+        if parent_index == def_index {
+            return def_index;
+        }
+
+        let root_index = find_root(parents, parent_index);
+        parents[def_index] = root_index;
+        root_index
+    }
+
+    let mut parents: Vec<usize> = (0..defs.len()).collect();
+
+    for (def_index, dep_indices) in def_edges(defs).into_iter().enumerate() {
+        for dep_index in dep_indices {
+            let def_root = find_root(&mut parents, def_index);
+            let dep_root = find_root(&mut parents, dep_index);
+
+            // Join on the earliest definition so each root is the first member of its group
+            parents[def_root.max(dep_root)] = def_root.min(dep_root);
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut root_groups: HashMap<usize, usize> = HashMap::new();
+
+    for def_index in 0..defs.len() {
+        let root_index = find_root(&mut parents, def_index);
+
+        let group_index = *root_groups.entry(root_index).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+
+        groups[group_index].push(def_index);
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::hir::lowering::module_for_file_str;
+
+    fn sccs_for_str(data_str: &str) -> Vec<Vec<usize>> {
+        let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+        cyclic_def_sccs(&lowered_module.defs)
+    }
+
+    fn groups_for_str(data_str: &str) -> Vec<Vec<usize>> {
+        let lowered_module = module_for_file_str(None, false, data_str).unwrap();
+        independent_def_groups(&lowered_module.defs)
+    }
+
+    #[test]
+    fn acyclic_defs() {
+        assert!(sccs_for_str("(def a 1) (def b (fn () a)) (def c (fn () (b)))").is_empty());
+    }
+
+    #[test]
+    fn self_recursive_def() {
+        assert_eq!(
+            vec![vec![1]],
+            sccs_for_str("(def a 1) (def f (fn () (f))) (def g (fn () (f)))")
+        );
+    }
+
+    #[test]
+    fn mutually_recursive_defs() {
+        assert_eq!(
+            vec![vec![0, 2], vec![3, 4]],
+            sccs_for_str(
+                "(def even (fn () (odd))) (def other 1) (def odd (fn () (even)))
+                 (def ping (fn () (pong))) (def pong (fn () (ping) (even)))"
+            )
+        );
+    }
+
+    #[test]
+    fn independent_groups() {
+        assert_eq!(
+            vec![vec![0, 2, 3], vec![1], vec![4, 5]],
+            groups_for_str(
+                "(def a 1) (def b 2) (def c (fn () a)) (def d (fn () (c)))
+                 (def e (fn () (f))) (def f (fn () (e)))"
+            )
+        );
+    }
+}
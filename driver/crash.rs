@@ -0,0 +1,277 @@
+//! Reporting of internal compiler errors
+//!
+//! Panics inside the compiler are caught by the driver and reported along with the span the
+//! compiler was processing. A self-contained reproduction can optionally be written for bug
+//! reports.
+
+use std::ffi::OsStr;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::{env, fs, io, path, process};
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use arret_compiler::{emit_diagnostics_to_stderr, SourceLoader};
+use arret_syntax::span::{FileId, Span};
+
+/// Exit code used after an internal compiler error
+///
+/// This matches the exit code of an uncaught Rust panic.
+const CRASH_EXIT_CODE: i32 = 101;
+
+/// Details of the first panic captured by our panic hook
+struct CapturedPanic {
+    message: String,
+    location: Option<String>,
+    processing_span: Option<Span>,
+}
+
+pub struct CrashReporter<'a> {
+    subcommand: &'static str,
+    arret_root_dir: &'a path::Path,
+    repro_dir: Option<&'a path::Path>,
+    captured_panic: Arc<Mutex<Option<CapturedPanic>>>,
+}
+
+fn panic_payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(unsupported) = payload.downcast_ref::<arret_compiler::crash::Unsupported>() {
+        format!(
+            "unsupported construct escaped its handler: {}",
+            unsupported.description()
+        )
+    } else {
+        "<non-string panic payload>".to_owned()
+    }
+}
+
+/// Returns a filesystem-safe version of a loaded file's name
+fn sanitised_file_name(filename: &OsStr) -> String {
+    let file_name = path::Path::new(filename)
+        .file_name()
+        .unwrap_or(filename)
+        .to_string_lossy();
+
+    file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl<'a> CrashReporter<'a> {
+    /// Installs a panic hook capturing the details of compiler panics
+    ///
+    /// The previous panic hook is still invoked so the panic message and any backtrace are
+    /// printed as usual.
+    pub fn install(
+        subcommand: &'static str,
+        arret_root_dir: &'a path::Path,
+        repro_dir: Option<&'a path::Path>,
+    ) -> Self {
+        let captured_panic: Arc<Mutex<Option<CapturedPanic>>> = Arc::new(Mutex::new(None));
+        let hook_captured_panic = captured_panic.clone();
+
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            if let Ok(mut captured_panic) = hook_captured_panic.lock() {
+                // Later panics are typically other threads failing after the original panic
+                if captured_panic.is_none() {
+                    *captured_panic = Some(CapturedPanic {
+                        message: panic_payload_message(panic_info.payload()),
+                        location: panic_info.location().map(ToString::to_string),
+                        processing_span: arret_compiler::crash::processing_span(),
+                    });
+                }
+            }
+
+            previous_hook(panic_info);
+        }));
+
+        CrashReporter {
+            subcommand,
+            arret_root_dir,
+            repro_dir,
+            captured_panic,
+        }
+    }
+
+    /// Runs the subcommand's work, reporting an internal compiler error if it panics
+    ///
+    /// `input_file_id` is used to name the input file in a written reproduction. This exits the
+    /// process if a panic is caught.
+    pub fn catch<F>(
+        &self,
+        source_loader: &SourceLoader,
+        input_file_id: Option<FileId>,
+        work: F,
+    ) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(work)) {
+            Ok(succeeded) => {
+                return succeeded;
+            }
+            Err(payload) => payload,
+        };
+
+        let captured_panic = self
+            .captured_panic
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+            .unwrap_or_else(|| CapturedPanic {
+                message: panic_payload_message(payload.as_ref()),
+                location: None,
+                processing_span: None,
+            });
+
+        self.report(source_loader, &captured_panic);
+
+        if let Some(repro_dir) = self.repro_dir {
+            match self.write_reproduction(repro_dir, source_loader, input_file_id, &captured_panic)
+            {
+                Ok(()) => {
+                    eprintln!("note: wrote a reproduction to `{}`", repro_dir.display());
+                }
+                Err(err) => {
+                    eprintln!(
+                        "note: unable to write a reproduction to `{}`: {}",
+                        repro_dir.display(),
+                        err
+                    );
+                }
+            }
+        } else {
+            eprintln!(
+                "note: run again with `-Z crash-repro=<dir>` to write a reproduction for a bug report"
+            );
+        }
+
+        process::exit(CRASH_EXIT_CODE);
+    }
+
+    fn report(&self, source_loader: &SourceLoader, captured_panic: &CapturedPanic) {
+        let mut notes = vec![
+            "the compiler unexpectedly panicked. This is a bug.".to_owned(),
+            format!(
+                "arret {} running `{}`",
+                clap::crate_version!(),
+                self.subcommand
+            ),
+        ];
+
+        if let Some(location) = &captured_panic.location {
+            notes.push(format!("panicked at {}", location));
+        }
+
+        let processing_label = captured_panic
+            .processing_span
+            .and_then(|span| Some(Label::primary(span.file_id()?, span.byte_range())))
+            .map(|label| label.with_message("while processing this"));
+
+        let diagnostic = Diagnostic::bug()
+            .with_message(format!(
+                "internal compiler error: {}",
+                captured_panic.message
+            ))
+            .with_labels(processing_label.into_iter().collect())
+            .with_notes(notes);
+
+        emit_diagnostics_to_stderr(source_loader, vec![diagnostic]);
+    }
+
+    /// Returns the path a loaded file should be written to inside a reproduction
+    fn repro_file_path(
+        &self,
+        file_id: FileId,
+        filename: &OsStr,
+        input_file_id: Option<FileId>,
+    ) -> path::PathBuf {
+        if Some(file_id) == input_file_id {
+            return path::PathBuf::from("input.arret");
+        }
+
+        if let Ok(relative_path) = path::Path::new(filename).strip_prefix(self.arret_root_dir) {
+            if relative_path.is_relative() && relative_path.file_name().is_some() {
+                return relative_path.to_owned();
+            }
+        }
+
+        path::Path::new("other").join(format!("{}-{}", file_id, sanitised_file_name(filename)))
+    }
+
+    fn write_reproduction(
+        &self,
+        repro_dir: &path::Path,
+        source_loader: &SourceLoader,
+        input_file_id: Option<FileId>,
+        captured_panic: &CapturedPanic,
+    ) -> io::Result<()> {
+        fs::create_dir_all(repro_dir)?;
+
+        let mut crash_report = fs::File::create(repro_dir.join("crash-report.txt"))?;
+        writeln!(crash_report, "arret {}", clap::crate_version!())?;
+        writeln!(crash_report, "subcommand: {}", self.subcommand)?;
+        writeln!(
+            crash_report,
+            "arguments: {}",
+            env::args().collect::<Vec<String>>().join(" ")
+        )?;
+        writeln!(crash_report, "message: {}", captured_panic.message)?;
+
+        if let Some(location) = &captured_panic.location {
+            writeln!(crash_report, "panicked at: {}", location)?;
+        }
+
+        if let Some(span) = captured_panic.processing_span {
+            if let (Some(filename), Some(line_col_range)) = (
+                source_loader.span_to_filename(span),
+                source_loader.span_to_line_col_range(span),
+            ) {
+                writeln!(
+                    crash_report,
+                    "processing: {}:{}:{}",
+                    filename,
+                    line_col_range.start.line + 1,
+                    line_col_range.start.column + 1
+                )?;
+            }
+        }
+
+        writeln!(crash_report)?;
+        writeln!(crash_report, "files:")?;
+
+        for (index, (filename, source)) in source_loader.loaded_files().into_iter().enumerate() {
+            let file_id = FileId::new(index as u32 + 1).unwrap();
+            let relative_path = self.repro_file_path(file_id, &filename, input_file_id);
+
+            let output_path = repro_dir.join(&relative_path);
+            if let Some(parent_dir) = output_path.parent() {
+                fs::create_dir_all(parent_dir)?;
+            }
+
+            let source: &str = source.as_ref();
+            fs::write(&output_path, source)?;
+
+            writeln!(
+                crash_report,
+                "{} <- {}",
+                relative_path.display(),
+                filename.to_string_lossy()
+            )?;
+        }
+
+        Ok(())
+    }
+}
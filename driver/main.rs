@@ -1,14 +1,16 @@
 #![warn(clippy::all)]
 #![warn(rust_2018_idioms)]
 
+mod crash;
 mod subcommand;
 
 use std::sync::Arc;
 use std::{env, path, process};
 
-use arret_compiler::{find_arret_root, CompileCtx, FindArretRootError};
+use arret_compiler::{find_arret_root, CompileCtx, FindArretRootError, ModuleName, OptLevel};
 
 const ARRET_FILE_EXTENSION: &str = ".arret";
+const MIR_FILE_EXTENSION: &str = ".mir";
 
 fn input_arg_to_source_file(
     source_loader: &arret_compiler::SourceLoader,
@@ -30,6 +32,76 @@ fn input_arg_to_source_file(
     }
 }
 
+/// Unstable options that aren't applied as soon as they're parsed
+#[derive(Default)]
+struct UnstableOptions {
+    crash_repro_dir: Option<path::PathBuf>,
+    typeck_cache_dir: Option<path::PathBuf>,
+    warn_boxing: bool,
+    warn_shadowing: bool,
+}
+
+/// Parses `-Z` options, starting tracing if requested
+fn apply_unstable_options<'a>(unstable_options: impl Iterator<Item = &'a str>) -> UnstableOptions {
+    use arret_compiler::trace::{start_tracing, TraceFilter, TraceOutput};
+
+    let mut parsed_options = UnstableOptions::default();
+    let mut filter = None;
+    let mut output = None;
+
+    for unstable_option in unstable_options {
+        let parsed_output = match unstable_option.split_once('=') {
+            Some(("trace", filter_str)) => match TraceFilter::parse(filter_str) {
+                Ok(parsed_filter) => {
+                    filter = Some(parsed_filter);
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("invalid `-Z trace` filter: {}", err);
+                    process::exit(1);
+                }
+            },
+            Some(("trace-chrome", output_path)) => TraceOutput::ChromeJson(output_path.into()),
+            Some(("self-profile", output_path)) => TraceOutput::FoldedStacks(output_path.into()),
+            Some(("crash-repro", repro_dir)) => {
+                parsed_options.crash_repro_dir = Some(repro_dir.into());
+                continue;
+            }
+            Some(("typeck-cache", cache_dir)) => {
+                parsed_options.typeck_cache_dir = Some(cache_dir.into());
+                continue;
+            }
+            None if unstable_option == "warn-boxing" => {
+                parsed_options.warn_boxing = true;
+                continue;
+            }
+            None if unstable_option == "warn-shadowing" => {
+                parsed_options.warn_shadowing = true;
+                continue;
+            }
+            _ => {
+                eprintln!("unknown unstable option `-Z {}`", unstable_option);
+                process::exit(1);
+            }
+        };
+
+        if output.is_some() {
+            eprintln!("only one of `-Z trace-chrome` or `-Z self-profile` can be used");
+            process::exit(1);
+        }
+
+        output = Some(parsed_output);
+    }
+
+    match (filter, output) {
+        (None, None) => {}
+        (Some(filter), None) => start_tracing(filter, TraceOutput::Log),
+        (filter, Some(output)) => start_tracing(filter.unwrap_or_else(TraceFilter::all), output),
+    }
+
+    parsed_options
+}
+
 fn main() {
     use arret_compiler::initialise_llvm;
     use clap::{crate_version, App, AppSettings, Arg, SubCommand};
@@ -39,10 +111,56 @@ fn main() {
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .about("Compiler and REPL for the Arret language")
         .arg(
-            Arg::with_name("NOOPT")
-                .long("no-llvm-opt")
-                .takes_value(false)
-                .help("Disables LLVM optimisation"),
+            Arg::with_name("OPT_LEVEL")
+                .short("O")
+                .value_name("LEVEL")
+                .takes_value(true)
+                .possible_values(&["0", "1", "2", "s"])
+                .default_value("2")
+                .help("Sets the optimisation level")
+                .long_help(
+                    "Sets the optimisation level.\n\
+                     This selects both the MIR optimisations and the LLVM pass pipeline. Runtime \
+                     checks such as integer overflow are kept at every level.\n\
+                     \n\
+                     `0` disables optimisation\n\
+                     `1` enables limited optimisation\n\
+                     `2` enables full optimisation\n\
+                     `s` enables full optimisation while preferring smaller code",
+                ),
+        )
+        .arg(
+            Arg::with_name("PRELUDE")
+                .long("prelude")
+                .value_name("MODULE")
+                .takes_value(true)
+                .help("Implicitly imports a module into every module")
+                .long_help(
+                    "Implicitly imports a module into every module.\n\
+                     The module is specified as its package followed by its path, e.g. \
+                     `--prelude \"[stdlib base]\"`. It isn't imported into modules from its own \
+                     package. The REPL imports the prelude instead of `[stdlib base]`.",
+                ),
+        )
+        .arg(
+            Arg::with_name("STRICT")
+                .long("strict")
+                .help("Forbids undeclared function parameters from being inferred as `Any`")
+                .long_help(
+                    "Forbids undeclared function parameters from being inferred as `Any`.\n\
+                     Parameters that accept any value must be explicitly declared as `Any`. This \
+                     doesn't apply to the standard library.",
+                ),
+        )
+        .arg(
+            Arg::with_name("VERIFY_MIR")
+                .long("verify-mir")
+                .help("Verifies MIR as it's built and optimised")
+                .long_help(
+                    "Verifies MIR as it's built and optimised.\n\
+                     This reports compiler bugs closer to their cause. It's always enabled in \
+                     debug builds of the compiler.",
+                ),
         )
         .arg(
             Arg::with_name("ARRET_ROOT")
@@ -50,13 +168,39 @@ fn main() {
                 .takes_value(true)
                 .help("Path to the root of a built `etaoins/arret` repository"),
         )
+        .arg(
+            Arg::with_name("UNSTABLE")
+                .short("Z")
+                .value_name("OPTION")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Sets an unstable compiler option")
+                .long_help(
+                    "Sets an unstable compiler option.\n\
+                     These are intended for debugging the compiler and may change without notice:\n\
+                     \n\
+                     `trace=<categories>` logs compiler passes to stderr. Categories are a comma \
+                     separated list of `hir`, `typeck`, `mir` and `codegen` or `all`\n\
+                     `trace-chrome=<file>` writes traced passes to a Chrome trace event JSON file\n\
+                     `self-profile=<file>` writes the time spent in each pass and definition as \
+                     folded stacks for flamegraph tools\n\
+                     `crash-repro=<dir>` writes a reproduction to a directory if the compiler \
+                     crashes\n\
+                     `typeck-cache=<dir>` restores the types inferred for unchanged modules in \
+                     previous runs instead of inferring them again\n\
+                     `warn-boxing` warns where `compile` implicitly boxes an `Int`, `Float` or \
+                     `Bool`\n\
+                     `warn-shadowing` warns where a local variable shadows an existing binding",
+                ),
+        )
         .subcommand(
             SubCommand::with_name("compile")
                 .about("Compiles an Arret program to a standalone binary")
                 .arg(
                     Arg::with_name("INPUT")
                         .required(true)
-                        .help("Input source file")
+                        .help("Input source file or MIR dump ending in `.mir`")
                         .index(1),
                 )
                 .arg(
@@ -74,12 +218,55 @@ fn main() {
                              `.o` will output an unlinked object file"
                         ),
                 )
+                .arg(
+                    Arg::with_name("EMIT")
+                        .long("emit")
+                        .value_name("KIND")
+                        .takes_value(true)
+                        .possible_values(&["program", "type-map"])
+                        .default_value("program")
+                        .help("Selects what to emit")
+                        .long_help(
+                            "Selects what to emit.\n\
+                             \n\
+                             `program` compiles the program to the output type selected by the \
+                             output filename\n\
+                             `type-map` writes the name, location, inferred type, purity, arity \
+                             and docstring of every definition as JSON. This doesn't require a \
+                             `main!` function",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("DUMP_TYPES")
+                        .long("dump-types")
+                        .help("Prints the type of every definition instead of compiling")
+                        .long_help(
+                            "Prints the type of every definition instead of compiling.\n\
+                             \n\
+                             Each top-level definition in the input file is printed to stdout as \
+                             `[name Type]` in source order. Declared types are printed as \
+                             declared; other definitions are printed with their inferred type. \
+                             This doesn't require a `main!` function",
+                        ),
+                )
                 .arg(
                     Arg::with_name("DEBUG")
                         .short("g")
                         .long("debug-info")
                         .help("Generates debugging information"),
                 )
+                .arg(
+                    Arg::with_name("ALLOC_PROFILE")
+                        .long("alloc-profile")
+                        .help("Profiles heap allocations made by the program")
+                        .long_help(
+                            "Profiles heap allocations made by the program.\n\
+                             \n\
+                             The compiled program counts its allocations by type and by the \
+                             function that made them. The profile is written to stderr when the \
+                             program exits.",
+                        ),
+                )
                 .arg(
                     Arg::with_name("TARGET")
                         .long("target")
@@ -97,6 +284,63 @@ fn main() {
                         .index(1),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("find")
+                .about("Searches the definitions of an Arret program and its imports")
+                .arg(
+                    Arg::with_name("QUERY")
+                        .required(true)
+                        .help("Name to search for")
+                        .long_help(
+                            "Name to search for.\n\
+                             Queries containing `*` or `?` are treated as globs matching the \
+                             entire name. Other queries match any name containing their \
+                             characters in order, with closer matches listed first.",
+                        )
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("INPUT")
+                        .required(true)
+                        .help("Input source file")
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name("EXPORTED")
+                        .long("exported")
+                        .help("Only includes exported definitions"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Outputs the module dependency graph of an Arret program")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .required(true)
+                        .help("Input source file")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Output filename; defaults to stdout"),
+                )
+                .arg(
+                    Arg::with_name("FORMAT")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["dot"])
+                        .default_value("dot")
+                        .help("Selects the output format"),
+                )
+                .arg(
+                    Arg::with_name("CALLS")
+                        .long("calls")
+                        .help("Includes references between definitions reachable from `main!`"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("repl")
                 .about("Starts an interactive REPL")
@@ -134,49 +378,102 @@ fn main() {
         }
     };
 
-    let enable_optimisations = !matches.is_present("NOOPT");
+    let opt_level: OptLevel = matches.value_of("OPT_LEVEL").unwrap().parse().unwrap();
+
+    let prelude: Option<ModuleName> = matches.value_of("PRELUDE").map(|prelude_param| {
+        prelude_param.parse().unwrap_or_else(|_| {
+            eprintln!(
+                "`{}` specified by the `--prelude` option is not a valid module name",
+                prelude_param
+            );
+            process::exit(1);
+        })
+    });
+
+    let strict = matches.is_present("STRICT");
+    let verify_mir = matches.is_present("VERIFY_MIR");
+
+    let unstable_options =
+        apply_unstable_options(matches.values_of("UNSTABLE").into_iter().flatten());
+    let crash_repro_dir = unstable_options.crash_repro_dir.as_deref();
 
-    if let Some(compile_matches) = matches.subcommand_matches("compile") {
+    let succeeded = if let Some(compile_matches) = matches.subcommand_matches("compile") {
         let package_paths = arret_compiler::PackagePaths::with_stdlib(
             &arret_root_dir,
             compile_matches.value_of("TARGET"),
         );
 
-        let ccx = CompileCtx::new(package_paths, enable_optimisations);
+        let ccx = CompileCtx::new(package_paths, opt_level)
+            .with_prelude(prelude.clone())
+            .with_strict(strict)
+            .with_verify_mir(verify_mir)
+            .with_typeck_cache_dir(unstable_options.typeck_cache_dir.clone())
+            .with_warn_boxing(unstable_options.warn_boxing)
+            .with_warn_shadowing(unstable_options.warn_shadowing);
 
         let input_arg = compile_matches.value_of("INPUT").unwrap();
         let input_file = input_arg_to_source_file(ccx.source_loader(), input_arg);
 
-        let output_path = path::Path::new(
-            if let Some(output_param) = compile_matches.value_of("OUTPUT") {
-                output_param
-            } else if input_arg.ends_with(ARRET_FILE_EXTENSION) {
-                &input_arg[0..input_arg.len() - ARRET_FILE_EXTENSION.len()]
+        let emit_type_map = compile_matches.value_of("EMIT") == Some("type-map");
+        let dump_types = compile_matches.is_present("DUMP_TYPES");
+
+        let output_path = if let Some(output_param) = compile_matches.value_of("OUTPUT") {
+            path::PathBuf::from(output_param)
+        } else if input_arg.ends_with(MIR_FILE_EXTENSION) {
+            path::PathBuf::from(&input_arg[0..input_arg.len() - MIR_FILE_EXTENSION.len()])
+        } else if input_arg.ends_with(ARRET_FILE_EXTENSION) {
+            let output_path =
+                path::PathBuf::from(&input_arg[0..input_arg.len() - ARRET_FILE_EXTENSION.len()]);
+
+            if emit_type_map {
+                output_path.with_extension("json")
             } else {
-                panic!(
-                    "Can't determine output filename from input arg `{}`",
-                    input_arg
-                );
-            },
-        );
+                output_path
+            }
+        } else {
+            panic!(
+                "Can't determine output filename from input arg `{}`",
+                input_arg
+            );
+        };
 
         let debug_info = compile_matches.is_present("DEBUG");
+        let alloc_profile = compile_matches.is_present("ALLOC_PROFILE");
 
         let target_triple = compile_matches.value_of("TARGET");
         initialise_llvm(target_triple.is_some());
 
-        if !subcommand::compile::compile_input_file(
-            &ccx,
-            &input_file,
-            target_triple,
-            output_path,
-            debug_info,
-        ) {
-            process::exit(2);
-        }
+        let crash_reporter =
+            crash::CrashReporter::install("compile", &arret_root_dir, crash_repro_dir);
+
+        crash_reporter.catch(ccx.source_loader(), Some(input_file.file_id()), || {
+            if dump_types {
+                return subcommand::compile::dump_types(&ccx, &input_file);
+            }
+
+            if emit_type_map {
+                return subcommand::compile::emit_type_map(&ccx, &input_file, &output_path);
+            }
+
+            subcommand::compile::compile_input_file(
+                &ccx,
+                &input_file,
+                input_arg.ends_with(MIR_FILE_EXTENSION),
+                target_triple,
+                &output_path,
+                debug_info,
+                alloc_profile,
+            )
+        })
     } else if let Some(repl_matches) = matches.subcommand_matches("repl") {
         let package_paths = arret_compiler::PackagePaths::with_stdlib(&arret_root_dir, None);
-        let ccx = Arc::new(CompileCtx::new(package_paths, enable_optimisations));
+        let ccx = Arc::new(
+            CompileCtx::new(package_paths, opt_level)
+                .with_prelude(prelude.clone())
+                .with_strict(strict)
+                .with_verify_mir(verify_mir)
+                .with_typeck_cache_dir(unstable_options.typeck_cache_dir.clone()),
+        );
 
         initialise_llvm(false);
 
@@ -185,20 +482,79 @@ fn main() {
             .map(|include_param| path::Path::new(include_param).to_owned());
 
         subcommand::repl::interactive_loop(ccx, include_path);
+        true
     } else if let Some(eval_matches) = matches.subcommand_matches("eval") {
         let package_paths = arret_compiler::PackagePaths::with_stdlib(&arret_root_dir, None);
-        let ccx = CompileCtx::new(package_paths, enable_optimisations);
+        let ccx = CompileCtx::new(package_paths, opt_level)
+            .with_prelude(prelude.clone())
+            .with_strict(strict)
+            .with_verify_mir(verify_mir)
+            .with_typeck_cache_dir(unstable_options.typeck_cache_dir.clone())
+            .with_warn_shadowing(unstable_options.warn_shadowing);
 
         let input_param = eval_matches.value_of("INPUT").unwrap();
         let input_file = input_arg_to_source_file(ccx.source_loader(), input_param);
 
         initialise_llvm(false);
 
-        if !subcommand::eval::eval_input_file(&ccx, &input_file) {
-            process::exit(2);
-        }
+        let crash_reporter =
+            crash::CrashReporter::install("eval", &arret_root_dir, crash_repro_dir);
+
+        crash_reporter.catch(ccx.source_loader(), Some(input_file.file_id()), || {
+            subcommand::eval::eval_input_file(&ccx, &input_file)
+        })
+    } else if let Some(find_matches) = matches.subcommand_matches("find") {
+        let package_paths = arret_compiler::PackagePaths::with_stdlib(&arret_root_dir, None);
+        let ccx = CompileCtx::new(package_paths, opt_level)
+            .with_prelude(prelude.clone())
+            .with_strict(strict);
+
+        let input_param = find_matches.value_of("INPUT").unwrap();
+        let input_file = input_arg_to_source_file(ccx.source_loader(), input_param);
+
+        let query = find_matches.value_of("QUERY").unwrap();
+        let exported_only = find_matches.is_present("EXPORTED");
+
+        // Macro functions are evaluated using the JIT
+        initialise_llvm(false);
+
+        let crash_reporter =
+            crash::CrashReporter::install("find", &arret_root_dir, crash_repro_dir);
+
+        crash_reporter.catch(ccx.source_loader(), Some(input_file.file_id()), || {
+            subcommand::find::find_in_input_file(&ccx, &input_file, query, exported_only)
+        })
+    } else if let Some(graph_matches) = matches.subcommand_matches("graph") {
+        let package_paths = arret_compiler::PackagePaths::with_stdlib(&arret_root_dir, None);
+        let ccx = CompileCtx::new(package_paths, opt_level)
+            .with_prelude(prelude.clone())
+            .with_strict(strict);
+
+        let input_param = graph_matches.value_of("INPUT").unwrap();
+        let input_file = input_arg_to_source_file(ccx.source_loader(), input_param);
+
+        let output_path = graph_matches.value_of("OUTPUT").map(path::Path::new);
+        let include_references = graph_matches.is_present("CALLS");
+
+        // Macro functions are evaluated using the JIT
+        initialise_llvm(false);
+
+        let crash_reporter =
+            crash::CrashReporter::install("graph", &arret_root_dir, crash_repro_dir);
+
+        crash_reporter.catch(ccx.source_loader(), Some(input_file.file_id()), || {
+            subcommand::graph::graph_input_file(&ccx, &input_file, output_path, include_references)
+        })
     } else {
         eprintln!("Sub-command not specified");
         process::exit(1);
+    };
+
+    if let Err(err) = arret_compiler::trace::finish_tracing() {
+        eprintln!("unable to write trace output: {}", err);
+    }
+
+    if !succeeded {
+        process::exit(2);
     }
 }
@@ -1,58 +1,99 @@
-use std::{fs, path};
+use std::sync::Arc;
+use std::{fs, io, path};
 
 use codespan_reporting::diagnostic::Diagnostic;
 
 use arret_syntax::span::FileId;
 
-use arret_compiler::{emit_diagnostics_to_stderr, print_program_mir, CompileCtx};
+use arret_compiler::{
+    emit_diagnostics_to_stderr, parse_program_mir, print_program_mir, verify_program_mir,
+    CompileCtx, LinkedLibrary,
+};
 
 // We don't use this ourselves so overload it for the purposes of dumping MIR
 const MIR_OUTPUT_TYPE: arret_compiler::OutputType = arret_compiler::OutputType::None;
 
+fn gen_mir_program(
+    ccx: &CompileCtx,
+    options: arret_compiler::GenProgramOptions<'_>,
+    linked_libraries: &[Arc<LinkedLibrary>],
+    mir_program: &arret_compiler::BuiltProgram,
+    output_path: &path::Path,
+) -> Result<(), Vec<Diagnostic<FileId>>> {
+    if options.output_type() == MIR_OUTPUT_TYPE {
+        let mut output_file = fs::File::create(output_path).unwrap();
+        print_program_mir(
+            &mut output_file,
+            mir_program,
+            linked_libraries,
+            Some(ccx.source_loader()),
+        )
+        .unwrap();
+        return Ok(());
+    }
+
+    arret_compiler::gen_program(
+        options,
+        linked_libraries,
+        mir_program,
+        output_path,
+        Some(ccx.source_loader()),
+    )
+    .map_err(|unsupported| vec![unsupported.into()])
+}
+
 fn try_compile_input_file(
     ccx: &CompileCtx,
     options: arret_compiler::GenProgramOptions<'_>,
     input_file: &arret_compiler::SourceFile,
     output_path: &path::Path,
-    debug_info: bool,
 ) -> Result<(), Vec<Diagnostic<FileId>>> {
     let arret_compiler::EvaluableProgram {
         ehx,
         main_export_id,
         linked_libraries,
+        warnings,
     } = arret_compiler::program_to_evaluable(ccx, input_file)?;
 
+    emit_diagnostics_to_stderr(ccx.source_loader(), warnings);
+
     let mir_program = ehx.into_built_program(main_export_id)?;
+    emit_diagnostics_to_stderr(ccx.source_loader(), mir_program.boxing_warnings.clone());
 
-    if options.output_type() == MIR_OUTPUT_TYPE {
-        let mut output_file = fs::File::create(output_path).unwrap();
-        print_program_mir(&mut output_file, &mir_program, Some(ccx.source_loader())).unwrap();
-        return Ok(());
-    }
+    gen_mir_program(ccx, options, &linked_libraries, &mir_program, output_path)
+}
 
-    let debug_source_loader = if debug_info {
-        Some(ccx.source_loader())
-    } else {
-        None
-    };
+fn try_compile_mir_file(
+    ccx: &CompileCtx,
+    options: arret_compiler::GenProgramOptions<'_>,
+    input_file: &arret_compiler::SourceFile,
+    output_path: &path::Path,
+) -> Result<(), Vec<Diagnostic<FileId>>> {
+    let parsed_program = parse_program_mir(Some(input_file.file_id()), input_file.source())
+        .map_err(|error| vec![error.into()])?;
 
-    arret_compiler::gen_program(
-        options,
-        &linked_libraries,
-        &mir_program,
-        output_path,
-        debug_source_loader,
-    );
+    let mir_program = parsed_program.program;
+    if ccx.verify_mir() {
+        verify_program_mir(&mir_program).map_err(|error| vec![error.into()])?;
+    }
+
+    let linked_libraries: Vec<Arc<LinkedLibrary>> = parsed_program
+        .linked_library_paths
+        .into_iter()
+        .map(|target_path| Arc::new(LinkedLibrary::unloaded(target_path)))
+        .collect();
 
-    Ok(())
+    gen_mir_program(ccx, options, &linked_libraries, &mir_program, output_path)
 }
 
 pub fn compile_input_file(
     ccx: &CompileCtx,
     input_file: &arret_compiler::SourceFile,
+    input_is_mir: bool,
     target_triple: Option<&str>,
     output_path: &path::Path,
     debug_info: bool,
+    alloc_profile: bool,
 ) -> bool {
     use std::ffi;
 
@@ -67,9 +108,15 @@ pub fn compile_input_file(
     let options = arret_compiler::GenProgramOptions::new()
         .with_target_triple(target_triple)
         .with_output_type(output_type)
-        .with_llvm_opt(ccx.enable_optimisations());
+        .with_opt_level(ccx.opt_level())
+        .with_debug_info(debug_info)
+        .with_alloc_profile(alloc_profile);
 
-    let result = try_compile_input_file(ccx, options, input_file, output_path, debug_info);
+    let result = if input_is_mir {
+        try_compile_mir_file(ccx, options, input_file, output_path)
+    } else {
+        try_compile_input_file(ccx, options, input_file, output_path)
+    };
 
     if let Err(diagnostics) = result {
         emit_diagnostics_to_stderr(ccx.source_loader(), diagnostics);
@@ -78,3 +125,35 @@ pub fn compile_input_file(
         true
     }
 }
+
+pub fn emit_type_map(
+    ccx: &CompileCtx,
+    input_file: &arret_compiler::SourceFile,
+    output_path: &path::Path,
+) -> bool {
+    match arret_compiler::program_type_map(ccx, input_file) {
+        Ok(type_map) => {
+            let mut output_file = fs::File::create(output_path).unwrap();
+            type_map.write_json(&mut output_file).unwrap();
+            true
+        }
+        Err(diagnostics) => {
+            emit_diagnostics_to_stderr(ccx.source_loader(), diagnostics);
+            false
+        }
+    }
+}
+
+pub fn dump_types(ccx: &CompileCtx, input_file: &arret_compiler::SourceFile) -> bool {
+    match arret_compiler::infer_program(ccx, input_file) {
+        Ok(program) => {
+            arret_compiler::write_program_def_types(ccx, &program, &mut io::stdout().lock())
+                .unwrap();
+            true
+        }
+        Err(diagnostics) => {
+            emit_diagnostics_to_stderr(ccx.source_loader(), diagnostics);
+            false
+        }
+    }
+}
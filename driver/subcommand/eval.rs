@@ -11,9 +11,12 @@ fn try_eval_input_file(
     let arret_compiler::EvaluableProgram {
         mut ehx,
         main_export_id,
+        warnings,
         ..
     } = arret_compiler::program_to_evaluable(ccx, input_file)?;
 
+    emit_diagnostics_to_stderr(ccx.source_loader(), warnings);
+
     ehx.eval_main_fun(main_export_id)?;
 
     Ok(())
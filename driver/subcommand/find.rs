@@ -0,0 +1,129 @@
+use arret_compiler::{emit_diagnostics_to_stderr, CompileCtx, TypeMapEntry};
+
+/// Returns if a name matches a glob containing `*` and `?` wildcards
+fn glob_matches(glob: &[char], name: &[char]) -> bool {
+    match (glob.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            // Try matching the wildcard against every possible suffix
+            (0..=name.len()).any(|skip| glob_matches(&glob[1..], &name[skip..]))
+        }
+        (Some('?'), Some(_)) => glob_matches(&glob[1..], &name[1..]),
+        (Some(glob_char), Some(name_char)) if glob_char == name_char => {
+            glob_matches(&glob[1..], &name[1..])
+        }
+        _ => false,
+    }
+}
+
+/// Returns if every character of the query appears in the name in order
+fn fuzzy_matches(query: &str, name: &str) -> bool {
+    let mut name_chars = name.chars();
+    query
+        .chars()
+        .all(|query_char| name_chars.any(|name_char| name_char == query_char))
+}
+
+/// Scores how well a name matches a query
+///
+/// Lower scores are better matches. `None` is returned if the name doesn't match at all. Queries
+/// containing `*` or `?` are treated as globs that must match the entire name; other queries are
+/// matched fuzzily.
+fn match_score(query: &str, name: &str) -> Option<u8> {
+    if query.contains(&['*', '?'][..]) {
+        let glob: Vec<char> = query.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+
+        return if glob_matches(&glob, &name) {
+            Some(0)
+        } else {
+            None
+        };
+    }
+
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if name.contains(query) {
+        Some(2)
+    } else if fuzzy_matches(query, name) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+fn print_entry(entry: &TypeMapEntry) {
+    let location = match (&entry.filename, entry.line_col_range) {
+        (Some(filename), Some(line_col_range)) => format!(
+            "{}:{}:{}",
+            filename,
+            line_col_range.start.line + 1,
+            line_col_range.start.column + 1
+        ),
+        (Some(filename), None) => filename.clone(),
+        _ => "<unknown>".to_owned(),
+    };
+
+    let visibility = if entry.exported { "" } else { " (internal)" };
+
+    println!(
+        "{}: {} {}{}",
+        location, entry.name, entry.type_str, visibility
+    );
+}
+
+pub fn find_in_input_file(
+    ccx: &CompileCtx,
+    input_file: &arret_compiler::SourceFile,
+    query: &str,
+    exported_only: bool,
+) -> bool {
+    let type_map = match arret_compiler::program_type_map(ccx, input_file) {
+        Ok(type_map) => type_map,
+        Err(diagnostics) => {
+            emit_diagnostics_to_stderr(ccx.source_loader(), diagnostics);
+            return false;
+        }
+    };
+
+    let mut matches: Vec<(u8, &TypeMapEntry)> = type_map
+        .entries()
+        .iter()
+        .filter(|entry| entry.exported || !exported_only)
+        .filter_map(|entry| match_score(query, &entry.name).map(|score| (score, entry)))
+        .collect();
+
+    // Entries are already sorted by location; keep that order for equally good matches
+    matches.sort_by_key(|(score, _)| *score);
+
+    for (_, entry) in matches {
+        print_entry(entry);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_match_score() {
+        assert_eq!(Some(0), match_score("str*", "str->sym"));
+        assert_eq!(Some(0), match_score("*->*", "str->sym"));
+        assert_eq!(Some(0), match_score("ma?", "map"));
+        assert_eq!(None, match_score("ma?", "mapcat"));
+        assert_eq!(None, match_score("*sym", "sym->str"));
+    }
+
+    #[test]
+    fn fuzzy_match_score() {
+        assert_eq!(Some(0), match_score("map", "map"));
+        assert_eq!(Some(1), match_score("map", "mapcat"));
+        assert_eq!(Some(2), match_score("cat", "mapcat"));
+        assert_eq!(Some(3), match_score("sts", "str->sym"));
+        assert_eq!(None, match_score("xyz", "str->sym"));
+    }
+}
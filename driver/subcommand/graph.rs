@@ -0,0 +1,27 @@
+use std::{fs, io, path};
+
+use arret_compiler::{emit_diagnostics_to_stderr, CompileCtx};
+
+pub fn graph_input_file(
+    ccx: &CompileCtx,
+    input_file: &arret_compiler::SourceFile,
+    output_path: Option<&path::Path>,
+    include_references: bool,
+) -> bool {
+    match arret_compiler::program_dep_graph(ccx, input_file, include_references) {
+        Ok(dep_graph) => {
+            if let Some(output_path) = output_path {
+                let mut output_file = fs::File::create(output_path).unwrap();
+                dep_graph.write_dot(&mut output_file).unwrap();
+            } else {
+                dep_graph.write_dot(&mut io::stdout().lock()).unwrap();
+            }
+
+            true
+        }
+        Err(diagnostics) => {
+            emit_diagnostics_to_stderr(ccx.source_loader(), diagnostics);
+            false
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod compile;
 pub mod eval;
+pub mod find;
+pub mod graph;
 pub mod repl;
@@ -6,12 +6,19 @@ use rustyline::validate::{ValidationContext, ValidationResult};
 
 use arret_syntax::datum::DataStr;
 
-use super::command::{HELP_COMMAND, QUIT_COMMAND, TYPE_ONLY_PREFIX};
+use super::command::{
+    DOC_PREFIX, EXPAND_ALL_PREFIX, EXPAND_PREFIX, HELP_COMMAND, HYGIENE_COMMAND, QUIT_COMMAND,
+    TYPE_ONLY_PREFIX,
+};
 use super::syntax::{error_context_for_eol, error_for_line, MAXIMUM_PARSED_LINE_LEN};
 
 /// Completions that don't map to a bound value in scope
 const UNBOUND_COMPLETIONS: &[&str] = &[
     TYPE_ONLY_PREFIX,
+    DOC_PREFIX,
+    EXPAND_PREFIX,
+    EXPAND_ALL_PREFIX,
+    HYGIENE_COMMAND,
     QUIT_COMMAND,
     HELP_COMMAND,
     "true",
@@ -1,10 +1,20 @@
 pub const TYPE_ONLY_PREFIX: &str = "/type ";
+pub const DOC_PREFIX: &str = "/doc ";
+pub const EXPAND_PREFIX: &str = "/expand ";
+pub const EXPAND_ALL_PREFIX: &str = "/expand-all ";
+pub const HYGIENE_COMMAND: &str = "/hygiene";
 pub const QUIT_COMMAND: &str = "/quit";
 pub const HELP_COMMAND: &str = "/help";
 
+/// Prefixes of commands taking an expression
+pub const EXPR_PREFIXES: &[&str] = &[TYPE_ONLY_PREFIX, EXPAND_PREFIX, EXPAND_ALL_PREFIX];
+
 pub enum ParsedCommand {
     EvalValue(String),
     EvalType(String),
+    EvalDoc(String),
+    Expand { input: String, all: bool },
+    ToggleHygiene,
     Quit,
     Other,
 }
@@ -15,14 +25,37 @@ pub fn parse_command(mut line: String) -> ParsedCommand {
             line.drain(0..TYPE_ONLY_PREFIX.len());
             ParsedCommand::EvalType(line)
         }
+        _ if line.starts_with(DOC_PREFIX) => {
+            line.drain(0..DOC_PREFIX.len());
+            ParsedCommand::EvalDoc(line)
+        }
+        _ if line.starts_with(EXPAND_PREFIX) => {
+            line.drain(0..EXPAND_PREFIX.len());
+            ParsedCommand::Expand {
+                input: line,
+                all: false,
+            }
+        }
+        _ if line.starts_with(EXPAND_ALL_PREFIX) => {
+            line.drain(0..EXPAND_ALL_PREFIX.len());
+            ParsedCommand::Expand {
+                input: line,
+                all: true,
+            }
+        }
         HELP_COMMAND => {
             println!("Available REPL commands:");
             println!();
             println!("/help                 Prints this summary");
             println!("/type <expression>    Evaluates the type of the given expression");
+            println!("/doc <identifier>     Prints the docstring of the given identifier");
+            println!("/expand <expression>  Expands the outermost macro of the given expression");
+            println!("/expand-all <expr>    Expands every macro in the given expression");
+            println!("/hygiene              Toggles showing hygiene marks in macro expansions");
             println!("/quit                 Exits the REPL");
             ParsedCommand::Other
         }
+        HYGIENE_COMMAND => ParsedCommand::ToggleHygiene,
         QUIT_COMMAND => ParsedCommand::Quit,
         _ => ParsedCommand::EvalValue(line),
     }
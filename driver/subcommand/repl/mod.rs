@@ -19,7 +19,7 @@ use history::repl_history_path;
 const PROMPT: &str = "arret> ";
 
 pub fn interactive_loop(ccx: Arc<CompileCtx>, include_path: Option<path::PathBuf>) {
-    use arret_compiler::repl::{EvalKind, EvaledExprValue, EvaledLine};
+    use arret_compiler::repl::{EvalKind, EvaledExprValue, EvaledLine, ExpandOptions};
     use rustyline::error::ReadlineError;
 
     // Setup our REPL backend
@@ -28,8 +28,13 @@ pub fn interactive_loop(ccx: Arc<CompileCtx>, include_path: Option<path::PathBuf
     // Setup Rustyline
     let mut rl = rustyline::Editor::<ArretHelper>::new();
 
-    // Import [stdlib base] so we have most useful things defined
-    let initial_import = "(import [stdlib base])".to_owned();
+    // Import our prelude or [stdlib base] so we have most useful things defined
+    let initial_import = if let Some(prelude) = ccx.prelude() {
+        format!("(import {})", prelude)
+    } else {
+        "(import [stdlib base])".to_owned()
+    };
+
     repl_ctx.send_line(initial_import, EvalKind::Value).unwrap();
     let mut sent_prelude_lines = 1;
 
@@ -67,6 +72,8 @@ pub fn interactive_loop(ccx: Arc<CompileCtx>, include_path: Option<path::PathBuf
     let type_style = Colour::Fixed(166); // DarkOrange3 (#d75f00)
     let type_brackets_style = Style::new().dimmed();
 
+    let mut show_hygiene = false;
+
     loop {
         let mut history_dirty = false;
         let readline = rl.readline(PROMPT);
@@ -80,6 +87,17 @@ pub fn interactive_loop(ccx: Arc<CompileCtx>, include_path: Option<path::PathBuf
                 let (eval_kind, input) = match parse_command(line) {
                     ParsedCommand::EvalValue(input) => (EvalKind::Value, input),
                     ParsedCommand::EvalType(input) => (EvalKind::Type, input),
+                    ParsedCommand::EvalDoc(input) => (EvalKind::Doc, input),
+                    ParsedCommand::Expand { input, all } => {
+                        (EvalKind::Expand(ExpandOptions { all, show_hygiene }), input)
+                    }
+                    ParsedCommand::ToggleHygiene => {
+                        show_hygiene = !show_hygiene;
+
+                        let state = if show_hygiene { "shown" } else { "hidden" };
+                        println!("hygiene marks are now {}", state);
+                        continue;
+                    }
                     ParsedCommand::Quit => {
                         break;
                     }
@@ -138,6 +156,15 @@ pub fn interactive_loop(ccx: Arc<CompileCtx>, include_path: Option<path::PathBuf
                             );
                         }
                     }
+                    Ok(EvaledLine::Doc(Some(doc))) => {
+                        println!("{}", doc);
+                    }
+                    Ok(EvaledLine::Expansion(expansion)) => {
+                        println!("{} {}", expr_arrow_style.paint("=>"), expansion);
+                    }
+                    Ok(EvaledLine::Doc(None)) => {
+                        println!("{}", type_brackets_style.paint("no documentation"));
+                    }
                     Err(diagnostics) => {
                         emit_diagnostics_to_stderr(ccx.source_loader(), diagnostics);
                     }
@@ -1,6 +1,6 @@
 use arret_syntax::span::ByteIndex;
 
-use super::command::TYPE_ONLY_PREFIX;
+use super::command::EXPR_PREFIXES;
 
 /// Maximum line length we'll provide parser hints and error highlighting for
 ///
@@ -13,9 +13,12 @@ pub const MAXIMUM_PARSED_LINE_LEN: usize = 512;
 pub fn error_for_line(mut line: &str) -> Option<arret_syntax::error::Error> {
     use arret_syntax::parser::datum_from_str_with_span_offset;
 
-    let span_offset = if line.starts_with(TYPE_ONLY_PREFIX) {
-        line = &line[TYPE_ONLY_PREFIX.len()..];
-        TYPE_ONLY_PREFIX.len()
+    let span_offset = if let Some(prefix) = EXPR_PREFIXES
+        .iter()
+        .find(|prefix| line.starts_with(*prefix))
+    {
+        line = &line[prefix.len()..];
+        prefix.len()
     } else {
         0
     };
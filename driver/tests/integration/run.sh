@@ -21,6 +21,12 @@ assert_outputs_hello_world "${test_binary} eval ${HELLO_WORLD_SOURCE}"
 
 assert_outputs_hello_world "${test_binary} eval -" < ${HELLO_WORLD_SOURCE}
 
+dumped_types=$(${test_binary} compile --dump-types ${HELLO_WORLD_SOURCE})
+if [ "${dumped_types}" != "[main! (->! ())]" ]; then
+    >&2 echo "unexpected dumped types '${dumped_types}'"
+    exit 1
+fi
+
 ${test_binary} compile ${HELLO_WORLD_SOURCE} -o "${TEMP_HELLO_WORLD_BINARY}"
 assert_outputs_hello_world "${TEMP_HELLO_WORLD_BINARY}"
 rm "${TEMP_HELLO_WORLD_BINARY}"
@@ -4,55 +4,64 @@ use std::sync::Arc;
 use tokio::sync::{mpsc, watch};
 use tokio::task;
 
-use arret_syntax::parser::data_from_str;
+use arret_syntax::error::Error;
+use arret_syntax::parser::data_from_str_with_recovery;
 
 use crate::json_rpc::{Notification, ServerMessage};
 use crate::model::Document;
 use crate::watcher::DocumentWatcher;
 
-fn syntax_diagnostics_for_document(
+fn diagnostic_for_syntax_error(
     url: &lsp_types::Url,
     document: &Document,
-) -> Vec<lsp_types::Diagnostic> {
-    match data_from_str(None, document.text()) {
-        Ok(_) => vec![],
-        Err(error) => {
-            let within = error.kind().within_context();
+    error: &Error,
+) -> lsp_types::Diagnostic {
+    let within = error.kind().within_context();
+
+    let mut related_information = vec![];
+
+    if let Some(within) = within {
+        if let Some(open_char_span) = within.open_char_span() {
+            related_information.push(lsp_types::DiagnosticRelatedInformation {
+                location: lsp_types::Location {
+                    uri: url.clone(),
+                    range: document.span_to_range(open_char_span),
+                },
+                message: format!("{} starts here", within.description()),
+            });
+        }
 
-            let mut related_information = vec![];
+        if let Some(expected_next) = within.expected_next() {
+            related_information.push(lsp_types::DiagnosticRelatedInformation {
+                location: lsp_types::Location {
+                    uri: url.clone(),
+                    range: document.span_to_range(error.span()),
+                },
+                message: expected_next.description(),
+            });
+        }
+    }
 
-            if let Some(within) = within {
-                if let Some(open_char_span) = within.open_char_span() {
-                    related_information.push(lsp_types::DiagnosticRelatedInformation {
-                        location: lsp_types::Location {
-                            uri: url.clone(),
-                            range: document.span_to_range(open_char_span),
-                        },
-                        message: format!("{} starts here", within.description()),
-                    });
-                }
+    lsp_types::Diagnostic {
+        range: document.span_to_range(error.span()),
+        severity: Some(lsp_types::DiagnosticSeverity::Error),
+        message: error.kind().message(),
+        related_information: Some(related_information),
+        source: Some("arret-syntax".to_owned()),
+        ..Default::default()
+    }
+}
 
-                if let Some(expected_next) = within.expected_next() {
-                    related_information.push(lsp_types::DiagnosticRelatedInformation {
-                        location: lsp_types::Location {
-                            uri: url.clone(),
-                            range: document.span_to_range(error.span()),
-                        },
-                        message: expected_next.description(),
-                    });
-                }
-            }
+fn syntax_diagnostics_for_document(
+    url: &lsp_types::Url,
+    document: &Document,
+) -> Vec<lsp_types::Diagnostic> {
+    let (_, errors) = data_from_str_with_recovery(None, document.text());
 
-            vec![lsp_types::Diagnostic {
-                range: document.span_to_range(error.span()),
-                severity: Some(lsp_types::DiagnosticSeverity::Error),
-                message: error.kind().message(),
-                related_information: Some(related_information),
-                source: Some("arret-syntax".to_owned()),
-                ..Default::default()
-            }]
-        }
-    }
+    errors
+        .iter()
+        .map(|error| diagnostic_for_syntax_error(url, document, error))
+        .collect()
 }
 
 struct DocumentTask {
@@ -265,4 +274,40 @@ mod test {
             diags
         );
     }
+
+    #[test]
+    fn multiple_error_diagnostics() {
+        let url = lsp_types::Url::parse("file:///foo/bar").unwrap();
+        let doc = Document::new(1, "(\\madeup)\n\\bogus".to_owned());
+
+        let diags = syntax_diagnostics_for_document(&url, &doc);
+
+        let diag_ranges: Vec<lsp_types::Range> = diags.iter().map(|diag| diag.range).collect();
+
+        assert_eq!(
+            vec![
+                lsp_types::Range {
+                    start: lsp_types::Position {
+                        line: 0,
+                        character: 2,
+                    },
+                    end: lsp_types::Position {
+                        line: 0,
+                        character: 8,
+                    }
+                },
+                lsp_types::Range {
+                    start: lsp_types::Position {
+                        line: 1,
+                        character: 1,
+                    },
+                    end: lsp_types::Position {
+                        line: 1,
+                        character: 6,
+                    }
+                }
+            ],
+            diag_ranges
+        );
+    }
 }
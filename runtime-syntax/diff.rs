@@ -0,0 +1,367 @@
+//! Structural differences between boxed values
+//!
+//! This makes failures comparing large values actionable by pointing at the nested values that
+//! differ instead of printing both values in full.
+
+use std::io::{Result, Write};
+
+use arret_runtime::boxed;
+use arret_runtime::boxed::prelude::*;
+use arret_runtime::boxed::refs::Gc;
+
+use crate::writer::write_boxed;
+
+/// Step from a collection to one of its members
+#[derive(Clone, Copy, Debug)]
+pub enum PathStep {
+    /// Element of a list or vector at the given index
+    Index(usize),
+    /// Value of a map with the given key
+    MapKey(Gc<boxed::Any>),
+    /// Field of a record at the given index
+    Field(usize),
+}
+
+/// Way a nested value differs
+#[derive(Clone, Copy, Debug)]
+pub enum DifferenceKind {
+    /// Both values exist but aren't equal
+    Mismatch {
+        expected: Gc<boxed::Any>,
+        actual: Gc<boxed::Any>,
+    },
+    /// Value only exists in the expected collection
+    Missing(Gc<boxed::Any>),
+    /// Value only exists in the actual collection
+    Unexpected(Gc<boxed::Any>),
+}
+
+/// Difference between an expected and actual value
+#[derive(Clone, Debug)]
+pub struct Difference {
+    /// Path from the root of the compared values to the difference
+    pub path: Vec<PathStep>,
+    pub kind: DifferenceKind,
+}
+
+struct DiffCtx<'h> {
+    heap: &'h boxed::Heap,
+    path: Vec<PathStep>,
+    differences: Vec<Difference>,
+}
+
+impl<'h> DiffCtx<'h> {
+    fn push_difference(&mut self, kind: DifferenceKind) {
+        self.differences.push(Difference {
+            path: self.path.clone(),
+            kind,
+        });
+    }
+
+    fn visit_step(&mut self, step: PathStep, expected: Gc<boxed::Any>, actual: Gc<boxed::Any>) {
+        self.path.push(step);
+        self.visit_value(expected, actual);
+        self.path.pop();
+    }
+
+    fn visit_seq(
+        &mut self,
+        expected_iter: impl Iterator<Item = Gc<boxed::Any>>,
+        mut actual_iter: impl Iterator<Item = Gc<boxed::Any>>,
+    ) {
+        let mut index = 0;
+
+        for expected in expected_iter {
+            match actual_iter.next() {
+                Some(actual) => self.visit_step(PathStep::Index(index), expected, actual),
+                None => {
+                    self.path.push(PathStep::Index(index));
+                    self.push_difference(DifferenceKind::Missing(expected));
+                    self.path.pop();
+                }
+            }
+
+            index += 1;
+        }
+
+        for actual in actual_iter {
+            self.path.push(PathStep::Index(index));
+            self.push_difference(DifferenceKind::Unexpected(actual));
+            self.path.pop();
+
+            index += 1;
+        }
+    }
+
+    fn visit_map(
+        &mut self,
+        expected: &boxed::Map<boxed::Any, boxed::Any>,
+        actual: &boxed::Map<boxed::Any, boxed::Any>,
+    ) {
+        for (key, expected_value) in expected.iter() {
+            match actual.get(self.heap, &key) {
+                Some(actual_value) => {
+                    self.visit_step(PathStep::MapKey(key), expected_value, actual_value)
+                }
+                None => {
+                    self.path.push(PathStep::MapKey(key));
+                    self.push_difference(DifferenceKind::Missing(expected_value));
+                    self.path.pop();
+                }
+            }
+        }
+
+        for (key, actual_value) in actual.iter() {
+            if expected.get(self.heap, &key).is_none() {
+                self.path.push(PathStep::MapKey(key));
+                self.push_difference(DifferenceKind::Unexpected(actual_value));
+                self.path.pop();
+            }
+        }
+    }
+
+    fn visit_set(&mut self, expected: &boxed::Set<boxed::Any>, actual: &boxed::Set<boxed::Any>) {
+        for expected_member in expected.iter() {
+            if !actual.contains(self.heap, &expected_member) {
+                self.push_difference(DifferenceKind::Missing(expected_member));
+            }
+        }
+
+        for actual_member in actual.iter() {
+            if !expected.contains(self.heap, &actual_member) {
+                self.push_difference(DifferenceKind::Unexpected(actual_member));
+            }
+        }
+    }
+
+    /// Visits the fields of two records of the same class
+    ///
+    /// Returns false if an unboxed field differs; these can't be reported individually.
+    fn visit_record_fields(&mut self, expected: &boxed::Record, actual: &boxed::Record) -> bool {
+        let field_pairs: Vec<(boxed::FieldValue, boxed::FieldValue)> = expected
+            .field_values(self.heap)
+            .zip(actual.field_values(self.heap))
+            .collect();
+
+        let unboxed_mismatch = field_pairs.iter().any(|(expected_field, actual_field)| {
+            !matches!(expected_field, boxed::FieldValue::Boxed(_))
+                && !expected_field.eq_in_heap(self.heap, actual_field)
+        });
+
+        if unboxed_mismatch {
+            return false;
+        }
+
+        for (index, field_pair) in field_pairs.into_iter().enumerate() {
+            if let (
+                boxed::FieldValue::Boxed(expected_field),
+                boxed::FieldValue::Boxed(actual_field),
+            ) = field_pair
+            {
+                self.visit_step(PathStep::Field(index), expected_field, actual_field);
+            }
+        }
+
+        true
+    }
+
+    fn visit_value(&mut self, expected: Gc<boxed::Any>, actual: Gc<boxed::Any>) {
+        use boxed::AnySubtype;
+
+        if expected.eq_in_heap(self.heap, &actual) {
+            return;
+        }
+
+        if let (Some(expected_list), Some(actual_list)) = (
+            expected.downcast_ref::<boxed::List<boxed::Any>>(),
+            actual.downcast_ref::<boxed::List<boxed::Any>>(),
+        ) {
+            self.visit_seq(expected_list.iter(), actual_list.iter());
+            return;
+        }
+
+        match (expected.as_subtype(), actual.as_subtype()) {
+            (AnySubtype::Vector(expected_vec), AnySubtype::Vector(actual_vec)) => {
+                self.visit_seq(expected_vec.iter(), actual_vec.iter());
+            }
+            (AnySubtype::Map(expected_map), AnySubtype::Map(actual_map)) => {
+                self.visit_map(expected_map, actual_map);
+            }
+            (AnySubtype::Set(expected_set), AnySubtype::Set(actual_set)) => {
+                self.visit_set(expected_set, actual_set);
+            }
+            (AnySubtype::Record(expected_record), AnySubtype::Record(actual_record))
+                if expected_record.class_id() == actual_record.class_id()
+                    && self.visit_record_fields(expected_record, actual_record) => {}
+            _ => {
+                self.push_difference(DifferenceKind::Mismatch { expected, actual });
+            }
+        }
+    }
+}
+
+/// Returns the differences between two values
+///
+/// Lists, vectors, maps, sets and records of the same class are compared member by member. Any
+/// other unequal values are reported as a single mismatch. The returned differences refer to
+/// values on the heap; they're invalidated by garbage collection.
+pub fn diff_boxed(
+    heap: &impl AsHeap,
+    expected: Gc<boxed::Any>,
+    actual: Gc<boxed::Any>,
+) -> Vec<Difference> {
+    let mut dcx = DiffCtx {
+        heap: heap.as_heap(),
+        path: vec![],
+        differences: vec![],
+    };
+
+    dcx.visit_value(expected, actual);
+    dcx.differences
+}
+
+fn write_path(w: &mut dyn Write, heap: &impl AsHeap, path: &[PathStep]) -> Result<()> {
+    if path.is_empty() {
+        return write!(w, "root");
+    }
+
+    for step in path {
+        match step {
+            PathStep::Index(index) => write!(w, "[{}]", index)?,
+            PathStep::MapKey(key) => {
+                write!(w, "{{")?;
+                write_boxed(w, heap, *key)?;
+                write!(w, "}}")?;
+            }
+            PathStep::Field(index) => write!(w, ".{}", index)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes one line per difference describing its path and values
+///
+/// Paths are written as `[index]` for list and vector elements, `{key}` for map values and
+/// `.index` for record fields.
+pub fn write_differences(
+    w: &mut dyn Write,
+    heap: &impl AsHeap,
+    differences: &[Difference],
+) -> Result<()> {
+    for Difference { path, kind } in differences {
+        write!(w, "at ")?;
+        write_path(w, heap, path)?;
+
+        match kind {
+            DifferenceKind::Mismatch { expected, actual } => {
+                write!(w, ": expected `")?;
+                write_boxed(w, heap, *expected)?;
+                write!(w, "`, found `")?;
+                write_boxed(w, heap, *actual)?;
+                writeln!(w, "`")?;
+            }
+            DifferenceKind::Missing(expected) => {
+                write!(w, ": missing `")?;
+                write_boxed(w, heap, *expected)?;
+                writeln!(w, "`")?;
+            }
+            DifferenceKind::Unexpected(actual) => {
+                write!(w, ": unexpected `")?;
+                write_boxed(w, heap, *actual)?;
+                writeln!(w, "`")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Panics with a description of each difference if two values aren't equal
+pub fn assert_boxed_eq(heap: &impl AsHeap, expected: Gc<boxed::Any>, actual: Gc<boxed::Any>) {
+    let differences = diff_boxed(heap, expected, actual);
+
+    if !differences.is_empty() {
+        let mut output = vec![];
+        write_differences(&mut output, heap, &differences).unwrap();
+
+        panic!(
+            "boxed values are not equal:\n{}",
+            String::from_utf8_lossy(&output)
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::reader;
+    use arret_syntax::parser::datum_from_str;
+
+    fn rendered_diff(expected_str: &str, actual_str: &str) -> String {
+        let mut heap = boxed::Heap::empty();
+
+        let expected =
+            reader::box_syntax_datum(&mut heap, &datum_from_str(None, expected_str).unwrap());
+        let actual =
+            reader::box_syntax_datum(&mut heap, &datum_from_str(None, actual_str).unwrap());
+
+        let mut output = vec![];
+        write_differences(&mut output, &heap, &diff_boxed(&heap, expected, actual)).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn equal_values() {
+        assert_eq!("", rendered_diff("1", "1"));
+        assert_eq!("", rendered_diff("{:a [1 2]}", "{:a [1 2]}"));
+    }
+
+    #[test]
+    fn scalar_mismatch() {
+        assert_eq!(
+            "at root: expected `1`, found `2`\n",
+            rendered_diff("1", "2")
+        );
+        assert_eq!(
+            "at root: expected `(1)`, found `[1]`\n",
+            rendered_diff("(1)", "[1]")
+        );
+    }
+
+    #[test]
+    fn seqs() {
+        assert_eq!(
+            "at [1]: expected `2`, found `3`\n",
+            rendered_diff("(1 2)", "(1 3)")
+        );
+        assert_eq!("at [2]: missing `3`\n", rendered_diff("[1 2 3]", "[1 2]"));
+        assert_eq!("at [0]: unexpected `1`\n", rendered_diff("()", "(1)"));
+    }
+
+    #[test]
+    fn nested_maps() {
+        // Maps are iterated in an unspecified order
+        let rendered = rendered_diff("{:a [1 2] :b true}", "{:a [1 4] :c false}");
+        let mut lines: Vec<&str> = rendered.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(
+            vec![
+                "at {:a}[1]: expected `2`, found `4`",
+                "at {:b}: missing `true`",
+                "at {:c}: unexpected `false`",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn sets() {
+        assert_eq!(
+            "at root: missing `1`\nat root: unexpected `3`\n",
+            rendered_diff("#{1 2}", "#{2 3}")
+        );
+    }
+}
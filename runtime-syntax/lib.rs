@@ -3,5 +3,6 @@
 #![warn(clippy::all)]
 #![warn(rust_2018_idioms)]
 
+pub mod diff;
 pub mod reader;
 pub mod writer;
@@ -1,4 +1,5 @@
 use arret_syntax::datum::Datum;
+use arret_syntax::span::Span;
 
 use arret_runtime::boxed;
 use arret_runtime::boxed::prelude::*;
@@ -10,6 +11,10 @@ pub fn box_syntax_datum(heap: &mut impl boxed::AsHeap, datum: &Datum) -> Gc<boxe
         Datum::Bool(_, value) => boxed::Bool::singleton_ref(*value).as_any_ref(),
         Datum::Int(_, val) => boxed::Int::new(heap, *val).as_any_ref(),
         Datum::Float(_, val) => boxed::Float::new(heap, *val).as_any_ref(),
+        Datum::Rational(_, numer, denom) => boxed::Rational::new(heap, *numer, *denom)
+            .expect("invalid rational datum")
+            .as_any_ref(),
+        Datum::BigInt(_, val) => boxed::BigInt::new(heap, val.as_ref().clone()).as_any_ref(),
         Datum::Char(_, val) => boxed::Char::new(heap, *val).as_any_ref(),
         Datum::Str(_, val) => boxed::Str::new(heap, val.as_ref()).as_any_ref(),
         Datum::Sym(_, val) => boxed::Sym::new(heap, val.as_ref()).as_any_ref(),
@@ -30,3 +35,94 @@ pub fn box_syntax_datum(heap: &mut impl boxed::AsHeap, datum: &Datum) -> Gc<boxe
 }
 
 // This is indirectly tested by `writer`
+
+/// Converts a boxed value back to a syntax datum
+///
+/// Every datum is given the passed span. `None` is returned if the value or any value it contains
+/// has no syntax representation, such as functions and records.
+pub fn unbox_syntax_datum(
+    heap: &impl boxed::AsHeap,
+    span: Span,
+    any_ref: Gc<boxed::Any>,
+) -> Option<Datum> {
+    use arret_runtime::boxed::AnySubtype;
+
+    let unbox_seq = |elems: &mut dyn Iterator<Item = Gc<boxed::Any>>| {
+        elems
+            .map(|elem| unbox_syntax_datum(heap, span, elem))
+            .collect::<Option<Box<[Datum]>>>()
+    };
+
+    Some(match any_ref.as_subtype() {
+        AnySubtype::True(_) => Datum::Bool(span, true),
+        AnySubtype::False(_) => Datum::Bool(span, false),
+        AnySubtype::Nil(_) => Datum::List(span, Box::new([])),
+        AnySubtype::Int(int_ref) => Datum::Int(span, int_ref.value()),
+        AnySubtype::Float(float_ref) => Datum::Float(span, float_ref.value()),
+        AnySubtype::Rational(rational_ref) => {
+            Datum::Rational(span, rational_ref.numer(), rational_ref.denom())
+        }
+        AnySubtype::BigInt(big_int_ref) => {
+            Datum::BigInt(span, Box::new(big_int_ref.value().clone()))
+        }
+        AnySubtype::Char(char_ref) => Datum::Char(span, char_ref.value()),
+        AnySubtype::Str(str_ref) => Datum::Str(span, str_ref.as_str().into()),
+        AnySubtype::Sym(sym_ref) => Datum::Sym(
+            span,
+            heap.as_heap()
+                .type_info()
+                .interner()
+                .unintern(&sym_ref.interned())
+                .into(),
+        ),
+        AnySubtype::Pair(list) => Datum::List(span, unbox_seq(&mut list.as_list_ref().iter())?),
+        AnySubtype::Vector(vec) => Datum::Vector(span, unbox_seq(&mut vec.iter())?),
+        AnySubtype::Set(set) => Datum::Set(span, unbox_seq(&mut set.iter())?),
+        AnySubtype::Map(map) => Datum::Map(
+            span,
+            map.iter()
+                .map(|(key, value)| {
+                    Some((
+                        unbox_syntax_datum(heap, span, key)?,
+                        unbox_syntax_datum(heap, span, value)?,
+                    ))
+                })
+                .collect::<Option<Box<[(Datum, Datum)]>>>()?,
+        ),
+        AnySubtype::FunThunk(_)
+        | AnySubtype::Record(_)
+        | AnySubtype::FloatArray(_)
+        | AnySubtype::IntArray(_)
+        | AnySubtype::Bytes(_) => {
+            return None;
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arret_syntax::parser::datum_from_str;
+
+    #[test]
+    fn unbox_round_trip() {
+        let mut heap = boxed::Heap::empty();
+
+        for source in &[
+            "true",
+            "()",
+            "(1 2.5 -3/4 \\a \"str\" sym :keyword)",
+            "[#{1} {one 2}]",
+        ] {
+            // Spans are all replaced with the passed span
+            let datum = datum_from_str(None, source).unwrap();
+            let any_ref = box_syntax_datum(&mut heap, &datum);
+
+            let unboxed = unbox_syntax_datum(&heap, datum.span(), any_ref).unwrap();
+            let reboxed_ref = box_syntax_datum(&mut heap, &unboxed);
+
+            assert!(any_ref.eq_in_heap(&heap, &reboxed_ref));
+        }
+    }
+}
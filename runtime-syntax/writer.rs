@@ -131,6 +131,45 @@ fn write_interned_sym(
     )
 }
 
+fn write_bytes(w: &mut dyn Write, bytes: &boxed::Bytes) -> Result<()> {
+    write!(w, "#bytes(")?;
+
+    let mut has_prev = false;
+    for byte in bytes.as_slice() {
+        if has_prev {
+            write!(w, " ")?;
+        } else {
+            has_prev = true;
+        }
+
+        write!(w, "{}", byte)?;
+    }
+
+    write!(w, ")")
+}
+
+fn write_num_array<T: boxed::NumArrayElement>(
+    w: &mut dyn Write,
+    prefix: &str,
+    array: &boxed::NumArray<T>,
+    write_elem: impl Fn(&mut dyn Write, T) -> Result<()>,
+) -> Result<()> {
+    write!(w, "#{}(", prefix)?;
+
+    let mut has_prev = false;
+    for elem in array.iter() {
+        if has_prev {
+            write!(w, " ")?;
+        } else {
+            has_prev = true;
+        }
+
+        write_elem(w, elem)?;
+    }
+
+    write!(w, ")")
+}
+
 fn write_record(w: &mut dyn Write, heap: &impl AsHeap, record: &boxed::Record) -> Result<()> {
     use boxed::FieldValue;
 
@@ -168,6 +207,10 @@ pub fn write_boxed(w: &mut dyn Write, heap: &impl AsHeap, any_ref: Gc<boxed::Any
         AnySubtype::False(_) => write!(w, "false"),
         AnySubtype::Nil(_) => write!(w, "()"),
         AnySubtype::Int(int_ref) => write!(w, "{}", int_ref.value()),
+        AnySubtype::Rational(rational_ref) => {
+            write!(w, "{}/{}", rational_ref.numer(), rational_ref.denom())
+        }
+        AnySubtype::BigInt(big_int_ref) => write!(w, "{}", big_int_ref.value()),
         AnySubtype::Sym(sym) => write_interned_sym(w, heap, sym.interned()),
         AnySubtype::Float(float_ref) => write_float(w, float_ref.value()),
         AnySubtype::Pair(list) => {
@@ -194,6 +237,11 @@ pub fn write_boxed(w: &mut dyn Write, heap: &impl AsHeap, any_ref: Gc<boxed::Any
         AnySubtype::FunThunk(_) => write!(w, "#fn"),
         AnySubtype::Record(record) => write_record(w, heap, record),
         AnySubtype::Map(map) => write_boxed_map(w, heap, map.iter()),
+        AnySubtype::FloatArray(array) => write_num_array(w, "float-array", array, write_float),
+        AnySubtype::IntArray(array) => {
+            write_num_array(w, "int-array", array, |w, i| write!(w, "{}", i))
+        }
+        AnySubtype::Bytes(bytes) => write_bytes(w, bytes),
     }
 }
 
@@ -234,13 +282,15 @@ mod test {
 
     fn assert_write(heap: &mut boxed::Heap, expected: &'static str, any_ref: Gc<boxed::Any>) {
         use crate::reader;
-        use arret_syntax::parser::datum_from_str;
+        use arret_syntax::parser::{datum_from_str_with_options, ParserOptions};
 
         let first_output = string_for_boxed(heap, any_ref);
         assert_eq!(expected, first_output);
 
         // Try to round trip this to make sure our output and tests are sane
-        let reparsed_syntax = datum_from_str(None, &first_output).unwrap();
+        let reparsed_syntax =
+            datum_from_str_with_options(None, &first_output, ParserOptions { big_ints: true })
+                .unwrap();
         let reboxed_ref = reader::box_syntax_datum(heap, &reparsed_syntax);
 
         let second_output = string_for_boxed(heap, reboxed_ref);
@@ -268,6 +318,25 @@ mod test {
         assert_write(&mut heap, "-120", boxed_negative.as_any_ref());
     }
 
+    #[test]
+    fn rationals() {
+        let mut heap = boxed::Heap::empty();
+
+        let boxed_positive = boxed::Rational::new(&mut heap, 3, 4).unwrap();
+        assert_write(&mut heap, "3/4", boxed_positive.as_any_ref());
+
+        let boxed_negative = boxed::Rational::new(&mut heap, 1, -2).unwrap();
+        assert_write(&mut heap, "-1/2", boxed_negative.as_any_ref());
+    }
+
+    #[test]
+    fn big_ints() {
+        let mut heap = boxed::Heap::empty();
+
+        let boxed_big = boxed::BigInt::new(&mut heap, "-10223372036854775807".parse().unwrap());
+        assert_write(&mut heap, "-10223372036854775807", boxed_big.as_any_ref());
+    }
+
     #[test]
     fn floats() {
         let mut heap = boxed::Heap::empty();
@@ -333,6 +402,23 @@ mod test {
         assert_write(&mut heap, "[1 2 3]", three_vector.as_any_ref());
     }
 
+    #[test]
+    fn maps() {
+        let mut heap = boxed::Heap::empty();
+
+        let empty_map = boxed::Map::<boxed::Any, boxed::Any>::new(&mut heap, std::iter::empty());
+        assert_write(&mut heap, "{}", empty_map.as_any_ref());
+
+        let one_map =
+            boxed::Map::from_values(&mut heap, [(1, 2)].iter().cloned(), |heap, (k, v)| {
+                (
+                    boxed::Int::new(heap, k).as_any_ref(),
+                    boxed::Int::new(heap, v).as_any_ref(),
+                )
+            });
+        assert_write(&mut heap, "{1 2}", one_map.as_any_ref());
+    }
+
     #[test]
     fn chars() {
         let mut heap = boxed::Heap::empty();
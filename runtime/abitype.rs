@@ -79,6 +79,11 @@ pub enum RetAbiType {
     Void,
     Never,
     Inhabited(AbiType),
+    /// Multiple values returned together in registers
+    ///
+    /// This is only used by functions generated by the compiler; it can't be returned by Rust
+    /// functions or callbacks.
+    Values(Box<[AbiType]>),
 }
 
 pub trait EncodeAbiType {
@@ -0,0 +1,291 @@
+//! Copying of boxed values between heaps
+//!
+//! Boxes can only refer to boxes on their own heap and symbols interned by their own heap's
+//! interner. Values passed between tasks or from the compiler to a compiled program need to be
+//! deep copied with [`Heap::adopt`].
+
+use std::collections::HashMap;
+
+use crate::boxed;
+use crate::boxed::heap::Heap;
+use crate::boxed::refs::Gc;
+use crate::boxed::{AllocType, AnySubtype, Boxed};
+use crate::class_map::FieldType;
+use crate::intern::InternedSym;
+
+struct AdoptCtx<'from> {
+    from_heap: &'from Heap,
+    /// Boxes on the source heap that have already been copied
+    ///
+    /// This preserves sharing within the adopted value.
+    adopted: HashMap<*const boxed::Any, Gc<boxed::Any>>,
+}
+
+impl<'from> AdoptCtx<'from> {
+    fn adopt_interned_sym(&self, to_heap: &mut Heap, interned_sym: InternedSym) -> InternedSym {
+        let sym_name = self
+            .from_heap
+            .type_info()
+            .interner()
+            .unintern(&interned_sym);
+        to_heap.type_info_mut().interner_mut().intern(sym_name)
+    }
+
+    fn adopt_record(&mut self, to_heap: &mut Heap, record_ref: &boxed::Record) -> Gc<boxed::Any> {
+        let mut data = record_ref.clone_data();
+
+        let classmap_class = self
+            .from_heap
+            .type_info()
+            .class_map()
+            .class_for_record_class_id(record_ref.class_id());
+
+        for classmap_field in classmap_class.field_iter() {
+            unsafe {
+                let field_base_ptr = data.as_mut_ptr().add(classmap_field.offset());
+
+                match classmap_field.field_type() {
+                    FieldType::InternedSym => {
+                        let interned_sym_ref = &mut *(field_base_ptr as *mut InternedSym);
+                        *interned_sym_ref = self.adopt_interned_sym(to_heap, *interned_sym_ref);
+                    }
+                    FieldType::Boxed => {
+                        let box_ref = &mut *(field_base_ptr as *mut Gc<boxed::Any>);
+                        *box_ref = self.adopt_any(to_heap, *box_ref);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        boxed::Record::new(to_heap, record_ref.class_id(), data).as_any_ref()
+    }
+
+    fn adopt_elements(
+        &mut self,
+        to_heap: &mut Heap,
+        elements: impl Iterator<Item = Gc<boxed::Any>>,
+    ) -> Vec<Gc<boxed::Any>> {
+        elements
+            .map(|element| self.adopt_any(to_heap, element))
+            .collect()
+    }
+
+    fn adopt_any(&mut self, to_heap: &mut Heap, box_ref: Gc<boxed::Any>) -> Gc<boxed::Any> {
+        if box_ref.header().alloc_type() == AllocType::Const {
+            // Constant boxes are shared by every heap of a program
+            return box_ref;
+        }
+
+        if let Some(adopted) = self.adopted.get(&box_ref.as_ptr()) {
+            return *adopted;
+        }
+
+        let adopted = match box_ref.as_subtype() {
+            AnySubtype::Int(int_ref) => boxed::Int::new(to_heap, int_ref.value()).as_any_ref(),
+            AnySubtype::Float(float_ref) => {
+                boxed::Float::new(to_heap, float_ref.value()).as_any_ref()
+            }
+            AnySubtype::Char(char_ref) => boxed::Char::new(to_heap, char_ref.value()).as_any_ref(),
+            AnySubtype::Rational(rational_ref) => {
+                boxed::Rational::new(to_heap, rational_ref.numer(), rational_ref.denom())
+                    .expect("invalid rational")
+                    .as_any_ref()
+            }
+            AnySubtype::BigInt(big_int_ref) => {
+                boxed::BigInt::new(to_heap, big_int_ref.value().clone()).as_any_ref()
+            }
+            AnySubtype::Str(str_ref) => boxed::Str::new(to_heap, str_ref.as_str()).as_any_ref(),
+            AnySubtype::Bytes(bytes_ref) => {
+                boxed::Bytes::new(to_heap, bytes_ref.as_slice()).as_any_ref()
+            }
+            AnySubtype::FloatArray(array_ref) => {
+                boxed::FloatArray::new(to_heap, array_ref.iter()).as_any_ref()
+            }
+            AnySubtype::IntArray(array_ref) => {
+                boxed::IntArray::new(to_heap, array_ref.iter()).as_any_ref()
+            }
+            AnySubtype::Sym(sym_ref) => {
+                let interned_sym = self.adopt_interned_sym(to_heap, sym_ref.interned());
+                boxed::Sym::from_interned_sym(to_heap, interned_sym).as_any_ref()
+            }
+            AnySubtype::Pair(pair_ref) => {
+                let elements = self.adopt_elements(to_heap, pair_ref.as_list_ref().iter());
+                boxed::List::new(to_heap, elements.into_iter()).as_any_ref()
+            }
+            AnySubtype::Vector(vector_ref) => {
+                let elements = self.adopt_elements(to_heap, vector_ref.iter());
+                boxed::Vector::new(to_heap, elements.into_iter()).as_any_ref()
+            }
+            AnySubtype::Set(set_ref) => {
+                let elements = self.adopt_elements(to_heap, set_ref.iter());
+                boxed::Set::new(to_heap, elements.into_iter()).as_any_ref()
+            }
+            AnySubtype::Map(map_ref) => {
+                let entries: Vec<(Gc<boxed::Any>, Gc<boxed::Any>)> = map_ref
+                    .iter()
+                    .map(|(key, value)| {
+                        (self.adopt_any(to_heap, key), self.adopt_any(to_heap, value))
+                    })
+                    .collect();
+
+                boxed::Map::new(to_heap, entries.into_iter()).as_any_ref()
+            }
+            AnySubtype::FunThunk(fun_thunk_ref) => {
+                let captures = self.adopt_any(to_heap, fun_thunk_ref.captures);
+                boxed::FunThunk::new(to_heap, captures, fun_thunk_ref.entry).as_any_ref()
+            }
+            AnySubtype::Record(record_ref) => self.adopt_record(to_heap, record_ref),
+            AnySubtype::Nil(_) | AnySubtype::True(_) | AnySubtype::False(_) => {
+                // These are only ever constant
+                box_ref
+            }
+        };
+
+        self.adopted.insert(box_ref.as_ptr(), adopted);
+        adopted
+    }
+}
+
+impl Heap {
+    /// Deep copies a value from another heap in to this heap
+    ///
+    /// Symbols are re-interned with this heap's interner. Boxes shared within the value remain
+    /// shared in the copy while constant boxes are referenced directly. The source heap isn't
+    /// modified and may be dropped once this returns.
+    ///
+    /// Records keep their class ID. Both heaps must use the same class map, such as heaps created
+    /// by the same compiled program.
+    pub fn adopt<T: Boxed>(&mut self, value: Gc<T>, from_heap: &Heap) -> Gc<T> {
+        let mut acx = AdoptCtx {
+            from_heap,
+            adopted: HashMap::new(),
+        };
+
+        let adopted = acx.adopt_any(self, value.as_any_ref());
+        unsafe { adopted.cast::<T>() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boxed::prelude::*;
+
+    #[test]
+    fn adopt_collections() {
+        let mut from_heap = Heap::empty();
+
+        let elements = [
+            boxed::Sym::new(&mut from_heap, "a-long-symbol-name-that-isnt-inline").as_any_ref(),
+            boxed::Str::new(&mut from_heap, "Hello, world!").as_any_ref(),
+            boxed::Int::new(&mut from_heap, 1).as_any_ref(),
+        ];
+
+        let vector = boxed::Vector::new(&mut from_heap, elements.iter().cloned());
+        let map = boxed::Map::new(
+            &mut from_heap,
+            std::iter::once((elements[0], vector.as_any_ref())),
+        );
+        let list = boxed::List::new(
+            &mut from_heap,
+            [map.as_any_ref(), vector.as_any_ref()].iter().cloned(),
+        );
+
+        let mut to_heap = Heap::empty();
+        // Make sure the symbol is interned with a different index
+        boxed::Sym::new(&mut to_heap, "another-long-symbol-name-not-inline");
+
+        let adopted_list = to_heap.adopt(list, &from_heap);
+        drop(from_heap);
+
+        let expected_list = boxed::List::from_values(
+            &mut to_heap,
+            ["a-long-symbol-name-that-isnt-inline"].iter(),
+            |heap, name| boxed::Sym::new(heap, name),
+        );
+        let expected_sym = expected_list.iter().next().unwrap();
+
+        let adopted_elements: Vec<Gc<boxed::Any>> = adopted_list.iter().collect();
+        let adopted_map = adopted_elements[0]
+            .downcast_ref::<boxed::Map<boxed::Any, boxed::Any>>()
+            .unwrap();
+
+        let adopted_vector = adopted_map
+            .get(&to_heap, &expected_sym.as_any_ref())
+            .unwrap();
+
+        // Shared boxes should remain shared
+        assert_eq!(adopted_vector.as_ptr(), adopted_elements[1].as_ptr());
+
+        let adopted_vector = adopted_vector
+            .downcast_ref::<boxed::Vector<boxed::Any>>()
+            .unwrap();
+
+        let adopted_sym = adopted_vector.get(0).unwrap();
+        assert_eq!(
+            "a-long-symbol-name-that-isnt-inline",
+            adopted_sym
+                .downcast_ref::<boxed::Sym>()
+                .unwrap()
+                .name(&to_heap)
+        );
+
+        assert_eq!(
+            "Hello, world!",
+            adopted_vector
+                .get(1)
+                .unwrap()
+                .downcast_ref::<boxed::Str>()
+                .unwrap()
+                .as_str()
+        );
+    }
+
+    #[test]
+    fn adopt_record() {
+        use crate::boxed::RecordData;
+        use crate::class_map::{BoxedClass, ClassMap, Field};
+        use std::alloc;
+
+        let mut class_map = ClassMap::empty();
+        let class_id = class_map.push_dynamic_class(BoxedClass::from_fields(
+            [
+                Field::new(FieldType::Int, 0),
+                Field::new(FieldType::Boxed, 8),
+            ]
+            .iter()
+            .cloned(),
+        ));
+
+        let type_info = || {
+            boxed::heap::type_info::TypeInfo::new(crate::intern::Interner::new(), class_map.clone())
+        };
+
+        let mut from_heap = Heap::new(type_info(), Heap::DEFAULT_CAPACITY);
+        let field_str = boxed::Str::new(&mut from_heap, "field");
+
+        let mut data = RecordData::alloc(Some(alloc::Layout::new::<[u64; 2]>()));
+        unsafe {
+            *(data.as_mut_ptr() as *mut i64) = 42;
+            *(data.as_mut_ptr().add(8) as *mut Gc<boxed::Any>) = field_str.as_any_ref();
+        }
+        let record = boxed::Record::new(&mut from_heap, class_id, data);
+
+        let mut to_heap = Heap::new(type_info(), Heap::DEFAULT_CAPACITY);
+        let adopted_record = to_heap.adopt(record, &from_heap);
+
+        let expected_str = boxed::Str::new(&mut to_heap, "field");
+
+        let field_values: Vec<boxed::FieldValue> = adopted_record.field_values(&to_heap).collect();
+        assert!(matches!(field_values[0], boxed::FieldValue::Int(42)));
+
+        match field_values[1] {
+            boxed::FieldValue::Boxed(adopted_str) => {
+                assert_ne!(adopted_str.as_ptr(), field_str.as_any_ref().as_ptr());
+                assert!(adopted_str.eq_in_heap(&to_heap, &expected_str.as_any_ref()));
+            }
+            _ => panic!("expected boxed field"),
+        }
+    }
+}
@@ -30,13 +30,13 @@ pub struct StrongPass {
 
 impl StrongPass {
     /// Consumes an existing heap to begin a garbage collection pass
-    pub fn new(old_heap: Heap) -> StrongPass {
+    pub fn new(mut old_heap: Heap) -> StrongPass {
         let type_info = old_heap.type_info().clone_for_collect_garbage();
 
-        StrongPass {
-            old_heap,
-            new_heap: Heap::new(type_info, Heap::DEFAULT_CAPACITY),
-        }
+        let mut new_heap = Heap::new(type_info, Heap::DEFAULT_CAPACITY);
+        new_heap.alloc_profile = old_heap.alloc_profile.take();
+
+        StrongPass { old_heap, new_heap }
     }
 
     /// Continues as a weak reference pass
@@ -95,6 +95,34 @@ impl StrongPass {
         *box_ref = unsafe { Gc::new(dest_location) };
     }
 
+    /// Records any storage owned by a moved box outside of the new heap's segments
+    fn note_external_storage(new_heap: &mut Heap, box_ref: &Gc<boxed::Any>) {
+        let external_bytes = match box_ref.header.type_tag {
+            TypeTag::Vector => unsafe {
+                (*(box_ref.as_ptr() as *const boxed::Vector<boxed::Any>)).external_payload_bytes()
+            },
+            TypeTag::Str => unsafe {
+                (*(box_ref.as_ptr() as *const boxed::Str)).external_byte_len()
+            },
+            TypeTag::Record => unsafe {
+                (*(box_ref.as_ptr() as *const boxed::Record)).external_data_bytes()
+            },
+            TypeTag::FloatArray => unsafe {
+                (*(box_ref.as_ptr() as *const boxed::FloatArray)).external_value_bytes()
+            },
+            TypeTag::IntArray => unsafe {
+                (*(box_ref.as_ptr() as *const boxed::IntArray)).external_value_bytes()
+            },
+            TypeTag::Bytes => unsafe { (*(box_ref.as_ptr() as *const boxed::Bytes)).len() },
+            TypeTag::BigInt => unsafe {
+                (*(box_ref.as_ptr() as *const boxed::BigInt)).external_value_bytes()
+            },
+            _ => 0,
+        };
+
+        new_heap.note_external_bytes(external_bytes);
+    }
+
     /// Re-interns the symbol on a new heap
     fn visit_interned_sym(old_heap: &Heap, new_heap: &mut Heap, interned_sym: &mut InternedSym) {
         let old_interner = old_heap.type_info().interner();
@@ -121,15 +149,22 @@ impl StrongPass {
                 }
                 AllocType::Heap16 => {
                     Self::move_box_to_new_heap(new_heap, box_ref, BoxSize::Size16);
+                    Self::note_external_storage(new_heap, box_ref);
                 }
                 AllocType::Heap32 => {
                     Self::move_box_to_new_heap(new_heap, box_ref, BoxSize::Size32);
+                    Self::note_external_storage(new_heap, box_ref);
                 }
                 AllocType::Stack => {
                     // Stack boxes cannot move but they may point to heap boxes
                 }
             }
 
+            if !box_ref.header.type_tag.may_contain_gc_refs() {
+                // Leaf boxes have nothing else to visit
+                return;
+            }
+
             match box_ref.header.type_tag {
                 TypeTag::Sym => {
                     let sym_ref = unsafe { &mut *(box_ref.as_mut_ptr() as *mut boxed::Sym) };
@@ -156,6 +191,24 @@ impl StrongPass {
                         Self::visit_any_box(old_heap, new_heap, elem_ref);
                     });
                 }
+                TypeTag::Set => {
+                    let set_ref =
+                        unsafe { &mut *(box_ref.as_mut_ptr() as *mut boxed::Set<boxed::Any>) };
+
+                    set_ref.visit_mut_elements(&mut |elem_ref| {
+                        Self::visit_any_box(old_heap, new_heap, elem_ref);
+                    });
+                }
+                TypeTag::Map => {
+                    let map_ref = unsafe {
+                        &mut *(box_ref.as_mut_ptr() as *mut boxed::Map<boxed::Any, boxed::Any>)
+                    };
+
+                    map_ref.visit_mut_entries(&mut |key_ref, value_ref| {
+                        Self::visit_any_box(old_heap, new_heap, key_ref);
+                        Self::visit_any_box(old_heap, new_heap, value_ref);
+                    });
+                }
                 TypeTag::FunThunk => {
                     let fun_thunk_ref =
                         unsafe { &mut *(box_ref.as_mut_ptr() as *mut boxed::FunThunk) };
@@ -365,4 +418,130 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn external_vector_accounting() {
+        use std::mem;
+
+        const ELEMENT_COUNT: usize = 100;
+        const PAYLOAD_BYTES: usize = ELEMENT_COUNT * mem::size_of::<Gc<Int>>();
+
+        let mut old_heap = Heap::empty();
+
+        let mut large_vec =
+            boxed::Vector::from_values(&mut old_heap, 0..ELEMENT_COUNT as i64, Int::new);
+        let _dead_vec =
+            boxed::Vector::from_values(&mut old_heap, 0..ELEMENT_COUNT as i64, Int::new);
+
+        // Inline vectors have no external storage
+        let mut small_vec = boxed::Vector::from_values(&mut old_heap, 0..2, Int::new);
+
+        assert_eq!(PAYLOAD_BYTES * 2, old_heap.external_bytes());
+
+        // Only the live vector's storage should move to the new heap
+        let mut large_vec_alias = large_vec;
+        let mut all_strong = StrongPass::new(old_heap);
+        all_strong.visit_box(&mut large_vec);
+        all_strong.visit_box(&mut small_vec);
+
+        // Visiting a forwarded box shouldn't count its storage again
+        all_strong.visit_box(&mut large_vec_alias);
+
+        let live_heap = all_strong.into_new_heap();
+        assert_eq!(PAYLOAD_BYTES, live_heap.external_bytes());
+        assert_eq!(ELEMENT_COUNT, large_vec.len());
+        assert_eq!(99, large_vec.get(99).unwrap().value());
+
+        // Root nothing
+        let empty_heap = StrongPass::new(live_heap).into_new_heap();
+        assert_eq!(0, empty_heap.external_bytes());
+    }
+
+    #[test]
+    fn external_str_and_record_accounting() {
+        use std::alloc;
+
+        use crate::class_map::{BoxedClass, Field, FieldType};
+
+        let long_str = "This is long enough to be stored outside of the string's box";
+        let data_layout = alloc::Layout::new::<[i64; 8]>();
+
+        let mut old_heap = Heap::empty();
+
+        let record_class_id =
+            old_heap
+                .type_info_mut()
+                .class_map_mut()
+                .push_dynamic_class(BoxedClass::from_fields(
+                    (0..8).map(|index| Field::new(FieldType::Int, index * 8)),
+                ));
+
+        let mut boxed_str = Str::new(&mut old_heap, long_str);
+        let mut boxed_record = boxed::Record::new(
+            &mut old_heap,
+            record_class_id,
+            boxed::RecordData::alloc(Some(data_layout)),
+        );
+        assert_eq!(
+            long_str.len() + data_layout.size(),
+            old_heap.external_bytes()
+        );
+
+        let mut all_strong = StrongPass::new(old_heap);
+        all_strong.visit_box(&mut boxed_str);
+        all_strong.visit_box(&mut boxed_record);
+
+        let all_heap = all_strong.into_new_heap();
+        assert_eq!(long_str, boxed_str.as_str());
+        assert_eq!(
+            long_str.len() + data_layout.size(),
+            all_heap.external_bytes()
+        );
+
+        // Only keep the string
+        let mut str_strong = StrongPass::new(all_heap);
+        str_strong.visit_box(&mut boxed_str);
+
+        let str_heap = str_strong.into_new_heap();
+        assert_eq!(long_str.len(), str_heap.external_bytes());
+    }
+
+    #[test]
+    fn external_array_bytes_and_big_int_accounting() {
+        use std::mem;
+
+        const ELEMENT_COUNT: usize = 100;
+
+        let mut old_heap = Heap::empty();
+
+        let mut float_array = boxed::FloatArray::from_elem(&mut old_heap, ELEMENT_COUNT, 0.5);
+        let mut int_array = boxed::IntArray::from_elem(&mut old_heap, ELEMENT_COUNT, 1);
+        let mut bytes = boxed::Bytes::new(&mut old_heap, &[0; ELEMENT_COUNT]);
+        let mut big_int =
+            boxed::BigInt::new(&mut old_heap, "10223372036854775807".parse().unwrap());
+
+        let big_int_bytes = big_int.external_value_bytes();
+        let all_bytes = ELEMENT_COUNT * mem::size_of::<f64>()
+            + ELEMENT_COUNT * mem::size_of::<i64>()
+            + ELEMENT_COUNT
+            + big_int_bytes;
+
+        assert_eq!(all_bytes, old_heap.external_bytes());
+
+        let mut all_strong = StrongPass::new(old_heap);
+        all_strong.visit_box(&mut float_array);
+        all_strong.visit_box(&mut int_array);
+        all_strong.visit_box(&mut bytes);
+        all_strong.visit_box(&mut big_int);
+
+        let all_heap = all_strong.into_new_heap();
+        assert_eq!(all_bytes, all_heap.external_bytes());
+
+        // Only keep the big integer
+        let mut big_int_strong = StrongPass::new(all_heap);
+        big_int_strong.visit_box(&mut big_int);
+
+        let big_int_heap = big_int_strong.into_new_heap();
+        assert_eq!(big_int_bytes, big_int_heap.external_bytes());
+    }
 }
@@ -1,11 +1,14 @@
+mod adopt;
 pub mod collect;
+pub mod profile;
 pub mod type_info;
 
 use std::{cmp, mem, ptr};
 
+use crate::boxed::heap::profile::{AllocProfile, AllocSite};
 use crate::boxed::heap::type_info::TypeInfo;
 use crate::boxed::refs::Gc;
-use crate::boxed::{AllocType, Any, Boxed};
+use crate::boxed::{AllocType, Any, Boxed, TypeTag};
 use crate::intern::{AsInterner, Interner};
 
 /// Allocated segment of garbage collected memory
@@ -26,6 +29,8 @@ pub struct Heap {
     full_segments: Vec<Segment>,
     type_info: TypeInfo,
     len_at_last_gc: usize,
+    external_bytes: usize,
+    alloc_profile: Option<Box<AllocProfile>>,
 }
 
 impl Segment {
@@ -120,19 +125,65 @@ impl Heap {
             full_segments: vec![],
             type_info,
             len_at_last_gc: 0,
+            external_bytes: 0,
+            alloc_profile: None,
         }
     }
 
     /// Hints if this heap should be garbage collected
     ///
-    /// This is a heuristic based on the number of allocations since the last GC cycle.
+    /// This is a heuristic based on the number of allocations since the last GC cycle. Storage
+    /// owned by boxes outside of the heap's segments is counted as its equivalent number of cells.
     pub fn should_collect(&self) -> bool {
         let maximum_len = std::cmp::max(Self::DEFAULT_SEGMENT_CAPACITY, self.len_at_last_gc) * 2;
-        self.len() > maximum_len
+        self.accounted_len() > maximum_len
     }
 
     fn save_len_at_gc(&mut self) {
-        self.len_at_last_gc = self.len();
+        self.len_at_last_gc = self.accounted_len();
+    }
+
+    fn accounted_len(&self) -> usize {
+        self.len() + (self.external_bytes / mem::size_of::<Any>())
+    }
+
+    /// Records `bytes` of storage owned by a box outside of the heap's segments
+    ///
+    /// This storage is freed when the owning box is dropped along with its segment. Boxes moved
+    /// by garbage collection are recorded again in their new heap.
+    pub(crate) fn note_external_bytes(&mut self, bytes: usize) {
+        self.external_bytes += bytes;
+    }
+
+    /// Returns the number of bytes of external storage owned by boxes in the heap
+    ///
+    /// This is approximate; storage shared between boxes may be counted multiple times.
+    pub fn external_bytes(&self) -> usize {
+        self.external_bytes
+    }
+
+    /// Starts profiling allocations made on this heap
+    ///
+    /// This has no effect if profiling is already enabled.
+    pub fn enable_alloc_profile(&mut self) {
+        if self.alloc_profile.is_none() {
+            self.alloc_profile = Some(Box::new(AllocProfile::new()));
+        }
+    }
+
+    /// Returns the allocation profile if profiling is enabled
+    pub fn alloc_profile(&self) -> Option<&AllocProfile> {
+        self.alloc_profile.as_deref()
+    }
+
+    /// Records an allocation made outside of [`place_box`](Heap::place_box)
+    ///
+    /// This is used by compiled code which allocates boxes directly from the heap's segments. It
+    /// has no effect if profiling is disabled.
+    pub fn record_alloc(&mut self, type_tag: TypeTag, cell_count: usize, site: Option<AllocSite>) {
+        if let Some(alloc_profile) = &mut self.alloc_profile {
+            alloc_profile.record(type_tag, cell_count, site);
+        }
     }
 
     /// Allocates space for `count` contiguous cells
@@ -185,6 +236,7 @@ impl Heap {
             .expect("non-heap alloc type");
 
         let needed_cells = heap_size.cell_count();
+        self.record_alloc(boxed.header().type_tag(), needed_cells, None);
 
         let insert_at = self.alloc_cells(needed_cells);
 
@@ -245,4 +297,67 @@ mod test {
         assert_eq!("HELLO", string1.as_str());
         assert_eq!("WORLD", string2.as_str());
     }
+
+    #[test]
+    fn external_storage_should_collect() {
+        use crate::boxed::FloatArray;
+
+        let mut heap = Heap::empty();
+        assert!(!heap.should_collect());
+
+        // This only occupies a single box but owns enough storage to require a collection
+        FloatArray::from_elem(&mut heap, Heap::DEFAULT_SEGMENT_CAPACITY * 8, 0.0);
+        assert!(heap.should_collect());
+    }
+
+    #[test]
+    fn alloc_profile() {
+        use crate::boxed::heap::collect::StrongPass;
+        use crate::boxed::heap::profile::AllocStats;
+        use crate::boxed::{Int, Str};
+
+        let mut heap = Heap::empty();
+
+        // Allocations before profiling is enabled aren't counted
+        Int::new(&mut heap, 1);
+        heap.enable_alloc_profile();
+
+        let mut boxed_int = Int::new(&mut heap, 2);
+        Str::new(&mut heap, "HELLO");
+
+        let site_name = b"site\0";
+        let site = unsafe { AllocSite::from_static_name(site_name.as_ptr() as *const _) };
+        heap.record_alloc(TypeTag::Int, 1, Some(site));
+
+        // The profile should survive collection without counting moved boxes
+        let mut strong_pass = StrongPass::new(heap);
+        strong_pass.visit_box(&mut boxed_int);
+        let heap = strong_pass.into_new_heap();
+
+        let alloc_profile = heap.alloc_profile().unwrap();
+        let cell_size = mem::size_of::<Any>();
+
+        assert_eq!(
+            AllocStats {
+                count: 2,
+                bytes: 2 * cell_size
+            },
+            alloc_profile.type_tag_stats(TypeTag::Int)
+        );
+        assert_eq!(1, alloc_profile.type_tag_stats(TypeTag::Str).count);
+        assert_eq!(3, alloc_profile.total().count);
+
+        let sites: Vec<_> = alloc_profile.sites().collect();
+        assert_eq!(
+            vec![(
+                site,
+                AllocStats {
+                    count: 1,
+                    bytes: cell_size
+                }
+            )],
+            sites
+        );
+        assert_eq!("site", sites[0].0.name());
+    }
 }
@@ -0,0 +1,163 @@
+//! Optional profiling of heap allocations
+//!
+//! Profiling is disabled by default. Once enabled with
+//! [`Heap::enable_alloc_profile`](crate::boxed::Heap::enable_alloc_profile) every box placed on
+//! the heap is counted by its type tag. Compiled code built with allocation profiling also
+//! attributes its allocations to the function that made them.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::{fmt, mem};
+
+use crate::boxed::{Any, TypeTag, ALL_TYPE_TAGS};
+
+/// Number and total size of profiled allocations
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct AllocStats {
+    /// Number of boxes allocated
+    pub count: usize,
+    /// Total size of the allocated boxes in bytes
+    pub bytes: usize,
+}
+
+impl AllocStats {
+    fn add_cells(&mut self, cell_count: usize) {
+        self.count += 1;
+        self.bytes += cell_count * mem::size_of::<Any>();
+    }
+}
+
+/// Site in compiled code responsible for an allocation
+///
+/// This is the name of the compiled function containing the allocation. Sites are compared by the
+/// address of their name; the compiler emits one name per function.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AllocSite(*const c_char);
+
+impl AllocSite {
+    /// Creates a site from a pointer to its name
+    ///
+    /// # Safety
+    ///
+    /// `name` must point to a static NUL terminated string.
+    pub unsafe fn from_static_name(name: *const c_char) -> AllocSite {
+        AllocSite(name)
+    }
+
+    /// Returns the name of the site
+    pub fn name(&self) -> &'static str {
+        unsafe { CStr::from_ptr(self.0) }
+            .to_str()
+            .unwrap_or("<invalid>")
+    }
+}
+
+/// Profile of all allocations made on a heap since profiling was enabled
+///
+/// This is carried across garbage collections. Boxes moved by the collector aren't counted again.
+#[derive(Clone, Debug)]
+pub struct AllocProfile {
+    by_type_tag: Vec<AllocStats>,
+    by_site: HashMap<AllocSite, AllocStats>,
+}
+
+impl AllocProfile {
+    pub(super) fn new() -> AllocProfile {
+        AllocProfile {
+            by_type_tag: vec![AllocStats::default(); ALL_TYPE_TAGS.len()],
+            by_site: HashMap::new(),
+        }
+    }
+
+    pub(super) fn record(&mut self, type_tag: TypeTag, cell_count: usize, site: Option<AllocSite>) {
+        self.by_type_tag[type_tag as usize].add_cells(cell_count);
+
+        if let Some(site) = site {
+            self.by_site.entry(site).or_default().add_cells(cell_count);
+        }
+    }
+
+    /// Returns the allocation statistics for a given type tag
+    pub fn type_tag_stats(&self, type_tag: TypeTag) -> AllocStats {
+        self.by_type_tag[type_tag as usize]
+    }
+
+    /// Returns the allocation statistics of every type tag with at least one allocation
+    pub fn allocated_type_tags(&self) -> impl Iterator<Item = (TypeTag, AllocStats)> + '_ {
+        ALL_TYPE_TAGS
+            .iter()
+            .map(move |type_tag| (*type_tag, self.type_tag_stats(*type_tag)))
+            .filter(|(_, stats)| stats.count > 0)
+    }
+
+    /// Returns the allocation statistics of every site in compiled code
+    ///
+    /// Allocations made by the runtime or RFI functions aren't attributed to a site.
+    pub fn sites(&self) -> impl Iterator<Item = (AllocSite, AllocStats)> + '_ {
+        self.by_site.iter().map(|(site, stats)| (*site, *stats))
+    }
+
+    /// Returns the total statistics for every allocation
+    pub fn total(&self) -> AllocStats {
+        self.by_type_tag
+            .iter()
+            .fold(AllocStats::default(), |acc, stats| AllocStats {
+                count: acc.count + stats.count,
+                bytes: acc.bytes + stats.bytes,
+            })
+    }
+}
+
+impl fmt::Display for AllocProfile {
+    /// Formats the profile as a human readable report
+    ///
+    /// Types and sites are sorted by the number of bytes they allocated.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        writeln!(
+            f,
+            "{} allocations totalling {} bytes",
+            total.count, total.bytes
+        )?;
+
+        let mut type_tag_stats: Vec<_> = self.allocated_type_tags().collect();
+        type_tag_stats.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.bytes));
+
+        writeln!(f, "\nBy type:")?;
+        for (type_tag, stats) in type_tag_stats {
+            writeln!(
+                f,
+                "{:>12} {:>12} bytes  {}",
+                stats.count,
+                stats.bytes,
+                type_tag.to_str()
+            )?;
+        }
+
+        let mut site_stats: Vec<_> = self.sites().collect();
+        if site_stats.is_empty() {
+            return Ok(());
+        }
+
+        site_stats.sort_by(|(site_a, stats_a), (site_b, stats_b)| {
+            stats_b
+                .bytes
+                .cmp(&stats_a.bytes)
+                .then_with(|| site_a.name().cmp(site_b.name()))
+        });
+
+        writeln!(f, "\nBy site:")?;
+        for (site, stats) in site_stats {
+            writeln!(
+                f,
+                "{:>12} {:>12} bytes  {}",
+                stats.count,
+                stats.bytes,
+                site.name()
+            )?;
+        }
+
+        Ok(())
+    }
+}
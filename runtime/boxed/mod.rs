@@ -21,8 +21,10 @@ use std::{fmt, ptr};
 use crate::abitype::{BoxedAbiType, EncodeBoxedAbiType};
 use crate::boxed::refs::Gc;
 
-pub use crate::boxed::heap::{collect, type_info};
+pub use crate::boxed::heap::{collect, profile, type_info};
 pub use crate::boxed::heap::{AsHeap, Heap};
+pub use crate::boxed::types::big_int::BigInt;
+pub use crate::boxed::types::bytes::{Bytes, BytesStorage};
 pub use crate::boxed::types::char::Char;
 pub use crate::boxed::types::field_value::{FieldValue, FieldValueIter};
 pub use crate::boxed::types::float::Float;
@@ -30,6 +32,8 @@ pub use crate::boxed::types::fun::{Captures, FunThunk, ThunkEntry};
 pub use crate::boxed::types::int::Int;
 pub use crate::boxed::types::list::{List, ListSubtype, Nil, Pair, NIL_INSTANCE};
 pub use crate::boxed::types::map::Map;
+pub use crate::boxed::types::num_array::{FloatArray, IntArray, NumArray, NumArrayElement};
+pub use crate::boxed::types::rational::Rational;
 pub use crate::boxed::types::record::{Record, RecordClassId, RecordStorage};
 pub use crate::boxed::types::record_data::RecordData;
 pub use crate::boxed::types::set::Set;
@@ -41,6 +45,7 @@ pub use crate::boxed::types::vector::Vector;
 pub mod prelude {
     pub use super::AsHeap;
     pub use super::Boxed;
+    pub use super::DebugWithHeap;
     pub use super::DistinctTagged;
     pub use super::HashInHeap;
     pub use super::PartialEqInHeap;
@@ -173,11 +178,66 @@ where
     }
 }
 
+/// Equivalent of [`fmt::Debug`] that receives an additional [`Heap`] parameter
+///
+/// This allows values referring to metadata in the heap to be formatted readably. For example,
+/// symbols are formatted with their name instead of their index in the heap's interner.
+pub trait DebugWithHeap {
+    /// Formats the value using the given formatter
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Returns a wrapper implementing [`fmt::Debug`] for this value
+    fn with_heap<'a>(&'a self, heap: &'a Heap) -> WithHeap<'a, Self> {
+        WithHeap { value: self, heap }
+    }
+}
+
+/// Value paired with the [`Heap`] required to format it
+///
+/// This is returned by [`DebugWithHeap::with_heap`].
+pub struct WithHeap<'a, T: ?Sized> {
+    value: &'a T,
+    heap: &'a Heap,
+}
+
+impl<T: DebugWithHeap + ?Sized> fmt::Debug for WithHeap<'_, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt_with_heap(self.heap, formatter)
+    }
+}
+
+/// Implements [`DebugWithHeap`] for types that don't need the heap to be formatted
+macro_rules! impl_debug_with_heap_via_debug {
+    ($($name:ty),*) => {
+        $(
+            impl DebugWithHeap for $name {
+                fn fmt_with_heap(&self, _heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Debug::fmt(self, formatter)
+                }
+            }
+        )*
+    };
+}
+
+impl_debug_with_heap_via_debug!(Float, Int, Char, Str, Nil, FunThunk, Bytes, Rational, BigInt);
+
+/// Calculates the hash used to index a value inside a [`Set`] or [`Map`]
+///
+/// This is deterministic between processes. This allows the compiler to generate constant sets and
+/// maps that are indexed identically to ones built at runtime.
+pub fn collection_hash<T: Boxed>(heap: &Heap, value: Gc<T>) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+
+    let mut state = DefaultHasher::new();
+    value.hash_in_heap(heap, &mut state);
+    state.finish()
+}
+
 /// Boxed value
 ///
 /// Boxes can be allocated on the stack, heap or a static constant. Every box is tagged with a
 /// top-level type.
-pub trait Boxed: Sized + PartialEqInHeap + HashInHeap + fmt::Debug {
+pub trait Boxed: Sized + PartialEqInHeap + HashInHeap + DebugWithHeap + fmt::Debug {
     /// Casts this value to an `Any` reference
     fn as_any_ref(&self) -> Gc<Any> {
         unsafe { Gc::new(&*(self as *const Self as *const Any)) }
@@ -294,6 +354,23 @@ impl TypeTag {
     pub fn to_heap_header(self, box_size: BoxSize) -> Header {
         Header::new(self, box_size.to_heap_alloc_type())
     }
+
+    /// Returns if boxes of this type can refer to other boxes or interned symbols
+    ///
+    /// Boxes of any other type are leaves; the garbage collector doesn't need to visit their
+    /// contents.
+    pub fn may_contain_gc_refs(self) -> bool {
+        matches!(
+            self,
+            TypeTag::Sym
+                | TypeTag::Pair
+                | TypeTag::Vector
+                | TypeTag::FunThunk
+                | TypeTag::Record
+                | TypeTag::Set
+                | TypeTag::Map
+        )
+    }
 }
 
 macro_rules! define_singleton_box {
@@ -314,6 +391,8 @@ macro_rules! define_singleton_box {
         impl Boxed for $type_name {}
         impl UniqueTagged for $type_name {}
 
+        impl_debug_with_heap_via_debug!($type_name);
+
         $(#[$static_docs])*
         #[export_name = $export_name]
         pub static $static_name: $type_name = $type_name {
@@ -425,6 +504,18 @@ macro_rules! define_supertype {
             }
         }
 
+        impl DebugWithHeap for $name {
+            fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.as_subtype() {
+                    $(
+                        $subtype_enum::$member(subtype) => {
+                            subtype.fmt_with_heap(heap, formatter)
+                        }
+                    )*
+                }
+            }
+        }
+
         impl Drop for $name {
             fn drop(&mut self) {
                 // Cast to the correct type so Rust knows which Drop implementation to call
@@ -501,7 +592,12 @@ define_const_tagged_boxes! {
     FunThunk,
     Record,
     Set,
-    Map
+    Map,
+    FloatArray,
+    IntArray,
+    Bytes,
+    Rational,
+    BigInt
 }
 
 define_singleton_box!(
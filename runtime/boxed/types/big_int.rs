@@ -0,0 +1,102 @@
+use std::hash::{Hash, Hasher};
+use std::{fmt, mem};
+
+use crate::boxed::refs::Gc;
+use crate::boxed::*;
+
+/// Boxed arbitrary-precision integer
+///
+/// The integer's digits are stored out-of-line and freed when the box is garbage collected. These
+/// are only used for integers that don't fit in an [`Int`].
+#[repr(C, align(16))]
+pub struct BigInt {
+    header: Header,
+    value: Box<num_bigint::BigInt>,
+}
+
+impl Boxed for BigInt {}
+impl UniqueTagged for BigInt {}
+
+impl BigInt {
+    /// Constructs a new instance with the specified value
+    pub fn new(heap: &mut impl AsHeap, value: num_bigint::BigInt) -> Gc<BigInt> {
+        let boxed = BigInt {
+            header: Self::TYPE_TAG.to_heap_header(Self::size()),
+            value: Box::new(value),
+        };
+
+        let heap = heap.as_heap_mut();
+        heap.note_external_bytes(boxed.external_value_bytes());
+        heap.place_box(boxed)
+    }
+
+    /// Returns the box size for big integers
+    pub fn size() -> BoxSize {
+        BoxSize::Size16
+    }
+
+    /// Returns the value of the big integer
+    pub fn value(&self) -> &num_bigint::BigInt {
+        &self.value
+    }
+
+    /// Returns the number of bytes of storage owned outside of the integer's box
+    ///
+    /// This is an estimate as the capacity of the digit storage isn't exposed.
+    pub(crate) fn external_value_bytes(&self) -> usize {
+        mem::size_of::<num_bigint::BigInt>() + ((self.value.bits() + 7) / 8) as usize
+    }
+}
+
+impl PartialEq for BigInt {
+    fn eq(&self, other: &BigInt) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl Hash for BigInt {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Self::TYPE_TAG.hash(state);
+        self.value().hash(state);
+    }
+}
+
+impl fmt::Debug for BigInt {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(formatter, "BigInt({})", self.value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boxed::heap::Heap;
+    use std::mem;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(16, mem::size_of::<BigInt>());
+    }
+
+    #[test]
+    fn equality() {
+        let mut heap = Heap::empty();
+
+        let big_value: num_bigint::BigInt = "10223372036854775807".parse().unwrap();
+
+        let boxed_big1 = BigInt::new(&mut heap, big_value.clone());
+        let boxed_big2 = BigInt::new(&mut heap, big_value.clone());
+        let boxed_negative = BigInt::new(&mut heap, -big_value);
+
+        assert_eq!(boxed_big1, boxed_big2);
+        assert_ne!(boxed_big1, boxed_negative);
+    }
+
+    #[test]
+    fn fmt_debug() {
+        let mut heap = Heap::empty();
+
+        let boxed_big = BigInt::new(&mut heap, "-10223372036854775807".parse().unwrap());
+        assert_eq!("BigInt(-10223372036854775807)", format!("{:?}", boxed_big));
+    }
+}
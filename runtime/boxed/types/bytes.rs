@@ -0,0 +1,190 @@
+use std::hash::{Hash, Hasher};
+use std::{fmt, slice};
+
+use crate::boxed::refs::Gc;
+use crate::boxed::*;
+
+/// Owner of the memory backing a [`Bytes`] box
+///
+/// The storage is dropped when its box is garbage collected. This allows the byte data to live
+/// outside of the GC heap; for example, a read-only memory mapped file can be exposed without
+/// copying it.
+pub trait BytesStorage: Send + Sync + 'static {
+    /// Returns the stored bytes
+    ///
+    /// This must return the same slice for the lifetime of the storage.
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl BytesStorage for Vec<u8> {
+    fn as_bytes(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl BytesStorage for Box<[u8]> {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Immutable sequence of bytes
+///
+/// The byte data is stored out-of-line in a [`BytesStorage`] owned by the box. Boxes contain no
+/// references to other boxes so they're treated as leaves by the garbage collector.
+#[repr(C, align(16))]
+pub struct Bytes {
+    header: Header,
+    len: usize,
+    data: *const u8,
+    storage: *mut Box<dyn BytesStorage>,
+}
+
+impl Boxed for Bytes {}
+impl UniqueTagged for Bytes {}
+
+impl Bytes {
+    /// Constructs a new instance by copying the passed bytes
+    pub fn new(heap: &mut impl AsHeap, value: &[u8]) -> Gc<Bytes> {
+        Self::from_storage(heap, value.to_vec())
+    }
+
+    /// Constructs a new instance taking ownership of the passed storage
+    ///
+    /// The storage will be dropped once the box is garbage collected.
+    pub fn from_storage(heap: &mut impl AsHeap, storage: impl BytesStorage) -> Gc<Bytes> {
+        let storage: *mut Box<dyn BytesStorage> = Box::into_raw(Box::new(Box::new(storage)));
+        let bytes = unsafe { (*storage).as_bytes() };
+
+        let heap = heap.as_heap_mut();
+        heap.note_external_bytes(bytes.len());
+
+        heap.place_box(Bytes {
+            header: Self::TYPE_TAG.to_heap_header(Self::size()),
+            len: bytes.len(),
+            data: bytes.as_ptr(),
+            storage,
+        })
+    }
+
+    /// Returns the box size for bytes
+    pub fn size() -> BoxSize {
+        BoxSize::Size32
+    }
+
+    /// Returns the length of the bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no bytes
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the bytes as a slice
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data, self.len) }
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Bytes) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Hash for Bytes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Self::TYPE_TAG.hash(state);
+        self.as_slice().hash(state);
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        formatter.write_str("Bytes(")?;
+        formatter.debug_list().entries(self.as_slice()).finish()?;
+        formatter.write_str(")")
+    }
+}
+
+impl Drop for Bytes {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(self.storage));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boxed::heap::Heap;
+    use std::mem;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(32, mem::size_of::<Bytes>());
+    }
+
+    #[test]
+    fn equality() {
+        let mut heap = Heap::empty();
+
+        let boxed_one_two1 = Bytes::new(&mut heap, &[1, 2]);
+        let boxed_one_two2 = Bytes::from_storage(&mut heap, vec![1u8, 2]);
+        let boxed_two_one = Bytes::new(&mut heap, &[2, 1]);
+
+        assert_eq!(boxed_one_two1, boxed_one_two2);
+        assert_ne!(boxed_one_two1, boxed_two_one);
+    }
+
+    #[test]
+    fn storage_dropped_with_heap() {
+        struct TrackedStorage {
+            data: Vec<u8>,
+            dropped: Arc<AtomicBool>,
+        }
+
+        impl BytesStorage for TrackedStorage {
+            fn as_bytes(&self) -> &[u8] {
+                &self.data
+            }
+        }
+
+        impl Drop for TrackedStorage {
+            fn drop(&mut self) {
+                self.dropped.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        {
+            let mut heap = Heap::empty();
+            let boxed_bytes = Bytes::from_storage(
+                &mut heap,
+                TrackedStorage {
+                    data: vec![1, 2, 3],
+                    dropped: dropped.clone(),
+                },
+            );
+
+            assert_eq!(&[1, 2, 3], boxed_bytes.as_slice());
+            assert!(!dropped.load(Ordering::Relaxed));
+        }
+
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn fmt_debug() {
+        let mut heap = Heap::empty();
+
+        let boxed_bytes = Bytes::new(&mut heap, &[1, 2]);
+        assert_eq!("Bytes([1, 2])", format!("{:?}", boxed_bytes));
+    }
+}
@@ -1,5 +1,5 @@
 use std::hash::{Hash, Hasher};
-use std::ptr;
+use std::{fmt, ptr};
 
 use crate::boxed;
 use crate::boxed::prelude::*;
@@ -42,6 +42,21 @@ impl PartialEqInHeap for FieldValue {
     }
 }
 
+impl DebugWithHeap for FieldValue {
+    fn fmt_with_heap(&self, heap: &boxed::Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Bool(v) => write!(formatter, "{:?}", v),
+            FieldValue::Char(v) => write!(formatter, "{:?}", v),
+            FieldValue::Float(v) => write!(formatter, "{:?}", v),
+            FieldValue::Int(v) => write!(formatter, "{:?}", v),
+            FieldValue::InternedSym(v) => {
+                write!(formatter, "'{}", heap.type_info().interner().unintern(v))
+            }
+            FieldValue::Boxed(v) => v.fmt_with_heap(heap, formatter),
+        }
+    }
+}
+
 impl HashInHeap for FieldValue {
     fn hash_in_heap<H: Hasher>(&self, heap: &boxed::Heap, state: &mut H) {
         match self {
@@ -56,7 +71,7 @@ impl HashInHeap for FieldValue {
                 }
             }
             FieldValue::Int(v) => (*v).hash(state),
-            FieldValue::InternedSym(v) => (*v).hash(state),
+            FieldValue::InternedSym(v) => heap.type_info().interner().unintern(v).hash(state),
             FieldValue::Boxed(v) => v.hash_in_heap(heap, state),
         }
     }
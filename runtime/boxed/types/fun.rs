@@ -26,7 +26,7 @@ pub type ThunkEntry = extern "C" fn(&mut task::Task, Captures, Gc<Any>) -> Gc<An
 pub struct FunThunk {
     header: Header,
     pub(crate) captures: Captures,
-    entry: ThunkEntry,
+    pub(crate) entry: ThunkEntry,
 }
 
 impl Boxed for FunThunk {}
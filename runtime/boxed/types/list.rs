@@ -89,6 +89,12 @@ impl<T: Boxed> fmt::Debug for Pair<T> {
     }
 }
 
+impl<T: Boxed> DebugWithHeap for Pair<T> {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_list_ref().fmt_with_heap(heap, formatter)
+    }
+}
+
 /// List of boxed values
 ///
 /// This allows O(n) access to its elements. It has the benefit of allowing constant time prepends
@@ -251,6 +257,20 @@ impl<T: Boxed> fmt::Debug for List<T> {
     }
 }
 
+impl<T: Boxed> DebugWithHeap for List<T> {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("List(")?;
+
+        let mut debug_list = formatter.debug_list();
+        for elem in self.iter() {
+            debug_list.entry(&elem.with_heap(heap));
+        }
+        debug_list.finish()?;
+
+        formatter.write_str(")")
+    }
+}
+
 pub struct ListIterator<T: Boxed> {
     head: Gc<List<T>>,
 }
@@ -352,6 +372,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn fmt_debug_with_heap() {
+        let mut heap = Heap::empty();
+
+        let long_name = "a-symbol-name-longer-than-inline-storage";
+        let inner_list = List::from_values(&mut heap, [long_name].iter().cloned(), Sym::new);
+        let boxed_one = Int::new(&mut heap, 1);
+        let outer_list = List::<Any>::new(
+            &mut heap,
+            [inner_list.as_any_ref(), boxed_one.as_any_ref()]
+                .iter()
+                .cloned(),
+        );
+
+        assert_eq!(
+            format!("List([List([Sym('{})]), Int(1)])", long_name),
+            format!("{:?}", outer_list.with_heap(&heap))
+        );
+    }
+
     #[test]
     fn construct_and_iter() {
         let mut heap = Heap::empty();
@@ -1,15 +1,27 @@
-use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::{fmt, ptr, slice};
 
 use crate::abitype::{BoxedAbiType, EncodeBoxedAbiType};
 use crate::boxed::refs::Gc;
 use crate::boxed::*;
 
+/// Entry in a map with the pre-calculated hash of its key
+#[repr(C)]
+pub struct MapEntry<K: Boxed, V: Boxed> {
+    hash: u64,
+    key: Gc<K>,
+    value: Gc<V>,
+}
+
 /// Immutable map of boxed values
+///
+/// Entries are stored out-of-line sorted by the hash of their key.
 #[repr(C, align(16))]
 pub struct Map<K: Boxed = Any, V: Boxed = Any> {
     header: Header,
+    entries_len: u32,
+    sorted_entries: *mut MapEntry<K, V>,
     _key: PhantomData<K>,
     _value: PhantomData<V>,
 }
@@ -17,17 +29,52 @@ pub struct Map<K: Boxed = Any, V: Boxed = Any> {
 impl<K: Boxed, V: Boxed> Boxed for Map<K, V> {}
 
 impl<K: Boxed, V: Boxed> Map<K, V> {
-    /// Constructs a new map with the given values
+    /// Constructs a new map with the given entries
+    ///
+    /// If a key appears multiple times the last entry for the key is used.
     pub fn new(
         heap: &mut impl AsHeap,
-        values: impl ExactSizeIterator<Item = (Gc<K>, Gc<V>)>,
+        entries: impl ExactSizeIterator<Item = (Gc<K>, Gc<V>)>,
     ) -> Gc<Map<K, V>> {
-        if values.len() != 0 {
-            todo!("non-empty maps");
+        let heap = heap.as_heap_mut();
+
+        let mut hashed_entries: Vec<MapEntry<K, V>> = entries
+            .map(|(key, value)| MapEntry {
+                hash: collection_hash(heap, key),
+                key,
+                value,
+            })
+            .collect();
+
+        // This is a stable sort so entries for the same key stay in their original order
+        hashed_entries.sort_by_key(|entry| entry.hash);
+
+        let mut sorted_entries: Vec<MapEntry<K, V>> = Vec::with_capacity(hashed_entries.len());
+        for entry in hashed_entries {
+            let existing_entry = sorted_entries
+                .iter_mut()
+                .rev()
+                .take_while(|existing| existing.hash == entry.hash)
+                .find(|existing| existing.key.eq_in_heap(heap, &entry.key));
+
+            if let Some(existing_entry) = existing_entry {
+                existing_entry.value = entry.value;
+            } else {
+                sorted_entries.push(entry);
+            }
         }
 
-        heap.as_heap_mut().place_box(Map {
+        let entries_len = sorted_entries.len();
+        let sorted_entries = if entries_len == 0 {
+            ptr::null_mut()
+        } else {
+            Box::into_raw(sorted_entries.into_boxed_slice()) as *mut MapEntry<K, V>
+        };
+
+        heap.place_box(Map {
             header: Map::TYPE_TAG.to_heap_header(Self::size()),
+            entries_len: entries_len as u32,
+            sorted_entries,
             _key: PhantomData,
             _value: PhantomData,
         })
@@ -37,20 +84,15 @@ impl<K: Boxed, V: Boxed> Map<K, V> {
     pub fn from_values<T, F>(
         heap: &mut impl AsHeap,
         values: impl ExactSizeIterator<Item = T>,
-        _cons: F,
+        cons: F,
     ) -> Gc<Map<K, V>>
     where
         F: Fn(&mut Heap, T) -> (Gc<K>, Gc<V>),
     {
-        if values.len() != 0 {
-            todo!("non-empty maps");
-        }
+        let heap = heap.as_heap_mut();
 
-        heap.as_heap_mut().place_box(Map {
-            header: Map::TYPE_TAG.to_heap_header(Self::size()),
-            _key: PhantomData,
-            _value: PhantomData,
-        })
+        let entries: Vec<(Gc<K>, Gc<V>)> = values.map(|v| cons(heap, v)).collect();
+        Self::new(heap, entries.into_iter())
     }
 
     /// Returns the box size for maps
@@ -58,32 +100,92 @@ impl<K: Boxed, V: Boxed> Map<K, V> {
         BoxSize::Size16
     }
 
+    fn sorted_entries(&self) -> &[MapEntry<K, V>] {
+        if self.entries_len == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.sorted_entries, self.entries_len as usize) }
+        }
+    }
+
+    fn sorted_entries_mut(&mut self) -> &mut [MapEntry<K, V>] {
+        if self.entries_len == 0 {
+            &mut []
+        } else {
+            unsafe { slice::from_raw_parts_mut(self.sorted_entries, self.entries_len as usize) }
+        }
+    }
+
     /// Return if the map is empty
     pub fn is_empty(&self) -> bool {
-        true
+        self.entries_len == 0
     }
 
     /// Returns the number of the entries in the map
     pub fn len(&self) -> usize {
-        0
+        self.entries_len as usize
     }
 
     /// Returns an iterator over the entries in map
-    pub fn iter(&self) -> impl Iterator<Item = (Gc<K>, Gc<V>)> + '_ {
-        std::iter::empty()
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Gc<K>, Gc<V>)> + '_ {
+        self.sorted_entries()
+            .iter()
+            .map(|entry| (entry.key, entry.value))
+    }
+
+    /// Returns the value for the passed key
+    pub fn get(&self, heap: &Heap, key: &Gc<K>) -> Option<Gc<V>> {
+        let sorted_entries = self.sorted_entries();
+        let key_hash = collection_hash(heap, *key);
+
+        let start_index = sorted_entries.partition_point(|entry| entry.hash < key_hash);
+
+        sorted_entries[start_index..]
+            .iter()
+            .take_while(|entry| entry.hash == key_hash)
+            .find(|entry| entry.key.eq_in_heap(heap, key))
+            .map(|entry| entry.value)
+    }
+
+    /// Returns true if the map contains the passed key
+    pub fn contains_key(&self, heap: &Heap, key: &Gc<K>) -> bool {
+        self.get(heap, key).is_some()
+    }
+
+    pub(crate) fn visit_mut_entries<F>(&mut self, visitor: &mut F)
+    where
+        F: FnMut(&mut Gc<K>, &mut Gc<V>),
+    {
+        for entry in self.sorted_entries_mut() {
+            visitor(&mut entry.key, &mut entry.value);
+        }
     }
 }
 
 impl<K: Boxed, V: Boxed> PartialEqInHeap for Map<K, V> {
-    fn eq_in_heap(&self, _heap: &Heap, _other: &Map<K, V>) -> bool {
-        // Both maps must be empty
-        true
+    fn eq_in_heap(&self, heap: &Heap, other: &Map<K, V>) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+
+        self.iter().all(|(key, self_value)| {
+            other
+                .get(heap, &key)
+                .map(|other_value| self_value.eq_in_heap(heap, &other_value))
+                .unwrap_or(false)
+        })
     }
 }
 
 impl<K: Boxed, V: Boxed> HashInHeap for Map<K, V> {
     fn hash_in_heap<H: Hasher>(&self, _heap: &Heap, state: &mut H) {
         TypeTag::Map.hash(state);
+        state.write_usize(self.len());
+
+        // Use the pre-calculated key hashes; equal maps have the same sorted key hashes
+        for entry in self.sorted_entries() {
+            state.write_u64(entry.hash);
+        }
     }
 }
 
@@ -95,6 +197,32 @@ impl<K: Boxed, V: Boxed> fmt::Debug for Map<K, V> {
     }
 }
 
+impl<K: Boxed, V: Boxed> DebugWithHeap for Map<K, V> {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("Map(")?;
+
+        let mut debug_list = formatter.debug_list();
+        for (key, value) in self.iter() {
+            debug_list.entry(&(key.with_heap(heap), value.with_heap(heap)));
+        }
+        debug_list.finish()?;
+
+        formatter.write_str(")")
+    }
+}
+
+impl<K: Boxed, V: Boxed> Drop for Map<K, V> {
+    fn drop(&mut self) {
+        if !self.sorted_entries.is_null() {
+            unsafe {
+                drop(Box::from_raw(
+                    self.sorted_entries_mut() as *mut [MapEntry<K, V>]
+                ));
+            }
+        }
+    }
+}
+
 impl<K: Boxed, V: Boxed> EncodeBoxedAbiType for Map<K, V>
 where
     K: EncodeBoxedAbiType,
@@ -106,10 +234,85 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::boxed::heap::Heap;
     use std::mem;
 
     #[test]
     fn sizes() {
         assert_eq!(16, mem::size_of::<Map<Any>>());
     }
+
+    #[test]
+    fn get() {
+        let mut heap = Heap::empty();
+
+        let one = Sym::new(&mut heap, "one");
+        let two = Sym::new(&mut heap, "two");
+        let three = Sym::new(&mut heap, "three");
+
+        let boxed1 = Int::new(&mut heap, 1);
+        let boxed2 = Int::new(&mut heap, 2);
+
+        let empty_map = Map::<Sym, Int>::new(&mut heap, std::iter::empty());
+        let map = Map::new(
+            &mut heap,
+            IntoIterator::into_iter([(one, boxed1), (two, boxed2)]),
+        );
+
+        assert_eq!(None, empty_map.get(&heap, &one));
+
+        assert_eq!(2, map.len());
+        assert_eq!(Some(boxed1), map.get(&heap, &one));
+        assert_eq!(Some(boxed2), map.get(&heap, &two));
+        assert_eq!(None, map.get(&heap, &three));
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let mut heap = Heap::empty();
+
+        let one = Sym::new(&mut heap, "one");
+        let boxed1 = Int::new(&mut heap, 1);
+        let boxed2 = Int::new(&mut heap, 2);
+
+        let map = Map::new(
+            &mut heap,
+            IntoIterator::into_iter([(one, boxed1), (one, boxed2)]),
+        );
+
+        assert_eq!(1, map.len());
+        assert_eq!(Some(boxed2), map.get(&heap, &one));
+    }
+
+    #[test]
+    fn equality() {
+        let mut heap = Heap::empty();
+
+        let one = Sym::new(&mut heap, "one");
+        let two = Sym::new(&mut heap, "two");
+
+        let boxed1 = Int::new(&mut heap, 1);
+        let boxed2 = Int::new(&mut heap, 2);
+
+        let forward_map = Map::new(
+            &mut heap,
+            IntoIterator::into_iter([(one, boxed1), (two, boxed2)]),
+        );
+
+        let reverse_map = Map::new(
+            &mut heap,
+            IntoIterator::into_iter([(two, boxed2), (one, boxed1)]),
+        );
+
+        let swapped_map = Map::new(
+            &mut heap,
+            IntoIterator::into_iter([(one, boxed2), (two, boxed1)]),
+        );
+
+        let partial_map = Map::new(&mut heap, IntoIterator::into_iter([(one, boxed1)]));
+
+        assert!(forward_map.eq_in_heap(&heap, &reverse_map));
+        assert!(!forward_map.eq_in_heap(&heap, &swapped_map));
+        assert!(!forward_map.eq_in_heap(&heap, &partial_map));
+    }
 }
@@ -1,3 +1,5 @@
+pub mod big_int;
+pub mod bytes;
 pub mod char;
 pub mod field_value;
 pub mod float;
@@ -5,6 +7,8 @@ pub mod fun;
 pub mod int;
 pub mod list;
 pub mod map;
+pub mod num_array;
+pub mod rational;
 pub mod record;
 pub mod record_data;
 pub mod set;
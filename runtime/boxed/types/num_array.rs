@@ -0,0 +1,243 @@
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+use std::{fmt, mem, ptr, slice};
+
+use crate::boxed::refs::Gc;
+use crate::boxed::*;
+
+/// Unboxed numeric type that can be stored in a [`NumArray`]
+pub trait NumArrayElement: Copy + PartialEq + fmt::Debug + 'static {
+    /// Type tag for arrays of this element type
+    const ARRAY_TYPE_TAG: TypeTag;
+
+    /// Feeds this element into the given [`Hasher`]
+    fn hash_element<H: Hasher>(self, state: &mut H);
+}
+
+impl NumArrayElement for f64 {
+    const ARRAY_TYPE_TAG: TypeTag = TypeTag::FloatArray;
+
+    fn hash_element<H: Hasher>(self, state: &mut H) {
+        // 0.0 == -0.0 so they need to hash to the same value
+        if self == 0.0 {
+            state.write_u64((0.0f64).to_bits())
+        } else {
+            state.write_u64(self.to_bits());
+        }
+    }
+}
+
+impl NumArrayElement for i64 {
+    const ARRAY_TYPE_TAG: TypeTag = TypeTag::IntArray;
+
+    fn hash_element<H: Hasher>(self, state: &mut H) {
+        state.write_i64(self)
+    }
+}
+
+/// Mutable fixed length array of unboxed numeric values
+///
+/// Unlike [`Vector`] the elements are stored contiguously without boxing. This makes them suitable
+/// for numeric workloads where the overhead of boxing each element would dominate. The array
+/// contains no references to other boxes so it's treated as a leaf by the garbage collector.
+///
+/// The element data is stored out-of-line and is owned by the box.
+#[repr(C, align(16))]
+pub struct NumArray<T: NumArrayElement> {
+    header: Header,
+    len: usize,
+    values: *mut Cell<T>,
+}
+
+/// Array of unboxed 64bit floating point values
+pub type FloatArray = NumArray<f64>;
+
+/// Array of unboxed signed 64bit integer values
+pub type IntArray = NumArray<i64>;
+
+impl<T: NumArrayElement> Boxed for NumArray<T> {}
+impl UniqueTagged for FloatArray {}
+impl UniqueTagged for IntArray {}
+
+impl<T: NumArrayElement> NumArray<T> {
+    /// Constructs a new array containing the passed values
+    pub fn new(heap: &mut impl AsHeap, values: impl Iterator<Item = T>) -> Gc<NumArray<T>> {
+        let cells: Box<[Cell<T>]> = values.map(Cell::new).collect();
+        Self::from_boxed_cells(heap, cells)
+    }
+
+    /// Constructs a new array of `len` elements all initialised to `value`
+    pub fn from_elem(heap: &mut impl AsHeap, len: usize, value: T) -> Gc<NumArray<T>> {
+        Self::from_boxed_cells(heap, vec![Cell::new(value); len].into_boxed_slice())
+    }
+
+    fn from_boxed_cells(heap: &mut impl AsHeap, cells: Box<[Cell<T>]>) -> Gc<NumArray<T>> {
+        let len = cells.len();
+        let values = Box::into_raw(cells) as *mut Cell<T>;
+
+        let heap = heap.as_heap_mut();
+        heap.note_external_bytes(len * mem::size_of::<T>());
+
+        heap.place_box(NumArray {
+            header: T::ARRAY_TYPE_TAG.to_heap_header(Self::size()),
+            len,
+            values,
+        })
+    }
+
+    /// Returns the box size for arrays
+    pub fn size() -> BoxSize {
+        BoxSize::Size32
+    }
+
+    /// Returns the length of the array
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the array is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the array's elements as a slice of cells
+    ///
+    /// Arrays are mutable; elements can be modified in-place through the returned cells.
+    pub fn as_cells(&self) -> &[Cell<T>] {
+        unsafe { slice::from_raw_parts(self.values, self.len) }
+    }
+
+    /// Returns the element at the provided index
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.as_cells().get(index).map(Cell::get)
+    }
+
+    /// Replaces the element at the provided index
+    ///
+    /// This will return [`None`] if the index is out of bounds.
+    pub fn set(&self, index: usize, value: T) -> Option<()> {
+        self.as_cells().get(index).map(|cell| cell.set(value))
+    }
+
+    /// Replaces every element in the array with `value`
+    pub fn fill(&self, value: T) {
+        for cell in self.as_cells() {
+            cell.set(value);
+        }
+    }
+
+    /// Returns an iterator over a snapshot of the array's elements
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = T> + '_ {
+        self.as_cells().iter().map(Cell::get)
+    }
+
+    /// Returns the number of bytes of element storage owned outside of the array's box
+    pub(crate) fn external_value_bytes(&self) -> usize {
+        self.len * mem::size_of::<T>()
+    }
+}
+
+impl<T: NumArrayElement> PartialEq for NumArray<T> {
+    fn eq(&self, other: &NumArray<T>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: NumArrayElement> Hash for NumArray<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        T::ARRAY_TYPE_TAG.hash(state);
+        state.write_usize(self.len());
+        for value in self.iter() {
+            value.hash_element(state);
+        }
+    }
+}
+
+impl<T: NumArrayElement> fmt::Debug for NumArray<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        formatter.write_str(T::ARRAY_TYPE_TAG.to_str())?;
+        formatter.write_str("(")?;
+        formatter.debug_list().entries(self.iter()).finish()?;
+        formatter.write_str(")")
+    }
+}
+
+impl<T: NumArrayElement> DebugWithHeap for NumArray<T> {
+    fn fmt_with_heap(&self, _heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, formatter)
+    }
+}
+
+impl<T: NumArrayElement> Drop for NumArray<T> {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                self.values,
+                self.len,
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boxed::heap::Heap;
+    use std::mem;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(32, mem::size_of::<FloatArray>());
+        assert_eq!(32, mem::size_of::<IntArray>());
+    }
+
+    #[test]
+    fn get_set() {
+        let mut heap = Heap::empty();
+
+        let boxed_array = IntArray::new(&mut heap, [1, 2, 3].iter().cloned());
+        assert_eq!(3, boxed_array.len());
+
+        assert_eq!(Some(2), boxed_array.get(1));
+        assert_eq!(None, boxed_array.get(3));
+
+        assert_eq!(Some(()), boxed_array.set(1, 20));
+        assert_eq!(None, boxed_array.set(3, 40));
+
+        assert_eq!(vec![1, 20, 3], boxed_array.iter().collect::<Vec<i64>>());
+
+        boxed_array.fill(7);
+        assert_eq!(vec![7, 7, 7], boxed_array.iter().collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn equality() {
+        let mut heap = Heap::empty();
+
+        let boxed_one_two1 = FloatArray::new(&mut heap, [1.0, 2.0].iter().cloned());
+        let boxed_one_two2 = FloatArray::from_elem(&mut heap, 2, 1.0);
+        boxed_one_two2.set(1, 2.0);
+
+        let boxed_two_one = FloatArray::new(&mut heap, [2.0, 1.0].iter().cloned());
+        let boxed_nan = FloatArray::from_elem(&mut heap, 1, std::f64::NAN);
+
+        assert_eq!(boxed_one_two1, boxed_one_two2);
+        assert_ne!(boxed_one_two1, boxed_two_one);
+
+        #[allow(clippy::eq_op)]
+        {
+            assert_ne!(boxed_nan, boxed_nan);
+        }
+    }
+
+    #[test]
+    fn fmt_debug() {
+        let mut heap = Heap::empty();
+
+        let boxed_float_array = FloatArray::new(&mut heap, [1.0, 2.0].iter().cloned());
+        assert_eq!("FloatArray([1.0, 2.0])", format!("{:?}", boxed_float_array));
+
+        let boxed_int_array = IntArray::new(&mut heap, std::iter::empty());
+        assert_eq!("IntArray([])", format!("{:?}", boxed_int_array));
+    }
+}
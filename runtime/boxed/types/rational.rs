@@ -0,0 +1,141 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::boxed::refs::Gc;
+use crate::boxed::*;
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+
+    a
+}
+
+/// Boxed exact rational number
+///
+/// Rationals are always stored in their lowest terms with a positive denominator. This allows
+/// them to be compared by their numerator and denominator.
+#[repr(C, align(16))]
+pub struct Rational {
+    header: Header,
+    numer: i64,
+    denom: i64,
+}
+
+impl Boxed for Rational {}
+impl UniqueTagged for Rational {}
+
+impl Rational {
+    /// Constructs a new rational reduced to its lowest terms
+    ///
+    /// This will return `None` if the denominator is zero or the reduced rational can't be
+    /// represented with 64bit integers.
+    pub fn new(heap: &mut impl AsHeap, numer: i64, denom: i64) -> Option<Gc<Rational>> {
+        if denom == 0 {
+            return None;
+        }
+
+        let divisor = gcd(numer.unsigned_abs(), denom.unsigned_abs()) as i64;
+        let (mut numer, mut denom) = (numer / divisor, denom / divisor);
+
+        if denom < 0 {
+            numer = numer.checked_neg()?;
+            denom = denom.checked_neg()?;
+        }
+
+        Some(heap.as_heap_mut().place_box(Rational {
+            header: Self::TYPE_TAG.to_heap_header(Self::size()),
+            numer,
+            denom,
+        }))
+    }
+
+    /// Returns the box size for rationals
+    pub fn size() -> BoxSize {
+        BoxSize::Size32
+    }
+
+    /// Returns the numerator of this rational
+    pub fn numer(&self) -> i64 {
+        self.numer
+    }
+
+    /// Returns the denominator of this rational
+    ///
+    /// This is always positive.
+    pub fn denom(&self) -> i64 {
+        self.denom
+    }
+
+    /// Returns the nearest floating point value to this rational
+    pub fn to_f64(&self) -> f64 {
+        self.numer as f64 / self.denom as f64
+    }
+}
+
+impl PartialEq for Rational {
+    fn eq(&self, other: &Rational) -> bool {
+        self.numer() == other.numer() && self.denom() == other.denom()
+    }
+}
+
+impl Hash for Rational {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Self::TYPE_TAG.hash(state);
+        self.numer().hash(state);
+        self.denom().hash(state);
+    }
+}
+
+impl fmt::Debug for Rational {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(formatter, "Rational({}/{})", self.numer, self.denom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boxed::heap::Heap;
+    use std::mem;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(32, mem::size_of::<Rational>());
+    }
+
+    #[test]
+    fn normalisation() {
+        let mut heap = Heap::empty();
+
+        let boxed_rational = Rational::new(&mut heap, 6, -8).unwrap();
+        assert_eq!(-3, boxed_rational.numer());
+        assert_eq!(4, boxed_rational.denom());
+
+        assert!(Rational::new(&mut heap, 1, 0).is_none());
+        assert!(Rational::new(&mut heap, 1, i64::MIN).is_none());
+    }
+
+    #[test]
+    fn equality() {
+        let mut heap = Heap::empty();
+
+        let boxed_half1 = Rational::new(&mut heap, 1, 2).unwrap();
+        let boxed_half2 = Rational::new(&mut heap, 2, 4).unwrap();
+        let boxed_third = Rational::new(&mut heap, 1, 3).unwrap();
+
+        assert_ne!(boxed_half1, boxed_third);
+        assert_eq!(boxed_half1, boxed_half2);
+    }
+
+    #[test]
+    fn fmt_debug() {
+        let mut heap = Heap::empty();
+
+        let boxed_half = Rational::new(&mut heap, 1, 2).unwrap();
+        assert_eq!("Rational(1/2)", format!("{:?}", boxed_half));
+    }
+}
@@ -63,18 +63,33 @@ impl Record {
         let storage = Self::storage_for_data_layout(data.layout());
         let box_size = storage.box_size();
 
+        let heap = heap.as_heap_mut();
+
+        // Records without data have no fields to look up
+        let may_contain_gc_refs = data.layout().is_some()
+            && heap
+                .type_info()
+                .class_map()
+                .class_may_contain_gc_refs(class_id);
+
+        let record_header = RecordHeader {
+            header: Record::TYPE_TAG.to_heap_header(box_size),
+            inline_byte_len: 0,
+            may_contain_gc_refs,
+            class_id,
+        };
+
         let boxed = unsafe {
             match storage {
                 RecordStorage::External => {
-                    mem::transmute(ExternalRecord::new(box_size, class_id, data))
-                }
-                RecordStorage::Inline(_) => {
-                    mem::transmute(InlineRecord::new(box_size, class_id, data))
+                    heap.note_external_bytes(data.layout().map_or(0, |layout| layout.size()));
+                    mem::transmute(ExternalRecord::new(record_header, data))
                 }
+                RecordStorage::Inline(_) => mem::transmute(InlineRecord::new(record_header, data)),
             }
         };
 
-        heap.as_heap_mut().place_box(boxed)
+        heap.place_box(boxed)
     }
 
     /// Returns the storage for given data layout
@@ -130,6 +145,41 @@ impl Record {
         }
     }
 
+    /// Returns a copy of the record's data
+    ///
+    /// Any boxes or interned symbols referenced by the data are copied as-is.
+    pub(crate) fn clone_data(&self) -> RecordData {
+        let data_layout = match self.as_repr() {
+            Repr::Inline(inline) => match inline.record_header.inline_byte_len {
+                0 => None,
+                inline_byte_len => Some(
+                    alloc::Layout::from_size_align(
+                        inline_byte_len as usize,
+                        Self::INLINE_DATA_ALIGNMENT,
+                    )
+                    .unwrap(),
+                ),
+            },
+            Repr::External(external) => external.external_data.layout(),
+        };
+
+        let mut data = RecordData::alloc(data_layout);
+        if let Some(data_layout) = data_layout {
+            unsafe {
+                ptr::copy_nonoverlapping(self.data_ptr(), data.as_mut_ptr(), data_layout.size());
+            }
+        }
+
+        data
+    }
+
+    fn has_data(&self) -> bool {
+        match self.as_repr() {
+            Repr::Inline(inline) => inline.record_header.inline_byte_len > 0,
+            Repr::External(_) => true,
+        }
+    }
+
     fn data_ptr(&self) -> *const u8 {
         match self.as_repr() {
             Repr::Inline(inline) => inline.inline_data.as_ptr() as *const u8,
@@ -137,6 +187,17 @@ impl Record {
         }
     }
 
+    /// Returns the number of bytes of record data owned outside of the record's box
+    pub(crate) fn external_data_bytes(&self) -> usize {
+        match self.as_repr() {
+            Repr::Inline(_) => 0,
+            Repr::External(external) => external
+                .external_data
+                .layout()
+                .map_or(0, |layout| layout.size()),
+        }
+    }
+
     fn is_empty(&self) -> bool {
         self.record_header.inline_byte_len == 0
     }
@@ -197,6 +258,25 @@ impl fmt::Debug for Record {
     }
 }
 
+impl DebugWithHeap for Record {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "Record({:?}", self.class_id())?;
+
+        // Records without data may not have a class registered in the heap
+        if self.has_data() {
+            formatter.write_str(", ")?;
+
+            let mut debug_list = formatter.debug_list();
+            for field_value in self.field_values(heap) {
+                debug_list.entry(&field_value.with_heap(heap));
+            }
+            debug_list.finish()?;
+        }
+
+        formatter.write_str(")")
+    }
+}
+
 #[repr(C, align(16))]
 struct InlineRecord {
     record_header: RecordHeader,
@@ -204,9 +284,7 @@ struct InlineRecord {
 }
 
 impl InlineRecord {
-    fn new(box_size: BoxSize, class_id: RecordClassId, data: RecordData) -> InlineRecord {
-        let header = Record::TYPE_TAG.to_heap_header(box_size);
-
+    fn new(record_header: RecordHeader, data: RecordData) -> InlineRecord {
         unsafe {
             let mut inline_data = mem::MaybeUninit::<[u8; Record::MAX_INLINE_BYTES]>::uninit();
 
@@ -220,14 +298,11 @@ impl InlineRecord {
 
             InlineRecord {
                 record_header: RecordHeader {
-                    header,
                     inline_byte_len: match data.layout() {
                         Some(layout) => layout.size() as u8,
                         None => 0,
                     },
-                    // This is conservative - we don't know if there are GC refs or not
-                    may_contain_gc_refs: data.layout().is_some(),
-                    class_id,
+                    ..record_header
                 },
                 inline_data,
             }
@@ -242,16 +317,11 @@ struct ExternalRecord {
 }
 
 impl ExternalRecord {
-    fn new(box_size: BoxSize, class_id: RecordClassId, data: RecordData) -> ExternalRecord {
-        let header = Record::TYPE_TAG.to_heap_header(box_size);
-
+    fn new(record_header: RecordHeader, data: RecordData) -> ExternalRecord {
         ExternalRecord {
             record_header: RecordHeader {
-                header,
                 inline_byte_len: std::u8::MAX,
-                // This is conservative - we don't know if there are GC refs or not
-                may_contain_gc_refs: true,
-                class_id,
+                ..record_header
             },
 
             external_data: data,
@@ -312,4 +382,64 @@ mod test {
         let boxed_one = Record::new(&mut heap, 1, RecordData::empty());
         assert_eq!("Record(1)", format!("{:?}", boxed_one));
     }
+
+    #[test]
+    fn may_contain_gc_refs() {
+        use crate::class_map::{BoxedClass, Field, FieldType};
+
+        let mut heap = Heap::empty();
+        let class_map = heap.type_info_mut().class_map_mut();
+
+        let int_class_id = class_map.push_dynamic_class(BoxedClass::from_fields(std::iter::once(
+            Field::new(FieldType::Int, 0),
+        )));
+        let boxed_class_id = class_map.push_dynamic_class(BoxedClass::from_fields(
+            std::iter::once(Field::new(FieldType::Boxed, 0)),
+        ));
+
+        let data_layout = Some(alloc::Layout::new::<i64>());
+
+        let int_record = Record::new(&mut heap, int_class_id, RecordData::alloc(data_layout));
+        assert!(!int_record.record_header.may_contain_gc_refs);
+
+        let boxed_record = Record::new(&mut heap, boxed_class_id, RecordData::alloc(data_layout));
+        assert!(boxed_record.record_header.may_contain_gc_refs);
+    }
+
+    #[test]
+    fn fmt_debug_with_heap() {
+        use crate::class_map::{BoxedClass, Field, FieldType};
+        use crate::intern::InternedSym;
+
+        let mut heap = Heap::empty();
+
+        let empty_record = Record::new(&mut heap, 1, RecordData::empty());
+        assert_eq!("Record(1)", format!("{:?}", empty_record.with_heap(&heap)));
+
+        let class_id =
+            heap.type_info_mut()
+                .class_map_mut()
+                .push_dynamic_class(BoxedClass::from_fields(
+                    vec![
+                        Field::new(FieldType::Int, 0),
+                        Field::new(FieldType::InternedSym, 8),
+                    ]
+                    .into_iter(),
+                ));
+
+        let interned = heap.type_info_mut().interner_mut().intern("field-value");
+
+        let data_layout = alloc::Layout::new::<(i64, InternedSym)>();
+        let mut record_data = RecordData::alloc(Some(data_layout));
+        unsafe {
+            let data_ptr = record_data.as_mut_ptr() as *mut (i64, InternedSym);
+            ptr::write(data_ptr, (42, interned));
+        }
+
+        let boxed_record = Record::new(&mut heap, class_id, record_data);
+        assert_eq!(
+            format!("Record({}, [42, 'field-value])", class_id),
+            format!("{:?}", boxed_record.with_heap(&heap))
+        );
+    }
 }
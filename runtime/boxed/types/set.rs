@@ -1,7 +1,6 @@
-use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::mem::MaybeUninit;
-use std::{fmt, marker, mem};
+use std::{fmt, marker, mem, slice};
 
 use crate::abitype::{BoxedAbiType, EncodeBoxedAbiType};
 use crate::boxed::refs::Gc;
@@ -54,13 +53,8 @@ impl<T: Boxed> Set<T> {
         let heap = heap.as_heap_mut();
 
         // Calculate the hash of our values
-        let mut hashed_values: Vec<(u64, Gc<T>)> = values
-            .map(|v| {
-                let mut state = DefaultHasher::new();
-                v.hash_in_heap(heap, &mut state);
-                (state.finish(), v)
-            })
-            .collect();
+        let mut hashed_values: Vec<(u64, Gc<T>)> =
+            values.map(|v| (collection_hash(heap, v), v)).collect();
 
         // Make the values sorted and unique
         hashed_values.sort_by_key(|(hash, _)| *hash);
@@ -159,6 +153,26 @@ impl<T: Boxed> Set<T> {
         }
     }
 
+    pub(crate) fn visit_mut_elements<F>(&mut self, visitor: &mut F)
+    where
+        F: FnMut(&mut Gc<T>),
+    {
+        match self.as_repr_mut() {
+            ReprMut::Inline(inline) => {
+                let inline_len = inline.len();
+
+                for value in inline.values[0..inline_len].iter_mut() {
+                    visitor(unsafe { &mut *value.as_mut_ptr() });
+                }
+            }
+            ReprMut::External(external) => {
+                for entry in external.sorted_hashed_values_mut() {
+                    visitor(&mut entry.value);
+                }
+            }
+        }
+    }
+
     /// Returns if this set is a subset of the passed set
     pub fn is_subset(&self, heap: &Heap, other: &Set<T>) -> bool {
         match (self.as_repr(), other.as_repr()) {
@@ -214,6 +228,20 @@ impl<T: Boxed> fmt::Debug for Set<T> {
     }
 }
 
+impl<T: Boxed> DebugWithHeap for Set<T> {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("Set(")?;
+
+        let mut debug_list = formatter.debug_list();
+        for elem in self.iter() {
+            debug_list.entry(&elem.with_heap(heap));
+        }
+        debug_list.finish()?;
+
+        formatter.write_str(")")
+    }
+}
+
 impl<T: Boxed> EncodeBoxedAbiType for Set<T>
 where
     T: EncodeBoxedAbiType,
@@ -278,41 +306,62 @@ impl<T: Boxed> InlineSet<T> {
     }
 }
 
+/// Value in an external set with its pre-calculated hash
+#[repr(C)]
+pub struct HashedValue<T: Boxed> {
+    hash: u64,
+    value: Gc<T>,
+}
+
 #[repr(C, align(16))]
 pub struct ExternalSet<T: Boxed> {
     header: Header,
     inline_len: u32,
-    sorted_hashed_values: Vec<(u64, Gc<T>)>,
+    values_len: usize,
+    sorted_hashed_values: *mut HashedValue<T>,
 }
 
 impl<T: Boxed> ExternalSet<T> {
     fn new(header: Header, sorted_hashed_values: Vec<(u64, Gc<T>)>) -> ExternalSet<T> {
+        let sorted_hashed_values: Box<[HashedValue<T>]> = sorted_hashed_values
+            .into_iter()
+            .map(|(hash, value)| HashedValue { hash, value })
+            .collect();
+
         ExternalSet {
             header,
             inline_len: Set::<T>::EXTERNAL_INLINE_LEN,
-            sorted_hashed_values,
+            values_len: sorted_hashed_values.len(),
+            sorted_hashed_values: Box::into_raw(sorted_hashed_values) as *mut HashedValue<T>,
         }
     }
 
+    fn sorted_hashed_values(&self) -> &[HashedValue<T>] {
+        unsafe { slice::from_raw_parts(self.sorted_hashed_values, self.values_len) }
+    }
+
+    fn sorted_hashed_values_mut(&mut self) -> &mut [HashedValue<T>] {
+        unsafe { slice::from_raw_parts_mut(self.sorted_hashed_values, self.values_len) }
+    }
+
     fn len(&self) -> usize {
-        self.sorted_hashed_values.len()
+        self.values_len
     }
 
     fn iter(&self) -> impl ExactSizeIterator<Item = &Gc<T>> {
-        self.sorted_hashed_values.iter().map(|(_, v)| v)
+        self.sorted_hashed_values()
+            .iter()
+            .map(|hashed_value| &hashed_value.value)
     }
 
     fn contains(&self, heap: &Heap, needle_value: &Gc<T>) -> bool {
-        // Hash our value
-        let mut state = DefaultHasher::new();
-        needle_value.hash_in_heap(heap, &mut state);
-        let needle_hash = state.finish();
+        let sorted_hashed_values = self.sorted_hashed_values();
+        let needle_hash = collection_hash(heap, *needle_value);
 
         // Do a binary search for the index
         // This will return an arbitrary matching index if there are multiple matches
-        let arbitrary_index = if let Ok(i) = self
-            .sorted_hashed_values
-            .binary_search_by_key(&needle_hash, |(haystack_hash, _)| *haystack_hash)
+        let arbitrary_index = if let Ok(i) =
+            sorted_hashed_values.binary_search_by_key(&needle_hash, |haystack| haystack.hash)
         {
             i
         } else {
@@ -322,17 +371,17 @@ impl<T: Boxed> ExternalSet<T> {
         // Search forwards through hash collisions, including the arbitrary index
         let mut forwards_index = arbitrary_index;
         loop {
-            let (hackstack_hash, haystack_value) = self.sorted_hashed_values[forwards_index];
+            let haystack = &sorted_hashed_values[forwards_index];
 
-            if hackstack_hash != needle_hash {
+            if haystack.hash != needle_hash {
                 break;
             }
-            if haystack_value.eq_in_heap(heap, needle_value) {
+            if haystack.value.eq_in_heap(heap, needle_value) {
                 return true;
             }
 
             forwards_index += 1;
-            if forwards_index >= self.sorted_hashed_values.len() {
+            if forwards_index >= sorted_hashed_values.len() {
                 break;
             }
         }
@@ -341,12 +390,12 @@ impl<T: Boxed> ExternalSet<T> {
         let mut backwards_index = arbitrary_index;
         while backwards_index > 0 {
             backwards_index -= 1;
-            let (hackstack_hash, haystack_value) = self.sorted_hashed_values[backwards_index];
+            let haystack = &sorted_hashed_values[backwards_index];
 
-            if hackstack_hash != needle_hash {
+            if haystack.hash != needle_hash {
                 break;
             }
-            if haystack_value.eq_in_heap(heap, needle_value) {
+            if haystack.value.eq_in_heap(heap, needle_value) {
                 return true;
             }
         }
@@ -356,11 +405,11 @@ impl<T: Boxed> ExternalSet<T> {
 
     /// Returns if this set is a subset of the passed set
     fn is_subset(&self, heap: &Heap, other: &ExternalSet<T>) -> bool {
-        let mut self_iter = self.sorted_hashed_values.iter();
-        let mut other_iter = other.sorted_hashed_values.iter();
+        let mut self_iter = self.sorted_hashed_values().iter();
+        let mut other_iter = other.sorted_hashed_values().iter();
 
         loop {
-            let (self_hash, self_value) = if let Some(entry) = self_iter.next() {
+            let self_entry = if let Some(entry) = self_iter.next() {
                 entry
             } else {
                 // No more elements left to check
@@ -369,7 +418,7 @@ impl<T: Boxed> ExternalSet<T> {
 
             // Try to find the element in the other set
             loop {
-                let (other_hash, other_value) = if let Some(entry) = other_iter.next() {
+                let other_entry = if let Some(entry) = other_iter.next() {
                     entry
                 } else {
                     // Ran past the end of the other set
@@ -379,10 +428,12 @@ impl<T: Boxed> ExternalSet<T> {
                 if self_iter.len() > other_iter.len() {
                     // Not enough items remaining in the other set
                     return false;
-                } else if other_hash == self_hash && other_value.eq_in_heap(heap, self_value) {
+                } else if other_entry.hash == self_entry.hash
+                    && other_entry.value.eq_in_heap(heap, &self_entry.value)
+                {
                     // Found corresponding element
                     break;
-                } else if other_hash > self_hash {
+                } else if other_entry.hash > self_entry.hash {
                     // We've gone past where the corresponding element should be
                     return false;
                 }
@@ -395,11 +446,12 @@ impl<T: Boxed> ExternalSet<T> {
             return false;
         }
 
-        self.sorted_hashed_values
+        self.sorted_hashed_values()
             .iter()
-            .zip(other.sorted_hashed_values.iter())
-            .all(|((self_hash, self_value), (other_hash, other_value))| {
-                self_hash == other_hash && self_value.eq_in_heap(heap, other_value)
+            .zip(other.sorted_hashed_values().iter())
+            .all(|(self_entry, other_entry)| {
+                self_entry.hash == other_entry.hash
+                    && self_entry.value.eq_in_heap(heap, &other_entry.value)
             })
     }
 
@@ -408,8 +460,18 @@ impl<T: Boxed> ExternalSet<T> {
         state.write_usize(self.len());
 
         // Instead of recursing into values, use their pre-calculated hash
-        for (hash, _) in self.sorted_hashed_values.iter() {
-            state.write_u64(*hash);
+        for entry in self.sorted_hashed_values() {
+            state.write_u64(entry.hash);
+        }
+    }
+}
+
+impl<T: Boxed> Drop for ExternalSet<T> {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(
+                self.sorted_hashed_values_mut() as *mut [HashedValue<T>]
+            ));
         }
     }
 }
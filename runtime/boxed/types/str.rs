@@ -47,14 +47,19 @@ impl Str {
         let storage = Self::storage_for_byte_len(value.len());
         let header = Self::TYPE_TAG.to_heap_header(storage.box_size());
 
+        let heap = heap.as_heap_mut();
+
         let boxed = unsafe {
             match storage {
-                StrStorage::External => mem::transmute(ExternalStr::new(header, value)),
+                StrStorage::External => {
+                    heap.note_external_bytes(value.len());
+                    mem::transmute(ExternalStr::new(header, value))
+                }
                 StrStorage::Inline(_) => mem::transmute(InlineStr::new(header, value)),
             }
         };
 
-        heap.as_heap_mut().place_box(boxed)
+        heap.place_box(boxed)
     }
 
     /// Returns the storage for given string byte length
@@ -96,6 +101,14 @@ impl Str {
             Repr::External(external) => external.shared_str.as_str(),
         }
     }
+
+    /// Returns the number of bytes of string data owned outside of the string's box
+    pub(crate) fn external_byte_len(&self) -> usize {
+        match self.as_repr() {
+            Repr::Inline(_) => 0,
+            Repr::External(external) => external.shared_str.as_str().len(),
+        }
+    }
 }
 
 impl PartialEq for Str {
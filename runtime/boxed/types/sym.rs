@@ -66,10 +66,13 @@ impl PartialEq for Sym {
     }
 }
 
-impl Hash for Sym {
-    fn hash<H: Hasher>(&self, state: &mut H) {
+impl HashInHeap for Sym {
+    fn hash_in_heap<H: Hasher>(&self, heap: &Heap, state: &mut H) {
         Self::TYPE_TAG.hash(state);
-        self.interned.hash(state);
+
+        // Hash the name instead of the interned symbol. Indexed symbols are specific to their
+        // interner which would make our hash change between heaps.
+        self.name(heap).hash(state);
     }
 }
 
@@ -79,6 +82,12 @@ impl fmt::Debug for Sym {
     }
 }
 
+impl DebugWithHeap for Sym {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "Sym('{})", self.name(heap))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -109,4 +118,18 @@ mod test {
         let boxed_one = Sym::new(&mut heap, "one");
         assert_eq!(r#"Sym('one)"#, format!("{:?}", boxed_one));
     }
+
+    #[test]
+    fn fmt_debug_with_heap() {
+        let mut heap = Heap::empty();
+
+        // This is too long to be stored inline
+        let long_name = "a-symbol-name-longer-than-inline-storage";
+        let boxed_long = Sym::new(&mut heap, long_name);
+
+        assert_eq!(
+            format!("Sym('{})", long_name),
+            format!("{:?}", boxed_long.with_heap(&heap))
+        );
+    }
 }
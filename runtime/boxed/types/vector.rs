@@ -59,14 +59,24 @@ impl<T: Boxed> Vector<T> {
         let storage = Self::storage_for_element_len(values.len());
         let header = Vector::TYPE_TAG.to_heap_header(storage.box_size());
 
-        let boxed = unsafe {
-            match storage {
-                VectorStorage::External => mem::transmute(ExternalVector::new(header, values)),
-                VectorStorage::Inline(_) => mem::transmute(InlineVector::new(header, values)),
+        match storage {
+            VectorStorage::External => {
+                Self::place_external(heap, ExternalVector::new(header, values))
             }
-        };
+            VectorStorage::Inline(_) => {
+                let boxed = unsafe { mem::transmute(InlineVector::new(header, values)) };
+                heap.as_heap_mut().place_box(boxed)
+            }
+        }
+    }
 
-        heap.as_heap_mut().place_box(boxed)
+    /// Places an external vector on the heap while accounting for its element storage
+    fn place_external(heap: &mut impl AsHeap, external: ExternalVector<T>) -> Gc<Vector<T>> {
+        let heap = heap.as_heap_mut();
+        heap.note_external_bytes(external.payload_bytes());
+
+        let boxed = unsafe { mem::transmute(external) };
+        heap.place_box(boxed)
     }
 
     /// Returns the storage for given element length
@@ -161,17 +171,14 @@ impl<T: Boxed> Vector<T> {
                         .map(|value| unsafe { value.assume_init() }),
                 )
             }
-            Repr::External(external) => {
-                let boxed = unsafe {
-                    mem::transmute(ExternalVector {
-                        header: Vector::TYPE_TAG.to_heap_header(VectorStorage::External.box_size()),
-                        inline_len: EXTERNAL_INLINE_LEN,
-                        values: external.values.assoc(index, value),
-                    })
-                };
-
-                heap.as_heap_mut().place_box(boxed)
-            }
+            Repr::External(external) => Self::place_external(
+                heap,
+                ExternalVector {
+                    header: Vector::TYPE_TAG.to_heap_header(VectorStorage::External.box_size()),
+                    inline_len: EXTERNAL_INLINE_LEN,
+                    values: external.values.assoc(index, value),
+                },
+            ),
         }
     }
 
@@ -198,15 +205,14 @@ impl<T: Boxed> Vector<T> {
             Repr::External(self_external) => {
                 let new_values = self_external.values.extend(new_values);
 
-                let boxed = unsafe {
-                    mem::transmute(ExternalVector {
+                Self::place_external(
+                    heap,
+                    ExternalVector {
                         header: Vector::TYPE_TAG.to_heap_header(VectorStorage::External.box_size()),
                         inline_len: EXTERNAL_INLINE_LEN,
                         values: new_values,
-                    })
-                };
-
-                heap.as_heap_mut().place_box(boxed)
+                    },
+                )
             }
             _ => {
                 let values: Vec<_> = self.iter().chain(new_values).collect();
@@ -224,22 +230,27 @@ impl<T: Boxed> Vector<T> {
         }
 
         match self.as_repr() {
-            Repr::External(self_external) => {
-                let boxed = unsafe {
-                    mem::transmute(ExternalVector {
-                        header: Vector::TYPE_TAG.to_heap_header(VectorStorage::External.box_size()),
-                        inline_len: EXTERNAL_INLINE_LEN,
-                        values: self_external.values.take(count),
-                    })
-                };
-
-                heap.as_heap_mut().place_box(boxed)
-            }
+            Repr::External(self_external) => Self::place_external(
+                heap,
+                ExternalVector {
+                    header: Vector::TYPE_TAG.to_heap_header(VectorStorage::External.box_size()),
+                    inline_len: EXTERNAL_INLINE_LEN,
+                    values: self_external.values.take(count),
+                },
+            ),
             // Shouldn't be reachable but is easy to handle
             _ => Self::new(heap, self.iter().take(count)),
         }
     }
 
+    /// Returns the number of bytes of element storage owned outside of the vector's box
+    pub(crate) fn external_payload_bytes(&self) -> usize {
+        match self.as_repr() {
+            Repr::Inline(_) => 0,
+            Repr::External(external) => external.payload_bytes(),
+        }
+    }
+
     pub(crate) fn visit_mut_elements<F>(&mut self, visitor: &mut F)
     where
         F: FnMut(&mut Gc<T>),
@@ -285,6 +296,20 @@ impl<T: Boxed> fmt::Debug for Vector<T> {
     }
 }
 
+impl<T: Boxed> DebugWithHeap for Vector<T> {
+    fn fmt_with_heap(&self, heap: &Heap, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("Vector(")?;
+
+        let mut debug_list = formatter.debug_list();
+        for elem in self.iter() {
+            debug_list.entry(&elem.with_heap(heap));
+        }
+        debug_list.finish()?;
+
+        formatter.write_str(")")
+    }
+}
+
 impl<T: Boxed> EncodeBoxedAbiType for Vector<T>
 where
     T: EncodeBoxedAbiType,
@@ -352,6 +377,10 @@ impl<T: Boxed> ExternalVector<T> {
             values: PersistentVector::new(values),
         }
     }
+
+    fn payload_bytes(&self) -> usize {
+        self.values.len() * mem::size_of::<Gc<T>>()
+    }
 }
 
 enum Repr<'a, T: Boxed> {
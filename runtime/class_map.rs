@@ -68,6 +68,11 @@ impl Field {
     pub fn is_last(self) -> bool {
         self.is_last
     }
+
+    /// Returns if the field's value needs to be visited during garbage collection
+    pub fn may_contain_gc_refs(self) -> bool {
+        matches!(self.field_type, FieldType::InternedSym | FieldType::Boxed)
+    }
 }
 
 /// Type information for a class
@@ -91,6 +96,11 @@ impl<'a> ClassRef<'a> {
             phantom_lifetime: PhantomData,
         }
     }
+
+    /// Returns if any of the class' fields need to be visited during garbage collection
+    pub fn may_contain_gc_refs(self) -> bool {
+        self.field_iter().any(Field::may_contain_gc_refs)
+    }
 }
 
 /// Owned version of [`ClassRef`]
@@ -99,6 +109,7 @@ impl<'a> ClassRef<'a> {
 #[derive(Clone)]
 pub struct BoxedClass {
     fields: Box<[Field]>,
+    may_contain_gc_refs: bool,
 }
 
 impl BoxedClass {
@@ -109,7 +120,12 @@ impl BoxedClass {
             last_field.is_last = true;
         }
 
-        BoxedClass { fields }
+        let may_contain_gc_refs = fields.iter().any(|field| field.may_contain_gc_refs());
+
+        BoxedClass {
+            fields,
+            may_contain_gc_refs,
+        }
     }
 
     pub fn as_ref(&self) -> ClassRef<'_> {
@@ -203,4 +219,21 @@ impl ClassMap {
             unsafe { *self.const_classes.add(record_class_id as usize) }
         }
     }
+
+    /// Returns if records of the given [`RecordClassId`] need their fields visited during garbage
+    /// collection
+    ///
+    /// This is precomputed for dynamic classes. Constant classes are generated by the compiler
+    /// which already marks records without GC references when they're allocated.
+    pub fn class_may_contain_gc_refs(&self, record_class_id: RecordClassId) -> bool {
+        if record_class_id >= Self::DYNAMIC_RECORD_CLASS_ID_BASE {
+            let dynamic_class_index =
+                (record_class_id - Self::DYNAMIC_RECORD_CLASS_ID_BASE) as usize;
+
+            self.dynamic_classes[dynamic_class_index].may_contain_gc_refs
+        } else {
+            self.class_for_record_class_id(record_class_id)
+                .may_contain_gc_refs()
+        }
+    }
 }
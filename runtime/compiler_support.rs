@@ -5,31 +5,103 @@
 
 #![allow(clippy::missing_safety_doc)]
 
-use std::{alloc, panic, process};
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{alloc, env, process};
 
 use crate::boxed;
 use crate::boxed::prelude::*;
+use crate::boxed::profile::AllocSite;
 use crate::boxed::refs::Gc;
 use crate::boxed::type_info::TypeInfo;
 use crate::class_map::{ClassMap, ClassRef};
 use crate::intern::{Interner, RawGlobalNames};
+use crate::task;
 use crate::task::Task;
 
 type TaskEntry = extern "C" fn(&mut Task);
 
+/// Environment variable used to override the stack size of the main task
+const STACK_SIZE_ENV_VAR: &str = "ARRET_STACK_SIZE";
+
+/// Indicates if the main task should profile its allocations
+///
+/// This is requested by programs compiled with allocation profiling before their task launches.
+static ALLOC_PROFILE_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Raw program data passed to the main task's thread
+struct TaskLaunchData {
+    global_names: *const RawGlobalNames,
+    classmap_classes: *const ClassRef<'static>,
+}
+
+// The program data is static and immutable
+unsafe impl Send for TaskLaunchData {}
+
+fn main_task_stack_size() -> usize {
+    let value = match env::var(STACK_SIZE_ENV_VAR) {
+        Ok(value) => value,
+        Err(_) => return Task::DEFAULT_STACK_SIZE,
+    };
+
+    match value.parse() {
+        Ok(stack_size) if stack_size >= Task::MIN_STACK_SIZE => stack_size,
+        Ok(_) => {
+            eprintln!(
+                "{} of `{}` is less than the minimum of {} bytes",
+                STACK_SIZE_ENV_VAR,
+                value,
+                Task::MIN_STACK_SIZE
+            );
+            process::exit(1);
+        }
+        Err(_) => {
+            eprintln!("invalid {} of `{}`", STACK_SIZE_ENV_VAR, value);
+            process::exit(1);
+        }
+    }
+}
+
 #[export_name = "arret_runtime_launch_task"]
 pub unsafe extern "C" fn launch_task(
     global_names: *const RawGlobalNames,
     classmap_classes: *const ClassRef<'static>,
     entry: TaskEntry,
 ) {
-    let interner = Interner::with_global_names(global_names);
-    let class_map = ClassMap::with_const_classes(classmap_classes);
+    let launch_data = TaskLaunchData {
+        global_names,
+        classmap_classes,
+    };
+
+    let new_task = move || {
+        let TaskLaunchData {
+            global_names,
+            classmap_classes,
+        } = launch_data;
+
+        let interner = Interner::with_global_names(global_names);
+        let class_map = ClassMap::with_const_classes(classmap_classes);
+
+        let type_info = TypeInfo::new(interner, class_map);
+        let mut task = Task::with_type_info(type_info);
 
-    let type_info = TypeInfo::new(interner, class_map);
-    let mut task = Task::with_type_info(type_info);
+        if ALLOC_PROFILE_REQUESTED.load(Ordering::Relaxed) {
+            task.heap_mut().enable_alloc_profile();
+        }
 
-    if let Err(err) = panic::catch_unwind(panic::AssertUnwindSafe(|| entry(&mut task))) {
+        task
+    };
+
+    let result = task::run_task_on_thread(main_task_stack_size(), new_task, move |task| {
+        entry(task);
+        task.run_exit_handlers();
+
+        if let Some(alloc_profile) = task.heap().alloc_profile() {
+            eprint!("{}", alloc_profile);
+        }
+    });
+
+    if let Err(err) = result {
         if let Some(message) = err.downcast_ref::<String>() {
             eprintln!("{}", message);
         } else {
@@ -45,8 +117,30 @@ pub extern "C" fn alloc_cells(task: &mut Task, count: u32) -> *mut boxed::Any {
     task.heap_mut().alloc_cells(count as usize)
 }
 
+#[export_name = "arret_runtime_request_alloc_profile"]
+pub extern "C" fn request_alloc_profile() {
+    ALLOC_PROFILE_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+#[export_name = "arret_runtime_record_alloc"]
+pub unsafe extern "C" fn record_alloc(
+    task: &mut Task,
+    type_tag: boxed::TypeTag,
+    cell_count: u32,
+    site_name: *const c_char,
+) {
+    task.heap_mut().record_alloc(
+        type_tag,
+        cell_count as usize,
+        Some(AllocSite::from_static_name(site_name)),
+    );
+}
+
 #[export_name = "arret_runtime_alloc_record_data"]
-pub extern "C" fn alloc_record_data(size: u64, align: u32) -> *mut u8 {
+pub extern "C" fn alloc_record_data(task: &mut Task, size: u64, align: u32) -> *mut u8 {
+    // This will be owned by a record allocated on the task's heap
+    task.heap_mut().note_external_bytes(size as usize);
+
     unsafe {
         let layout = alloc::Layout::from_size_align_unchecked(size as usize, align as usize);
         alloc::alloc(layout)
@@ -58,6 +152,21 @@ pub extern "C" fn equals(task: &Task, lhs: Gc<boxed::Any>, rhs: Gc<boxed::Any>)
     lhs.eq_in_heap(task.as_heap(), &rhs)
 }
 
+#[export_name = "arret_runtime_map_get"]
+pub extern "C" fn map_get(
+    task: &mut Task,
+    map: Gc<boxed::Map>,
+    key: Gc<boxed::Sym>,
+) -> Gc<boxed::Any> {
+    match map.get(task.as_heap(), &key.as_any_ref()) {
+        Some(value) => value,
+        None => {
+            let key_name = key.name(task.heap()).to_owned();
+            match task.panic(format!("map is missing destructured key `{}`", key_name)) {}
+        }
+    }
+}
+
 #[export_name = "arret_runtime_panic_with_string"]
 pub unsafe extern "C" fn panic_with_string(
     task: &mut Task,
@@ -12,10 +12,16 @@
 //! The encoding for names larger than 8 bytes uses an index in to a [`Vec`] stored in the
 //! `Interner`. The indexed representation is invalid UTF-8 so it cannot collide with a valid
 //! symbol name.
+//!
+//! Interners for tasks running on different threads can share a [`SharedNames`] table. Names
+//! interned in the table have the same `InternedSym` in every interner sharing it.
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{fmt, ptr, str};
 
 // UTF-8 sequences cannot start with 10xxxxxxx. This is pattern for the last continuation byte,
@@ -24,6 +30,7 @@ use std::{fmt, ptr, str};
 const INLINE_FILL_BYTE: u8 = 0x80;
 const LOCAL_INDEXED_FLAG: u8 = 0x81;
 const GLOBAL_INDEXED_FLAG: u8 = 0x82;
+const SHARED_INDEXED_FLAG: u8 = 0x83;
 
 const INLINE_SIZE: usize = 8;
 
@@ -88,6 +95,7 @@ enum InternedRepr<'a> {
     Inline(&'a InternedInline),
     LocalIndexed(&'a InternedIndexed),
     GlobalIndexed(&'a InternedIndexed),
+    SharedIndexed(&'a InternedIndexed),
 }
 
 impl InternedSym {
@@ -137,6 +145,16 @@ impl InternedSym {
         }
     }
 
+    fn from_shared_index(index: u32) -> InternedSym {
+        InternedSym {
+            indexed: InternedIndexed {
+                flag_byte: SHARED_INDEXED_FLAG,
+                _padding: [0; 3],
+                name_index: index,
+            },
+        }
+    }
+
     pub fn to_raw_u64(self) -> u64 {
         unsafe { self.raw }
     }
@@ -146,6 +164,7 @@ impl InternedSym {
             match self.indexed.flag_byte {
                 LOCAL_INDEXED_FLAG => InternedRepr::LocalIndexed(&self.indexed),
                 GLOBAL_INDEXED_FLAG => InternedRepr::GlobalIndexed(&self.indexed),
+                SHARED_INDEXED_FLAG => InternedRepr::SharedIndexed(&self.indexed),
                 _ => InternedRepr::Inline(&self.inline),
             }
         }
@@ -171,7 +190,9 @@ impl Hash for InternedSym {
 impl fmt::Debug for InternedSym {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self.repr() {
-            InternedRepr::LocalIndexed(indexed) | InternedRepr::GlobalIndexed(indexed) => {
+            InternedRepr::LocalIndexed(indexed)
+            | InternedRepr::GlobalIndexed(indexed)
+            | InternedRepr::SharedIndexed(indexed) => {
                 // We don't have access to the `Interner` so we can't print our interned value
                 write!(formatter, "`{:x}", indexed.name_index)
             }
@@ -180,12 +201,196 @@ impl fmt::Debug for InternedSym {
     }
 }
 
+/// Number of names in the first chunk of a [`SharedNames`] table
+///
+/// Each following chunk is double the size of the previous chunk.
+const SHARED_FIRST_CHUNK_LEN: u64 = 64;
+
+/// Number of chunks required to store every possible `u32` index
+const SHARED_MAX_CHUNKS: usize = 27;
+
+/// Number of independently locked shards used to find existing names
+const SHARED_SHARD_COUNT: usize = 16;
+
+type SharedChunk = [AtomicPtr<Box<str>>];
+type SharedShard = Mutex<HashMap<Box<str>, u32>>;
+
+/// Append-only table of names shared between interners on multiple threads
+///
+/// Looking up the name for an index never takes a lock. Names are stored in chunks which are never
+/// moved or freed once allocated; a name is published by atomically storing a pointer to it in its
+/// slot. Finding the index of an existing name takes a lock on one of several shards selected by
+/// the name's hash.
+///
+/// Names are never removed from the table. Symbols interned in the table don't need to be rooted
+/// and survive garbage collection.
+pub struct SharedNames {
+    chunks: [AtomicPtr<AtomicPtr<Box<str>>>; SHARED_MAX_CHUNKS],
+    next_index: AtomicU32,
+    shards: Box<[SharedShard]>,
+}
+
+/// Returns the chunk and offset within that chunk for a shared name index
+fn shared_chunk_position(index: u32) -> (usize, usize) {
+    let chunk_relative = u64::from(index) / SHARED_FIRST_CHUNK_LEN + 1;
+    let chunk_index = (63 - chunk_relative.leading_zeros()) as usize;
+
+    let chunk_start = SHARED_FIRST_CHUNK_LEN * ((1 << chunk_index) - 1);
+    (chunk_index, (u64::from(index) - chunk_start) as usize)
+}
+
+fn shared_chunk_len(chunk_index: usize) -> usize {
+    (SHARED_FIRST_CHUNK_LEN << chunk_index) as usize
+}
+
+impl SharedNames {
+    /// Creates a new empty table
+    pub fn new() -> SharedNames {
+        SharedNames {
+            chunks: Default::default(),
+            next_index: AtomicU32::new(0),
+            shards: (0..SHARED_SHARD_COUNT)
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Returns the number of names that have been interned
+    pub fn len(&self) -> usize {
+        self.next_index.load(Ordering::Relaxed) as usize
+    }
+
+    /// Returns true if no names have been interned
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn chunk(&self, chunk_index: usize) -> Option<&SharedChunk> {
+        let chunk_ptr = self.chunks[chunk_index].load(Ordering::Acquire);
+
+        if chunk_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { std::slice::from_raw_parts(chunk_ptr, shared_chunk_len(chunk_index)) })
+        }
+    }
+
+    /// Returns the chunk with the passed index, allocating it if needed
+    fn chunk_or_alloc(&self, chunk_index: usize) -> &SharedChunk {
+        if let Some(chunk) = self.chunk(chunk_index) {
+            return chunk;
+        }
+
+        let new_chunk: Box<SharedChunk> = (0..shared_chunk_len(chunk_index))
+            .map(|_| AtomicPtr::new(ptr::null_mut()))
+            .collect();
+        let new_chunk_ptr = Box::into_raw(new_chunk) as *mut AtomicPtr<Box<str>>;
+
+        // Names in different shards can allocate the same chunk concurrently
+        if self.chunks[chunk_index]
+            .compare_exchange(
+                ptr::null_mut(),
+                new_chunk_ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            unsafe {
+                drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                    new_chunk_ptr,
+                    shared_chunk_len(chunk_index),
+                )));
+            }
+        }
+
+        self.chunk(chunk_index).unwrap()
+    }
+
+    fn shard_for_name(&self, name: &str) -> &SharedShard {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    /// Returns the index for a name, adding it to the table if required
+    pub fn intern(&self, name: &str) -> u32 {
+        let mut shard = self.shard_for_name(name).lock().unwrap();
+
+        if let Some(index) = shard.get(name) {
+            return *index;
+        }
+
+        let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        if index == u32::MAX {
+            panic!("shared name table is full");
+        }
+
+        let (chunk_index, offset) = shared_chunk_position(index);
+        let name_ptr = Box::into_raw(Box::new(Box::<str>::from(name)));
+        self.chunk_or_alloc(chunk_index)[offset].store(name_ptr, Ordering::Release);
+
+        shard.insert(name.into(), index);
+        index
+    }
+
+    /// Returns the name for a previously interned index
+    ///
+    /// This never blocks.
+    ///
+    /// # Panics
+    /// Panics if the index hasn't been published by [`intern`](SharedNames::intern)
+    pub fn name(&self, index: u32) -> &str {
+        let (chunk_index, offset) = shared_chunk_position(index);
+
+        let name_ptr = self
+            .chunk(chunk_index)
+            .map(|chunk| chunk[offset].load(Ordering::Acquire))
+            .filter(|name_ptr| !name_ptr.is_null())
+            .expect("unpublished shared name index");
+
+        unsafe { &*name_ptr }
+    }
+}
+
+impl Default for SharedNames {
+    fn default() -> SharedNames {
+        Self::new()
+    }
+}
+
+impl Drop for SharedNames {
+    fn drop(&mut self) {
+        for chunk_index in 0..SHARED_MAX_CHUNKS {
+            let chunk_ptr = *self.chunks[chunk_index].get_mut();
+            if chunk_ptr.is_null() {
+                continue;
+            }
+
+            unsafe {
+                let mut chunk = Box::from_raw(ptr::slice_from_raw_parts_mut(
+                    chunk_ptr,
+                    shared_chunk_len(chunk_index),
+                ));
+
+                for name_ptr in chunk.iter_mut() {
+                    let name_ptr = *name_ptr.get_mut();
+                    if !name_ptr.is_null() {
+                        drop(Box::from_raw(name_ptr));
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub struct Interner {
     names: Vec<Rc<str>>,
     name_to_interned: HashMap<Rc<str>, InternedSym>,
     /// Contains the highest static index + 1
     static_index_watermark: u32,
     global_names: Option<&'static [GlobalName]>,
+    shared_names: Option<Arc<SharedNames>>,
 }
 
 impl Interner {
@@ -195,9 +400,27 @@ impl Interner {
             name_to_interned: HashMap::new(),
             static_index_watermark: 0,
             global_names: None,
+            shared_names: None,
         }
     }
 
+    /// Creates a new `Interner` adding indexed names to a table shared with other interners
+    ///
+    /// Symbols interned by any interner sharing the table can be compared directly and uninterned
+    /// by any of the other interners. This allows symbols to be passed between tasks on different
+    /// threads.
+    pub fn with_shared_names(shared_names: Arc<SharedNames>) -> Interner {
+        Interner {
+            shared_names: Some(shared_names),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the shared names table used by this interner
+    pub fn shared_names(&self) -> Option<&Arc<SharedNames>> {
+        self.shared_names.as_ref()
+    }
+
     /// Creates a new `Interner` with a global names struct produced by codegen
     ///
     /// # Safety
@@ -213,6 +436,7 @@ impl Interner {
             name_to_interned: HashMap::new(),
             static_index_watermark: 0,
             global_names,
+            shared_names: None,
         }
     }
 
@@ -245,6 +469,13 @@ impl Interner {
             return interned;
         }
 
+        if let Some(shared_names) = &self.shared_names {
+            // Cache this so we don't need to take a shard lock for this name again
+            let interned = InternedSym::from_shared_index(shared_names.intern(name));
+            self.name_to_interned.insert(name.into(), interned);
+            return interned;
+        }
+
         let shared_name: Rc<str> = name.into();
 
         let index = self.names.len() as u32;
@@ -277,6 +508,9 @@ impl Interner {
             InternedRepr::GlobalIndexed(indexed) => {
                 self.global_names.unwrap()[indexed.name_index as usize].as_str()
             }
+            InternedRepr::SharedIndexed(indexed) => {
+                self.shared_names.as_ref().unwrap().name(indexed.name_index)
+            }
             InternedRepr::Inline(inline) => inline.as_str(),
         }
     }
@@ -285,7 +519,7 @@ impl Interner {
     ///
     /// This preserves the index of all static [`InternedSym`]s.
     pub(crate) fn clone_for_collect_garbage(&self) -> Self {
-        if self.static_index_watermark == 0 {
+        if self.static_index_watermark == 0 && self.shared_names.is_none() {
             // Avoid iterating over our HashMap
             return Self::new();
         };
@@ -297,13 +531,16 @@ impl Interner {
             .name_to_interned
             .iter()
             .filter_map(|(name, interned)| {
-                if let InternedRepr::LocalIndexed(indexed) = interned.repr() {
-                    if indexed.name_index < self.static_index_watermark {
-                        return Some((name.clone(), *interned));
+                match interned.repr() {
+                    InternedRepr::LocalIndexed(indexed)
+                        if indexed.name_index < self.static_index_watermark =>
+                    {
+                        Some((name.clone(), *interned))
                     }
+                    // Shared names are never collected
+                    InternedRepr::SharedIndexed(_) => Some((name.clone(), *interned)),
+                    _ => None,
                 }
-
-                None
             })
             .collect();
 
@@ -312,6 +549,7 @@ impl Interner {
             name_to_interned,
             static_index_watermark,
             global_names: self.global_names,
+            shared_names: self.shared_names.clone(),
         }
     }
 }
@@ -435,4 +673,82 @@ mod test {
         assert_eq!(3, interner.names.len());
         assert_eq!(3, interner.name_to_interned.len());
     }
+
+    #[test]
+    fn shared_chunk_positions() {
+        assert_eq!((0, 0), shared_chunk_position(0));
+        assert_eq!((0, 63), shared_chunk_position(63));
+        assert_eq!((1, 0), shared_chunk_position(64));
+        assert_eq!((1, 127), shared_chunk_position(191));
+        assert_eq!((2, 0), shared_chunk_position(192));
+
+        let (last_chunk_index, last_offset) = shared_chunk_position(u32::MAX);
+        assert!(last_chunk_index < SHARED_MAX_CHUNKS);
+        assert!(last_offset < shared_chunk_len(last_chunk_index));
+    }
+
+    #[test]
+    fn shared_names_between_interners() {
+        let shared_names = Arc::new(SharedNames::new());
+        let long_name = "This must be longer than eight bytes";
+
+        let mut interner1 = Interner::with_shared_names(shared_names.clone());
+        let mut interner2 = Interner::with_shared_names(shared_names.clone());
+
+        let interned1 = interner1.intern(long_name);
+        let interned2 = interner2.intern(long_name);
+        assert_eq!(interned1, interned2);
+
+        assert_eq!(long_name, interner2.unintern(&interned1));
+        assert_eq!(1, shared_names.len());
+
+        // Shared names don't need to be static to survive garbage collection
+        interner1 = interner1.clone_for_collect_garbage();
+        assert_eq!(long_name, interner1.unintern(&interned2));
+        assert_eq!(interned1, interner1.intern(long_name));
+    }
+
+    #[test]
+    fn shared_names_across_threads() {
+        use std::thread;
+
+        const THREAD_COUNT: usize = 8;
+        const NAME_COUNT: usize = 500;
+
+        let shared_names = Arc::new(SharedNames::new());
+
+        let handles: Vec<_> = (0..THREAD_COUNT)
+            .map(|_| {
+                let shared_names = shared_names.clone();
+
+                thread::spawn(move || {
+                    let mut interner = Interner::with_shared_names(shared_names);
+
+                    (0..NAME_COUNT)
+                        .map(|i| interner.intern(&format!("shared name number {}", i)))
+                        .collect::<Vec<InternedSym>>()
+                })
+            })
+            .collect();
+
+        let thread_interneds: Vec<Vec<InternedSym>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        // Every thread should have the same symbols
+        for interneds in &thread_interneds[1..] {
+            assert!(interneds == &thread_interneds[0]);
+        }
+
+        assert_eq!(NAME_COUNT, shared_names.len());
+
+        let interner = Interner::with_shared_names(shared_names);
+        for (i, interned) in thread_interneds[0].iter().enumerate() {
+            assert_eq!(
+                format!("shared name number {}", i),
+                interner.unintern(interned)
+            );
+        }
+    }
 }
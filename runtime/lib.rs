@@ -9,4 +9,5 @@ pub mod class_map;
 pub mod compiler_support;
 pub mod intern;
 pub mod persistent;
+pub mod serialize;
 pub mod task;
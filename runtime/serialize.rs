@@ -0,0 +1,624 @@
+//! Serialisation of boxed values to a stable binary format
+//!
+//! The encoding starts with a magic number and format version followed by a single encoded value.
+//! Every value begins with a tag byte identifying its type. Integers and lengths are encoded as
+//! little endian and collections are prefixed with their member count.
+//!
+//! Symbols are encoded by their name. Their interned representation depends on the program and
+//! heap that created them so it can't be shared between programs.
+//!
+//! Functions and records are specific to the program that created them and cannot be serialised.
+
+use std::convert::TryInto;
+use std::{error, fmt, str};
+
+use crate::boxed;
+use crate::boxed::prelude::*;
+use crate::boxed::refs::Gc;
+
+const MAGIC: &[u8; 4] = b"ARBX";
+
+/// Current version of the binary format
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Maximum depth of nested collections that will be serialised or deserialised
+///
+/// This prevents deeply nested values or malicious input from exhausting the stack. It's enforced
+/// while encoding so any serialised value can also be deserialised.
+const MAX_NESTING_DEPTH: usize = 128;
+
+const TAG_NIL: u8 = 0;
+const TAG_TRUE: u8 = 1;
+const TAG_FALSE: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_FLOAT: u8 = 4;
+const TAG_CHAR: u8 = 5;
+const TAG_STR: u8 = 6;
+const TAG_SYM: u8 = 7;
+const TAG_LIST: u8 = 8;
+const TAG_VECTOR: u8 = 9;
+const TAG_SET: u8 = 10;
+const TAG_MAP: u8 = 11;
+const TAG_FLOAT_ARRAY: u8 = 12;
+const TAG_INT_ARRAY: u8 = 13;
+const TAG_BYTES: u8 = 14;
+const TAG_RATIONAL: u8 = 15;
+const TAG_BIG_INT: u8 = 16;
+
+/// Error encountered while serialising a value
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SerializeError {
+    /// Values of this type cannot be serialised
+    UnsupportedType(boxed::TypeTag),
+    /// Collections were nested deeper than the maximum supported depth
+    TooDeeplyNested,
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeError::UnsupportedType(type_tag) => {
+                write!(
+                    formatter,
+                    "cannot serialize values of type {}",
+                    type_tag.to_str()
+                )
+            }
+            SerializeError::TooDeeplyNested => write!(formatter, "value nested too deeply"),
+        }
+    }
+}
+
+impl error::Error for SerializeError {}
+
+/// Error encountered while deserialising a value
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DeserializeError {
+    /// The input doesn't start with the expected magic number
+    BadMagic,
+    /// The input was encoded with an unsupported format version
+    UnsupportedVersion(u8),
+    /// The input ended in the middle of a value
+    UnexpectedEof,
+    /// An unknown value tag was encountered
+    InvalidTag(u8),
+    /// A string or symbol contained invalid UTF-8
+    InvalidUtf8,
+    /// A character wasn't a valid Unicode scalar value
+    InvalidChar(u32),
+    /// A rational had a zero denominator
+    InvalidRational,
+    /// Collections were nested deeper than the maximum supported depth
+    TooDeeplyNested,
+    /// Additional data followed the encoded value
+    TrailingBytes,
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::BadMagic => write!(formatter, "not a serialized value"),
+            DeserializeError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported serialization version {}", version)
+            }
+            DeserializeError::UnexpectedEof => write!(formatter, "unexpected end of input"),
+            DeserializeError::InvalidTag(tag) => write!(formatter, "invalid value tag {}", tag),
+            DeserializeError::InvalidUtf8 => write!(formatter, "invalid UTF-8"),
+            DeserializeError::InvalidChar(code_point) => {
+                write!(formatter, "invalid character U+{:04X}", code_point)
+            }
+            DeserializeError::InvalidRational => write!(formatter, "invalid rational"),
+            DeserializeError::TooDeeplyNested => write!(formatter, "value nested too deeply"),
+            DeserializeError::TrailingBytes => write!(formatter, "trailing data after value"),
+        }
+    }
+}
+
+impl error::Error for DeserializeError {}
+
+struct Encoder<'h> {
+    heap: &'h boxed::Heap,
+    output: Vec<u8>,
+    /// Number of collections enclosing the value being encoded
+    depth: usize,
+}
+
+impl<'h> Encoder<'h> {
+    fn push_len(&mut self, len: usize) {
+        self.output.extend_from_slice(&(len as u64).to_le_bytes());
+    }
+
+    fn push_str(&mut self, tag: u8, value: &str) {
+        self.output.push(tag);
+        self.push_len(value.len());
+        self.output.extend_from_slice(value.as_bytes());
+    }
+
+    fn push_seq(
+        &mut self,
+        tag: u8,
+        values: impl ExactSizeIterator<Item = Gc<boxed::Any>>,
+    ) -> Result<(), SerializeError> {
+        self.output.push(tag);
+        self.push_len(values.len());
+
+        for value in values {
+            self.push_value(value)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_value(&mut self, any_ref: Gc<boxed::Any>) -> Result<(), SerializeError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(SerializeError::TooDeeplyNested);
+        }
+
+        self.depth += 1;
+        let result = self.push_nested_value(any_ref);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn push_nested_value(&mut self, any_ref: Gc<boxed::Any>) -> Result<(), SerializeError> {
+        use crate::boxed::AnySubtype;
+
+        match any_ref.as_subtype() {
+            AnySubtype::Nil(_) => self.output.push(TAG_NIL),
+            AnySubtype::True(_) => self.output.push(TAG_TRUE),
+            AnySubtype::False(_) => self.output.push(TAG_FALSE),
+            AnySubtype::Int(int_ref) => {
+                self.output.push(TAG_INT);
+                self.output
+                    .extend_from_slice(&int_ref.value().to_le_bytes());
+            }
+            AnySubtype::Float(float_ref) => {
+                self.output.push(TAG_FLOAT);
+                self.output
+                    .extend_from_slice(&float_ref.value().to_bits().to_le_bytes());
+            }
+            AnySubtype::Rational(rational_ref) => {
+                self.output.push(TAG_RATIONAL);
+                self.output
+                    .extend_from_slice(&rational_ref.numer().to_le_bytes());
+                self.output
+                    .extend_from_slice(&rational_ref.denom().to_le_bytes());
+            }
+            AnySubtype::BigInt(big_int_ref) => {
+                let signed_bytes = big_int_ref.value().to_signed_bytes_le();
+
+                self.output.push(TAG_BIG_INT);
+                self.push_len(signed_bytes.len());
+                self.output.extend_from_slice(&signed_bytes);
+            }
+            AnySubtype::Char(char_ref) => {
+                self.output.push(TAG_CHAR);
+                self.output
+                    .extend_from_slice(&(char_ref.value() as u32).to_le_bytes());
+            }
+            AnySubtype::Str(str_ref) => self.push_str(TAG_STR, str_ref.as_str()),
+            AnySubtype::Sym(sym_ref) => self.push_str(TAG_SYM, sym_ref.name(self.heap)),
+            AnySubtype::Pair(pair_ref) => self.push_seq(TAG_LIST, pair_ref.as_list_ref().iter())?,
+            AnySubtype::Vector(vector_ref) => self.push_seq(TAG_VECTOR, vector_ref.iter())?,
+            AnySubtype::Set(set_ref) => self.push_seq(TAG_SET, set_ref.iter())?,
+            AnySubtype::Map(map_ref) => {
+                self.output.push(TAG_MAP);
+                self.push_len(map_ref.len());
+
+                for (key, value) in map_ref.iter() {
+                    self.push_value(key)?;
+                    self.push_value(value)?;
+                }
+            }
+            AnySubtype::FloatArray(array_ref) => {
+                self.output.push(TAG_FLOAT_ARRAY);
+                self.push_len(array_ref.len());
+
+                for value in array_ref.iter() {
+                    self.output
+                        .extend_from_slice(&value.to_bits().to_le_bytes());
+                }
+            }
+            AnySubtype::IntArray(array_ref) => {
+                self.output.push(TAG_INT_ARRAY);
+                self.push_len(array_ref.len());
+
+                for value in array_ref.iter() {
+                    self.output.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            AnySubtype::Bytes(bytes_ref) => {
+                self.output.push(TAG_BYTES);
+                self.push_len(bytes_ref.len());
+                self.output.extend_from_slice(bytes_ref.as_slice());
+            }
+            AnySubtype::FunThunk(_) | AnySubtype::Record(_) => {
+                return Err(SerializeError::UnsupportedType(any_ref.header().type_tag()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Serialises a boxed value to the binary format
+pub fn serialize_boxed(
+    heap: &impl boxed::AsHeap,
+    any_ref: Gc<boxed::Any>,
+) -> Result<Vec<u8>, SerializeError> {
+    let heap = heap.as_heap();
+
+    let mut encoder = Encoder {
+        heap,
+        output: MAGIC.to_vec(),
+        depth: 0,
+    };
+
+    encoder.output.push(FORMAT_VERSION);
+    encoder.push_value(any_ref)?;
+    Ok(encoder.output)
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    /// Number of collections enclosing the value being decoded
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take_bytes(&mut self, count: usize) -> Result<&'a [u8], DeserializeError> {
+        if self.input.len() < count {
+            return Err(DeserializeError::UnexpectedEof);
+        }
+
+        let (taken, rest) = self.input.split_at(count);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn take_u8(&mut self) -> Result<u8, DeserializeError> {
+        Ok(self.take_bytes(1)?[0])
+    }
+
+    fn take_u32(&mut self) -> Result<u32, DeserializeError> {
+        Ok(u32::from_le_bytes(self.take_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn take_u64(&mut self) -> Result<u64, DeserializeError> {
+        Ok(u64::from_le_bytes(self.take_bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Takes a length prefix for members of at least `min_member_size` bytes
+    ///
+    /// This ensures the length is plausible before we attempt to allocate space for the members.
+    fn take_len(&mut self, min_member_size: usize) -> Result<usize, DeserializeError> {
+        let len = self.take_u64()?;
+
+        if len > (self.input.len() / min_member_size) as u64 {
+            return Err(DeserializeError::UnexpectedEof);
+        }
+
+        Ok(len as usize)
+    }
+
+    fn take_str(&mut self) -> Result<&'a str, DeserializeError> {
+        let len = self.take_len(1)?;
+        str::from_utf8(self.take_bytes(len)?).map_err(|_| DeserializeError::InvalidUtf8)
+    }
+
+    fn take_values(
+        &mut self,
+        heap: &mut boxed::Heap,
+    ) -> Result<Vec<Gc<boxed::Any>>, DeserializeError> {
+        let len = self.take_len(1)?;
+        (0..len).map(|_| self.take_value(heap)).collect()
+    }
+
+    fn take_value(&mut self, heap: &mut boxed::Heap) -> Result<Gc<boxed::Any>, DeserializeError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(DeserializeError::TooDeeplyNested);
+        }
+
+        self.depth += 1;
+        let result = self.take_nested_value(heap);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn take_nested_value(
+        &mut self,
+        heap: &mut boxed::Heap,
+    ) -> Result<Gc<boxed::Any>, DeserializeError> {
+        Ok(match self.take_u8()? {
+            TAG_NIL => boxed::NIL_INSTANCE.as_any_ref(),
+            TAG_TRUE => boxed::TRUE_INSTANCE.as_any_ref(),
+            TAG_FALSE => boxed::FALSE_INSTANCE.as_any_ref(),
+            TAG_INT => boxed::Int::new(heap, self.take_u64()? as i64).as_any_ref(),
+            TAG_FLOAT => boxed::Float::new(heap, f64::from_bits(self.take_u64()?)).as_any_ref(),
+            TAG_RATIONAL => {
+                let numer = self.take_u64()? as i64;
+                let denom = self.take_u64()? as i64;
+
+                boxed::Rational::new(heap, numer, denom)
+                    .ok_or(DeserializeError::InvalidRational)?
+                    .as_any_ref()
+            }
+            TAG_BIG_INT => {
+                let len = self.take_len(1)?;
+                let value = num_bigint::BigInt::from_signed_bytes_le(self.take_bytes(len)?);
+
+                boxed::BigInt::new(heap, value).as_any_ref()
+            }
+            TAG_CHAR => {
+                let code_point = self.take_u32()?;
+                let value = std::char::from_u32(code_point)
+                    .ok_or(DeserializeError::InvalidChar(code_point))?;
+
+                boxed::Char::new(heap, value).as_any_ref()
+            }
+            TAG_STR => boxed::Str::new(heap, self.take_str()?).as_any_ref(),
+            TAG_SYM => boxed::Sym::new(heap, self.take_str()?).as_any_ref(),
+            TAG_LIST => {
+                let values = self.take_values(heap)?;
+                boxed::List::new(heap, values.into_iter()).as_any_ref()
+            }
+            TAG_VECTOR => {
+                let values = self.take_values(heap)?;
+                boxed::Vector::new(heap, values.into_iter()).as_any_ref()
+            }
+            TAG_SET => {
+                let values = self.take_values(heap)?;
+                boxed::Set::new(heap, values.into_iter()).as_any_ref()
+            }
+            TAG_MAP => {
+                let len = self.take_len(2)?;
+                let entries = (0..len)
+                    .map(|_| Ok((self.take_value(heap)?, self.take_value(heap)?)))
+                    .collect::<Result<Vec<_>, DeserializeError>>()?;
+
+                boxed::Map::new(heap, entries.into_iter()).as_any_ref()
+            }
+            TAG_FLOAT_ARRAY => {
+                let len = self.take_len(8)?;
+                let values = (0..len)
+                    .map(|_| self.take_u64().map(f64::from_bits))
+                    .collect::<Result<Vec<f64>, DeserializeError>>()?;
+
+                boxed::FloatArray::new(heap, values.into_iter()).as_any_ref()
+            }
+            TAG_INT_ARRAY => {
+                let len = self.take_len(8)?;
+                let values = (0..len)
+                    .map(|_| self.take_u64().map(|value| value as i64))
+                    .collect::<Result<Vec<i64>, DeserializeError>>()?;
+
+                boxed::IntArray::new(heap, values.into_iter()).as_any_ref()
+            }
+            TAG_BYTES => {
+                let len = self.take_len(1)?;
+                boxed::Bytes::new(heap, self.take_bytes(len)?).as_any_ref()
+            }
+            other => {
+                return Err(DeserializeError::InvalidTag(other));
+            }
+        })
+    }
+}
+
+/// Deserialises a boxed value from the binary format
+///
+/// Symbols are interned using the heap's interner.
+pub fn deserialize_boxed(
+    heap: &mut impl boxed::AsHeap,
+    input: &[u8],
+) -> Result<Gc<boxed::Any>, DeserializeError> {
+    let heap = heap.as_heap_mut();
+
+    let mut decoder = Decoder { input, depth: 0 };
+
+    if decoder.take_bytes(MAGIC.len()) != Ok(MAGIC) {
+        return Err(DeserializeError::BadMagic);
+    }
+
+    let version = decoder.take_u8()?;
+    if version != FORMAT_VERSION {
+        return Err(DeserializeError::UnsupportedVersion(version));
+    }
+
+    let value = decoder.take_value(heap)?;
+
+    if !decoder.input.is_empty() {
+        return Err(DeserializeError::TrailingBytes);
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::boxed::Heap;
+
+    fn assert_round_trip(heap: &mut Heap, any_ref: Gc<boxed::Any>) {
+        let serialized = serialize_boxed(heap, any_ref).unwrap();
+        let deserialized = deserialize_boxed(heap, &serialized).unwrap();
+
+        assert!(
+            any_ref.eq_in_heap(heap, &deserialized),
+            "{:?} did not round trip; got {:?}",
+            any_ref,
+            deserialized
+        );
+    }
+
+    #[test]
+    fn scalar_round_trip() {
+        let mut heap = Heap::empty();
+
+        let values = [
+            boxed::NIL_INSTANCE.as_any_ref(),
+            boxed::TRUE_INSTANCE.as_any_ref(),
+            boxed::FALSE_INSTANCE.as_any_ref(),
+            boxed::Int::new(&mut heap, -1234).as_any_ref(),
+            boxed::Float::new(&mut heap, 0.5).as_any_ref(),
+            boxed::Rational::new(&mut heap, -3, 4).unwrap().as_any_ref(),
+            boxed::BigInt::new(&mut heap, "-10223372036854775807".parse().unwrap()).as_any_ref(),
+            boxed::Char::new(&mut heap, 'λ').as_any_ref(),
+            boxed::Str::new(&mut heap, "Hello, world!").as_any_ref(),
+            boxed::Sym::new(&mut heap, "short").as_any_ref(),
+            boxed::Sym::new(&mut heap, "much-longer-symbol").as_any_ref(),
+            boxed::Bytes::new(&mut heap, &[0, 1, 255]).as_any_ref(),
+        ];
+
+        for value in values.iter() {
+            assert_round_trip(&mut heap, *value);
+        }
+    }
+
+    #[test]
+    fn collection_round_trip() {
+        let mut heap = Heap::empty();
+
+        let list = boxed::List::from_values(&mut heap, [1, 2, 3].iter().cloned(), boxed::Int::new);
+        assert_round_trip(&mut heap, list.as_any_ref());
+
+        let members = [
+            list.as_any_ref(),
+            boxed::Str::new(&mut heap, "member").as_any_ref(),
+        ];
+
+        let vector = boxed::Vector::new(&mut heap, members.iter().cloned());
+        assert_round_trip(&mut heap, vector.as_any_ref());
+
+        let set = boxed::Set::new(&mut heap, members.iter().cloned());
+        assert_round_trip(&mut heap, set.as_any_ref());
+
+        let map = boxed::Map::<boxed::Any, boxed::Any>::new(&mut heap, std::iter::empty());
+        assert_round_trip(&mut heap, map.as_any_ref());
+
+        let int_array = boxed::IntArray::new(&mut heap, [1, -2, 3].iter().cloned());
+        assert_round_trip(&mut heap, int_array.as_any_ref());
+
+        let float_array = boxed::FloatArray::new(&mut heap, [1.0, -2.5].iter().cloned());
+        assert_round_trip(&mut heap, float_array.as_any_ref());
+    }
+
+    #[test]
+    fn stable_encoding() {
+        let mut heap = Heap::empty();
+
+        let value = boxed::List::from_values(&mut heap, [1, 2].iter().cloned(), boxed::Int::new);
+        let serialized = serialize_boxed(&heap, value.as_any_ref()).unwrap();
+
+        assert_eq!(
+            vec![
+                b'A', b'R', b'B', b'X', // Magic
+                1,    // Version
+                TAG_LIST, 2, 0, 0, 0, 0, 0, 0, 0, // List of length 2
+                TAG_INT, 1, 0, 0, 0, 0, 0, 0, 0, // 1
+                TAG_INT, 2, 0, 0, 0, 0, 0, 0, 0, // 2
+            ],
+            serialized
+        );
+    }
+
+    #[test]
+    fn unsupported_types() {
+        let mut heap = Heap::empty();
+
+        extern "C" fn return_nil_entry(
+            _task: &mut crate::task::Task,
+            _captures: boxed::Captures,
+            _rest: Gc<boxed::Any>,
+        ) -> Gc<boxed::Any> {
+            boxed::NIL_INSTANCE.as_any_ref()
+        }
+
+        let fun_thunk = boxed::FunThunk::new(
+            &mut heap,
+            boxed::NIL_INSTANCE.as_any_ref(),
+            return_nil_entry,
+        );
+
+        assert_eq!(
+            Err(SerializeError::UnsupportedType(boxed::TypeTag::FunThunk)),
+            serialize_boxed(&heap, fun_thunk.as_any_ref())
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut heap = Heap::empty();
+
+        assert_eq!(
+            Err(DeserializeError::BadMagic),
+            deserialize_boxed(&mut heap, b"nope").map(|_| ())
+        );
+
+        assert_eq!(
+            Err(DeserializeError::UnsupportedVersion(99)),
+            deserialize_boxed(&mut heap, b"ARBX\x63\0").map(|_| ())
+        );
+
+        assert_eq!(
+            Err(DeserializeError::UnexpectedEof),
+            deserialize_boxed(&mut heap, b"ARBX\x01\x03\x01").map(|_| ())
+        );
+
+        assert_eq!(
+            Err(DeserializeError::InvalidTag(200)),
+            deserialize_boxed(&mut heap, b"ARBX\x01\xc8").map(|_| ())
+        );
+
+        assert_eq!(
+            Err(DeserializeError::TrailingBytes),
+            deserialize_boxed(&mut heap, b"ARBX\x01\0\0").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let mut heap = Heap::empty();
+
+        let nested_lists = |depth: usize| {
+            let mut input = b"ARBX\x01".to_vec();
+
+            for _ in 0..depth {
+                input.push(TAG_LIST);
+                input.extend_from_slice(&1u64.to_le_bytes());
+            }
+
+            input.push(TAG_NIL);
+            input
+        };
+
+        assert!(deserialize_boxed(&mut heap, &nested_lists(MAX_NESTING_DEPTH - 1)).is_ok());
+
+        assert_eq!(
+            Err(DeserializeError::TooDeeplyNested),
+            deserialize_boxed(&mut heap, &nested_lists(MAX_NESTING_DEPTH)).map(|_| ())
+        );
+
+        // The encoder should only produce output the decoder accepts
+        let nested_boxed_lists = |heap: &mut Heap, depth: usize| {
+            let mut value = boxed::NIL_INSTANCE.as_any_ref();
+
+            for _ in 0..depth {
+                value = boxed::List::new(heap, std::iter::once(value)).as_any_ref();
+            }
+
+            value
+        };
+
+        let deepest_value = nested_boxed_lists(&mut heap, MAX_NESTING_DEPTH - 1);
+        assert_round_trip(&mut heap, deepest_value);
+
+        let too_deep_value = nested_boxed_lists(&mut heap, MAX_NESTING_DEPTH);
+        assert_eq!(
+            Err(SerializeError::TooDeeplyNested),
+            serialize_boxed(&heap, too_deep_value)
+        );
+    }
+}
@@ -2,25 +2,45 @@
 
 //! Isolated tasks of execution
 
-use std::panic;
+use std::{panic, ptr, thread};
 
 use crate::binding::Never;
 use crate::boxed::prelude::*;
+use crate::boxed::refs::Gc;
 use crate::boxed::type_info::TypeInfo;
-use crate::boxed::Heap;
+use crate::boxed::{Any, FunThunk, Heap, List};
 
 /// Isolated task of execution
 ///
 /// All Arret and RFI code must run inside a task. It provides a dedicated garbage collected
 /// [`Heap`] as well as an isolation boundary against panics. A task is inherently single threaded;
 /// it's not possible for one task to be executing on multiple threads at the same time.
+///
+/// This is accessed directly from generated native code. The stack limit must remain the first
+/// field followed by the heap.
+#[repr(C)]
 pub struct Task {
+    stack_limit: *const u8,
     heap: Heap,
+    exit_handlers: Vec<Gc<FunThunk>>,
 }
 
 impl Task {
     const DEFAULT_CAPACITY: usize = 32;
 
+    /// Default stack size for tasks launched with [`run_with_stack_size`]
+    pub const DEFAULT_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+    /// Amount of stack reserved for the runtime after compiled code reaches the stack limit
+    ///
+    /// This leaves space for RFI functions, garbage collection and unwinding the task's panic.
+    const STACK_RED_ZONE_SIZE: usize = 256 * 1024;
+
+    /// Minimum stack size for tasks launched with [`run_with_stack_size`]
+    ///
+    /// This allows compiled code to use as much stack as is reserved for the red zone.
+    pub const MIN_STACK_SIZE: usize = 2 * Self::STACK_RED_ZONE_SIZE;
+
     /// Creates a new empty task
     pub fn new() -> Task {
         Self::with_type_info(TypeInfo::empty())
@@ -28,10 +48,32 @@ impl Task {
 
     pub(crate) fn with_type_info(type_info: TypeInfo) -> Task {
         Self {
+            stack_limit: ptr::null(),
             heap: Heap::new(type_info, Self::DEFAULT_CAPACITY),
+            exit_handlers: vec![],
         }
     }
 
+    /// Returns the lowest stack address compiled Arret code may use before panicking
+    ///
+    /// This is null if the stack usage of the task is unlimited.
+    pub fn stack_limit(&self) -> *const u8 {
+        self.stack_limit
+    }
+
+    /// Limits the stack usage of compiled Arret code running in this task
+    ///
+    /// `stack_size` is measured downwards from the caller's stack frame and should be no larger
+    /// than the remaining stack of the current thread. Once compiled code has used the stack up to
+    /// the limit the task will panic with a stack overflow instead of crashing the process.
+    pub fn limit_stack_size(&mut self, stack_size: usize) {
+        let stack_marker = 0u8;
+        let stack_position = &stack_marker as *const u8 as usize;
+
+        let usable_size = stack_size.saturating_sub(Self::STACK_RED_ZONE_SIZE);
+        self.stack_limit = stack_position.saturating_sub(usable_size) as *const u8;
+    }
+
     /// Returns this task's dedicated heap
     pub fn heap(&self) -> &Heap {
         &self.heap
@@ -42,6 +84,31 @@ impl Task {
         &mut self.heap
     }
 
+    /// Registers a function to be called with no arguments when the task exits
+    ///
+    /// Exit handlers are run in the reverse order of their registration by
+    /// [`run_exit_handlers`](Task::run_exit_handlers).
+    pub fn add_exit_handler(&mut self, handler: Gc<FunThunk>) {
+        self.exit_handlers.push(handler);
+    }
+
+    /// Returns mutable references to the registered exit handlers
+    ///
+    /// This allows the handlers to be visited as roots during garbage collection.
+    pub fn exit_handlers_mut(&mut self) -> &mut [Gc<FunThunk>] {
+        &mut self.exit_handlers
+    }
+
+    /// Runs all registered exit handlers in the reverse order of their registration
+    ///
+    /// Handlers registered while exit handlers are running will also be run. Each handler is only
+    /// run once; the task has no registered exit handlers once this returns.
+    pub fn run_exit_handlers(&mut self) {
+        while let Some(handler) = self.exit_handlers.pop() {
+            handler.apply(self, List::<Any>::empty().as_any_ref());
+        }
+    }
+
     /// Panics the current task
     ///
     /// This destroys the current task and invokes any cleanup required.
@@ -61,6 +128,45 @@ impl Task {
     }
 }
 
+/// Runs a function inside a new task on a dedicated thread
+///
+/// The thread is created with a stack of `stack_size` bytes protected by a guard page. Sizes below
+/// [`Task::MIN_STACK_SIZE`] are increased to the minimum. The task's stack limit is set so that
+/// stack overflows in compiled Arret code panic the task. Any panic is returned as an error, in the
+/// same way as [`std::thread::JoinHandle::join`]. If the thread can't be spawned the error contains
+/// a `String` describing the failure.
+pub fn run_with_stack_size<F, R>(stack_size: usize, f: F) -> thread::Result<R>
+where
+    F: FnOnce(&mut Task) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    run_task_on_thread(stack_size, Task::new, f)
+}
+
+pub(crate) fn run_task_on_thread<C, F, R>(stack_size: usize, new_task: C, f: F) -> thread::Result<R>
+where
+    C: FnOnce() -> Task + Send + 'static,
+    F: FnOnce(&mut Task) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let stack_size = stack_size.max(Task::MIN_STACK_SIZE);
+
+    let spawn_result = thread::Builder::new()
+        .name("arret-task".to_owned())
+        .stack_size(stack_size)
+        .spawn(move || {
+            let mut task = new_task();
+            task.limit_stack_size(stack_size);
+
+            f(&mut task)
+        });
+
+    match spawn_result {
+        Ok(join_handle) => join_handle.join(),
+        Err(err) => Err(Box::new(format!("failed to spawn task thread: {}", err))),
+    }
+}
+
 impl Default for Task {
     fn default() -> Task {
         Task::new()
@@ -76,3 +182,89 @@ impl AsHeap for Task {
         &mut self.heap
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unlimited_stack_by_default() {
+        let task = Task::new();
+        assert!(task.stack_limit().is_null());
+    }
+
+    #[test]
+    fn run_with_stack_size_limits_stack() {
+        let stack_limit = run_with_stack_size(Task::DEFAULT_STACK_SIZE, |task| {
+            let stack_marker = 0u8;
+            let stack_position = &stack_marker as *const u8 as usize;
+            let stack_limit = task.stack_limit() as usize;
+
+            assert!(stack_limit < stack_position);
+            assert!(stack_position - stack_limit < Task::DEFAULT_STACK_SIZE);
+
+            stack_limit
+        })
+        .unwrap();
+
+        assert_ne!(0, stack_limit);
+    }
+
+    #[test]
+    fn run_with_stack_size_clamps_to_minimum() {
+        let usable_size = run_with_stack_size(0, |task| {
+            let stack_marker = 0u8;
+            let stack_position = &stack_marker as *const u8 as usize;
+
+            stack_position - task.stack_limit() as usize
+        })
+        .unwrap();
+
+        assert!(usable_size > 0);
+    }
+
+    #[test]
+    fn run_with_stack_size_returns_panic() {
+        let result = run_with_stack_size(Task::DEFAULT_STACK_SIZE, |task| {
+            task.panic("task panicked".to_owned());
+        });
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            Some(&"task panicked".to_owned()),
+            err.downcast_ref::<String>()
+        );
+    }
+
+    #[test]
+    fn exit_handlers_run_in_reverse_order() {
+        use crate::boxed::Int;
+        use std::cell::RefCell;
+
+        thread_local! {
+            static CALLED_HANDLERS: RefCell<Vec<i64>> = RefCell::new(vec![]);
+        }
+
+        extern "C" fn record_entry(_: &mut Task, captures: Gc<Any>, _: Gc<Any>) -> Gc<Any> {
+            let handler_id = captures.downcast_ref::<Int>().unwrap().value();
+            CALLED_HANDLERS.with(|called| called.borrow_mut().push(handler_id));
+
+            List::<Any>::empty().as_any_ref()
+        }
+
+        let mut task = Task::new();
+
+        for handler_id in 1..=2 {
+            let captures = Int::new(&mut task, handler_id).as_any_ref();
+            let handler = FunThunk::new(&mut task, captures, record_entry);
+            task.add_exit_handler(handler);
+        }
+
+        task.run_exit_handlers();
+        CALLED_HANDLERS.with(|called| assert_eq!(vec![2, 1], *called.borrow()));
+
+        // Handlers should only run once
+        task.run_exit_handlers();
+        CALLED_HANDLERS.with(|called| assert_eq!(2, called.borrow().len()));
+    }
+}
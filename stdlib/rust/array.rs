@@ -0,0 +1,178 @@
+use arret_runtime::binding::*;
+use arret_runtime::boxed;
+use arret_runtime::boxed::refs::Gc;
+use arret_runtime::task::Task;
+
+fn checked_array_index<T: boxed::NumArrayElement>(
+    task: &mut Task,
+    array: Gc<boxed::NumArray<T>>,
+    index: i64,
+) -> usize {
+    if index < 0 {
+        task.panic(format!("index {} is negative", index));
+        unreachable!("returned from panic")
+    }
+
+    let usize_index = index as usize;
+    if usize_index >= array.len() {
+        task.panic(format!(
+            "index {} out of bounds for array of length {}",
+            usize_index,
+            array.len()
+        ));
+        unreachable!("returned from panic")
+    }
+
+    usize_index
+}
+
+fn checked_array_len(task: &mut Task, len: i64) -> usize {
+    if len < 0 {
+        task.panic(format!("array length {} is negative", len));
+        unreachable!("returned from panic")
+    }
+
+    len as usize
+}
+
+// Arrays are mutable so their constructors must be impure. Otherwise separate calls with the same
+// arguments could be merged in to a single shared array.
+
+#[arret_rfi_derive::rust_fun("(& Float ->! FloatArray)")]
+pub fn stdlib_float_array(
+    task: &mut Task,
+    values: Gc<boxed::List<boxed::Float>>,
+) -> Gc<boxed::FloatArray> {
+    boxed::FloatArray::new(task, values.iter().map(|value| value.value()))
+}
+
+#[arret_rfi_derive::rust_fun("(Int Float ->! FloatArray)")]
+pub fn stdlib_make_float_array(task: &mut Task, len: i64, value: f64) -> Gc<boxed::FloatArray> {
+    let usize_len = checked_array_len(task, len);
+    boxed::FloatArray::from_elem(task, usize_len, value)
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray -> Int)")]
+pub fn stdlib_float_array_length(array: Gc<boxed::FloatArray>) -> i64 {
+    array.len() as i64
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray Int ->! Float)")]
+pub fn stdlib_float_array_ref(task: &mut Task, array: Gc<boxed::FloatArray>, index: i64) -> f64 {
+    let usize_index = checked_array_index(task, array, index);
+    array.as_cells()[usize_index].get()
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray Int Float ->! ())")]
+pub fn stdlib_float_array_set(
+    task: &mut Task,
+    array: Gc<boxed::FloatArray>,
+    index: i64,
+    value: f64,
+) {
+    let usize_index = checked_array_index(task, array, index);
+    array.as_cells()[usize_index].set(value);
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray Float ->! ())")]
+pub fn stdlib_float_array_fill(array: Gc<boxed::FloatArray>, value: f64) {
+    array.fill(value);
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray ->! (List & Float))")]
+pub fn stdlib_float_array_to_list(
+    task: &mut Task,
+    array: Gc<boxed::FloatArray>,
+) -> Gc<boxed::List<boxed::Float>> {
+    let boxed_values: Vec<Gc<boxed::Float>> = array
+        .iter()
+        .map(|value| boxed::Float::new(task, value))
+        .collect();
+
+    boxed::List::new(task, boxed_values.into_iter())
+}
+
+#[arret_rfi_derive::rust_fun("(& Int ->! IntArray)")]
+pub fn stdlib_int_array(
+    task: &mut Task,
+    values: Gc<boxed::List<boxed::Int>>,
+) -> Gc<boxed::IntArray> {
+    boxed::IntArray::new(task, values.iter().map(|value| value.value()))
+}
+
+#[arret_rfi_derive::rust_fun("(Int Int ->! IntArray)")]
+pub fn stdlib_make_int_array(task: &mut Task, len: i64, value: i64) -> Gc<boxed::IntArray> {
+    let usize_len = checked_array_len(task, len);
+    boxed::IntArray::from_elem(task, usize_len, value)
+}
+
+#[arret_rfi_derive::rust_fun("(IntArray -> Int)")]
+pub fn stdlib_int_array_length(array: Gc<boxed::IntArray>) -> i64 {
+    array.len() as i64
+}
+
+#[arret_rfi_derive::rust_fun("(IntArray Int ->! Int)")]
+pub fn stdlib_int_array_ref(task: &mut Task, array: Gc<boxed::IntArray>, index: i64) -> i64 {
+    let usize_index = checked_array_index(task, array, index);
+    array.as_cells()[usize_index].get()
+}
+
+#[arret_rfi_derive::rust_fun("(IntArray Int Int ->! ())")]
+pub fn stdlib_int_array_set(task: &mut Task, array: Gc<boxed::IntArray>, index: i64, value: i64) {
+    let usize_index = checked_array_index(task, array, index);
+    array.as_cells()[usize_index].set(value);
+}
+
+#[arret_rfi_derive::rust_fun("(IntArray Int ->! ())")]
+pub fn stdlib_int_array_fill(array: Gc<boxed::IntArray>, value: i64) {
+    array.fill(value);
+}
+
+#[arret_rfi_derive::rust_fun("(IntArray ->! (List & Int))")]
+pub fn stdlib_int_array_to_list(
+    task: &mut Task,
+    array: Gc<boxed::IntArray>,
+) -> Gc<boxed::List<boxed::Int>> {
+    let boxed_values: Vec<Gc<boxed::Int>> = array
+        .iter()
+        .map(|value| boxed::Int::new(task, value))
+        .collect();
+
+    boxed::List::new(task, boxed_values.into_iter())
+}
+
+fn check_matching_array_lens(
+    task: &mut Task,
+    lhs: Gc<boxed::FloatArray>,
+    rhs: Gc<boxed::FloatArray>,
+) {
+    if lhs.len() != rhs.len() {
+        task.panic("array lengths differ".to_owned());
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray FloatArray ->! ())")]
+pub fn stdlib_array_add(task: &mut Task, lhs: Gc<boxed::FloatArray>, rhs: Gc<boxed::FloatArray>) {
+    check_matching_array_lens(task, lhs, rhs);
+
+    for (lhs_cell, rhs_cell) in lhs.as_cells().iter().zip(rhs.as_cells().iter()) {
+        lhs_cell.set(lhs_cell.get() + rhs_cell.get());
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray Float ->! ())")]
+pub fn stdlib_array_scale(array: Gc<boxed::FloatArray>, factor: f64) {
+    for cell in array.as_cells() {
+        cell.set(cell.get() * factor);
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(FloatArray FloatArray ->! Float)")]
+pub fn stdlib_dot_product(
+    task: &mut Task,
+    lhs: Gc<boxed::FloatArray>,
+    rhs: Gc<boxed::FloatArray>,
+) -> f64 {
+    check_matching_array_lens(task, lhs, rhs);
+    lhs.iter().zip(rhs.iter()).map(|(l, r)| l * r).sum()
+}
@@ -0,0 +1,72 @@
+use std::fs;
+
+use arret_runtime::binding::*;
+use arret_runtime::boxed;
+use arret_runtime::boxed::refs::Gc;
+use arret_runtime::task::Task;
+
+/// Read-only memory mapped file used as [`boxed::BytesStorage`]
+///
+/// The file is unmapped once the owning `Bytes` box is garbage collected.
+struct MappedFile(memmap2::Mmap);
+
+impl boxed::BytesStorage for MappedFile {
+    fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(Str ->! Bytes)")]
+pub fn stdlib_mmap_file(task: &mut Task, path: Gc<boxed::Str>) -> Gc<boxed::Bytes> {
+    let map_result = fs::File::open(path.as_str()).and_then(|file| {
+        // This is only unsafe if the file is modified while it's mapped
+        unsafe { memmap2::Mmap::map(&file) }
+    });
+
+    match map_result {
+        Ok(mmap) => boxed::Bytes::from_storage(task, MappedFile(mmap)),
+        Err(err) => {
+            task.panic(format!("cannot map file `{}`: {}", path.as_str(), err));
+            unreachable!("returned from panic")
+        }
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(Bytes -> Int)")]
+pub fn stdlib_bytes_length(bytes: Gc<boxed::Bytes>) -> i64 {
+    bytes.len() as i64
+}
+
+#[arret_rfi_derive::rust_fun("(Bytes Int -> Int)")]
+pub fn stdlib_bytes_ref(task: &mut Task, bytes: Gc<boxed::Bytes>, index: i64) -> i64 {
+    if index < 0 {
+        task.panic(format!("index {} is negative", index));
+        unreachable!("returned from panic")
+    }
+
+    match bytes.as_slice().get(index as usize) {
+        Some(byte) => i64::from(*byte),
+        None => {
+            task.panic(format!(
+                "index {} out of bounds for bytes of length {}",
+                index,
+                bytes.len()
+            ));
+            unreachable!("returned from panic")
+        }
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(Bytes -> (List & Int))")]
+pub fn stdlib_bytes_to_list(
+    task: &mut Task,
+    bytes: Gc<boxed::Bytes>,
+) -> Gc<boxed::List<boxed::Int>> {
+    let boxed_ints: Vec<Gc<boxed::Int>> = bytes
+        .as_slice()
+        .iter()
+        .map(|byte| boxed::Int::new(task, i64::from(*byte)))
+        .collect();
+
+    boxed::List::new(task, boxed_ints.into_iter())
+}
@@ -34,6 +34,15 @@ use crate::set::*;
 pub mod bitwise;
 use crate::bitwise::*;
 
+pub mod array;
+use crate::array::*;
+
+pub mod bytes;
+use crate::bytes::*;
+
+pub mod serialize;
+use crate::serialize::*;
+
 use arret_runtime_syntax::writer::pretty_print_boxed;
 
 use arret_runtime::binding::*;
@@ -64,15 +73,23 @@ pub fn stdlib_panic_impure(task: &mut Task, values: Gc<boxed::List<boxed::Any>>)
 }
 
 #[arret_rfi_derive::rust_fun("(Int ->! (U))")]
-pub fn stdlib_exit(exit_code: i64) {
+pub fn stdlib_exit(task: &mut Task, exit_code: i64) {
     use std::process::exit;
+
+    task.run_exit_handlers();
     exit(exit_code as i32);
 }
 
+#[arret_rfi_derive::rust_fun("((->! Any) ->! ())")]
+pub fn stdlib_on_exit(task: &mut Task, handler: Gc<boxed::FunThunk>) {
+    task.add_exit_handler(handler);
+}
+
 define_rust_module!(ARRET_STDLIB_RUST_EXPORTS, {
     "panic" => stdlib_panic,
     "panic!" => stdlib_panic_impure,
     "exit!" => stdlib_exit,
+    "on-exit!" => stdlib_on_exit,
 
     "print!" => stdlib_print,
     "println!" => stdlib_println,
@@ -82,6 +99,7 @@ define_rust_module!(ARRET_STDLIB_RUST_EXPORTS, {
     "write-str" => stdlib_write_str,
 
     "read-str" => stdlib_read_str,
+    "read-all-str" => stdlib_read_all_str,
 
     "length" => stdlib_length,
     "map" => stdlib_map,
@@ -111,10 +129,16 @@ define_rust_module!(ARRET_STDLIB_RUST_EXPORTS, {
     "rem" => stdlib_rem,
     "sqrt" => stdlib_sqrt,
 
+    "numerator" => stdlib_numerator,
+    "denominator" => stdlib_denominator,
+    "rational->float" => stdlib_rational_to_float,
+
     "black-box" => stdlib_black_box,
     "black-box!" => stdlib_black_box_impure,
     "heap-alloc-count" => stdlib_heap_alloc_count,
+    "alloc-profile" => stdlib_alloc_profile,
     "fn-op-categories" => stdlib_fn_op_categories,
+    "value-diff" => stdlib_value_diff,
 
     "vector" => stdlib_vector,
     "vector-length" => stdlib_vector_length,
@@ -139,5 +163,33 @@ define_rust_module!(ARRET_STDLIB_RUST_EXPORTS, {
     "bit-not" => stdlib_bit_not,
     "bit-shift-left" => stdlib_bit_shift_left,
     "bit-shift-right" => stdlib_bit_shift_right,
-    "unsigned-bit-shift-right" => stdlib_unsigned_bit_shift_right
+    "unsigned-bit-shift-right" => stdlib_unsigned_bit_shift_right,
+
+    "float-array" => stdlib_float_array,
+    "make-float-array" => stdlib_make_float_array,
+    "float-array-length" => stdlib_float_array_length,
+    "float-array-ref" => stdlib_float_array_ref,
+    "float-array-set!" => stdlib_float_array_set,
+    "float-array-fill!" => stdlib_float_array_fill,
+    "float-array->list" => stdlib_float_array_to_list,
+
+    "int-array" => stdlib_int_array,
+    "make-int-array" => stdlib_make_int_array,
+    "int-array-length" => stdlib_int_array_length,
+    "int-array-ref" => stdlib_int_array_ref,
+    "int-array-set!" => stdlib_int_array_set,
+    "int-array-fill!" => stdlib_int_array_fill,
+    "int-array->list" => stdlib_int_array_to_list,
+
+    "array-add!" => stdlib_array_add,
+    "array-scale!" => stdlib_array_scale,
+    "dot-product" => stdlib_dot_product,
+
+    "mmap-file!" => stdlib_mmap_file,
+    "bytes-length" => stdlib_bytes_length,
+    "bytes-ref" => stdlib_bytes_ref,
+    "bytes->list" => stdlib_bytes_to_list,
+
+    "serialize!" => stdlib_serialize,
+    "deserialize" => stdlib_deserialize
 });
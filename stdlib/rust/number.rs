@@ -99,3 +99,18 @@ pub fn stdlib_num_gt(initial: Gc<boxed::Num>, rest: Gc<boxed::List<boxed::Num>>)
 pub fn stdlib_num_ge(initial: Gc<boxed::Num>, rest: Gc<boxed::List<boxed::Num>>) -> bool {
     compare_nums(initial, rest, i64::ge, f64::ge)
 }
+
+#[arret_rfi_derive::rust_fun("(Rational -> Int)")]
+pub fn stdlib_numerator(input: Gc<boxed::Rational>) -> i64 {
+    input.numer()
+}
+
+#[arret_rfi_derive::rust_fun("(Rational -> Int)")]
+pub fn stdlib_denominator(input: Gc<boxed::Rational>) -> i64 {
+    input.denom()
+}
+
+#[arret_rfi_derive::rust_fun("(Rational -> Float)")]
+pub fn stdlib_rational_to_float(input: Gc<boxed::Rational>) -> f64 {
+    input.to_f64()
+}
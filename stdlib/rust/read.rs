@@ -1,4 +1,7 @@
-use arret_syntax::parser::datum_from_str;
+use arret_syntax::datum::Datum;
+use arret_syntax::parser::{
+    data_from_str_with_options, datum_from_str_with_options, ParserOptions,
+};
 
 use arret_runtime::binding::*;
 use arret_runtime::boxed;
@@ -7,8 +10,38 @@ use arret_runtime::task::Task;
 
 use arret_runtime_syntax::reader;
 
+/// Parser options for reading data at runtime
+///
+/// Integers that overflow `Int` are read as `BigInt`s instead of being rejected.
+const READ_PARSER_OPTIONS: ParserOptions = ParserOptions { big_ints: true };
+
+fn unwrap_parsed<T>(task: &mut Task, result: arret_syntax::error::Result<T>) -> T {
+    match result {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            task.panic(format!("syntax error: {}", err.kind().message()));
+            unreachable!("returned from panic")
+        }
+    }
+}
+
 #[arret_rfi_derive::rust_fun("(Str -> Any)")]
 pub fn stdlib_read_str(task: &mut Task, edn_str: Gc<boxed::Str>) -> Gc<boxed::Any> {
-    let parsed_syntax = datum_from_str(None, edn_str.as_str()).unwrap();
+    let parsed_syntax = unwrap_parsed(
+        task,
+        datum_from_str_with_options(None, edn_str.as_str(), READ_PARSER_OPTIONS),
+    );
     reader::box_syntax_datum(task, &parsed_syntax)
 }
+
+#[arret_rfi_derive::rust_fun("(Str -> (List & Any))")]
+pub fn stdlib_read_all_str(
+    task: &mut Task,
+    edn_str: Gc<boxed::Str>,
+) -> Gc<boxed::List<boxed::Any>> {
+    let parsed_data: Vec<Datum> = unwrap_parsed(
+        task,
+        data_from_str_with_options(None, edn_str.as_str(), READ_PARSER_OPTIONS),
+    );
+    boxed::List::from_values(task, parsed_data.iter(), reader::box_syntax_datum)
+}
@@ -0,0 +1,28 @@
+use arret_runtime::binding::*;
+use arret_runtime::boxed;
+use arret_runtime::boxed::refs::Gc;
+use arret_runtime::serialize::{deserialize_boxed, serialize_boxed};
+use arret_runtime::task::Task;
+
+// This is impure as `Bytes` can't be represented as a compile-time constant
+#[arret_rfi_derive::rust_fun("(Any ->! Bytes)")]
+pub fn stdlib_serialize(task: &mut Task, value: Gc<boxed::Any>) -> Gc<boxed::Bytes> {
+    match serialize_boxed(task, value) {
+        Ok(serialized) => boxed::Bytes::from_storage(task, serialized),
+        Err(err) => {
+            task.panic(err.to_string());
+            unreachable!("returned from panic")
+        }
+    }
+}
+
+#[arret_rfi_derive::rust_fun("(Bytes -> Any)")]
+pub fn stdlib_deserialize(task: &mut Task, bytes: Gc<boxed::Bytes>) -> Gc<boxed::Any> {
+    match deserialize_boxed(task, bytes.as_slice()) {
+        Ok(value) => value,
+        Err(err) => {
+            task.panic(format!("cannot deserialize value: {}", err));
+            unreachable!("returned from panic")
+        }
+    }
+}
@@ -29,6 +29,61 @@ pub fn stdlib_heap_alloc_count(
     boxed::List::new(task, [alloc_count.as_any_ref(), ret].iter().cloned())
 }
 
+/// Returns the number of boxes and bytes allocated for each type
+///
+/// This is empty unless the program was compiled with allocation profiling.
+#[arret_rfi_derive::rust_fun("(->! (List & (List Sym Int Int)))")]
+pub fn stdlib_alloc_profile(task: &mut Task) -> Gc<boxed::List<boxed::List<boxed::Any>>> {
+    let type_tag_stats: Vec<_> = task
+        .heap()
+        .alloc_profile()
+        .map(|alloc_profile| alloc_profile.allocated_type_tags().collect())
+        .unwrap_or_default();
+
+    let entries: Vec<Gc<boxed::List<boxed::Any>>> = type_tag_stats
+        .into_iter()
+        .map(|(type_tag, stats)| {
+            let type_name = boxed::Sym::new(task, type_tag.to_str()).as_any_ref();
+            let count = boxed::Int::new(task, stats.count as i64).as_any_ref();
+            let bytes = boxed::Int::new(task, stats.bytes as i64).as_any_ref();
+
+            boxed::List::new(task, [type_name, count, bytes].iter().cloned())
+        })
+        .collect();
+
+    boxed::List::new(task, entries.into_iter())
+}
+
+/// Describes the nested values that differ between two values
+///
+/// Each difference is written on its own line preceded by a newline. This is empty if the values
+/// are equal or only differ at their root; that wouldn't add anything to printing the values.
+#[arret_rfi_derive::rust_fun("(Any Any -> Str)")]
+pub fn stdlib_value_diff(
+    task: &mut Task,
+    expected: Gc<boxed::Any>,
+    actual: Gc<boxed::Any>,
+) -> Gc<boxed::Str> {
+    use arret_runtime_syntax::diff::{diff_boxed, write_differences};
+
+    let differences = diff_boxed(task, expected, actual);
+    let mut output: Vec<u8> = vec![];
+
+    if differences
+        .iter()
+        .any(|difference| !difference.path.is_empty())
+    {
+        output.push(b'\n');
+        write_differences(&mut output, task, &differences).unwrap();
+        output.pop();
+    }
+
+    boxed::Str::new(
+        task,
+        std::str::from_utf8(&output).expect("wrote invalid UTF-8"),
+    )
+}
+
 // TODO: This should return a `Set` once they're better supported
 #[arret_rfi_derive::rust_fun("((... ->! Any) -> (List & Sym))")]
 pub fn stdlib_fn_op_categories(_value: Gc<boxed::FunThunk>) -> Gc<boxed::List<boxed::Sym>> {
@@ -1,5 +1,7 @@
 use std::sync::Arc;
 
+pub use num_bigint::BigInt;
+
 use crate::span::Span;
 
 pub type DataStr = Arc<str>;
@@ -9,7 +11,13 @@ pub enum Datum {
     Bool(Span, bool),
     Char(Span, char),
     Int(Span, i64),
+    /// Integer that doesn't fit in an `i64`
+    ///
+    /// These are only produced when parsing with [`ParserOptions::big_ints`](crate::parser::ParserOptions::big_ints)
+    BigInt(Span, Box<BigInt>),
     Float(Span, f64),
+    /// Exact rational with a numerator and positive denominator in lowest terms
+    Rational(Span, i64, i64),
     List(Span, Box<[Datum]>),
     Str(Span, DataStr),
     Sym(Span, DataStr),
@@ -24,7 +32,9 @@ impl Datum {
             Datum::Bool(span, _)
             | Datum::Char(span, _)
             | Datum::Int(span, _)
+            | Datum::BigInt(span, _)
             | Datum::Float(span, _)
+            | Datum::Rational(span, _, _)
             | Datum::List(span, _)
             | Datum::Str(span, _)
             | Datum::Sym(span, _)
@@ -40,7 +50,9 @@ impl Datum {
             Datum::Bool(_, false) => "boolean false",
             Datum::Char(_, _) => "character",
             Datum::Int(_, _) => "integer",
+            Datum::BigInt(_, _) => "big integer",
             Datum::Float(_, _) => "floating point number",
+            Datum::Rational(_, _, _) => "rational number",
             Datum::Str(_, _) => "string",
             Datum::Sym(_, name) => {
                 if name.starts_with(':') {
@@ -47,6 +47,8 @@ pub enum ErrorKind {
     UnexpectedChar(char, WithinContext),
     UnevenMap,
     InvalidArgLiteral,
+    ConfusableWhitespace(char),
+    InvalidRational,
 }
 
 impl ErrorKind {
@@ -70,6 +72,13 @@ impl ErrorKind {
             ErrorKind::InvalidArgLiteral => {
                 "arg literal must be `%`, `%{integer}` or `%&`".to_owned()
             }
+            ErrorKind::ConfusableWhitespace(c) => format!(
+                "unsupported whitespace character U+{:04X}; only ASCII whitespace is allowed",
+                *c as u32
+            ),
+            ErrorKind::InvalidRational => {
+                "rational literal must have a non-zero denominator".to_owned()
+            }
         }
     }
 
@@ -1,36 +1,118 @@
-use crate::datum::Datum;
+use unicode_normalization::UnicodeNormalization;
+use unicode_xid::UnicodeXID;
+
+use crate::datum::{BigInt, Datum};
 use crate::error::{Error, ErrorKind, Result, WithinContext};
 use crate::span::{ByteIndex, FileId, Span};
 
+/// Options controlling how data is parsed
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ParserOptions {
+    /// Parse integer literals that don't fit in an `i64` as [`Datum::BigInt`]
+    ///
+    /// If this is false they're reported as [`ErrorKind::IntegerOverflow`].
+    pub big_ints: bool,
+}
+
 pub fn data_from_str_with_span_offset(
     file_id: Option<FileId>,
     s: &str,
     span_offset: ByteIndex,
 ) -> Result<Vec<Datum>> {
-    Parser::from_str(file_id, s, span_offset).parse_data()
+    Parser::from_str(file_id, s, span_offset, ParserOptions::default()).parse_data()
 }
 
 pub fn data_from_str(file_id: Option<FileId>, s: &str) -> Result<Vec<Datum>> {
     data_from_str_with_span_offset(file_id, s, 0)
 }
 
+pub fn data_from_str_with_options(
+    file_id: Option<FileId>,
+    s: &str,
+    options: ParserOptions,
+) -> Result<Vec<Datum>> {
+    Parser::from_str(file_id, s, 0, options).parse_data()
+}
+
+/// Parses all data from the passed string while recovering from syntax errors
+///
+/// When a syntax error is encountered the remainder of the enclosing top-level datum is skipped up
+/// to its balancing delimiter and parsing resumes. This allows multiple syntax errors to be
+/// reported at once. The returned data excludes any datum containing an error.
+pub fn data_from_str_with_recovery_with_span_offset(
+    file_id: Option<FileId>,
+    s: &str,
+    span_offset: ByteIndex,
+) -> (Vec<Datum>, Vec<Error>) {
+    Parser::from_str(file_id, s, span_offset, ParserOptions::default()).parse_data_with_recovery()
+}
+
+pub fn data_from_str_with_recovery(file_id: Option<FileId>, s: &str) -> (Vec<Datum>, Vec<Error>) {
+    data_from_str_with_recovery_with_span_offset(file_id, s, 0)
+}
+
 pub fn datum_from_str_with_span_offset(
     file_id: Option<FileId>,
     s: &str,
     span_offset: ByteIndex,
 ) -> Result<Datum> {
-    Parser::from_str(file_id, s, span_offset).parse_datum()
+    Parser::from_str(file_id, s, span_offset, ParserOptions::default()).parse_datum()
 }
 
 pub fn datum_from_str(file_id: Option<FileId>, s: &str) -> Result<Datum> {
     datum_from_str_with_span_offset(file_id, s, 0)
 }
 
+pub fn datum_from_str_with_options(
+    file_id: Option<FileId>,
+    s: &str,
+    options: ParserOptions,
+) -> Result<Datum> {
+    Parser::from_str(file_id, s, 0, options).parse_datum()
+}
+
 fn is_whitespace(c: char) -> bool {
     matches!(c, ',' | ' ' | '\n' | '\t' | '\r')
 }
 
+/// Returns true if the character is whitespace that could be confused with our whitespace
+///
+/// These are rejected instead of being treated as part of an identifier or as a separator.
+fn is_confusable_whitespace(c: char) -> bool {
+    !c.is_ascii() && (c.is_whitespace() || matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}'))
+}
+
+/// Returns true if the character is a zero width joiner or non-joiner
+///
+/// These are invisible so they're only allowed in an identifier directly after a virama. This is
+/// the only context from UAX #31 where they're supported.
+fn is_joiner(c: char) -> bool {
+    matches!(c, '\u{200C}' | '\u{200D}')
+}
+
+/// Returns true if the character is an invisible character from `XID_Continue`
+///
+/// These are the default ignorable code points that would otherwise be accepted in an identifier.
+/// Allowing them would let visually identical identifiers refer to different bindings.
+fn is_ignorable_identifier_char(c: char) -> bool {
+    matches!(c,
+        '\u{034F}' | '\u{115F}' | '\u{1160}' | '\u{17B4}' | '\u{17B5}' |
+        '\u{180B}'..='\u{180F}' | '\u{3164}' | '\u{FE00}'..='\u{FE0F}' | '\u{FFA0}' |
+        '\u{E0100}'..='\u{E01EF}'
+    ) || is_joiner(c)
+}
+
+/// Returns true if the character is a virama allowing a following joiner
+fn is_virama(c: char) -> bool {
+    unicode_normalization::char::canonical_combining_class(c) == 9
+}
+
 pub fn is_identifier_char(c: char) -> bool {
+    if !c.is_ascii() {
+        // Unicode letters, marks and digits
+        return c.is_xid_continue() && !is_ignorable_identifier_char(c);
+    }
+
     matches!(c,
         'A'..='Z' | 'a'..='z' | '0'..='9' |
         // Punctuation allowed at beginning of an identifier
@@ -42,18 +124,61 @@ pub fn is_identifier_char(c: char) -> bool {
     )
 }
 
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+
+    a
+}
+
+/// Builds a datum for a rational with a positive denominator
+///
+/// The rational is reduced to its lowest terms. Rationals with a denominator of one become integers.
+fn rational_datum(span: Span, numer: i64, denom: i64) -> Datum {
+    let divisor = gcd(numer.unsigned_abs(), denom as u64) as i64;
+
+    match (numer / divisor, denom / divisor) {
+        (numer, 1) => Datum::Int(span, numer),
+        (numer, denom) => Datum::Rational(span, numer, denom),
+    }
+}
+
+/// Parses a hexadecimal code point in to a character
+///
+/// Surrogates and values beyond the Unicode range are reported as `InvalidCodePoint`.
+fn char_from_hex_code_point(span: Span, hex_string: &str) -> Result<char> {
+    if hex_string.is_empty() || !hex_string.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::new(span, ErrorKind::UnsupportedChar));
+    }
+
+    let code_point = u32::from_str_radix(hex_string, 16)
+        .map_err(|_| Error::new(span, ErrorKind::InvalidCodePoint))?;
+
+    std::char::from_u32(code_point).ok_or_else(|| Error::new(span, ErrorKind::InvalidCodePoint))
+}
+
 pub struct Parser<'input> {
     file_id: Option<FileId>,
     input: &'input str,
     consumed_bytes: ByteIndex,
+    options: ParserOptions,
 }
 
 impl<'input> Parser<'input> {
-    fn from_str(file_id: Option<FileId>, input: &'input str, span_offset: ByteIndex) -> Self {
+    fn from_str(
+        file_id: Option<FileId>,
+        input: &'input str,
+        span_offset: ByteIndex,
+        options: ParserOptions,
+    ) -> Self {
         Parser {
             file_id,
             input,
             consumed_bytes: span_offset,
+            options,
         }
     }
 
@@ -123,6 +248,10 @@ impl<'input> Parser<'input> {
                         }
                     }
                 }
+                c if is_confusable_whitespace(c) => {
+                    let (span, _) = self.capture_span(|s| s.eat_bytes(c.len_utf8()));
+                    break Err(Error::new(span, ErrorKind::ConfusableWhitespace(c)));
+                }
                 other => {
                     break Ok(other);
                 }
@@ -157,6 +286,77 @@ impl<'input> Parser<'input> {
         self.consume_until(|c| !predicate(c))
     }
 
+    /// Skips past the next datum without parsing it
+    ///
+    /// This only tracks the nesting of delimiters, strings, comments and character literals. It's
+    /// intended to resynchronise the parser after a syntax error. At least one character will be
+    /// consumed unless the input is exhausted.
+    fn skip_balanced_datum(&mut self) {
+        let mut depth = 0usize;
+        let mut seen_token = false;
+
+        while let Some(c) = self.input.chars().next() {
+            match c {
+                ';' => {
+                    self.consume_until(|c| c == '\n');
+                    continue;
+                }
+                c if is_whitespace(c) => {
+                    if depth == 0 && seen_token {
+                        return;
+                    }
+
+                    self.eat_bytes(c.len_utf8());
+                    continue;
+                }
+                '"' => {
+                    self.eat_bytes(1);
+
+                    let mut escaped = false;
+                    self.consume_until(|c| {
+                        let is_end = c == '"' && !escaped;
+                        escaped = c == '\\' && !escaped;
+                        is_end
+                    });
+
+                    // Consume the closing quote if we found one
+                    let _ = self.consume_char(WithinContext::Datum);
+                }
+                '\\' => {
+                    // Consume the \ and the character following it
+                    self.eat_bytes(1);
+                    let _ = self.consume_char(WithinContext::Datum);
+                }
+                '(' | '[' | '{' => {
+                    self.eat_bytes(1);
+                    depth += 1;
+                }
+                ')' | ']' | '}' => {
+                    if depth == 0 {
+                        if !seen_token {
+                            // This is an unbalanced closing delimiter; skip it
+                            self.eat_bytes(1);
+                        }
+
+                        return;
+                    }
+
+                    self.eat_bytes(1);
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                other => {
+                    self.eat_bytes(other.len_utf8());
+                }
+            }
+
+            seen_token = true;
+        }
+    }
+
     fn capture_span<F, R>(&mut self, block: F) -> (Span, R)
     where
         F: FnOnce(&mut Parser<'_>) -> R,
@@ -173,8 +373,10 @@ impl<'input> Parser<'input> {
             Sign,
             Whole,
             Fractional,
+            Denominator,
         }
 
+        let big_ints = self.options.big_ints;
         let mut state: State = State::Sign;
 
         let (span, digits) = self.consume_while(|c| match state {
@@ -190,24 +392,52 @@ impl<'input> Parser<'input> {
                     state = State::Fractional;
                     true
                 }
+                '/' => {
+                    state = State::Denominator;
+                    true
+                }
                 '0'..='9' => true,
                 _ => false,
             },
-            State::Fractional => matches!(c, '0'..='9'),
+            State::Fractional | State::Denominator => matches!(c, '0'..='9'),
         });
 
         match state {
             State::Sign => Err(Error::new(span, ErrorKind::InvalidFloat)),
 
-            State::Whole => digits
-                .parse::<i64>()
-                .map_err(|_| Error::new(span, ErrorKind::IntegerOverflow))
-                .map(|i| Datum::Int(span, i)),
+            State::Whole => match digits.parse::<i64>() {
+                Ok(i) => Ok(Datum::Int(span, i)),
+                Err(_) if big_ints => digits
+                    .parse::<BigInt>()
+                    .map_err(|_| Error::new(span, ErrorKind::IntegerOverflow))
+                    .map(|i| Datum::BigInt(span, Box::new(i))),
+                Err(_) => Err(Error::new(span, ErrorKind::IntegerOverflow)),
+            },
 
             State::Fractional => digits
                 .parse::<f64>()
                 .map_err(|_| Error::new(span, ErrorKind::InvalidFloat))
                 .map(|f| Datum::Float(span, f)),
+
+            State::Denominator => {
+                let (numer_digits, denom_digits) = digits.split_at(digits.find('/').unwrap());
+                let denom_digits = &denom_digits[1..];
+
+                let numer = numer_digits
+                    .parse::<i64>()
+                    .map_err(|_| Error::new(span, ErrorKind::IntegerOverflow))?;
+
+                let denom = match denom_digits.parse::<i64>() {
+                    Ok(0) => return Err(Error::new(span, ErrorKind::InvalidRational)),
+                    Ok(denom) => denom,
+                    Err(_) if denom_digits.is_empty() => {
+                        return Err(Error::new(span, ErrorKind::InvalidRational))
+                    }
+                    Err(_) => return Err(Error::new(span, ErrorKind::IntegerOverflow)),
+                };
+
+                Ok(rational_datum(span, numer, denom))
+            }
         }
     }
 
@@ -258,14 +488,9 @@ impl<'input> Parser<'input> {
                     return Ok(first_char);
                 }
 
-                if first_char == 'u' {
+                if first_char == 'u' || first_char == 'x' {
                     // This is a hex code point
-                    let hex_string = &char_name[1..];
-                    let code_point = u32::from_str_radix(hex_string, 16)
-                        .map_err(|_| Error::new(span, ErrorKind::UnsupportedChar))?;
-
-                    return std::char::from_u32(code_point)
-                        .ok_or_else(|| Error::new(span, ErrorKind::InvalidCodePoint));
+                    return char_from_hex_code_point(span, &char_name[1..]);
                 }
             }
 
@@ -395,17 +620,13 @@ impl<'input> Parser<'input> {
             '"' => Ok('"'),
             'x' => {
                 let (span, hex_string) = self.consume_until(|c| c == ';');
-                let code_point = u32::from_str_radix(hex_string, 16);
-
-                let code_point =
-                    code_point.map_err(|_| Error::new(span, ErrorKind::UnsupportedChar))?;
+                let c = char_from_hex_code_point(span, hex_string)?;
 
                 if self.consume_char(WithinContext::CodePoint)? != ';' {
                     return Err(Error::new(span, ErrorKind::UnsupportedChar));
                 }
 
-                std::char::from_u32(code_point)
-                    .ok_or_else(|| Error::new(span, ErrorKind::InvalidCodePoint))
+                Ok(c)
             }
             _ => Err(Error::new(
                 Span::new(self.file_id, escape_start, self.consumed_bytes),
@@ -441,7 +662,24 @@ impl<'input> Parser<'input> {
     }
 
     fn parse_identifier(&mut self, within: WithinContext) -> Result<Datum> {
-        let (span, content) = self.consume_while(is_identifier_char);
+        let first_char = self.peek_char(within)?;
+
+        if !first_char.is_ascii() && !first_char.is_xid_start() {
+            // Unicode identifiers must start with a letter
+            let (span, _) = self.capture_span(|s| s.eat_bytes(first_char.len_utf8()));
+            return Err(Error::new(
+                span,
+                ErrorKind::UnexpectedChar(first_char, within),
+            ));
+        }
+
+        let mut prev_char = None;
+        let (span, content) = self.consume_while(|c| {
+            let is_valid =
+                is_identifier_char(c) || (is_joiner(c) && prev_char.map_or(false, is_virama));
+            prev_char = Some(c);
+            is_valid
+        });
 
         if content.is_empty() {
             let (span, next_char) = self.capture_span(|s| s.consume_char(within));
@@ -454,7 +692,12 @@ impl<'input> Parser<'input> {
         match content {
             "true" => Ok(Datum::Bool(span, true)),
             "false" => Ok(Datum::Bool(span, false)),
-            _ => Ok(Datum::Sym(span, content.into())),
+            _ if unicode_normalization::is_nfc(content) => Ok(Datum::Sym(span, content.into())),
+            _ => {
+                // Normalise so identifiers with different encodings refer to the same binding
+                let normalized: String = content.nfc().collect();
+                Ok(Datum::Sym(span, normalized.into()))
+            }
         }
     }
 
@@ -514,6 +757,35 @@ impl<'input> Parser<'input> {
             }
         }
     }
+
+    fn parse_data_with_recovery(&mut self) -> (Vec<Datum>, Vec<Error>) {
+        let mut datum_vec = Vec::new();
+        let mut errors = Vec::new();
+
+        loop {
+            let datum_input = self.input;
+            let datum_consumed_bytes = self.consumed_bytes;
+
+            match self.parse_datum() {
+                Ok(datum) => {
+                    datum_vec.push(datum);
+                }
+                Err(err) if err.kind() == &ErrorKind::Eof(WithinContext::Datum) => {
+                    break;
+                }
+                Err(err) => {
+                    errors.push(err);
+
+                    // Rewind to the start of the datum and skip over it entirely
+                    self.input = datum_input;
+                    self.consumed_bytes = datum_consumed_bytes;
+                    self.skip_balanced_datum();
+                }
+            }
+        }
+
+        (datum_vec, errors)
+    }
 }
 
 /////////
@@ -686,6 +958,64 @@ mod test {
         }
     }
 
+    #[test]
+    fn unicode_symbol_datum() {
+        for &test_symbol in &["λ", "größe", "日本語", "кириллица?", "naïve->ascii"]
+        {
+            let s = whole_str_span(test_symbol);
+            let expected = Datum::Sym(s, test_symbol.into());
+
+            assert_eq!(expected, datum_from_str(None, test_symbol).unwrap());
+        }
+
+        // Decomposed "é" should be normalised to its composed form
+        let decomposed = "cafe\u{301}";
+        let s = whole_str_span(decomposed);
+        let expected = Datum::Sym(s, "caf\u{e9}".into());
+
+        assert_eq!(expected, datum_from_str(None, decomposed).unwrap());
+
+        // Joiners are allowed directly after a virama
+        let joined = "\u{915}\u{94D}\u{200D}\u{937}";
+        let s = whole_str_span(joined);
+        let expected = Datum::Sym(s, joined.into());
+
+        assert_eq!(expected, datum_from_str(None, joined).unwrap());
+    }
+
+    #[test]
+    fn invisible_identifier_chars() {
+        for &c in &['\u{200C}', '\u{200D}', '\u{FE0F}', '\u{E0100}'] {
+            let j = format!("abc{}def", c);
+            let span = Span::new(None, 3, 3 + c.len_utf8() as ByteIndex);
+            let err = Error::new(span, ErrorKind::UnexpectedChar(c, WithinContext::Datum));
+
+            assert_eq!(err, data_from_str(None, &j).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn unicode_identifier_start() {
+        // Combining marks can only continue an identifier
+        for &c in &['\u{301}', '\u{94D}', '\u{200D}'] {
+            let j = format!("{}abc", c);
+            let span = Span::new(None, 0, c.len_utf8() as ByteIndex);
+            let err = Error::new(span, ErrorKind::UnexpectedChar(c, WithinContext::Datum));
+
+            assert_eq!(err, datum_from_str(None, &j).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn confusable_whitespace() {
+        for &(j, c) in &[("(a\u{a0}b)", '\u{a0}'), ("(a\u{200b}b)", '\u{200b}')] {
+            let span = Span::new(None, 2, 2 + c.len_utf8() as ByteIndex);
+            let err = Error::new(span, ErrorKind::ConfusableWhitespace(c));
+
+            assert_eq!(err, datum_from_str(None, j).unwrap_err());
+        }
+    }
+
     #[test]
     fn string_datum() {
         let test_strings = [
@@ -736,6 +1066,10 @@ mod test {
             ("\\(", '('),
             ("\\☃", '\u{2603}'),
             ("\\u03BB", '\u{03bb}'),
+            ("\\x41", 'A'),
+            ("\\x1F600", '\u{1f600}'),
+            ("\\u10FFFF", '\u{10ffff}'),
+            ("\\😀", '\u{1f600}'),
         ];
 
         for (j, expected_char) in &test_chars {
@@ -755,6 +1089,22 @@ mod test {
         let err = Error::new(t2s(t), ErrorKind::InvalidCodePoint);
         assert_eq!(err, datum_from_str(None, j).unwrap_err());
 
+        // Surrogates aren't Unicode scalar values
+        let j = r#"\xD800"#;
+        let t = r#" ^^^^^"#;
+        let err = Error::new(t2s(t), ErrorKind::InvalidCodePoint);
+        assert_eq!(err, datum_from_str(None, j).unwrap_err());
+
+        let j = r#"\xFFFFFFFFF"#;
+        let t = r#" ^^^^^^^^^^"#;
+        let err = Error::new(t2s(t), ErrorKind::InvalidCodePoint);
+        assert_eq!(err, datum_from_str(None, j).unwrap_err());
+
+        let j = r#"\x+41"#;
+        let t = r#" ^^^^"#;
+        let err = Error::new(t2s(t), ErrorKind::UnsupportedChar);
+        assert_eq!(err, datum_from_str(None, j).unwrap_err());
+
         let j = r#"[\newline]"#;
         let t = r#" ^^^^^^^^ "#;
         let expected = Datum::Vector(whole_str_span(j), Box::new([Datum::Char(t2s(t), '\n')]));
@@ -797,6 +1147,82 @@ mod test {
         assert_eq!(err, datum_from_str(None, j).unwrap_err());
     }
 
+    #[test]
+    fn big_int_datum() {
+        let options = ParserOptions { big_ints: true };
+
+        let test_big_ints = [
+            "10223372036854775807",
+            "+10223372036854775807",
+            "-10223372036854775807",
+            "4545894549584910223372036854775807",
+        ];
+
+        for &j in &test_big_ints {
+            let expected_big_int: BigInt = j.parse().unwrap();
+            let expected = Datum::BigInt(whole_str_span(j), Box::new(expected_big_int));
+
+            assert_eq!(
+                expected,
+                datum_from_str_with_options(None, j, options).unwrap()
+            );
+        }
+
+        // Integers that fit in an `i64` are still parsed as `Int`
+        assert_eq!(
+            Datum::Int(t2s("^^^^"), 1000),
+            datum_from_str_with_options(None, "1000", options).unwrap()
+        );
+    }
+
+    #[test]
+    fn rational_datum() {
+        let test_rationals = [
+            ("3/4", 3, 4),
+            ("+3/4", 3, 4),
+            ("-3/4", -3, 4),
+            ("6/8", 3, 4),
+            ("-9223372036854775808/2", -4611686018427387904, 1),
+            ("1/9223372036854775807", 1, 9223372036854775807),
+        ];
+
+        for &(j, expected_numer, expected_denom) in &test_rationals {
+            let s = whole_str_span(j);
+            let expected = if expected_denom == 1 {
+                Datum::Int(s, expected_numer)
+            } else {
+                Datum::Rational(s, expected_numer, expected_denom)
+            };
+
+            assert_eq!(expected, datum_from_str(None, j).unwrap());
+        }
+
+        // Whole rationals are integers
+        assert_eq!(
+            Datum::Int(t2s("^^^"), 2),
+            datum_from_str(None, "4/2").unwrap()
+        );
+        assert_eq!(
+            Datum::Int(t2s("^^^"), 0),
+            datum_from_str(None, "0/5").unwrap()
+        );
+
+        let j = "1/0";
+        let t = "^^^";
+        let err = Error::new(t2s(t), ErrorKind::InvalidRational);
+        assert_eq!(err, datum_from_str(None, j).unwrap_err());
+
+        let j = "1/";
+        let t = "^^";
+        let err = Error::new(t2s(t), ErrorKind::InvalidRational);
+        assert_eq!(err, datum_from_str(None, j).unwrap_err());
+
+        let j = "1/10223372036854775807";
+        let t = "^^^^^^^^^^^^^^^^^^^^^^";
+        let err = Error::new(t2s(t), ErrorKind::IntegerOverflow);
+        assert_eq!(err, datum_from_str(None, j).unwrap_err());
+    }
+
     #[test]
     fn float_datum() {
         let test_floats = [
@@ -1017,4 +1443,53 @@ mod test {
         let err = Error::new(t2s(t), ErrorKind::Eof(WithinContext::List(t2s(u))));
         assert_eq!(err, data_from_str(None, j).unwrap_err());
     }
+
+    #[test]
+    fn data_with_recovery() {
+        let j = "(one 99999999999999999999) two";
+        let t = "                           ^^^";
+        let u = "     ^^^^^^^^^^^^^^^^^^^^     ";
+
+        let expected_data = vec![Datum::Sym(t2s(t), "two".into())];
+        let expected_errors = vec![Error::new(t2s(u), ErrorKind::IntegerOverflow)];
+
+        assert_eq!(
+            (expected_data, expected_errors),
+            data_from_str_with_recovery(None, j)
+        );
+
+        let j = r#") [#loop ")"] (\a #foo \)) three"#;
+        let t = r#"^                                "#;
+        let u = r#"   ^^                            "#;
+        let v = r#"                  ^^             "#;
+        let w = r#"                           ^^^^^"#;
+
+        let expected_data = vec![Datum::Sym(t2s(w), "three".into())];
+        let expected_errors = vec![
+            Error::new(t2s(t), ErrorKind::UnexpectedChar(')', WithinContext::Datum)),
+            Error::new(t2s(u), ErrorKind::UnsupportedDispatch),
+            Error::new(t2s(v), ErrorKind::UnsupportedDispatch),
+        ];
+
+        assert_eq!(
+            (expected_data, expected_errors),
+            data_from_str_with_recovery(None, j)
+        );
+
+        let j = "one (two";
+        let t = "^^^     ";
+        let u = "       >";
+        let v = "    ^   ";
+
+        let expected_data = vec![Datum::Sym(t2s(t), "one".into())];
+        let expected_errors = vec![Error::new(
+            t2s(u),
+            ErrorKind::Eof(WithinContext::List(t2s(v))),
+        )];
+
+        assert_eq!(
+            (expected_data, expected_errors),
+            data_from_str_with_recovery(None, j)
+        );
+    }
 }